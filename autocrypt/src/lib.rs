@@ -45,6 +45,7 @@ use openpgp::types::RevocationStatus;
 
 mod cert;
 pub use cert::cert_builder;
+pub mod mime;
 
 /// Version of Autocrypt to use. `Autocrypt::default()` always returns the
 /// latest version.
@@ -0,0 +1,860 @@
+//! PGP/MIME support.
+//!
+//! Autocrypt requires that encrypted and signed mails use PGP/MIME,
+//! i.e. the `multipart/signed` and `multipart/encrypted` structures
+//! defined in [RFC 3156].  This module creates and parses these
+//! structures.  It deals with the bodies of the multipart entities
+//! and their `Content-Type` header values; the other header fields
+//! and the rest of the mail are left to the MUA's mail library.
+//!
+//!   [RFC 3156]: https://www.rfc-editor.org/rfc/rfc3156
+//!
+//! # Examples
+//!
+//! Signing a MIME entity and verifying the result:
+//!
+//! ```
+//! # fn main() -> sequoia_openpgp::Result<()> {
+//! use sequoia_openpgp as openpgp;
+//! use openpgp::cert::prelude::*;
+//! use openpgp::parse::stream::*;
+//! use openpgp::policy::StandardPolicy;
+//! use sequoia_autocrypt::mime::MultipartSigned;
+//!
+//! let p = &StandardPolicy::new();
+//! let (cert, _) = CertBuilder::new()
+//!     .add_signing_subkey()
+//!     .generate()?;
+//! let signer = cert.keys().with_policy(p, None).for_signing()
+//!     .secret().next().unwrap().key().clone().into_keypair()?;
+//!
+//! let entity = b"Content-Type: text/plain\r\n\r\nHello world.\r\n";
+//! let signed = MultipartSigned::new("=-=-=", entity, signer)?;
+//!
+//! // Hand these to the mail library.
+//! let content_type = signed.content_type();
+//! let mut body = Vec::new();
+//! signed.serialize_body(&mut body)?;
+//!
+//! // On the receiving side.
+//! let signed = MultipartSigned::parse(&content_type, &body)?;
+//! assert_eq!(signed.content(), &entity[..]);
+//!
+//! struct Helper(openpgp::Cert);
+//! impl VerificationHelper for Helper {
+//!     fn get_certs(&mut self, _: &[openpgp::KeyHandle])
+//!                  -> openpgp::Result<Vec<openpgp::Cert>> {
+//!         Ok(vec![self.0.clone()])
+//!     }
+//!
+//!     fn check(&mut self, structure: MessageStructure)
+//!              -> openpgp::Result<()> {
+//!         for layer in structure {
+//!             if let MessageLayer::SignatureGroup { results } = layer {
+//!                 if results.iter().any(|r| r.is_ok()) {
+//!                     return Ok(());
+//!                 }
+//!             }
+//!         }
+//!         Err(openpgp::Error::InvalidOperation(
+//!             "No valid signature".into()).into())
+//!     }
+//! }
+//!
+//! signed.verify(p, Helper(cert))?;
+//! # Ok(()) }
+//! ```
+
+use std::io::Write;
+
+use sequoia_openpgp as openpgp;
+use openpgp::armor;
+use openpgp::crypto;
+use openpgp::Error;
+use openpgp::Packet;
+use openpgp::Result;
+use openpgp::parse::Parse;
+use openpgp::parse::stream::{
+    DetachedVerifierBuilder,
+    VerificationHelper,
+};
+use openpgp::policy::Policy;
+use openpgp::serialize::stream::{
+    Armorer,
+    Encryptor2,
+    LiteralWriter,
+    Message,
+    Recipient,
+    Signer,
+};
+use openpgp::types::HashAlgorithm;
+
+/// The protocol of `multipart/signed` PGP/MIME entities.
+const SIGNATURE_PROTOCOL: &str = "application/pgp-signature";
+
+/// The protocol of `multipart/encrypted` PGP/MIME entities.
+const ENCRYPTED_PROTOCOL: &str = "application/pgp-encrypted";
+
+/// Returns the value of the `micalg` parameter for the given hash
+/// algorithm.
+///
+/// See [Section 5 of RFC 3156].
+///
+///   [Section 5 of RFC 3156]: https://www.rfc-editor.org/rfc/rfc3156#section-5
+pub fn micalg(algo: HashAlgorithm) -> Result<&'static str> {
+    match algo {
+        HashAlgorithm::MD5 => Ok("pgp-md5"),
+        HashAlgorithm::SHA1 => Ok("pgp-sha1"),
+        HashAlgorithm::RipeMD => Ok("pgp-ripemd160"),
+        HashAlgorithm::SHA224 => Ok("pgp-sha224"),
+        HashAlgorithm::SHA256 => Ok("pgp-sha256"),
+        HashAlgorithm::SHA384 => Ok("pgp-sha384"),
+        HashAlgorithm::SHA512 => Ok("pgp-sha512"),
+        a => Err(Error::UnsupportedHashAlgorithm(a).into()),
+    }
+}
+
+/// Converts the line endings in `data` to CRLF.
+///
+/// Signed MIME entities must be in canonical form, i.e. use CRLF
+/// line endings (see [Section 5 of RFC 3156]).  Mail is often stored
+/// with local line endings, so this needs to be applied before
+/// signing or verifying.
+///
+///   [Section 5 of RFC 3156]: https://www.rfc-editor.org/rfc/rfc3156#section-5
+pub fn canonicalize(data: &[u8]) -> Vec<u8> {
+    let mut canonical = Vec::with_capacity(data.len() + data.len() / 32);
+    let mut last = None;
+    for &b in data {
+        if b == b'\n' && last != Some(b'\r') {
+            canonical.push(b'\r');
+        }
+        canonical.push(b);
+        last = Some(b);
+    }
+    canonical
+}
+
+/// A `multipart/signed` PGP/MIME entity.
+///
+/// See [Section 5 of RFC 3156].
+///
+///   [Section 5 of RFC 3156]: https://www.rfc-editor.org/rfc/rfc3156#section-5
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartSigned {
+    boundary: String,
+    hash_algo: HashAlgorithm,
+    content: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl MultipartSigned {
+    /// Signs the given MIME entity.
+    ///
+    /// `entity` is the complete MIME entity to sign, including its
+    /// header fields.  It is converted to canonical form before it
+    /// is signed.
+    ///
+    /// Returns an error if `boundary` is not a valid boundary, or if
+    /// it occurs in `entity`.
+    pub fn new<'a, S>(boundary: &str, entity: &[u8], signer: S)
+                      -> Result<Self>
+    where
+        S: crypto::Signer + Send + Sync + 'a,
+    {
+        check_boundary(boundary)?;
+        let content = canonicalize(entity);
+        check_boundary_absent(boundary, &content)?;
+
+        let mut signature = Vec::new();
+        {
+            let message = Message::new(&mut signature);
+            let message = Armorer::new(message)
+                .kind(armor::Kind::Signature)
+                .build()?;
+            let mut message = Signer::new(message, signer)
+                .detached()
+                .build()?;
+            message.write_all(&content)?;
+            message.finalize()?;
+        }
+
+        let hash_algo = match Packet::from_bytes(&signature)? {
+            Packet::Signature(sig) => sig.hash_algo(),
+            p => return Err(Error::InvalidOperation(
+                format!("Expected a signature, got a {}", p.tag())).into()),
+        };
+        // Make sure we can express the algorithm.
+        micalg(hash_algo)?;
+
+        Ok(MultipartSigned {
+            boundary: boundary.into(),
+            hash_algo,
+            content,
+            signature: canonicalize(&signature),
+        })
+    }
+
+    /// Parses a `multipart/signed` PGP/MIME entity.
+    ///
+    /// `content_type` is the value of the entity's `Content-Type`
+    /// header field, and `body` is the entity's body.
+    pub fn parse(content_type: &str, body: &[u8]) -> Result<Self> {
+        let (boundary, params) = parse_content_type(
+            content_type, "multipart/signed", SIGNATURE_PROTOCOL)?;
+        let hash_algo = match param(&params, "micalg") {
+            Some(micalg) => parse_micalg(micalg)?,
+            None => return Err(Error::MalformedMessage(
+                "multipart/signed without micalg parameter".into()).into()),
+        };
+
+        let parts = split_parts(body, &boundary)?;
+        if parts.len() != 2 {
+            return Err(Error::MalformedMessage(
+                format!("multipart/signed has {} parts, expected 2",
+                        parts.len())).into());
+        }
+
+        let (headers, signature) = split_part(parts[1]);
+        check_part_type(headers, SIGNATURE_PROTOCOL)?;
+
+        Ok(MultipartSigned {
+            boundary,
+            hash_algo,
+            content: parts[0].to_vec(),
+            signature: signature.to_vec(),
+        })
+    }
+
+    /// Returns the boundary.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns the hash algorithm announced in the `micalg`
+    /// parameter.
+    pub fn hash_algo(&self) -> HashAlgorithm {
+        self.hash_algo
+    }
+
+    /// Returns the signed MIME entity, including its header fields.
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    /// Returns the ASCII armored detached signature.
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    /// Returns the value of the `Content-Type` header field.
+    pub fn content_type(&self) -> String {
+        format!("multipart/signed; micalg={}; protocol=\"{}\"; \
+                 boundary=\"{}\"",
+                micalg(self.hash_algo).unwrap_or("pgp-unknown"),
+                SIGNATURE_PROTOCOL, self.boundary)
+    }
+
+    /// Writes the body of the `multipart/signed` entity.
+    pub fn serialize_body(&self, sink: &mut dyn Write) -> Result<()> {
+        write!(sink, "--{}\r\n", self.boundary)?;
+        sink.write_all(&self.content)?;
+        write!(sink, "\r\n--{}\r\n", self.boundary)?;
+        write!(sink, "Content-Type: {}; name=\"signature.asc\"\r\n",
+               SIGNATURE_PROTOCOL)?;
+        write!(sink, "Content-Description: OpenPGP digital signature\r\n")?;
+        write!(sink, "Content-Disposition: attachment; \
+                      filename=\"signature.asc\"\r\n")?;
+        write!(sink, "\r\n")?;
+        sink.write_all(&self.signature)?;
+        write!(sink, "\r\n--{}--\r\n", self.boundary)?;
+        Ok(())
+    }
+
+    /// Verifies the signature over the signed MIME entity.
+    ///
+    /// The entity is converted to canonical form before it is
+    /// verified.  On success, returns the helper.
+    pub fn verify<H>(&self, policy: &dyn Policy, helper: H) -> Result<H>
+    where
+        H: VerificationHelper,
+    {
+        let mut v = DetachedVerifierBuilder::from_bytes(&self.signature)?
+            .with_policy(policy, None, helper)?;
+        v.verify_bytes(canonicalize(&self.content))?;
+        Ok(v.into_helper())
+    }
+}
+
+/// A `multipart/encrypted` PGP/MIME entity.
+///
+/// See [Section 4 of RFC 3156].
+///
+///   [Section 4 of RFC 3156]: https://www.rfc-editor.org/rfc/rfc3156#section-4
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartEncrypted {
+    boundary: String,
+    message: Vec<u8>,
+}
+
+impl MultipartEncrypted {
+    /// Wraps the given encrypted OpenPGP message.
+    ///
+    /// `message` is the binary OpenPGP message.  It is ASCII armored
+    /// here.
+    pub fn new(boundary: &str, message: &[u8]) -> Result<Self> {
+        check_boundary(boundary)?;
+
+        let mut armored = Vec::new();
+        {
+            let mut w = armor::Writer::new(&mut armored,
+                                           armor::Kind::Message)?;
+            w.write_all(message)?;
+            w.finalize()?;
+        }
+
+        Ok(MultipartEncrypted {
+            boundary: boundary.into(),
+            message: canonicalize(&armored),
+        })
+    }
+
+    /// Encrypts the given MIME entity for the given recipients.
+    ///
+    /// `entity` is the complete MIME entity to encrypt, including its
+    /// header fields.  To sign and encrypt, encrypt a
+    /// [`MultipartSigned`] entity (see [Section 6.1 of RFC 3156]).
+    ///
+    ///   [Section 6.1 of RFC 3156]: https://www.rfc-editor.org/rfc/rfc3156#section-6.1
+    pub fn encrypt<'a, R>(boundary: &str, entity: &[u8], recipients: R)
+                          -> Result<Self>
+    where
+        R: IntoIterator,
+        R::Item: Into<Recipient<'a>>,
+    {
+        check_boundary(boundary)?;
+
+        let mut armored = Vec::new();
+        {
+            let message = Message::new(&mut armored);
+            let message = Armorer::new(message)
+                .kind(armor::Kind::Message)
+                .build()?;
+            let message = Encryptor2::for_recipients(message, recipients)
+                .build()?;
+            let mut message = LiteralWriter::new(message).build()?;
+            message.write_all(entity)?;
+            message.finalize()?;
+        }
+
+        Ok(MultipartEncrypted {
+            boundary: boundary.into(),
+            message: canonicalize(&armored),
+        })
+    }
+
+    /// Parses a `multipart/encrypted` PGP/MIME entity.
+    ///
+    /// `content_type` is the value of the entity's `Content-Type`
+    /// header field, and `body` is the entity's body.
+    pub fn parse(content_type: &str, body: &[u8]) -> Result<Self> {
+        let (boundary, _) = parse_content_type(
+            content_type, "multipart/encrypted", ENCRYPTED_PROTOCOL)?;
+
+        let parts = split_parts(body, &boundary)?;
+        if parts.len() != 2 {
+            return Err(Error::MalformedMessage(
+                format!("multipart/encrypted has {} parts, expected 2",
+                        parts.len())).into());
+        }
+
+        let (headers, control) = split_part(parts[0]);
+        check_part_type(headers, ENCRYPTED_PROTOCOL)?;
+        let version = String::from_utf8_lossy(control);
+        if ! version.lines().any(|l| l.trim() == "Version: 1") {
+            return Err(Error::MalformedMessage(
+                "PGP/MIME version identification missing".into()).into());
+        }
+
+        let (headers, message) = split_part(parts[1]);
+        check_part_type(headers, "application/octet-stream")?;
+
+        Ok(MultipartEncrypted {
+            boundary,
+            message: message.to_vec(),
+        })
+    }
+
+    /// Returns the boundary.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns the ASCII armored OpenPGP message.
+    ///
+    /// This can be passed to [`DecryptorBuilder::from_bytes`].
+    ///
+    ///   [`DecryptorBuilder::from_bytes`]: openpgp::parse::stream::DecryptorBuilder
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Returns the value of the `Content-Type` header field.
+    pub fn content_type(&self) -> String {
+        format!("multipart/encrypted; protocol=\"{}\"; boundary=\"{}\"",
+                ENCRYPTED_PROTOCOL, self.boundary)
+    }
+
+    /// Writes the body of the `multipart/encrypted` entity.
+    pub fn serialize_body(&self, sink: &mut dyn Write) -> Result<()> {
+        write!(sink, "--{}\r\n", self.boundary)?;
+        write!(sink, "Content-Type: {}\r\n", ENCRYPTED_PROTOCOL)?;
+        write!(sink, "Content-Description: PGP/MIME version \
+                      identification\r\n")?;
+        write!(sink, "\r\n")?;
+        write!(sink, "Version: 1\r\n")?;
+        write!(sink, "\r\n--{}\r\n", self.boundary)?;
+        write!(sink, "Content-Type: application/octet-stream; \
+                      name=\"encrypted.asc\"\r\n")?;
+        write!(sink, "Content-Description: OpenPGP encrypted message\r\n")?;
+        write!(sink, "Content-Disposition: inline; \
+                      filename=\"encrypted.asc\"\r\n")?;
+        write!(sink, "\r\n")?;
+        sink.write_all(&self.message)?;
+        write!(sink, "\r\n--{}--\r\n", self.boundary)?;
+        Ok(())
+    }
+}
+
+/// Checks that the boundary conforms to [Section 5.1.1 of RFC 2046].
+///
+///   [Section 5.1.1 of RFC 2046]: https://www.rfc-editor.org/rfc/rfc2046#section-5.1.1
+fn check_boundary(boundary: &str) -> Result<()> {
+    let valid = ! boundary.is_empty()
+        && boundary.len() <= 70
+        && ! boundary.ends_with(' ')
+        && boundary.bytes().all(|b| b.is_ascii_alphanumeric()
+                                || b"'()+_,-./:=? ".contains(&b));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(
+            format!("Invalid MIME boundary: {:?}", boundary)).into())
+    }
+}
+
+/// Checks that the boundary does not occur in the data.
+fn check_boundary_absent(boundary: &str, data: &[u8]) -> Result<()> {
+    let delimiter = format!("--{}", boundary);
+    if lines(data).any(|l| l.starts_with(delimiter.as_bytes())) {
+        Err(Error::InvalidArgument(
+            format!("MIME boundary {:?} occurs in the content", boundary))
+            .into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the lines of the data, including the line endings.
+fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    data.split_inclusive(|&b| b == b'\n')
+}
+
+/// Strips the line ending.
+fn chomp(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Parses a `Content-Type` header field's value.
+///
+/// Checks the type and the protocol parameter, and returns the
+/// boundary and all parameters.
+fn parse_content_type(content_type: &str, expected: &str, protocol: &str)
+                      -> Result<(String, Vec<(String, String)>)>
+{
+    let mut fields = split_params(content_type).into_iter();
+    let typ = fields.next().unwrap_or_default();
+    if ! typ.eq_ignore_ascii_case(expected) {
+        return Err(Error::MalformedMessage(
+            format!("Expected {}, got {}", expected, typ)).into());
+    }
+
+    let params = fields
+        .filter_map(|p| {
+            let (name, value) = p.split_once('=')?;
+            Some((name.trim().to_ascii_lowercase(), unquote(value.trim())))
+        })
+        .collect::<Vec<_>>();
+
+    match param(&params, "protocol") {
+        Some(p) if p.eq_ignore_ascii_case(protocol) => (),
+        Some(p) => return Err(Error::MalformedMessage(
+            format!("Expected protocol {}, got {}", protocol, p)).into()),
+        None => return Err(Error::MalformedMessage(
+            format!("{} without protocol parameter", expected)).into()),
+    }
+
+    let boundary = param(&params, "boundary")
+        .ok_or_else(|| Error::MalformedMessage(
+            format!("{} without boundary parameter", expected)))?
+        .to_string();
+    check_boundary(&boundary)?;
+
+    Ok((boundary, params))
+}
+
+/// Splits a header field's value at semicolons outside of quoted
+/// strings.
+fn split_params(value: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = ! quoted;
+        } else if c == ';' && ! quoted {
+            fields.push(String::new());
+            continue;
+        }
+        fields.last_mut().expect("not empty").push(c);
+    }
+    fields.into_iter()
+        .map(|f| f.trim().to_string())
+        .filter(|f| ! f.is_empty())
+        .collect()
+}
+
+/// Removes the quotes and escapes from a quoted string.
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(v) => {
+            let mut s = String::with_capacity(v.len());
+            let mut escaped = false;
+            for c in v.chars() {
+                if ! escaped && c == '\\' {
+                    escaped = true;
+                } else {
+                    s.push(c);
+                    escaped = false;
+                }
+            }
+            s
+        },
+        None => value.into(),
+    }
+}
+
+/// Looks up a parameter by its lowercase name.
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+}
+
+/// Parses the value of the `micalg` parameter.
+fn parse_micalg(value: &str) -> Result<HashAlgorithm> {
+    use HashAlgorithm::*;
+    [MD5, SHA1, RipeMD, SHA224, SHA256, SHA384, SHA512].iter()
+        .find(|&&a| micalg(a).map(|m| m.eq_ignore_ascii_case(value))
+              .unwrap_or(false))
+        .cloned()
+        .ok_or_else(|| Error::MalformedMessage(
+            format!("Unknown micalg {:?}", value)).into())
+}
+
+/// Splits a multipart body into its parts.
+///
+/// The preamble and the epilogue are discarded.  The line ending
+/// preceding a delimiter belongs to the delimiter, and is not part of
+/// the preceding part.  See [Section 5.1.1 of RFC 2046].
+///
+///   [Section 5.1.1 of RFC 2046]: https://www.rfc-editor.org/rfc/rfc2046#section-5.1.1
+fn split_parts<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<&'a [u8]>> {
+    let delimiter = format!("--{}", boundary);
+    let close_delimiter = format!("--{}--", boundary);
+
+    let mut parts = Vec::new();
+    // The start of the current part, and the end of the last line
+    // that may belong to it.
+    let mut start = None;
+    let mut end = 0;
+    let mut offset = 0;
+    for line in lines(body) {
+        let line_start = offset;
+        offset += line.len();
+
+        let l = chomp(line);
+        if ! l.starts_with(delimiter.as_bytes()) {
+            end = line_start + l.len();
+            continue;
+        }
+
+        let closing = l.starts_with(close_delimiter.as_bytes());
+        let rest = if closing {
+            &l[close_delimiter.len()..]
+        } else {
+            &l[delimiter.len()..]
+        };
+        if ! rest.iter().all(|b| *b == b' ' || *b == b'\t') {
+            // Not a delimiter, just a line starting with the same
+            // characters.
+            end = line_start + l.len();
+            continue;
+        }
+
+        if let Some(s) = start {
+            parts.push(&body[s..end.max(s)]);
+        }
+
+        if closing {
+            return Ok(parts);
+        }
+        start = Some(offset);
+        end = offset;
+    }
+
+    Err(Error::MalformedMessage(
+        "Multipart body lacks the close delimiter".into()).into())
+}
+
+/// Splits a body part into its header fields and its body.
+fn split_part(part: &[u8]) -> (&[u8], &[u8]) {
+    let mut offset = 0;
+    for line in lines(part) {
+        offset += line.len();
+        if chomp(line).is_empty() {
+            return (&part[..offset], &part[offset..]);
+        }
+    }
+    (part, &[])
+}
+
+/// Checks the `Content-Type` header field of a body part.
+fn check_part_type(headers: &[u8], expected: &str) -> Result<()> {
+    // Unfold the header fields.
+    let mut fields: Vec<String> = Vec::new();
+    for line in lines(headers) {
+        let line = String::from_utf8_lossy(chomp(line));
+        if line.starts_with(|c| c == ' ' || c == '\t') {
+            if let Some(f) = fields.last_mut() {
+                f.push_str(&line);
+            }
+        } else {
+            fields.push(line.into_owned());
+        }
+    }
+
+    let typ = fields.iter()
+        .filter_map(|f| f.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-type"))
+        .and_then(|(_, value)| split_params(value).into_iter().next());
+    match typ {
+        Some(t) if t.eq_ignore_ascii_case(expected) => Ok(()),
+        Some(t) => Err(Error::MalformedMessage(
+            format!("Expected a {} part, got {}", expected, t)).into()),
+        None => Err(Error::MalformedMessage(
+            format!("Expected a {} part, got none", expected)).into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use openpgp::cert::prelude::*;
+    use openpgp::parse::stream::*;
+    use openpgp::policy::StandardPolicy as P;
+
+    #[test]
+    fn canonicalize_line_endings() {
+        assert_eq!(canonicalize(b""), b"");
+        assert_eq!(canonicalize(b"a\nb\r\nc\n"), b"a\r\nb\r\nc\r\n");
+        assert_eq!(canonicalize(b"\n\n"), b"\r\n\r\n");
+        assert_eq!(canonicalize(b"a\rb"), b"a\rb");
+    }
+
+    #[test]
+    fn boundaries() {
+        assert!(check_boundary("=-=-=").is_ok());
+        assert!(check_boundary("simple boundary").is_ok());
+        assert!(check_boundary("").is_err());
+        assert!(check_boundary("trailing space ").is_err());
+        assert!(check_boundary("semi;colon").is_err());
+        assert!(check_boundary(&"x".repeat(71)).is_err());
+    }
+
+    #[test]
+    fn parts() -> Result<()> {
+        let body = b"preamble\r\n\
+                     --b\r\n\
+                     one\r\n\
+                     --b \r\n\
+                     two\r\n\r\n\
+                     --bx\r\n\
+                     --b--\r\n\
+                     epilogue\r\n";
+        assert_eq!(split_parts(body, "b")?,
+                   vec![&b"one"[..], &b"two\r\n\r\n--bx"[..]]);
+
+        // LF line endings.
+        let body = b"--b\none\n--b\ntwo\n--b--\n";
+        assert_eq!(split_parts(body, "b")?, vec![&b"one"[..], &b"two"[..]]);
+
+        // No close delimiter.
+        assert!(split_parts(b"--b\r\none\r\n", "b").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn content_type() -> Result<()> {
+        let (boundary, params) = parse_content_type(
+            "Multipart/Signed; micalg=pgp-sha256;\r\n \
+             protocol=\"application/pgp-signature\"; \
+             x-note=\"a \\\"b\\\" ;c\"; boundary=\"=-= a,b =-=\"",
+            "multipart/signed", SIGNATURE_PROTOCOL)?;
+        assert_eq!(boundary, "=-= a,b =-=");
+        assert_eq!(param(&params, "micalg"), Some("pgp-sha256"));
+        assert_eq!(param(&params, "x-note"), Some("a \"b\" ;c"));
+
+        assert!(parse_content_type(
+            "multipart/signed; protocol=\"application/pkcs7-signature\"; \
+             boundary=b",
+            "multipart/signed", SIGNATURE_PROTOCOL).is_err());
+        assert!(parse_content_type(
+            "multipart/signed; protocol=\"application/pgp-signature\"",
+            "multipart/signed", SIGNATURE_PROTOCOL).is_err());
+        assert!(parse_content_type(
+            "multipart/mixed; protocol=\"application/pgp-signature\"; \
+             boundary=b",
+            "multipart/signed", SIGNATURE_PROTOCOL).is_err());
+        Ok(())
+    }
+
+    struct Helper {
+        cert: openpgp::Cert,
+        good: usize,
+    }
+
+    impl VerificationHelper for Helper {
+        fn get_certs(&mut self, _ids: &[openpgp::KeyHandle])
+                     -> Result<Vec<openpgp::Cert>> {
+            Ok(vec![self.cert.clone()])
+        }
+
+        fn check(&mut self, structure: MessageStructure) -> Result<()> {
+            for layer in structure {
+                if let MessageLayer::SignatureGroup { results } = layer {
+                    self.good += results.iter().filter(|r| r.is_ok()).count();
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl DecryptionHelper for Helper {
+        fn decrypt<D>(&mut self, pkesks: &[openpgp::packet::PKESK],
+                      _: &[openpgp::packet::SKESK],
+                      sym_algo: Option<openpgp::types::SymmetricAlgorithm>,
+                      mut decrypt: D)
+                      -> Result<Option<openpgp::Fingerprint>>
+        where D: FnMut(openpgp::types::SymmetricAlgorithm,
+                       &openpgp::crypto::SessionKey) -> bool
+        {
+            let p = &P::new();
+            let mut keypair = self.cert.keys().with_policy(p, None)
+                .for_transport_encryption().secret().next().unwrap()
+                .key().clone().into_keypair()?;
+            pkesks[0].decrypt(&mut keypair, sym_algo)
+                .map(|(algo, sk)| decrypt(algo, &sk));
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn signed() -> Result<()> {
+        let p = &P::new();
+        let (cert, _) = CertBuilder::new()
+            .add_signing_subkey()
+            .generate()?;
+        let signer = cert.keys().with_policy(p, None).for_signing()
+            .secret().next().unwrap().key().clone().into_keypair()?;
+
+        let entity = b"Content-Type: text/plain\n\nHello world.\n";
+        let signed = MultipartSigned::new("=-=-=", entity, signer)?;
+        assert_eq!(signed.content(),
+                   b"Content-Type: text/plain\r\n\r\nHello world.\r\n");
+        assert!(signed.content_type().starts_with(
+            &format!("multipart/signed; micalg={};",
+                     micalg(signed.hash_algo())?)));
+
+        let mut body = Vec::new();
+        signed.serialize_body(&mut body)?;
+        let parsed = MultipartSigned::parse(&signed.content_type(), &body)?;
+        assert_eq!(parsed, signed);
+
+        let h = parsed.verify(p, Helper { cert: cert.clone(), good: 0 })?;
+        assert_eq!(h.good, 1);
+
+        // Mail stored with local line endings still verifies.
+        let body = String::from_utf8(body)?.replace("\r\n", "\n");
+        let parsed = MultipartSigned::parse(&signed.content_type(),
+                                            body.as_bytes())?;
+        let h = parsed.verify(p, Helper { cert: cert.clone(), good: 0 })?;
+        assert_eq!(h.good, 1);
+
+        // But a modified entity does not.
+        let body = body.replace("Hello", "Jello");
+        let parsed = MultipartSigned::parse(&signed.content_type(),
+                                            body.as_bytes())?;
+        let h = parsed.verify(p, Helper { cert, good: 0 })?;
+        assert_eq!(h.good, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn boundary_in_content() -> Result<()> {
+        let p = &P::new();
+        let (cert, _) = CertBuilder::new()
+            .add_signing_subkey()
+            .generate()?;
+        let signer = cert.keys().with_policy(p, None).for_signing()
+            .secret().next().unwrap().key().clone().into_keypair()?;
+
+        assert!(MultipartSigned::new("b", b"a\r\n--b\r\n", signer).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn encrypted() -> Result<()> {
+        let p = &P::new();
+        let (cert, _) = CertBuilder::new()
+            .add_transport_encryption_subkey()
+            .generate()?;
+        let recipients = cert.keys().with_policy(p, None)
+            .for_transport_encryption();
+
+        let entity = b"Content-Type: text/plain\r\n\r\nHello world.\r\n";
+        let encrypted =
+            MultipartEncrypted::encrypt("=-=-=", entity, recipients)?;
+        assert_eq!(encrypted.content_type(),
+                   "multipart/encrypted; \
+                    protocol=\"application/pgp-encrypted\"; \
+                    boundary=\"=-=-=\"");
+
+        let mut body = Vec::new();
+        encrypted.serialize_body(&mut body)?;
+        let parsed =
+            MultipartEncrypted::parse(&encrypted.content_type(), &body)?;
+        assert_eq!(parsed, encrypted);
+
+        let mut d = DecryptorBuilder::from_bytes(parsed.message())?
+            .with_policy(p, None, Helper { cert, good: 0 })?;
+        let mut content = Vec::new();
+        std::io::copy(&mut d, &mut content)?;
+        assert_eq!(content, entity);
+        Ok(())
+    }
+}
@@ -9,12 +9,21 @@ use super::*;
 ///
 /// Note: this will likely cause the underlying stream to buffer as
 /// much data as you read.  Thus, it should only be used for peeking
-/// at the underlying `BufferedReader`.
+/// at the underlying `BufferedReader`.  To limit the amount of data
+/// that is retained, use [`Dup::bounded`].
 #[derive(Debug)]
 pub struct Dup<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> {
-    // The number of bytes that have been consumed.
+    // The number of bytes that have been consumed, relative to the
+    // underlying reader's position.
     cursor: usize,
 
+    // The maximum number of consumed bytes that are retained.
+    window: Option<usize>,
+
+    // The number of bytes that fell out of the window, and have
+    // been consumed from the underlying reader.
+    discarded: usize,
+
     // The user settable cookie.
     cookie: C,
 
@@ -29,6 +38,8 @@ impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> fmt::Display for Dup<T,
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Dup")
             .field("cursor", &self.cursor)
+            .field("window", &self.window)
+            .field("discarded", &self.discarded)
             .finish()
     }
 }
@@ -40,6 +51,36 @@ impl<T: BufferedReader<()>> Dup<T, ()> {
     pub fn new(reader: T) -> Self {
         Self::with_cookie(reader, ())
     }
+
+    /// Instantiates a new bounded `Dup` buffered reader.
+    ///
+    /// `reader` is the `BufferedReader` to duplicate.  At most
+    /// `window` bytes of consumed data are retained.  Once more
+    /// data is consumed, the oldest data is consumed from `reader`,
+    /// and can no longer be returned to using [`Dup::rewind`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use buffered_reader::{BufferedReader, Dup, Memory};
+    ///
+    /// let mut reader = Dup::bounded(Memory::new(b"0123456789"), 4);
+    /// reader.data_consume_hard(6)?;
+    /// assert_eq!(reader.total_out(), 6);
+    ///
+    /// // Only the last four bytes are retained.
+    /// reader.rewind();
+    /// assert_eq!(reader.data_eof()?, b"23456789");
+    ///
+    /// // The rest has been consumed from the underlying reader.
+    /// let inner = Box::new(reader).into_inner().unwrap();
+    /// assert_eq!(inner.buffer(), b"23456789");
+    /// # Ok(()) }
+    /// ```
+    pub fn bounded(reader: T, window: usize) -> Self {
+        Self::bounded_with_cookie(reader, window, ())
+    }
 }
 
 impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> Dup<T, C> {
@@ -51,19 +92,65 @@ impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> Dup<T, C> {
         Dup {
             reader,
             cursor: 0,
+            window: None,
+            discarded: 0,
             cookie,
         }
     }
 
+    /// Like [`Self::bounded`], but uses a cookie.
+    ///
+    /// The cookie can be retrieved using the [`BufferedReader::cookie_ref`] and
+    /// [`BufferedReader::cookie_mut`] methods, and set using the [`BufferedReader::cookie_set`] method.
+    pub fn bounded_with_cookie(reader: T, window: usize, cookie: C) -> Self {
+        Dup {
+            window: Some(window),
+            ..Self::with_cookie(reader, cookie)
+        }
+    }
+
+    /// Returns the maximum number of consumed bytes that are
+    /// retained.
+    ///
+    /// Returns `None` if the reader is not bounded.
+    pub fn window(&self) -> Option<usize> {
+        self.window
+    }
+
     /// Returns the number of bytes that this reader has consumed.
     pub fn total_out(&self) -> usize {
-        self.cursor
+        self.discarded + self.cursor
     }
 
     /// Resets the cursor to the beginning of the stream.
+    ///
+    /// If the reader is bounded, the cursor is reset to the oldest
+    /// retained byte.
     pub fn rewind(&mut self) {
         self.cursor = 0;
     }
+
+    /// Advances the cursor by `amount` bytes, and returns the data
+    /// starting at the old cursor.
+    ///
+    /// If the reader is bounded, data that falls out of the window
+    /// is consumed from the underlying reader.
+    fn advance(&mut self, amount: usize) -> &[u8] {
+        let cursor = self.cursor;
+        self.cursor += amount;
+
+        let excess = match self.window {
+            Some(window) if self.cursor > window => self.cursor - window,
+            _ => 0,
+        };
+        if excess > 0 {
+            self.cursor -= excess;
+            self.discarded += excess;
+            &self.reader.consume(excess)[cursor..]
+        } else {
+            &self.reader.buffer()[cursor..]
+        }
+    }
 }
 
 impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> io::Read for Dup<T, C> {
@@ -97,25 +184,20 @@ impl<T: BufferedReader<C>, C: fmt::Debug + Send + Sync> BufferedReader<C> for Du
     fn consume(&mut self, amount: usize) -> &[u8] {
         let data = self.reader.buffer();
         assert!(data.len() >= self.cursor + amount);
-        let data = &data[self.cursor..];
-        self.cursor += amount;
-        data
+        self.advance(amount)
     }
 
     fn data_consume(&mut self, amount: usize) -> Result<&[u8], io::Error> {
         let data = self.reader.data(self.cursor + amount)?;
         assert!(data.len() >= self.cursor);
-        let data = &data[self.cursor..];
-        self.cursor += cmp::min(data.len(), amount);
-        Ok(data)
+        let amount = cmp::min(data.len() - self.cursor, amount);
+        Ok(self.advance(amount))
     }
 
     fn data_consume_hard(&mut self, amount: usize) -> Result<&[u8], io::Error> {
         let data = self.reader.data_hard(self.cursor + amount)?;
         assert!(data.len() >= self.cursor + amount);
-        let data = &data[self.cursor..];
-        self.cursor += amount;
-        Ok(data)
+        Ok(self.advance(amount))
     }
 
     fn get_mut(&mut self) -> Option<&mut dyn BufferedReader<C>> {
@@ -172,6 +254,37 @@ mod test {
         buffered_reader_test_data_check(&mut reader);
     }
 
+    #[test]
+    fn bounded() {
+        let data = crate::BUFFERED_READER_TEST_DATA;
+        let reader = Memory::new(data);
+        let mut reader = Dup::bounded(reader, 100);
+        assert_eq!(reader.window(), Some(100));
+
+        buffered_reader_test_data_check(&mut reader);
+        assert_eq!(reader.total_out(), data.len());
+
+        // Only the window can be rewound.
+        reader.rewind();
+        assert_eq!(reader.total_out(), data.len() - 100);
+        assert_eq!(reader.data_eof().unwrap(), &data[data.len() - 100..]);
+
+        // The rest was consumed from the underlying reader.
+        let mut reader = Box::new(reader).into_inner().unwrap();
+        assert_eq!(reader.data_eof().unwrap(), &data[data.len() - 100..]);
+
+        // Consuming in large steps.
+        let mut reader = Dup::bounded(Memory::new(data), 10);
+        let chunk = reader.data_consume_hard(1000).unwrap();
+        assert_eq!(&chunk[..1000], &data[..1000]);
+        let chunk = reader.data_consume(1000).unwrap();
+        assert_eq!(&chunk[..1000], &data[1000..2000]);
+        assert_eq!(reader.total_out(), 2000);
+        reader.rewind();
+        assert_eq!(reader.total_out(), 1990);
+        assert_eq!(&reader.data_hard(10).unwrap()[..10], &data[1990..2000]);
+    }
+
     // Test that buffer() returns the same data as data().
     #[test]
     fn buffer_test() {
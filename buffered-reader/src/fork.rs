@@ -0,0 +1,321 @@
+use std::io;
+use std::fmt;
+use std::cmp;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::*;
+
+/// State shared by the two halves of a fork.
+#[derive(Debug)]
+struct Shared<T> {
+    reader: T,
+
+    // The number of bytes that have been consumed from `reader`.
+    base: usize,
+
+    // The number of bytes that each half has consumed, or `None` if
+    // the half has been dropped.
+    positions: [Option<usize>; 2],
+}
+
+impl<T> Shared<T> {
+    /// Consumes the data that neither half needs anymore from the
+    /// underlying reader.
+    fn release<C>(&mut self)
+        where T: BufferedReader<C>,
+              C: fmt::Debug + Sync + Send,
+    {
+        if let Some(min) = self.positions.iter().flatten().min().cloned() {
+            if min > self.base {
+                self.reader.consume(min - self.base);
+                self.base = min;
+            }
+        }
+    }
+}
+
+/// Splits a `BufferedReader` into two independent readers.
+///
+/// [`Fork::new`] returns two readers that both return all of the
+/// underlying reader's data.  They can be read at different speeds,
+/// and from different threads.  Data is only consumed from the
+/// underlying reader once both halves have consumed it.
+///
+/// Note: the underlying reader has to buffer the data between the
+/// two halves.  If one half is read much further than the other,
+/// this will cause the underlying reader to buffer that much data.
+/// Dropping a half releases the data that only it was holding back.
+///
+/// Because the underlying reader is shared, [`BufferedReader::get_ref`],
+/// [`BufferedReader::get_mut`], and [`BufferedReader::into_inner`]
+/// return `None`.
+///
+/// # Examples
+///
+/// Sniffing the content type before handing the data to a parser:
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::Read;
+/// use buffered_reader::{BufferedReader, Fork, Memory};
+///
+/// let data = b"-----BEGIN PGP MESSAGE-----\n...";
+/// let (mut sniffer, mut reader) = Fork::new(Memory::new(data));
+///
+/// let armored = sniffer.data(5)?.starts_with(b"-----");
+/// assert!(armored);
+/// drop(sniffer);
+///
+/// // The other half still returns all of the data.
+/// let mut content = Vec::new();
+/// reader.read_to_end(&mut content)?;
+/// assert_eq!(&content[..], &data[..]);
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Fork<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> {
+    shared: Arc<Mutex<Shared<T>>>,
+
+    // Which half this is, 0 or 1.
+    half: usize,
+
+    // A copy of the underlying reader's data.  The data before
+    // `cursor` has been consumed.
+    buffer: Vec<u8>,
+    cursor: usize,
+
+    // The number of bytes that have been consumed.
+    position: usize,
+
+    // The user settable cookie.
+    cookie: C,
+}
+
+assert_send_and_sync!(Fork<T, C>
+                      where T: BufferedReader<C>,
+                            C: fmt::Debug);
+
+impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> fmt::Display for Fork<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fork ({} of 2, {} bytes read)", self.half + 1, self.position)
+    }
+}
+
+impl<T: BufferedReader<()>> Fork<T, ()> {
+    /// Forks `reader` into two independent readers.
+    ///
+    /// `reader` is the `BufferedReader` to split.
+    pub fn new(reader: T) -> (Self, Self) {
+        Self::with_cookies(reader, (), ())
+    }
+}
+
+impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> Fork<T, C> {
+    /// Like [`Self::new`], but sets the halves' cookies.
+    ///
+    /// The cookies can be retrieved using the [`BufferedReader::cookie_ref`] and
+    /// [`BufferedReader::cookie_mut`] methods, and set using the [`BufferedReader::cookie_set`] method.
+    pub fn with_cookies(reader: T, first: C, second: C) -> (Self, Self) {
+        let shared = Arc::new(Mutex::new(Shared {
+            reader,
+            base: 0,
+            positions: [Some(0), Some(0)],
+        }));
+
+        let half = |half, cookie| Fork {
+            shared: shared.clone(),
+            half,
+            buffer: Vec::new(),
+            cursor: 0,
+            position: 0,
+            cookie,
+        };
+        (half(0, first), half(1, second))
+    }
+
+    /// Returns the number of bytes that this reader has consumed.
+    pub fn total_out(&self) -> usize {
+        self.position
+    }
+
+    /// Locks the shared state.
+    fn lock(&self) -> MutexGuard<'_, Shared<T>> {
+        // If the other half panicked while holding the lock, the
+        // state is still consistent: positions are only updated
+        // after the underlying reader returned.
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Makes sure that at least `amount` bytes are buffered, if
+    /// possible.
+    fn fill(&mut self, amount: usize, hard: bool) -> Result<(), io::Error> {
+        let have = self.buffer.len() - self.cursor;
+        if have >= amount {
+            return Ok(());
+        }
+
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        let offset = self.position - shared.base;
+        let data = if hard {
+            shared.reader.data_hard(offset + amount)?
+        } else {
+            shared.reader.data(offset + amount)?
+        };
+        // The data between the two halves is retained by the
+        // underlying reader.
+        assert!(data.len() >= offset + have);
+
+        self.buffer.drain(..self.cursor);
+        self.cursor = 0;
+        self.buffer.extend_from_slice(&data[offset + have..]);
+        Ok(())
+    }
+}
+
+impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> Drop for Fork<T, C> {
+    fn drop(&mut self) {
+        let mut shared = self.lock();
+        shared.positions[self.half] = None;
+        shared.release();
+    }
+}
+
+impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> io::Read for Fork<T, C> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        buffered_reader_generic_read_impl(self, buf)
+    }
+}
+
+impl<T: BufferedReader<C>, C: fmt::Debug + Send + Sync> BufferedReader<C> for Fork<T, C> {
+    fn buffer(&self) -> &[u8] {
+        &self.buffer[self.cursor..]
+    }
+
+    fn data(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.fill(amount, false)?;
+        Ok(&self.buffer[self.cursor..])
+    }
+
+    fn data_hard(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.fill(amount, true)?;
+        Ok(&self.buffer[self.cursor..])
+    }
+
+    fn consume(&mut self, amount: usize) -> &[u8] {
+        // The caller can't consume more than is buffered!
+        assert!(amount <= self.buffer.len() - self.cursor,
+                "Attempt to consume {} bytes, but buffer only has {} bytes!",
+                amount, self.buffer.len() - self.cursor);
+        self.cursor += amount;
+        self.position += amount;
+
+        let position = self.position;
+        let mut shared = self.lock();
+        shared.positions[self.half] = Some(position);
+        shared.release();
+        drop(shared);
+
+        &self.buffer[self.cursor - amount..]
+    }
+
+    fn data_consume(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.fill(amount, false)?;
+        let amount = cmp::min(amount, self.buffer.len() - self.cursor);
+        Ok(self.consume(amount))
+    }
+
+    fn data_consume_hard(&mut self, amount: usize) -> Result<&[u8], io::Error> {
+        self.fill(amount, true)?;
+        Ok(self.consume(amount))
+    }
+
+    fn get_mut(&mut self) -> Option<&mut dyn BufferedReader<C>> {
+        None
+    }
+
+    fn get_ref(&self) -> Option<&dyn BufferedReader<C>> {
+        None
+    }
+
+    fn into_inner<'b>(self: Box<Self>) -> Option<Box<dyn BufferedReader<C> + 'b>>
+            where Self: 'b {
+        None
+    }
+
+    fn cookie_set(&mut self, cookie: C) -> C {
+        use std::mem;
+
+        mem::replace(&mut self.cookie, cookie)
+    }
+
+    fn cookie_ref(&self) -> &C {
+        &self.cookie
+    }
+
+    fn cookie_mut(&mut self) -> &mut C {
+        &mut self.cookie
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buffered_reader_memory_test() {
+        let data = crate::BUFFERED_READER_TEST_DATA;
+        let (mut a, mut b) = Fork::new(Memory::new(data));
+
+        buffered_reader_test_data_check(&mut a);
+        assert_eq!(a.total_out(), data.len());
+        // Nothing was consumed from the underlying reader, because
+        // `b` still needs the data.
+        assert_eq!(a.lock().base, 0);
+
+        buffered_reader_test_data_check(&mut b);
+        assert_eq!(b.total_out(), data.len());
+        assert_eq!(b.lock().base, data.len());
+    }
+
+    #[test]
+    fn interleaved() {
+        let data = crate::BUFFERED_READER_TEST_DATA;
+        let (mut a, mut b) = Fork::new(Generic::new(data, Some(17)));
+
+        let mut i = 0;
+        while i < data.len() {
+            let n = cmp::min(data.len() - i, 1 + i % 101);
+            assert_eq!(&a.data_consume_hard(n).unwrap()[..n], &data[i..i + n]);
+            if i % 3 == 0 {
+                let n = b.total_out();
+                let chunk = b.data_consume(100).unwrap();
+                let m = cmp::min(chunk.len(), 100);
+                assert_eq!(&chunk[..m], &data[n..n + m]);
+            }
+            i += n;
+        }
+        assert!(a.data_eof().unwrap().is_empty());
+
+        // Dropping `a` doesn't affect `b`.
+        drop(a);
+        let n = b.total_out();
+        assert_eq!(b.data_eof().unwrap(), &data[n..]);
+        assert!(b.data_hard(data.len() - n + 1).is_err());
+
+        // Once `b` is done, the data has been consumed.
+        b.data_consume_hard(data.len() - n).unwrap();
+        assert_eq!(b.lock().base, data.len());
+    }
+
+    #[test]
+    fn threads() {
+        let data = crate::BUFFERED_READER_TEST_DATA;
+        let (a, b) = Fork::new(Memory::new(data));
+
+        std::thread::scope(|s| {
+            for mut half in [a, b] {
+                s.spawn(move || buffered_reader_test_data_check(&mut half));
+            }
+        });
+    }
+}
@@ -241,6 +241,7 @@ mod memory;
 mod limitor;
 mod reserve;
 mod dup;
+mod fork;
 mod eof;
 mod adapter;
 #[cfg(feature = "compression-deflate")]
@@ -253,6 +254,7 @@ pub use self::memory::Memory;
 pub use self::limitor::Limitor;
 pub use self::reserve::Reserve;
 pub use self::dup::Dup;
+pub use self::fork::Fork;
 pub use self::eof::EOF;
 pub use self::adapter::Adapter;
 #[cfg(feature = "compression-deflate")]
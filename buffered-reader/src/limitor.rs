@@ -47,6 +47,24 @@ impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> Limitor<T, C> {
             cookie,
         }
     }
+
+    /// Returns the number of bytes that may still be read.
+    ///
+    /// This is the limit minus the amount of data that has already
+    /// been consumed.  Note: the underlying reader may hit EOF before
+    /// the limit is reached.
+    pub fn remaining(&self) -> u64 {
+        self.limit
+    }
+
+    /// Extends the limit by `n` bytes.
+    ///
+    /// This is useful for framings where the amount of data is not
+    /// known up front, but is announced in chunks, like OpenPGP's
+    /// partial body lengths.  The limit saturates at `u64::MAX`.
+    pub fn extend(&mut self, n: u64) {
+        self.limit = self.limit.saturating_add(n);
+    }
 }
 
 impl<T: BufferedReader<C>, C: fmt::Debug + Sync + Send> io::Read for Limitor<T, C> {
@@ -303,4 +321,31 @@ mod test {
         eprintln!("{:?}", l);
         assert!(! l.consummated());
     }
+
+    #[test]
+    fn remaining_and_extend() {
+        let data = b"0123456789";
+
+        let mut l = Limitor::new(Memory::new(data), 3);
+        assert_eq!(l.remaining(), 3);
+        assert_eq!(l.data_consume_hard(2).unwrap(), &b"012"[..]);
+        assert_eq!(l.remaining(), 1);
+        assert!(l.data_consume_hard(2).is_err());
+
+        // Extend the limit and continue reading.
+        l.extend(4);
+        assert_eq!(l.remaining(), 5);
+        assert_eq!(l.data_consume_hard(5).unwrap(), &b"23456"[..]);
+        assert_eq!(l.remaining(), 0);
+        assert!(l.consummated());
+        assert_eq!(l.data(1).unwrap(), &b""[..]);
+
+        // Extending past the end of the underlying reader is fine.
+        l.extend(10);
+        assert_eq!(l.data_eof().unwrap(), &b"789"[..]);
+        assert!(! l.consummated());
+
+        l.extend(u64::MAX);
+        assert_eq!(l.remaining(), u64::MAX);
+    }
 }
@@ -66,6 +66,7 @@ use std::thread;
 pub mod keybox;
 mod keygrip;
 pub use self::keygrip::Keygrip;
+pub mod ownertrust;
 pub mod sexp;
 mod core;
 pub use crate::core::{Config, Context, IPCPolicy};
@@ -0,0 +1,402 @@
+//! Support for GnuPG's ownertrust format.
+//!
+//! GnuPG stores how much the user trusts the owners of certificates
+//! to make certifications, the so-called ownertrust, in its trust
+//! database.  Using `gpg --export-ownertrust`, the ownertrust values
+//! can be exported in a simple line-based format, which can be
+//! imported again using `gpg --import-ownertrust`:
+//!
+//! ```text
+//! # List of assigned trustvalues, created Thu Jan  1 00:00:00 2024 UTC
+//! # (Use "gpg --import-ownertrust" to restore them)
+//! 3E8877C877274692975189F5D03F6F865226FE8B:6:
+//! 0123456789ABCDEF0123456789ABCDEF01234567:4:
+//! ```
+//!
+//! This module reads and writes this format, so that users switching
+//! to Sequoia can keep their trust settings.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # fn main() -> sequoia_openpgp::Result<()> {
+//! use sequoia_openpgp::parse::Parse;
+//! use sequoia_ipc::ownertrust::{OwnerTrustDb, OwnerTrustLevel};
+//!
+//! let exported = "\
+//! ## List of assigned trustvalues, created Thu Jan  1 00:00:00 2024 UTC
+//! 3E8877C877274692975189F5D03F6F865226FE8B:6:
+//! ";
+//!
+//! let db = OwnerTrustDb::from_bytes(exported)?;
+//! let fpr = "3E8877C877274692975189F5D03F6F865226FE8B".parse()?;
+//! assert_eq!(db.get(&fpr).map(|t| t.level()),
+//!            Some(OwnerTrustLevel::Ultimate));
+//! # Ok(()) }
+//! ```
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use sequoia_openpgp as openpgp;
+use openpgp::Fingerprint;
+use openpgp::Result;
+use openpgp::parse::Parse;
+
+/// The level of trust in a certificate's owner.
+///
+/// These are the values used by GnuPG.
+///
+/// Note: This enum cannot be exhaustively matched to allow future
+/// extensions.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OwnerTrustLevel {
+    /// The trust has not been set.
+    Unknown,
+    /// The trust has expired.
+    Expired,
+    /// The user doesn't know how far to trust the owner.
+    Undefined,
+    /// The owner is not trusted to make certifications.
+    Never,
+    /// The owner is partially trusted to make certifications.
+    Marginal,
+    /// The owner is fully trusted to make certifications.
+    Fully,
+    /// The certificate is one of the user's own certificates.
+    ///
+    /// Such certificates are trust roots.
+    Ultimate,
+    /// An unknown value.
+    Other(u8),
+}
+
+impl From<u8> for OwnerTrustLevel {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => OwnerTrustLevel::Unknown,
+            1 => OwnerTrustLevel::Expired,
+            2 => OwnerTrustLevel::Undefined,
+            3 => OwnerTrustLevel::Never,
+            4 => OwnerTrustLevel::Marginal,
+            5 => OwnerTrustLevel::Fully,
+            6 => OwnerTrustLevel::Ultimate,
+            v => OwnerTrustLevel::Other(v),
+        }
+    }
+}
+
+impl From<OwnerTrustLevel> for u8 {
+    fn from(level: OwnerTrustLevel) -> Self {
+        match level {
+            OwnerTrustLevel::Unknown => 0,
+            OwnerTrustLevel::Expired => 1,
+            OwnerTrustLevel::Undefined => 2,
+            OwnerTrustLevel::Never => 3,
+            OwnerTrustLevel::Marginal => 4,
+            OwnerTrustLevel::Fully => 5,
+            OwnerTrustLevel::Ultimate => 6,
+            OwnerTrustLevel::Other(v) => v,
+        }
+    }
+}
+
+impl fmt::Display for OwnerTrustLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OwnerTrustLevel::Unknown => write!(f, "unknown"),
+            OwnerTrustLevel::Expired => write!(f, "expired"),
+            OwnerTrustLevel::Undefined => write!(f, "undefined"),
+            OwnerTrustLevel::Never => write!(f, "never"),
+            OwnerTrustLevel::Marginal => write!(f, "marginal"),
+            OwnerTrustLevel::Fully => write!(f, "full"),
+            OwnerTrustLevel::Ultimate => write!(f, "ultimate"),
+            OwnerTrustLevel::Other(v) => write!(f, "unknown value {}", v),
+        }
+    }
+}
+
+impl OwnerTrustLevel {
+    /// Returns the equivalent trust amount.
+    ///
+    /// Trust signatures and web of trust implementations express
+    /// trust as an amount: 120 means the owner is fully trusted, and
+    /// smaller values mean the owner is partially trusted, see
+    /// [Section 5.2.3.13 of RFC 4880].  GnuPG uses an amount of 60
+    /// for marginal trust.
+    ///
+    /// Returns `None` for levels that don't designate any trust,
+    /// i.e., for unknown, expired, and undefined trust.
+    ///
+    ///   [Section 5.2.3.13 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.13
+    pub fn amount(&self) -> Option<u8> {
+        match self {
+            OwnerTrustLevel::Never => Some(0),
+            OwnerTrustLevel::Marginal => Some(60),
+            OwnerTrustLevel::Fully | OwnerTrustLevel::Ultimate => Some(120),
+            _ => None,
+        }
+    }
+
+    /// Returns the level equivalent to the given trust amount.
+    ///
+    /// This is the inverse of [`OwnerTrustLevel::amount`], except
+    /// that trust roots are not distinguished from fully trusted
+    /// owners.  Amounts of 120 or more map to
+    /// [`OwnerTrustLevel::Fully`], amounts between 1 and 119 map to
+    /// [`OwnerTrustLevel::Marginal`], and zero maps to
+    /// [`OwnerTrustLevel::Never`].
+    pub fn from_amount(amount: u8) -> Self {
+        match amount {
+            0 => OwnerTrustLevel::Never,
+            1..=119 => OwnerTrustLevel::Marginal,
+            _ => OwnerTrustLevel::Fully,
+        }
+    }
+}
+
+/// The ownertrust of a certificate.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnerTrust {
+    fingerprint: Fingerprint,
+    level: OwnerTrustLevel,
+    disabled: bool,
+}
+
+/// GnuPG's flag for disabled certificates.
+const FLAG_DISABLED: u32 = 128;
+
+/// GnuPG's mask for the trust level.
+const TRUST_MASK: u32 = 15;
+
+impl OwnerTrust {
+    /// Returns a new ownertrust designation.
+    pub fn new(fingerprint: Fingerprint, level: OwnerTrustLevel) -> Self {
+        OwnerTrust {
+            fingerprint,
+            level,
+            disabled: false,
+        }
+    }
+
+    /// Returns the certificate's fingerprint.
+    pub fn fingerprint(&self) -> &Fingerprint {
+        &self.fingerprint
+    }
+
+    /// Returns the trust level.
+    pub fn level(&self) -> OwnerTrustLevel {
+        self.level
+    }
+
+    /// Returns whether the certificate has been disabled.
+    ///
+    /// GnuPG doesn't use disabled certificates, independent of the
+    /// trust level.
+    pub fn disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// Sets whether the certificate has been disabled.
+    pub fn set_disabled(mut self, disabled: bool) -> Self {
+        self.disabled = disabled;
+        self
+    }
+
+    /// Returns the value as stored by GnuPG.
+    fn value(&self) -> u32 {
+        (u32::from(u8::from(self.level)) & TRUST_MASK)
+            | if self.disabled { FLAG_DISABLED } else { 0 }
+    }
+}
+
+/// A set of ownertrust designations.
+///
+/// This can be parsed from, and exported to the format used by `gpg
+/// --export-ownertrust` and `gpg --import-ownertrust`.  See the
+/// [module-level documentation] for an example.
+///
+///   [module-level documentation]: self
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OwnerTrustDb {
+    entries: Vec<OwnerTrust>,
+}
+
+impl<'a> Parse<'a, OwnerTrustDb> for OwnerTrustDb {
+    /// Parses the output of `gpg --export-ownertrust`.
+    ///
+    /// Like GnuPG, empty lines and comments are skipped, as are
+    /// entries with a value of zero.  If a certificate is listed more
+    /// than once, the last entry wins.
+    fn from_reader<R: 'a + Read + Send + Sync>(reader: R) -> Result<Self> {
+        let mut db = OwnerTrustDb::default();
+        for (i, line) in io::BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let malformed = |msg: &str| Error::Malformed(i + 1, msg.into());
+            let (fpr, rest) = line.split_once(':')
+                .ok_or_else(|| malformed("missing colon"))?;
+            if fpr.is_empty() || fpr.len() > 64
+                || ! fpr.bytes().all(|b| b.is_ascii_hexdigit())
+            {
+                return Err(malformed("invalid fingerprint").into());
+            }
+            let fingerprint = fpr.parse::<Fingerprint>()
+                .map_err(|_| malformed("invalid fingerprint"))?;
+            if let Fingerprint::Invalid(_) = fingerprint {
+                return Err(malformed("invalid fingerprint").into());
+            }
+
+            let value = rest.split(':').next().unwrap_or("");
+            let value = value.parse::<u32>()
+                .map_err(|_| malformed("invalid trust value"))?;
+            if value == 0 {
+                continue;
+            }
+
+            db.insert(OwnerTrust {
+                fingerprint,
+                level: OwnerTrustLevel::from((value & TRUST_MASK) as u8),
+                disabled: value & FLAG_DISABLED != 0,
+            });
+        }
+
+        Ok(db)
+    }
+}
+
+impl OwnerTrustDb {
+    /// Returns a new, empty set of ownertrust designations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns an iterator over the designations.
+    pub fn iter(&self) -> impl Iterator<Item = &OwnerTrust> {
+        self.entries.iter()
+    }
+
+    /// Returns the number of designations.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether there are no designations.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the designation for the given certificate, if any.
+    pub fn get(&self, fingerprint: &Fingerprint) -> Option<&OwnerTrust> {
+        self.entries.iter().find(|e| e.fingerprint() == fingerprint)
+    }
+
+    /// Adds a designation.
+    ///
+    /// An existing designation for the same certificate is replaced.
+    pub fn insert(&mut self, trust: OwnerTrust) {
+        match self.entries.iter_mut()
+            .find(|e| e.fingerprint() == trust.fingerprint())
+        {
+            Some(e) => *e = trust,
+            None => self.entries.push(trust),
+        }
+    }
+
+    /// Writes the designations in the format used by `gpg
+    /// --export-ownertrust`.
+    ///
+    /// Designations whose value is zero are skipped, because GnuPG
+    /// ignores them.
+    pub fn export(&self, o: &mut dyn Write) -> Result<()> {
+        writeln!(o, "# List of assigned trustvalues")?;
+        writeln!(o, "# (Use \"gpg --import-ownertrust\" to restore them)")?;
+        for e in self.entries.iter().filter(|e| e.value() != 0) {
+            writeln!(o, "{:X}:{}:", e.fingerprint(), e.value())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+/// Errors used in this module.
+#[non_exhaustive]
+pub enum Error {
+    /// A line could not be parsed.
+    #[error("Malformed ownertrust, line {0}: {1}")]
+    Malformed(usize, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORTED: &str = "\
+# List of assigned trustvalues, created Thu Jan  1 00:00:00 2024 UTC
+# (Use \"gpg --import-ownertrust\" to restore them)
+3E8877C877274692975189F5D03F6F865226FE8B:6:
+0123456789ABCDEF0123456789ABCDEF01234567:4:
+
+89ABCDEF0123456789ABCDEF0123456789ABCDEF:131:
+FEDCBA9876543210FEDCBA9876543210FEDCBA98:0:
+";
+
+    #[test]
+    fn parse() -> Result<()> {
+        let db = OwnerTrustDb::from_bytes(EXPORTED)?;
+        assert_eq!(db.len(), 3);
+
+        let get = |fpr: &str| db.get(&fpr.parse().unwrap()).cloned();
+        let t = get("3E8877C877274692975189F5D03F6F865226FE8B").unwrap();
+        assert_eq!(t.level(), OwnerTrustLevel::Ultimate);
+        assert!(! t.disabled());
+        let t = get("0123456789ABCDEF0123456789ABCDEF01234567").unwrap();
+        assert_eq!(t.level(), OwnerTrustLevel::Marginal);
+        let t = get("89ABCDEF0123456789ABCDEF0123456789ABCDEF").unwrap();
+        assert_eq!(t.level(), OwnerTrustLevel::Never);
+        assert!(t.disabled());
+        assert!(get("FEDCBA9876543210FEDCBA9876543210FEDCBA98").is_none());
+
+        assert!(OwnerTrustDb::from_bytes("3E8877C8:6:").is_err());
+        assert!(OwnerTrustDb::from_bytes(
+            "3E8877C877274692975189F5D03F6F865226FE8B").is_err());
+        assert!(OwnerTrustDb::from_bytes(
+            "3E8877C877274692975189F5D03F6F865226FE8B:x:").is_err());
+        assert!(OwnerTrustDb::from_bytes(
+            "3E8877C877274692975189F5D03F6F865226FE8G:6:").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let db = OwnerTrustDb::from_bytes(EXPORTED)?;
+        let mut exported = Vec::new();
+        db.export(&mut exported)?;
+        let exported = String::from_utf8(exported)?;
+        assert!(exported.contains(
+            "\n3E8877C877274692975189F5D03F6F865226FE8B:6:\n"));
+        assert!(exported.contains(
+            "\n89ABCDEF0123456789ABCDEF0123456789ABCDEF:131:\n"));
+        assert_eq!(OwnerTrustDb::from_bytes(&exported)?, db);
+        Ok(())
+    }
+
+    #[test]
+    fn amounts() {
+        use OwnerTrustLevel::*;
+        for level in [Never, Marginal, Fully] {
+            assert_eq!(OwnerTrustLevel::from_amount(level.amount().unwrap()),
+                       level);
+        }
+        assert_eq!(Ultimate.amount(), Some(120));
+        assert_eq!(Undefined.amount(), None);
+        for v in 0..=255u8 {
+            assert_eq!(u8::from(OwnerTrustLevel::from(v)), v);
+        }
+    }
+}
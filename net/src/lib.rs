@@ -6,6 +6,21 @@
 //! Additionally the `pks` module exposes private key operations using
 //! the [PKS][PKS] protocol.
 //!
+//! If the `recon` feature is enabled, the `recon` module implements
+//! the set reconciliation protocol used to synchronize SKS and
+//! Hockeypuck keyservers.
+//!
+//! The `lookup` module provides a verification helper that fetches
+//! the signers' certificates on demand, or the issuer of an individual
+//! signature.
+//!
+//! If the `tracing` feature is enabled, requests to keyservers are
+//! instrumented using the [`tracing`] crate.  Every request is
+//! wrapped in a `keyserver_request` span, and responses, retries, and
+//! failovers are emitted as events.
+//!
+//! [`tracing`]: https://docs.rs/tracing
+//!
 //! [HKP]: https://tools.ietf.org/html/draft-shaw-openpgp-hkp-00
 //! [Web Key Directories]: https://datatracker.ietf.org/doc/html/draft-koch-openpgp-webkey-service
 //! [PKS]: https://gitlab.com/wiktor/pks
@@ -41,6 +56,10 @@
 // Re-exports of crates that we use in our API.
 pub use reqwest;
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
 use percent_encoding::{percent_encode, AsciiSet, CONTROLS};
 
 use reqwest::{
@@ -50,19 +69,36 @@ use reqwest::{
 
 use sequoia_openpgp::{
     self as openpgp,
-    cert::{Cert, CertParser},
+    cert::{
+        Cert,
+        CertParser,
+        Preferences,
+        ValidCert,
+        bundle::ComponentBundle,
+    },
     KeyHandle,
+    Packet,
     packet::UserID,
     parse::Parse,
+    policy::Policy,
     serialize::Serialize,
 };
 
 #[macro_use] mod macros;
 pub mod dane;
 mod email;
+pub mod lookup;
+mod pool;
+pub mod ratelimit;
+#[cfg(feature = "recon")]
+pub mod recon;
 pub mod updates;
+pub mod vindex;
 pub mod wkd;
 
+use pool::Pool;
+use ratelimit::RateLimiter;
+
 /// <https://url.spec.whatwg.org/#fragment-percent-encode-set>
 const KEYSERVER_ENCODE_SET: &AsciiSet =
     // Formerly DEFAULT_ENCODE_SET
@@ -72,18 +108,540 @@ const KEYSERVER_ENCODE_SET: &AsciiSet =
     // respect to the encoding.
     .add(b'-').add(b'+').add(b'/');
 
+/// How often a request is retried by default if the server asks us
+/// to back off.
+const DEFAULT_RETRIES: u32 = 3;
+
+/// The maximum time we are willing to wait before retrying a request.
+///
+/// If the server asks us to wait longer, we give up.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// How many requests [`KeyServer::get_many`] sends concurrently.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// How long idle connections are kept open for reuse.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// How much of a response body we read to extract an error message.
+const MAX_ERROR_BODY: usize = 16 * 1024;
+
+/// How much of an unused response body we read so that the
+/// connection can be reused.
+const MAX_DISCARDED_BODY: usize = 64 * 1024;
+
+/// The maximum length of an error message extracted from a response
+/// body, in characters.
+const MAX_ERROR_MESSAGE: usize = 256;
+
 /// For accessing keyservers using HKP.
+///
+/// A `KeyServer` either talks to a single server, or to a pool of
+/// servers, see [`KeyServer::new_pool`].
+///
+/// If the server responds with `429 Too Many Requests` or `503
+/// Service Unavailable`, the request is retried after the delay
+/// given in the `Retry-After` header, or after an exponentially
+/// increasing delay if the server didn't provide one.  See
+/// [`KeyServer::with_retries`].  To limit the rate of requests in
+/// the first place, see [`KeyServer::with_rate_limiter`].
+///
+/// Connections are kept open and reused for subsequent requests.
+/// For `hkps` servers, HTTP/2 is negotiated if the server supports
+/// it, allowing concurrent requests to share a single connection.
+/// Clones of a `KeyServer` share the connections, so prefer cloning
+/// over creating new handles.
 #[derive(Clone)]
 pub struct KeyServer {
     client: reqwest::Client,
-    /// The original URL given to the constructor.
-    url: Url,
-    /// The URL we use for the requests.
-    request_url: Url,
+    /// The servers.
+    pool: Pool,
+    /// Limits the rate of requests, if set.
+    rate_limiter: Option<RateLimiter>,
+    /// How often a request is retried if the server asks us to back
+    /// off.
+    retries: u32,
+    /// Statistics, shared between clones.
+    counters: Arc<Counters>,
+    /// Whether `refresh` contacts the holder's preferred keyserver.
+    preferred_key_server: PreferredKeyServer,
 }
 
 assert_send_and_sync!(KeyServer);
 
+/// How the servers of a pool are selected.
+///
+/// See [`KeyServer::with_selection`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerSelection {
+    /// The servers are tried in the given order.
+    Ordered,
+
+    /// The servers are tried in a random order.
+    ///
+    /// This spreads the load over the servers.
+    Random,
+}
+
+/// Whether [`KeyServer::refresh`] contacts the certificate holder's
+/// preferred keyserver.
+///
+/// The preferred keyserver is set by the certificate holder, i.e.,
+/// whoever created the certificate.  Contacting it reveals to that
+/// party, and to anyone on the network path if the connection is not
+/// encrypted, that the certificate is being refreshed.  Therefore, it
+/// is not contacted by default.
+///
+/// See [`KeyServer::with_preferred_key_server`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PreferredKeyServer {
+    /// The preferred keyserver is never contacted.
+    ///
+    /// This is the default.
+    #[default]
+    Ignore,
+
+    /// The preferred keyserver is contacted if it is reachable over
+    /// HTTPS, i.e., it is given as an `hkps` URL, or an `https` URL
+    /// without a path.
+    Https,
+
+    /// The preferred keyserver is contacted even if it is only
+    /// reachable over unencrypted HTTP, i.e., it is given as an
+    /// `hkp` or `http` URL.
+    Any,
+}
+
+/// What parts of a certificate are sent to a keyserver.
+///
+/// Keyservers publish everything they are given.  This selects what
+/// is uploaded by [`KeyServer::send_filtered`].  To see exactly what
+/// would be uploaded without contacting the server, use
+/// [`ExportFilter::payload`].
+///
+/// In any case, secret key material, and signatures that are marked
+/// as non-exportable (local signatures), are never sent.  A
+/// certificate whose self-signatures are all non-exportable is not
+/// sent at all.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFilter {
+    /// Sends all exportable components and signatures.
+    ///
+    /// This is the default.
+    #[default]
+    Exportable,
+
+    /// Sends a minimal certificate.
+    ///
+    /// For every component, only the newest self-signature and any
+    /// revocations are sent.  Third-party certifications,
+    /// attestations, superseded self-signatures, and unknown
+    /// components are dropped.  This is like GnuPG's
+    /// `export-minimal` option.
+    Minimal,
+}
+
+impl ExportFilter {
+    /// Returns the ASCII-armored data that is sent to the keyserver.
+    ///
+    /// This is exactly what [`KeyServer::send_filtered`] uploads, and
+    /// can be used to implement a dry run.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_net::Result<()> {
+    /// use sequoia_openpgp::cert::prelude::*;
+    /// use sequoia_net::ExportFilter;
+    ///
+    /// let (cert, _) =
+    ///     CertBuilder::general_purpose(None, Some("alice@example.org"))
+    ///     .generate()?;
+    /// let payload = ExportFilter::Minimal.payload(&cert)?;
+    /// assert!(payload.starts_with(b"-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+    /// # Ok(()) }
+    /// ```
+    pub fn payload(self, cert: &Cert) -> Result<Vec<u8>> {
+        use openpgp::armor::{Writer, Kind};
+
+        if ! cert.exportable() {
+            return Err(openpgp::Error::InvalidOperation(
+                "Certificate is not exportable".into()).into());
+        }
+
+        let mut w = Writer::new(Vec::new(), Kind::PublicKey)?;
+        match self {
+            ExportFilter::Exportable => cert.export(&mut w)?,
+            ExportFilter::Minimal => self_signed(cert, true)?.export(&mut w)?,
+        }
+        Ok(w.finalize()?)
+    }
+}
+
+/// Identifies a version of a server's response.
+///
+/// Servers may label their responses using an `ETag` header, or
+/// report when the resource was last modified using a
+/// `Last-Modified` header.  A `CacheToken` remembers these, so that a
+/// later request can ask the server to only send the resource if it
+/// changed in the meantime.  See [`KeyServer::get_if_modified`].
+///
+/// To persist a token across runs, save [`CacheToken::etag`] and
+/// [`CacheToken::last_modified`], and recreate the token using
+/// [`CacheToken::new`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CacheToken {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl CacheToken {
+    /// Creates a cache token from the values of a previous response's
+    /// `ETag` and `Last-Modified` headers.
+    pub fn new(etag: Option<String>, last_modified: Option<String>) -> Self {
+        CacheToken { etag, last_modified }
+    }
+
+    /// Extracts the cache token from a response's headers.
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        use reqwest::header::{ETAG, LAST_MODIFIED};
+
+        let get = |name| headers.get(name)
+            .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+            .filter(|v| ! v.is_empty());
+        CacheToken {
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+        }
+    }
+
+    /// Returns the value of the `ETag` header, if any.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// Returns the value of the `Last-Modified` header, if any.
+    pub fn last_modified(&self) -> Option<&str> {
+        self.last_modified.as_deref()
+    }
+
+    /// Returns whether the token is empty.
+    ///
+    /// This is the case if the server provided neither an `ETag` nor
+    /// a `Last-Modified` header.  Conditional requests using an empty
+    /// token are unconditional.
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Adds the conditional request headers to `req`.
+    fn apply(&self, mut req: reqwest::RequestBuilder)
+             -> reqwest::RequestBuilder
+    {
+        use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+
+        if let Some(etag) = &self.etag {
+            req = req.header(IF_NONE_MATCH, etag.as_str());
+        }
+        if let Some(last_modified) = &self.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified.as_str());
+        }
+        req
+    }
+}
+
+/// The result of a conditional request.
+///
+/// See [`KeyServer::get_if_modified`].
+#[derive(Debug)]
+pub enum Fetched<T> {
+    /// The resource changed, or the request was unconditional.
+    ///
+    /// This contains the resource, and the token to use for the next
+    /// conditional request.
+    Modified(T, CacheToken),
+
+    /// The resource didn't change since it was last fetched.
+    NotModified,
+}
+
+/// Returns the self-signed parts of `cert`.
+///
+/// Only the components, their exportable self-signatures, and their
+/// revocations are kept.  If `minimal` is true, only the newest
+/// self-signature of every component is kept, see
+/// [`ExportFilter::Minimal`].
+fn self_signed(cert: &Cert, minimal: bool) -> Result<Cert> {
+    fn add<C>(packets: &mut Vec<Packet>, bundle: &ComponentBundle<C>,
+              minimal: bool)
+    where
+        C: Clone + Into<Packet>,
+    {
+        packets.push(bundle.component().clone().into());
+        packets.extend(
+            bundle.self_signatures2()
+                .filter(|sig| sig.exportable().is_ok())
+                .take(if minimal { 1 } else { usize::MAX })
+                .chain(bundle.self_revocations2())
+                .chain(bundle.other_revocations2())
+                .cloned()
+                .map(Packet::from));
+    }
+
+    let mut packets = Vec::new();
+    add(&mut packets, cert.primary_key().bundle(), minimal);
+    for ua in cert.userids() {
+        add(&mut packets, ua.bundle(), minimal);
+    }
+    for ua in cert.user_attributes() {
+        add(&mut packets, ua.bundle(), minimal);
+    }
+    for ka in cert.keys().subkeys() {
+        add(&mut packets, ka.bundle(), minimal);
+    }
+
+    Cert::from_packets(packets.into_iter())
+}
+
+/// Returns the keyserver the certificate holder prefers for updates.
+///
+/// Only keyservers that we can talk to, and that `allowed` permits,
+/// are returned.  That is, HKP URLs, and HTTP URLs without a path.
+fn preferred_key_server(vc: &ValidCert, allowed: PreferredKeyServer)
+                        -> Option<Url>
+{
+    let uri = std::str::from_utf8(vc.preferred_key_server()?).ok()?;
+    let url = Url::parse(uri.trim()).ok()?;
+    let https = match url.scheme() {
+        "hkps" | "https" => true,
+        "hkp" | "http" => false,
+        _ => return None,
+    };
+    match allowed {
+        PreferredKeyServer::Ignore => return None,
+        PreferredKeyServer::Https if ! https => return None,
+        PreferredKeyServer::Https | PreferredKeyServer::Any => (),
+    }
+
+    match url.scheme() {
+        "hkp" | "hkps" => Some(url),
+        "http" | "https" if matches!(url.path(), "" | "/")
+            && url.query().is_none() =>
+        {
+            let scheme = if https { "hkps" } else { "hkp" };
+            format!("{}://{}:{}", scheme, url.host_str()?,
+                    url.port_or_known_default()?).parse().ok()
+        },
+        _ => None,
+    }
+}
+
+/// Statistics about the requests sent by a [`KeyServer`].
+///
+/// These count requests and responses, not connections.  Whether a
+/// request reused a pooled connection is not tracked.
+///
+/// See [`KeyServer::request_statistics`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestStatistics {
+    /// The number of requests sent, including retries.
+    pub requests: u64,
+
+    /// The number of requests that were retried, because the server
+    /// asked us to back off.
+    pub retries: u64,
+
+    /// The number of requests that were sent to another server of
+    /// the pool, because a server failed.
+    pub failovers: u64,
+
+    /// The number of responses received over HTTP/2.
+    ///
+    /// Requests over HTTP/2 are multiplexed over a single connection
+    /// per server.
+    pub http2_responses: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    requests: AtomicU64,
+    retries: AtomicU64,
+    failovers: AtomicU64,
+    http2_responses: AtomicU64,
+}
+
+impl Counters {
+    fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns the `Client` used if the user doesn't supply one.
+fn default_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+       .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+       .pool_max_idle_per_host(MAX_CONCURRENT_REQUESTS)
+       .http2_adaptive_window(true)
+       .dns_resolver(Arc::new(SystemResolver))
+       .build()?)
+}
+
+/// Resolves host names using the system's resolver.
+///
+/// This is the resolver reqwest uses by default, but failures are
+/// wrapped in a [`ResolverError`] so that [`ErrorKind::of`] can
+/// recognize them.
+struct SystemResolver;
+
+impl reqwest::dns::Resolve for SystemResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            // The connector sets the port.
+            let addrs = tokio::net::lookup_host((host.as_str(), 0)).await
+                .map_err(ResolverError)?
+                .collect::<Vec<_>>();
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// A host name could not be resolved by the system's resolver.
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to resolve host name")]
+struct ResolverError(#[source] std::io::Error);
+
+/// Reads and discards the response body.
+///
+/// This allows the connection to be reused.  At most
+/// [`MAX_DISCARDED_BODY`] bytes are read.  If the body is larger, the
+/// response is dropped, closing the connection.
+async fn discard(mut res: reqwest::Response) {
+    let mut read = 0;
+    while read < MAX_DISCARDED_BODY {
+        match res.chunk().await {
+            Ok(Some(chunk)) => read += chunk.len(),
+            _ => break,
+        }
+    }
+}
+
+/// Returns whether the response is an HTML document.
+fn is_html(res: &reqwest::Response) -> bool {
+    res.headers().get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim_start().to_ascii_lowercase().starts_with("text/html"))
+        .unwrap_or(false)
+}
+
+/// Returns an error for the unexpected response `res`.
+///
+/// If the server explains the error in the response body, the
+/// explanation is included in the error.
+async fn status_error(res: reqwest::Response) -> anyhow::Error {
+    let status = res.status();
+    match error_message(res).await {
+        Some(message) => Error::ServerError(status, message).into(),
+        None => Error::HttpStatus(status).into(),
+    }
+}
+
+/// Extracts a message from the response body.
+///
+/// At most [`MAX_ERROR_BODY`] bytes of the body are read.
+async fn error_message(mut res: reqwest::Response) -> Option<String> {
+    let html = is_html(&res);
+    let mut body = Vec::new();
+    while body.len() < MAX_ERROR_BODY {
+        match res.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+    body.truncate(MAX_ERROR_BODY);
+    body_to_message(&body, html)
+}
+
+/// Turns a response body into a short, single-line message.
+///
+/// If `html` is true, or the body looks like markup, tags are
+/// removed.  Control characters are removed, whitespace is collapsed,
+/// and the message is truncated to [`MAX_ERROR_MESSAGE`] characters.
+/// Returns `None` if nothing remains.
+fn body_to_message(body: &[u8], html: bool) -> Option<String> {
+    let body = String::from_utf8_lossy(body);
+
+    let text = if html || body.trim_start().starts_with('<') {
+        let mut text = String::new();
+        let mut rest = &body[..];
+        while let Some(start) = rest.find('<') {
+            text.push_str(&rest[..start]);
+            text.push(' ');
+
+            let tag = &rest[start..];
+            let end = tag.find('>').map(|i| i + 1).unwrap_or(tag.len());
+            rest = &tag[end..];
+
+            // Skip the content of elements that are not displayed.
+            let name = tag[1..end].split(|c: char| ! c.is_ascii_alphanumeric())
+                .next().unwrap_or("").to_ascii_lowercase();
+            if let "head" | "script" | "style" = name.as_str() {
+                let close = format!("</{}", name);
+                rest = rest.to_ascii_lowercase().find(&close)
+                    .map(|i| &rest[i..]).unwrap_or("");
+            }
+        }
+        text.push_str(rest);
+
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&nbsp;", " ")
+            .replace("&amp;", "&")
+    } else {
+        body.into_owned()
+    };
+
+    let text = text
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|w| ! w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.is_empty() {
+        return None;
+    }
+
+    if text.chars().count() > MAX_ERROR_MESSAGE {
+        let mut text = text.chars().take(MAX_ERROR_MESSAGE).collect::<String>();
+        text.push('…');
+        Some(text)
+    } else {
+        Some(text)
+    }
+}
+
+/// Parses the certificates in a successful lookup response.
+///
+/// Some keyservers report errors using an HTML page and a `200 OK`
+/// status.  In that case, the page's text is returned as error.
+async fn certs_from_response(res: reqwest::Response)
+                             -> Result<Vec<Result<Cert>>>
+{
+    if is_html(&res) {
+        return Err(status_error(res).await);
+    }
+
+    let body = res.bytes().await?;
+    let certs = CertParser::from_bytes(&body)?.collect();
+    Ok(certs)
+}
+
 impl Default for KeyServer {
     fn default() -> Self {
 	Self::new("hkps://keys.openpgp.org/").unwrap()
@@ -93,13 +651,122 @@ impl Default for KeyServer {
 impl KeyServer {
     /// Returns a handle for the given URL.
     pub fn new(url: &str) -> Result<Self> {
-	Self::with_client(url, reqwest::Client::new())
+	Self::with_client(url, default_client()?)
     }
 
     /// Returns a handle for the given URL with a custom `Client`.
+    ///
+    /// To reuse connections and negotiate HTTP/2, the `Client` should
+    /// be shared, and reqwest's `http2` feature has to be enabled.
     pub fn with_client(url: &str, client: reqwest::Client) -> Result<Self> {
-        let url = reqwest::Url::parse(url)?;
+        Self::pool_with_client(Some(url), client)
+    }
 
+    /// Returns a handle for a pool of keyservers.
+    ///
+    /// Requests are sent to one server at a time.  If a server
+    /// cannot be reached, or responds with a server error, the next
+    /// server is tried.  Servers that failed are avoided for a while,
+    /// the time growing with every consecutive failure.  By default,
+    /// the servers are tried in the given order, see
+    /// [`KeyServer::with_selection`].
+    ///
+    /// The pool's health information is shared between clones of the
+    /// returned `KeyServer`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use sequoia_openpgp::KeyID;
+    /// # use sequoia_net::{KeyServer, Result};
+    /// # async fn f() -> Result<()> {
+    /// let ks = KeyServer::new_pool([
+    ///     "hkps://keys.openpgp.org",
+    ///     "hkps://keyserver.ubuntu.com",
+    /// ])?;
+    /// let keyid: KeyID = "31855247603831FD".parse()?;
+    /// println!("{:?}", ks.get(keyid).await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_pool<I, S>(urls: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::pool_with_client(urls, default_client()?)
+    }
+
+    /// Returns a handle for a pool of keyservers with a custom
+    /// `Client`.
+    ///
+    /// See [`KeyServer::new_pool`].
+    pub fn pool_with_client<I, S>(urls: I, client: reqwest::Client)
+                                  -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let servers = urls.into_iter()
+            .map(|url| -> Result<_> {
+                let url = reqwest::Url::parse(url.as_ref())?;
+                let request_url = Self::request_url(&url)?;
+                Ok(pool::Server { url, request_url })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if servers.is_empty() {
+            return Err(openpgp::Error::InvalidArgument(
+                "No keyservers given".into()).into());
+        }
+
+        Ok(KeyServer {
+            client,
+            pool: Pool::new(servers),
+            rate_limiter: None,
+            retries: DEFAULT_RETRIES,
+            counters: Default::default(),
+            preferred_key_server: Default::default(),
+        })
+    }
+
+    /// Returns a handle for the keyservers announced in `domain`'s
+    /// DNS SRV records.
+    ///
+    /// Looks up the `_pgpkey-https._tcp` SRV records of `domain`, and
+    /// returns a pool of the announced HKPS servers, ordered by their
+    /// priority and weight (see [RFC 2782]).
+    ///
+    ///   [RFC 2782]: https://www.rfc-editor.org/rfc/rfc2782
+    ///
+    /// See [`KeyServer::new_pool`] for details on pools.
+    pub async fn from_srv(domain: &str) -> Result<Self> {
+        use hickory_resolver::TokioAsyncResolver;
+
+        let resolver =
+            TokioAsyncResolver::tokio(Default::default(), Default::default());
+        let records = resolver
+            .srv_lookup(format!("_pgpkey-https._tcp.{}.", domain))
+            .await?;
+
+        let mut records = records.iter().collect::<Vec<_>>();
+        records.sort_by_key(
+            |srv| (srv.priority(), std::cmp::Reverse(srv.weight())));
+        let urls = records.into_iter()
+            .map(|srv| {
+                let target = srv.target().to_utf8();
+                format!("hkps://{}:{}",
+                        target.trim_end_matches('.'), srv.port())
+            })
+            .collect::<Vec<_>>();
+        if urls.is_empty() {
+            return Err(Error::NotFound.into());
+        }
+
+        Self::new_pool(urls)
+    }
+
+    /// Computes the URL we use for requests to the server at `url`.
+    fn request_url(url: &Url) -> Result<Url> {
         let s = url.scheme();
         match s {
             "hkp" => (),
@@ -107,23 +774,190 @@ impl KeyServer {
             _ => return Err(Error::MalformedUrl.into()),
         }
 
-        let request_url =
-            format!("{}://{}:{}",
-                    match s {"hkp" => "http", "hkps" => "https",
-                             _ => unreachable!()},
-                    url.host().ok_or(Error::MalformedUrl)?,
-                    match s {
-                        "hkp" => url.port().or(Some(11371)),
-                        "hkps" => url.port().or(Some(443)),
-                        _ => unreachable!(),
-                    }.unwrap()).parse()?;
+        Ok(format!("{}://{}:{}",
+                   match s {"hkp" => "http", "hkps" => "https",
+                            _ => unreachable!()},
+                   url.host().ok_or(Error::MalformedUrl)?,
+                   match s {
+                       "hkp" => url.port().or(Some(11371)),
+                       "hkps" => url.port().or(Some(443)),
+                       _ => unreachable!(),
+                   }.unwrap()).parse()?)
+    }
+
+    /// Returns a handle for the server at `url` that shares this
+    /// handle's client, rate limiter, and statistics.
+    fn with_server(&self, url: &Url) -> Result<Self> {
+        let mut ks = Self::with_client(url.as_str(), self.client.clone())?;
+        ks.rate_limiter = self.rate_limiter.clone();
+        ks.retries = self.retries;
+        ks.counters = self.counters.clone();
+        Ok(ks)
+    }
+
+    /// Limits the rate of requests using the given rate limiter.
+    ///
+    /// The rate limiter may be shared with other `KeyServer`s, see
+    /// [`RateLimiter`].  By default, the rate is not limited.
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Sets how often a request is retried if the server asks us to
+    /// back off.
+    ///
+    /// If the server responds with `429 Too Many Requests` or `503
+    /// Service Unavailable`, the request is retried up to `retries`
+    /// times.  Setting this to zero disables retrying.  The default
+    /// is 3.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how the servers of a pool are selected.
+    ///
+    /// The default is [`ServerSelection::Ordered`].  Regardless of
+    /// this setting, servers that recently failed are tried last.
+    pub fn with_selection(mut self, selection: ServerSelection) -> Self {
+        self.pool.set_selection(selection);
+        self
+    }
 
-        Ok(KeyServer { client, url, request_url })
+    /// Sets whether [`KeyServer::refresh`] contacts the certificate
+    /// holder's preferred keyserver.
+    ///
+    /// The default is [`PreferredKeyServer::Ignore`], i.e., only the
+    /// configured keyservers are contacted.
+    pub fn with_preferred_key_server(mut self, preferred: PreferredKeyServer)
+                                     -> Self
+    {
+        self.preferred_key_server = preferred;
+        self
     }
 
     /// Returns the keyserver's base URL.
+    ///
+    /// For a pool, this is the first server's URL.
     pub fn url(&self) -> &reqwest::Url {
-        &self.url
+        &self.pool.servers()[0].url
+    }
+
+    /// Returns the base URLs of all servers in the pool.
+    pub fn urls(&self) -> impl Iterator<Item = &reqwest::Url> {
+        self.pool.servers().iter().map(|s| &s.url)
+    }
+
+    /// Returns statistics about the requests sent so far.
+    ///
+    /// The statistics are shared between clones of this `KeyServer`.
+    pub fn request_statistics(&self) -> RequestStatistics {
+        let c = &self.counters;
+        RequestStatistics {
+            requests: c.requests.load(Ordering::Relaxed),
+            retries: c.retries.load(Ordering::Relaxed),
+            failovers: c.failovers.load(Ordering::Relaxed),
+            http2_responses: c.http2_responses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sends a request for `path`, failing over to the next server
+    /// in the pool if necessary.
+    ///
+    /// `build` is invoked for every attempt with the URL to request.
+    async fn request<F>(&self, path: &str, build: F)
+                        -> Result<reqwest::Response>
+    where
+        F: Fn(Url) -> reqwest::RequestBuilder,
+    {
+        let mut last = None;
+        for i in self.pool.candidates() {
+            if last.is_some() {
+                Counters::bump(&self.counters.failovers);
+            }
+            let url = self.pool.servers()[i].request_url.join(path)?;
+            #[cfg(feature = "tracing")]
+            let span = tracing::debug_span!("keyserver_request", %url);
+            let res = self.request_one(url, &build);
+            #[cfg(feature = "tracing")]
+            let res = tracing::Instrument::instrument(res, span);
+
+            match res.await {
+                Ok(res) if res.status().is_server_error() => {
+                    event!(WARN, server = %self.pool.servers()[i].url,
+                           status = %res.status(), "server error");
+                    self.pool.failed(i);
+                    if let Some(Ok(previous)) = last.replace(Ok(res)) {
+                        discard(previous).await;
+                    }
+                },
+                Ok(res) => {
+                    self.pool.succeeded(i);
+                    return Ok(res);
+                },
+                Err(err) => {
+                    event!(WARN, server = %self.pool.servers()[i].url,
+                           error = %err, "request failed");
+                    self.pool.failed(i);
+                    last = Some(Err(err));
+                },
+            }
+        }
+
+        last.expect("pools are not empty")
+    }
+
+    /// Sends a request to a single server, honoring the rate limit
+    /// and the server's requests to back off.
+    async fn request_one<F>(&self, url: Url, build: &F)
+                            -> Result<reqwest::Response>
+    where
+        F: Fn(Url) -> reqwest::RequestBuilder,
+    {
+        let host = url.host_str().unwrap_or_default();
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.rate_limiter {
+                limiter.acquire(host).await;
+            }
+
+            Counters::bump(&self.counters.requests);
+            let res = build(url.clone()).send().await?;
+            if res.version() == reqwest::Version::HTTP_2 {
+                Counters::bump(&self.counters.http2_responses);
+            }
+            event!(DEBUG, status = %res.status(), version = ?res.version(),
+                   "received response");
+            match res.status() {
+                StatusCode::TOO_MANY_REQUESTS
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    if attempt < self.retries =>
+                {
+                    // The server's Date is real time, so don't consult
+                    // the installed clock.
+                    let delay =
+                        ratelimit::retry_after(res.headers(), SystemTime::now())
+                        .unwrap_or_else(
+                            || Duration::from_secs(1 << attempt.min(8)));
+                    if delay > MAX_RETRY_DELAY {
+                        return Ok(res);
+                    }
+                    discard(res).await;
+                    event!(DEBUG, ?delay, attempt, "server asked us to back off");
+
+                    match &self.rate_limiter {
+                        // Also holds back other users of the limiter.
+                        // The next call to acquire waits.
+                        Some(limiter) => limiter.pause(host, delay),
+                        None => tokio::time::sleep(delay).await,
+                    }
+                    Counters::bump(&self.counters.retries);
+                    attempt += 1;
+                },
+                _ => return Ok(res),
+            }
+        }
     }
 
     /// Retrieves the certificate with the given handle.
@@ -134,23 +968,130 @@ impl KeyServer {
     /// interpreted under a policy and trust model.
     pub async fn get<H: Into<KeyHandle>>(&self, handle: H)
                                          -> Result<Vec<Result<Cert>>>
+    {
+        match self.get_if_modified(handle, None).await? {
+            Fetched::Modified(certs, _) => Ok(certs),
+            Fetched::NotModified => Err(Error::ProtocolViolation.into()),
+        }
+    }
+
+    /// Retrieves the certificate with the given handle, if it changed.
+    ///
+    /// This sends a conditional request: if `token` is given, the
+    /// server is asked to only send the certificate if it changed
+    /// since the response the token was taken from.  If it didn't,
+    /// [`Fetched::NotModified`] is returned.  Otherwise, the
+    /// certificates are returned together with the token to use for
+    /// the next request.
+    ///
+    /// This is useful to periodically refresh certificates, because
+    /// large certificates that rarely change are not transferred
+    /// again.  Servers are not required to support conditional
+    /// requests, in which case the certificates are always returned,
+    /// and the returned token may be empty.
+    ///
+    /// # Warning
+    ///
+    /// Returned certificates must be mistrusted, and be carefully
+    /// interpreted under a policy and trust model.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use sequoia_openpgp::KeyHandle;
+    /// # use sequoia_net::{CacheToken, Fetched, KeyServer, Result};
+    /// # async fn f(handle: KeyHandle) -> Result<()> {
+    /// let ks = KeyServer::default();
+    /// let mut token: Option<CacheToken> = None;
+    /// loop {
+    ///     match ks.get_if_modified(handle.clone(), token.as_ref()).await? {
+    ///         Fetched::Modified(certs, t) => {
+    ///             println!("{}: {} certs", handle, certs.len());
+    ///             token = Some(t);
+    ///         },
+    ///         Fetched::NotModified => println!("{}: unchanged", handle),
+    ///     }
+    ///
+    ///     tokio::time::sleep(std::time::Duration::from_secs(3600)).await;
+    /// }
+    /// # }
+    /// ```
+    pub async fn get_if_modified<H>(&self, handle: H,
+                                    token: Option<&CacheToken>)
+                                    -> Result<Fetched<Vec<Result<Cert>>>>
+    where
+        H: Into<KeyHandle>,
     {
         let handle = handle.into();
-        let url = self.request_url.join(
-            &format!("pks/lookup?op=get&options=mr&search=0x{:X}", handle))?;
+        let path =
+            format!("pks/lookup?op=get&options=mr&search=0x{:X}", handle);
 
-        let res = self.client.get(url).send().await?;
+        let res = self.request(&path, |url| match token {
+            Some(token) => token.apply(self.client.get(url)),
+            None => self.client.get(url),
+        }).await?;
         match res.status() {
             StatusCode::OK => {
-                let body = res.bytes().await?;
-                let certs = CertParser::from_bytes(&body)?.collect();
-                Ok(certs)
-            }
-            StatusCode::NOT_FOUND => Err(Error::NotFound.into()),
-            n => Err(Error::HttpStatus(n).into()),
+                let token = CacheToken::from_headers(res.headers());
+                Ok(Fetched::Modified(certs_from_response(res).await?, token))
+            },
+            StatusCode::NOT_MODIFIED if token.is_some() => {
+                discard(res).await;
+                Ok(Fetched::NotModified)
+            },
+            StatusCode::NOT_FOUND => {
+                discard(res).await;
+                Err(Error::NotFound.into())
+            },
+            _ => Err(status_error(res).await),
         }
     }
 
+    /// Retrieves the certificates with the given handles.
+    ///
+    /// Up to eight requests are sent concurrently.  Connections are
+    /// reused, and over HTTP/2 the requests are multiplexed over a
+    /// single connection.  The results are returned in the order of
+    /// `handles`.  To limit the rate of requests, see
+    /// [`KeyServer::with_rate_limiter`].
+    ///
+    /// # Warning
+    ///
+    /// Returned certificates must be mistrusted, and be carefully
+    /// interpreted under a policy and trust model.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use sequoia_openpgp::KeyHandle;
+    /// # use sequoia_net::{KeyServer, Result};
+    /// # async fn f(handles: Vec<KeyHandle>) -> Result<()> {
+    /// let ks = KeyServer::default();
+    /// for (handle, certs) in handles.iter()
+    ///     .zip(ks.get_many(handles.iter().cloned()).await)
+    /// {
+    ///     match certs {
+    ///         Ok(certs) => println!("{}: {} certs", handle, certs.len()),
+    ///         Err(e) => println!("{}: {}", handle, e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_many<I, H>(&self, handles: I)
+                                -> Vec<Result<Vec<Result<Cert>>>>
+    where
+        I: IntoIterator<Item = H>,
+        H: Into<KeyHandle>,
+    {
+        use futures_util::stream::{self, StreamExt};
+
+        stream::iter(handles.into_iter().map(|h| self.get(h)))
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .collect()
+            .await
+    }
+
     /// Retrieves certificates containing the given `UserID`.
     ///
     /// If the given [`UserID`] does not follow the de facto
@@ -170,28 +1111,162 @@ impl KeyServer {
         let email = userid.email2().and_then(|addr| addr.ok_or_else(||
             openpgp::Error::InvalidArgument(
                 "UserID does not contain an email address".into()).into()))?;
-        let url = self.request_url.join(
-            &format!("pks/lookup?op=get&options=mr&search={}", email))?;
+        let path = format!("pks/lookup?op=get&options=mr&search={}", email);
+
+        let res = self.request(&path, |url| self.client.get(url)).await?;
+        match res.status() {
+            StatusCode::OK => certs_from_response(res).await,
+            StatusCode::NOT_FOUND => {
+                discard(res).await;
+                Err(Error::NotFound.into())
+            },
+            _ => Err(status_error(res).await),
+        }
+    }
+
+    /// Retrieves the verbose index for the given handle.
+    ///
+    /// This lists the matching certificates, their user IDs, and the
+    /// signatures over the user IDs as reported by the server.  See
+    /// the [`vindex`] module for details and how to turn the index
+    /// into a graph of certifications.
+    ///
+    /// # Warning
+    ///
+    /// The returned information has not been verified.
+    pub async fn vindex<H: Into<KeyHandle>>(&self, handle: H)
+                                            -> Result<Vec<vindex::Entry>>
+    {
+        let handle = handle.into();
+        let path =
+            format!("pks/lookup?op=vindex&options=mr&search=0x{:X}", handle);
 
-        let res = self.client.get(url).send().await?;
+        let res = self.request(&path, |url| self.client.get(url)).await?;
         match res.status() {
             StatusCode::OK => {
-                Ok(CertParser::from_bytes(&res.bytes().await?)?.collect())
+                let body = res.text().await?;
+                vindex::parse(&body)
+            },
+            StatusCode::NOT_FOUND => {
+                discard(res).await;
+                Err(Error::NotFound.into())
+            },
+            _ => Err(status_error(res).await),
+        }
+    }
+
+    /// Fetches updates for the given certificate.
+    ///
+    /// The updates are merged into `cert`, and the result is
+    /// returned.  Secret key material in `cert` is preserved.
+    ///
+    /// The certificate holder's preferences are looked up using
+    /// `policy`:
+    ///
+    ///   - If the holder designated a [preferred keyserver], it is an
+    ///     HKP server or an HTTP URL without a path, and contacting
+    ///     it was enabled using [`KeyServer::with_preferred_key_server`],
+    ///     it is queried instead of this keyserver.  If that fails,
+    ///     this keyserver is used.  By default, only this keyserver
+    ///     is used.
+    ///
+    ///   - If the holder set the [no-modify] flag, they asked that
+    ///     their certificate is only modified by them.  In that case,
+    ///     only components, self-signatures, and revocations are
+    ///     merged, and any third-party certifications returned by the
+    ///     server are ignored.
+    ///
+    ///   [preferred keyserver]: openpgp::cert::Preferences::preferred_key_server()
+    ///   [no-modify]: openpgp::types::KeyServerPreferences::no_modify()
+    ///
+    /// Returns [`Error::NotFound`] if the server doesn't know the
+    /// certificate.
+    ///
+    /// # Warning
+    ///
+    /// The updates must be mistrusted.  The returned certificate
+    /// must be carefully interpreted under a policy and trust model.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use sequoia_openpgp::Cert;
+    /// # use sequoia_openpgp::policy::StandardPolicy;
+    /// # use sequoia_net::{KeyServer, Result};
+    /// # async fn f(cert: Cert) -> Result<()> {
+    /// let p = &StandardPolicy::new();
+    /// let ks = KeyServer::default();
+    /// let cert = ks.refresh(&cert, p).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn refresh(&self, cert: &Cert, policy: &dyn Policy)
+                         -> Result<Cert>
+    {
+        let fingerprint = cert.fingerprint();
+        let (preferred, no_modify) = match cert.with_policy(policy, None) {
+            Ok(vc) => (
+                preferred_key_server(&vc, self.preferred_key_server)
+                    .filter(|url| ! self.urls().any(|u| u == url)),
+                vc.key_server_preferences()
+                    .map(|p| p.no_modify()).unwrap_or(false),
+            ),
+            Err(_) => (None, false),
+        };
+
+        let updates = match preferred.map(|url| self.with_server(&url)) {
+            Some(Ok(ks)) => match ks.get(fingerprint.clone()).await {
+                Ok(updates) => updates,
+                Err(_err) => {
+                    event!(WARN, server = %ks.url(), error = %_err,
+                           "preferred keyserver failed, falling back");
+                    self.get(fingerprint.clone()).await?
+                },
             },
-            StatusCode::NOT_FOUND => Err(Error::NotFound.into()),
-            n => Err(Error::HttpStatus(n).into()),
+            _ => self.get(fingerprint.clone()).await?,
+        };
+
+        let mut merged = None;
+        for update in updates.into_iter().flatten()
+            .filter(|c| c.fingerprint() == fingerprint)
+        {
+            let update = if no_modify {
+                self_signed(&update, false)?
+            } else {
+                update
+            };
+            merged = Some(merged.unwrap_or_else(|| cert.clone())
+                          .merge_public(update)?);
         }
+
+        merged.ok_or_else(|| Error::NotFound.into())
     }
 
     /// Sends the given key to the server.
+    ///
+    /// All exportable components and signatures are sent, see
+    /// [`ExportFilter::Exportable`].  Use
+    /// [`KeyServer::send_filtered`] to send less.
+    ///
+    /// If the server rejects the key, and explains why in the
+    /// response, the explanation is included in the returned
+    /// [`Error::ServerError`].
     pub async fn send(&self, key: &Cert) -> Result<()> {
-        use sequoia_openpgp::armor::{Writer, Kind};
-
-        let url = self.request_url.join("pks/add")?;
-        let mut w =  Writer::new(Vec::new(), Kind::PublicKey)?;
-        key.serialize(&mut w)?;
+        self.send_filtered(key, ExportFilter::default()).await
+    }
 
-        let armored_blob = w.finalize()?;
+    /// Sends the given key to the server, filtered using `filter`.
+    ///
+    /// To see what would be sent without contacting the server, use
+    /// [`ExportFilter::payload`].
+    ///
+    /// If the server rejects the key, and explains why in the
+    /// response, the explanation is included in the returned
+    /// [`Error::ServerError`].
+    pub async fn send_filtered(&self, key: &Cert, filter: ExportFilter)
+                               -> Result<()>
+    {
+        let armored_blob = filter.payload(key)?;
 
         // Prepare to send url-encoded data.
         let mut post_data = b"keytext=".to_vec();
@@ -199,15 +1274,33 @@ impl KeyServer {
                                     .collect::<String>().as_bytes());
         let length = post_data.len();
 
-        let res = self.client.post(url)
+        let res = self.request("pks/add", |url| self.client.post(url)
             .header("content-type", "application/x-www-form-urlencoded")
             .header("content-length", length.to_string())
-            .body(post_data).send().await?;
+            .body(post_data.clone())).await?;
 
         match res.status() {
-            StatusCode::OK => Ok(()),
-            StatusCode::NOT_FOUND => Err(Error::ProtocolViolation.into()),
-            n => Err(Error::HttpStatus(n).into()),
+            StatusCode::OK => {
+                // Some keyservers report errors using an HTML page
+                // and a 200 status.
+                if ! is_html(&res) {
+                    discard(res).await;
+                    return Ok(());
+                }
+
+                match error_message(res).await {
+                    Some(message)
+                        if message.to_lowercase().contains("error") =>
+                        Err(Error::ServerError(StatusCode::OK, message)
+                            .into()),
+                    _ => Ok(()),
+                }
+            },
+            StatusCode::NOT_FOUND => {
+                discard(res).await;
+                Err(Error::ProtocolViolation.into())
+            },
+            _ => Err(status_error(res).await),
         }
     }
 }
@@ -234,6 +1327,13 @@ pub enum Error {
     /// Encountered an unexpected low-level http status.
     #[error("server returned status {0}")]
     HttpStatus(hyper::StatusCode),
+    /// The server returned an error, and explained it.
+    ///
+    /// This contains the status, and the explanation extracted from
+    /// the response body.  The explanation has been sanitized and
+    /// truncated, but it has not been authenticated.
+    #[error("server returned status {0}: {1}")]
+    ServerError(hyper::StatusCode, String),
     /// A `hyper::error::UrlError` occurred.
     #[error(transparent)]
     UrlError(#[from] url::ParseError),
@@ -252,6 +1352,229 @@ pub enum Error {
     /// An email address was not found in Cert userids.
     #[error("Email address {0} not found in Cert's userids")]
     EmailNotInUserids(String),
+
+    /// A lookup was not permitted by the network policy.
+    #[error("Network policy violation: {0}")]
+    PolicyViolation(String),
+}
+
+impl Error {
+    /// Classifies the error.
+    ///
+    /// Errors returned by the network routines are not necessarily
+    /// of this type, use [`ErrorKind::of`] to classify them.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::NotFound => ErrorKind::NotFound,
+            Error::MalformedUrl
+                | Error::MalformedEmail(_)
+                | Error::EmailNotInUserids(_)
+                | Error::UrlError(_)
+                | Error::HttpError(_) => ErrorKind::InvalidInput,
+            Error::MalformedResponse => ErrorKind::MalformedResponse,
+            Error::ProtocolViolation => ErrorKind::ProtocolViolation,
+            Error::HttpStatus(status)
+                | Error::ServerError(status, _) => ErrorKind::from(*status),
+            Error::HyperError(e) => ErrorKind::from(e),
+            Error::PolicyViolation(_) => ErrorKind::PolicyViolation,
+        }
+    }
+
+    /// Returns whether the operation may succeed if retried later.
+    ///
+    /// See [`ErrorKind::is_transient`].
+    pub fn is_transient(&self) -> bool {
+        self.kind().is_transient()
+    }
+}
+
+/// The kind of an error.
+///
+/// This classifies errors so that callers can decide whether to
+/// retry an operation, and how to explain a failure to the user,
+/// without inspecting error messages.  Use [`ErrorKind::of`] to
+/// classify the errors returned by the network routines.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use sequoia_net::{KeyServer, ErrorKind, Result};
+/// # use sequoia_openpgp::KeyID;
+/// # async fn f() -> Result<()> {
+/// let ks = KeyServer::default();
+/// let keyid: KeyID = "31855247603831FD".parse()?;
+/// for _ in 0..3 {
+///     match ks.get(keyid.clone()).await {
+///         Ok(certs) => {
+///             println!("{:?}", certs);
+///             break;
+///         },
+///         Err(e) if ErrorKind::of(&e).is_transient() => continue,
+///         Err(e) => return Err(e),
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The requested data was not found.
+    NotFound,
+    /// The arguments are invalid, e.g. a URL or email address is
+    /// malformed.
+    InvalidInput,
+    /// A host name could not be resolved.
+    Dns,
+    /// A connection could not be established, or was interrupted.
+    Connect,
+    /// An operation timed out.
+    Timeout,
+    /// The TLS handshake failed, e.g. because the server's
+    /// certificate could not be verified.
+    Tls,
+    /// The server rejected the request with a 4xx status code.
+    HttpClientError,
+    /// The server asked us to slow down using status code 429.
+    RateLimited,
+    /// The server failed with a 5xx status code.
+    HttpServerError,
+    /// The server's response could not be parsed.
+    MalformedResponse,
+    /// The server violated the protocol.
+    ProtocolViolation,
+    /// The operation was not permitted by the network policy.
+    PolicyViolation,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    /// Classifies an error returned by the network routines.
+    ///
+    /// This looks at the error and its sources, recognizing this
+    /// crate's [`Error`]s as well as errors from the HTTP client,
+    /// the DNS resolver, and the operating system.
+    ///
+    /// If the [`reqwest::Client`] is supplied by the caller, e.g. to
+    /// [`KeyServer::with_client`] or [`wkd::get`], DNS errors are
+    /// only recognized if the client resolves names using hickory.
+    /// Otherwise, they are reported as connection errors.
+    pub fn of(err: &anyhow::Error) -> ErrorKind {
+        err.chain().map(Self::of_source)
+            .find(|kind| *kind != ErrorKind::Other)
+            .unwrap_or(ErrorKind::Other)
+    }
+
+    /// Classifies a single error in an error chain.
+    fn of_source(e: &(dyn std::error::Error + 'static)) -> ErrorKind {
+        use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+
+        if let Some(e) = e.downcast_ref::<Error>() {
+            e.kind()
+        } else if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+            if e.is_timeout() {
+                ErrorKind::Timeout
+            } else if let Some(status) = e.status() {
+                ErrorKind::from(status)
+            } else if e.is_decode() || e.is_body() {
+                ErrorKind::MalformedResponse
+            } else if e.is_redirect() {
+                ErrorKind::ProtocolViolation
+            } else if e.is_builder() {
+                ErrorKind::InvalidInput
+            } else {
+                // The details are in the sources.  If there is nothing
+                // more specific, it is a connection problem.
+                let mut source = std::error::Error::source(e);
+                while let Some(e) = source {
+                    match Self::of_source(e) {
+                        ErrorKind::Other => (),
+                        kind => return kind,
+                    }
+                    source = e.source();
+                }
+                ErrorKind::Connect
+            }
+        } else if let Some(e) = e.downcast_ref::<hyper::Error>() {
+            ErrorKind::from(e)
+        } else if e.downcast_ref::<hyper_tls::native_tls::Error>().is_some() {
+            ErrorKind::Tls
+        } else if e.downcast_ref::<ResolverError>().is_some() {
+            ErrorKind::Dns
+        } else if let Some(e) = e.downcast_ref::<ResolveError>() {
+            match e.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => ErrorKind::NotFound,
+                ResolveErrorKind::Timeout => ErrorKind::Timeout,
+                _ => ErrorKind::Dns,
+            }
+        } else if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind as IoKind;
+            match e.kind() {
+                IoKind::TimedOut => ErrorKind::Timeout,
+                IoKind::ConnectionRefused
+                    | IoKind::ConnectionReset
+                    | IoKind::ConnectionAborted
+                    | IoKind::NotConnected
+                    | IoKind::BrokenPipe
+                    | IoKind::UnexpectedEof => ErrorKind::Connect,
+                _ => ErrorKind::Other,
+            }
+        } else if e.downcast_ref::<openpgp::Error>().is_some() {
+            // The server returned data we couldn't parse.
+            ErrorKind::MalformedResponse
+        } else {
+            ErrorKind::Other
+        }
+    }
+
+    /// Returns whether the operation may succeed if retried later.
+    ///
+    /// DNS failures, connection failures, timeouts, rate limiting,
+    /// and server errors are considered transient.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ErrorKind::Dns
+                | ErrorKind::Connect
+                | ErrorKind::Timeout
+                | ErrorKind::RateLimited
+                | ErrorKind::HttpServerError => true,
+            ErrorKind::NotFound
+                | ErrorKind::InvalidInput
+                | ErrorKind::Tls
+                | ErrorKind::HttpClientError
+                | ErrorKind::MalformedResponse
+                | ErrorKind::ProtocolViolation
+                | ErrorKind::PolicyViolation
+                | ErrorKind::Other => false,
+        }
+    }
+}
+
+impl From<StatusCode> for ErrorKind {
+    fn from(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND | StatusCode::GONE => ErrorKind::NotFound,
+            StatusCode::REQUEST_TIMEOUT => ErrorKind::Timeout,
+            StatusCode::TOO_MANY_REQUESTS => ErrorKind::RateLimited,
+            s if s.is_client_error() => ErrorKind::HttpClientError,
+            s if s.is_server_error() => ErrorKind::HttpServerError,
+            _ => ErrorKind::ProtocolViolation,
+        }
+    }
+}
+
+impl From<&hyper::Error> for ErrorKind {
+    fn from(e: &hyper::Error) -> Self {
+        if e.is_timeout() {
+            ErrorKind::Timeout
+        } else if e.is_parse() || e.is_parse_status() {
+            ErrorKind::MalformedResponse
+        } else if e.is_user() {
+            ErrorKind::InvalidInput
+        } else {
+            ErrorKind::Connect
+        }
+    }
 }
 
 #[cfg(test)]
@@ -264,4 +1587,287 @@ mod tests {
         assert!(KeyServer::new("hkp://keys.openpgp.org").is_ok());
         assert!(KeyServer::new("hkps://keys.openpgp.org").is_ok());
     }
+
+    #[test]
+    fn pool() -> Result<()> {
+        assert!(KeyServer::new_pool(Vec::<&str>::new()).is_err());
+        assert!(KeyServer::new_pool(["hkps://keys.openpgp.org",
+                                     "keyserver.ubuntu.com"]).is_err());
+
+        let ks = KeyServer::new_pool(["hkps://keys.openpgp.org",
+                                      "hkp://keyserver.ubuntu.com"])?;
+        assert_eq!(ks.url().as_str(), "hkps://keys.openpgp.org");
+        assert_eq!(ks.urls().map(|u| u.as_str()).collect::<Vec<_>>(),
+                   ["hkps://keys.openpgp.org", "hkp://keyserver.ubuntu.com"]);
+        Ok(())
+    }
+
+    #[test]
+    fn error_kinds() {
+        assert_eq!(Error::NotFound.kind(), ErrorKind::NotFound);
+        assert_eq!(Error::MalformedUrl.kind(), ErrorKind::InvalidInput);
+        assert!(! Error::MalformedResponse.is_transient());
+
+        let status = |s: u16| Error::HttpStatus(
+            StatusCode::from_u16(s).unwrap()).kind();
+        assert_eq!(status(404), ErrorKind::NotFound);
+        assert_eq!(status(400), ErrorKind::HttpClientError);
+        assert_eq!(status(429), ErrorKind::RateLimited);
+        assert_eq!(status(503), ErrorKind::HttpServerError);
+        assert!(! ErrorKind::HttpClientError.is_transient());
+        assert!(ErrorKind::RateLimited.is_transient());
+        assert!(Error::ServerError(StatusCode::BAD_GATEWAY, "x".into())
+                .is_transient());
+
+        // Errors are classified through contexts and sources.
+        let e = anyhow::Error::from(Error::HttpStatus(StatusCode::BAD_GATEWAY))
+            .context("Fetching cert");
+        assert_eq!(ErrorKind::of(&e), ErrorKind::HttpServerError);
+        let e = anyhow::Error::from(std::io::Error::new(
+            std::io::ErrorKind::TimedOut, "too slow"));
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Timeout);
+        assert!(ErrorKind::of(&e).is_transient());
+        let e = anyhow::Error::from(
+            hickory_resolver::error::ResolveError::from("no connections"))
+            .context("Looking up example.org");
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Dns);
+        assert!(ErrorKind::of(&e).is_transient());
+        let e = anyhow::Error::from(ResolverError(std::io::Error::new(
+            std::io::ErrorKind::Other, "no such host")));
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Dns);
+        let e = anyhow::Error::from(openpgp::Error::MalformedCert("x".into()));
+        assert_eq!(ErrorKind::of(&e), ErrorKind::MalformedResponse);
+        assert_eq!(ErrorKind::of(&anyhow::anyhow!("oops")), ErrorKind::Other);
+    }
+
+    /// Checks that resolver failures are recognized through the HTTP
+    /// client's error chain.
+    #[tokio::test]
+    async fn error_kinds_dns() -> Result<()> {
+        struct Failing;
+        impl reqwest::dns::Resolve for Failing {
+            fn resolve(&self, _: reqwest::dns::Name)
+                       -> reqwest::dns::Resolving
+            {
+                Box::pin(async {
+                    Err(ResolverError(std::io::Error::new(
+                        std::io::ErrorKind::Other, "no such host")).into())
+                })
+            }
+        }
+
+        let client = reqwest::Client::builder()
+            .dns_resolver(Arc::new(Failing))
+            .build()?;
+        let e = anyhow::Error::from(
+            client.get("http://example.org").send().await.unwrap_err());
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Dns);
+
+        // Names are resolved using the system's resolver, and
+        // connection failures are not mistaken for DNS failures.
+        let e = anyhow::Error::from(
+            default_client()?.get("http://localhost:1").send().await
+                .unwrap_err());
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Connect);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_token() -> Result<()> {
+        use reqwest::header::*;
+
+        let mut headers = HeaderMap::new();
+        let token = CacheToken::from_headers(&headers);
+        assert!(token.is_empty());
+
+        headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+        headers.insert(LAST_MODIFIED,
+                       HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        let token = CacheToken::from_headers(&headers);
+        assert!(! token.is_empty());
+        assert_eq!(token.etag(), Some("\"abc\""));
+        assert_eq!(token.last_modified(),
+                   Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(token, CacheToken::new(
+            token.etag().map(Into::into),
+            token.last_modified().map(Into::into)));
+
+        let client = reqwest::Client::new();
+        let req = token.apply(client.get("https://example.org/")).build()?;
+        assert_eq!(req.headers()[IF_NONE_MATCH], "\"abc\"");
+        assert_eq!(req.headers()[IF_MODIFIED_SINCE],
+                   "Wed, 21 Oct 2015 07:28:00 GMT");
+
+        let req = CacheToken::default()
+            .apply(client.get("https://example.org/")).build()?;
+        assert!(req.headers().get(IF_NONE_MATCH).is_none());
+        assert!(req.headers().get(IF_MODIFIED_SINCE).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn export_filter() -> Result<()> {
+        use openpgp::cert::prelude::*;
+        use openpgp::packet::signature::SignatureBuilder;
+        use openpgp::types::SignatureType;
+
+        let (alice, _) =
+            CertBuilder::general_purpose(None, Some("alice@example.org"))
+            .generate()?;
+        let (bob, _) =
+            CertBuilder::general_purpose(None, Some("bob@example.org"))
+            .generate()?;
+        let mut bob_signer = bob.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+
+        let userid = alice.userids().next().unwrap().userid().clone();
+        let certification =
+            SignatureBuilder::new(SignatureType::GenericCertification)
+            .sign_userid_binding(&mut bob_signer, alice.primary_key().key(),
+                                 &userid)?;
+        let local =
+            SignatureBuilder::new(SignatureType::GenericCertification)
+            .set_exportable_certification(false)?
+            .sign_userid_binding(&mut bob_signer, alice.primary_key().key(),
+                                 &userid)?;
+        let alice = alice.insert_packets(vec![certification, local])?;
+        assert_eq!(alice.userids().next().unwrap().certifications().count(), 2);
+
+        let sent = |filter: ExportFilter| -> Result<Cert> {
+            let payload = filter.payload(&alice)?;
+            // Secrets are never sent.
+            assert!(payload.starts_with(
+                b"-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+            Cert::from_bytes(&payload)
+        };
+
+        let cert = sent(ExportFilter::default())?;
+        assert!(! cert.is_tsk());
+        assert_eq!(cert.fingerprint(), alice.fingerprint());
+        assert_eq!(cert.keys().subkeys().count(), 2);
+        assert_eq!(cert.userids().next().unwrap().certifications().count(), 1);
+
+        let cert = sent(ExportFilter::Minimal)?;
+        assert!(! cert.is_tsk());
+        assert_eq!(cert.keys().subkeys().count(), 2);
+        let ua = cert.userids().next().unwrap();
+        assert_eq!(ua.self_signatures().count(), 1);
+        assert_eq!(ua.certifications().count(), 0);
+
+        // Non-exportable certificates are not sent at all.
+        let (local, _) =
+            CertBuilder::general_purpose(None, Some("carol@example.org"))
+            .set_exportable(false)
+            .generate()?;
+        assert!(ExportFilter::default().payload(&local).is_err());
+        assert!(ExportFilter::Minimal.payload(&local).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn preferred_key_server() -> Result<()> {
+        use std::time::{Duration, SystemTime};
+        use openpgp::cert::prelude::*;
+        use openpgp::packet::signature::SignatureBuilder;
+        use openpgp::policy::StandardPolicy;
+        use openpgp::types::SignatureType;
+
+        let p = &StandardPolicy::new();
+        let (cert, _) = CertBuilder::new()
+            .set_creation_time(SystemTime::now() - Duration::from_secs(3600))
+            .add_userid("alice@example.org")
+            .generate()?;
+        let mut signer = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        assert_eq!(super::preferred_key_server(&cert.with_policy(p, None)?,
+                                               PreferredKeyServer::Any),
+                   None);
+
+        let mut prefer = |uri: &str, allowed| -> Result<Option<Url>> {
+            let userid = cert.userids().next().unwrap();
+            let sig = SignatureBuilder::from(
+                userid.binding_signature(p, None)?.clone())
+                .set_signature_creation_time(SystemTime::now())?
+                .set_preferred_key_server(uri)?
+                .sign_userid_binding(&mut signer, cert.primary_key().key(),
+                                     userid.userid())?;
+            let cert = cert.clone().insert_packets(sig)?;
+            Ok(super::preferred_key_server(&cert.with_policy(p, None)?,
+                                           allowed))
+        };
+
+        use PreferredKeyServer::*;
+        assert_eq!(prefer("hkps://keys.example.org", Https)?,
+                   Some("hkps://keys.example.org".parse()?));
+        assert_eq!(prefer("https://keys.example.org/", Https)?,
+                   Some("hkps://keys.example.org:443".parse()?));
+        assert_eq!(prefer("http://keys.example.org", Any)?,
+                   Some("hkp://keys.example.org:80".parse()?));
+        assert_eq!(prefer("hkp://keys.example.org", Any)?,
+                   Some("hkp://keys.example.org".parse()?));
+        // Unencrypted keyservers are only contacted if allowed.
+        assert_eq!(prefer("http://keys.example.org", Https)?, None);
+        assert_eq!(prefer("hkp://keys.example.org", Https)?, None);
+        // By default, the preferred keyserver is ignored.
+        assert_eq!(prefer("hkps://keys.example.org", Ignore)?, None);
+        // Links to a certificate are not keyservers.
+        assert_eq!(prefer("https://example.org/alice.asc", Any)?, None);
+        assert_eq!(prefer("ldap://keys.example.org", Any)?, None);
+        assert_eq!(prefer("keys.example.org", Any)?, None);
+
+        // With the no-modify flag, third-party certifications are
+        // ignored.
+        let (bob, _) = CertBuilder::new()
+            .add_userid("bob@example.org")
+            .generate()?;
+        let mut bob_signer = bob.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        let userid = cert.userids().next().unwrap().userid().clone();
+        let certification =
+            SignatureBuilder::new(SignatureType::GenericCertification)
+            .sign_userid_binding(&mut bob_signer, cert.primary_key().key(),
+                                 &userid)?;
+        let certified = cert.clone().insert_packets(certification)?;
+        let stripped = self_signed(&certified, false)?;
+        assert_eq!(stripped, self_signed(&cert, false)?);
+        assert_eq!(stripped.userids().next().unwrap().certifications().count(),
+                   0);
+        assert_eq!(stripped.userids().next().unwrap().self_signatures().count(),
+                   cert.userids().next().unwrap().self_signatures().count());
+        Ok(())
+    }
+
+    #[test]
+    fn body_to_message() {
+        use super::body_to_message as m;
+
+        assert_eq!(m(b"", false), None);
+        assert_eq!(m(b" \r\n\t", false), None);
+        assert_eq!(m(b"key rejected: no user ID\n", false).as_deref(),
+                   Some("key rejected: no user ID"));
+        assert_eq!(m(b"bad\x1b[31m  \n key", false).as_deref(),
+                   Some("bad [31m key"));
+
+        // SKS style.
+        assert_eq!(m(b"<html><head><title>Error handling request</title>\
+                       <style>p { color: red; }</style></head>\
+                       <body><h2>Error handling request</h2>\
+                       Error: Key rejected &amp; &lt;dropped&gt;\
+                       </body></html>", true).as_deref(),
+                   Some("Error handling request Error: Key rejected & <dropped>"));
+        // Markup is detected even without the content type.
+        assert_eq!(m(b"<p>Not <b>found</b></p>", false).as_deref(),
+                   Some("Not found"));
+        assert_eq!(m(b"<html><head><title>x</title></head></html>", true),
+                   None);
+        // Unterminated tags.
+        assert_eq!(m(b"Oops <b", true).as_deref(), Some("Oops"));
+
+        let long = vec![b'x'; MAX_ERROR_BODY];
+        let message = m(&long, false).unwrap();
+        assert_eq!(message.chars().count(), MAX_ERROR_MESSAGE + 1);
+        assert!(message.ends_with('…'));
+    }
 }
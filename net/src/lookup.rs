@@ -0,0 +1,681 @@
+//! Network-assisted signature verification.
+//!
+//! Verifying a signature requires the signer's certificate.  Simple
+//! tools often don't manage a certificate store, and would rather
+//! fetch the certificates on demand.  [`Lookup`] is a
+//! [`VerificationHelper`] that does just that: it looks up missing
+//! certificates in a configurable sequence of [`Source`]s, caches
+//! them, and only accesses the network as permitted by the
+//! [`NetworkPolicy`].
+//!
+//! # Warning
+//!
+//! Certificates fetched from the network are not authenticated.  By
+//! default, [`Lookup`] accepts any good signature, which only proves
+//! that the message was signed by *some* key that could be found.
+//! Use [`Lookup::with_check`] to decide which signers to trust, e.g.
+//! by comparing the certificate's fingerprint to the expected one.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> sequoia_net::Result<()> {
+//! use std::io;
+//!
+//! use sequoia_openpgp as openpgp;
+//! use openpgp::parse::{Parse, stream::VerifierBuilder};
+//! use openpgp::policy::StandardPolicy;
+//! use sequoia_net::KeyServer;
+//! use sequoia_net::lookup::{Lookup, NetworkPolicy, Source};
+//!
+//! # let message: &[u8] = b"";
+//! let p = &StandardPolicy::new();
+//! let helper = Lookup::new(NetworkPolicy::Encrypted)
+//!     .with_source(Source::Keyring("keyring.pgp".into()))?
+//!     .with_source(Source::Wkd)?
+//!     .with_source(Source::KeyServer(KeyServer::default()))?;
+//!
+//! let mut v = VerifierBuilder::from_bytes(message)?
+//!     .with_policy(p, None, helper)?;
+//! io::copy(&mut v, &mut io::stdout())?;
+//! # Ok(()) }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use sequoia_openpgp::{
+    self as openpgp,
+    Cert,
+    Fingerprint,
+    KeyHandle,
+    Packet,
+    cert::CertParser,
+    packet::{
+        Signature,
+        UserID,
+    },
+    parse::{
+        Parse,
+        PacketParser,
+        stream::{
+            MessageLayer,
+            MessageStructure,
+            VerificationHelper,
+        },
+    },
+};
+
+use crate::{
+    Error,
+    KeyServer,
+    Result,
+    wkd,
+};
+
+/// A source of certificates.
+///
+/// See [`Lookup::with_source`].
+#[non_exhaustive]
+#[derive(Clone)]
+pub enum Source {
+    /// A local keyring file.
+    Keyring(PathBuf),
+
+    /// Web Key Directories.
+    ///
+    /// WKDs are indexed by email address, not by key handle.  The
+    /// addresses looked up are those given using
+    /// [`Lookup::with_address`], and, if enabled using
+    /// [`Lookup::with_signers_user_id_hints`], those in the Signer's
+    /// User ID subpackets of the signatures being verified.
+    Wkd,
+
+    /// A keyserver.
+    KeyServer(KeyServer),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Keyring(path) => write!(f, "keyring {}", path.display()),
+            Source::Wkd => write!(f, "WKD"),
+            Source::KeyServer(ks) => write!(f, "keyserver {}", ks.url()),
+        }
+    }
+}
+
+/// Which network accesses are permitted.
+///
+/// The policies are ordered from the most to the least restrictive.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NetworkPolicy {
+    /// No network access, only local sources are used.
+    Offline,
+
+    /// Only encrypted network connections are used.
+    ///
+    /// WKD lookups always use HTTPS.  Keyservers must use `hkps`.
+    Encrypted,
+
+    /// Unencrypted network connections are used as well.
+    Insecure,
+}
+
+impl NetworkPolicy {
+    /// Checks whether `source` may be used under this policy.
+    pub fn check(&self, source: &Source) -> Result<()> {
+        let violation = match source {
+            Source::Keyring(_) => false,
+            Source::Wkd => *self < NetworkPolicy::Encrypted,
+            Source::KeyServer(ks) => match self {
+                NetworkPolicy::Offline => true,
+                NetworkPolicy::Encrypted =>
+                    ks.urls().any(|url| url.scheme() != "hkps"),
+                NetworkPolicy::Insecure => false,
+            },
+        };
+
+        if violation {
+            Err(Error::PolicyViolation(
+                format!("{} not permitted by {:?} policy", source, self))
+                .into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The check performed by [`Lookup`] if none is given.
+///
+/// Accepts the message if there is at least one good signature.
+fn any_good_signature(structure: MessageStructure) -> openpgp::Result<()> {
+    let mut error = None;
+    for layer in structure {
+        if let MessageLayer::SignatureGroup { results } = layer {
+            for result in results {
+                match result {
+                    Ok(_) => return Ok(()),
+                    Err(e) => if error.is_none() {
+                        error = Some(openpgp::Error::from(e));
+                    },
+                }
+            }
+        }
+    }
+
+    Err(error.unwrap_or_else(|| openpgp::Error::InvalidOperation(
+        "No signature found".into())).into())
+}
+
+/// A [`VerificationHelper`] fetching certificates on demand.
+///
+/// When the verifier asks for the certificates of the issuers, they
+/// are looked up in the cache first.  The remaining ones are looked
+/// up in the sources, in the order they were added, until all of them
+/// have been found.  Certificates that have been found are cached,
+/// and so are the key handles that could not be found, so that
+/// reusing the helper (see, e.g., [`Verifier::into_helper`]) doesn't
+/// repeat lookups.
+///
+/// Errors accessing a source are not fatal: the source is skipped,
+/// and the signature is reported as made by a missing key if no
+/// other source has the certificate.
+///
+/// The lookups are performed synchronously.  Hence, the helper must
+/// not be used from within an asynchronous runtime.
+///
+/// See the [module-level documentation] for an example.
+///
+///   [`Verifier::into_helper`]: sequoia_openpgp::parse::stream::Verifier::into_helper()
+///   [module-level documentation]: self
+pub struct Lookup {
+    policy: NetworkPolicy,
+    sources: Vec<Source>,
+    client: reqwest::Client,
+    addresses: Vec<String>,
+    signers_user_id_hints: bool,
+    certs: BTreeMap<Fingerprint, Cert>,
+    misses: Vec<KeyHandle>,
+    check: Box<dyn FnMut(MessageStructure) -> openpgp::Result<()>
+               + Send + Sync>,
+}
+assert_send_and_sync!(Lookup);
+
+impl Lookup {
+    /// Returns a helper without sources using the given policy.
+    pub fn new(policy: NetworkPolicy) -> Self {
+        Lookup {
+            policy,
+            sources: Vec::new(),
+            client: crate::default_client()
+                .expect("the HTTP client can be initialized"),
+            addresses: Vec::new(),
+            signers_user_id_hints: false,
+            certs: BTreeMap::new(),
+            misses: Vec::new(),
+            check: Box::new(any_good_signature),
+        }
+    }
+
+    /// Adds a source to look up certificates in.
+    ///
+    /// Sources are consulted in the order they are added.  Returns
+    /// an error if the source is not permitted by the
+    /// [`NetworkPolicy`].
+    pub fn with_source(mut self, source: Source) -> Result<Self> {
+        self.policy.check(&source)?;
+        self.sources.push(source);
+        Ok(self)
+    }
+
+    /// Adds an email address to look up in Web Key Directories.
+    ///
+    /// See [`Source::Wkd`].
+    pub fn with_address<S: Into<String>>(mut self, address: S) -> Self {
+        self.addresses.push(address.into());
+        self
+    }
+
+    /// Controls whether Signer's User ID subpackets are used as hints.
+    ///
+    /// If enabled, the email addresses in the hashed Signer's User ID
+    /// subpackets of the signatures being verified are looked up in
+    /// [`Source::Wkd`].  This is disabled by default.
+    ///
+    /// Note: the hints are taken from signatures that have not been
+    /// verified yet, so they are controlled by whoever created the
+    /// message.  Enabling this lets an attacker choose which domains
+    /// are contacted, which may leak that a message is being
+    /// verified.  The certificates found this way are not trusted
+    /// any more than those from other sources: the signature must
+    /// still verify, and the check must still accept the signer.
+    pub fn with_signers_user_id_hints(mut self, enable: bool) -> Self {
+        self.signers_user_id_hints = enable;
+        self
+    }
+
+    /// Adds certificates to the cache.
+    ///
+    /// These certificates are used without consulting any source.
+    pub fn with_certs<I>(mut self, certs: I) -> Self
+        where I: IntoIterator<Item = Cert>,
+    {
+        certs.into_iter().for_each(|cert| self.insert(cert));
+        self
+    }
+
+    /// Replaces the check deciding whether the message is acceptable.
+    ///
+    /// See [`VerificationHelper::check`].  By default, the message is
+    /// accepted if it has at least one good signature.
+    pub fn with_check<F>(mut self, check: F) -> Self
+        where F: FnMut(MessageStructure) -> openpgp::Result<()>
+                 + Send + Sync + 'static,
+    {
+        self.check = Box::new(check);
+        self
+    }
+
+    /// Returns the certificates found so far.
+    pub fn certs(&self) -> impl Iterator<Item = &Cert> {
+        self.certs.values()
+    }
+
+    /// Looks up the certificate that issued `sig`.
+    ///
+    /// The certificates containing a key named by one of the
+    /// signature's Issuer Fingerprint or Issuer subpackets are looked
+    /// up as described in the [type-level documentation].  If enabled
+    /// using [`Lookup::with_signers_user_id_hints`], the email
+    /// address in the Signer's User ID subpacket, if any, is used as
+    /// a hint for [`Source::Wkd`].  This is useful to offer
+    /// fetching a missing key after a signature could not be
+    /// verified.
+    ///
+    /// Note: a Key ID may alias several keys, and the returned
+    /// certificates have not been authenticated.  The signature must
+    /// still be verified using the returned certificates.
+    ///
+    /// Returns an empty vector if the issuer could not be found, and
+    /// an error if the signature doesn't name its issuer.
+    ///
+    ///   [type-level documentation]: Lookup
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> sequoia_net::Result<()> {
+    /// # use sequoia_openpgp as openpgp;
+    /// # use openpgp::packet::Signature;
+    /// use sequoia_net::KeyServer;
+    /// use sequoia_net::lookup::{Lookup, NetworkPolicy, Source};
+    ///
+    /// # let sig: Signature = unimplemented!();
+    /// let mut lookup = Lookup::new(NetworkPolicy::Encrypted)
+    ///     .with_source(Source::Wkd)?
+    ///     .with_source(Source::KeyServer(KeyServer::default()))?;
+    ///
+    /// for cert in lookup.lookup_issuer(&sig)? {
+    ///     println!("{} might have issued the signature",
+    ///              cert.fingerprint());
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn lookup_issuer(&mut self, sig: &Signature) -> Result<Vec<Cert>> {
+        let issuers = sig.get_issuers();
+        if issuers.is_empty() {
+            return Err(openpgp::Error::InvalidArgument(
+                "Signature does not name its issuer".into()).into());
+        }
+
+        self.add_hints(sig);
+        self.lookup(&issuers)
+    }
+
+    /// Returns the key handles that could not be found.
+    ///
+    /// These handles are not looked up again.  See
+    /// [`Lookup::forget_misses`].
+    pub fn misses(&self) -> impl Iterator<Item = &KeyHandle> {
+        self.misses.iter()
+    }
+
+    /// Forgets which key handles could not be found.
+    ///
+    /// Subsequent lookups consult the sources again, e.g. after a
+    /// transient network error, or after the user asked to retry.
+    pub fn forget_misses(&mut self) {
+        self.misses.clear();
+    }
+
+    /// Records the hints in `sig` for looking up its issuer.
+    fn add_hints(&mut self, sig: &Signature) {
+        if ! self.signers_user_id_hints {
+            return;
+        }
+
+        if let Some(address) = sig.signers_user_id()
+            .and_then(|uid| UserID::from(uid).email2().ok().flatten()
+                      .map(String::from))
+        {
+            if ! self.addresses.contains(&address) {
+                self.addresses.push(address);
+            }
+        }
+    }
+
+    /// Inserts `cert` into the cache.
+    fn insert(&mut self, cert: Cert) {
+        let fp = cert.fingerprint();
+        let cert = match self.certs.remove(&fp) {
+            Some(existing) => match existing.clone().merge_public(cert) {
+                Ok(merged) => merged,
+                Err(_) => existing,
+            },
+            None => cert,
+        };
+        self.certs.insert(fp, cert);
+    }
+
+    /// Returns the cached certificates containing a key `handle`.
+    fn cached<'a>(&'a self, handle: &'a KeyHandle)
+                  -> impl Iterator<Item = &'a Cert> + 'a
+    {
+        self.certs.values().filter(move |cert| {
+            cert.keys().key_handle(handle.clone()).next().is_some()
+        })
+    }
+
+    /// Returns the certificates containing any of `ids`, looking
+    /// up those that are neither cached nor known to be missing.
+    fn lookup(&mut self, ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+        let mut missing: Vec<KeyHandle> = ids.iter()
+            .filter(|h| self.cached(h).next().is_none())
+            .filter(|h| ! self.misses.iter().any(|m| m.aliases(*h)))
+            .cloned()
+            .collect();
+
+        let mut found = Vec::new();
+        if ! missing.is_empty() && ! self.sources.is_empty() {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+
+            for source in &self.sources {
+                if missing.is_empty() {
+                    break;
+                }
+
+                match self.fetch(&rt, source, &missing) {
+                    Ok(certs) => {
+                        event!(DEBUG, %source, certs = certs.len(),
+                               "looked up certificates");
+                        missing.retain(|h| ! certs.iter().any(|cert| {
+                            cert.keys().key_handle(h.clone()).next().is_some()
+                        }));
+                        found.extend(certs);
+                    },
+                    Err(_error) => {
+                        event!(WARN, %source, error = %_error,
+                               "looking up certificates failed");
+                    },
+                }
+            }
+        }
+
+        found.into_iter().for_each(|cert| self.insert(cert));
+        self.misses.extend(missing);
+
+        let mut certs: Vec<&Cert> = Vec::new();
+        for id in ids {
+            for cert in self.cached(id) {
+                if ! certs.iter().any(|c| c.fingerprint() == cert.fingerprint())
+                {
+                    certs.push(cert);
+                }
+            }
+        }
+        Ok(certs.into_iter().cloned().collect())
+    }
+
+    /// Looks up the certificates containing any of `handles` in
+    /// `source`.
+    fn fetch(&self, rt: &tokio::runtime::Runtime, source: &Source,
+             handles: &[KeyHandle])
+             -> Result<Vec<Cert>>
+    {
+        let certs: Vec<Cert> = match source {
+            Source::Keyring(path) =>
+                CertParser::from_file(path)?
+                .filter_map(|cert| cert.ok())
+                .collect(),
+            Source::Wkd => rt.block_on(async {
+                let mut certs = Vec::new();
+                for address in &self.addresses {
+                    if let Ok(found) = wkd::get(&self.client, address).await {
+                        certs.extend(found.into_iter().filter_map(|r| r.ok()));
+                    }
+                }
+                certs
+            }),
+            Source::KeyServer(ks) => rt.block_on(
+                ks.get_many(handles.iter().cloned()))
+                .into_iter()
+                .filter_map(|r| r.ok())
+                .flatten()
+                .filter_map(|r| r.ok())
+                .collect(),
+        };
+
+        // Sources may return unrelated certificates, e.g. a WKD
+        // returns all certificates for an address.
+        Ok(certs.into_iter()
+           .filter(|cert| {
+               cert.keys().key_handles(handles.iter()).next().is_some()
+           })
+           .collect())
+    }
+}
+
+impl VerificationHelper for Lookup {
+    fn inspect(&mut self, pp: &PacketParser) -> openpgp::Result<()> {
+        if let Packet::Signature(sig) = &pp.packet {
+            self.add_hints(sig);
+        }
+        Ok(())
+    }
+
+    fn get_certs(&mut self, ids: &[KeyHandle]) -> openpgp::Result<Vec<Cert>> {
+        self.lookup(ids)
+    }
+
+    fn check(&mut self, structure: MessageStructure) -> openpgp::Result<()> {
+        (self.check)(structure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use openpgp::cert::prelude::*;
+    use openpgp::parse::stream::VerifierBuilder;
+    use openpgp::packet::signature::SignatureBuilder;
+    use openpgp::packet::signature::subpacket::SubpacketTag;
+    use openpgp::policy::StandardPolicy;
+    use openpgp::serialize::Serialize;
+    use openpgp::types::SignatureType;
+    use openpgp::serialize::stream::{LiteralWriter, Message, Signer};
+
+    #[test]
+    fn policy() -> Result<()> {
+        let hkp = Source::KeyServer(KeyServer::new("hkp://keys.openpgp.org")?);
+        let hkps =
+            Source::KeyServer(KeyServer::new("hkps://keys.openpgp.org")?);
+        let keyring = Source::Keyring("keyring.pgp".into());
+
+        use NetworkPolicy::*;
+        for (policy, source, ok) in [
+            (Offline, &keyring, true),
+            (Offline, &Source::Wkd, false),
+            (Offline, &hkps, false),
+            (Encrypted, &keyring, true),
+            (Encrypted, &Source::Wkd, true),
+            (Encrypted, &hkps, true),
+            (Encrypted, &hkp, false),
+            (Insecure, &Source::Wkd, true),
+            (Insecure, &hkp, true),
+        ] {
+            assert_eq!(policy.check(source).is_ok(), ok,
+                       "{:?} {}", policy, source);
+        }
+
+        assert!(Lookup::new(Encrypted).with_source(hkp).is_err());
+        Ok(())
+    }
+
+    fn sign(cert: &Cert) -> Result<Vec<u8>> {
+        let p = &StandardPolicy::new();
+        let signer = cert.keys().with_policy(p, None).for_signing()
+            .secret().next().unwrap().key().clone().into_keypair()?;
+
+        let mut sink = Vec::new();
+        let message = Message::new(&mut sink);
+        let message = Signer::new(message, signer).build()?;
+        let mut message = LiteralWriter::new(message).build()?;
+        message.write_all(b"Hello world.")?;
+        message.finalize()?;
+        Ok(sink)
+    }
+
+    #[test]
+    fn keyring() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (alice, _) =
+            CertBuilder::general_purpose(None, Some("alice@example.org"))
+            .generate()?;
+        let (bob, _) =
+            CertBuilder::general_purpose(None, Some("bob@example.org"))
+            .generate()?;
+
+        let mut keyring = tempfile::NamedTempFile::new()?;
+        alice.serialize(&mut keyring)?;
+        keyring.flush()?;
+
+        let helper = Lookup::new(NetworkPolicy::Offline)
+            .with_source(Source::Keyring(keyring.path().into()))?;
+
+        // Alice's certificate is found in the keyring.
+        let message = sign(&alice)?;
+        let v = VerifierBuilder::from_bytes(&message)?
+            .with_policy(p, None, helper)?;
+        assert!(v.message_processed());
+        let helper = v.into_helper();
+        assert_eq!(helper.certs().map(|c| c.fingerprint()).collect::<Vec<_>>(),
+                   vec![alice.fingerprint()]);
+        assert!(helper.misses.is_empty());
+
+        // Bob's is not, and the miss is remembered.
+        assert!(VerifierBuilder::from_bytes(&sign(&bob)?)?
+                .with_policy(p, None, helper).is_err());
+
+        // Once we know Bob's certificate, his signature verifies.
+        let helper = Lookup::new(NetworkPolicy::Offline)
+            .with_certs(Some(bob.clone()));
+        let message = sign(&bob)?;
+        let v = VerifierBuilder::from_bytes(&message)?
+            .with_policy(p, None, helper)?;
+        assert!(v.message_processed());
+        Ok(())
+    }
+
+    #[test]
+    fn misses() -> Result<()> {
+        let (bob, _) =
+            CertBuilder::general_purpose(None, Some("bob@example.org"))
+            .generate()?;
+        let keyring = tempfile::NamedTempFile::new()?;
+
+        let mut helper = Lookup::new(NetworkPolicy::Offline)
+            .with_source(Source::Keyring(keyring.path().into()))?;
+        let handle = bob.primary_key().key_handle();
+        assert!(helper.get_certs(std::slice::from_ref(&handle))?.is_empty());
+        assert_eq!(helper.misses.len(), 1);
+
+        // Asking again doesn't record the miss twice.
+        assert!(helper.get_certs(&[handle])?.is_empty());
+        assert_eq!(helper.misses.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_issuer() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (alice, _) =
+            CertBuilder::general_purpose(None, Some("alice@example.org"))
+            .generate()?;
+        let mut signer = alice.keys().with_policy(p, None).for_signing()
+            .secret().next().unwrap().key().clone().into_keypair()?;
+        let sig = SignatureBuilder::new(SignatureType::Binary)
+            .sign_message(&mut signer, b"Hello world.")?;
+
+        let mut keyring = tempfile::NamedTempFile::new()?;
+        let mut helper = Lookup::new(NetworkPolicy::Offline)
+            .with_source(Source::Keyring(keyring.path().into()))?;
+
+        // The keyring is empty, and the miss is remembered.
+        assert!(helper.lookup_issuer(&sig)?.is_empty());
+        assert_eq!(helper.misses().count(), sig.get_issuers().len());
+
+        // Even once Alice's certificate is in the keyring.
+        alice.serialize(&mut keyring)?;
+        keyring.flush()?;
+        assert!(helper.lookup_issuer(&sig)?.is_empty());
+
+        // Until we forget the misses.
+        helper.forget_misses();
+        let certs = helper.lookup_issuer(&sig)?;
+        assert_eq!(certs.len(), 1);
+        assert_eq!(certs[0].fingerprint(), alice.fingerprint());
+        assert_eq!(helper.misses().count(), 0);
+
+        // A signature without issuer is an error.
+        let mut sig = sig;
+        sig.unhashed_area_mut().clear();
+        sig.hashed_area_mut().remove_all(SubpacketTag::Issuer);
+        sig.hashed_area_mut().remove_all(SubpacketTag::IssuerFingerprint);
+        assert!(helper.lookup_issuer(&sig).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn signers_user_id_hints() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (alice, _) =
+            CertBuilder::general_purpose(None, Some("alice@example.org"))
+            .generate()?;
+        let mut signer = alice.keys().with_policy(p, None).for_signing()
+            .secret().next().unwrap().key().clone().into_keypair()?;
+        let sig = SignatureBuilder::new(SignatureType::Binary)
+            .set_signers_user_id("Alice <alice@example.org>")?
+            .sign_message(&mut signer, b"Hello world.")?;
+
+        // Hints are ignored by default.
+        let mut helper = Lookup::new(NetworkPolicy::Offline);
+        helper.add_hints(&sig);
+        assert!(helper.addresses.is_empty());
+
+        // Unless they are explicitly enabled.
+        let mut helper = Lookup::new(NetworkPolicy::Offline)
+            .with_signers_user_id_hints(true);
+        helper.add_hints(&sig);
+        helper.add_hints(&sig);
+        assert_eq!(helper.addresses, vec!["alice@example.org".to_string()]);
+        Ok(())
+    }
+}
@@ -71,6 +71,17 @@ macro_rules! assert_send_and_sync {
     };
 }
 
+// Emits a `tracing` event.
+//
+// This expands to nothing if the `tracing` feature is disabled.  The
+// fields are only evaluated if the event is enabled.
+macro_rules! event {
+    ( $level:ident, $($args:tt)* ) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($args)*);
+    };
+}
+
 pub(crate) trait Sendable : Send {}
 pub(crate) trait Syncable : Sync {}
 
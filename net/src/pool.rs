@@ -0,0 +1,187 @@
+//! Keyserver pools.
+//!
+//! Tracks the health of the servers in a [`KeyServer`] pool, and
+//! decides in which order they are tried.
+//!
+//!   [`KeyServer`]: crate::KeyServer
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::Url;
+
+use sequoia_openpgp as openpgp;
+
+use super::ServerSelection;
+
+/// How long a server is avoided after its first failure.
+///
+/// The time doubles with every consecutive failure up to
+/// [`MAX_BACKOFF`].
+const MIN_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The maximum time a failing server is avoided.
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// A server in a pool.
+#[derive(Debug)]
+pub(crate) struct Server {
+    /// The original URL.
+    pub(crate) url: Url,
+    /// The URL we use for the requests.
+    pub(crate) request_url: Url,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Health {
+    /// The number of consecutive failures.
+    failures: u32,
+    /// Avoid the server until then.
+    down_until: Option<Instant>,
+}
+
+/// A pool of servers.
+///
+/// Clones share the health information.
+#[derive(Debug, Clone)]
+pub(crate) struct Pool {
+    servers: Arc<Vec<Server>>,
+    health: Arc<Mutex<Vec<Health>>>,
+    selection: ServerSelection,
+}
+
+impl Pool {
+    /// Returns a new pool.
+    ///
+    /// `servers` must not be empty.
+    pub(crate) fn new(servers: Vec<Server>) -> Self {
+        assert!(! servers.is_empty());
+        let health = vec![Health::default(); servers.len()];
+        Pool {
+            servers: Arc::new(servers),
+            health: Arc::new(Mutex::new(health)),
+            selection: ServerSelection::Ordered,
+        }
+    }
+
+    /// Changes how servers are selected.
+    pub(crate) fn set_selection(&mut self, selection: ServerSelection) {
+        self.selection = selection;
+    }
+
+    /// Returns the servers.
+    pub(crate) fn servers(&self) -> &[Server] {
+        &self.servers
+    }
+
+    /// Returns the indices of the servers in the order they should
+    /// be tried.
+    ///
+    /// Servers that recently failed come last, the one that is
+    /// expected to recover first, first.
+    pub(crate) fn candidates(&self) -> Vec<usize> {
+        self.candidates_at(Instant::now())
+    }
+
+    fn candidates_at(&self, now: Instant) -> Vec<usize> {
+        let health = self.health.lock().unwrap();
+        let (mut up, mut down): (Vec<usize>, Vec<usize>) =
+            (0..health.len()).partition(
+                |&i| health[i].down_until.map(|t| t <= now).unwrap_or(true));
+
+        if self.selection == ServerSelection::Random {
+            // Fisher-Yates.
+            for i in (1..up.len()).rev() {
+                let mut r = [0; 4];
+                openpgp::crypto::random(&mut r);
+                let j = u32::from_be_bytes(r) as usize % (i + 1);
+                up.swap(i, j);
+            }
+        }
+
+        down.sort_by_key(|&i| health[i].down_until);
+        up.append(&mut down);
+        up
+    }
+
+    /// Records that the server `i` worked.
+    pub(crate) fn succeeded(&self, i: usize) {
+        self.health.lock().unwrap()[i] = Health::default();
+    }
+
+    /// Records that the server `i` failed.
+    pub(crate) fn failed(&self, i: usize) {
+        self.failed_at(i, Instant::now());
+    }
+
+    fn failed_at(&self, i: usize, now: Instant) {
+        let mut health = self.health.lock().unwrap();
+        let h = &mut health[i];
+        let backoff = MIN_BACKOFF
+            .checked_mul(1 << h.failures.min(16))
+            .unwrap_or(MAX_BACKOFF)
+            .min(MAX_BACKOFF);
+        h.failures = h.failures.saturating_add(1);
+        h.down_until = Some(now + backoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(n: usize) -> Pool {
+        Pool::new((0..n).map(|i| {
+            let url: Url = format!("hkps://keys{}.example.org", i)
+                .parse().unwrap();
+            Server {
+                url: url.clone(),
+                request_url: url,
+            }
+        }).collect())
+    }
+
+    #[test]
+    fn failover() {
+        let p = pool(3);
+        let t0 = Instant::now();
+        assert_eq!(p.candidates_at(t0), vec![0, 1, 2]);
+
+        // Failing servers are tried last.
+        p.failed_at(0, t0);
+        assert_eq!(p.candidates_at(t0), vec![1, 2, 0]);
+        p.failed_at(1, t0 + Duration::from_secs(1));
+        assert_eq!(p.candidates_at(t0), vec![2, 0, 1]);
+
+        // Clones share the health information.
+        let p2 = p.clone();
+        p2.failed_at(2, t0 + Duration::from_secs(2));
+        assert_eq!(p.candidates_at(t0), vec![0, 1, 2]);
+
+        // Until they recover.
+        let t1 = t0 + MIN_BACKOFF + Duration::from_secs(1);
+        assert_eq!(p.candidates_at(t1), vec![0, 1, 2]);
+
+        // Consecutive failures back off exponentially.
+        p.failed_at(0, t1);
+        let t2 = t1 + MIN_BACKOFF + Duration::from_secs(1);
+        assert_eq!(p.candidates_at(t2), vec![1, 2, 0]);
+
+        // A success resets the server's health.
+        p.succeeded(0);
+        assert_eq!(p.candidates_at(t2), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn random() {
+        let mut p = pool(8);
+        p.set_selection(ServerSelection::Random);
+        let mut c = p.candidates();
+        c.sort();
+        assert_eq!(c, (0..8).collect::<Vec<_>>());
+
+        // Failing servers are still tried last.
+        p.failed(3);
+        assert_eq!(p.candidates().last(), Some(&3));
+    }
+}
@@ -0,0 +1,234 @@
+//! Rate limiting for keyserver requests.
+//!
+//! Public keyservers are a shared resource.  Batch operations, like
+//! refreshing all certificates in a store, can easily issue thousands
+//! of requests in a short time, and many keyservers throttle or block
+//! clients that do so.
+//!
+//! A [`RateLimiter`] spaces out requests using a token bucket per
+//! host.  It can be shared between [`KeyServer`]s by cloning it: all
+//! clones use the same buckets.
+//!
+//!   [`KeyServer`]: crate::KeyServer
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use sequoia_openpgp::KeyHandle;
+//! # use sequoia_net::{KeyServer, Result, ratelimit::RateLimiter};
+//! # async fn f(handles: Vec<KeyHandle>) -> Result<()> {
+//! // Allow bursts of up to 5 requests, then one request per second.
+//! let limiter = RateLimiter::new(5, Duration::from_secs(1));
+//! let ks = KeyServer::default()
+//!     .with_rate_limiter(limiter.clone());
+//! for h in handles {
+//!     let _ = ks.get(h).await;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+/// A token bucket.
+#[derive(Debug)]
+struct Bucket {
+    /// The number of available requests.
+    ///
+    /// This is negative if requests are waiting for tokens.
+    tokens: f64,
+
+    /// When `tokens` was last updated.
+    updated: Instant,
+
+    /// The server asked us not to send requests before this time.
+    not_before: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    burst: u32,
+    interval: Duration,
+    buckets: HashMap<String, Bucket>,
+}
+
+/// Limits the rate of requests per host.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    inner: Arc<Mutex<Inner>>,
+}
+
+assert_send_and_sync!(RateLimiter);
+
+impl RateLimiter {
+    /// Returns a new rate limiter.
+    ///
+    /// For each host, the limiter allows bursts of up to `burst`
+    /// requests, and then one request every `interval`.  A `burst`
+    /// of zero is treated like one.
+    pub fn new(burst: u32, interval: Duration) -> Self {
+        RateLimiter {
+            inner: Arc::new(Mutex::new(Inner {
+                burst: burst.max(1),
+                interval,
+                buckets: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Waits until a request may be sent to `host`.
+    pub async fn acquire(&self, host: &str) {
+        let delay = self.reserve(host, Instant::now());
+        if ! delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Asks requests to `host` to wait for `delay`.
+    ///
+    /// This is used to honor a server's request to back off, for
+    /// instance via a `Retry-After` header.  Subsequent calls to
+    /// [`RateLimiter::acquire`] for `host` wait at least until the
+    /// delay has passed.
+    pub fn pause(&self, host: &str, delay: Duration) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        let bucket = inner.bucket(host, now);
+        let until = now + delay;
+        bucket.not_before =
+            Some(bucket.not_before.map_or(until, |t| t.max(until)));
+    }
+
+    /// Reserves a request to `host`, returning how long the caller
+    /// has to wait before sending it.
+    fn reserve(&self, host: &str, now: Instant) -> Duration {
+        let mut inner = self.inner.lock().unwrap();
+        let interval = inner.interval;
+        let bucket = inner.bucket(host, now);
+
+        bucket.tokens -= 1.;
+        let mut delay = if bucket.tokens < 0. {
+            interval.mul_f64(-bucket.tokens)
+        } else {
+            Duration::ZERO
+        };
+        if let Some(not_before) = bucket.not_before {
+            delay = delay.max(not_before.saturating_duration_since(now));
+        }
+        delay
+    }
+}
+
+impl Inner {
+    /// Returns `host`'s bucket, replenished as of `now`.
+    fn bucket(&mut self, host: &str, now: Instant) -> &mut Bucket {
+        let burst = self.burst as f64;
+        let interval = self.interval;
+        let bucket = self.buckets.entry(host.into())
+            .or_insert_with(|| Bucket {
+                tokens: burst,
+                updated: now,
+                not_before: None,
+            });
+
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        if interval.is_zero() {
+            bucket.tokens = burst;
+        } else {
+            bucket.tokens = (bucket.tokens
+                             + elapsed.as_secs_f64() / interval.as_secs_f64())
+                .min(burst);
+        }
+        bucket.updated = now;
+        bucket
+    }
+}
+
+/// Returns how long the server asked us to wait, if at all.
+///
+/// The `Retry-After` header is either a number of seconds, or an
+/// HTTP date (see [Section 10.2.3 of RFC 9110]).
+///
+///   [Section 10.2.3 of RFC 9110]: https://www.rfc-editor.org/rfc/rfc9110#section-10.2.3
+pub(crate) fn retry_after(headers: &HeaderMap, now: SystemTime)
+                          -> Option<Duration>
+{
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        Some(Duration::from_secs(seconds))
+    } else {
+        let date = httpdate::parse_http_date(value).ok()?;
+        Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn token_bucket() {
+        let l = RateLimiter::new(2, Duration::from_secs(1));
+        let t0 = Instant::now();
+
+        // The burst is free.
+        assert_eq!(l.reserve("a", t0), Duration::ZERO);
+        assert_eq!(l.reserve("a", t0), Duration::ZERO);
+        // Then, requests are queued.
+        assert_eq!(l.reserve("a", t0), Duration::from_secs(1));
+        assert_eq!(l.reserve("a", t0), Duration::from_secs(2));
+        // Other hosts are not affected.
+        assert_eq!(l.reserve("b", t0), Duration::ZERO);
+
+        // Tokens are replenished, but not beyond the burst.
+        let t1 = t0 + Duration::from_secs(10);
+        assert_eq!(l.reserve("a", t1), Duration::ZERO);
+        assert_eq!(l.reserve("a", t1), Duration::ZERO);
+        assert_eq!(l.reserve("a", t1), Duration::from_secs(1));
+
+        // Clones share the buckets.
+        let l2 = l.clone();
+        assert_eq!(l2.reserve("a", t1), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn pause() {
+        let l = RateLimiter::new(10, Duration::from_secs(1));
+        l.pause("a", Duration::from_secs(60));
+        let d = l.reserve("a", Instant::now());
+        assert!(d > Duration::from_secs(50) && d <= Duration::from_secs(60));
+        assert_eq!(l.reserve("b", Instant::now()), Duration::ZERO);
+    }
+
+    #[test]
+    fn retry_after_header() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap();
+        let mut h = HeaderMap::new();
+        assert_eq!(retry_after(&h, now), None);
+
+        h.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&h, now), Some(Duration::from_secs(120)));
+
+        h.insert(RETRY_AFTER,
+                 HeaderValue::from_static("Wed, 21 Oct 2015 07:30:00 GMT"));
+        assert_eq!(retry_after(&h, now), Some(Duration::from_secs(120)));
+
+        // Dates in the past mean now.
+        h.insert(RETRY_AFTER,
+                 HeaderValue::from_static("Wed, 21 Oct 2015 07:00:00 GMT"));
+        assert_eq!(retry_after(&h, now), Some(Duration::ZERO));
+
+        h.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&h, now), None);
+    }
+}
@@ -0,0 +1,474 @@
+//! Set reconciliation with SKS and Hockeypuck keyservers.
+//!
+//! The SKS keyserver network synchronizes the peers' certificate
+//! databases using a set reconciliation protocol: each peer
+//! organizes the digests of its certificates in a [`PrefixTree`],
+//! and two peers find the digests in which their sets differ by
+//! exchanging summaries of the tree's nodes, descending only into
+//! nodes that differ.  The amount of data exchanged is proportional
+//! to the number of differences, not to the size of the databases.
+//! See Minsky, Trachtenberg, and Zippel: "Set Reconciliation with
+//! Nearly Optimal Communication Complexity".
+//!
+//! This module implements the initiating side of the protocol as
+//! spoken by [SKS] and [Hockeypuck].  This allows mirroring a
+//! keyserver incrementally: [`reconcile`] returns the digests of
+//! the certificates that the peer has and we don't, which can then
+//! be fetched using the peer's HKP interface (`op=hget`).  Note that
+//! the peer may also learn which certificates we have that it is
+//! missing, and may ask for them in turn.
+//!
+//! The elements of the set are the MD5 digests SKS computes over a
+//! certificate's canonicalized packets.  Computing these digests is
+//! outside the scope of this module.
+//!
+//! This module is only available if the `recon` feature is enabled.
+//!
+//!   [SKS]: https://github.com/SKS-Keyserver/sks-keyserver
+//!   [Hockeypuck]: https://github.com/hockeypuck/hockeypuck
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use sequoia_net::recon::{self, PrefixTree, Settings};
+//! # fn f(digests: Vec<[u8; 16]>) -> sequoia_net::Result<()> {
+//!
+//! let mut tree = PrefixTree::new();
+//! for d in &digests {
+//!     tree.insert(d);
+//! }
+//!
+//! let stream = TcpStream::connect(("keyserver.example.org",
+//!                                  recon::DEFAULT_PORT))?;
+//! let missing = recon::reconcile(stream, &tree, &Settings::default())?;
+//! println!("We are missing {} certificates", missing.len());
+//! # Ok(()) }
+//! ```
+
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+
+use crate::Result;
+
+mod message;
+mod poly;
+mod tree;
+mod zp;
+
+pub use tree::PrefixTree;
+
+use message::Message;
+use poly::LowMBar;
+use zp::Zp;
+
+/// The port reconciliation servers usually listen on.
+pub const DEFAULT_PORT: u16 = 11370;
+
+/// The number of bits of an element that select a node's child.
+///
+/// Both peers have to agree on this, and all known implementations
+/// use 2.
+const BIT_QUANTUM: usize = 2;
+
+/// The maximum number of differences that can be recovered from a
+/// node's summary.
+///
+/// Both peers have to agree on this, and all known implementations
+/// use 5.
+const MBAR: usize = 5;
+
+/// The protocol version we announce.
+const VERSION: &str = "1.1.6";
+
+/// The filters SKS applies to certificates before computing their
+/// digests.
+const DEFAULT_FILTERS: &str = "yminsky.dedup,yminsky.merge";
+
+/// Errors returned by the reconciliation routines.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The peer sent a malformed message.
+    #[error("Malformed message: {0}")]
+    MalformedMessage(String),
+    /// The peer sent a message that is not valid at this point.
+    #[error("Unexpected message: {0}")]
+    UnexpectedMessage(String),
+    /// The peers' configurations are incompatible.
+    #[error("Incompatible configuration: {0}")]
+    Incompatible(String),
+    /// The peer reported an error.
+    #[error("Peer reported an error: {0}")]
+    Remote(String),
+}
+
+/// Settings for reconciliation.
+///
+/// The settings are announced to the peer, which may reject them.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    http_port: u16,
+    filters: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            http_port: 11371,
+            filters: DEFAULT_FILTERS.into(),
+        }
+    }
+}
+
+impl Settings {
+    /// Sets the port of our HKP interface.
+    ///
+    /// The peer may use it to fetch the certificates it is missing.
+    /// The default is 11371.
+    pub fn http_port(mut self, port: u16) -> Self {
+        self.http_port = port;
+        self
+    }
+
+    /// Sets the filters applied to certificates before computing
+    /// their digests.
+    ///
+    /// Both peers have to use the same filters.  The default is
+    /// `yminsky.dedup,yminsky.merge`, which is what SKS and
+    /// Hockeypuck use.
+    pub fn filters<S: Into<String>>(mut self, filters: S) -> Self {
+        self.filters = filters.into();
+        self
+    }
+
+    fn to_message(&self) -> Message {
+        Message::Config(vec![
+            ("version".into(), VERSION.into()),
+            ("http port".into(), self.http_port.to_string()),
+            ("bitquantum".into(), BIT_QUANTUM.to_string()),
+            ("mbar".into(), MBAR.to_string()),
+            ("filters".into(), self.filters.clone()),
+        ])
+    }
+
+    /// Checks that the peer's configuration is compatible.
+    fn check(&self, remote: &[(String, String)])
+             -> std::result::Result<(), String>
+    {
+        let get = |key: &str| remote.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str());
+
+        let version = get("version").unwrap_or("");
+        if ! version.starts_with("1.") {
+            return Err(format!("unsupported version {:?}", version));
+        }
+        if get("bitquantum") != Some(BIT_QUANTUM.to_string().as_str()) {
+            return Err("mismatched bitquantum".into());
+        }
+        if get("mbar") != Some(MBAR.to_string().as_str()) {
+            return Err("mismatched mbar".into());
+        }
+        if get("filters") != Some(self.filters.as_str()) {
+            return Err("mismatched filters".into());
+        }
+        Ok(())
+    }
+}
+
+/// The response to a successful configuration exchange.
+const CONFIG_PASSED: &str = "passed";
+
+/// The response to a failed configuration exchange.
+const CONFIG_FAILED: &str = "failed";
+
+/// Reconciles `tree` with the peer at the other end of `stream`.
+///
+/// Returns the digests of the elements that the peer has, but that
+/// are not in `tree`.  The peer drives the reconciliation, and
+/// `stream` should have a read timeout set to guard against
+/// unresponsive peers.
+///
+/// See the [module-level documentation](self) for an example.
+pub fn reconcile<S>(mut stream: S, tree: &PrefixTree, settings: &Settings)
+                    -> Result<Vec<[u8; 16]>>
+where
+    S: Read + Write,
+{
+    handshake(&mut stream, settings)?;
+
+    let mut missing = BTreeSet::new();
+    let mut pending: Vec<Message> = Vec::new();
+    loop {
+        match Message::read(&mut stream)? {
+            Message::ReconRqstPoly { prefix, size, samples } =>
+                pending.push(respond_poly(tree, &prefix, size, &samples)?),
+            Message::ReconRqstFull { prefix, elements } => {
+                let local = tree.elements(&prefix).into_iter()
+                    .copied()
+                    .collect::<BTreeSet<_>>();
+                let remote = elements.into_iter().collect::<BTreeSet<_>>();
+                missing.extend(remote.difference(&local).copied());
+                pending.push(Message::Elements(
+                    local.difference(&remote).copied().collect()));
+            },
+            Message::Elements(elements) => missing.extend(elements),
+            Message::Flush => {
+                let mut buf = Vec::new();
+                for m in pending.drain(..) {
+                    m.write(&mut buf)?;
+                }
+                stream.write_all(&buf)?;
+                stream.flush()?;
+            },
+            Message::Done => break,
+            Message::Error(e) => return Err(Error::Remote(e).into()),
+            m => return Err(Error::UnexpectedMessage(
+                format!("{:?}", m)).into()),
+        }
+    }
+
+    Ok(missing.into_iter()
+       .map(|z| {
+           let mut d = [0; 16];
+           d.copy_from_slice(&z.to_le_bytes()[..16]);
+           d
+       })
+       .collect())
+}
+
+/// Exchanges and checks the peers' configurations.
+fn handshake<S>(stream: &mut S, settings: &Settings) -> Result<()>
+where
+    S: Read + Write,
+{
+    settings.to_message().write(stream)?;
+    stream.flush()?;
+
+    let remote = match Message::read(stream)? {
+        Message::Config(c) => c,
+        m => return Err(Error::UnexpectedMessage(
+            format!("expected configuration, got {:?}", m)).into()),
+    };
+
+    let mut buf = Vec::new();
+    let check = settings.check(&remote);
+    match &check {
+        Ok(()) => message::write_string(&mut buf, CONFIG_PASSED)?,
+        Err(reason) => {
+            message::write_string(&mut buf, CONFIG_FAILED)?;
+            message::write_string(&mut buf, reason)?;
+        },
+    }
+    stream.write_all(&buf)?;
+    stream.flush()?;
+    check.map_err(Error::Incompatible)?;
+
+    match message::read_string(stream)?.as_str() {
+        CONFIG_PASSED => Ok(()),
+        _ => Err(Error::Incompatible(
+            format!("rejected by peer: {}", message::read_string(stream)?))
+                 .into()),
+    }
+}
+
+/// Computes the response to a request for the differences of a
+/// node.
+fn respond_poly(tree: &PrefixTree, prefix: &tree::Prefix,
+                remote_size: usize, remote_samples: &[Zp])
+                -> Result<Message>
+{
+    let summary = tree.summary(prefix);
+    if remote_samples.len() != summary.svalues.len() {
+        return Err(Error::MalformedMessage(
+            format!("expected {} samples, got {}",
+                    summary.svalues.len(), remote_samples.len())).into());
+    }
+
+    let values = summary.svalues.iter().zip(remote_samples.iter())
+        .map(|(l, r)| r.inv().map(|r| l.mul(&r)))
+        .collect::<Option<Vec<_>>>();
+    let diff = values.ok_or(LowMBar).and_then(|values| {
+        poly::reconcile(&values, tree.points(),
+                        summary.size as i64 - remote_size as i64)
+    });
+
+    Ok(match diff {
+        Ok((local, _remote)) => Message::Elements(local),
+        Err(LowMBar) => match summary.elements {
+            Some(elements) =>
+                Message::FullElements(elements.into_iter().copied().collect()),
+            None if summary.size < tree::SPLIT_THRESHOLD =>
+                Message::FullElements(tree.elements(prefix).into_iter()
+                                      .copied().collect()),
+            None => Message::SyncFail,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+
+    use tree::Prefix;
+
+    /// A stream that replays the peer's messages and records ours.
+    struct Script {
+        input: VecDeque<u8>,
+        output: Vec<u8>,
+    }
+
+    impl Script {
+        fn new(messages: &[Message], config: &str) -> Self {
+            let mut input = Vec::new();
+            Settings::default().to_message().write(&mut input).unwrap();
+            message::write_string(&mut input, config).unwrap();
+            if config == CONFIG_FAILED {
+                message::write_string(&mut input, "no").unwrap();
+            }
+            for m in messages {
+                m.write(&mut input).unwrap();
+            }
+            Script { input: input.into(), output: Vec::new() }
+        }
+
+        /// Returns the messages we sent after the handshake.
+        fn sent(&self) -> Vec<Message> {
+            let mut r = &self.output[..];
+            assert!(matches!(Message::read(&mut r).unwrap(),
+                             Message::Config(_)));
+            assert_eq!(message::read_string(&mut r).unwrap(), CONFIG_PASSED);
+            let mut messages = Vec::new();
+            while ! r.is_empty() {
+                messages.push(Message::read(&mut r).unwrap());
+            }
+            messages
+        }
+    }
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn digest(i: u32) -> [u8; 16] {
+        let mut d = [0; 16];
+        d[..4].copy_from_slice(&i.wrapping_mul(2654435761).to_be_bytes());
+        d[4..8].copy_from_slice(&i.to_be_bytes());
+        d[15] = 0x55;
+        d
+    }
+
+    fn tree(range: std::ops::Range<u32>) -> PrefixTree {
+        let mut t = PrefixTree::new();
+        for i in range {
+            t.insert(&digest(i));
+        }
+        t
+    }
+
+    #[test]
+    fn handshake() -> Result<()> {
+        let mut s = Script::new(&[Message::Done], CONFIG_PASSED);
+        assert!(reconcile(&mut s, &PrefixTree::new(), &Settings::default())?
+                .is_empty());
+        assert!(s.sent().is_empty());
+
+        // The peer rejects us.
+        let mut s = Script::new(&[], CONFIG_FAILED);
+        assert!(reconcile(&mut s, &PrefixTree::new(), &Settings::default())
+                .is_err());
+
+        // We reject the peer.
+        let mut s = Script::new(&[], CONFIG_PASSED);
+        assert!(reconcile(&mut s, &PrefixTree::new(),
+                          &Settings::default().filters("none"))
+                .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn poly() -> Result<()> {
+        // The peer has 0..100, we have 2..103.
+        let local = tree(2..103);
+        let remote = tree(0..100);
+        let root = Prefix::default();
+        let s = remote.summary(&root);
+
+        let mut script = Script::new(&[
+            Message::ReconRqstPoly {
+                prefix: root.clone(),
+                size: s.size,
+                samples: s.svalues.clone(),
+            },
+            Message::Flush,
+            Message::Elements(vec![Zp::from_le_bytes(&digest(0)),
+                                   Zp::from_le_bytes(&digest(1))]),
+            Message::Done,
+        ], CONFIG_PASSED);
+        let missing = reconcile(&mut script, &local, &Settings::default())?;
+
+        let mut expected = vec![digest(0), digest(1)];
+        expected.sort();
+        let mut missing = missing;
+        missing.sort();
+        assert_eq!(missing, expected);
+
+        let mut ours = (100..103).map(|i| Zp::from_le_bytes(&digest(i)))
+            .collect::<Vec<_>>();
+        ours.sort();
+        assert_eq!(script.sent(), vec![Message::Elements(ours)]);
+        Ok(())
+    }
+
+    #[test]
+    fn too_many_differences() -> Result<()> {
+        let local = tree(0..30);
+        let remote = tree(100..130);
+        let root = Prefix::default();
+        let s = remote.summary(&root);
+
+        let mut script = Script::new(&[
+            Message::ReconRqstPoly {
+                prefix: root.clone(),
+                size: s.size,
+                samples: s.svalues.clone(),
+            },
+            Message::ReconRqstFull {
+                prefix: root,
+                elements: remote.elements(&Prefix::default()).into_iter()
+                    .copied().collect(),
+            },
+            Message::Flush,
+            Message::Done,
+        ], CONFIG_PASSED);
+        let missing = reconcile(&mut script, &local, &Settings::default())?;
+        assert_eq!(missing.len(), 30);
+        assert!(missing.iter().all(|d| remote.contains(d)));
+
+        let sent = script.sent();
+        assert_eq!(sent.len(), 2);
+        match &sent[0] {
+            Message::FullElements(e) => assert_eq!(e.len(), 30),
+            m => panic!("unexpected {:?}", m),
+        }
+        match &sent[1] {
+            Message::Elements(e) => assert_eq!(e.len(), 30),
+            m => panic!("unexpected {:?}", m),
+        }
+        Ok(())
+    }
+}
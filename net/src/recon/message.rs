@@ -0,0 +1,282 @@
+//! The messages exchanged during reconciliation.
+
+use std::io::{self, Read, Write};
+
+use crate::Result;
+use super::Error;
+use super::tree::Prefix;
+use super::zp::{self, Zp};
+
+/// The maximum size of a message we accept.
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// A reconciliation message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Message {
+    /// Asks for the differences of the node with the given prefix,
+    /// given the sender's size and sample values.
+    ReconRqstPoly {
+        prefix: Prefix,
+        size: usize,
+        samples: Vec<Zp>,
+    },
+    /// Asks for the differences of the leaf with the given prefix,
+    /// given the sender's elements.
+    ReconRqstFull {
+        prefix: Prefix,
+        elements: Vec<Zp>,
+    },
+    /// Elements the receiver is missing.
+    Elements(Vec<Zp>),
+    /// All elements of a node, sent if the differences could not be
+    /// computed.
+    FullElements(Vec<Zp>),
+    /// The differences of a node could not be computed.
+    SyncFail,
+    /// The reconciliation is complete.
+    Done,
+    /// Asks the receiver to send its pending responses.
+    Flush,
+    /// An error message.
+    Error(String),
+    /// A database request.  Not used in reconciliation.
+    DbRqst(String),
+    /// A database reply.  Not used in reconciliation.
+    DbRepl(String),
+    /// The peer's configuration.
+    Config(Vec<(String, String)>),
+}
+
+impl Message {
+    fn tag(&self) -> u8 {
+        use Message::*;
+        match self {
+            ReconRqstPoly { .. } => 0,
+            ReconRqstFull { .. } => 1,
+            Elements(_) => 2,
+            FullElements(_) => 3,
+            SyncFail => 4,
+            Done => 5,
+            Flush => 6,
+            Message::Error(_) => 7,
+            DbRqst(_) => 8,
+            DbRepl(_) => 9,
+            Config(_) => 10,
+        }
+    }
+
+    /// Writes the message.
+    pub(crate) fn write(&self, sink: &mut dyn Write) -> Result<()> {
+        use Message::*;
+        let mut body = vec![self.tag()];
+        match self {
+            ReconRqstPoly { prefix, size, samples } => {
+                write_prefix(&mut body, prefix)?;
+                write_int(&mut body, *size)?;
+                write_zps(&mut body, samples)?;
+            },
+            ReconRqstFull { prefix, elements } => {
+                write_prefix(&mut body, prefix)?;
+                write_zps(&mut body, elements)?;
+            },
+            Elements(elements) | FullElements(elements) =>
+                write_zps(&mut body, elements)?,
+            SyncFail | Done | Flush => (),
+            Message::Error(s) | DbRqst(s) | DbRepl(s) =>
+                write_string(&mut body, s)?,
+            Config(entries) => {
+                write_int(&mut body, entries.len())?;
+                for (k, v) in entries {
+                    write_string(&mut body, k)?;
+                    write_string(&mut body, v)?;
+                }
+            },
+        }
+
+        write_int(sink, body.len())?;
+        sink.write_all(&body)?;
+        Ok(())
+    }
+
+    /// Reads a message.
+    pub(crate) fn read(source: &mut dyn Read) -> Result<Message> {
+        let len = read_int(source)?;
+        if len == 0 || len > MAX_MESSAGE_SIZE {
+            return Err(Error::MalformedMessage(
+                format!("invalid message length {}", len)).into());
+        }
+        let mut body = vec![0; len];
+        source.read_exact(&mut body)?;
+
+        let mut r = &body[1..];
+        let r = &mut r;
+        let m = match body[0] {
+            0 => Message::ReconRqstPoly {
+                prefix: read_prefix(r)?,
+                size: read_int(r)?,
+                samples: read_zps(r)?,
+            },
+            1 => Message::ReconRqstFull {
+                prefix: read_prefix(r)?,
+                elements: read_zps(r)?,
+            },
+            2 => Message::Elements(read_zps(r)?),
+            3 => Message::FullElements(read_zps(r)?),
+            4 => Message::SyncFail,
+            5 => Message::Done,
+            6 => Message::Flush,
+            7 => Message::Error(read_string(r)?),
+            8 => Message::DbRqst(read_string(r)?),
+            9 => Message::DbRepl(read_string(r)?),
+            10 => {
+                let n = read_int(r)?;
+                let mut entries = Vec::new();
+                for _ in 0..n {
+                    entries.push((read_string(r)?, read_string(r)?));
+                }
+                Message::Config(entries)
+            },
+            t => return Err(Error::MalformedMessage(
+                format!("unknown message type {}", t)).into()),
+        };
+
+        if ! r.is_empty() {
+            return Err(Error::MalformedMessage(
+                format!("{} bytes of trailing data", r.len())).into());
+        }
+        Ok(m)
+    }
+}
+
+/// Writes an unsigned 32 bit big endian integer.
+pub(crate) fn write_int(sink: &mut dyn Write, v: usize) -> Result<()> {
+    let v = u32::try_from(v).map_err(
+        |_| Error::MalformedMessage(format!("{} is out of range", v)))?;
+    sink.write_all(&v.to_be_bytes())?;
+    Ok(())
+}
+
+/// Reads an unsigned 32 bit big endian integer.
+pub(crate) fn read_int(source: &mut dyn Read) -> Result<usize> {
+    let mut b = [0; 4];
+    source.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b) as usize)
+}
+
+/// Writes a length-prefixed string.
+pub(crate) fn write_string(sink: &mut dyn Write, s: &str) -> Result<()> {
+    write_int(sink, s.len())?;
+    sink.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+/// Reads a length-prefixed string.
+pub(crate) fn read_string(source: &mut dyn Read) -> Result<String> {
+    let len = read_int(source)?;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(Error::MalformedMessage(
+            format!("invalid string length {}", len)).into());
+    }
+    let mut b = vec![0; len];
+    source.read_exact(&mut b)?;
+    String::from_utf8(b).map_err(
+        |_| Error::MalformedMessage("string is not UTF-8".into()).into())
+}
+
+fn write_prefix(sink: &mut dyn Write, p: &Prefix) -> Result<()> {
+    write_int(sink, p.len())?;
+    write_int(sink, p.as_bytes().len())?;
+    sink.write_all(p.as_bytes())?;
+    Ok(())
+}
+
+fn read_prefix(source: &mut &[u8]) -> Result<Prefix> {
+    let bits = read_int(source)?;
+    let len = read_int(source)?;
+    if len > source.len() {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    let (bytes, rest) = source.split_at(len);
+    *source = rest;
+    Prefix::from_bytes(bits, bytes.to_vec()).ok_or_else(
+        || Error::MalformedMessage("truncated bit string".into()).into())
+}
+
+fn write_zps(sink: &mut dyn Write, zs: &[Zp]) -> Result<()> {
+    write_int(sink, zs.len())?;
+    for z in zs {
+        sink.write_all(&z.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_zps(source: &mut &[u8]) -> Result<Vec<Zp>> {
+    let n = read_int(source)?;
+    if n.saturating_mul(zp::ENCODED_SIZE) > source.len() {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    let (elements, rest) = source.split_at(n * zp::ENCODED_SIZE);
+    *source = rest;
+    Ok(elements.chunks(zp::ENCODED_SIZE).map(Zp::from_le_bytes).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let prefix = Prefix::default().child(3).child(0).child(2).child(1)
+            .child(1);
+        let zs = (0..5).map(|i| Zp::from_i64(i * 1000 - 2000))
+            .collect::<Vec<_>>();
+        let messages = vec![
+            Message::ReconRqstPoly {
+                prefix: prefix.clone(),
+                size: 1234,
+                samples: zs.clone(),
+            },
+            Message::ReconRqstFull {
+                prefix: Prefix::default(),
+                elements: zs.clone(),
+            },
+            Message::Elements(zs.clone()),
+            Message::FullElements(Vec::new()),
+            Message::SyncFail,
+            Message::Done,
+            Message::Flush,
+            Message::Error("oops".into()),
+            Message::DbRqst("".into()),
+            Message::DbRepl("ok".into()),
+            Message::Config(vec![("version".into(), "1.1.6".into()),
+                                 ("mbar".into(), "5".into())]),
+        ];
+
+        let mut buf = Vec::new();
+        for m in &messages {
+            m.write(&mut buf)?;
+        }
+
+        let mut r = &buf[..];
+        for m in &messages {
+            assert_eq!(&Message::read(&mut r)?, m);
+        }
+        assert!(r.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn framing() -> Result<()> {
+        let mut buf = Vec::new();
+        Message::Done.write(&mut buf)?;
+        assert_eq!(buf, [0, 0, 0, 1, 5]);
+
+        // Trailing data, unknown types, and truncated messages are
+        // rejected.
+        assert!(Message::read(&mut &[0, 0, 0, 2, 5, 0][..]).is_err());
+        assert!(Message::read(&mut &[0, 0, 0, 1, 42][..]).is_err());
+        assert!(Message::read(&mut &[0, 0, 0, 5, 2, 0, 0, 0, 1][..]).is_err());
+        assert!(Message::read(&mut &[0, 0, 0, 0][..]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+//! Polynomials over Z/pZ, rational function interpolation, and root
+//! finding.
+//!
+//! This is the mathematical core of the reconciliation protocol, see
+//! Minsky, Trachtenberg, and Zippel: "Set Reconciliation with Nearly
+//! Optimal Communication Complexity".
+
+use sequoia_openpgp as openpgp;
+
+use super::zp::{self, Zp};
+
+/// A polynomial.
+///
+/// The coefficients are stored starting with the constant term.
+/// There are no leading zero coefficients.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Poly(Vec<Zp>);
+
+impl Poly {
+    /// Returns a polynomial with the given coefficients.
+    pub(crate) fn new(mut coeffs: Vec<Zp>) -> Self {
+        while coeffs.last().map(|c| c.is_zero()).unwrap_or(false) {
+            coeffs.pop();
+        }
+        Poly(coeffs)
+    }
+
+    /// Returns the polynomial `x`.
+    fn x() -> Self {
+        Poly(vec![Zp::zero(), Zp::one()])
+    }
+
+    /// Returns whether this is the zero polynomial.
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the degree.
+    ///
+    /// The zero polynomial has degree zero.
+    pub(crate) fn degree(&self) -> usize {
+        self.0.len().saturating_sub(1)
+    }
+
+    /// Returns the leading coefficient.
+    fn lead(&self) -> Zp {
+        self.0.last().copied().unwrap_or_else(Zp::zero)
+    }
+
+    /// Evaluates the polynomial at `x`.
+    pub(crate) fn eval(&self, x: &Zp) -> Zp {
+        self.0.iter().rev().fold(Zp::zero(), |acc, c| acc.mul(x).add(c))
+    }
+
+    fn sub(&self, other: &Poly) -> Poly {
+        let n = self.0.len().max(other.0.len());
+        Poly::new((0..n).map(|i| {
+            let a = self.0.get(i).copied().unwrap_or_else(Zp::zero);
+            let b = other.0.get(i).copied().unwrap_or_else(Zp::zero);
+            a.sub(&b)
+        }).collect())
+    }
+
+    fn mul(&self, other: &Poly) -> Poly {
+        if self.is_zero() || other.is_zero() {
+            return Poly(Vec::new());
+        }
+        let mut r = vec![Zp::zero(); self.0.len() + other.0.len() - 1];
+        for (i, a) in self.0.iter().enumerate() {
+            for (j, b) in other.0.iter().enumerate() {
+                r[i + j] = r[i + j].add(&a.mul(b));
+            }
+        }
+        Poly::new(r)
+    }
+
+    /// Divides by `d`, returning the quotient and the remainder.
+    ///
+    /// `d` must not be zero.
+    fn divrem(&self, d: &Poly) -> (Poly, Poly) {
+        let inv = d.lead().inv().expect("division by zero");
+        let mut r = self.0.clone();
+        if r.len() < d.0.len() {
+            return (Poly(Vec::new()), self.clone());
+        }
+        let mut q = vec![Zp::zero(); r.len() - d.0.len() + 1];
+        for i in (0..q.len()).rev() {
+            let c = r[i + d.0.len() - 1].mul(&inv);
+            q[i] = c;
+            for (j, dc) in d.0.iter().enumerate() {
+                r[i + j] = r[i + j].sub(&c.mul(dc));
+            }
+        }
+        (Poly::new(q), Poly::new(r))
+    }
+
+    fn rem(&self, d: &Poly) -> Poly {
+        self.divrem(d).1
+    }
+
+    /// Returns the polynomial scaled to be monic.
+    fn monic(&self) -> Poly {
+        match self.lead().inv() {
+            Some(inv) => Poly(self.0.iter().map(|c| c.mul(&inv)).collect()),
+            None => self.clone(),
+        }
+    }
+
+    /// Returns the monic greatest common divisor.
+    fn gcd(&self, other: &Poly) -> Poly {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        while ! b.is_zero() {
+            let r = a.rem(&b);
+            a = b;
+            b = r;
+        }
+        a.monic()
+    }
+
+    /// Computes `self^e mod m`, `e` given as little-endian limbs.
+    fn powmod(&self, e: &[u64], m: &Poly) -> Poly {
+        let mut r = Poly(vec![Zp::one()]).rem(m);
+        let base = self.rem(m);
+        for i in (0..e.len() * 64).rev() {
+            r = r.mul(&r).rem(m);
+            if e[i / 64] & (1 << (i % 64)) != 0 {
+                r = r.mul(&base).rem(m);
+            }
+        }
+        r
+    }
+
+    /// Returns the roots of a monic polynomial.
+    ///
+    /// Returns `None` if the polynomial is not a product of distinct
+    /// linear factors.
+    pub(crate) fn roots(&self) -> Option<Vec<Zp>> {
+        if self.is_zero() {
+            return None;
+        }
+        if self.degree() == 0 {
+            return Some(Vec::new());
+        }
+
+        // The polynomial splits into distinct linear factors iff it
+        // divides x^p - x.
+        let x = Poly::x();
+        if x.powmod(&zp::modulus(), self) != x.rem(self) {
+            return None;
+        }
+
+        let mut roots = Vec::with_capacity(self.degree());
+        self.split(&mut roots);
+        roots.sort();
+        Some(roots)
+    }
+
+    /// Finds the roots of a product of distinct linear factors using
+    /// the Cantor-Zassenhaus algorithm.
+    fn split(&self, roots: &mut Vec<Zp>) {
+        match self.degree() {
+            0 => (),
+            1 => roots.push(self.0[0].neg()),
+            _ => loop {
+                // gcd(f, (x + a)^((p-1)/2) - 1) is a non-trivial
+                // factor with probability about 1/2.
+                let mut r = [0; 16];
+                openpgp::crypto::random(&mut r);
+                let a = Poly(vec![Zp::from_le_bytes(&r), Zp::one()]);
+                let h = a.powmod(&zp::half_order(), self)
+                    .sub(&Poly(vec![Zp::one()]));
+                let g = self.gcd(&h);
+                if g.degree() > 0 && g.degree() < self.degree() {
+                    g.split(roots);
+                    self.divrem(&g).0.split(roots);
+                    return;
+                }
+            },
+        }
+    }
+}
+
+/// Reconciliation failed, because the sets differ in too many
+/// elements.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct LowMBar;
+
+/// Computes the set differences from sampled values of the
+/// characteristic polynomials.
+///
+/// `values[i]` is the local characteristic polynomial divided by the
+/// remote one, evaluated at `points[i]`.  `size_diff` is the number
+/// of local elements minus the number of remote elements.  Returns
+/// the elements only present locally and the ones only present
+/// remotely.
+pub(crate) fn reconcile(values: &[Zp], points: &[Zp], size_diff: i64)
+                        -> Result<(Vec<Zp>, Vec<Zp>), LowMBar>
+{
+    assert_eq!(values.len(), points.len());
+    if points.len() < 2 {
+        return Err(LowMBar);
+    }
+
+    // The last point is used to check the result.
+    let mut m = points.len() as i64 - 1;
+    if size_diff.abs() > m {
+        return Err(LowMBar);
+    }
+    if (m + size_diff) % 2 != 0 {
+        m -= 1;
+    }
+    let ma = ((m + size_diff) / 2) as usize;
+    let mb = ((m - size_diff) / 2) as usize;
+
+    let (a, b) = interpolate(&values[..m as usize], &points[..m as usize],
+                             ma, mb).ok_or(LowMBar)?;
+
+    // Check the last point.
+    let k = points.last().unwrap();
+    let f = values.last().unwrap();
+    if a.eval(k) != f.mul(&b.eval(k)) {
+        return Err(LowMBar);
+    }
+
+    let g = a.gcd(&b);
+    let a = a.divrem(&g).0;
+    let b = b.divrem(&g).0;
+
+    Ok((a.roots().ok_or(LowMBar)?, b.roots().ok_or(LowMBar)?))
+}
+
+/// Finds monic polynomials `A` of degree `ma` and `B` of degree `mb`
+/// so that `A(points[i]) = values[i] * B(points[i])`.
+fn interpolate(values: &[Zp], points: &[Zp], ma: usize, mb: usize)
+               -> Option<(Poly, Poly)>
+{
+    // The unknowns are the non-leading coefficients of A and B:
+    //
+    //   sum a_j k^j - f sum b_j k^j = f k^mb - k^ma
+    let n = ma + mb;
+    debug_assert_eq!(values.len(), n);
+    let mut rows: Vec<Vec<Zp>> = values.iter().zip(points.iter())
+        .map(|(f, k)| {
+            let mut row = Vec::with_capacity(n + 1);
+            let mut kj = Zp::one();
+            for _ in 0..ma {
+                row.push(kj);
+                kj = kj.mul(k);
+            }
+            let k_ma = kj;
+            let mut kj = Zp::one();
+            for _ in 0..mb {
+                row.push(f.mul(&kj).neg());
+                kj = kj.mul(k);
+            }
+            row.push(f.mul(&kj).sub(&k_ma));
+            row
+        })
+        .collect();
+
+    // Gaussian elimination.  If the system is underdetermined,
+    // because the sets differ in fewer elements, free variables are
+    // set to zero.  Any solution has the same reduced fraction.
+    let mut pivots = Vec::new();
+    let mut r = 0;
+    for c in 0..n {
+        let p = match (r..rows.len()).find(|&i| ! rows[i][c].is_zero()) {
+            Some(p) => p,
+            None => continue,
+        };
+        rows.swap(r, p);
+        let inv = rows[r][c].inv().unwrap();
+        for v in rows[r].iter_mut() {
+            *v = v.mul(&inv);
+        }
+        let pivot = rows[r].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i != r && ! row[c].is_zero() {
+                let factor = row[c];
+                for (v, p) in row.iter_mut().zip(pivot.iter()) {
+                    *v = v.sub(&factor.mul(p));
+                }
+            }
+        }
+        pivots.push(c);
+        r += 1;
+    }
+
+    // Check for inconsistencies.
+    if rows[r..].iter().any(|row| ! row[n].is_zero()) {
+        return None;
+    }
+
+    let mut x = vec![Zp::zero(); n];
+    for (i, &c) in pivots.iter().enumerate() {
+        x[c] = rows[i][n];
+    }
+
+    let mut a = x[..ma].to_vec();
+    a.push(Zp::one());
+    let mut b = x[ma..].to_vec();
+    b.push(Zp::one());
+    Some((Poly::new(a), Poly::new(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evaluates the characteristic polynomial of `set` at `k`.
+    fn char_poly(set: &[Zp], k: &Zp) -> Zp {
+        set.iter().fold(Zp::one(), |acc, x| acc.mul(&k.sub(x)))
+    }
+
+    fn points(n: i64) -> Vec<Zp> {
+        (0..n).map(|i| Zp::from_i64(if i % 2 == 0 {
+            (i + 1) / 2
+        } else {
+            -((i + 1) / 2)
+        })).collect()
+    }
+
+    fn check(common: &[i64], local: &[i64], remote: &[i64], n: i64)
+             -> Result<(Vec<Zp>, Vec<Zp>), LowMBar>
+    {
+        let z = |s: &[i64]| s.iter().map(|&v| Zp::from_i64(v))
+            .collect::<Vec<_>>();
+        let mut l = z(common);
+        l.extend(z(local));
+        let mut r = z(common);
+        r.extend(z(remote));
+
+        let points = points(n);
+        let values = points.iter().map(|k| {
+            char_poly(&l, k).mul(&char_poly(&r, k).inv().unwrap())
+        }).collect::<Vec<_>>();
+        reconcile(&values, &points, l.len() as i64 - r.len() as i64)
+    }
+
+    #[test]
+    fn roots() {
+        let set = [3, 1000, -17, 123456789].iter()
+            .map(|&v| Zp::from_i64(v))
+            .collect::<Vec<_>>();
+        let p = set.iter().fold(Poly(vec![Zp::one()]), |acc, x| {
+            acc.mul(&Poly(vec![x.neg(), Zp::one()]))
+        });
+        let mut expected = set.clone();
+        expected.sort();
+        assert_eq!(p.roots(), Some(expected));
+
+        // x^2 + 1 has no roots, because p = 3 mod 4.
+        assert_eq!(Poly(vec![Zp::one(), Zp::zero(), Zp::one()]).roots(),
+                   None);
+        // Repeated roots are rejected.
+        let q = Poly(vec![Zp::from_i64(-1), Zp::one()]);
+        assert_eq!(q.mul(&q).roots(), None);
+    }
+
+    #[test]
+    fn reconcile_sets() {
+        let z = |s: &[i64]| {
+            let mut v = s.iter().map(|&v| Zp::from_i64(v))
+                .collect::<Vec<_>>();
+            v.sort();
+            v
+        };
+        let common = [100, 200, 300, 400];
+
+        assert_eq!(check(&common, &[], &[], 6), Ok((vec![], vec![])));
+        assert_eq!(check(&common, &[7], &[], 6), Ok((z(&[7]), vec![])));
+        assert_eq!(check(&common, &[], &[9, 10], 6), Ok((vec![], z(&[9, 10]))));
+        assert_eq!(check(&common, &[7, 8], &[9, 10, 11], 6),
+                   Ok((z(&[7, 8]), z(&[9, 10, 11]))));
+        assert_eq!(check(&[], &[7, 8, 12], &[9, 10], 6),
+                   Ok((z(&[7, 8, 12]), z(&[9, 10]))));
+
+        // Too many differences.
+        assert_eq!(check(&common, &[7, 8, 12], &[9, 10, 11], 6),
+                   Err(LowMBar));
+        assert_eq!(check(&common, &(1..=20).collect::<Vec<_>>(), &[], 6),
+                   Err(LowMBar));
+    }
+}
@@ -0,0 +1,491 @@
+//! The prefix tree.
+
+use super::zp::Zp;
+use super::{BIT_QUANTUM, MBAR};
+
+/// The number of points the characteristic polynomials are sampled
+/// at.
+pub(crate) const NUM_SAMPLES: usize = MBAR + 1;
+
+/// A leaf is split once it holds more elements than this.
+pub(crate) const SPLIT_THRESHOLD: usize = 50;
+
+/// An interior node is turned into a leaf once it holds fewer
+/// elements than this.
+const JOIN_THRESHOLD: usize = SPLIT_THRESHOLD / 2;
+
+/// The depth of the tree is bounded by the number of bits of the
+/// elements.
+const MAX_DEPTH: usize = 128 / BIT_QUANTUM;
+
+/// A prefix of an element's bit string.
+///
+/// Bits are numbered as in [`Zp::bit`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Prefix {
+    bits: usize,
+    bytes: Vec<u8>,
+}
+
+impl Prefix {
+    /// Returns a prefix from its encoding.
+    ///
+    /// Returns `None` if `bytes` is too short.
+    pub(crate) fn from_bytes(bits: usize, mut bytes: Vec<u8>) -> Option<Self> {
+        let partial = bits % 8;
+        let len = bits / 8 + usize::from(partial > 0);
+        if bytes.len() < len {
+            return None;
+        }
+        bytes.truncate(len);
+        // Clear the unused bits.
+        if partial > 0 {
+            bytes[len - 1] &= 0xff << (8 - partial);
+        }
+        Some(Prefix { bits, bytes })
+    }
+
+    /// Returns the number of bits.
+    pub(crate) fn len(&self) -> usize {
+        self.bits
+    }
+
+    /// Returns the encoded bits.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the `i`th bit.
+    pub(crate) fn bit(&self, i: usize) -> bool {
+        self.bytes[i / 8] & (0x80 >> (i % 8)) != 0
+    }
+
+    /// Returns the prefix extended by the child index `child`.
+    #[cfg(test)]
+    pub(crate) fn child(&self, child: usize) -> Prefix {
+        let mut p = self.clone();
+        for i in 0..BIT_QUANTUM {
+            if p.bytes.len() * 8 == p.bits {
+                p.bytes.push(0);
+            }
+            if child & (1 << (BIT_QUANTUM - 1 - i)) != 0 {
+                p.bytes[p.bits / 8] |= 0x80 >> (p.bits % 8);
+            }
+            p.bits += 1;
+        }
+        p
+    }
+
+    /// Returns the child index of the node at `depth` on the way to
+    /// this prefix.
+    fn index(&self, depth: usize) -> usize {
+        (0..BIT_QUANTUM).fold(0, |acc, i| {
+            (acc << 1) | self.bit(depth * BIT_QUANTUM + i) as usize
+        })
+    }
+
+    /// Returns whether `z` starts with this prefix.
+    pub(crate) fn matches(&self, z: &Zp) -> bool {
+        (0..self.bits).all(|i| self.bit(i) == z.bit(i))
+    }
+}
+
+/// Returns the index of the child at `depth` containing `z`.
+fn child_index(z: &Zp, depth: usize) -> usize {
+    (0..BIT_QUANTUM).fold(0, |acc, i| {
+        (acc << 1) | z.bit(depth * BIT_QUANTUM + i) as usize
+    })
+}
+
+/// The summary of a node.
+#[derive(Debug)]
+pub(crate) struct Summary<'a> {
+    /// The characteristic polynomial of the node's elements sampled
+    /// at the tree's points.
+    pub(crate) svalues: Vec<Zp>,
+    /// The number of elements.
+    pub(crate) size: usize,
+    /// The node's elements, if it is a leaf.
+    pub(crate) elements: Option<Vec<&'a Zp>>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    svalues: Vec<Zp>,
+    size: usize,
+    /// Either the children, or the elements of a leaf.
+    children: Vec<Node>,
+    elements: Vec<Zp>,
+}
+
+impl Node {
+    fn new() -> Self {
+        Node {
+            svalues: vec![Zp::one(); NUM_SAMPLES],
+            size: 0,
+            children: Vec::new(),
+            elements: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Collects all elements below this node.
+    fn collect<'a>(&'a self, acc: &mut Vec<&'a Zp>) {
+        if self.is_leaf() {
+            acc.extend(self.elements.iter());
+        } else {
+            for c in &self.children {
+                c.collect(acc);
+            }
+        }
+    }
+}
+
+/// A set of key hashes organized for reconciliation.
+///
+/// Elements are the MD5 digests that SKS and Hockeypuck use to
+/// identify certificates.  Each node of the tree summarizes the
+/// elements below it by sampling their characteristic polynomial at
+/// a few fixed points, which allows two peers to find the elements
+/// in which their sets differ without exchanging the whole set.
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_net::recon::PrefixTree;
+///
+/// let mut tree = PrefixTree::new();
+/// tree.insert(&[0x42; 16]);
+/// assert!(tree.contains(&[0x42; 16]));
+/// assert_eq!(tree.len(), 1);
+/// tree.remove(&[0x42; 16]);
+/// assert!(tree.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrefixTree {
+    points: Vec<Zp>,
+    root: Node,
+}
+
+impl Default for PrefixTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrefixTree {
+    /// Returns an empty tree.
+    pub fn new() -> Self {
+        PrefixTree {
+            points: sample_points(),
+            root: Node::new(),
+        }
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.root.size
+    }
+
+    /// Returns whether the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether the tree contains the given digest.
+    pub fn contains(&self, digest: &[u8; 16]) -> bool {
+        self.contains_zp(&Zp::from_le_bytes(digest))
+    }
+
+    pub(crate) fn contains_zp(&self, z: &Zp) -> bool {
+        let mut node = &self.root;
+        let mut depth = 0;
+        while ! node.is_leaf() {
+            node = &node.children[child_index(z, depth)];
+            depth += 1;
+        }
+        node.elements.contains(z)
+    }
+
+    /// Inserts the given digest.
+    ///
+    /// Returns whether the digest was newly inserted.
+    pub fn insert(&mut self, digest: &[u8; 16]) -> bool {
+        self.insert_zp(Zp::from_le_bytes(digest))
+    }
+
+    pub(crate) fn insert_zp(&mut self, z: Zp) -> bool {
+        if self.contains_zp(&z) {
+            return false;
+        }
+
+        let factors = self.factors(&z);
+        let mut node = &mut self.root;
+        let mut depth = 0;
+        loop {
+            for (s, f) in node.svalues.iter_mut().zip(factors.iter()) {
+                *s = s.mul(f);
+            }
+            node.size += 1;
+            if node.is_leaf() {
+                break;
+            }
+            node = &mut node.children[child_index(&z, depth)];
+            depth += 1;
+        }
+
+        node.elements.push(z);
+        if node.elements.len() > SPLIT_THRESHOLD && depth < MAX_DEPTH {
+            Self::split(&self.points, node, depth);
+        }
+        true
+    }
+
+    /// Removes the given digest.
+    ///
+    /// Returns whether the digest was present.
+    pub fn remove(&mut self, digest: &[u8; 16]) -> bool {
+        self.remove_zp(&Zp::from_le_bytes(digest))
+    }
+
+    pub(crate) fn remove_zp(&mut self, z: &Zp) -> bool {
+        if ! self.contains_zp(z) {
+            return false;
+        }
+
+        // The factors are never zero, see `PrefixTree::factors`.
+        let factors = self.factors(z).iter()
+            .map(|f| f.inv().expect("element is not a sample point"))
+            .collect::<Vec<_>>();
+        let mut node = &mut self.root;
+        let mut depth = 0;
+        loop {
+            for (s, f) in node.svalues.iter_mut().zip(factors.iter()) {
+                *s = s.mul(f);
+            }
+            node.size -= 1;
+            if node.is_leaf() {
+                node.elements.retain(|e| e != z);
+                break;
+            }
+            if node.size < JOIN_THRESHOLD {
+                // Turn the node into a leaf.
+                let mut elements = Vec::with_capacity(node.size + 1);
+                node.collect(&mut elements);
+                node.elements = elements.into_iter()
+                    .filter(|e| *e != z)
+                    .copied()
+                    .collect();
+                node.children.clear();
+                break;
+            }
+            node = &mut node.children[child_index(z, depth)];
+            depth += 1;
+        }
+        true
+    }
+
+    /// Returns the factors contributed by `z` to the sample values,
+    /// i.e., the points minus `z`.
+    ///
+    /// If `z` happens to be a sample point, the respective factor
+    /// would be zero.  Since the elements are digests, this doesn't
+    /// happen in practice, but we make sure the tree stays usable.
+    fn factors(&self, z: &Zp) -> Vec<Zp> {
+        self.points.iter().map(|k| {
+            let f = k.sub(z);
+            if f.is_zero() {
+                Zp::one()
+            } else {
+                f
+            }
+        }).collect()
+    }
+
+    /// Splits the leaf `node`.
+    fn split(points: &[Zp], node: &mut Node, depth: usize) {
+        let mut children = vec![Node::new(); 1 << BIT_QUANTUM];
+        for z in node.elements.drain(..) {
+            let child = &mut children[child_index(&z, depth)];
+            for (s, k) in child.svalues.iter_mut().zip(points.iter()) {
+                let f = k.sub(&z);
+                if ! f.is_zero() {
+                    *s = s.mul(&f);
+                }
+            }
+            child.size += 1;
+            child.elements.push(z);
+        }
+        for child in children.iter_mut() {
+            if child.elements.len() > SPLIT_THRESHOLD && depth + 1 < MAX_DEPTH {
+                Self::split(points, child, depth + 1);
+            }
+        }
+        node.children = children;
+    }
+
+    /// Returns the sample points.
+    pub(crate) fn points(&self) -> &[Zp] {
+        &self.points
+    }
+
+    /// Returns the summary of the node with the given prefix.
+    ///
+    /// If the tree is not deep enough, the summary is computed from
+    /// the elements of the leaf on the prefix's path that match the
+    /// prefix.
+    pub(crate) fn summary(&self, prefix: &Prefix) -> Summary<'_> {
+        let (node, depth) = self.descend(prefix);
+        if depth * BIT_QUANTUM == prefix.len() {
+            let elements = if node.is_leaf() {
+                Some(node.elements.iter().collect())
+            } else {
+                None
+            };
+            return Summary {
+                svalues: node.svalues.clone(),
+                size: node.size,
+                elements,
+            };
+        }
+
+        // The prefix is not aligned to a node.
+        let mut elements = Vec::new();
+        node.collect(&mut elements);
+        elements.retain(|z| prefix.matches(z));
+        let mut svalues = vec![Zp::one(); self.points.len()];
+        for z in &elements {
+            for (s, f) in svalues.iter_mut().zip(self.factors(z)) {
+                *s = s.mul(&f);
+            }
+        }
+        Summary {
+            svalues,
+            size: elements.len(),
+            elements: Some(elements),
+        }
+    }
+
+    /// Returns all elements with the given prefix.
+    pub(crate) fn elements(&self, prefix: &Prefix) -> Vec<&Zp> {
+        let mut elements = Vec::new();
+        self.descend(prefix).0.collect(&mut elements);
+        elements.retain(|z| prefix.matches(z));
+        elements
+    }
+
+    /// Returns the deepest node whose elements all start with
+    /// `prefix`, or the leaf on the prefix's path, and its depth.
+    fn descend(&self, prefix: &Prefix) -> (&Node, usize) {
+        let mut node = &self.root;
+        let mut depth = 0;
+        while ! node.is_leaf() && (depth + 1) * BIT_QUANTUM <= prefix.len() {
+            node = &node.children[prefix.index(depth)];
+            depth += 1;
+        }
+        (node, depth)
+    }
+}
+
+/// Returns the points the characteristic polynomials are sampled at:
+/// 0, -1, 1, -2, 2, ...
+fn sample_points() -> Vec<Zp> {
+    (0..NUM_SAMPLES as i64).map(|i| {
+        let v = (i + 1) / 2;
+        Zp::from_i64(if i % 2 == 0 { v } else { -v })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(i: u32) -> [u8; 16] {
+        let mut d = [0; 16];
+        // Spread the values over the tree.
+        let x = i.wrapping_mul(2654435761);
+        d[..4].copy_from_slice(&x.to_be_bytes());
+        d[4..8].copy_from_slice(&i.to_be_bytes());
+        d[15] = 0x55;
+        d
+    }
+
+    /// Recomputes the node's sample values from scratch.
+    fn check_node(points: &[Zp], node: &Node) {
+        let mut elements = Vec::new();
+        node.collect(&mut elements);
+        assert_eq!(elements.len(), node.size);
+        let svalues = points.iter().map(|k| {
+            elements.iter().fold(Zp::one(), |acc, z| acc.mul(&k.sub(z)))
+        }).collect::<Vec<_>>();
+        assert_eq!(svalues, node.svalues);
+        for c in &node.children {
+            check_node(points, c);
+        }
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut t = PrefixTree::new();
+        for i in 0..500 {
+            assert!(t.insert(&digest(i)));
+        }
+        assert!(! t.insert(&digest(7)));
+        assert_eq!(t.len(), 500);
+        assert!(! t.root.is_leaf());
+        check_node(&t.points, &t.root);
+
+        for i in 0..500 {
+            assert!(t.contains(&digest(i)));
+        }
+        assert!(! t.contains(&digest(500)));
+
+        for i in 0..480 {
+            assert!(t.remove(&digest(i)));
+        }
+        assert!(! t.remove(&digest(0)));
+        assert_eq!(t.len(), 20);
+        assert!(t.root.is_leaf());
+        check_node(&t.points, &t.root);
+    }
+
+    #[test]
+    fn summaries() {
+        let mut t = PrefixTree::new();
+        for i in 0..200 {
+            t.insert(&digest(i));
+        }
+
+        let root = t.summary(&Prefix::default());
+        assert_eq!(root.size, 200);
+        assert!(root.elements.is_none());
+
+        // The children partition the set.
+        let children = (0..1 << BIT_QUANTUM)
+            .map(|c| t.summary(&Prefix::default().child(c)))
+            .collect::<Vec<_>>();
+        assert_eq!(children.iter().map(|s| s.size).sum::<usize>(), 200);
+
+        // Summaries of prefixes that are deeper than the tree are
+        // computed on the fly.
+        let deep = Prefix::default().child(1).child(2).child(3).child(0);
+        let s = t.summary(&deep);
+        assert_eq!(s.size, t.elements(&deep).len());
+        assert!(s.elements.unwrap().iter().all(|z| deep.matches(z)));
+    }
+
+    #[test]
+    fn prefix() {
+        let p = Prefix::default().child(2).child(1).child(3).child(0).child(1);
+        assert_eq!(p.len(), 10);
+        assert_eq!(p.as_bytes(), &[0b1001_1100, 0b0100_0000]);
+        assert_eq!(Prefix::from_bytes(10, vec![0b1001_1100, 0b0111_1111]),
+                   Some(p.clone()));
+        assert_eq!(Prefix::from_bytes(10, vec![0]), None);
+        assert_eq!(p.index(0), 2);
+        assert_eq!(p.index(4), 1);
+    }
+}
@@ -0,0 +1,282 @@
+//! Arithmetic in the prime field used by the reconciliation protocol.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// The modulus.
+///
+/// This is the 129 bit prime used by SKS and Hockeypuck,
+/// 530512889551602322505127520352579437339.
+const P: [u64; 3] = [0xc91f85d930a5431b, 0x8f1d10e4878b1fdf, 0x1];
+
+/// The number of bits of the modulus.
+const P_BITS: usize = 129;
+
+/// The constant for Barrett reduction, floor(2^258 / p).
+const MU: [u64; 3] = [0x583fbb007657a2b8, 0x90d0cb647588caed, 0x2];
+
+/// The size of an encoded element.
+pub(crate) const ENCODED_SIZE: usize = 17;
+
+/// An element of Z/pZ.
+///
+/// The value is always reduced, i.e., smaller than the modulus.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Zp([u64; 3]);
+
+impl fmt::Debug for Zp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Zp({:x}{:016x}{:016x})", self.0[2], self.0[1], self.0[0])
+    }
+}
+
+impl Zp {
+    /// Returns zero.
+    pub(crate) const fn zero() -> Self {
+        Zp([0; 3])
+    }
+
+    /// Returns one.
+    pub(crate) const fn one() -> Self {
+        Zp([1, 0, 0])
+    }
+
+    /// Returns whether this is zero.
+    pub(crate) fn is_zero(&self) -> bool {
+        self.0 == [0; 3]
+    }
+
+    /// Converts a small integer.
+    pub(crate) fn from_i64(v: i64) -> Self {
+        let z = Zp([v.unsigned_abs(), 0, 0]);
+        if v < 0 {
+            z.neg()
+        } else {
+            z
+        }
+    }
+
+    /// Converts a little-endian number, reducing it.
+    pub(crate) fn from_le_bytes(bytes: &[u8]) -> Self {
+        // Horner's method, from the most significant byte.
+        let b256 = Zp::from_i64(256);
+        bytes.iter().rev().fold(Zp::zero(), |acc, &b| {
+            acc.mul(&b256).add(&Zp([b as u64, 0, 0]))
+        })
+    }
+
+    /// Returns the little-endian encoding.
+    pub(crate) fn to_le_bytes(self) -> [u8; ENCODED_SIZE] {
+        let mut r = [0; ENCODED_SIZE];
+        for (i, b) in r.iter_mut().enumerate() {
+            *b = (self.0[i / 8] >> (8 * (i % 8))) as u8;
+        }
+        r
+    }
+
+    /// Returns the `i`th bit of the encoding.
+    ///
+    /// Bits are numbered from the first byte of the little-endian
+    /// encoding, most significant bit first.
+    pub(crate) fn bit(&self, i: usize) -> bool {
+        let byte = (self.0[i / 64] >> (8 * ((i / 8) % 8))) as u8;
+        byte & (0x80 >> (i % 8)) != 0
+    }
+
+    pub(crate) fn add(&self, other: &Zp) -> Zp {
+        let mut r = [0; 3];
+        let mut carry = false;
+        for (r, (a, b)) in r.iter_mut().zip(self.0.iter().zip(other.0.iter())) {
+            let (s, c1) = a.overflowing_add(*b);
+            let (s, c2) = s.overflowing_add(carry as u64);
+            *r = s;
+            carry = c1 || c2;
+        }
+        // Both operands are smaller than 2^129, so there is no carry
+        // out of the last limb.
+        debug_assert!(! carry);
+        if cmp(&r, &P) != Ordering::Less {
+            sub_assign(&mut r, &P);
+        }
+        Zp(r)
+    }
+
+    pub(crate) fn neg(&self) -> Zp {
+        if self.is_zero() {
+            *self
+        } else {
+            let mut r = P;
+            sub_assign(&mut r, &self.0);
+            Zp(r)
+        }
+    }
+
+    pub(crate) fn sub(&self, other: &Zp) -> Zp {
+        self.add(&other.neg())
+    }
+
+    pub(crate) fn mul(&self, other: &Zp) -> Zp {
+        // Barrett reduction, see Handbook of Applied Cryptography,
+        // algorithm 14.42, with base 2 and k = 129.
+        let x = mul_wide(&self.0, &other.0);
+        let q = shr(&mul_wide(&shr(&x, P_BITS - 1)[..3], &MU), P_BITS + 1);
+        let mut r = x;
+        sub_assign(&mut r, &mul_wide(&q[..3], &P));
+        while cmp(&r, &P) != Ordering::Less {
+            sub_assign(&mut r, &P);
+        }
+        Zp([r[0], r[1], r[2]])
+    }
+
+    /// Raises this to the power `e`, given as little-endian limbs.
+    pub(crate) fn pow(&self, e: &[u64]) -> Zp {
+        let mut r = Zp::one();
+        for i in (0..e.len() * 64).rev() {
+            r = r.mul(&r);
+            if e[i / 64] & (1 << (i % 64)) != 0 {
+                r = r.mul(self);
+            }
+        }
+        r
+    }
+
+    /// Returns the multiplicative inverse.
+    ///
+    /// Returns `None` for zero.
+    pub(crate) fn inv(&self) -> Option<Zp> {
+        if self.is_zero() {
+            return None;
+        }
+        // Fermat's little theorem: a^(p-2) = a^-1.
+        let mut e = P;
+        sub_assign(&mut e, &[2]);
+        Some(self.pow(&e))
+    }
+}
+
+/// Returns the modulus.
+pub(crate) fn modulus() -> [u64; 3] {
+    P
+}
+
+/// Returns (p - 1) / 2.
+pub(crate) fn half_order() -> [u64; 3] {
+    let mut e = P;
+    sub_assign(&mut e, &[1]);
+    [(e[0] >> 1) | (e[1] << 63), (e[1] >> 1) | (e[2] << 63), e[2] >> 1]
+}
+
+/// Compares two little-endian numbers.
+///
+/// Missing limbs are treated as zero.
+fn cmp(a: &[u64], b: &[u64]) -> Ordering {
+    let n = a.len().max(b.len());
+    for i in (0..n).rev() {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        match x.cmp(&y) {
+            Ordering::Equal => (),
+            o => return o,
+        }
+    }
+    Ordering::Equal
+}
+
+/// Subtracts `b` from `a`.
+///
+/// `a` must not be smaller than `b`.
+fn sub_assign(a: &mut [u64], b: &[u64]) {
+    let mut borrow = false;
+    for (i, a) in a.iter_mut().enumerate() {
+        let (d, b1) = a.overflowing_sub(b.get(i).copied().unwrap_or(0));
+        let (d, b2) = d.overflowing_sub(borrow as u64);
+        *a = d;
+        borrow = b1 || b2;
+    }
+    debug_assert!(! borrow);
+}
+
+/// Multiplies two little-endian numbers.
+fn mul_wide(a: &[u64], b: &[u64]) -> [u64; 8] {
+    let mut r = [0; 8];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u128;
+        for (j, y) in b.iter().enumerate() {
+            let v = r[i + j] as u128 + *x as u128 * *y as u128 + carry;
+            r[i + j] = v as u64;
+            carry = v >> 64;
+        }
+        r[i + b.len()] = carry as u64;
+    }
+    r
+}
+
+/// Shifts `a` right by `shift` bits.
+fn shr(a: &[u64; 8], shift: usize) -> [u64; 8] {
+    let mut r = [0; 8];
+    let (limbs, bits) = (shift / 64, shift % 64);
+    for i in 0..8 - limbs {
+        r[i] = a[i + limbs] >> bits;
+        if bits > 0 && i + limbs + 1 < 8 {
+            r[i] |= a[i + limbs + 1] << (64 - bits);
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        let a = Zp::from_i64(-5);
+        let b = Zp::from_i64(7);
+        assert_eq!(a.add(&b), Zp::from_i64(2));
+        assert_eq!(a.sub(&b), Zp::from_i64(-12));
+        assert_eq!(a.mul(&b), Zp::from_i64(-35));
+        assert_eq!(a.mul(&a), Zp::from_i64(25));
+
+        // p - 1 is -1.
+        let mut m1 = P;
+        sub_assign(&mut m1, &[1]);
+        assert_eq!(Zp(m1), Zp::from_i64(-1));
+        assert_eq!(Zp(m1).mul(&Zp(m1)), Zp::one());
+
+        let a = Zp::from_le_bytes(&(1..18).collect::<Vec<u8>>());
+        let b = Zp::from_le_bytes(&(200..217).collect::<Vec<u8>>());
+        assert_eq!(a.mul(&b).to_le_bytes(),
+                   [0x96, 0x27, 0x0e, 0xad, 0x3c, 0x23, 0xeb, 0x03, 0x0b,
+                    0x93, 0xa1, 0xde, 0xaf, 0x76, 0xc5, 0x1e, 0x00]);
+
+        for v in [1, 2, 3, -7, 123456789, i64::MAX] {
+            let z = Zp::from_i64(v);
+            assert_eq!(z.mul(&z.inv().unwrap()), Zp::one());
+        }
+        assert!(Zp::zero().inv().is_none());
+
+        // Euler's criterion: -1 is a non-residue iff p = 3 mod 4.
+        assert_eq!(P[0] % 4, 3);
+        assert_eq!(Zp::from_i64(-1).pow(&half_order()), Zp::from_i64(-1));
+    }
+
+    #[test]
+    fn encoding() {
+        let z = Zp::from_i64(0x1234);
+        let e = z.to_le_bytes();
+        assert_eq!(&e[..3], &[0x34, 0x12, 0]);
+        assert_eq!(Zp::from_le_bytes(&e), z);
+        assert!(! z.bit(0));
+        assert!(z.bit(2));
+        assert!(z.bit(11));
+
+        // Large values are reduced.
+        let max = Zp::from_le_bytes(&[0xff; ENCODED_SIZE]);
+        assert!(cmp(&max.0, &P) == Ordering::Less);
+        let mut p = [0; ENCODED_SIZE];
+        for (i, b) in p.iter_mut().enumerate() {
+            *b = (P[i / 8] >> (8 * (i % 8))) as u8;
+        }
+        assert_eq!(Zp::from_le_bytes(&p), Zp::zero());
+    }
+}
@@ -212,8 +212,12 @@ impl TryFrom<std::time::SystemTime> for Epoch {
 
 impl Epoch {
     /// Returns the currently active Epoch.
+    ///
+    /// This honors the clock installed using
+    /// [`openpgp::types::set_clock`].
     pub fn current() -> anyhow::Result<Epoch> {
-        std::time::SystemTime::now().try_into()
+        std::time::SystemTime::from(openpgp::types::Timestamp::now())
+            .try_into()
     }
 
     /// Returns the epoch for the given UNIX epoch.
@@ -0,0 +1,487 @@
+//! Parsing keyserver indices.
+//!
+//! Keyservers answer `op=index` and `op=vindex` requests with a list
+//! of matching certificates, their user IDs, and, for `op=vindex`,
+//! the signatures over the user IDs.  This module parses the
+//! machine-readable format described in [Section 5.2 of
+//! draft-shaw-openpgp-hkp-00], and builds a [`Graph`] of who
+//! certified whom from it.
+//!
+//!   [Section 5.2 of draft-shaw-openpgp-hkp-00]: https://datatracker.ietf.org/doc/html/draft-shaw-openpgp-hkp-00#section-5.2
+//!
+//! The draft only defines `info`, `pub`, and `uid` records.  Some
+//! servers extend the `op=vindex` output with `sig` records
+//! following a `uid` record, one for each signature over the user
+//! ID:
+//!
+//! ```text
+//! sig:<keyid>:<creationdate>:<expirationdate>:<sigtype>
+//! ```
+//!
+//! where `<keyid>` is the issuer's key ID or fingerprint and
+//! `<sigtype>` is the signature type in hexadecimal.  `sig` records
+//! following a `pub` record directly are signatures over the key.
+//! Unknown records are ignored.
+//!
+//! # Warning
+//!
+//! The signatures listed in an index have not been verified by us,
+//! and keyservers may list signatures that do not exist or are not
+//! valid.  The graph is only suitable for exploration, and must not
+//! be used to make trust decisions.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use sequoia_openpgp::KeyID;
+//! # use sequoia_net::{KeyServer, Result};
+//! # async fn f() -> Result<()> {
+//! use sequoia_net::vindex::Graph;
+//!
+//! let ks = KeyServer::default();
+//! let keyid: KeyID = "31855247603831FD".parse()?;
+//! let index = ks.vindex(keyid.clone()).await?;
+//! let graph = Graph::new(&index);
+//! for c in graph.certifications_of(&keyid.into()) {
+//!     println!("{} certified {:?}", c.issuer(), c.userid());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use percent_encoding::percent_decode_str;
+
+use sequoia_openpgp::{
+    KeyHandle,
+    packet::UserID,
+    types::{PublicKeyAlgorithm, SignatureType},
+};
+
+use crate::{Error, Result};
+
+/// Flags of index entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags {
+    revoked: bool,
+    disabled: bool,
+    expired: bool,
+}
+
+impl Flags {
+    fn parse(s: &str) -> Self {
+        Flags {
+            revoked: s.contains('r'),
+            disabled: s.contains('d'),
+            expired: s.contains('e'),
+        }
+    }
+
+    /// Returns whether the server considers the object revoked.
+    pub fn revoked(&self) -> bool {
+        self.revoked
+    }
+
+    /// Returns whether the server considers the object disabled.
+    pub fn disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// Returns whether the server considers the object expired.
+    pub fn expired(&self) -> bool {
+        self.expired
+    }
+}
+
+/// A signature listed in an index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    issuer: KeyHandle,
+    creation_time: Option<SystemTime>,
+    expiration_time: Option<SystemTime>,
+    typ: Option<SignatureType>,
+}
+
+impl Signature {
+    /// Returns the issuer's key handle.
+    pub fn issuer(&self) -> &KeyHandle {
+        &self.issuer
+    }
+
+    /// Returns the signature's creation time, if given.
+    pub fn creation_time(&self) -> Option<SystemTime> {
+        self.creation_time
+    }
+
+    /// Returns the signature's expiration time, if given.
+    pub fn expiration_time(&self) -> Option<SystemTime> {
+        self.expiration_time
+    }
+
+    /// Returns the signature's type, if given.
+    pub fn typ(&self) -> Option<SignatureType> {
+        self.typ
+    }
+}
+
+/// A user ID listed in an index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedUserID {
+    userid: UserID,
+    creation_time: Option<SystemTime>,
+    expiration_time: Option<SystemTime>,
+    flags: Flags,
+    signatures: Vec<Signature>,
+}
+
+impl IndexedUserID {
+    /// Returns the user ID.
+    pub fn userid(&self) -> &UserID {
+        &self.userid
+    }
+
+    /// Returns the user ID's creation time, if given.
+    pub fn creation_time(&self) -> Option<SystemTime> {
+        self.creation_time
+    }
+
+    /// Returns the user ID's expiration time, if given.
+    pub fn expiration_time(&self) -> Option<SystemTime> {
+        self.expiration_time
+    }
+
+    /// Returns the user ID's flags.
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// Returns the signatures over the user ID.
+    pub fn signatures(&self) -> &[Signature] {
+        &self.signatures
+    }
+}
+
+/// A certificate listed in an index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    handle: KeyHandle,
+    algo: Option<PublicKeyAlgorithm>,
+    bits: Option<usize>,
+    creation_time: Option<SystemTime>,
+    expiration_time: Option<SystemTime>,
+    flags: Flags,
+    signatures: Vec<Signature>,
+    userids: Vec<IndexedUserID>,
+}
+
+impl Entry {
+    /// Returns the primary key's handle.
+    ///
+    /// Depending on the server, this is either a key ID or a
+    /// fingerprint.
+    pub fn key_handle(&self) -> &KeyHandle {
+        &self.handle
+    }
+
+    /// Returns the primary key's algorithm, if given.
+    pub fn pk_algo(&self) -> Option<PublicKeyAlgorithm> {
+        self.algo
+    }
+
+    /// Returns the primary key's size in bits, if given.
+    pub fn bits(&self) -> Option<usize> {
+        self.bits
+    }
+
+    /// Returns the primary key's creation time, if given.
+    pub fn creation_time(&self) -> Option<SystemTime> {
+        self.creation_time
+    }
+
+    /// Returns the primary key's expiration time, if given.
+    pub fn expiration_time(&self) -> Option<SystemTime> {
+        self.expiration_time
+    }
+
+    /// Returns the primary key's flags.
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// Returns the signatures over the primary key.
+    pub fn signatures(&self) -> &[Signature] {
+        &self.signatures
+    }
+
+    /// Returns the user IDs.
+    pub fn userids(&self) -> &[IndexedUserID] {
+        &self.userids
+    }
+}
+
+/// Parses a machine-readable index.
+///
+/// See the [module-level documentation](self) for the format.
+pub fn parse(index: &str) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in index.lines() {
+        let line = line.trim_end();
+        let mut fields = line.split(':');
+        match fields.next() {
+            Some("pub") => {
+                let handle = fields.next().unwrap_or("").parse()
+                    .map_err(|_| Error::MalformedResponse)?;
+                entries.push(Entry {
+                    handle,
+                    algo: number(fields.next())?
+                        .map(|a: u8| a.into()),
+                    bits: number(fields.next())?,
+                    creation_time: time(fields.next())?,
+                    expiration_time: time(fields.next())?,
+                    flags: Flags::parse(fields.next().unwrap_or("")),
+                    signatures: Vec::new(),
+                    userids: Vec::new(),
+                });
+            },
+            Some("uid") => {
+                let entry = entries.last_mut()
+                    .ok_or(Error::MalformedResponse)?;
+                let userid = percent_decode_str(fields.next().unwrap_or(""))
+                    .collect::<Vec<u8>>();
+                entry.userids.push(IndexedUserID {
+                    userid: userid.into(),
+                    creation_time: time(fields.next())?,
+                    expiration_time: time(fields.next())?,
+                    flags: Flags::parse(fields.next().unwrap_or("")),
+                    signatures: Vec::new(),
+                });
+            },
+            Some("sig") => {
+                let entry = entries.last_mut()
+                    .ok_or(Error::MalformedResponse)?;
+                let issuer = fields.next().unwrap_or("").parse()
+                    .map_err(|_| Error::MalformedResponse)?;
+                let sig = Signature {
+                    issuer,
+                    creation_time: time(fields.next())?,
+                    expiration_time: time(fields.next())?,
+                    typ: match fields.next().filter(|f| ! f.is_empty()) {
+                        Some(t) => Some(u8::from_str_radix(t, 16)
+                                        .map_err(|_| Error::MalformedResponse)?
+                                        .into()),
+                        None => None,
+                    },
+                };
+                match entry.userids.last_mut() {
+                    Some(uid) => uid.signatures.push(sig),
+                    None => entry.signatures.push(sig),
+                }
+            },
+            // "info" and unknown records.
+            _ => (),
+        }
+    }
+    Ok(entries)
+}
+
+/// Parses an optional decimal field.
+fn number<T: std::str::FromStr>(field: Option<&str>) -> Result<Option<T>> {
+    match field.filter(|f| ! f.is_empty()) {
+        Some(f) => Ok(Some(f.parse().map_err(|_| Error::MalformedResponse)?)),
+        None => Ok(None),
+    }
+}
+
+/// Parses an optional timestamp field.
+fn time(field: Option<&str>) -> Result<Option<SystemTime>> {
+    Ok(number::<u64>(field)?
+       .and_then(|t| UNIX_EPOCH.checked_add(Duration::from_secs(t))))
+}
+
+/// A certification listed in an index.
+///
+/// See [`Graph`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Certification<'a> {
+    target: &'a Entry,
+    userid: Option<&'a IndexedUserID>,
+    signature: &'a Signature,
+}
+
+impl<'a> Certification<'a> {
+    /// Returns the issuer's key handle.
+    pub fn issuer(&self) -> &'a KeyHandle {
+        &self.signature.issuer
+    }
+
+    /// Returns the certified certificate.
+    pub fn target(&self) -> &'a Entry {
+        self.target
+    }
+
+    /// Returns the certified user ID.
+    ///
+    /// Returns `None` for signatures over the primary key.
+    pub fn userid(&self) -> Option<&'a IndexedUserID> {
+        self.userid
+    }
+
+    /// Returns the signature.
+    pub fn signature(&self) -> &'a Signature {
+        self.signature
+    }
+}
+
+/// A graph of who certified whom.
+///
+/// The nodes are the certificates listed in an index, and the
+/// certificates that issued signatures.  Self signatures are not
+/// included.  Key handles are compared using
+/// [`KeyHandle::aliases`], so a certification issued by a key ID
+/// matches a certificate listed with its fingerprint and vice
+/// versa.
+///
+/// See the [module-level documentation](self) for an example.
+#[derive(Debug, Clone)]
+pub struct Graph<'a> {
+    certifications: Vec<Certification<'a>>,
+}
+
+impl<'a> Graph<'a> {
+    /// Builds the graph from the given index entries.
+    pub fn new(entries: &'a [Entry]) -> Self {
+        let mut certifications = Vec::new();
+        for target in entries {
+            let sigs = target.signatures.iter().map(|s| (None, s))
+                .chain(target.userids.iter().flat_map(
+                    |u| u.signatures.iter().map(move |s| (Some(u), s))));
+            for (userid, signature) in sigs {
+                if signature.issuer.aliases(&target.handle) {
+                    continue;
+                }
+                certifications.push(Certification {
+                    target,
+                    userid,
+                    signature,
+                });
+            }
+        }
+        Graph { certifications }
+    }
+
+    /// Returns all certifications.
+    pub fn certifications(&self)
+                          -> impl Iterator<Item = &Certification<'a>>
+    {
+        self.certifications.iter()
+    }
+
+    /// Returns the certifications of the certificate `handle`.
+    pub fn certifications_of<'b>(&'b self, handle: &'b KeyHandle)
+        -> impl Iterator<Item = &'b Certification<'a>> + 'b
+    {
+        self.certifications.iter()
+            .filter(move |c| c.target.handle.aliases(handle))
+    }
+
+    /// Returns the certifications issued by `handle`.
+    pub fn certifications_by<'b>(&'b self, handle: &'b KeyHandle)
+        -> impl Iterator<Item = &'b Certification<'a>> + 'b
+    {
+        self.certifications.iter()
+            .filter(move |c| c.signature.issuer.aliases(handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: &str = "\
+info:1:2
+pub:D03F6F865226FE8B:1:2048:1511355130::
+uid:Testy McTestface %3Ctesty@example.org%3E:1511355130::
+sig:D03F6F865226FE8B:1511355130::13
+sig:DD53F7D2C2A4B6D5:1520000000:1600000000:10
+sig:AACB3243630052D9:1530000000::12
+pub:7A58B15E3C9C3B56A6B2E27CDD53F7D2C2A4B6D5:22::1520000000:1600000000:re
+sig:0A0B0C0D0E0F1011:1520000001::1f
+uid:Alice%3A Example:1520000000::r
+sig:D03F6F865226FE8B:1530000000::10
+bogus:ignored
+";
+
+    #[test]
+    fn parse_index() -> Result<()> {
+        let entries = parse(INDEX)?;
+        assert_eq!(entries.len(), 2);
+
+        let e = &entries[0];
+        assert_eq!(e.key_handle(),
+                   &"D03F6F865226FE8B".parse::<KeyHandle>()?);
+        assert_eq!(e.pk_algo(), Some(PublicKeyAlgorithm::RSAEncryptSign));
+        assert_eq!(e.bits(), Some(2048));
+        assert_eq!(e.creation_time(),
+                   Some(UNIX_EPOCH + Duration::from_secs(1511355130)));
+        assert_eq!(e.expiration_time(), None);
+        assert_eq!(e.flags(), Flags::default());
+        assert!(e.signatures().is_empty());
+        assert_eq!(e.userids().len(), 1);
+        let u = &e.userids()[0];
+        assert_eq!(u.userid().value(), b"Testy McTestface <testy@example.org>");
+        assert_eq!(u.signatures().len(), 3);
+        assert_eq!(u.signatures()[1].typ(),
+                   Some(SignatureType::GenericCertification));
+        assert_eq!(u.signatures()[1].expiration_time(),
+                   Some(UNIX_EPOCH + Duration::from_secs(1600000000)));
+
+        let e = &entries[1];
+        assert!(matches!(e.key_handle(), KeyHandle::Fingerprint(_)));
+        assert_eq!(e.pk_algo(), Some(PublicKeyAlgorithm::EdDSA));
+        assert_eq!(e.bits(), None);
+        assert!(e.flags().revoked() && e.flags().expired());
+        assert!(! e.flags().disabled());
+        assert_eq!(e.signatures().len(), 1);
+        assert_eq!(e.signatures()[0].typ(), Some(SignatureType::DirectKey));
+        assert_eq!(e.userids()[0].userid().value(), b"Alice: Example");
+        assert!(e.userids()[0].flags().revoked());
+
+        // Malformed input.
+        assert!(parse("uid:orphan::").is_err());
+        assert!(parse("pub:not a keyid:1:2048::").is_err());
+        assert!(parse("pub:D03F6F865226FE8B:x:2048::").is_err());
+        assert!(parse("pub:D03F6F865226FE8B:1:2048::\nsig:D03F6F865226FE8B::\
+                       :zz").is_err());
+        assert!(parse("")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn graph() -> Result<()> {
+        let entries = parse(INDEX)?;
+        let graph = Graph::new(&entries);
+
+        // The self signature is skipped.
+        assert_eq!(graph.certifications().count(), 4);
+
+        let testy: KeyHandle = "D03F6F865226FE8B".parse()?;
+        let alice: KeyHandle = "DD53F7D2C2A4B6D5".parse()?;
+
+        // Key IDs match fingerprints.
+        let of_testy = graph.certifications_of(&testy).collect::<Vec<_>>();
+        assert_eq!(of_testy.len(), 2);
+        assert!(of_testy[0].issuer().aliases(&alice));
+        assert_eq!(of_testy[0].userid().unwrap().userid().value(),
+                   b"Testy McTestface <testy@example.org>");
+
+        let by_testy = graph.certifications_by(&testy).collect::<Vec<_>>();
+        assert_eq!(by_testy.len(), 1);
+        assert!(by_testy[0].target().key_handle().aliases(&alice));
+
+        let of_alice = graph.certifications_of(&alice).collect::<Vec<_>>();
+        assert_eq!(of_alice.len(), 2);
+        assert!(of_alice[0].userid().is_none());
+        Ok(())
+    }
+}
@@ -110,6 +110,17 @@ impl Url {
         Ok(reqwest::Url::parse(self.build(variant).as_str())?)
     }
 
+    /// Returns the URL of the policy file.
+    fn policy_url(&self, variant: Variant) -> Result<reqwest::Url> {
+        let url = if variant == Variant::Direct {
+            format!("https://{}/.well-known/openpgpkey/policy", self.domain)
+        } else {
+            format!("https://openpgpkey.{}/.well-known/openpgpkey/{}/policy",
+                    self.domain, self.domain)
+        };
+        Ok(reqwest::Url::parse(&url)?)
+    }
+
     /// Returns a [`PathBuf`].
     pub fn to_file_path<V>(&self, variant: V) -> Result<PathBuf>
         where V: Into<Option<Variant>>
@@ -249,6 +260,229 @@ pub async fn get<S: AsRef<str>>(c: &reqwest::Client, email_address: S)
     }
 }
 
+/// A problem with a Web Key Directory, found by [`check`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// Neither the advanced nor the direct method could be used to
+    /// contact the server.
+    ///
+    /// This contains the error message.
+    Unreachable(String),
+
+    /// The server didn't respond with `200 OK`.
+    HttpStatus(reqwest::StatusCode),
+
+    /// The response's content type is not
+    /// `application/octet-stream`.
+    ///
+    /// This contains the content type, if any.
+    WrongContentType(Option<String>),
+
+    /// The response lacks the `Access-Control-Allow-Origin: *`
+    /// header.
+    ///
+    /// Without it, web applications can't look up certificates.
+    MissingCors,
+
+    /// The policy file is missing.
+    PolicyMissing,
+
+    /// The certificates are ASCII armored.
+    ///
+    /// Certificates must be served in binary form.
+    Armored,
+
+    /// The response could not be parsed as certificates.
+    ///
+    /// This contains the error message.
+    MalformedCert(String),
+
+    /// None of the certificates contains a User ID with the address.
+    AddressNotInCert,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Unreachable(e) =>
+                write!(f, "server is unreachable: {}", e),
+            Problem::HttpStatus(s) =>
+                write!(f, "server returned status {}", s),
+            Problem::WrongContentType(Some(t)) =>
+                write!(f, "wrong content type {:?}, \
+                           expected \"application/octet-stream\"", t),
+            Problem::WrongContentType(None) =>
+                write!(f, "missing content type, \
+                           expected \"application/octet-stream\""),
+            Problem::MissingCors =>
+                write!(f, "missing \"Access-Control-Allow-Origin: *\" header"),
+            Problem::PolicyMissing =>
+                write!(f, "policy file is missing"),
+            Problem::Armored =>
+                write!(f, "certificates are ASCII armored, \
+                           expected binary"),
+            Problem::MalformedCert(e) =>
+                write!(f, "malformed certificate: {}", e),
+            Problem::AddressNotInCert =>
+                write!(f, "no certificate contains the address"),
+        }
+    }
+}
+
+/// The result of checking a Web Key Directory, see [`check`].
+#[derive(Debug, Clone, Default)]
+pub struct Diagnosis {
+    variant: Option<Variant>,
+    certs: Vec<Cert>,
+    problems: Vec<Problem>,
+}
+
+impl Diagnosis {
+    /// Returns the variant the server responded to, if any.
+    pub fn variant(&self) -> Option<Variant> {
+        self.variant
+    }
+
+    /// Returns the certificates served for the address.
+    ///
+    /// This includes certificates that don't contain the address.
+    pub fn certs(&self) -> &[Cert] {
+        &self.certs
+    }
+
+    /// Returns the problems found.
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+
+    /// Returns whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Checks the Web Key Directory for the given email address.
+///
+/// This looks up the address like [`get`] does, and diagnoses common
+/// misconfigurations: an unexpected status or content type, a
+/// missing `Access-Control-Allow-Origin` header, a missing policy
+/// file, ASCII armored or malformed certificates, and certificates
+/// that don't contain the address.
+///
+/// Only the variant that the server responds to is checked: the
+/// advanced method, or, if the server can't be reached using it, the
+/// direct method.  Errors are only returned if `email_address` is
+/// malformed; problems with the server are reported in the returned
+/// [`Diagnosis`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use sequoia_net::{Result, wkd};
+/// # async fn f() -> Result<()> {
+/// let diagnosis =
+///     wkd::check(&reqwest::Client::new(), "foo@bar.baz").await?;
+/// for problem in diagnosis.problems() {
+///     eprintln!("{}", problem);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn check<S: AsRef<str>>(c: &reqwest::Client, email_address: S)
+                                  -> Result<Diagnosis>
+{
+    let email = email_address.as_ref();
+    let wkd_url = Url::from(email)?;
+
+    let mut diagnosis = Diagnosis::default();
+    let mut unreachable = None;
+    for variant in [Variant::Advanced, Variant::Direct] {
+        let res = match c.get(wkd_url.to_url(variant)?).send().await {
+            Ok(res) => res,
+            Err(e) => {
+                unreachable = Some(e.to_string());
+                continue;
+            },
+        };
+        diagnosis.variant = Some(variant);
+
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.bytes().await.unwrap_or_default();
+        diagnose_response(&mut diagnosis, status, &headers, &body, email);
+
+        match c.get(wkd_url.policy_url(variant)?).send().await {
+            Ok(res) if res.status().is_success() => (),
+            _ => diagnosis.problems.push(Problem::PolicyMissing),
+        }
+        return Ok(diagnosis);
+    }
+
+    diagnosis.problems.push(Problem::Unreachable(
+        unreachable.expect("tried at least one variant")));
+    Ok(diagnosis)
+}
+
+/// Diagnoses a response to a lookup of `email`.
+fn diagnose_response(diagnosis: &mut Diagnosis,
+                     status: reqwest::StatusCode,
+                     headers: &reqwest::header::HeaderMap,
+                     body: &[u8], email: &str)
+{
+    use reqwest::header::{ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE};
+
+    let problems = &mut diagnosis.problems;
+    if status != reqwest::StatusCode::OK {
+        problems.push(Problem::HttpStatus(status));
+        return;
+    }
+
+    let content_type = headers.get(CONTENT_TYPE)
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned());
+    match content_type.as_deref().and_then(|t| t.split(';').next()) {
+        Some(t) if t.trim().eq_ignore_ascii_case("application/octet-stream") =>
+            (),
+        _ => problems.push(Problem::WrongContentType(content_type)),
+    }
+
+    if ! headers.get(ACCESS_CONTROL_ALLOW_ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim() == "*").unwrap_or(false)
+    {
+        problems.push(Problem::MissingCors);
+    }
+
+    let start = body.iter().position(|b| ! b.is_ascii_whitespace())
+        .unwrap_or(body.len());
+    if body[start..].starts_with(b"-----BEGIN PGP") {
+        problems.push(Problem::Armored);
+    }
+
+    match CertParser::from_bytes(body) {
+        Ok(parser) => for cert in parser {
+            match cert {
+                Ok(cert) => diagnosis.certs.push(cert),
+                Err(e) => problems.push(Problem::MalformedCert(e.to_string())),
+            }
+        },
+        Err(e) => problems.push(Problem::MalformedCert(e.to_string())),
+    }
+    if diagnosis.certs.is_empty() && ! problems.iter()
+        .any(|p| matches!(p, Problem::MalformedCert(_)))
+    {
+        problems.push(Problem::MalformedCert("no certificates".into()));
+    }
+
+    if ! diagnosis.certs.iter().any(|cert| cert.userids().any(|uidb| {
+        uidb.userid().email2().ok().flatten()
+            .map(|a| a.eq_ignore_ascii_case(email))
+            .unwrap_or(false)
+    })) {
+        problems.push(Problem::AddressNotInCert);
+    }
+}
+
 /// Returns all e-mail addresses from certificate's User IDs matching `domain`.
 fn get_cert_domains<'a>(domain: &'a str, cert: &ValidCert<'a>) -> impl Iterator<Item = Url> + 'a
 {
@@ -465,6 +699,56 @@ mod tests {
         // XXX: Test with more Certs
     }
 
+    #[test]
+    fn diagnose() -> Result<()> {
+        use reqwest::StatusCode;
+        use reqwest::header::*;
+        use openpgp::serialize::SerializeInto;
+
+        let (cert, _) = CertBuilder::new()
+            .add_userid("juga@sequoia-pgp.org")
+            .generate()?;
+        let binary = cert.to_vec()?;
+        let armored = cert.armored().to_vec()?;
+
+        let mut good = HeaderMap::new();
+        good.insert(CONTENT_TYPE,
+                    HeaderValue::from_static("application/octet-stream"));
+        good.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+
+        let diagnose = |status, headers: &HeaderMap, body: &[u8], email| {
+            let mut d = Diagnosis::default();
+            diagnose_response(&mut d, status, headers, body, email);
+            d
+        };
+
+        let d = diagnose(StatusCode::OK, &good, &binary,
+                         "juga@sequoia-pgp.org");
+        assert!(d.is_ok(), "{:?}", d.problems());
+        assert_eq!(d.certs(), &[cert.clone()]);
+
+        let d = diagnose(StatusCode::OK, &good, &binary,
+                         "justus@sequoia-pgp.org");
+        assert_eq!(d.problems(), &[Problem::AddressNotInCert]);
+
+        let d = diagnose(StatusCode::OK, &HeaderMap::new(), &armored,
+                         "juga@sequoia-pgp.org");
+        assert_eq!(d.problems(), &[Problem::WrongContentType(None),
+                                   Problem::MissingCors,
+                                   Problem::Armored]);
+        assert_eq!(d.certs().len(), 1);
+
+        let d = diagnose(StatusCode::OK, &good, b"<html></html>",
+                         "juga@sequoia-pgp.org");
+        assert!(matches!(d.problems(), [Problem::MalformedCert(_),
+                                        Problem::AddressNotInCert]));
+
+        let d = diagnose(StatusCode::NOT_FOUND, &good, &binary,
+                         "juga@sequoia-pgp.org");
+        assert_eq!(d.problems(), &[Problem::HttpStatus(StatusCode::NOT_FOUND)]);
+        Ok(())
+    }
+
     #[test]
     fn wkd_generate() {
        let (cert, _) = CertBuilder::new()
@@ -51,18 +51,27 @@ Pu1xwz57O4zo1VYf6TqHJzVC3OMvMUM2hhdecMUe5x6GorNaj6g=
 
 const FP: &str = "3E8877C877274692975189F5D03F6F865226FE8B";
 const ID: &str = "D03F6F865226FE8B";
+const BAD_REQUEST: &str = "0x0000000000000000";
+const HTML_ERROR: &str = "0xFFFFFFFFFFFFFFFF";
+const ENDLESS_ERROR: &str = "0xEEEEEEEEEEEEEEEE";
 
 async fn service(
     req: Request<hyper::body::Incoming>,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
     match (req.method(), req.uri().path()) {
         (&Method::GET, "/pks/lookup") => {
+            let mut found = false;
+            let mut search = String::new();
             if let Some(args) = req.uri().query() {
                 for (key, value) in url::form_urlencoded::parse(args.as_bytes()) {
                     match key.clone().into_owned().as_ref() {
                         "op" => assert_eq!(value, "get"),
                         "options" => assert_eq!(value, "mr"),
-                        "search" => assert_eq!(value, "0xD03F6F865226FE8B"),
+                        "search" => {
+                            found = value == "0xD03F6F865226FE8B"
+                                || value == format!("0x{}", FP);
+                            search = value.into_owned();
+                        },
                         _ => panic!("Bad query: {}:{}", key, value),
                     }
                 }
@@ -70,7 +79,31 @@ async fn service(
                 panic!("Expected query string");
             }
 
-            Ok(Response::new(full(RESPONSE)))
+            if found {
+                Ok(Response::new(full(RESPONSE)))
+            } else if search == BAD_REQUEST {
+                Ok(Response::builder()
+                   .status(StatusCode::BAD_REQUEST)
+                   .body(full("Invalid search: key ID must not be zero\n"))
+                   .unwrap())
+            } else if search == ENDLESS_ERROR {
+                Ok(Response::builder()
+                   .status(StatusCode::INTERNAL_SERVER_ERROR)
+                   .body(Endless.boxed())
+                   .unwrap())
+            } else if search == HTML_ERROR {
+                Ok(Response::builder()
+                   .header("content-type", "text/html; charset=utf-8")
+                   .body(full("<html><head><title>Error</title></head>\
+                               <body><h2>Error handling request</h2>\
+                               Database &quot;keys&quot; unavailable\
+                               </body></html>"))
+                   .unwrap())
+            } else {
+                Ok(Response::builder()
+                   .status(StatusCode::NOT_FOUND)
+                   .body(full("Not found")).unwrap())
+            }
         },
         (&Method::POST, "/pks/add") => {
             let b = req.collect().await?.to_bytes();
@@ -106,6 +139,23 @@ fn full<T: Into<Bytes>>(chunk: T) -> BoxBody<Bytes, hyper::Error> {
         .boxed()
 }
 
+/// A response body that never ends.
+struct Endless;
+
+impl hyper::body::Body for Endless {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(self: std::pin::Pin<&mut Self>,
+                  _: &mut std::task::Context<'_>)
+        -> std::task::Poll<Option<Result<hyper::body::Frame<Bytes>,
+                                         hyper::Error>>>
+    {
+        std::task::Poll::Ready(Some(Ok(hyper::body::Frame::data(
+            Bytes::from_static(&[b'x'; 4096])))))
+    }
+}
+
 /// Starts a server on a random port.
 ///
 /// Returns the address, a channel to drop() to kill the server, and
@@ -153,6 +203,37 @@ async fn get() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn get_error_message() -> anyhow::Result<()> {
+    // Start server.
+    let addr = start_server().await;
+
+    let keyserver = KeyServer::new(&format!("hkp://{}", addr))?;
+
+    let keyid: KeyID = BAD_REQUEST[2..].parse()?;
+    let err = keyserver.get(keyid).await.unwrap_err();
+    match err.downcast_ref::<sequoia_net::Error>() {
+        Some(sequoia_net::Error::ServerError(status, message)) => {
+            assert_eq!(*status, StatusCode::BAD_REQUEST);
+            assert_eq!(message, "Invalid search: key ID must not be zero");
+        },
+        e => panic!("unexpected error: {:?}", e),
+    }
+
+    // An HTML error page with a 200 status.
+    let keyid: KeyID = HTML_ERROR[2..].parse()?;
+    let err = keyserver.get(keyid).await.unwrap_err();
+    match err.downcast_ref::<sequoia_net::Error>() {
+        Some(sequoia_net::Error::ServerError(status, message)) => {
+            assert_eq!(*status, StatusCode::OK);
+            assert_eq!(message, "Error handling request \
+                                 Database \"keys\" unavailable");
+        },
+        e => panic!("unexpected error: {:?}", e),
+    }
+    Ok(())
+}
+
 #[tokio::test]
 async fn send() -> anyhow::Result<()> {
     // Start server.
@@ -165,3 +246,85 @@ async fn send() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn refresh() -> anyhow::Result<()> {
+    use sequoia_openpgp::policy::StandardPolicy;
+
+    // Start server.
+    let addr = start_server().await;
+
+    let p = &StandardPolicy::new();
+    let keyserver = KeyServer::new(&format!("hkp://{}", addr))?;
+    let cert = Cert::from_reader(Reader::from_reader(Cursor::new(RESPONSE), None))?;
+    let refreshed = keyserver.refresh(&cert, p).await?;
+    assert_eq!(refreshed, cert);
+    assert_eq!(keyserver.request_statistics().requests, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pool_failover() -> anyhow::Result<()> {
+    // Start server.
+    let addr = start_server().await;
+
+    // Nothing listens on port 1.
+    let keyserver = KeyServer::new_pool([
+        "hkp://127.0.0.1:1".to_string(),
+        format!("hkp://{}", addr),
+    ])?;
+    let keyid: KeyID = ID.parse()?;
+    let keys = keyserver.get(keyid.clone()).await?;
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keyserver.request_statistics().failovers, 1);
+
+    // The failed server is now tried last.
+    let keys = keyserver.get(keyid).await?;
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keyserver.request_statistics().failovers, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_many() -> anyhow::Result<()> {
+    // Start server.
+    let addr = start_server().await;
+
+    let keyserver = KeyServer::new(&format!("hkp://{}", addr))?;
+    let keyid: KeyID = ID.parse()?;
+    let unknown: KeyID = "0123456789ABCDEF".parse()?;
+    let results = keyserver.get_many(
+        vec![keyid.clone(), unknown, keyid]).await;
+    assert_eq!(results.len(), 3);
+
+    // The results are in order.
+    assert_eq!(results[0].as_ref().unwrap()[0].as_ref().unwrap()
+               .fingerprint(), FP.parse().unwrap());
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().unwrap().len(), 1);
+
+    let statistics = keyserver.request_statistics();
+    assert_eq!(statistics.requests, 3);
+    assert_eq!(statistics.retries, 0);
+    assert_eq!(statistics.failovers, 0);
+    // Plain hkp doesn't negotiate HTTP/2.
+    assert_eq!(statistics.http2_responses, 0);
+    Ok(())
+}
+
+/// Checks that the bodies of failed responses are not read without
+/// bound.
+#[tokio::test]
+async fn endless_error_body() -> anyhow::Result<()> {
+    // Start server.
+    let addr = start_server().await;
+
+    // Both servers fail, so the first response is discarded.
+    let keyserver = KeyServer::new_pool([
+        format!("hkp://{}", addr),
+        format!("hkp://localhost:{}", addr.port()),
+    ])?;
+    let keyid: KeyID = ENDLESS_ERROR[2..].parse()?;
+    assert!(keyserver.get(keyid).await.is_err());
+    Ok(())
+}
@@ -0,0 +1,201 @@
+//! Public keys.
+
+use core::fmt;
+
+use crate::{Error, Result, take, take_be};
+#[cfg(feature = "ed25519")]
+use crate::take_mpi;
+use crate::packet::{self, Packet, Tag};
+
+/// The OID of Ed25519 when used with EdDSA.
+#[cfg(feature = "ed25519")]
+const ED25519_OID: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01];
+
+/// The public key algorithm identifier for EdDSA.
+#[cfg(feature = "ed25519")]
+pub(crate) const EDDSA: u8 = 22;
+
+/// A version 4 public key.
+///
+/// This is the content of a public key packet, or a public subkey
+/// packet.  See [Section 5.5.2 of RFC 4880] for details.
+///
+///   [Section 5.5.2 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.5.2
+#[derive(Clone, PartialEq, Eq)]
+pub struct PublicKey<'a> {
+    body: &'a [u8],
+    creation_time: u32,
+    pk_algo: u8,
+    mpis: &'a [u8],
+    fingerprint: [u8; 20],
+}
+
+impl fmt::Debug for PublicKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Hex<'a>(&'a [u8]);
+        impl fmt::Debug for Hex<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.iter().try_for_each(|b| write!(f, "{:02X}", b))
+            }
+        }
+
+        f.debug_struct("PublicKey")
+            .field("fingerprint", &Hex(&self.fingerprint))
+            .field("creation_time", &self.creation_time)
+            .field("pk_algo", &self.pk_algo)
+            .finish()
+    }
+}
+
+impl<'a> TryFrom<Packet<'a>> for PublicKey<'a> {
+    type Error = Error;
+
+    fn try_from(p: Packet<'a>) -> Result<Self> {
+        match p.tag() {
+            Tag::PublicKey | Tag::PublicSubkey => Self::from_body(p.body()),
+            _ => Err(Error::Malformed("not a public key packet")),
+        }
+    }
+}
+
+impl<'a> PublicKey<'a> {
+    /// Parses the first packet in `data` as public key.
+    ///
+    /// `data` is binary OpenPGP data starting with a public key
+    /// packet, or a public subkey packet, for instance a certificate.
+    /// Any data following the first packet is ignored.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        packet::first(data, &[Tag::PublicKey, Tag::PublicSubkey])?
+            .try_into()
+    }
+
+    /// Parses a public key packet's body.
+    pub fn from_body(body: &'a [u8]) -> Result<Self> {
+        let mut data = body;
+        match take(&mut data, 1)?[0] {
+            4 => (),
+            _ => return Err(Error::Unsupported("key version")),
+        }
+        let creation_time = take_be(&mut data, 4)?;
+        let pk_algo = take(&mut data, 1)?[0];
+
+        // The fingerprint is computed over the whole body.
+        let mut h = sha1collisiondetection::Builder::default()
+            .detect_collisions(true)
+            .use_ubc(true)
+            .safe_hash(true)
+            .build();
+        let len = u16::try_from(body.len())
+            .map_err(|_| Error::Malformed("key too large"))?;
+        h.update([0x99]);
+        h.update(len.to_be_bytes());
+        h.update(body);
+        let mut fingerprint = sha1collisiondetection::Output::default();
+        h.finalize_into_dirty_cd(&mut fingerprint)
+            .map_err(|_| Error::Malformed("SHA-1 collision"))?;
+
+        Ok(PublicKey {
+            body,
+            creation_time,
+            pk_algo,
+            mpis: data,
+            fingerprint: fingerprint.into(),
+        })
+    }
+
+    /// Returns the packet's body.
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// Returns the key's creation time as seconds since the UNIX
+    /// epoch.
+    pub fn creation_time(&self) -> u32 {
+        self.creation_time
+    }
+
+    /// Returns the public key algorithm's identifier.
+    pub fn pk_algo(&self) -> u8 {
+        self.pk_algo
+    }
+
+    /// Returns the algorithm specific public key material.
+    pub fn mpis(&self) -> &'a [u8] {
+        self.mpis
+    }
+
+    /// Returns the key's fingerprint.
+    pub fn fingerprint(&self) -> &[u8; 20] {
+        &self.fingerprint
+    }
+
+    /// Returns the key's key ID.
+    pub fn keyid(&self) -> &[u8; 8] {
+        self.fingerprint[12..].try_into().expect("8 bytes")
+    }
+
+    /// Returns the raw Ed25519 public key.
+    ///
+    /// Returns an error if this is not an EdDSA key using Ed25519.
+    #[cfg(feature = "ed25519")]
+    pub(crate) fn ed25519(&self) -> Result<[u8; 32]> {
+        if self.pk_algo != EDDSA {
+            return Err(Error::Unsupported("public key algorithm"));
+        }
+
+        let mut data = self.mpis;
+        let oid_len = take(&mut data, 1)?[0] as usize;
+        if take(&mut data, oid_len)? != ED25519_OID {
+            return Err(Error::Unsupported("curve"));
+        }
+
+        // The point is prefixed with 0x40.
+        match take_mpi(&mut data)? {
+            [0x40, point @ ..] if point.len() == 32 =>
+                Ok(point.try_into().expect("32 bytes")),
+            _ => Err(Error::Malformed("Ed25519 public key")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sequoia_openpgp as openpgp;
+    use openpgp::parse::Parse;
+
+    const CERT: &[u8] = include_bytes!("../tests/data/ed25519.pgp");
+
+    #[test]
+    fn fingerprint() {
+        let cert = openpgp::Cert::from_bytes(CERT).unwrap();
+        let key = PublicKey::from_bytes(CERT).unwrap();
+
+        assert_eq!(&key.fingerprint()[..], cert.fingerprint().as_bytes());
+        assert_eq!(&key.keyid()[..], cert.keyid().as_bytes());
+        assert_eq!(key.pk_algo(), u8::from(cert.primary_key().pk_algo()));
+        assert_eq!(key.creation_time(),
+                   openpgp::types::Timestamp::try_from(
+                       cert.primary_key().creation_time()).unwrap().into());
+        #[cfg(feature = "ed25519")]
+        assert!(key.ed25519().is_ok());
+
+        // The subkey is a Cv25519 encryption key.
+        let subkey = cert.keys().subkeys().next().unwrap();
+        let p = crate::Packets::new(CERT)
+            .filter_map(|p| p.ok())
+            .find(|p| p.tag() == Tag::PublicSubkey)
+            .unwrap();
+        let key = PublicKey::try_from(p).unwrap();
+        assert_eq!(&key.fingerprint()[..], subkey.fingerprint().as_bytes());
+        #[cfg(feature = "ed25519")]
+        assert_eq!(key.ed25519(),
+                   Err(Error::Unsupported("public key algorithm")));
+
+        // Only public key packets are accepted.
+        let sig = include_bytes!("../tests/data/ed25519.sig");
+        assert_eq!(PublicKey::from_bytes(sig),
+                   Err(Error::Malformed("unexpected packet type")));
+    }
+}
@@ -0,0 +1,125 @@
+//! A `no_std` subset of OpenPGP.
+//!
+//! This crate implements a small subset of [OpenPGP] that works
+//! without the standard library, and without a heap allocator.  It
+//! is intended for constrained environments like firmware, where
+//! the full [`sequoia-openpgp`] crate is not an option.  A typical
+//! use case is checking a detached signature over a firmware image
+//! using a public key that is baked into the bootloader.
+//!
+//! The subset consists of:
+//!
+//!   - Splitting binary OpenPGP data into packets, see [`Packets`].
+//!
+//!   - Version 4 public keys: their metadata, fingerprints, and key
+//!     IDs, see [`PublicKey`].
+//!
+//!   - Version 4 signatures: their metadata, and their subpackets,
+//!     see [`Signature`].
+//!
+//!   - If the `ed25519` feature is enabled (the default), verifying
+//!     Ed25519 signatures over binary documents, see
+//!     [`Signature::verify_ed25519`].
+//!
+//! Everything else, in particular ASCII armor, certificate
+//! canonicalization, and policy checks, is out of scope.  Parsing
+//! borrows from the input, nothing is copied.
+//!
+//! [OpenPGP]: https://tools.ietf.org/html/rfc4880
+//! [`sequoia-openpgp`]: https://docs.rs/sequoia-openpgp
+//!
+//! # Examples
+//!
+//! ```
+//! # fn main() -> Result<(), sequoia_openpgp_core::Error> {
+//! use sequoia_openpgp_core::{PublicKey, Signature};
+//!
+//! # let key: &[u8] = include_bytes!("../tests/data/ed25519.pgp");
+//! # let sig: &[u8] = include_bytes!("../tests/data/ed25519.sig");
+//! # let firmware: &[u8] = b"Hello world.";
+//! // The certificate baked into the bootloader.  Only the first
+//! // packet, the primary key, is used.
+//! let key = PublicKey::from_bytes(key)?;
+//!
+//! // Check the detached signature over the firmware image.
+//! let sig = Signature::from_bytes(sig)?;
+//! assert_eq!(sig.issuer_fingerprint(), Some(key.fingerprint()));
+//! # #[cfg(feature = "ed25519")]
+//! sig.verify_ed25519(&key, firmware)?;
+//! # Ok(()) }
+//! ```
+
+#![no_std]
+#![doc(html_favicon_url = "https://docs.sequoia-pgp.org/favicon.png")]
+#![doc(html_logo_url = "https://docs.sequoia-pgp.org/logo.svg")]
+#![warn(missing_docs)]
+
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
+use core::fmt;
+
+mod packet;
+pub use packet::{Packet, Packets, Tag};
+mod key;
+pub use key::PublicKey;
+mod signature;
+pub use signature::{Signature, Subpacket, Subpackets};
+
+/// Errors returned by this crate.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The data ended prematurely.
+    Truncated,
+
+    /// The data is malformed.
+    Malformed(&'static str),
+
+    /// The data uses a feature that is not supported.
+    Unsupported(&'static str),
+
+    /// The signature is not valid.
+    BadSignature,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "Truncated data"),
+            Error::Malformed(what) => write!(f, "Malformed data: {}", what),
+            Error::Unsupported(what) => write!(f, "Unsupported: {}", what),
+            Error::BadSignature => write!(f, "Bad signature"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Result type for this crate.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Splits `n` bytes off the front of `data`.
+fn take<'a>(data: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if data.len() < n {
+        return Err(Error::Truncated);
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+/// Reads a big endian integer of `n` bytes from the front of `data`.
+fn take_be(data: &mut &[u8], n: usize) -> Result<u32> {
+    Ok(take(data, n)?.iter().fold(0, |acc, b| acc << 8 | u32::from(*b)))
+}
+
+/// Reads an MPI from the front of `data`, returning its value.
+///
+/// The value does not include the length prefix.
+#[cfg(feature = "ed25519")]
+fn take_mpi<'a>(data: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let bits = take_be(data, 2)? as usize;
+    take(data, (bits + 7) / 8)
+}
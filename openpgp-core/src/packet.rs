@@ -0,0 +1,234 @@
+//! Packet framing.
+
+use core::fmt;
+
+use crate::{Error, Result, take, take_be};
+
+/// The packet types.
+///
+/// See [Section 4.3 of RFC 4880] for details.
+///
+///   [Section 4.3 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-4.3
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tag {
+    /// Signature packet.
+    Signature,
+    /// Public key packet.
+    PublicKey,
+    /// Public subkey packet.
+    PublicSubkey,
+    /// User ID packet.
+    UserID,
+    /// User attribute packet.
+    UserAttribute,
+    /// Trust packet.
+    Trust,
+    /// Any other packet.
+    Other(u8),
+}
+
+impl From<u8> for Tag {
+    fn from(t: u8) -> Self {
+        match t {
+            2 => Tag::Signature,
+            6 => Tag::PublicKey,
+            12 => Tag::Trust,
+            13 => Tag::UserID,
+            14 => Tag::PublicSubkey,
+            17 => Tag::UserAttribute,
+            t => Tag::Other(t),
+        }
+    }
+}
+
+impl From<Tag> for u8 {
+    fn from(t: Tag) -> Self {
+        match t {
+            Tag::Signature => 2,
+            Tag::PublicKey => 6,
+            Tag::Trust => 12,
+            Tag::UserID => 13,
+            Tag::PublicSubkey => 14,
+            Tag::UserAttribute => 17,
+            Tag::Other(t) => t,
+        }
+    }
+}
+
+/// A packet.
+///
+/// This is the packet's type and its body.  Use [`PublicKey`] and
+/// [`Signature`] to parse the body.
+///
+///   [`PublicKey`]: crate::PublicKey
+///   [`Signature`]: crate::Signature
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Packet<'a> {
+    tag: Tag,
+    body: &'a [u8],
+}
+
+impl fmt::Debug for Packet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Packet")
+            .field("tag", &self.tag)
+            .field("body_len", &self.body.len())
+            .finish()
+    }
+}
+
+impl<'a> Packet<'a> {
+    /// Returns the packet's type.
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    /// Returns the packet's body.
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+}
+
+/// Splits binary OpenPGP data into packets.
+///
+/// Both the old and the new packet format are supported.  Partial
+/// body lengths and indeterminate lengths are not supported, because
+/// they are not used for keys and signatures.
+///
+/// After the first error, the iterator is exhausted.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), sequoia_openpgp_core::Error> {
+/// use sequoia_openpgp_core::{Packets, Tag};
+///
+/// // A user ID packet, followed by a trust packet.
+/// let data = b"\xcd\x05Alice\xcc\x02\x00\x00";
+/// let mut packets = Packets::new(data);
+/// let p = packets.next().unwrap()?;
+/// assert_eq!(p.tag(), Tag::UserID);
+/// assert_eq!(p.body(), b"Alice");
+/// assert_eq!(packets.next().unwrap()?.tag(), Tag::Trust);
+/// assert!(packets.next().is_none());
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Packets<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Packets<'a> {
+    /// Returns an iterator over the packets in `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Packets { data }
+    }
+
+    /// Parses the packet at the front of the data.
+    fn parse(&mut self) -> Result<Packet<'a>> {
+        let data = &mut self.data;
+        let ctb = take(data, 1)?[0];
+        if ctb & 0x80 == 0 {
+            return Err(Error::Malformed("invalid packet header"));
+        }
+
+        let (tag, len) = if ctb & 0x40 != 0 {
+            // New format.
+            let tag = ctb & 0x3f;
+            let len = match take(data, 1)?[0] {
+                o @ 0..=191 => o as usize,
+                o @ 192..=223 =>
+                    ((o as usize - 192) << 8) + take(data, 1)?[0] as usize + 192,
+                255 => take_be(data, 4)? as usize,
+                _ => return Err(Error::Unsupported("partial body length")),
+            };
+            (tag, len)
+        } else {
+            // Old format.
+            let tag = (ctb >> 2) & 0xf;
+            let len = match ctb & 0x3 {
+                0 => take_be(data, 1)?,
+                1 => take_be(data, 2)?,
+                2 => take_be(data, 4)?,
+                _ => return Err(Error::Unsupported("indeterminate length")),
+            } as usize;
+            (tag, len)
+        };
+
+        Ok(Packet {
+            tag: tag.into(),
+            body: take(data, len)?,
+        })
+    }
+}
+
+impl<'a> Iterator for Packets<'a> {
+    type Item = Result<Packet<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let r = self.parse();
+        if r.is_err() {
+            self.data = &[];
+        }
+        Some(r)
+    }
+}
+
+/// Returns the first packet in `data`, checking its type.
+pub(crate) fn first<'a>(data: &'a [u8], tags: &[Tag]) -> Result<Packet<'a>> {
+    let packet = Packets::new(data).next().ok_or(Error::Truncated)??;
+    if tags.contains(&packet.tag()) {
+        Ok(packet)
+    } else {
+        Err(Error::Malformed("unexpected packet type"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framing() {
+        // Old format, one, two, and four octet lengths.
+        let data = b"\xb4\x01A\xb5\x00\x01B\xb6\x00\x00\x00\x01C";
+        let packets: std::vec::Vec<_> =
+            Packets::new(data).collect::<Result<_>>().unwrap();
+        assert_eq!(packets.len(), 3);
+        for (p, b) in packets.iter().zip([b"A", b"B", b"C"]) {
+            assert_eq!(p.tag(), Tag::UserID);
+            assert_eq!(p.body(), b);
+        }
+
+        // New format, two octet length.
+        let mut data = std::vec![0xcd, 0xc0, 0x08];
+        data.extend_from_slice(&[0x41; 200]);
+        let p = Packets::new(&data).next().unwrap().unwrap();
+        assert_eq!(p.body().len(), 200);
+
+        // New format, five octet length.
+        let p = Packets::new(b"\xcd\xff\x00\x00\x00\x01A")
+            .next().unwrap().unwrap();
+        assert_eq!(p.body(), b"A");
+
+        // Errors.
+        assert_eq!(Packets::new(b"\x4d\x01A").next(),
+                   Some(Err(Error::Malformed("invalid packet header"))));
+        assert_eq!(Packets::new(b"\xcd\x05A").next(),
+                   Some(Err(Error::Truncated)));
+        assert_eq!(Packets::new(b"\xcd\xe0A").next(),
+                   Some(Err(Error::Unsupported("partial body length"))));
+        assert_eq!(Packets::new(b"\xb7A").next(),
+                   Some(Err(Error::Unsupported("indeterminate length"))));
+
+        // The iterator is fused after an error.
+        let mut packets = Packets::new(b"\xcd\x05A\xcd\x01A");
+        assert!(packets.next().unwrap().is_err());
+        assert!(packets.next().is_none());
+    }
+}
@@ -0,0 +1,490 @@
+//! Signatures.
+
+use core::fmt;
+
+use crate::{Error, Result, take, take_be};
+use crate::packet::{self, Packet, Tag};
+#[cfg(feature = "ed25519")]
+use crate::PublicKey;
+
+/// A version 4 signature.
+///
+/// This is the content of a signature packet.  See [Section 5.2.3 of
+/// RFC 4880] for details.
+///
+///   [Section 5.2.3 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3
+#[derive(Clone, PartialEq, Eq)]
+pub struct Signature<'a> {
+    /// The hashed part of the body, i.e. everything up to and
+    /// including the hashed subpacket area.
+    hashed: &'a [u8],
+    typ: u8,
+    pk_algo: u8,
+    hash_algo: u8,
+    hashed_area: &'a [u8],
+    unhashed_area: &'a [u8],
+    digest_prefix: [u8; 2],
+    mpis: &'a [u8],
+}
+
+impl fmt::Debug for Signature<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signature")
+            .field("typ", &self.typ)
+            .field("pk_algo", &self.pk_algo)
+            .field("hash_algo", &self.hash_algo)
+            .field("creation_time", &self.creation_time())
+            .finish()
+    }
+}
+
+impl<'a> TryFrom<Packet<'a>> for Signature<'a> {
+    type Error = Error;
+
+    fn try_from(p: Packet<'a>) -> Result<Self> {
+        match p.tag() {
+            Tag::Signature => Self::from_body(p.body()),
+            _ => Err(Error::Malformed("not a signature packet")),
+        }
+    }
+}
+
+impl<'a> Signature<'a> {
+    /// Parses the first packet in `data` as signature.
+    ///
+    /// `data` is binary OpenPGP data starting with a signature packet,
+    /// for instance a detached signature.  Any data following the
+    /// first packet is ignored.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        packet::first(data, &[Tag::Signature])?.try_into()
+    }
+
+    /// Parses a signature packet's body.
+    pub fn from_body(body: &'a [u8]) -> Result<Self> {
+        let mut data = body;
+        match take(&mut data, 1)?[0] {
+            4 => (),
+            _ => return Err(Error::Unsupported("signature version")),
+        }
+        let typ = take(&mut data, 1)?[0];
+        let pk_algo = take(&mut data, 1)?[0];
+        let hash_algo = take(&mut data, 1)?[0];
+        let len = take_be(&mut data, 2)? as usize;
+        let hashed_area = take(&mut data, len)?;
+        let hashed = &body[..body.len() - data.len()];
+        let len = take_be(&mut data, 2)? as usize;
+        let unhashed_area = take(&mut data, len)?;
+        let digest_prefix = take(&mut data, 2)?;
+
+        // Make sure the subpacket areas are well-formed.
+        for area in [hashed_area, unhashed_area] {
+            Subpackets::new(area).try_for_each(|s| s.map(|_| ()))?;
+        }
+
+        Ok(Signature {
+            hashed,
+            typ,
+            pk_algo,
+            hash_algo,
+            hashed_area,
+            unhashed_area,
+            digest_prefix: [digest_prefix[0], digest_prefix[1]],
+            mpis: data,
+        })
+    }
+
+    /// Returns the signature type.
+    ///
+    /// For instance, signatures over binary documents have type 0.
+    pub fn typ(&self) -> u8 {
+        self.typ
+    }
+
+    /// Returns the public key algorithm's identifier.
+    pub fn pk_algo(&self) -> u8 {
+        self.pk_algo
+    }
+
+    /// Returns the hash algorithm's identifier.
+    pub fn hash_algo(&self) -> u8 {
+        self.hash_algo
+    }
+
+    /// Returns the subpackets in the hashed area.
+    ///
+    /// These subpackets are protected by the signature.
+    pub fn hashed_area(&self) -> Subpackets<'a> {
+        Subpackets::new(self.hashed_area)
+    }
+
+    /// Returns the subpackets in the unhashed area.
+    ///
+    /// These subpackets are not protected by the signature.
+    pub fn unhashed_area(&self) -> Subpackets<'a> {
+        Subpackets::new(self.unhashed_area)
+    }
+
+    /// Returns the first two bytes of the digest.
+    pub fn digest_prefix(&self) -> &[u8; 2] {
+        &self.digest_prefix
+    }
+
+    /// Returns the algorithm specific signature material.
+    pub fn mpis(&self) -> &'a [u8] {
+        self.mpis
+    }
+
+    /// Returns the body of the first subpacket of type `tag`.
+    ///
+    /// If `hashed_only` is true, only the hashed area is considered.
+    fn subpacket(&self, tag: u8, hashed_only: bool) -> Option<&'a [u8]> {
+        let unhashed = if hashed_only {
+            Subpackets::new(&[])
+        } else {
+            self.unhashed_area()
+        };
+        self.hashed_area().chain(unhashed)
+            .filter_map(|s| s.ok())
+            .find(|s| s.tag() == tag)
+            .map(|s| s.body())
+    }
+
+    /// Returns the signature's creation time as seconds since the
+    /// UNIX epoch.
+    ///
+    /// Only the hashed area is considered.
+    pub fn creation_time(&self) -> Option<u32> {
+        self.subpacket(2, true)
+            .and_then(|b| b.try_into().ok())
+            .map(u32::from_be_bytes)
+    }
+
+    /// Returns the signature's validity period in seconds.
+    ///
+    /// Only the hashed area is considered.  The signature expires
+    /// this many seconds after its creation time.
+    pub fn expiration_time(&self) -> Option<u32> {
+        self.subpacket(3, true)
+            .and_then(|b| b.try_into().ok())
+            .map(u32::from_be_bytes)
+    }
+
+    /// Returns the issuer's key ID.
+    ///
+    /// Both the hashed and the unhashed area are considered.  This
+    /// information is not authenticated.
+    pub fn issuer(&self) -> Option<&'a [u8; 8]> {
+        self.subpacket(16, false)
+            .and_then(|b| b.try_into().ok())
+    }
+
+    /// Returns the issuer's fingerprint.
+    ///
+    /// Both the hashed and the unhashed area are considered.  Only
+    /// version 4 fingerprints are returned.  This information is not
+    /// authenticated.
+    pub fn issuer_fingerprint(&self) -> Option<&'a [u8; 20]> {
+        match self.subpacket(33, false)? {
+            [4, fp @ ..] => fp.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    /// Verifies the signature over a binary document using an Ed25519
+    /// key.
+    ///
+    /// The signature must be over a binary document (type 0), it
+    /// must have been made using EdDSA on Ed25519, and using
+    /// SHA2-256, SHA2-384, or SHA2-512.
+    ///
+    /// Signatures with critical subpackets in the hashed area that
+    /// this crate doesn't understand are rejected.  The understood
+    /// subpackets are the signature creation time, the signature
+    /// expiration time, the issuer, and the issuer fingerprint.
+    ///
+    /// Note: only the cryptographic signature is checked.  In
+    /// particular, neither the signature's nor the key's creation
+    /// and expiration times are checked, and the key's revocation
+    /// status is not considered.  It is up to the caller to decide
+    /// whether that is acceptable, for instance because the key is
+    /// baked into the firmware.
+    ///
+    /// This function is only available if the `ed25519` feature is
+    /// enabled.
+    #[cfg(feature = "ed25519")]
+    pub fn verify_ed25519(&self, key: &PublicKey, data: &[u8]) -> Result<()> {
+        use ed25519_dalek::Verifier;
+        use sha2::Digest;
+
+        if self.typ != 0 {
+            return Err(Error::Unsupported("signature type"));
+        }
+        if self.pk_algo != crate::key::EDDSA {
+            return Err(Error::Unsupported("public key algorithm"));
+        }
+        for s in self.hashed_area() {
+            let s = s?;
+            if s.critical() && ! KNOWN_SUBPACKETS.contains(&s.tag()) {
+                return Err(Error::Unsupported("critical subpacket"));
+            }
+        }
+        let public = key.ed25519()?;
+
+        fn hash<D: Digest>(sig: &Signature, data: &[u8]) -> Result<D> {
+            let len = u32::try_from(sig.hashed.len())
+                .map_err(|_| Error::Malformed("signature too large"))?;
+            let mut h = D::new();
+            h.update(data);
+            h.update(sig.hashed);
+            h.update([4, 0xff]);
+            h.update(len.to_be_bytes());
+            Ok(h)
+        }
+
+        let mut digest = [0; 64];
+        let digest = match self.hash_algo {
+            8 => {
+                digest[..32].copy_from_slice(
+                    &hash::<sha2::Sha256>(self, data)?.finalize());
+                &digest[..32]
+            },
+            9 => {
+                digest[..48].copy_from_slice(
+                    &hash::<sha2::Sha384>(self, data)?.finalize());
+                &digest[..48]
+            },
+            10 => {
+                digest.copy_from_slice(
+                    &hash::<sha2::Sha512>(self, data)?.finalize());
+                &digest[..]
+            },
+            _ => return Err(Error::Unsupported("hash algorithm")),
+        };
+        if digest[..2] != self.digest_prefix {
+            return Err(Error::BadSignature);
+        }
+
+        // The signature consists of two MPIs, R and S.  Leading
+        // zeros are stripped, so we need to add them back.
+        let mut sig = [0; 64];
+        let mut mpis = self.mpis;
+        for half in sig.chunks_mut(32) {
+            let v = crate::take_mpi(&mut mpis)?;
+            if v.len() > 32 {
+                return Err(Error::Malformed("EdDSA signature"));
+            }
+            half[32 - v.len()..].copy_from_slice(v);
+        }
+
+        let public = ed25519_dalek::VerifyingKey::from_bytes(&public)
+            .map_err(|_| Error::Malformed("Ed25519 public key"))?;
+        public.verify(digest, &ed25519_dalek::Signature::from_bytes(&sig))
+            .map_err(|_| Error::BadSignature)
+    }
+}
+
+/// The subpackets understood by this crate.
+///
+/// These are the signature creation time, the signature expiration
+/// time, the issuer, and the issuer fingerprint subpackets.
+#[cfg(feature = "ed25519")]
+const KNOWN_SUBPACKETS: &[u8] = &[2, 3, 16, 33];
+
+/// A signature subpacket.
+///
+/// See [Section 5.2.3.1 of RFC 4880] for details.
+///
+///   [Section 5.2.3.1 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subpacket<'a> {
+    tag: u8,
+    critical: bool,
+    body: &'a [u8],
+}
+
+impl<'a> Subpacket<'a> {
+    /// Returns the subpacket's type.
+    ///
+    /// The critical bit is masked out, see
+    /// [`Subpacket::critical`].
+    pub fn tag(&self) -> u8 {
+        self.tag
+    }
+
+    /// Returns whether the subpacket is marked as critical.
+    pub fn critical(&self) -> bool {
+        self.critical
+    }
+
+    /// Returns the subpacket's body.
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+}
+
+/// Iterates over the subpackets in a subpacket area.
+///
+/// After the first error, the iterator is exhausted.
+#[derive(Debug, Clone)]
+pub struct Subpackets<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Subpackets<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Subpackets { data }
+    }
+
+    /// Parses the subpacket at the front of the data.
+    fn parse(&mut self) -> Result<Subpacket<'a>> {
+        let data = &mut self.data;
+        let len = match take(data, 1)?[0] {
+            o @ 0..=191 => o as usize,
+            o @ 192..=254 =>
+                ((o as usize - 192) << 8) + take(data, 1)?[0] as usize + 192,
+            255 => take_be(data, 4)? as usize,
+        };
+        if len == 0 {
+            return Err(Error::Malformed("empty subpacket"));
+        }
+        let body = take(data, len)?;
+        Ok(Subpacket {
+            tag: body[0] & 0x7f,
+            critical: body[0] & 0x80 != 0,
+            body: &body[1..],
+        })
+    }
+}
+
+impl<'a> Iterator for Subpackets<'a> {
+    type Item = Result<Subpacket<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let r = self.parse();
+        if r.is_err() {
+            self.data = &[];
+        }
+        Some(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sequoia_openpgp as openpgp;
+    use openpgp::parse::Parse;
+
+    const SIG: &[u8] = include_bytes!("../tests/data/ed25519.sig");
+
+    #[test]
+    fn subpackets() {
+        let p = openpgp::Packet::from_bytes(SIG).unwrap();
+        let theirs = if let openpgp::Packet::Signature(s) = p {
+            s
+        } else {
+            panic!("not a signature");
+        };
+        let ours = Signature::from_bytes(SIG).unwrap();
+
+        assert_eq!(ours.typ(), u8::from(theirs.typ()));
+        assert_eq!(ours.pk_algo(), u8::from(theirs.pk_algo()));
+        assert_eq!(ours.hash_algo(), u8::from(theirs.hash_algo()));
+        assert_eq!(ours.digest_prefix(), theirs.digest_prefix());
+        assert_eq!(ours.creation_time(),
+                   theirs.signature_creation_time().map(|t| {
+                       openpgp::types::Timestamp::try_from(t).unwrap().into()
+                   }));
+        assert_eq!(ours.expiration_time(), None);
+        assert_eq!(&ours.issuer().unwrap()[..],
+                   theirs.issuers().next().unwrap().as_bytes());
+        assert_eq!(&ours.issuer_fingerprint().unwrap()[..],
+                   theirs.issuer_fingerprints().next().unwrap().as_bytes());
+        assert_eq!(ours.hashed_area().count(),
+                   theirs.hashed_area().iter().count());
+        assert_eq!(ours.unhashed_area().count(),
+                   theirs.unhashed_area().iter().count());
+        assert!(ours.hashed_area().chain(ours.unhashed_area())
+                .all(|s| s.is_ok()));
+    }
+
+    #[test]
+    fn malformed() {
+        // A subpacket running past the end of the hashed area.
+        let body = b"\x04\x00\x16\x08\x00\x02\x05\x02\x00\x00\xab\xcd";
+        assert_eq!(Signature::from_body(body), Err(Error::Truncated));
+
+        // An empty subpacket.
+        let body = b"\x04\x00\x16\x08\x00\x01\x00\x00\x00\xab\xcd";
+        assert_eq!(Signature::from_body(body),
+                   Err(Error::Malformed("empty subpacket")));
+
+        // Version 3 signatures are not supported.
+        let body = b"\x03\x05\x00";
+        assert_eq!(Signature::from_body(body),
+                   Err(Error::Unsupported("signature version")));
+
+        // Well-formed, with a critical subpacket.
+        let body = b"\x04\x00\x16\x08\x00\x06\x05\x82\x00\x00\x00\x2a\
+                     \x00\x00\xab\xcd";
+        let sig = Signature::from_body(body).unwrap();
+        let s = sig.hashed_area().next().unwrap().unwrap();
+        assert_eq!(s.tag(), 2);
+        assert!(s.critical());
+        assert_eq!(sig.creation_time(), Some(42));
+        assert_eq!(sig.digest_prefix(), &[0xab, 0xcd]);
+        assert_eq!(sig.mpis(), b"");
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn verify_ed25519() {
+        let key = PublicKey::from_bytes(
+            include_bytes!("../tests/data/ed25519.pgp")).unwrap();
+        let sig = Signature::from_bytes(SIG).unwrap();
+
+        assert_eq!(sig.verify_ed25519(&key, b"Hello world."), Ok(()));
+        assert_eq!(sig.verify_ed25519(&key, b"Hello world!"),
+                   Err(Error::BadSignature));
+
+        // The subkey is not an Ed25519 key.
+        let p = crate::Packets::new(include_bytes!("../tests/data/ed25519.pgp"))
+            .filter_map(|p| p.ok())
+            .find(|p| p.tag() == crate::Tag::PublicSubkey)
+            .unwrap();
+        let subkey = PublicKey::try_from(p).unwrap();
+        assert_eq!(sig.verify_ed25519(&subkey, b"Hello world."),
+                   Err(Error::Unsupported("public key algorithm")));
+
+        // Tamper with the signature.
+        let mut tampered = SIG.to_vec();
+        let n = tampered.len();
+        tampered[n - 1] ^= 1;
+        let sig = Signature::from_bytes(&tampered).unwrap();
+        assert_eq!(sig.verify_ed25519(&key, b"Hello world."),
+                   Err(Error::BadSignature));
+
+        // Unknown critical subpackets are rejected.
+        let body = b"\x04\x00\x16\x08\x00\x03\x02\xe4\x00\x00\x00\xab\xcd";
+        let sig = Signature::from_body(body).unwrap();
+        assert_eq!(sig.verify_ed25519(&key, b"Hello world."),
+                   Err(Error::Unsupported("critical subpacket")));
+
+        // But not if they are not marked as critical.
+        let body = b"\x04\x00\x16\x08\x00\x03\x02\x64\x00\x00\x00\xab\xcd";
+        let sig = Signature::from_body(body).unwrap();
+        assert_eq!(sig.verify_ed25519(&key, b"Hello world."),
+                   Err(Error::BadSignature));
+
+        // Nor if they are known.
+        let body = b"\x04\x00\x16\x08\x00\x06\x05\x82\x00\x00\x00\x2a\
+                     \x00\x00\xab\xcd";
+        let sig = Signature::from_body(body).unwrap();
+        assert_eq!(sig.verify_ed25519(&key, b"Hello world."),
+                   Err(Error::BadSignature));
+    }
+}
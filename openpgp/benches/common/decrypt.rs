@@ -8,7 +8,7 @@ use openpgp::parse::stream::{
     VerificationHelper, VerifierBuilder,
 };
 use openpgp::parse::Parse;
-use openpgp::policy::StandardPolicy;
+use openpgp::policy::{Policy, StandardPolicy};
 use openpgp::types::SymmetricAlgorithm;
 use openpgp::{Fingerprint, KeyHandle, Result};
 
@@ -61,13 +61,23 @@ pub fn decrypt_with_password(
     sink: &mut dyn Write,
     ciphertext: &[u8],
     password: &str,
+) -> openpgp::Result<()> {
+    decrypt_with_password_and_policy(
+        sink, ciphertext, password, &StandardPolicy::new())
+}
+
+/// Decrypts the given message using the given password and policy.
+pub fn decrypt_with_password_and_policy(
+    sink: &mut dyn Write,
+    ciphertext: &[u8],
+    password: &str,
+    p: &dyn Policy,
 ) -> openpgp::Result<()> {
     let password = password.into();
     // Make a helper that that feeds the password to the decryptor.
     let helper = PasswordHelper { password };
 
     // Now, create a decryptor with a helper using the given Certs.
-    let p = &StandardPolicy::new();
     let mut decryptor = DecryptorBuilder::from_bytes(ciphertext)?
         .with_policy(p, None, helper)?;
 
@@ -0,0 +1,161 @@
+use criterion::{criterion_group, BenchmarkId, Criterion, Throughput};
+
+use sequoia_openpgp as openpgp;
+use openpgp::cert::Cert;
+use openpgp::parse::Parse;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::stream::{
+    Encryptor2, LiteralWriter, Message, Signer,
+};
+
+use std::io::{self, Write};
+
+#[cfg(feature = "experimental-aed")]
+use crate::common::decrypt;
+
+/// The size of the large message.
+///
+/// Defaults to 128 MiB.  To benchmark with a 1 GiB message, set
+/// `SEQUOIA_BENCH_LARGE_MESSAGE_SIZE=1073741824`.
+fn message_size() -> usize {
+    std::env::var("SEQUOIA_BENCH_LARGE_MESSAGE_SIZE").ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(128 * 1024 * 1024)
+}
+
+/// The sizes of the individual writes.
+///
+/// Applications either write the whole message at once, or copy it
+/// using a moderately sized buffer, like `io::copy` does.
+const WRITE_SIZES: &[usize] = &[8 * 1024, 1024 * 1024, usize::MAX];
+
+/// Writes `bytes` to `w` using writes of at most `write_size` bytes.
+fn write_in_chunks(mut w: Message, bytes: &[u8], write_size: usize)
+                   -> openpgp::Result<()>
+{
+    for chunk in bytes.chunks(write_size.min(bytes.len()).max(1)) {
+        w.write_all(chunk)?;
+    }
+    w.finalize()
+}
+
+/// Encrypts with a password, discarding the output.
+fn encrypt_with_password(bytes: &[u8], write_size: usize)
+                         -> openpgp::Result<()>
+{
+    let message = Message::new(io::sink());
+    let message = Encryptor2::with_passwords(message, Some("ściśle tajne"))
+        .build()?;
+    let w = LiteralWriter::new(message).build()?;
+    write_in_chunks(w, bytes, write_size)
+}
+
+/// Encrypts to a cert, discarding the output.
+fn encrypt_to_cert(bytes: &[u8], cert: &Cert, write_size: usize)
+                   -> openpgp::Result<()>
+{
+    let p = &StandardPolicy::new();
+    let recipients = cert
+        .keys()
+        .with_policy(p, None)
+        .supported()
+        .for_transport_encryption()
+        .for_storage_encryption();
+    let message = Message::new(io::sink());
+    let message = Encryptor2::for_recipients(message, recipients).build()?;
+    let w = LiteralWriter::new(message).build()?;
+    write_in_chunks(w, bytes, write_size)
+}
+
+/// Signs, discarding the output.
+fn sign(bytes: &[u8], cert: &Cert, write_size: usize) -> openpgp::Result<()> {
+    let p = &StandardPolicy::new();
+    let signing_keypair = cert
+        .keys()
+        .with_policy(p, None)
+        .secret()
+        .for_signing()
+        .next()
+        .unwrap()
+        .key()
+        .clone()
+        .into_keypair()?;
+
+    let message = Message::new(io::sink());
+    let message = Signer::new(message, signing_keypair).build()?;
+    let w = LiteralWriter::new(message).build()?;
+    write_in_chunks(w, bytes, write_size)
+}
+
+/// Encrypts with a password using AEAD, returning the ciphertext.
+///
+/// Decrypting AEAD-encrypted messages benefits from the
+/// `parallel-aead-decryption` feature.
+#[cfg(feature = "experimental-aed")]
+fn encrypt_with_password_aead(bytes: &[u8]) -> openpgp::Result<Vec<u8>> {
+    let mut sink = Vec::with_capacity(bytes.len() + 1024 * 1024);
+    let message = Message::new(&mut sink);
+    let message = Encryptor2::with_passwords(message, Some("ściśle tajne"))
+        .aead_algo(Default::default())
+        .build()?;
+    let w = LiteralWriter::new(message).build()?;
+    write_in_chunks(w, bytes, usize::MAX)?;
+    Ok(sink)
+}
+
+fn bench_large_message(c: &mut Criterion) {
+    let testy =
+        Cert::from_bytes(&include_bytes!("../tests/data/keys/testy.pgp")[..])
+            .unwrap();
+    let testy_private = Cert::from_bytes(
+        &include_bytes!("../tests/data/keys/testy-new-private.pgp")[..])
+        .unwrap();
+
+    let message = vec![0; message_size()];
+
+    let mut group = c.benchmark_group("large message");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(message.len() as u64));
+
+    for &write_size in WRITE_SIZES {
+        let id = if write_size == usize::MAX {
+            format!("{}/single write", message.len())
+        } else {
+            format!("{}/{} byte writes", message.len(), write_size)
+        };
+
+        group.bench_with_input(
+            BenchmarkId::new("encrypt password", &id),
+            &message,
+            |b, m| b.iter(|| encrypt_with_password(m, write_size).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("encrypt cert", &id),
+            &message,
+            |b, m| b.iter(|| encrypt_to_cert(m, &testy, write_size).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("sign", &id),
+            &message,
+            |b, m| b.iter(|| sign(m, &testy_private, write_size).unwrap()),
+        );
+    }
+
+    #[cfg(feature = "experimental-aed")]
+    {
+        let ciphertext = encrypt_with_password_aead(&message).unwrap();
+        let mut p = StandardPolicy::new();
+        p.accept_packet_tag(openpgp::packet::Tag::AED);
+        group.bench_with_input(
+            BenchmarkId::new("decrypt password aead", message.len()),
+            &ciphertext,
+            |b, c| b.iter(|| {
+                decrypt::decrypt_with_password_and_policy(
+                    &mut io::sink(), c, "ściśle tajne", &p).unwrap()
+            }),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_large_message);
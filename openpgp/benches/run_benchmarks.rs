@@ -20,6 +20,8 @@ mod parse_cert;
 use parse_cert::benches as parse_cert;
 mod merge_cert;
 use merge_cert::benches as merge_cert;
+mod large_message;
+use large_message::benches as large_message;
 
 // Add all benchmark functions here
 criterion_main!(
@@ -32,4 +34,5 @@ criterion_main!(
     generate_cert,
     parse_cert,
     merge_cert,
+    large_message,
 );
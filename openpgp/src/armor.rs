@@ -15,6 +15,14 @@
 //! Both the reader and the writer allocate memory in the order of the
 //! size of chunks read or written.
 //!
+//! # Streaming
+//!
+//! [`Writer`] armors data written to it, and [`Reader`] dearmors data
+//! read from it.  To transcode in the other direction, for instance,
+//! to armor data that is pulled through a pipeline, use [`Encoder`],
+//! which armors data read from it, and [`Decoder`], which dearmors
+//! data written to it.
+//!
 //! # Examples
 //!
 //! ```rust, no_run
@@ -39,7 +47,7 @@ use std::cmp;
 use std::str;
 use std::borrow::Cow;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use base64::Engine;
@@ -54,8 +62,10 @@ use crate::{vec_resize, vec_truncate};
 
 mod base64_utils;
 use base64_utils::*;
-mod crc;
+pub(crate) mod crc;
 use crc::Crc;
+mod transcode;
+pub use transcode::{Decoder, Encoder};
 
 /// Whether to trace execution by default (on stderr).
 const TRACE: bool = false;
@@ -88,7 +98,7 @@ pub enum Kind {
 }
 assert_send_and_sync!(Kind);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Kind {
     fn arbitrary(g: &mut Gen) -> Self {
         use self::Kind::*;
@@ -538,6 +548,145 @@ pub enum ReaderMode {
 }
 assert_send_and_sync!(ReaderMode);
 
+/// How an ArmorReader should treat the CRC-24 checksum.
+///
+/// The checksum is [deprecated], and implementations must not reject
+/// data because of a bad checksum.  Hence, by default, the checksum
+/// is only checked, and the result is reported by
+/// [`Reader::crc_status`].
+///
+///   [deprecated]: https://www.rfc-editor.org/rfc/rfc9580.html#section-6.1
+///
+/// See [`Reader::with_crc_mode`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcMode {
+    /// Yields the data even if the checksum doesn't match.
+    ///
+    /// This is the default.
+    #[default]
+    Tolerant,
+
+    /// Returns an error if the checksum doesn't match.
+    ///
+    /// A missing checksum is not an error.  Note: the checksum is at
+    /// the end of the armored data, so the error is only returned
+    /// after all of the data has been read.
+    Strict,
+}
+assert_send_and_sync!(CrcMode);
+
+/// The result of checking an ASCII Armor's CRC-24 checksum.
+///
+/// See [`Reader::crc_status`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrcStatus {
+    /// The armored data didn't include a checksum.
+    Missing,
+
+    /// The checksum matches the data.
+    Valid,
+
+    /// The checksum doesn't match the data.
+    Mismatch {
+        /// The checksum included in the armored data.
+        expected: u32,
+        /// The checksum computed over the data.
+        computed: u32,
+    },
+}
+assert_send_and_sync!(CrcStatus);
+
+/// The UTF-8 encoded byte order mark.
+///
+/// Some text editors insert a byte order mark at the start of text
+/// files.  It is never part of binary OpenPGP data, nor of the ASCII
+/// armor header line.
+pub(crate) const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// The number of bytes examined by [`sniff`].
+pub(crate) const SNIFF_LEN: usize = 4096;
+
+/// The result of [`sniff`]ing data.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// The data starts with a valid OpenPGP packet header.
+    Binary,
+
+    /// The data looks like text, which may contain ASCII Armor.
+    Armored,
+
+    /// The data is neither binary OpenPGP data nor text.
+    ///
+    /// This is the case for, e.g., archives or executables.
+    Unknown,
+}
+assert_send_and_sync!(Encoding);
+
+/// Guesses whether `data` is binary OpenPGP data or ASCII armored.
+///
+/// Only the first 4 KiB of `data` are examined.  Binary OpenPGP data
+/// starts with a valid packet header.  Otherwise, the data is
+/// considered ASCII armored if it looks like text, i.e. it doesn't
+/// contain NUL bytes and few other control characters.  Note that
+/// this doesn't check whether the data actually contains an Armor
+/// Header Line, which may be preceded by an arbitrary amount of
+/// text.
+///
+/// This is used by the [`Parse`] implementations to decide whether
+/// to strip ASCII Armor (see [`Dearmor::Auto`]).  For data that is
+/// neither binary OpenPGP data nor text, only the examined prefix is
+/// scanned for an Armor Header Line, so that parsing fails quickly
+/// instead of scanning all of the data.
+///
+///   [`Parse`]: crate::parse::Parse
+///   [`Dearmor::Auto`]: crate::parse::Dearmor::Auto
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::armor::{Encoding, sniff};
+/// use openpgp::cert::prelude::*;
+/// use openpgp::serialize::SerializeInto;
+///
+/// # fn main() -> openpgp::Result<()> {
+/// let (cert, _) = CertBuilder::new().generate()?;
+/// assert_eq!(sniff(&cert.to_vec()?), Encoding::Binary);
+/// assert_eq!(sniff(&cert.armored().to_vec()?), Encoding::Armored);
+/// // A zip archive.
+/// assert_eq!(sniff(b"PK\x03\x04\x14\x00\x00\x00\x08\x00"),
+///            Encoding::Unknown);
+/// # Ok(()) }
+/// ```
+pub fn sniff(data: &[u8]) -> Encoding {
+    let data = &data[..data.len().min(SNIFF_LEN)];
+    if data.starts_with(BOM) {
+        // Binary data doesn't start with a byte order mark.
+        return Encoding::Armored;
+    }
+
+    let mut reader = buffered_reader::Memory::with_cookie(
+        data, Cookie::default());
+    if let Ok(header) = Header::parse(&mut reader) {
+        if header.valid(false).is_ok() {
+            return Encoding::Binary;
+        }
+    }
+
+    let control = data.iter().filter(|&&c| {
+        (c < 0x20 && ! matches!(c, b'\t' | b'\n' | 0x0b | 0x0c | b'\r' | 0x1b))
+            || c == 0x7f
+    }).count();
+    if data.contains(&0) || control * 10 > data.len() {
+        Encoding::Unknown
+    } else {
+        Encoding::Armored
+    }
+}
+
 /// A filter that strips ASCII Armor from a stream of data.
 #[derive(Debug)]
 pub struct Reader<'a> {
@@ -575,6 +724,19 @@ pub struct Reader<'a> {
     prefix: Vec<u8>,
     prefix_remaining: usize,
 
+    /// The checksum over the decoded data.
+    crc: Crc,
+    crc_mode: CrcMode,
+    /// The result of checking the checksum, once we saw it.
+    crc_status: Option<CrcStatus>,
+
+    /// The maximum number of bytes skipped while looking for the
+    /// Armor Header Line.
+    max_scan_bytes: usize,
+    /// The maximum number of lines skipped while looking for the
+    /// Armor Header Line.
+    max_scan_lines: usize,
+
     /// Controls the transformation of messages using the Cleartext
     /// Signature Framework into inline signed messages.
     enable_csft: bool,
@@ -779,11 +941,121 @@ impl<'a> Reader<'a> {
             finalized: false,
             prefix: Vec::with_capacity(0),
             prefix_remaining: 0,
+            crc: Crc::new(),
+            crc_mode: CrcMode::default(),
+            crc_status: None,
+            max_scan_bytes: usize::MAX,
+            max_scan_lines: usize::MAX,
             enable_csft,
             csft: None,
         }
     }
 
+    /// Sets how the CRC-24 checksum is treated.
+    ///
+    /// By default, the data is returned even if the checksum doesn't
+    /// match, see [`CrcMode`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Read};
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::armor::{CrcMode, CrcStatus, Kind, Reader, ReaderMode};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let data =
+    ///     "-----BEGIN PGP ARMORED FILE-----
+    ///
+    ///      SGVsbG8gd29ybGQh
+    ///      =AAAA
+    ///      -----END PGP ARMORED FILE-----";
+    ///
+    /// // By default, the mismatch is only reported.
+    /// let mut reader = Reader::from_bytes(
+    ///     data.as_bytes(), ReaderMode::Tolerant(Some(Kind::File)));
+    /// let mut content = String::new();
+    /// reader.read_to_string(&mut content)?;
+    /// assert_eq!(content, "Hello world!");
+    /// assert_eq!(reader.crc_status(),
+    ///            Some(CrcStatus::Mismatch {
+    ///                expected: 0,
+    ///                computed: 0xb381ae,
+    ///            }));
+    ///
+    /// // In strict mode, it is an error.
+    /// let mut reader = Reader::from_bytes(
+    ///     data.as_bytes(), ReaderMode::Tolerant(Some(Kind::File)))
+    ///     .with_crc_mode(CrcMode::Strict);
+    /// let mut content = String::new();
+    /// assert!(reader.read_to_string(&mut content).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_crc_mode(mut self, mode: CrcMode) -> Self {
+        self.crc_mode = mode;
+        self
+    }
+
+    /// Limits the number of bytes skipped while looking for the
+    /// Armor Header Line.
+    ///
+    /// The reader skips any data preceding the Armor Header Line,
+    /// like the text of an email.  By default, there is no limit.
+    /// If no Armor Header Line is found within the first `max` bytes,
+    /// reading fails.  This prevents scanning large inputs that don't
+    /// contain ASCII Armor at all.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::armor::{Kind, Reader, ReaderMode};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let data = format!(
+    ///     "{}-----BEGIN PGP ARMORED FILE-----
+    ///
+    ///      SGVsbG8gd29ybGQh
+    ///      -----END PGP ARMORED FILE-----",
+    ///     "Lorem ipsum dolor sit amet.\n".repeat(100));
+    ///
+    /// let mut reader = Reader::from_bytes(
+    ///     data.as_bytes(), ReaderMode::Tolerant(Some(Kind::File)))
+    ///     .with_max_scan_bytes(1024);
+    /// let mut content = String::new();
+    /// assert!(reader.read_to_string(&mut content).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_max_scan_bytes(mut self, max: usize) -> Self {
+        self.max_scan_bytes = max;
+        self
+    }
+
+    /// Limits the number of lines skipped while looking for the
+    /// Armor Header Line.
+    ///
+    /// By default, there is no limit.  If no Armor Header Line is
+    /// found within the first `max` + 1 lines, reading fails.  See
+    /// [`Reader::with_max_scan_bytes`].
+    pub fn with_max_scan_lines(mut self, max: usize) -> Self {
+        self.max_scan_lines = max;
+        self
+    }
+
+    /// Returns the result of checking the CRC-24 checksum.
+    ///
+    /// The checksum follows the armored data.  If the end of the
+    /// armored data has not been reached yet, this function returns
+    /// None.
+    ///
+    /// See [`Reader::with_crc_mode`] for an example.
+    pub fn crc_status(&self) -> Option<CrcStatus> {
+        self.crc_status
+    }
+
     /// Returns the kind of data this reader is for.
     ///
     /// Useful if the kind of data is not known in advance.  If the
@@ -913,13 +1185,43 @@ impl<'a> Reader<'a> {
             &START_CHARS_VERY_TOLERANT[..]
         };
 
+        let max_scan_bytes = self.max_scan_bytes;
+        let check_scan_bytes = |skipped: usize| {
+            if skipped > max_scan_bytes {
+                Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("No Armor Header Line found in the first {} bytes",
+                            max_scan_bytes)))
+            } else {
+                Ok(())
+            }
+        };
+
+        let max_scan_lines = self.max_scan_lines;
+        let check_scan_lines = |skipped_lines: usize| {
+            if skipped_lines > max_scan_lines {
+                Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("No Armor Header Line found in the first {} lines",
+                            max_scan_lines)))
+            } else {
+                Ok(())
+            }
+        };
+
         let mut lines = 0;
+        let mut skipped = 0;
+        let mut skipped_lines = 0;
         let mut prefix = Vec::new();
         let n = 'search: loop {
             if lines > 0 {
                 // Find the start of the next line.
-                self.source.drop_through(&[b'\n'], true)?;
+                let (_, dropped) = self.source.drop_through(&[b'\n'], true)?;
+                skipped += dropped;
+                skipped_lines += 1;
                 crate::vec_truncate(&mut prefix, 0);
+                check_scan_bytes(skipped)?;
+                check_scan_lines(skipped_lines)?;
             }
             lines += 1;
 
@@ -934,10 +1236,14 @@ impl<'a> Reader<'a> {
                 if c == b'\n' {
                     // We found a newline while walking whitespace, reset prefix
                     crate::vec_truncate(&mut prefix, 0);
+                    skipped_lines += 1;
+                    check_scan_lines(skipped_lines)?;
                 } else {
                     prefix.push(self.source.data_hard(1)?[0]);
                 }
                 self.source.consume(1);
+                skipped += 1;
+                check_scan_bytes(skipped)?;
             }
 
             // Don't bother if the first byte is not plausible.
@@ -945,6 +1251,7 @@ impl<'a> Reader<'a> {
             if !start_chars.binary_search(&start).is_ok()
             {
                 self.source.consume(1);
+                skipped += 1;
                 continue;
             }
 
@@ -1264,6 +1571,7 @@ impl<'a> Reader<'a> {
                 // include padding.)
                 self.decode_buffer = base64std.decode(&base64data)
                     .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                self.crc.update(&self.decode_buffer);
 
                 let copied = cmp::min(buf.len(), self.decode_buffer.len());
                 buf[..copied].copy_from_slice(&self.decode_buffer[..copied]);
@@ -1273,8 +1581,10 @@ impl<'a> Reader<'a> {
             } else {
                 // We can decode directly into the caller-supplied
                 // buffer.
-                base64std.decode_slice(&base64data, buf)
-                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?
+                let decoded = base64std.decode_slice(&base64data, buf)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                self.crc.update(&buf[..decoded]);
+                decoded
             };
 
             self.prefix_remaining = prefix_remaining;
@@ -1307,13 +1617,37 @@ impl<'a> Reader<'a> {
                     && data[1..5].iter().all(is_base64_char)
                 {
                     /* Found.  */
+                    let mut expected = [0; 4];
+                    base64std.decode_slice(&data[1..5], &mut expected[1..])
+                        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                    let expected = u32::from_be_bytes(expected);
+                    let computed = self.crc.finalize();
+
+                    self.crc_status = Some(if expected == computed {
+                        CrcStatus::Valid
+                    } else {
+                        CrcStatus::Mismatch { expected, computed }
+                    });
                     5
                 } else {
+                    self.crc_status = Some(CrcStatus::Missing);
                     0
                 }
             };
             self.source.consume(consumed);
 
+            if self.crc_mode == CrcMode::Strict {
+                if let Some(CrcStatus::Mismatch { expected, computed })
+                    = self.crc_status
+                {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("ASCII Armor checksum mismatch: \
+                                 expected {:06X}, computed {:06X}",
+                                expected, computed)));
+                }
+            }
+
             // Skip any expected prefix
             self.source.data_consume_hard(self.prefix.len())?;
             // Look for a footer.
@@ -1949,7 +2283,10 @@ mod test {
                -----END PGP ARMORED FILE-----\n"[..]);
     }
 
-    use super::{Reader, ReaderMode};
+    use super::{CrcMode, CrcStatus, Reader, ReaderMode};
+    use super::{BOM, Encoding, sniff};
+    use super::base64std;
+    use base64::Engine;
 
     #[test]
     fn dearmor_robust() {
@@ -2003,6 +2340,20 @@ mod test {
         // > the CRC24 footer is present, missing, malformed, or
         // > disagrees with the computed CRC24 sum.
         assert!(e.is_ok());
+        let mut data = buf[..e.unwrap()].to_vec();
+        r.read_to_end(&mut data).unwrap();
+        assert_eq!(&data[..], TEST_BIN[0]);
+        assert!(matches!(r.crc_status(), Some(CrcStatus::Mismatch { .. })));
+
+        // Unless we are strict.
+        let mut r = Reader::from_reader(
+            Cursor::new(
+                &include_bytes!("../tests/data/armor/test-0.bad-crc.asc")[..]),
+            ReaderMode::Tolerant(Some(Kind::File)))
+            .with_crc_mode(CrcMode::Strict);
+        let mut data = Vec::new();
+        let e = r.read_to_end(&mut data).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
     }
 
     #[test]
@@ -2033,6 +2384,8 @@ mod test {
         let mut buf = [0; 5];
         let e = r.read(&mut buf);
         assert!(e.unwrap() == 1 && buf[0] == 0xde);
+        assert_eq!(r.read(&mut buf).unwrap(), 0);
+        assert_eq!(r.crc_status(), Some(CrcStatus::Missing));
     }
 
     #[test]
@@ -2092,6 +2445,79 @@ mod test {
         assert!(e.is_err());
     }
 
+    #[test]
+    fn scan_limits() {
+        let armored =
+            include_bytes!("../tests/data/armor/test-3.with-headers.asc");
+        let mut b: Vec<u8> = "Some\ngarbage\nlines\n".into();
+        b.extend_from_slice(armored);
+
+        let read = |r: Reader| -> std::io::Result<Vec<u8>> {
+            let mut r = r;
+            let mut buf = Vec::new();
+            r.read_to_end(&mut buf)?;
+            Ok(buf)
+        };
+
+        // Within the limits.
+        for mode in [ReaderMode::Tolerant(None), ReaderMode::VeryTolerant] {
+            let r = Reader::from_bytes(&b, mode)
+                .with_max_scan_bytes(19)
+                .with_max_scan_lines(3);
+            assert_eq!(read(r).unwrap(), TEST_BIN[3]);
+        }
+
+        // Too many bytes.
+        let r = Reader::from_bytes(&b, ReaderMode::Tolerant(None))
+            .with_max_scan_bytes(18);
+        assert_eq!(read(r).unwrap_err().kind(),
+                   std::io::ErrorKind::InvalidInput);
+
+        // Too many lines.
+        let r = Reader::from_bytes(&b, ReaderMode::Tolerant(None))
+            .with_max_scan_lines(2);
+        assert_eq!(read(r).unwrap_err().kind(),
+                   std::io::ErrorKind::InvalidInput);
+
+        // A lot of whitespace counts, too.
+        let mut b = b" \n".repeat(2048);
+        b.extend_from_slice(armored);
+        let r = Reader::from_bytes(&b, ReaderMode::Tolerant(None))
+            .with_max_scan_bytes(1024);
+        assert!(read(r).is_err());
+        let r = Reader::from_bytes(&b, ReaderMode::Tolerant(None))
+            .with_max_scan_lines(1024);
+        assert!(read(r).is_err());
+        let r = Reader::from_bytes(&b, ReaderMode::Tolerant(None))
+            .with_max_scan_bytes(4096);
+        assert_eq!(read(r).unwrap(), TEST_BIN[3]);
+    }
+
+    #[test]
+    fn sniffer() -> crate::Result<()> {
+        use crate::parse::{Parse, PacketParser};
+
+        for asc in TEST_ASC.iter() {
+            assert_eq!(sniff(asc), Encoding::Armored);
+        }
+
+        let literal = crate::tests::message("literal-mode-t-partial-body.gpg");
+        assert_eq!(sniff(literal), Encoding::Binary);
+
+        let mut b = BOM.to_vec();
+        b.extend_from_slice(TEST_ASC[3]);
+        assert_eq!(sniff(&b), Encoding::Armored);
+
+        // Binary data that is not OpenPGP isn't scanned for ASCII
+        // Armor.
+        let mut b = vec![0u8; 1 << 20];
+        b[0] = 0x23;
+        assert_eq!(sniff(&b), Encoding::Unknown);
+        b.extend_from_slice(TEST_ASC[3]);
+        assert!(PacketParser::from_bytes(&b).is_err());
+        Ok(())
+    }
+
     #[test]
     fn dearmor() {
         for (bin, asc) in TEST_BIN.iter().zip(TEST_ASC.iter()) {
@@ -2221,6 +2647,56 @@ mod test {
         }
     }
 
+    quickcheck! {
+        fn crc_mismatch(payload: Vec<u8>, bit: usize) -> bool {
+            if payload.is_empty() {
+                return true;
+            }
+
+            let mut w = Writer::new(Vec::new(), Kind::File).unwrap();
+            w.write_all(&payload).unwrap();
+            let encoded = w.finalize().unwrap();
+
+            let read = |encoded: &[u8], mode| {
+                let mut r = Reader::from_bytes(
+                    encoded, ReaderMode::Tolerant(Some(Kind::File)))
+                    .with_crc_mode(mode);
+                let mut recovered = Vec::new();
+                let result = r.read_to_end(&mut recovered);
+                (result.map(|_| recovered), r.crc_status())
+            };
+
+            let (recovered, status) = read(&encoded, CrcMode::Strict);
+            assert_eq!(recovered.unwrap(), payload);
+            assert_eq!(status, Some(CrcStatus::Valid));
+
+            // Flip a bit in the checksum.
+            let crc = encoded.windows(2).rposition(|w| w == b"\n=")
+                .expect("has a checksum") + 2;
+            let crc_bytes = base64std.decode(&encoded[crc..crc + 4]).unwrap();
+            let mut crc_bytes = [0, crc_bytes[0], crc_bytes[1], crc_bytes[2]];
+            let expected = u32::from_be_bytes(crc_bytes);
+            crc_bytes[1 + bit / 8 % 3] ^= 1 << (bit % 8);
+            let mut corrupted = encoded[..crc].to_vec();
+            corrupted.extend_from_slice(
+                base64std.encode(&crc_bytes[1..]).as_bytes());
+            corrupted.extend_from_slice(&encoded[crc + 4..]);
+
+            // In tolerant mode, the data is returned, and the
+            // mismatch is reported.
+            let (recovered, status) = read(&corrupted, CrcMode::Tolerant);
+            assert_eq!(recovered.unwrap(), payload);
+            assert_eq!(status, Some(CrcStatus::Mismatch {
+                expected: u32::from_be_bytes(crc_bytes),
+                computed: expected,
+            }));
+
+            // In strict mode, it is an error.
+            let (recovered, _) = read(&corrupted, CrcMode::Strict);
+            recovered.is_err()
+        }
+    }
+
     /// Tests issue #404, zero-sized reads break reader.
     ///
     /// See: https://gitlab.com/sequoia-pgp/sequoia/-/issues/404
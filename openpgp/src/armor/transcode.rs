@@ -0,0 +1,523 @@
+//! Streaming adapters for transcoding between binary and armored data.
+//!
+//! [`Writer`] is a [`Write`] adapter that armors, and [`Reader`] is
+//! a [`Read`] adapter that dearmors.  This module provides the
+//! opposite adapters: [`Encoder`] is a `Read` adapter that armors,
+//! and [`Decoder`] is a `Write` adapter that dearmors.  Both use a
+//! constant amount of memory, independent of the size of the data.
+
+use super::*;
+
+/// The amount of binary data read from the source at once.
+const CHUNK_SIZE: usize = 4096;
+
+/// The maximum length of an armor line that is not base64 data.
+///
+/// Header lines, the checksum, and the footer are buffered until the
+/// end of the line.  Base64 data is decoded as it arrives.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// A reader that applies ASCII Armor to the data read from it.
+///
+/// This is the pull-based counterpart to [`Writer`]: it reads binary
+/// data from the underlying reader, and returns the armored data.
+///
+/// Like [`Writer`], if the underlying reader is empty, no armor is
+/// emitted.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Read;
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::armor::{Encoder, Kind};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut encoder = Encoder::with_headers(&b"Hello world!"[..], Kind::File,
+///     vec![("Comment", "Greeting")])?;
+/// let mut armored = String::new();
+/// encoder.read_to_string(&mut armored)?;
+/// assert_eq!(armored,
+///     "-----BEGIN PGP ARMORED FILE-----
+/// Comment: Greeting
+///
+/// SGVsbG8gd29ybGQh
+/// =s4Gu
+/// -----END PGP ARMORED FILE-----
+/// ");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Encoder<R: Read> {
+    source: R,
+    // `None` once the source has been exhausted.
+    writer: Option<Writer<Vec<u8>>>,
+    // Armored data that has not yet been returned.
+    pending: Vec<u8>,
+    cursor: usize,
+    scratch: Vec<u8>,
+}
+assert_send_and_sync!(Encoder<R> where R: Read);
+
+impl<R: Read> Encoder<R> {
+    /// Constructs a new reader armoring the given type of data.
+    pub fn new(source: R, kind: Kind) -> Result<Self> {
+        Self::with_headers(source, kind, Option::<(&str, &str)>::None)
+    }
+
+    /// Constructs a new reader armoring the given type of data,
+    /// emitting the given headers.
+    pub fn with_headers<I, K, V>(source: R, kind: Kind, headers: I)
+                                 -> Result<Self>
+        where I: IntoIterator<Item = (K, V)>,
+              K: AsRef<str>,
+              V: AsRef<str>,
+    {
+        Ok(Encoder {
+            source,
+            writer: Some(Writer::with_headers(Vec::new(), kind, headers)?),
+            pending: Vec::new(),
+            cursor: 0,
+            scratch: vec![0; CHUNK_SIZE],
+        })
+    }
+
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.source
+    }
+
+    /// Returns a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.source
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl<R: Read> Read for Encoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.cursor == self.pending.len() {
+            let writer = if let Some(w) = self.writer.as_mut() {
+                w
+            } else {
+                return Ok(0);
+            };
+
+            let n = self.source.read(&mut self.scratch)?;
+            self.cursor = 0;
+            if n == 0 {
+                self.pending = self.writer.take().expect("checked above")
+                    .finalize()?;
+            } else {
+                writer.write_all(&self.scratch[..n])?;
+                // Reuse the buffer for the next chunk.
+                std::mem::swap(&mut self.pending, writer.get_mut());
+                crate::vec_truncate(writer.get_mut(), 0);
+            }
+        }
+
+        let n = buf.len().min(self.pending.len() - self.cursor);
+        buf[..n].copy_from_slice(&self.pending[self.cursor..self.cursor + n]);
+        self.cursor += n;
+        Ok(n)
+    }
+}
+
+/// Where a [`Decoder`] is in the armored data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Looking for the header line.
+    Begin,
+    /// Reading the armor headers.
+    Headers,
+    /// Reading the base64 encoded data.
+    Body,
+    /// The footer has been read.
+    Done,
+}
+
+/// A writer that removes ASCII Armor from the data written to it.
+///
+/// This is the push-based counterpart to [`Reader`]: armored data
+/// written to it is decoded, and the binary data is written to the
+/// underlying writer as soon as it is available.
+///
+/// Unlike [`Reader`], `Decoder` only understands well-formed ASCII
+/// Armor: text before the header line is skipped, but the armor
+/// itself must be intact.  Only a single armored block is decoded,
+/// any data after the footer is ignored.  Messages using the
+/// Cleartext Signature Framework are not supported.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::armor::{Decoder, Kind};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut decoder = Decoder::new(Vec::new());
+/// decoder.write_all(b"-----BEGIN PGP ARMORED FILE-----
+/// Comment: Greeting
+///
+/// SGVsbG8gd29ybGQh
+/// =s4Gu
+/// -----END PGP ARMORED FILE-----
+/// ")?;
+/// assert_eq!(decoder.kind(), Some(Kind::File));
+/// assert_eq!(decoder.headers(),
+///            &[("Comment".into(), "Greeting".into())]);
+/// assert_eq!(decoder.finalize()?, b"Hello world!");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Decoder<W: Write> {
+    sink: W,
+    state: State,
+    kind: Option<Kind>,
+    headers: Vec<(String, String)>,
+    crc: Crc,
+    crc_mode: CrcMode,
+    crc_status: Option<CrcStatus>,
+
+    // The start of the current line, if it hasn't been processed
+    // yet.
+    line: Vec<u8>,
+    // Whether the rest of the current line is base64 data.
+    in_data: bool,
+    // Base64 characters that have not yet been decoded.
+    stash: Vec<u8>,
+    scratch: Vec<u8>,
+}
+assert_send_and_sync!(Decoder<W> where W: Write);
+
+impl<W: Write> Decoder<W> {
+    /// Constructs a new filter writing the binary data to `sink`.
+    pub fn new(sink: W) -> Self {
+        Decoder {
+            sink,
+            state: State::Begin,
+            kind: None,
+            headers: Vec::new(),
+            crc: Crc::new(),
+            crc_mode: CrcMode::default(),
+            crc_status: None,
+            line: Vec::new(),
+            in_data: false,
+            stash: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Changes how the CRC-24 checksum is handled.
+    ///
+    /// See [`Reader::with_crc_mode`].  Note: the checksum is at the
+    /// end of the armored data, so in [`CrcMode::Strict`], all of
+    /// the data has already been written to the underlying writer
+    /// when the error is returned.
+    pub fn with_crc_mode(mut self, mode: CrcMode) -> Self {
+        self.crc_mode = mode;
+        self
+    }
+
+    /// Returns the result of checking the CRC-24 checksum.
+    ///
+    /// This returns `None` until the checksum has been read.
+    pub fn crc_status(&self) -> Option<CrcStatus> {
+        self.crc_status
+    }
+
+    /// Returns the kind of data, once the header line has been read.
+    pub fn kind(&self) -> Option<Kind> {
+        self.kind
+    }
+
+    /// Returns the armor headers read so far.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.sink
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.sink
+    }
+
+    /// Checks that the armored data is complete, and returns the
+    /// inner writer.
+    pub fn finalize(self) -> Result<W> {
+        if self.state != State::Done {
+            return Err(Error::new(ErrorKind::UnexpectedEof,
+                                  "ASCII Armor is truncated"));
+        }
+        Ok(self.sink)
+    }
+
+    /// Decodes base64 data, writing the result to the sink.
+    fn data(&mut self, data: &[u8]) -> Result<()> {
+        self.stash.extend(data.iter().filter(|b| ! b.is_ascii_whitespace()));
+
+        let n = self.stash.len() / 4 * 4;
+        if n > 0 {
+            vec_resize(&mut self.scratch, n / 4 * 3);
+            let decoded = base64std.decode_slice(&self.stash[..n],
+                                                 &mut self.scratch)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            self.crc.update(&self.scratch[..decoded]);
+            self.sink.write_all(&self.scratch[..decoded])?;
+            self.stash.drain(..n);
+        }
+        Ok(())
+    }
+
+    /// Processes a line that is not base64 data.
+    fn process_line(&mut self, line: &[u8]) -> Result<()> {
+        let malformed = |msg: &str| Err(Error::new(ErrorKind::InvalidData,
+                                                   msg.to_string()));
+        let line = line.strip_prefix(BOM).unwrap_or(line);
+        let line = trim_end(line);
+
+        match self.state {
+            State::Begin => match Label::detect_header(trim_start(line)) {
+                Some((label, _)) => {
+                    self.kind = Some(Kind::try_from(label).map_err(
+                        |e| Error::new(ErrorKind::InvalidData, e))?);
+                    self.state = State::Headers;
+                },
+                // Skip any text before the header line.
+                None => (),
+            },
+
+            State::Headers => if line.is_empty() {
+                self.state = State::Body;
+            } else if let Some(i) = line.windows(2).position(|w| w == b": ") {
+                self.headers.push((
+                    String::from_utf8_lossy(&line[..i]).into(),
+                    String::from_utf8_lossy(&line[i + 2..]).into()));
+            } else {
+                return malformed("Malformed ASCII Armor header");
+            },
+
+            State::Body => if line.is_empty() {
+                // Skip empty lines.
+            } else if line[0] == b'=' {
+                if line.len() != 5 || ! line[1..].iter().all(is_base64_char) {
+                    return malformed("Malformed ASCII Armor checksum");
+                }
+                let mut expected = [0; 4];
+                base64std.decode_slice(&line[1..], &mut expected[1..])
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                let expected = u32::from_be_bytes(expected);
+                let computed = self.crc.finalize();
+                self.crc_status = Some(if expected == computed {
+                    CrcStatus::Valid
+                } else {
+                    CrcStatus::Mismatch { expected, computed }
+                });
+            } else if self.kind.expect("set in State::Begin")
+                .detect_footer(line).is_some()
+            {
+                if ! self.stash.is_empty() {
+                    return malformed("Truncated base64 data");
+                }
+                if self.crc_status.is_none() {
+                    self.crc_status = Some(CrcStatus::Missing);
+                }
+                if self.crc_mode == CrcMode::Strict {
+                    if let Some(CrcStatus::Mismatch { expected, computed })
+                        = self.crc_status
+                    {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("ASCII Armor checksum mismatch: \
+                                     expected {:06X}, computed {:06X}",
+                                    expected, computed)));
+                    }
+                }
+                self.state = State::Done;
+            } else {
+                return malformed("Malformed ASCII Armor footer");
+            },
+
+            // Ignore anything after the footer.
+            State::Done => (),
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Decoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut input = buf;
+        while ! input.is_empty() {
+            let (chunk, eol) = match input.iter().position(|&b| b == b'\n') {
+                Some(i) => (&input[..i], true),
+                None => (input, false),
+            };
+            input = &input[chunk.len() + eol as usize..];
+
+            if self.in_data {
+                self.data(chunk)?;
+            } else {
+                let mut line = std::mem::take(&mut self.line);
+                line.extend_from_slice(chunk);
+                let line_ = trim_start(&line);
+
+                if self.state == State::Body
+                    && ! line_.is_empty()
+                    && line_[0] != b'='
+                    && dash_prefix(line_).0.is_empty()
+                {
+                    // This is base64 data, decode it right away.
+                    self.in_data = true;
+                    self.data(&line)?;
+                    crate::vec_truncate(&mut line, 0);
+                } else if eol {
+                    self.process_line(&line)?;
+                    crate::vec_truncate(&mut line, 0);
+                } else if line.len() > MAX_LINE_LENGTH {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                                          "ASCII Armor line too long"));
+                }
+                self.line = line;
+            }
+
+            if eol {
+                self.in_data = false;
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.sink.flush()
+    }
+}
+
+/// Strips leading whitespace.
+fn trim_start(mut line: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = line {
+        if ! first.is_ascii_whitespace() {
+            break;
+        }
+        line = rest;
+    }
+    line
+}
+
+/// Strips trailing whitespace, including carriage returns.
+fn trim_end(mut line: &[u8]) -> &[u8] {
+    while let [rest @ .., last] = line {
+        if ! last.is_ascii_whitespace() {
+            break;
+        }
+        line = rest;
+    }
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Armors `data` using `Writer`.
+    fn armor(data: &[u8], kind: Kind) -> Vec<u8> {
+        let mut w = Writer::with_headers(Vec::new(), kind,
+                                         vec![("Comment", "Test")]).unwrap();
+        w.write_all(data).unwrap();
+        w.finalize().unwrap()
+    }
+
+    /// Reads `r` in chunks of `n` bytes.
+    fn read_in_chunks<R: Read>(mut r: R, n: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut buf = vec![0; n];
+        loop {
+            let got = r.read(&mut buf).unwrap();
+            if got == 0 {
+                return out;
+            }
+            out.extend_from_slice(&buf[..got]);
+        }
+    }
+
+    #[test]
+    fn encoder() {
+        for len in [0, 1, 2, 3, 47, 48, 49, CHUNK_SIZE, 3 * CHUNK_SIZE + 1] {
+            let data = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+            let expected = armor(&data, Kind::Message);
+            for n in [1, 7, 4096] {
+                let e = Encoder::with_headers(&data[..], Kind::Message,
+                                              vec![("Comment", "Test")])
+                    .unwrap();
+                assert_eq!(read_in_chunks(e, n), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn decoder() {
+        for len in [1, 2, 3, 47, 48, 49, CHUNK_SIZE, 3 * CHUNK_SIZE + 1] {
+            let data = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+            let mut armored = b"Some text.\r\n".to_vec();
+            armored.extend_from_slice(&armor(&data, Kind::Signature));
+            armored.extend_from_slice(b"Trailing text.\n");
+
+            for n in [1, 7, 4096] {
+                let mut d = Decoder::new(Vec::new());
+                for chunk in armored.chunks(n) {
+                    d.write_all(chunk).unwrap();
+                }
+                assert_eq!(d.kind(), Some(Kind::Signature));
+                assert_eq!(d.headers(), &[("Comment".into(), "Test".into())]);
+                assert_eq!(d.crc_status(), Some(CrcStatus::Valid));
+                assert_eq!(d.finalize().unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn decoder_errors() {
+        let armored = armor(b"Hello world!", Kind::File);
+
+        // Truncated.
+        let mut d = Decoder::new(Vec::new());
+        d.write_all(&armored[..armored.len() - 10]).unwrap();
+        assert!(d.finalize().is_err());
+
+        // Bad checksum.
+        let bad = String::from_utf8(armored.clone()).unwrap()
+            .replace("=s4Gu", "=AAAA");
+        let mut d = Decoder::new(Vec::new());
+        d.write_all(bad.as_bytes()).unwrap();
+        assert!(matches!(d.crc_status(), Some(CrcStatus::Mismatch { .. })));
+        assert_eq!(d.finalize().unwrap(), b"Hello world!");
+        let mut d = Decoder::new(Vec::new()).with_crc_mode(CrcMode::Strict);
+        assert!(d.write_all(bad.as_bytes()).is_err());
+
+        // Mismatched footer.
+        let bad = String::from_utf8(armored).unwrap()
+            .replace("END PGP ARMORED FILE", "END PGP MESSAGE");
+        let mut d = Decoder::new(Vec::new());
+        assert!(d.write_all(bad.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn transcode() {
+        let data = crate::tests::message("a-cypherpunks-manifesto.txt");
+        let mut d = Decoder::new(Vec::new());
+        io::copy(&mut Encoder::new(data, Kind::File).unwrap(), &mut d)
+            .unwrap();
+        assert_eq!(d.finalize().unwrap(), data);
+    }
+}
@@ -131,6 +131,7 @@ use std::io;
 use std::collections::btree_map::BTreeMap;
 use std::collections::btree_map::Entry;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::hash::Hasher;
@@ -138,11 +139,13 @@ use std::path::Path;
 use std::mem;
 use std::fmt;
 use std::time;
+use std::sync::{Arc, Mutex};
 
 use buffered_reader::BufferedReader;
 
 use crate::{
     crypto::{
+        Password,
         Signer,
         hash::Digest,
     },
@@ -171,17 +174,28 @@ use crate::types::{
     CompressionAlgorithm,
     Features,
     HashAlgorithm,
+    KeyFlags,
     KeyServerPreferences,
     ReasonForRevocation,
     RevocationKey,
     RevocationStatus,
+    RevocationType,
     SymmetricAlgorithm,
 };
 
+#[cfg(any(test, feature = "quickcheck"))]
+use quickcheck::{Arbitrary, Gen};
+
 pub mod amalgamation;
+mod bare_revocation;
+pub use bare_revocation::{BareRevocation, PendingRevocations, RevocationClass};
 mod builder;
 mod bindings;
 pub mod bundle;
+#[cfg(feature = "cert-cache")]
+mod cache;
+#[cfg(feature = "keyring-index")]
+pub mod index;
 use bundle::{
     ComponentBundles,
     UserIDBundles,
@@ -190,12 +204,21 @@ use bundle::{
     UnknownBundles,
 };
 mod lazysigs;
+use lazysigs::SigState;
+mod limits;
+mod paper;
+pub mod lint;
 mod parser;
 pub mod raw;
 mod revoke;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 
 pub use self::builder::{CertBuilder, CipherSuite, KeyBuilder, SubkeyBuilder};
 
+pub use limits::CertificationLimits;
+pub use paper::PaperBackup;
+
 pub use parser::{
     CertParser,
 };
@@ -208,6 +231,7 @@ pub(crate) use parser::{
 };
 
 pub use revoke::{
+    CertificationRevocationBuilder,
     SubkeyRevocationBuilder,
     CertRevocationBuilder,
     UserAttributeRevocationBuilder,
@@ -273,6 +297,11 @@ impl fmt::Display for Cert {
 /// then the direct key signature is checked.  This policy and its
 /// justification are described in [Section 5.2.3.3] of RFC 4880.
 ///
+/// When a key is addressed, e.g. using a [`ValidKeyAmalgamation`],
+/// the primary key is treated like the certificate.  For subkeys,
+/// the subkey's binding signature is checked first, and then the
+/// certificate as described above.
+///
 /// Note: User IDs may be stripped.  For instance, the [WKD] standard
 /// requires User IDs that are unrelated to the WKD's domain be
 /// stripped from the certificate prior to publication.  As such, any
@@ -283,6 +312,7 @@ impl fmt::Display for Cert {
 /// should also create a direct key signature with this information.
 ///
 /// [Section 5.2.3.3]: https://tools.ietf.org/html/rfc4880#section-5.2.3.3
+/// [`ValidKeyAmalgamation`]: amalgamation::key::ValidKeyAmalgamation
 /// [WKD]: https://tools.ietf.org/html/draft-koch-openpgp-webkey-service-09#section-5
 /// [without any User ID packets]: https://tools.ietf.org/html/draft-ietf-openpgp-rfc4880bis-09#section-11.1
 ///
@@ -452,7 +482,11 @@ pub trait Preferences<'a>: seal::Sealed {
 /// comes first.  Components are sorted, but in an undefined manner
 /// (i.e., when parsing the same certificate multiple times, the
 /// components will be in the same order, but we reserve the right to
-/// change the sort function between versions).
+/// change the sort function between versions).  If you need a
+/// serialized form that is stable across versions, use
+/// [`Cert::canonical_serialization`].
+///
+/// [`Cert::canonical_serialization`]: Cert::canonical_serialization()
 ///
 /// # Secret Keys
 ///
@@ -882,6 +916,108 @@ impl Cert {
             .build(primary_signer, self, None)
     }
 
+    /// Checks whether a revocation applies to this certificate.
+    ///
+    /// This checks whether `sig` is a valid revocation of the
+    /// certificate, or one of its subkeys, user IDs, or user
+    /// attributes, without merging it into the certificate.  On
+    /// success, it returns the revoked component, and whether the
+    /// revocation is a hard or a soft revocation.  A revocation
+    /// without a [Reason for Revocation subpacket] is considered to
+    /// be a hard revocation.
+    ///
+    /// The revocation must have been made by the certificate's
+    /// primary key.  Revocations made by designated revokers cannot
+    /// be verified without the revoker's certificate, and are
+    /// rejected.
+    ///
+    /// Note: this only checks that the revocation is
+    /// cryptographically valid.  It does not check the revocation
+    /// against a [`Policy`].
+    ///
+    ///   [Reason for Revocation subpacket]: https://tools.ietf.org/html/rfc4880#section-5.2.3.23
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::cert::RevocationClass;
+    /// use openpgp::types::{ReasonForRevocation, RevocationType};
+    ///
+    /// let (cert, rev) = CertBuilder::new()
+    ///     .add_userid("Alice")
+    ///     .generate()?;
+    /// assert_eq!(cert.validate_revocation(&rev)?,
+    ///            RevocationClass::PrimaryKey(RevocationType::Hard));
+    ///
+    /// let mut signer = cert.primary_key().key().clone()
+    ///     .parts_into_secret()?.into_keypair()?;
+    /// let ua = cert.userids().next().unwrap();
+    /// let rev = UserIDRevocationBuilder::new()
+    ///     .set_reason_for_revocation(ReasonForRevocation::UIDRetired,
+    ///                                b"Left the company")?
+    ///     .build(&mut signer, &cert, ua.userid(), None)?;
+    /// assert_eq!(cert.validate_revocation(&rev)?,
+    ///            RevocationClass::UserID(ua.userid(), RevocationType::Soft));
+    /// # Ok(()) }
+    /// ```
+    pub fn validate_revocation(&self, sig: &Signature)
+                               -> Result<RevocationClass<'_>>
+    {
+        let pk = self.primary_key().key();
+        let issuers = sig.get_issuers();
+        if ! issuers.is_empty()
+            && ! issuers.iter().any(|h| h.aliases(pk.key_handle()))
+        {
+            return Err(Error::InvalidArgument(format!(
+                "Revocation was not issued by {}", pk.fingerprint())).into());
+        }
+
+        let typ = sig.reason_for_revocation()
+            .map(|(r, _)| r.revocation_type())
+            // If there is no Reason for Revocation packet, assume
+            // that it is a hard revocation.
+            .unwrap_or(RevocationType::Hard);
+
+        match sig.typ() {
+            SignatureType::KeyRevocation => {
+                sig.verify_primary_key_revocation(pk, pk)?;
+                return Ok(RevocationClass::PrimaryKey(typ));
+            },
+            SignatureType::SubkeyRevocation => {
+                for ka in self.keys().subkeys() {
+                    if sig.verify_subkey_revocation(pk, pk, ka.key()).is_ok() {
+                        return Ok(RevocationClass::Subkey(ka.key(), typ));
+                    }
+                }
+            },
+            SignatureType::CertificationRevocation => {
+                for ua in self.userids() {
+                    if sig.verify_userid_revocation(pk, pk, ua.userid())
+                        .is_ok()
+                    {
+                        return Ok(RevocationClass::UserID(ua.userid(), typ));
+                    }
+                }
+                for ua in self.user_attributes() {
+                    if sig.verify_user_attribute_revocation(
+                        pk, pk, ua.user_attribute()).is_ok()
+                    {
+                        return Ok(RevocationClass::UserAttribute(
+                            ua.user_attribute(), typ));
+                    }
+                }
+            },
+            t => return Err(Error::UnsupportedSignatureType(t).into()),
+        }
+
+        Err(Error::BadSignature(format!(
+            "Revocation does not apply to any component of {}",
+            pk.fingerprint())).into())
+    }
+
     /// Sets the key to expire in delta seconds.
     ///
     /// Note: the time is relative to the key's creation time, not the
@@ -964,7 +1100,7 @@ impl Cert {
 
     /// Returns the primary User ID at the reference time, if any.
     fn primary_userid_relaxed<'a, T>(&'a self, policy: &'a dyn Policy, t: T,
-                                     valid_cert: bool)
+                                     valid_cert: Option<&ValidCert<'a>>)
         -> Result<ValidUserIDAmalgamation<'a>>
         where T: Into<Option<std::time::SystemTime>>
     {
@@ -1246,6 +1382,77 @@ impl Cert {
         Box::new(keys.into_iter())
     }
 
+    /// Returns the issuers of all third-party certifications.
+    ///
+    /// This returns the issuers of the third-party certifications on
+    /// all of the certificate's components.  This is useful to
+    /// prefetch the certificates that are needed to check the
+    /// certifications, for instance, when authenticating the
+    /// certificate using the web of trust.
+    ///
+    /// Note: the certifications are not checked, and the returned
+    /// list is deduplicated.  If an issuer is identified by both its
+    /// [`Fingerprint`] and its [`KeyID`], only the fingerprint is
+    /// returned.  Fingerprints come before key IDs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::types::SignatureType;
+    ///
+    /// let (alice, _) = CertBuilder::new()
+    ///     .add_userid("Alice")
+    ///     .generate()?;
+    /// let (bob, _) = CertBuilder::new().generate()?;
+    /// let mut bob_signer =
+    ///     bob.primary_key().key().clone().parts_into_secret()?.into_keypair()?;
+    /// assert!(alice.certifiers().is_empty());
+    ///
+    /// // Bob certifies Alice's user ID.
+    /// let userid = alice.userids().next().unwrap().userid().clone();
+    /// let certification = userid.certify(
+    ///     &mut bob_signer, &alice, SignatureType::GenericCertification,
+    ///     None, None)?;
+    /// let alice = alice.insert_packets(certification)?;
+    ///
+    /// assert_eq!(alice.certifiers(), vec![bob.key_handle()]);
+    /// # Ok(()) }
+    /// ```
+    pub fn certifiers(&self) -> Vec<KeyHandle> {
+        let mut issuers = self.primary.certifications2()
+            .chain(self.userids.iter().flat_map(|b| b.certifications2()))
+            .chain(self.user_attributes.iter()
+                   .flat_map(|b| b.certifications2()))
+            .chain(self.subkeys.iter().flat_map(|b| b.certifications2()))
+            .chain(self.unknowns.iter().flat_map(|b| b.certifications2()))
+            .flat_map(|sig| sig.get_issuers())
+            .collect::<Vec<_>>();
+
+        // Fingerprints come before key IDs.
+        issuers.sort_by_key(|h| matches!(h, KeyHandle::KeyID(_)));
+
+        let mut fingerprints = std::collections::HashSet::new();
+        let mut keyids = std::collections::HashSet::new();
+        let mut certifiers = Vec::new();
+        for issuer in issuers {
+            // Drop duplicates, and key IDs that alias a fingerprint.
+            let new = match &issuer {
+                KeyHandle::Fingerprint(fpr) => {
+                    keyids.insert(KeyID::from(fpr));
+                    fingerprints.insert(fpr.clone())
+                },
+                KeyHandle::KeyID(keyid) => keyids.insert(keyid.clone()),
+            };
+            if new {
+                certifiers.push(issuer);
+            }
+        }
+        certifiers
+    }
+
     /// Converts the certificate into an iterator over a sequence of
     /// packets.
     ///
@@ -1493,8 +1700,21 @@ impl Cert {
         self.unknowns.sort_and_dedup(Unknown::best_effort_cmp, |_, _| {});
     }
 
-    fn canonicalize(mut self) -> Self {
+    fn canonicalize(self) -> Self {
+        self.canonicalize_with_limits(&CertificationLimits::new(),
+                                      &mut Vec::new())
+    }
+
+    /// Like [`Cert::canonicalize`], but also drops third-party
+    /// certifications exceeding `limits`.
+    ///
+    /// The dropped certifications are appended to `excess`.
+    fn canonicalize_with_limits(mut self, limits: &CertificationLimits,
+                                excess: &mut Vec<Signature>)
+                                -> Self
+    {
         tracer!(TRACE, "canonicalize", 0);
+        span!(DEBUG, "canonicalize", cert = %self.fingerprint());
         use SignatureType::*;
 
         // Before we do anything, we'll order and deduplicate the
@@ -1530,11 +1750,11 @@ impl Cert {
              $sig_type_pat:pat,     // pattern to test signature types against
              $($hash_args:expr),* // additional arguments to pass to hash_method
             ) => ({
-                let sigs = $binding.$sigs.take();
+                let sigs = $binding.$sigs.take_with_states();
                 t!("check!({}, {}, {} ({:?}), {}, ...)",
                    $desc, stringify!($binding), sigs.len(), sigs,
                    stringify!($hash_method));
-                for sig in sigs.into_iter() {
+                for (sig, state) in sigs.into_iter() {
                     // Use hash prefix as heuristic.
                     let key = self.primary.key();
                     match sig.hash_algo().context().and_then(|mut ctx| {
@@ -1547,8 +1767,18 @@ impl Cert {
                     }) {
                       Ok(hash) => {
                         if &sig.digest_prefix()[..] == &hash[..2] {
+                            // If the signature has been verified over
+                            // the same digest before, e.g. because we
+                            // are merging in an update, we keep the
+                            // result.
+                            let state =
+                                if sig.computed_digest() == Some(&hash[..]) {
+                                    state
+                                } else {
+                                    SigState::Unverified
+                                };
                             sig.set_computed_digest(Some(hash));
-                            $binding.$sigs.push(sig);
+                            $binding.$sigs.push_with_state(sig, state);
                         } else {
                             t!("Sig {:02X}{:02X}, type = {} \
                                 doesn't belong to {} (computed hash's prefix: {:02X}{:02X})",
@@ -2144,6 +2374,8 @@ impl Cert {
         if !self.bad.is_empty() {
             t!("{}: ignoring {} bad self signatures",
                self.keyid(), self.bad.len());
+            event!(DEBUG, bad_signatures = self.bad.len(),
+                   "ignoring bad self signatures");
         }
 
         // Sort again.  We may have moved signatures to the right
@@ -2168,6 +2400,16 @@ impl Cert {
         // signatures than to keep them around and have many keys
         // being shown as "potentially revoked".
 
+        // Drop excess third-party certifications.  This relies on
+        // the certifications being sorted so that the newest one
+        // comes first.
+        let n = excess.len();
+        self.apply_certification_limits(limits, excess);
+        if excess.len() > n {
+            t!("{}: dropped {} excess certifications",
+               self.keyid(), excess.len() - n);
+        }
+
         // XXX Do some more canonicalization.
 
         self
@@ -3301,6 +3543,80 @@ impl Cert {
         self
     }
 
+    /// Changes the password protecting the secret key material.
+    ///
+    /// Encrypted secret key material is decrypted using the first of
+    /// `old_passwords` that works.  Then, if `new_password` is given,
+    /// all secret key material is encrypted using it.  Otherwise, it
+    /// is left unencrypted.  Keys without secret key material are
+    /// left alone.
+    ///
+    /// If some encrypted secret key material cannot be decrypted
+    /// using any of `old_passwords`, this returns
+    /// [`Error::InvalidPassword`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let (cert, _) =
+    ///       CertBuilder::general_purpose(None, Some("alice@example.org"))
+    ///       .set_password(Some("old".into()))
+    ///       .generate()?;
+    ///
+    /// let cert = cert.change_secret_key_password(&["old".into()],
+    ///                                            Some(&"new".into()))?;
+    /// for ka in cert.keys().secret() {
+    ///     ka.key().clone().decrypt_secret(&"new".into())?;
+    /// }
+    ///
+    /// // Remove the password.
+    /// let cert = cert.change_secret_key_password(&["new".into()], None)?;
+    /// assert!(cert.keys().all(|ka| ka.has_unencrypted_secret()));
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn change_secret_key_password(mut self,
+                                      old_passwords: &[Password],
+                                      new_password: Option<&Password>)
+                                      -> Result<Cert>
+    {
+        fn change<R>(key: &mut Key<key::PublicParts, R>,
+                     old_passwords: &[Password],
+                     new_password: Option<&Password>)
+                     -> Result<()>
+            where R: key::KeyRole,
+        {
+            let pk_algo = key.pk_algo();
+            let secret = match key.parts_as_secret_mut() {
+                Ok(key) => key.secret_mut(),
+                // No secret key material.
+                Err(_) => return Ok(()),
+            };
+
+            if secret.is_encrypted()
+                && ! old_passwords.iter().any(
+                    |p| secret.decrypt_in_place(pk_algo, p).is_ok())
+            {
+                return Err(Error::InvalidPassword.into());
+            }
+
+            if let Some(password) = new_password {
+                secret.encrypt_in_place(password)?;
+            }
+            Ok(())
+        }
+
+        change(self.primary.key_mut(), old_passwords, new_password)?;
+        for sk in self.subkeys.iter_mut() {
+            change(sk.key_mut(), old_passwords, new_password)?;
+        }
+        Ok(self)
+    }
+
     /// Retains only the userids specified by the predicate.
     ///
     /// Removes all the userids for which the given predicate returns
@@ -3449,6 +3765,79 @@ impl Cert {
         self
     }
 
+    /// Drops third-party certifications exceeding the given limits.
+    ///
+    /// This mitigates certificate flooding, see
+    /// [`CertificationLimits`] for details.  Returns the certificate,
+    /// and the certifications that were dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::cert::CertificationLimits;
+    /// use openpgp::packet::signature::SignatureBuilder;
+    /// use openpgp::types::SignatureType;
+    ///
+    /// let (alice, _) = CertBuilder::new()
+    ///     .add_userid("Alice")
+    ///     .generate()?;
+    /// let (bob, _) = CertBuilder::new().generate()?;
+    /// let mut bob_signer =
+    ///     bob.primary_key().key().clone().parts_into_secret()?.into_keypair()?;
+    ///
+    /// // Bob certifies Alice's user ID three times.
+    /// let userid = alice.userids().next().unwrap().userid().clone();
+    /// let mut certifications = Vec::new();
+    /// for typ in [SignatureType::GenericCertification,
+    ///             SignatureType::PersonaCertification,
+    ///             SignatureType::CasualCertification]
+    /// {
+    ///     certifications.push(
+    ///         userid.certify(&mut bob_signer, &alice, typ, None, None)?);
+    /// }
+    /// let alice = alice.insert_packets(certifications)?;
+    /// assert_eq!(alice.userids().next().unwrap().certifications().count(),
+    ///            3);
+    ///
+    /// // Keep only the newest certification by each issuer.
+    /// let (alice, dropped) = alice.limit_certifications(
+    ///     &CertificationLimits::default().set_per_issuer(Some(1)));
+    /// assert_eq!(alice.userids().next().unwrap().certifications().count(),
+    ///            1);
+    /// assert_eq!(dropped.len(), 2);
+    /// # Ok(()) }
+    /// ```
+    pub fn limit_certifications(mut self, limits: &CertificationLimits)
+                                -> (Cert, Vec<Signature>)
+    {
+        let mut excess = Vec::new();
+        self.apply_certification_limits(limits, &mut excess);
+        (self, excess)
+    }
+
+    /// Drops third-party certifications exceeding the given limits,
+    /// and appends them to `excess`.
+    fn apply_certification_limits(&mut self, limits: &CertificationLimits,
+                                  excess: &mut Vec<Signature>)
+    {
+        self.primary.limit_certifications(limits, excess);
+        for b in self.userids.iter_mut() {
+            b.limit_certifications(limits, excess);
+        }
+        for b in self.user_attributes.iter_mut() {
+            b.limit_certifications(limits, excess);
+        }
+        for b in self.subkeys.iter_mut() {
+            b.limit_certifications(limits, excess);
+        }
+        for b in self.unknowns.iter_mut() {
+            b.limit_certifications(limits, excess);
+        }
+    }
+
     /// Associates a policy and a reference time with the certificate.
     ///
     /// This is used to turn a `Cert` into a
@@ -3492,13 +3881,173 @@ impl Cert {
         where T: Into<Option<time::SystemTime>>,
     {
         let time = time.into().unwrap_or_else(crate::now);
-        self.primary_key().with_policy(policy, time)?;
+        let pka = self.primary_key().with_policy(policy, time)?;
 
-        Ok(ValidCert {
-            cert: self,
-            policy,
-            time,
-        })
+        Ok(ValidCert::new(self, policy, time,
+                          Some(pka.binding_signature())))
+    }
+
+    /// Checks whether the certificate can be used to encrypt data.
+    ///
+    /// This checks that the certificate is valid under `policy`, is
+    /// neither revoked nor expired at time `time` (or now, if `time`
+    /// is `None`), and has at least one key that is marked as
+    /// transport or storage encryption capable, that is neither
+    /// revoked nor expired, and whose algorithm is supported.
+    ///
+    /// If that is not the case, the returned error explains why.
+    /// This is useful for user interfaces that need to tell the user
+    /// why a recipient cannot be used.  If there are several
+    /// encryption-capable keys, but none of them can be used, the
+    /// reason why the first one cannot be used is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, SystemTime};
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let p = &StandardPolicy::new();
+    ///
+    /// let now = SystemTime::now();
+    /// let (cert, _) = CertBuilder::new()
+    ///     .set_creation_time(now)
+    ///     .add_transport_encryption_subkey()
+    ///     .set_validity_period(Duration::from_secs(3600))
+    ///     .generate()?;
+    /// assert!(cert.encryption_capable(p, now).is_ok());
+    ///
+    /// // After the certificate expires, it cannot be used.
+    /// let err = cert.encryption_capable(p, now + Duration::from_secs(7200))
+    ///     .unwrap_err();
+    /// assert!(matches!(err.downcast_ref::<openpgp::Error>(),
+    ///                  Some(openpgp::Error::Expired(_))));
+    ///
+    /// // A certificate without an encryption subkey cannot be used.
+    /// let (cert, _) = CertBuilder::new()
+    ///     .set_creation_time(now)
+    ///     .add_signing_subkey()
+    ///     .generate()?;
+    /// assert!(cert.encryption_capable(p, now).is_err());
+    /// # Ok(()) }
+    /// ```
+    pub fn encryption_capable<T>(&self, policy: &dyn Policy, time: T)
+                                 -> Result<()>
+        where T: Into<Option<time::SystemTime>>,
+    {
+        self.capable(policy, time.into(), "encryption",
+                     |f| f.for_transport_encryption()
+                     || f.for_storage_encryption())
+    }
+
+    /// Checks whether the certificate can be used to make signatures.
+    ///
+    /// This checks that the certificate is valid under `policy`, is
+    /// neither revoked nor expired at time `time` (or now, if `time`
+    /// is `None`), and has at least one key that is marked as signing
+    /// capable, that is neither revoked nor expired, and whose
+    /// algorithm is supported.  Note: this does not check whether the
+    /// secret key material is available.
+    ///
+    /// If that is not the case, the returned error explains why.  See
+    /// [`Cert::encryption_capable`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let p = &StandardPolicy::new();
+    ///
+    /// let (cert, _) = CertBuilder::new()
+    ///     .add_signing_subkey()
+    ///     .generate()?;
+    /// assert!(cert.signing_capable(p, None).is_ok());
+    ///
+    /// let (cert, _) = CertBuilder::new()
+    ///     .add_transport_encryption_subkey()
+    ///     .generate()?;
+    /// assert!(cert.signing_capable(p, None).is_err());
+    /// # Ok(()) }
+    /// ```
+    pub fn signing_capable<T>(&self, policy: &dyn Policy, time: T)
+                              -> Result<()>
+        where T: Into<Option<time::SystemTime>>,
+    {
+        self.capable(policy, time.into(), "signing", |f| f.for_signing())
+    }
+
+    /// Checks whether the certificate has a usable key with the
+    /// capability `what`, as selected by `flags`.
+    fn capable(&self, policy: &dyn Policy, time: Option<time::SystemTime>,
+               what: &str, flags: fn(&KeyFlags) -> bool)
+               -> Result<()>
+    {
+        use anyhow::Context;
+
+        let time = time.unwrap_or_else(crate::now);
+
+        let vc = self.with_policy(policy, time)
+            .context("The certificate is not valid under the policy")?;
+        if let RevocationStatus::Revoked(_) = vc.revocation_status() {
+            return Err(Error::InvalidKey(
+                "The certificate is revoked".into()).into());
+        }
+        vc.alive().context("The certificate is not alive")?;
+
+        // The reason why the first key with the capability can't be
+        // used.
+        let mut unusable = None;
+        // The reason why the first key is rejected by the policy.
+        let mut invalid = None;
+        for ka in self.keys() {
+            let fpr = ka.fingerprint();
+            let ka = match ka.with_policy(policy, time) {
+                Ok(ka) => ka,
+                Err(err) => {
+                    if invalid.is_none() {
+                        invalid = Some(err.context(format!(
+                            "Key {} is not valid under the policy", fpr)));
+                    }
+                    continue;
+                },
+            };
+
+            if ! ka.key_flags().map(|f| flags(&f)).unwrap_or(false) {
+                continue;
+            }
+
+            let result = if let RevocationStatus::Revoked(_)
+                = ka.revocation_status()
+            {
+                Err(Error::InvalidKey(
+                    format!("Key {} is revoked", fpr)).into())
+            } else if let Err(err) = ka.alive() {
+                Err(err.context(format!("Key {} is not alive", fpr)))
+            } else if ! ka.pk_algo().is_supported() {
+                Err(anyhow::Error::from(
+                    Error::UnsupportedPublicKeyAlgorithm(ka.pk_algo()))
+                    .context(format!("Key {} cannot be used", fpr)))
+            } else {
+                Ok(())
+            };
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(err) => if unusable.is_none() {
+                    unusable = Some(err);
+                },
+            }
+        }
+
+        Err(unusable.or(invalid).unwrap_or_else(|| Error::InvalidKey(
+            format!("The certificate has no {}-capable key", what)).into()))
     }
 }
 
@@ -3745,6 +4294,39 @@ impl IntoIterator for Cert
     }
 }
 
+#[cfg(any(test, feature = "quickcheck"))]
+impl Arbitrary for Cert {
+    /// Generates a valid certificate.
+    ///
+    /// The certificate has up to three arbitrary User IDs, and up to
+    /// two signing and two encryption subkeys.  All keys use
+    /// [`CipherSuite::Cv25519`].  Half of the generated certificates
+    /// include unencrypted secret key material.
+    fn arbitrary(g: &mut Gen) -> Self {
+        use crate::arbitrary_helper::gen_arbitrary_from_range;
+
+        let mut builder = CertBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519);
+        for _ in 0..gen_arbitrary_from_range(0..4, g) {
+            builder = builder.add_userid(UserID::arbitrary(g));
+        }
+        for _ in 0..gen_arbitrary_from_range(0..3, g) {
+            builder = builder.add_signing_subkey();
+        }
+        for _ in 0..gen_arbitrary_from_range(0..3, g) {
+            builder = builder.add_transport_encryption_subkey();
+        }
+
+        let (cert, _) = builder.generate()
+            .expect("generating Cv25519 certificates does not fail");
+        if bool::arbitrary(g) {
+            cert
+        } else {
+            cert.strip_secret_key_material()
+        }
+    }
+}
+
 /// A `Cert` plus a `Policy` and a reference time.
 ///
 /// A `ValidCert` combines a [`Cert`] with a [`Policy`] and a
@@ -3794,6 +4376,19 @@ pub struct ValidCert<'a> {
     policy: &'a dyn Policy,
     // The reference time.
     time: time::SystemTime,
+
+    // The primary key's binding signature at time `time`, if it was
+    // computed when the `ValidCert` was created.  Looking it up walks
+    // the self signatures.  (This is just a cache.)
+    primary_key_binding: Option<&'a Signature>,
+
+    // The binding signatures of the subkeys and User IDs at time
+    // `time` that have been looked up so far.  Maps the address of a
+    // component bundle to the index of its binding signature in the
+    // bundle's self signatures.  We store indices rather than
+    // references so that `ValidCert` stays covariant.  Clones share
+    // the cache.  (This is just a cache.)
+    bindings: Arc<Mutex<HashMap<usize, usize>>>,
 }
 assert_send_and_sync!(ValidCert<'_>);
 
@@ -3812,6 +4407,52 @@ impl<'a> fmt::Display for ValidCert<'a> {
 }
 
 impl<'a> ValidCert<'a> {
+    /// Returns a new `ValidCert`.
+    ///
+    /// This does not check that the certificate is valid under the
+    /// given policy at the given time.  The caller must ensure that.
+    /// If the primary key's binding signature has already been
+    /// computed, it should be passed in `primary_key_binding`.
+    pub(crate) fn new(cert: &'a Cert, policy: &'a dyn Policy,
+                      time: time::SystemTime,
+                      primary_key_binding: Option<&'a Signature>)
+                      -> Self
+    {
+        ValidCert {
+            cert,
+            policy,
+            time,
+            primary_key_binding,
+            bindings: Default::default(),
+        }
+    }
+
+    /// Returns the binding signature of one of the certificate's
+    /// components at the reference time under the policy.
+    ///
+    /// The result is cached, so that iterating over a `ValidCert`'s
+    /// components multiple times only looks up and verifies the
+    /// binding signatures once.  This must not be used for the
+    /// primary key, whose binding signature may be a User ID's.
+    pub(crate) fn bundle_binding_signature<C>(&self,
+                                              bundle: &'a bundle::ComponentBundle<C>)
+                                              -> Result<&'a Signature>
+    {
+        let sigs = bundle.self_signatures.as_slice_unverified();
+        let addr = bundle as *const _ as usize;
+        if let Some(&i) = self.bindings.lock().expect("not poisoned")
+            .get(&addr)
+        {
+            return Ok(&sigs[i]);
+        }
+
+        let sig = bundle.binding_signature(self.policy, self.time)?;
+        if let Some(i) = sigs.iter().position(|s| std::ptr::eq(s, sig)) {
+            self.bindings.lock().expect("not poisoned").insert(addr, i);
+        }
+        Ok(sig)
+    }
+
     /// Returns the underlying certificate.
     ///
     /// # Examples
@@ -4094,6 +4735,10 @@ impl<'a> ValidCert<'a> {
     pub fn primary_key(&self)
         -> ValidPrimaryKeyAmalgamation<'a, key::PublicParts>
     {
+        if let Some(binding) = self.primary_key_binding {
+            return ValidPrimaryKeyAmalgamation::new(self.clone(), binding);
+        }
+
         self.cert.primary_key().with_policy(self.policy, self.time)
             .expect("A ValidKeyAmalgamation must have a ValidPrimaryKeyAmalgamation")
     }
@@ -4142,7 +4787,7 @@ impl<'a> ValidCert<'a> {
     /// # }
     /// ```
     pub fn keys(&self) -> ValidKeyAmalgamationIter<'a, key::PublicParts, key::UnspecifiedRole> {
-        self.cert.keys().with_policy(self.policy, self.time)
+        self.cert.keys().with_valid_cert(self)
     }
 
     /// Returns the primary User ID at the reference time, if any.
@@ -4237,7 +4882,7 @@ impl<'a> ValidCert<'a> {
     /// # Ok(()) }
     pub fn primary_userid(&self) -> Result<ValidUserIDAmalgamation<'a>>
     {
-        self.cert.primary_userid_relaxed(self.policy(), self.time(), true)
+        self.cert.primary_userid_relaxed(self.policy(), self.time(), Some(self))
     }
 
     /// Returns an iterator over the certificate's valid User IDs.
@@ -4291,7 +4936,7 @@ impl<'a> ValidCert<'a> {
     /// # }
     /// ```
     pub fn userids(&self) -> ValidUserIDAmalgamationIter<'a> {
-        self.cert.userids().with_policy(self.policy, self.time)
+        self.cert.userids().with_valid_cert(self)
     }
 
     /// Returns the primary User Attribute, if any.
@@ -4333,7 +4978,8 @@ impl<'a> ValidCert<'a> {
     {
         ValidComponentAmalgamation::primary(self.cert,
                                             self.cert.user_attributes.iter(),
-                                            self.policy(), self.time(), true)
+                                            self.policy(), self.time(),
+                                            Some(self))
     }
 
     /// Returns an iterator over the certificate's valid
@@ -4371,7 +5017,7 @@ impl<'a> ValidCert<'a> {
     /// # }
     /// ```
     pub fn user_attributes(&self) -> ValidUserAttributeAmalgamationIter<'a> {
-        self.cert.user_attributes().with_policy(self.policy, self.time)
+        self.cert.user_attributes().with_valid_cert(self)
     }
 
     /// Returns a list of any designated revokers for this certificate.
@@ -5189,6 +5835,19 @@ mod test {
         );
     }
 
+    quickcheck! {
+        fn arbitrary_roundtrip(cert: Cert) -> bool {
+            use crate::serialize::SerializeInto;
+            let p = &P::new();
+            assert!(cert.with_policy(p, None).is_ok());
+            assert_eq!(cert.bad_signatures().count(), 0);
+
+            let bytes = cert.as_tsk().to_vec().unwrap();
+            assert_eq!(Cert::from_bytes(&bytes).unwrap(), cert);
+            true
+        }
+    }
+
     #[test]
     fn set_validity_period_two_uids() -> Result<()> {
         use quickcheck::{Arbitrary, Gen};
@@ -6404,6 +7063,50 @@ Pu1xwz57O4zo1VYf6TqHJzVC3OMvMUM2hhdecMUe5x6GorNaj6g=
         Ok(())
     }
 
+    #[test]
+    fn change_secret_key_password() -> Result<()> {
+        use crate::crypto::Password;
+        let a: Password = "a".into();
+        let b: Password = "b".into();
+        let c: Password = "c".into();
+
+        // The primary key is protected with `a`, the subkey with `b`.
+        let (cert, _) = CertBuilder::new()
+            .add_transport_encryption_subkey()
+            .set_password(Some(a.clone()))
+            .generate()?;
+        let subkey = cert.keys().subkeys().secret().next().unwrap()
+            .key().clone()
+            .decrypt_secret(&a)?
+            .encrypt_secret(&b)?;
+        let cert = cert.insert_packets(subkey)?;
+
+        // Both passwords are needed.
+        assert_eq!(cert.clone()
+                   .change_secret_key_password(&[a.clone()], Some(&c))
+                   .unwrap_err().downcast::<Error>()?,
+                   Error::InvalidPassword);
+
+        let cert = cert.change_secret_key_password(&[b, a], Some(&c))?;
+        assert_eq!(cert.keys().secret().count(), 2);
+        for ka in cert.keys().secret() {
+            ka.key().clone().decrypt_secret(&c)?;
+        }
+
+        let cert = cert.change_secret_key_password(&[c], None)?;
+        assert_eq!(cert.keys().unencrypted_secret().count(), 2);
+
+        // Unencrypted secrets don't need a password.
+        let cert = cert.change_secret_key_password(&[], None)?;
+        assert_eq!(cert.keys().unencrypted_secret().count(), 2);
+
+        // Keys without secrets are left alone.
+        let cert = cert.strip_secret_key_material()
+            .change_secret_key_password(&[], Some(&"d".into()))?;
+        assert!(! cert.is_tsk());
+        Ok(())
+    }
+
     /// Tests that Cert:.into_packets2() and Cert::serialize(..) agree.
     #[test]
     fn test_into_packets2() -> Result<()> {
@@ -7540,4 +8243,177 @@ Pu1xwz57O4zo1VYf6TqHJzVC3OMvMUM2hhdecMUe5x6GorNaj6g=
 
         Ok(())
     }
+
+    /// Checks that the primary key binding signature cached in
+    /// ValidCert matches the one looked up from scratch.
+    #[test]
+    fn valid_cert_primary_key_binding() -> Result<()> {
+        use crate::cert::amalgamation::ValidAmalgamation;
+
+        let p = &crate::policy::StandardPolicy::new();
+        let (cert, _) = CertBuilder::general_purpose(
+            None, Some("alice@example.org"))
+            .generate()?;
+        let expected = cert.primary_key().with_policy(p, None)?
+            .binding_signature();
+
+        let vc = cert.with_policy(p, None)?;
+        assert!(std::ptr::eq(vc.primary_key().binding_signature(),
+                             expected));
+        for ka in vc.keys() {
+            assert!(std::ptr::eq(
+                ka.cert().primary_key().binding_signature(), expected));
+        }
+        for ua in vc.userids() {
+            assert!(std::ptr::eq(
+                ua.cert().primary_key().binding_signature(), expected));
+        }
+        Ok(())
+    }
+
+    /// Checks that ValidCert caches the subkeys' and User IDs'
+    /// binding signatures, and that the cached signatures match the
+    /// ones looked up from scratch.
+    #[test]
+    fn valid_cert_binding_cache() -> Result<()> {
+        use crate::cert::amalgamation::ValidAmalgamation;
+
+        let p = &crate::policy::StandardPolicy::new();
+        let (cert, _) = CertBuilder::general_purpose(
+            None, Some("alice@example.org"))
+            .add_userid("alice@example.net")
+            .generate()?;
+
+        let vc = cert.with_policy(p, None)?;
+        assert_eq!(vc.bindings.lock().unwrap().len(), 0);
+
+        for _ in 0..2 {
+            for ka in vc.keys().subkeys() {
+                assert!(std::ptr::eq(
+                    ka.binding_signature(),
+                    ka.bundle().binding_signature(p, vc.time())?));
+            }
+            for ua in vc.userids() {
+                assert!(std::ptr::eq(
+                    ua.binding_signature(),
+                    ua.bundle().binding_signature(p, vc.time())?));
+            }
+            assert!(std::ptr::eq(
+                vc.primary_userid()?.binding_signature(),
+                cert.userids().with_policy(p, vc.time())
+                    .find(|ua| ua.userid().value() == b"alice@example.org")
+                    .unwrap().binding_signature()));
+
+            assert_eq!(vc.bindings.lock().unwrap().len(),
+                       cert.subkeys().count() + cert.userids().count());
+        }
+
+        // Clones share the cache, fresh ValidCerts don't.
+        assert_eq!(vc.clone().bindings.lock().unwrap().len(),
+                   cert.subkeys().count() + cert.userids().count());
+        assert_eq!(cert.with_policy(p, vc.time())?
+                   .bindings.lock().unwrap().len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn capable() -> Result<()> {
+        use std::time::Duration;
+        use crate::cert::revoke::SubkeyRevocationBuilder;
+
+        let p = &P::new();
+        let now = crate::now();
+        let hour = Duration::from_secs(3600);
+
+        // No capable keys.
+        let (cert, _) = CertBuilder::new()
+            .set_creation_time(now - hour)
+            .generate()?;
+        assert!(cert.encryption_capable(p, now).is_err());
+        assert!(cert.signing_capable(p, now).is_err());
+
+        // The capable subkey is expired, the other one is fine.
+        let (cert, _) = CertBuilder::new()
+            .set_creation_time(now - hour)
+            .add_subkey(KeyFlags::empty().set_transport_encryption(),
+                        hour / 2, None)
+            .add_subkey(KeyFlags::empty().set_signing(), None, None)
+            .generate()?;
+        let err = cert.encryption_capable(p, now).unwrap_err();
+        assert_match!(Some(Error::Expired(_))
+                      = err.downcast_ref::<Error>());
+        cert.signing_capable(p, now)?;
+        cert.encryption_capable(p, now - hour * 3 / 4)?;
+
+        // Revoked subkey.
+        let mut signer = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        let subkey = cert.keys().subkeys()
+            .find(|ka| ka.self_signatures().next().unwrap()
+                  .key_flags().unwrap().for_signing())
+            .unwrap();
+        let sig = SubkeyRevocationBuilder::new()
+            .set_reason_for_revocation(ReasonForRevocation::KeyRetired,
+                                       b"")?
+            .build(&mut signer, &cert, subkey.key(), None)?;
+        let cert = cert.insert_packets(sig)?;
+        let err = cert.signing_capable(p, None).unwrap_err();
+        assert_match!(Some(Error::InvalidKey(_))
+                      = err.downcast_ref::<Error>());
+
+        // Revoked certificate.
+        let (cert, _) = CertBuilder::new()
+            .set_creation_time(now - hour)
+            .add_transport_encryption_subkey()
+            .add_signing_subkey()
+            .generate()?;
+        cert.encryption_capable(p, None)?;
+        cert.signing_capable(p, None)?;
+        let mut signer = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        let sig = cert.revoke(&mut signer,
+                              ReasonForRevocation::KeyCompromised, b"")?;
+        let cert = cert.insert_packets(sig)?;
+        let err = cert.encryption_capable(p, None).unwrap_err();
+        assert_match!(Some(Error::InvalidKey(_))
+                      = err.downcast_ref::<Error>());
+        assert!(cert.signing_capable(p, None).is_err());
+
+        Ok(())
+    }
+
+    /// Checks that Cert::certifiers deduplicates issuers.
+    #[test]
+    fn certifiers() -> Result<()> {
+        let (alice, _) = CertBuilder::new()
+            .add_userid("Alice")
+            .generate()?;
+        let userid = alice.userids().next().unwrap().userid().clone();
+
+        let mut certifications = Vec::new();
+        let mut certify = |certifier: &Cert, keyid_only: bool| -> Result<()> {
+            let mut signer = certifier.primary_key().key().clone()
+                .parts_into_secret()?.into_keypair()?;
+            let mut builder =
+                signature::SignatureBuilder::new(SignatureType::GenericCertification);
+            if keyid_only {
+                builder = builder.set_issuer(certifier.keyid())?;
+            }
+            certifications.push(userid.bind(&mut signer, &alice, builder)?);
+            Ok(())
+        };
+
+        let (bob, _) = CertBuilder::new().generate()?;
+        let (carol, _) = CertBuilder::new().generate()?;
+        certify(&bob, true)?;
+        certify(&carol, true)?;
+        certify(&bob, false)?;
+        certify(&bob, false)?;
+
+        let alice = alice.insert_packets(certifications)?;
+        assert_eq!(alice.certifiers(),
+                   vec![bob.key_handle(), KeyHandle::from(carol.keyid())]);
+
+        Ok(())
+    }
 }
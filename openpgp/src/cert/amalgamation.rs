@@ -337,45 +337,6 @@ pub trait ValidateAmalgamation<'a, C: 'a>: seal::Sealed {
               Self: Sized;
 }
 
-/// Applies a policy to an amalgamation.
-///
-/// This is an internal variant of `ValidateAmalgamation`, which
-/// allows validating a component for an otherwise invalid
-/// certificate.  See `ValidComponentAmalgamation::primary` for an
-/// explanation.
-trait ValidateAmalgamationRelaxed<'a, C: 'a> {
-    /// The type returned by `with_policy`.
-    type V;
-
-    /// Changes the amalgamation's policy.
-    ///
-    /// If `time` is `None`, the current time is used.
-    ///
-    /// If `valid_cert` is `false`, then this does not also check
-    /// whether the certificate is valid; it only checks whether the
-    /// component is valid.  Normally, this should be `true`.  This
-    /// option is only expose to allow breaking an infinite recursion:
-    ///
-    ///   - To check if a certificate is valid, we check if the
-    ///     primary key is valid.
-    ///
-    ///   - To check if the primary key is valid, we need the primary
-    ///     key's self signature
-    ///
-    ///   - To find the primary key's self signature, we need to find
-    ///     the primary user id
-    ///
-    ///   - To find the primary user id, we need to check if the user
-    ///     id is valid.
-    ///
-    ///   - To check if the user id is valid, we need to check that
-    ///     the corresponding certificate is valid.
-    fn with_policy_relaxed<T>(self, policy: &'a dyn Policy, time: T,
-                              valid_cert: bool) -> Result<Self::V>
-        where T: Into<Option<time::SystemTime>>,
-              Self: Sized;
-}
-
 /// Methods for valid amalgamations.
 ///
 /// The methods exposed by a `ValidComponentAmalgamation` are similar
@@ -773,21 +734,6 @@ impl<'a, C> ComponentAmalgamation<'a, C> {
         self.cert
     }
 
-    /// Selects a binding signature.
-    ///
-    /// Uses the provided policy and reference time to select an
-    /// appropriate binding signature.
-    ///
-    /// Note: this function is not exported.  Users of this interface
-    /// should do: ca.with_policy(policy, time)?.binding_signature().
-    fn binding_signature<T>(&self, policy: &dyn Policy, time: T)
-        -> Result<&'a Signature>
-        where T: Into<Option<time::SystemTime>>
-    {
-        let time = time.into().unwrap_or_else(crate::now);
-        self.bundle.binding_signature(policy, time)
-    }
-
     /// Returns this amalgamation's bundle.
     ///
     /// Note: although `ComponentAmalgamation` derefs to a
@@ -1040,35 +986,24 @@ impl<'a, C> ComponentAmalgamation<'a, C> {
     }
 }
 
-macro_rules! impl_with_policy {
-    ($func:ident, $value:ident $(, $arg:ident: $type:ty )*) => {
-        fn $func<T>(self, policy: &'a dyn Policy, time: T, $($arg: $type, )*)
-            -> Result<Self::V>
-            where T: Into<Option<time::SystemTime>>,
-                  Self: Sized
-        {
-            let time = time.into().unwrap_or_else(crate::now);
-
-            if $value {
-                self.cert.with_policy(policy, time)?;
-            }
+impl<'a, C> ComponentAmalgamation<'a, C> {
+    /// Returns a `ValidComponentAmalgamation` for a component of an
+    /// already validated certificate.
+    ///
+    /// This is like `with_policy`, but uses `vc`'s policy and
+    /// reference time, does not check the certificate again, and uses
+    /// `vc`'s cache of binding signatures.
+    pub(crate) fn with_valid_cert(self, vc: &ValidCert<'a>)
+        -> Result<ValidComponentAmalgamation<'a, C>>
+    {
+        assert!(std::ptr::eq(self.cert, vc.cert()));
 
-            let binding_signature = self.binding_signature(policy, time)?;
-            let cert = self.cert;
-            // We can't do `Cert::with_policy` as that would
-            // result in infinite recursion.  But at this point,
-            // we know the certificate is valid (unless the caller
-            // doesn't care).
-            Ok(ValidComponentAmalgamation {
-                ca: self,
-                cert: ValidCert {
-                    cert,
-                    policy,
-                    time,
-                },
-                binding_signature,
-            })
-        }
+        let binding_signature = vc.bundle_binding_signature(self.bundle)?;
+        Ok(ValidComponentAmalgamation {
+            ca: self,
+            cert: vc.clone(),
+            binding_signature,
+        })
     }
 }
 
@@ -1076,13 +1011,14 @@ impl<'a, C> seal::Sealed for ComponentAmalgamation<'a, C> {}
 impl<'a, C> ValidateAmalgamation<'a, C> for ComponentAmalgamation<'a, C> {
     type V = ValidComponentAmalgamation<'a, C>;
 
-    impl_with_policy!(with_policy, true);
-}
-
-impl<'a, C> ValidateAmalgamationRelaxed<'a, C> for ComponentAmalgamation<'a, C> {
-    type V = ValidComponentAmalgamation<'a, C>;
-
-    impl_with_policy!(with_policy_relaxed, valid_cert, valid_cert: bool);
+    fn with_policy<T>(self, policy: &'a dyn Policy, time: T)
+        -> Result<Self::V>
+        where T: Into<Option<time::SystemTime>>,
+              Self: Sized
+    {
+        let vc = self.cert.with_policy(policy, time)?;
+        self.with_valid_cert(&vc)
+    }
 }
 
 impl<'a> UserIDAmalgamation<'a> {
@@ -2130,10 +2066,12 @@ impl<'a, C> ValidComponentAmalgamation<'a, C>
     /// If there is more than one, then one is selected in a
     /// deterministic, but undefined manner.
     ///
-    /// If `valid_cert` is `false`, then this does not also check
-    /// whether the certificate is valid; it only checks whether the
-    /// component is valid.  Normally, this should be `true`.  This
-    /// option is only exposed to allow breaking an infinite recursion:
+    /// `valid_cert` is the certificate validated under `policy` at
+    /// time `t`; its cache of binding signatures is used.  If
+    /// `valid_cert` is `None`, then this does not check whether the
+    /// certificate is valid; it only checks whether the component is
+    /// valid.  Normally, this should be `Some`.  This option is only
+    /// exposed to allow breaking an infinite recursion:
     ///
     ///   - To check if a certificate is valid, we check if the
     ///     primary key is valid.
@@ -2152,7 +2090,7 @@ impl<'a, C> ValidComponentAmalgamation<'a, C>
     pub(super) fn primary(cert: &'a Cert,
                           iter: std::slice::Iter<'a, ComponentBundle<C>>,
                           policy: &'a dyn Policy, t: SystemTime,
-                          valid_cert: bool)
+                          valid_cert: Option<&ValidCert<'a>>)
         -> Result<ValidComponentAmalgamation<'a, C>>
     {
         use std::cmp::Ordering;
@@ -2166,7 +2104,11 @@ impl<'a, C> ValidComponentAmalgamation<'a, C>
         // times.
         iter.filter_map(|c| {
             // No binding signature at time `t` => not alive.
-            let sig = match c.binding_signature(policy, t) {
+            let sig = match valid_cert {
+                Some(vc) => vc.bundle_binding_signature(c),
+                None => c.binding_signature(policy, t),
+            };
+            let sig = match sig {
                 Ok(sig) => Some(sig),
                 Err(e) => {
                     error = Some(e);
@@ -2221,8 +2163,12 @@ impl<'a, C> ValidComponentAmalgamation<'a, C>
                     "No binding signature at time {}", crate::fmt::time(&t))))
                     .unwrap_or_else(|| Error::NoBindingSignature(t).into())
             })
-            .and_then(|c| ComponentAmalgamation::new(cert, (c.0).0)
-                      .with_policy_relaxed(policy, t, valid_cert))
+            .map(|((c, sig, _), _, _)| ValidComponentAmalgamation {
+                ca: ComponentAmalgamation::new(cert, c),
+                cert: valid_cert.cloned().unwrap_or_else(
+                    || ValidCert::new(cert, policy, t, None)),
+                binding_signature: sig,
+            })
     }
 
     /// The component's self-signatures.
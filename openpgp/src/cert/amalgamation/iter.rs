@@ -179,9 +179,24 @@ impl<'a, C> ComponentAmalgamationIter<'a, C> {
             iter: self.iter,
             time: time.into().unwrap_or_else(crate::now),
             policy,
+            valid_cert: None,
             revoked: None,
         }
     }
+
+    /// Changes the iterator to only return components that are valid
+    /// for the already validated certificate `vc`.
+    ///
+    /// This is like `with_policy`, but uses `vc`'s policy and
+    /// reference time, does not check the certificate again, and
+    /// shares `vc`'s cache of binding signatures.
+    pub(crate) fn with_valid_cert(self, vc: &ValidCert<'a>)
+        -> ValidComponentAmalgamationIter<'a, C>
+    {
+        let mut iter = self.with_policy(vc.policy(), vc.time());
+        iter.valid_cert = Some(vc.clone());
+        iter
+    }
 }
 
 /// An iterator over valid components.
@@ -235,6 +250,11 @@ pub struct ValidComponentAmalgamationIter<'a, C> {
     // The time.
     time: SystemTime,
 
+    // The certificate validated under `policy` at `time`, once it has
+    // been checked.  This avoids checking the certificate again for
+    // every component, and shares the cache of binding signatures.
+    valid_cert: Option<ValidCert<'a>>,
+
     // If not None, filters by whether the component is revoked or not
     // at time `t`.
     revoked: Option<bool>,
@@ -276,7 +296,19 @@ impl<'a, C> Iterator for ValidComponentAmalgamationIter<'a, C>
             let ca = ComponentAmalgamation::new(self.cert, self.iter.next()?);
             t!("Considering component: {:?}", ca.component());
 
-            let vca = match ca.with_policy(self.policy, self.time) {
+            if self.valid_cert.is_none() {
+                match self.cert.with_policy(self.policy, self.time) {
+                    Ok(vc) => self.valid_cert = Some(vc),
+                    Err(e) => {
+                        // The certificate is bad.  Abort.
+                        t!("Invalid certificate: {}", e);
+                        return None;
+                    },
+                }
+            }
+            let vc = self.valid_cert.as_ref().expect("set above");
+
+            let vca = match ca.with_valid_cert(vc) {
                 Ok(vca) => vca,
                 Err(e) => {
                     t!("Rejected: {}", e);
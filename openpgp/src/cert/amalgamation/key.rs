@@ -263,6 +263,7 @@ use crate::{
         ValidAmalgamation,
         ValidateAmalgamation,
     },
+    cert::Preferences,
     cert::ValidCert,
     crypto::Signer,
     Error,
@@ -275,10 +276,17 @@ use crate::{
     Result,
     seal,
     types::{
+        AEADAlgorithm,
+        CompressionAlgorithm,
+        Features,
+        HashAlgorithm,
         KeyFlags,
+        KeyServerPreferences,
         RevocationKey,
         RevocationStatus,
         SignatureType,
+        SymmetricAlgorithm,
+        Timestamp,
     },
 };
 
@@ -571,37 +579,69 @@ impl<'a, P> ValidateAmalgamation<'a, Key<P, key::UnspecifiedRole>>
         // We need to make sure the certificate is okay.  This means
         // checking the primary key.  But, be careful: we don't need
         // to double check.
-        if ! self.primary() {
+        let primary_key_binding = if ! self.primary() {
             let pka = PrimaryKeyAmalgamation::new(self.cert());
-            pka.with_policy(policy, time).context("primary key")?;
-        }
+            Some(pka.with_policy(policy, time).context("primary key")?
+                 .binding_signature())
+        } else {
+            None
+        };
 
         let binding_signature = self.binding_signature(policy, time)?;
         let cert = self.ca.cert();
+        let primary = self.primary;
+        // We need some black magic to avoid infinite
+        // recursion: a ValidCert must be valid for the
+        // specified policy and reference time.  A ValidCert
+        // is consider valid if the primary key is valid.
+        // ValidCert::with_policy checks that by calling this
+        // function.  So, if we call ValidCert::with_policy
+        // here we'll recurse infinitely.
+        //
+        // But, hope is not lost!  We know that if we get
+        // here, we've already checked that the primary key is
+        // valid (see above), or that we're in the process of
+        // evaluating the primary key's validity and we just
+        // need to check the user's policy.  So, it is safe to
+        // create a ValidCert from scratch.
+        self.validate(ValidCert::new(
+            cert, policy, time,
+            primary_key_binding.or(primary.then_some(binding_signature))),
+                      binding_signature)
+    }
+}
+
+impl<'a, P> ErasedKeyAmalgamation<'a, P>
+    where P: 'a + key::KeyParts
+{
+    /// Returns a `ValidErasedKeyAmalgamation` for a subkey of an
+    /// already validated certificate.
+    ///
+    /// This is like `with_policy`, but uses `vc`'s policy and
+    /// reference time, does not check the primary key again, and
+    /// uses `vc`'s cache of binding signatures.
+    pub(crate) fn with_valid_cert(self, vc: &ValidCert<'a>)
+        -> Result<ValidErasedKeyAmalgamation<'a, P>>
+    {
+        assert!(! self.primary);
+        assert!(std::ptr::eq(self.cert(), vc.cert()));
+
+        let binding_signature = vc.bundle_binding_signature(self.bundle())?;
+        self.validate(vc.clone(), binding_signature)
+    }
+
+    /// Checks the key against `cert`'s policy given its binding
+    /// signature.
+    fn validate(self, cert: ValidCert<'a>, binding_signature: &'a Signature)
+        -> Result<ValidErasedKeyAmalgamation<'a, P>>
+    {
+        let policy = cert.policy();
         let vka = ValidErasedKeyAmalgamation {
             ka: KeyAmalgamation {
                 ca: self.ca.parts_into_public(),
                 primary: self.primary,
             },
-            // We need some black magic to avoid infinite
-            // recursion: a ValidCert must be valid for the
-            // specified policy and reference time.  A ValidCert
-            // is consider valid if the primary key is valid.
-            // ValidCert::with_policy checks that by calling this
-            // function.  So, if we call ValidCert::with_policy
-            // here we'll recurse infinitely.
-            //
-            // But, hope is not lost!  We know that if we get
-            // here, we've already checked that the primary key is
-            // valid (see above), or that we're in the process of
-            // evaluating the primary key's validity and we just
-            // need to check the user's policy.  So, it is safe to
-            // create a ValidCert from scratch.
-            cert: ValidCert {
-                cert,
-                policy,
-                time,
-            },
+            cert,
             binding_signature
         };
         policy.key(&vka)?;
@@ -757,6 +797,22 @@ impl<'a> PrimaryKeyAmalgamation<'a, key::PublicParts> {
     }
 }
 
+impl<'a> ValidPrimaryKeyAmalgamation<'a, key::PublicParts> {
+    /// Returns the primary key of an already validated certificate.
+    ///
+    /// `binding_signature` must be the primary key's binding
+    /// signature under `cert`'s policy at `cert`'s reference time.
+    pub(crate) fn new(cert: ValidCert<'a>, binding_signature: &'a Signature)
+                      -> Self
+    {
+        ValidKeyAmalgamation {
+            ka: PrimaryKeyAmalgamation::new(cert.cert()),
+            cert,
+            binding_signature,
+        }
+    }
+}
+
 impl<'a, P: 'a + key::KeyParts> SubordinateKeyAmalgamation<'a, P> {
     pub(crate) fn new(
         cert: &'a Cert, bundle: &'a KeyBundle<P, key::SubordinateRole>)
@@ -782,7 +838,7 @@ impl<'a, P: 'a + key::KeyParts> ErasedKeyAmalgamation<'a, P> {
     {
         let time = time.into().unwrap_or_else(crate::now);
         if self.primary {
-            self.cert().primary_userid_relaxed(policy, time, false)
+            self.cert().primary_userid_relaxed(policy, time, None)
                 .map(|u| u.binding_signature())
                 .or_else(|e0| {
                     // Lookup of the primary user id binding failed.
@@ -1574,6 +1630,55 @@ impl<'a, P, R, R2> ValidAmalgamation<'a, Key<P, R>>
     }
 }
 
+impl<'a, P, R, R2> ValidKeyAmalgamation<'a, P, R, R2>
+    where P: 'a + key::KeyParts,
+          R: 'a + key::KeyRole,
+          R2: Copy,
+          Self: PrimaryKey<'a, P, R>,
+{
+    /// Looks up a preference.
+    ///
+    /// The primary key is addressed like the certificate, see
+    /// [`Preferences`].  For subkeys, the binding signature takes
+    /// precedence over the certificate's preferences.
+    ///
+    ///   [`Preferences`]: crate::cert::Preferences
+    fn preference<T, S, C>(&self, sig: S, cert: C) -> Option<T>
+        where S: Fn(&'a Signature) -> Option<T>,
+              C: Fn(&ValidCert<'a>) -> Option<T>,
+    {
+        if self.primary() {
+            cert(&self.cert)
+        } else {
+            sig(self.binding_signature).or_else(|| cert(&self.cert))
+        }
+    }
+}
+
+macro_rules! impl_pref {
+    ($subpacket:ident, $rt:ty) => {
+        #[allow(deprecated)]
+        fn $subpacket(&self) -> Option<$rt> {
+            self.preference(|s| s.$subpacket(), |c| c.$subpacket())
+        }
+    }
+}
+
+impl<'a, P, R, R2> Preferences<'a> for ValidKeyAmalgamation<'a, P, R, R2>
+    where P: 'a + key::KeyParts,
+          R: 'a + key::KeyRole,
+          R2: Copy,
+          Self: PrimaryKey<'a, P, R>,
+{
+    impl_pref!(preferred_symmetric_algorithms, &'a [SymmetricAlgorithm]);
+    impl_pref!(preferred_hash_algorithms, &'a [HashAlgorithm]);
+    impl_pref!(preferred_compression_algorithms, &'a [CompressionAlgorithm]);
+    impl_pref!(preferred_aead_algorithms, &'a [AEADAlgorithm]);
+    impl_pref!(key_server_preferences, KeyServerPreferences);
+    impl_pref!(preferred_key_server, &'a [u8]);
+    impl_pref!(policy_uri, &'a [u8]);
+    impl_pref!(features, Features);
+}
 
 impl<'a, P> PrimaryKey<'a, P, key::PrimaryRole>
     for ValidPrimaryKeyAmalgamation<'a, P>
@@ -2240,7 +2345,7 @@ impl<'a, P> ValidErasedKeyAmalgamation<'a, P>
         -> Result<Vec<Signature>>
     {
         let expiration =
-            if let Some(e) = expiration.map(crate::types::normalize_systemtime)
+            if let Some(e) = expiration.map(Timestamp::normalize).transpose()?
         {
             let ct = self.creation_time();
             match e.duration_since(ct) {
@@ -3444,4 +3549,47 @@ mod test {
         key_amalgamation_valid_third_party_revocations_by_key(
             ReasonForRevocation::KeyCompromised)
     }
+
+    #[test]
+    fn preferences() -> Result<()> {
+        let p = &P::new();
+
+        let (cert, _) = CertBuilder::new()
+            .add_userid("Alice")
+            .add_signing_subkey()
+            .add_subkey_with(
+                KeyFlags::empty().set_storage_encryption(), None, None,
+                SignatureBuilder::new(SignatureType::SubkeyBinding)
+                    .set_preferred_symmetric_algorithms(
+                        vec![SymmetricAlgorithm::AES128])?)?
+            .generate()?;
+        let vc = cert.with_policy(p, None)?;
+        let cert_algos = vc.preferred_symmetric_algorithms();
+        assert!(cert_algos.is_some());
+        assert_ne!(cert_algos, Some(&[SymmetricAlgorithm::AES128][..]));
+
+        // The primary key is addressed like the certificate.
+        let pka = vc.primary_key();
+        assert_eq!(pka.preferred_symmetric_algorithms(), cert_algos);
+        assert_eq!(pka.features(), vc.features());
+        assert_eq!(ValidErasedKeyAmalgamation::<key::PublicParts>::from(pka)
+                   .preferred_symmetric_algorithms(), cert_algos);
+
+        // The signing subkey's binding signature doesn't have
+        // preferences, so we fall back to the certificate.
+        let ka = vc.keys().for_signing().next().unwrap();
+        assert!(ka.binding_signature().preferred_symmetric_algorithms()
+                .is_none());
+        assert_eq!(ka.preferred_symmetric_algorithms(), cert_algos);
+        assert_eq!(ka.preferred_hash_algorithms(),
+                   vc.preferred_hash_algorithms());
+
+        // The encryption subkey's binding signature takes precedence.
+        let ka = vc.keys().for_storage_encryption().next().unwrap();
+        assert_eq!(ka.preferred_symmetric_algorithms(),
+                   Some(&[SymmetricAlgorithm::AES128][..]));
+        assert_eq!(ka.preferred_hash_algorithms(),
+                   vc.preferred_hash_algorithms());
+        Ok(())
+    }
 }
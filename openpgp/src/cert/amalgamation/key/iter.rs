@@ -580,6 +580,7 @@ impl<'a, P, R> KeyAmalgamationIter<'a, P, R>
 
             policy,
             time: time.into().unwrap_or_else(crate::now),
+            valid_cert: None,
 
             // The filters.
             encrypted_secret: self.encrypted_secret,
@@ -594,6 +595,20 @@ impl<'a, P, R> KeyAmalgamationIter<'a, P, R>
             _r: self._r,
         }
     }
+
+    /// Changes the iterator to only return keys that are valid for
+    /// the already validated certificate `vc`.
+    ///
+    /// This is like `with_policy`, but uses `vc`'s policy and
+    /// reference time, does not check the primary key again, and
+    /// shares `vc`'s cache of binding signatures.
+    pub(crate) fn with_valid_cert(self, vc: &ValidCert<'a>)
+        -> ValidKeyAmalgamationIter<'a, P, R>
+    {
+        let mut iter = self.with_policy(vc.policy(), vc.time());
+        iter.valid_cert = Some(vc.clone());
+        iter
+    }
 }
 
 /// An iterator over valid `Key`s.
@@ -690,6 +705,12 @@ pub struct ValidKeyAmalgamationIter<'a, P, R>
     // The time.
     time: SystemTime,
 
+    // The certificate validated under `policy` at `time`, once the
+    // primary key has been checked.  This avoids checking the
+    // primary key again for every subkey, and shares the cache of
+    // binding signatures.
+    valid_cert: Option<ValidCert<'a>>,
+
     // If not None, filters by whether a key has an encrypted secret.
     encrypted_secret: Option<bool>,
 
@@ -803,19 +824,37 @@ impl<'a, P, R> ValidKeyAmalgamationIter<'a, P, R>
                 self.primary = true;
                 let ka : ErasedKeyAmalgamation<'a, key::PublicParts>
                     = PrimaryKeyAmalgamation::new(cert).into();
-                match ka.with_policy(self.policy, self.time) {
-                    Ok(ka) => ka,
-                    Err(err) => {
-                        // The primary key is bad.  Abort.
-                        t!("Getting primary key: {:?}", err);
-                        return None;
+                if let Some(vc) = self.valid_cert.as_ref() {
+                    vc.primary_key().into()
+                } else {
+                    match ka.with_policy(self.policy, self.time) {
+                        Ok(ka) => {
+                            self.valid_cert = Some(ka.cert().clone());
+                            ka
+                        },
+                        Err(err) => {
+                            // The primary key is bad.  Abort.
+                            t!("Getting primary key: {:?}", err);
+                            return None;
+                        }
                     }
                 }
             } else {
                 let ka : ErasedKeyAmalgamation<'a, key::PublicParts>
                     = SubordinateKeyAmalgamation::new(
                         cert, self.subkey_iter.next()?).into();
-                match ka.with_policy(self.policy, self.time) {
+                if self.valid_cert.is_none() {
+                    match cert.with_policy(self.policy, self.time) {
+                        Ok(vc) => self.valid_cert = Some(vc),
+                        Err(err) => {
+                            // The primary key is bad.  Abort.
+                            t!("Getting primary key: {:?}", err);
+                            return None;
+                        }
+                    }
+                }
+                let vc = self.valid_cert.as_ref().expect("set above");
+                match ka.with_valid_cert(vc) {
                     Ok(ka) => ka,
                     Err(err) => {
                         // The subkey is bad, abort.
@@ -1381,6 +1420,7 @@ impl<'a, P, R> ValidKeyAmalgamationIter<'a, P, R>
 
             time: self.time,
             policy: self.policy,
+            valid_cert: self.valid_cert,
 
             // The filters.
             encrypted_secret: Some(true),
@@ -1430,6 +1470,7 @@ impl<'a, P, R> ValidKeyAmalgamationIter<'a, P, R>
 
             time: self.time,
             policy: self.policy,
+            valid_cert: self.valid_cert,
 
             // The filters.
             encrypted_secret: Some(true),
@@ -1475,6 +1516,7 @@ impl<'a, P, R> ValidKeyAmalgamationIter<'a, P, R>
 
             time: self.time,
             policy: self.policy,
+            valid_cert: self.valid_cert,
 
             // The filters.
             encrypted_secret: self.encrypted_secret,
@@ -1661,6 +1703,7 @@ impl<'a, P, R> ValidKeyAmalgamationIter<'a, P, R>
 
             time: self.time,
             policy: self.policy,
+            valid_cert: self.valid_cert,
 
             // The filters.
             encrypted_secret: self.encrypted_secret,
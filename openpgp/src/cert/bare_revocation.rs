@@ -0,0 +1,490 @@
+//! Revocation certificates that are not attached to a certificate.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+use std::path::Path;
+
+use buffered_reader::BufferedReader;
+
+use crate::{
+    Cert,
+    Error,
+    KeyHandle,
+    KeyID,
+    Packet,
+    PacketPile,
+    Result,
+    packet::{key, Key, Signature, UserAttribute, UserID},
+    parse::{Cookie, Parse},
+    types::{RevocationType, SignatureType},
+};
+
+/// A revocation certificate that is not attached to a certificate.
+///
+/// A revocation certificate for an OpenPGP certificate is a single
+/// [key revocation signature].  It is usually created ahead of time
+/// and stored in a safe place, so that the certificate can be revoked
+/// if the secret key material is lost or compromised.  When it is
+/// published, the recipient, e.g. a key server, may not have the
+/// certificate it revokes.  This data structure holds such a
+/// revocation until the certificate shows up.  See
+/// [`PendingRevocations`] for a collection of such revocations.
+///
+/// Since the signature isn't bound to the certificate, it is not
+/// possible to verify it on its own.  [`BareRevocation::issuers`]
+/// returns the key handles of the key that allegedly made the
+/// revocation, which is the certificate's primary key, unless the
+/// revocation was made by a designated revoker.
+/// [`BareRevocation::attach`] verifies the revocation and merges it
+/// into the certificate.
+///
+/// A `BareRevocation` can be parsed using [`Parse`].  When parsing,
+/// [`Marker`] packets are skipped, and the input must contain exactly
+/// one key revocation signature.
+///
+///   [key revocation signature]: https://www.rfc-editor.org/rfc/rfc9580.html#name-key-revocation-signature-ty
+///   [`Marker`]: crate::packet::Marker
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::Packet;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::cert::BareRevocation;
+/// use openpgp::parse::Parse;
+/// use openpgp::policy::StandardPolicy;
+/// use openpgp::serialize::MarshalInto;
+/// use openpgp::types::RevocationStatus;
+///
+/// # fn main() -> openpgp::Result<()> {
+/// let p = &StandardPolicy::new();
+/// let (cert, rev) = CertBuilder::new()
+///     .add_userid("alice@example.org")
+///     .generate()?;
+/// let cert = cert.strip_secret_key_material();
+///
+/// // The revocation certificate is published on its own.
+/// let rev = BareRevocation::from_bytes(&Packet::from(rev).to_vec()?)?;
+/// assert!(rev.issuers().iter()
+///         .any(|h| h.aliases(cert.key_handle())));
+///
+/// // Once we get hold of the certificate, we attach it.
+/// let cert = rev.attach(cert)?;
+/// assert!(matches!(cert.revocation_status(p, None),
+///                  RevocationStatus::Revoked(_)));
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BareRevocation {
+    sig: Signature,
+}
+
+impl BareRevocation {
+    /// Returns the revocation signature.
+    pub fn signature(&self) -> &Signature {
+        &self.sig
+    }
+
+    /// Returns the key handles of the key that allegedly made the
+    /// revocation.
+    ///
+    /// These are taken from the signature's issuer and issuer
+    /// fingerprint subpackets, fingerprints first.  They have not
+    /// been authenticated.
+    ///
+    /// Unless the revocation was made by a designated revoker, this
+    /// is the primary key of the revoked certificate.
+    pub fn issuers(&self) -> Vec<KeyHandle> {
+        self.sig.get_issuers()
+    }
+
+    /// Verifies the revocation, and merges it into `cert`.
+    ///
+    /// The revocation must have been made by `cert`'s primary key.
+    /// Revocations made by designated revokers cannot be verified
+    /// without the revoker's certificate, and are rejected.
+    pub fn attach(self, cert: Cert) -> Result<Cert> {
+        let pk = cert.primary_key().key();
+        if ! self.issuers().iter().any(|h| h.aliases(pk.key_handle())) {
+            return Err(Error::InvalidArgument(format!(
+                "Revocation was not issued by {}", pk.fingerprint())).into());
+        }
+
+        self.sig.verify_primary_key_revocation(pk, pk)?;
+        cert.insert_packets(self.sig)
+    }
+}
+
+impl TryFrom<Signature> for BareRevocation {
+    type Error = anyhow::Error;
+
+    /// Converts a key revocation signature.
+    ///
+    /// The signature must be a key revocation signature, and it must
+    /// have at least one issuer or issuer fingerprint subpacket.
+    fn try_from(sig: Signature) -> Result<Self> {
+        if sig.typ() != SignatureType::KeyRevocation {
+            return Err(Error::UnsupportedSignatureType(sig.typ()).into());
+        }
+
+        if sig.get_issuers().is_empty() {
+            return Err(Error::InvalidArgument(
+                "Revocation does not identify the issuer".into()).into());
+        }
+
+        Ok(BareRevocation { sig })
+    }
+}
+
+impl TryFrom<Vec<Packet>> for BareRevocation {
+    type Error = anyhow::Error;
+
+    /// Converts a sequence of packets.
+    ///
+    /// Marker packets are skipped.  The remaining packets must be
+    /// exactly one key revocation signature.
+    fn try_from(packets: Vec<Packet>) -> Result<Self> {
+        let mut packets = packets.into_iter()
+            .filter(|p| ! matches!(p, Packet::Marker(_)));
+        match (packets.next(), packets.next()) {
+            (Some(Packet::Signature(sig)), None) => Self::try_from(sig),
+            (Some(p), None) => Err(Error::MalformedPacket(format!(
+                "Expected a signature, got a {} packet", p.tag())).into()),
+            (None, _) => Err(Error::MalformedPacket(
+                "Expected a signature, got nothing".into()).into()),
+            (Some(_), Some(_)) => Err(Error::MalformedPacket(
+                "Expected a single signature".into()).into()),
+        }
+    }
+}
+
+impl TryFrom<PacketPile> for BareRevocation {
+    type Error = anyhow::Error;
+
+    /// Converts a packet pile.
+    ///
+    /// Marker packets are skipped.  The remaining packets must be
+    /// exactly one key revocation signature.
+    fn try_from(pile: PacketPile) -> Result<Self> {
+        Self::try_from(Vec::from(pile))
+    }
+}
+
+impl From<BareRevocation> for Signature {
+    fn from(rev: BareRevocation) -> Self {
+        rev.sig
+    }
+}
+
+impl From<BareRevocation> for Packet {
+    fn from(rev: BareRevocation) -> Self {
+        rev.sig.into()
+    }
+}
+
+/// What a revocation applies to, and how.
+///
+/// This is returned by [`Cert::validate_revocation`].  It identifies
+/// the revoked component, and whether the revocation is a
+/// [hard or soft revocation].
+///
+///   [hard or soft revocation]: crate::types::RevocationType
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevocationClass<'a> {
+    /// The revocation revokes the certificate.
+    PrimaryKey(RevocationType),
+
+    /// The revocation revokes the given subkey.
+    Subkey(&'a Key<key::PublicParts, key::SubordinateRole>, RevocationType),
+
+    /// The revocation revokes the given user ID.
+    UserID(&'a UserID, RevocationType),
+
+    /// The revocation revokes the given user attribute.
+    UserAttribute(&'a UserAttribute, RevocationType),
+}
+
+impl RevocationClass<'_> {
+    /// Returns whether the revocation is a hard or soft revocation.
+    pub fn revocation_type(&self) -> RevocationType {
+        match self {
+            RevocationClass::PrimaryKey(t)
+                | RevocationClass::Subkey(_, t)
+                | RevocationClass::UserID(_, t)
+                | RevocationClass::UserAttribute(_, t) => *t,
+        }
+    }
+}
+
+impl<'a> Parse<'a, BareRevocation> for BareRevocation {
+    /// Reads a revocation certificate from the specified reader.
+    fn from_buffered_reader<R>(reader: R) -> Result<BareRevocation>
+    where
+        R: BufferedReader<Cookie> + 'a,
+    {
+        Self::try_from(PacketPile::from_buffered_reader(reader)?)
+    }
+
+    /// Reads a revocation certificate from the specified reader.
+    fn from_reader<R: 'a + io::Read + Send + Sync>(reader: R) -> Result<Self> {
+        Self::try_from(PacketPile::from_reader(reader)?)
+    }
+
+    /// Reads a revocation certificate from the specified file.
+    fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::try_from(PacketPile::from_file(path)?)
+    }
+
+    /// Reads a revocation certificate from `buf`.
+    fn from_bytes<D: AsRef<[u8]> + ?Sized + Send + Sync>(data: &'a D) -> Result<Self> {
+        Self::try_from(PacketPile::from_bytes(data)?)
+    }
+}
+
+/// Revocation certificates waiting for their certificates.
+///
+/// Holds [`BareRevocation`]s indexed by their issuers, so that
+/// looking up and attaching the revocations for a certificate doesn't
+/// depend on the number of pending revocations.  When a certificate
+/// arrives, [`PendingRevocations::attach`] merges the revocations
+/// made by its primary key into it.
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::cert::{BareRevocation, PendingRevocations};
+/// use openpgp::policy::StandardPolicy;
+/// use openpgp::types::RevocationStatus;
+///
+/// # fn main() -> openpgp::Result<()> {
+/// let p = &StandardPolicy::new();
+/// let (cert, rev) = CertBuilder::new().generate()?;
+///
+/// let mut pending = PendingRevocations::new();
+/// pending.insert(BareRevocation::try_from(rev)?);
+/// assert_eq!(pending.get(&cert.key_handle()).count(), 1);
+///
+/// let cert = pending.attach(cert)?;
+/// assert!(matches!(cert.revocation_status(p, None),
+///                  RevocationStatus::Revoked(_)));
+/// assert!(pending.is_empty());
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PendingRevocations {
+    /// Maps the key ID of the first issuer of each revocation, see
+    /// [`BareRevocation::issuers`], to the revocations.
+    revocations: HashMap<KeyID, Vec<BareRevocation>>,
+}
+
+impl PendingRevocations {
+    /// Returns an empty set of pending revocations.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the number of pending revocations.
+    pub fn len(&self) -> usize {
+        self.revocations.values().map(Vec::len).sum()
+    }
+
+    /// Returns whether there are no pending revocations.
+    pub fn is_empty(&self) -> bool {
+        self.revocations.is_empty()
+    }
+
+    /// Iterates over the pending revocations.
+    pub fn iter(&self) -> impl Iterator<Item = &BareRevocation> {
+        self.revocations.values().flatten()
+    }
+
+    /// Adds a revocation.
+    ///
+    /// Duplicates are ignored.
+    pub fn insert(&mut self, rev: BareRevocation) {
+        // BareRevocations always have at least one issuer.
+        let id = KeyID::from(&rev.issuers()[0]);
+        let revs = self.revocations.entry(id).or_default();
+        if ! revs.contains(&rev) {
+            revs.push(rev);
+        }
+    }
+
+    /// Returns the pending revocations allegedly issued by `handle`.
+    ///
+    /// Revocations are indexed by their first issuer.  A revocation
+    /// is only valid if it was made by the revoked certificate's
+    /// primary key, so all of its issuers should name the same key.
+    pub fn get<'a>(&'a self, handle: &KeyHandle)
+        -> impl Iterator<Item = &'a BareRevocation> + 'a
+    {
+        let handle = handle.clone();
+        self.revocations.get(&KeyID::from(&handle))
+            .into_iter()
+            .flatten()
+            .filter(move |rev| rev.issuers().iter().any(|h| h.aliases(&handle)))
+    }
+
+    /// Merges the pending revocations for `cert` into it.
+    ///
+    /// All pending revocations allegedly issued by `cert`'s primary
+    /// key are removed.  Those that can be verified are merged into
+    /// `cert`, the others are discarded.
+    pub fn attach(&mut self, mut cert: Cert) -> Result<Cert> {
+        let handle = cert.key_handle();
+        let id = KeyID::from(&handle);
+        let revs = match self.revocations.get_mut(&id) {
+            Some(revs) => {
+                // Key IDs may collide, keep revocations for other
+                // certificates.
+                let (revs, pending): (Vec<_>, Vec<_>) =
+                    std::mem::take(revs).into_iter()
+                    .partition(|rev| {
+                        rev.issuers().iter().any(|h| h.aliases(&handle))
+                    });
+                if pending.is_empty() {
+                    self.revocations.remove(&id);
+                } else {
+                    self.revocations.insert(id, pending);
+                }
+                revs
+            },
+            None => return Ok(cert),
+        };
+
+        for rev in revs {
+            let pk = cert.primary_key().key();
+            if rev.sig.verify_primary_key_revocation(pk, pk).is_ok() {
+                cert = cert.insert_packets(rev.sig)?;
+            }
+        }
+        Ok(cert)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::armor;
+    use crate::cert::prelude::*;
+    use crate::packet::signature::SignatureBuilder;
+    use crate::policy::StandardPolicy;
+    use crate::serialize::{Marshal, MarshalInto};
+    use crate::types::RevocationStatus;
+
+    #[test]
+    fn parse() -> Result<()> {
+        let (cert, rev) = CertBuilder::new().generate()?;
+
+        let r = BareRevocation::from_bytes(
+            &Packet::from(rev.clone()).to_vec()?)?;
+        assert_eq!(r.signature(), &rev);
+
+        // Revocation certificates are usually armored as public key
+        // blocks.
+        let mut w = armor::Writer::new(Vec::new(), armor::Kind::PublicKey)?;
+        Packet::from(rev.clone()).serialize(&mut w)?;
+        let r = BareRevocation::from_bytes(&w.finalize()?)?;
+        assert_eq!(r.signature(), &rev);
+
+        // A certificate is not a revocation certificate.
+        assert!(BareRevocation::from_bytes(&cert.armored().to_vec()?)
+                .is_err());
+
+        // Not a key revocation.
+        let sig = cert.primary_key().self_signatures().next().unwrap();
+        assert!(BareRevocation::try_from(sig.clone()).is_err());
+
+        // Two signatures.
+        let mut buf = Packet::from(rev.clone()).to_vec()?;
+        buf.extend_from_slice(&Packet::from(rev.clone()).to_vec()?);
+        assert!(BareRevocation::from_bytes(&buf).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pending() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (alice, alice_rev) = CertBuilder::new().generate()?;
+        let (bob, bob_rev) = CertBuilder::new().generate()?;
+
+        // A revocation claiming to be from Alice, but signed by Bob.
+        let mut signer = bob.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        let forged = SignatureBuilder::new(SignatureType::KeyRevocation)
+            .set_issuer_fingerprint(alice.fingerprint())?
+            .sign_direct_key(&mut signer, alice.primary_key().key())?;
+
+        let mut pending = PendingRevocations::new();
+        pending.insert(BareRevocation::try_from(alice_rev.clone())?);
+        pending.insert(BareRevocation::try_from(alice_rev.clone())?);
+        pending.insert(BareRevocation::try_from(forged)?);
+        pending.insert(BareRevocation::try_from(bob_rev)?);
+        assert_eq!(pending.len(), 3);
+        assert_eq!(pending.get(&alice.key_handle()).count(), 2);
+        assert_eq!(pending.get(&alice.keyid().into()).count(), 2);
+
+        // Bad revocations are not attached, but discarded.
+        let alice = pending.attach(alice)?;
+        assert_eq!(alice.revocation_status(p, None),
+                   RevocationStatus::Revoked(vec![&alice_rev]));
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending.get(&alice.key_handle()).count(), 0);
+
+        // Bob's revocation can't be attached to Alice's cert.
+        let rev = pending.get(&bob.key_handle()).next().unwrap().clone();
+        assert!(rev.clone().attach(alice).is_err());
+        let bob = rev.attach(bob)?;
+        assert!(matches!(bob.revocation_status(p, None),
+                         RevocationStatus::Revoked(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_revocation() -> Result<()> {
+        use crate::types::ReasonForRevocation;
+
+        let (alice, alice_rev) = CertBuilder::new()
+            .add_userid("Alice")
+            .add_signing_subkey()
+            .generate()?;
+        let (bob, bob_rev) = CertBuilder::new().generate()?;
+        let mut signer = alice.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+
+        assert_eq!(alice.validate_revocation(&alice_rev)?,
+                   RevocationClass::PrimaryKey(RevocationType::Hard));
+        assert!(bob.validate_revocation(&alice_rev).is_err());
+        assert!(alice.validate_revocation(&bob_rev).is_err());
+
+        let subkey = alice.keys().subkeys().next().unwrap();
+        let rev = SubkeyRevocationBuilder::new()
+            .set_reason_for_revocation(ReasonForRevocation::KeyRetired, b"")?
+            .build(&mut signer, &alice, subkey.key(), None)?;
+        let class = alice.validate_revocation(&rev)?;
+        assert_eq!(class, RevocationClass::Subkey(subkey.key(),
+                                                  RevocationType::Soft));
+        assert_eq!(class.revocation_type(), RevocationType::Soft);
+        // The subkey isn't part of Bob's certificate.
+        assert!(bob.validate_revocation(&rev).is_err());
+
+        let userid = alice.userids().next().unwrap().userid();
+        let rev = UserIDRevocationBuilder::new()
+            .set_reason_for_revocation(ReasonForRevocation::KeyCompromised,
+                                       b"")?
+            .build(&mut signer, &alice, userid, None)?;
+        assert_eq!(alice.validate_revocation(&rev)?,
+                   RevocationClass::UserID(userid, RevocationType::Hard));
+
+        // Not a revocation.
+        let sig = alice.primary_key().self_signatures().next().unwrap();
+        assert!(alice.validate_revocation(sig).is_err());
+        Ok(())
+    }
+}
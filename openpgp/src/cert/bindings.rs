@@ -3,7 +3,7 @@ use std::time;
 use crate::Error;
 use crate::Result;
 use crate::Cert;
-use crate::types::{HashAlgorithm, SignatureType};
+use crate::types::{HashAlgorithm, KeyFlags, SignatureType};
 use crate::crypto::Signer;
 use crate::packet::{UserID, UserAttribute, key, Key, signature, Signature};
 
@@ -16,14 +16,31 @@ impl<P: key::KeyParts> Key<P, key::SubordinateRole> {
     /// current time.
     ///
     /// Note that subkeys with signing capabilities need a [primary
-    /// key binding signature].  If you are creating this binding
-    /// signature from a previous binding signature, you can reuse the
-    /// primary key binding signature if it is still valid and meets
-    /// current algorithm requirements.  Otherwise, you can create one
-    /// using [`SignatureBuilder::sign_primary_key_binding`].
+    /// key binding signature].  If `signature` marks the subkey as
+    /// signing, certification, or authentication capable, does not
+    /// already contain a primary key binding signature, and the
+    /// subkey has unencrypted secret key material, this function
+    /// creates one and embeds it in the binding signature.
+    ///
+    /// If you are creating this binding signature from a previous
+    /// binding signature, you can reuse the primary key binding
+    /// signature if it is still valid and meets current algorithm
+    /// requirements.  If the subkey's secret key material is not
+    /// available, you need to create the primary key binding
+    /// signature yourself using
+    /// [`SignatureBuilder::sign_primary_key_binding`], and embed it
+    /// using [`SignatureBuilder::set_embedded_signature`].
     ///
     ///   [primary key binding signature]: https://tools.ietf.org/html/rfc4880#section-5.2.1
     ///   [`SignatureBuilder::sign_primary_key_binding`]: signature::SignatureBuilder::sign_primary_key_binding()
+    ///   [`SignatureBuilder::set_embedded_signature`]: signature::SignatureBuilder::set_embedded_signature()
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if the subkey is signing
+    /// capable, `signature` does not contain a primary key binding
+    /// signature, and the subkey's secret key material is not
+    /// available.
     ///
     /// This function adds a creation time subpacket, a issuer
     /// fingerprint subpacket, and a issuer subpacket to the
@@ -69,10 +86,75 @@ impl<P: key::KeyParts> Key<P, key::SubordinateRole> {
     ///                .key_flags(flags).count(),
     ///            1);
     /// # Ok(()) }
+    /// ```
+    ///
+    /// Signing subkeys need a primary key binding signature, which is
+    /// created automatically if the subkey's secret key material is
+    /// available:
+    ///
+    /// ```
+    /// # use sequoia_openpgp::{*, packet::prelude::*, types::*, cert::*};
+    /// # fn main() -> Result<()> {
+    /// use sequoia_openpgp::policy::StandardPolicy;
+    /// let p = &StandardPolicy::new();
+    ///
+    /// let (cert, _) = CertBuilder::new().generate()?;
+    /// let mut keypair = cert.primary_key().key().clone()
+    ///     .parts_into_secret()?.into_keypair()?;
+    ///
+    /// let flags = KeyFlags::empty().set_signing();
+    /// let subkey: Key<_, key::SubordinateRole> =
+    ///     Key4::generate_ecc(true, Curve::Ed25519)?
+    ///     .into();
+    /// let builder = signature::SignatureBuilder::new(SignatureType::SubkeyBinding)
+    ///     .set_key_flags(flags.clone())?;
+    /// let binding = subkey.bind(&mut keypair, &cert, builder.clone())?;
+    /// assert_eq!(binding.embedded_signatures().count(), 1);
+    ///
+    /// // Without the secret key material, the primary key binding
+    /// // signature cannot be created.
+    /// let (public_subkey, _) = subkey.clone().take_secret();
+    /// assert!(public_subkey.bind(&mut keypair, &cert, builder).is_err());
+    ///
+    /// let cert = cert.insert_packets(vec![Packet::from(subkey),
+    ///                                    binding.into()])?;
+    /// assert_eq!(cert.keys().with_policy(p, None).alive().revoked(false)
+    ///                .key_flags(flags).count(),
+    ///            1);
+    /// # Ok(()) }
+    /// ```
     pub fn bind(&self, signer: &mut dyn Signer, cert: &Cert,
-                signature: signature::SignatureBuilder)
+                mut signature: signature::SignatureBuilder)
         -> Result<Signature>
     {
+        let flags = signature.key_flags().unwrap_or_else(KeyFlags::empty);
+        if (flags.for_signing() || flags.for_certification()
+            || flags.for_authentication())
+            && signature.embedded_signatures().next().is_none()
+        {
+            if self.has_unencrypted_secret() {
+                // We need to create a primary key binding signature.
+                let mut subkey_signer =
+                    self.clone().parts_into_secret()?.into_keypair()?;
+                let mut backsig =
+                    signature::SignatureBuilder::new(
+                        SignatureType::PrimaryKeyBinding)
+                    // GnuPG wants at least a 512-bit hash for P521 keys.
+                    .set_hash_algo(HashAlgorithm::SHA512);
+                if let Some(t) = signature.signature_creation_time() {
+                    backsig = backsig.set_signature_creation_time(t)?;
+                }
+                let backsig = backsig.sign_primary_key_binding(
+                    &mut subkey_signer, cert.primary_key().key(), self)?;
+                signature = signature.set_embedded_signature(backsig)?;
+            } else if flags.for_signing() {
+                return Err(Error::InvalidArgument(
+                    "Binding a signing-capable subkey requires a primary \
+                     key binding signature, or the subkey's unencrypted \
+                     secret key material".into()).into());
+            }
+        }
+
         signature.sign_subkey_binding(
             signer, cert.primary_key().key(), self)
     }
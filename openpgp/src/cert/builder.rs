@@ -23,6 +23,7 @@ use crate::types::{
     Features,
     HashAlgorithm,
     KeyFlags,
+    Profile,
     SignatureType,
     SymmetricAlgorithm,
     RevocationKey,
@@ -287,6 +288,7 @@ pub struct CertBuilder<'a> {
     password: Option<Password>,
     revocation_keys: Option<Vec<RevocationKey>>,
     exportable: bool,
+    profile: Profile,
     phantom: PhantomData<&'a ()>,
 }
 assert_send_and_sync!(CertBuilder<'_>);
@@ -343,6 +345,7 @@ impl CertBuilder<'_> {
             password: None,
             revocation_keys: None,
             exportable: true,
+            profile: Profile::default(),
             phantom: PhantomData,
         }
     }
@@ -562,6 +565,47 @@ impl CertBuilder<'_> {
         self
     }
 
+    /// Sets the profile.
+    ///
+    /// The profile restricts the packet versions and algorithms that
+    /// are used for the generated certificate, see [`Profile`] for
+    /// details.  If a key would use an algorithm that the profile
+    /// does not allow, [`CertBuilder::generate`] fails.
+    ///
+    /// By default, [`Profile::RFC4880bis`] is used.
+    ///
+    /// [`CertBuilder::generate`]: CertBuilder::generate()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::types::{Profile, PublicKeyAlgorithm};
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let (cert, _) =
+    ///     CertBuilder::general_purpose(CipherSuite::RSA3k,
+    ///                                  Some("alice@example.org"))
+    ///         .set_profile(Profile::RFC4880)
+    ///         .generate()?;
+    /// assert_eq!(cert.primary_key().pk_algo(),
+    ///            PublicKeyAlgorithm::RSAEncryptSign);
+    ///
+    /// // RFC 4880 does not define elliptic curve cryptography.
+    /// assert!(
+    ///     CertBuilder::general_purpose(CipherSuite::P256,
+    ///                                  Some("alice@example.org"))
+    ///         .set_profile(Profile::RFC4880)
+    ///         .generate().is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
     /// Adds a User ID.
     ///
     /// Adds a User ID to the certificate.  The first User ID that is
@@ -1506,6 +1550,7 @@ impl CertBuilder<'_> {
             let mut subkey = blueprint.ciphersuite
                 .unwrap_or(self.ciphersuite)
                 .generate_key(flags)?;
+            self.profile.check_key(&subkey)?;
             subkey.set_creation_time(creation_time)?;
 
             let sig = template.unwrap_or_else(
@@ -1563,6 +1608,7 @@ impl CertBuilder<'_> {
         let mut key = self.primary.ciphersuite
             .unwrap_or(self.ciphersuite)
             .generate_key(KeyFlags::empty().set_certification())?;
+        self.profile.check_key(&key)?;
         key.set_creation_time(creation_time)?;
         let sig = SignatureBuilder::new(SignatureType::DirectKey);
         let sig = Self::signature_common(
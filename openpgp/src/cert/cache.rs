@@ -0,0 +1,637 @@
+//! A binary cache format for certificates.
+//!
+//! Parsing a certificate is expensive: the certificate has to be
+//! canonicalized, which involves hashing every signature, and the
+//! self signatures have to be verified.  Applications that keep a
+//! large number of certificates, like key stores, can use the cache
+//! format to quickly load certificates they have already parsed.
+//!
+//! The cache format stores the canonicalized certificate, together
+//! with the signatures' verification states and computed digests.
+//! Loading a certificate from the cache neither canonicalizes it nor
+//! verifies the signatures again.
+//!
+//! This module is only available if the `cert-cache` feature is
+//! enabled.
+
+use std::sync::Arc;
+
+use crate::{
+    Cert,
+    Error,
+    Packet,
+    Result,
+    cert::{
+        bundle::ComponentBundle,
+        lazysigs::{LazySignatures, SigState},
+    },
+    crypto::hash::Digest,
+    packet::{
+        Signature,
+        signature::subpacket::{SubpacketArea, SubpacketValue},
+    },
+    parse::{Dearmor, PacketParserBuilder, PacketParserResult, Parse},
+    policy::HashAlgoSecurity,
+    serialize::Marshal,
+    types::HashAlgorithm,
+};
+
+/// Identifies the cache format.
+const MAGIC: &[u8] = b"SQCC";
+
+/// The version of the cache format.
+///
+/// This must be incremented whenever the format, or the meaning of
+/// the cached information changes.
+const VERSION: u8 = 1;
+
+/// The hash algorithm used for the integrity check.
+const INTEGRITY_ALGO: HashAlgorithm = HashAlgorithm::SHA256;
+
+/// The length of the integrity check.
+const INTEGRITY_LEN: usize = 32;
+
+/// The kind of a cached packet.
+///
+/// For every packet, a record describing it is stored in the
+/// metadata section.  For signatures, the record includes the
+/// signature's verification state, its computed digest, and the
+/// subpackets' authentication states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    PrimaryKey,
+    Subkey,
+    UserID,
+    UserAttribute,
+    Unknown,
+    SelfSignature,
+    Certification,
+    Attestation,
+    SelfRevocation,
+    OtherRevocation,
+    Bad,
+}
+
+impl From<Kind> for u8 {
+    fn from(k: Kind) -> u8 {
+        match k {
+            Kind::PrimaryKey => 0,
+            Kind::Subkey => 1,
+            Kind::UserID => 2,
+            Kind::UserAttribute => 3,
+            Kind::Unknown => 4,
+            Kind::SelfSignature => 16,
+            Kind::Certification => 17,
+            Kind::Attestation => 18,
+            Kind::SelfRevocation => 19,
+            Kind::OtherRevocation => 20,
+            Kind::Bad => 21,
+        }
+    }
+}
+
+impl TryFrom<u8> for Kind {
+    type Error = anyhow::Error;
+
+    fn try_from(k: u8) -> Result<Kind> {
+        Ok(match k {
+            0 => Kind::PrimaryKey,
+            1 => Kind::Subkey,
+            2 => Kind::UserID,
+            3 => Kind::UserAttribute,
+            4 => Kind::Unknown,
+            16 => Kind::SelfSignature,
+            17 => Kind::Certification,
+            18 => Kind::Attestation,
+            19 => Kind::SelfRevocation,
+            20 => Kind::OtherRevocation,
+            21 => Kind::Bad,
+            _ => return Err(malformed(format!("unknown record kind {}", k))),
+        })
+    }
+}
+
+impl From<&SigState> for u8 {
+    fn from(s: &SigState) -> u8 {
+        match s {
+            SigState::Unverified => 0,
+            SigState::Good => 1,
+            SigState::Bad => 2,
+        }
+    }
+}
+
+impl TryFrom<u8> for SigState {
+    type Error = anyhow::Error;
+
+    fn try_from(s: u8) -> Result<SigState> {
+        Ok(match s {
+            0 => SigState::Unverified,
+            1 => SigState::Good,
+            2 => SigState::Bad,
+            _ => return Err(malformed(format!("unknown signature state {}", s))),
+        })
+    }
+}
+
+/// Returns an error describing a malformed cache entry.
+fn malformed<S: AsRef<str>>(msg: S) -> anyhow::Error {
+    Error::MalformedCert(format!("Malformed cert cache: {}", msg.as_ref()))
+        .into()
+}
+
+/// Accumulates the packets and the metadata records.
+struct Writer {
+    packets: Vec<u8>,
+    records: Vec<u8>,
+}
+
+impl Writer {
+    fn component(&mut self, kind: Kind, packet: Packet) -> Result<()> {
+        packet.serialize(&mut self.packets)?;
+        self.records.push(kind.into());
+        Ok(())
+    }
+
+    fn signatures<'a>(&mut self, kind: Kind,
+                      sigs: impl Iterator<Item = (&'a Signature, SigState)>)
+                      -> Result<()>
+    {
+        for (sig, state) in sigs {
+            Packet::from(sig.clone()).serialize(&mut self.packets)?;
+
+            self.records.push(kind.into());
+            self.records.push((&state).into());
+            if let Some(digest) = sig.computed_digest() {
+                self.records.push(1);
+                self.records.push(digest.len().try_into()
+                                  .map_err(|_| Error::InvalidArgument(
+                                      "digest too long".into()))?);
+                self.records.extend_from_slice(digest);
+            } else {
+                self.records.push(0);
+            }
+            authentication_flags(sig, &mut self.records);
+        }
+        Ok(())
+    }
+
+    fn lazy(&mut self, kind: Kind, sigs: &LazySignatures) -> Result<()> {
+        self.signatures(kind, sigs.as_slice_unverified().iter()
+                        .zip(sigs.states()))
+    }
+
+    fn eager(&mut self, kind: Kind, sigs: &[Signature]) -> Result<()> {
+        self.signatures(kind, sigs.iter()
+                        .map(|s| (s, SigState::Unverified)))
+    }
+
+    fn bundle<C>(&mut self, kind: Kind, packet: Packet,
+                 bundle: &ComponentBundle<C>)
+                 -> Result<()>
+    {
+        self.component(kind, packet)?;
+        self.lazy(Kind::SelfSignature, &bundle.self_signatures)?;
+        self.eager(Kind::Certification, &bundle.certifications)?;
+        self.lazy(Kind::Attestation, &bundle.attestations)?;
+        self.lazy(Kind::SelfRevocation, &bundle.self_revocations)?;
+        self.eager(Kind::OtherRevocation, &bundle.other_revocations)?;
+        Ok(())
+    }
+}
+
+/// Appends the subpackets' authentication states.
+///
+/// Verifying a signature marks its subpackets as authenticated.
+/// Since we don't verify the signatures again when loading them, we
+/// need to restore that.  This includes the subpackets of embedded
+/// signatures.
+fn authentication_flags(sig: &Signature, o: &mut Vec<u8>) {
+    fn area(a: &SubpacketArea, o: &mut Vec<u8>) {
+        for p in a.iter() {
+            o.push(p.authenticated().into());
+            if let SubpacketValue::EmbeddedSignature(sig) = p.value() {
+                authentication_flags(sig, o);
+            }
+        }
+    }
+
+    area(sig.hashed_area(), o);
+    area(sig.unhashed_area(), o);
+}
+
+/// Restores the subpackets' authentication states.
+fn restore_authentication_flags(sig: &Signature, r: &mut Reader)
+                                -> Result<()>
+{
+    fn area(a: &SubpacketArea, r: &mut Reader) -> Result<()> {
+        for p in a.iter() {
+            p.set_authenticated(r.u8()? != 0);
+            if let SubpacketValue::EmbeddedSignature(sig) = p.value() {
+                restore_authentication_flags(sig, r)?;
+            }
+        }
+        Ok(())
+    }
+
+    area(sig.hashed_area(), r)?;
+    area(sig.unhashed_area(), r)
+}
+
+/// Reads the metadata records.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.data.len() < n {
+            return Err(malformed("truncated"));
+        }
+        let (b, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(b)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Reads a signature's record, and restores the cached
+    /// information.
+    fn signature(&mut self, sig: &Signature) -> Result<SigState> {
+        let state = SigState::try_from(self.u8()?)?;
+        if self.u8()? != 0 {
+            let len = self.u8()?.into();
+            sig.set_computed_digest(Some(self.bytes(len)?.to_vec()));
+        }
+        restore_authentication_flags(sig, self)?;
+        Ok(state)
+    }
+}
+
+impl Cert {
+    /// Serializes the certificate into the binary cache format.
+    ///
+    /// The cache format stores the canonicalized certificate along
+    /// with the signatures' verification states, so that
+    /// [`Cert::from_cache`] can load it without canonicalizing the
+    /// certificate and verifying the signatures again.  This is
+    /// useful for applications that need to quickly load a large
+    /// number of certificates, like key stores.
+    ///
+    /// The format is versioned and protected by an integrity check,
+    /// but it is not authenticated.  Since it includes the
+    /// verification results, an attacker who can modify the cache can
+    /// make bad signatures appear good.  The cache must be stored in
+    /// a location that is only writable by the application, and it
+    /// must not be exchanged with others.  Use the OpenPGP
+    /// serialization for that.
+    ///
+    /// Secret key material is not included.
+    ///
+    /// This function is only available if the `cert-cache` feature is
+    /// enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let (cert, _) = CertBuilder::new()
+    ///     .add_userid("alice@example.org")
+    ///     .add_signing_subkey()
+    ///     .generate()?;
+    ///
+    /// let cached = cert.to_cache()?;
+    /// let cert_ = Cert::from_cache(&cached)?;
+    /// assert_eq!(cert.strip_secret_key_material(), cert_);
+    /// # Ok(()) }
+    /// ```
+    pub fn to_cache(&self) -> Result<Vec<u8>> {
+        let mut w = Writer {
+            packets: Vec::new(),
+            records: Vec::new(),
+        };
+
+        w.bundle(Kind::PrimaryKey,
+                 self.primary.key().clone().take_secret().0.into(),
+                 &self.primary)?;
+        for b in self.subkeys.iter() {
+            w.bundle(Kind::Subkey,
+                     b.key().clone().take_secret().0.into(), b)?;
+        }
+        for b in self.userids.iter() {
+            w.bundle(Kind::UserID, b.userid().clone().into(), b)?;
+        }
+        for b in self.user_attributes.iter() {
+            w.bundle(Kind::UserAttribute,
+                     b.user_attribute().clone().into(), b)?;
+        }
+        for b in self.unknowns.iter() {
+            w.bundle(Kind::Unknown, b.unknown().clone().into(), b)?;
+        }
+        w.eager(Kind::Bad, &self.bad)?;
+
+        let packets_len: u32 = w.packets.len().try_into()
+            .map_err(|_| Error::InvalidArgument(
+                "certificate too large".into()))?;
+
+        let mut cache = Vec::with_capacity(
+            MAGIC.len() + 1 + 4 + w.packets.len() + w.records.len()
+                + INTEGRITY_LEN);
+        cache.extend_from_slice(MAGIC);
+        cache.push(VERSION);
+        cache.extend_from_slice(&packets_len.to_be_bytes());
+        cache.extend_from_slice(&w.packets);
+        cache.extend_from_slice(&w.records);
+
+        let mut ctx = INTEGRITY_ALGO.context()?;
+        ctx.update(&cache);
+        cache.extend_from_slice(&ctx.into_digest()?);
+        Ok(cache)
+    }
+
+    /// Loads a certificate from the binary cache format.
+    ///
+    /// `data` must have been produced by [`Cert::to_cache`].  The
+    /// certificate is neither canonicalized, nor are the signatures
+    /// verified again.  See [`Cert::to_cache`] for the security
+    /// implications.
+    ///
+    /// Returns an error if the data is corrupted, or if it was
+    /// produced by a version of Sequoia using a different version of
+    /// the cache format.  In that case, the certificate should be
+    /// parsed from its OpenPGP serialization, and the cache updated.
+    ///
+    /// This function is only available if the `cert-cache` feature is
+    /// enabled.
+    pub fn from_cache(data: &[u8]) -> Result<Cert> {
+        if data.len() < MAGIC.len() + 1 + 4 + INTEGRITY_LEN
+            || ! data.starts_with(MAGIC)
+        {
+            return Err(malformed("not a cert cache entry"));
+        }
+        let version = data[MAGIC.len()];
+        if version != VERSION {
+            return Err(malformed(format!("unsupported version {}", version)));
+        }
+
+        let (data, integrity) = data.split_at(data.len() - INTEGRITY_LEN);
+        let mut ctx = INTEGRITY_ALGO.context()?;
+        ctx.update(data);
+        if ctx.into_digest()? != integrity {
+            return Err(malformed("integrity check failed"));
+        }
+
+        let mut r = Reader { data: &data[MAGIC.len() + 1..] };
+        let packets_len = r.u32()? as usize;
+        let packets = r.bytes(packets_len)?;
+
+        let mut ppr = PacketParserBuilder::from_bytes(packets)?
+            .dearmor(Dearmor::Disabled)
+            .buffer_unread_content()
+            .build()?;
+        let mut next = || -> Result<Option<Packet>> {
+            match ppr.take() {
+                PacketParserResult::Some(pp) => {
+                    let (p, rest) = pp.next()?;
+                    ppr = rest;
+                    Ok(Some(p))
+                },
+                eof @ PacketParserResult::EOF(_) => {
+                    ppr = eof;
+                    Ok(None)
+                },
+            }
+        };
+
+        let mut cert: Option<Cert> = None;
+        let mut primary_key = None;
+        // The kind of the last component.  Signatures are stored
+        // right after the component they belong to.
+        let mut last = Kind::PrimaryKey;
+        while let Some(packet) = next()? {
+            let kind = Kind::try_from(r.u8()?)?;
+            let unexpected = || malformed(format!(
+                "unexpected {} packet in {:?} record", packet.tag(), kind));
+
+            let cert = match (kind, cert.as_mut()) {
+                (Kind::PrimaryKey, None) => {
+                    let key = match packet {
+                        Packet::PublicKey(k) => k,
+                        _ => return Err(unexpected()),
+                    };
+                    let pk = Arc::new(key.clone());
+                    let sec = key.hash_algo_security();
+                    primary_key = Some(pk.clone());
+                    cert = Some(Cert {
+                        primary: ComponentBundle::new(key, sec, vec![], pk),
+                        subkeys: Default::default(),
+                        userids: Default::default(),
+                        user_attributes: Default::default(),
+                        unknowns: Default::default(),
+                        bad: vec![],
+                    });
+                    continue;
+                },
+                (_, None) | (Kind::PrimaryKey, Some(_)) =>
+                    return Err(malformed("expected exactly one primary key")),
+                (_, Some(cert)) => cert,
+            };
+            let pk = primary_key.clone().expect("set with cert");
+
+            match (kind, packet) {
+                (Kind::Subkey, Packet::PublicSubkey(k)) => {
+                    let sec = k.hash_algo_security();
+                    cert.subkeys.push(
+                        ComponentBundle::new_subkey(k, sec, vec![], pk));
+                    last = kind;
+                },
+                (Kind::UserID, Packet::UserID(u)) => {
+                    let sec = u.hash_algo_security();
+                    cert.userids.push(ComponentBundle::new(u, sec, vec![], pk));
+                    last = kind;
+                },
+                (Kind::UserAttribute, Packet::UserAttribute(u)) => {
+                    let sec = u.hash_algo_security();
+                    cert.user_attributes.push(
+                        ComponentBundle::new(u, sec, vec![], pk));
+                    last = kind;
+                },
+                (Kind::Unknown, Packet::Unknown(u)) => {
+                    cert.unknowns.push(ComponentBundle::new(
+                        u, HashAlgoSecurity::CollisionResistance,
+                        vec![], pk));
+                    last = kind;
+                },
+                (_, Packet::Signature(sig)) => {
+                    let state = r.signature(&sig)?;
+
+                    macro_rules! push {
+                        ($field: ident, $push: ident $(, $arg: expr)*) => {{
+                            let b = match last {
+                                Kind::Subkey => &mut cert.subkeys.last_mut()
+                                    .expect("pushed").$field,
+                                Kind::UserID => &mut cert.userids.last_mut()
+                                    .expect("pushed").$field,
+                                Kind::UserAttribute =>
+                                    &mut cert.user_attributes.last_mut()
+                                    .expect("pushed").$field,
+                                Kind::Unknown => &mut cert.unknowns.last_mut()
+                                    .expect("pushed").$field,
+                                _ => &mut cert.primary.$field,
+                            };
+                            b.$push(sig $(, $arg)*)
+                        }};
+                    }
+
+                    match kind {
+                        Kind::SelfSignature =>
+                            push!(self_signatures, push_with_state, state),
+                        Kind::Certification => push!(certifications, push),
+                        Kind::Attestation =>
+                            push!(attestations, push_with_state, state),
+                        Kind::SelfRevocation =>
+                            push!(self_revocations, push_with_state, state),
+                        Kind::OtherRevocation =>
+                            push!(other_revocations, push),
+                        Kind::Bad => cert.bad.push(sig),
+                        _ => return Err(malformed(format!(
+                            "unexpected signature in {:?} record", kind))),
+                    }
+                },
+                (kind, packet) => return Err(malformed(format!(
+                    "unexpected {} packet in {:?} record",
+                    packet.tag(), kind))),
+            }
+        }
+
+        if ! r.data.is_empty() {
+            return Err(malformed("trailing data"));
+        }
+
+        cert.ok_or_else(|| malformed("no primary key"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cert::prelude::*;
+    use crate::policy::StandardPolicy;
+
+    /// Returns the signature states of all components.
+    fn states(cert: &Cert) -> Vec<Vec<SigState>> {
+        let mut states = vec![
+            cert.primary.self_signatures.states(),
+            cert.primary.self_revocations.states(),
+        ];
+        for b in cert.subkeys.iter() {
+            states.push(b.self_signatures.states());
+            states.push(b.self_revocations.states());
+        }
+        for b in cert.userids.iter() {
+            states.push(b.self_signatures.states());
+            states.push(b.attestations.states());
+            states.push(b.self_revocations.states());
+        }
+        states
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let p = &StandardPolicy::new();
+        for name in ["neal.pgp", "dkg.gpg", "1pa3pc-dkgpg.pgp",
+                     "bannon-all-uids-subkeys.gpg",
+                     "really-revoked-user-attribute-4-new-self-sig.pgp"]
+        {
+            let cert = Cert::from_bytes(crate::tests::key(name))?;
+            // Verify some of the signatures.
+            let _ = cert.with_policy(p, None).map(|vc| vc.primary_userid());
+
+            let cert_ = Cert::from_cache(&cert.to_cache()?)?;
+            assert_eq!(cert, cert_);
+            assert_eq!(states(&cert), states(&cert_));
+            assert_eq!(cert.bad_signatures().count(),
+                       cert_.bad_signatures().count());
+
+            // The computed digests are restored.
+            for (a, b) in cert.userids().zip(cert_.userids()) {
+                for (a, b) in a.self_signatures().zip(b.self_signatures()) {
+                    assert_eq!(a.computed_digest(), b.computed_digest());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn skips_verification() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (cert, _) = CertBuilder::new()
+            .add_userid("alice@example.org")
+            .add_signing_subkey()
+            .generate()?;
+        let cert = cert.strip_secret_key_material();
+        cert.with_policy(p, None)?.keys().for_signing().count();
+        assert!(states(&cert).iter().flatten()
+                .any(|s| *s == SigState::Good));
+
+        let cert_ = Cert::from_cache(&cert.to_cache()?)?;
+        assert_eq!(states(&cert), states(&cert_));
+
+        // The backsig's subpackets are marked as authenticated.
+        let flags = |cert: &Cert| {
+            let mut flags = Vec::new();
+            for b in cert.subkeys.iter() {
+                for s in b.self_signatures.as_slice_unverified() {
+                    authentication_flags(s, &mut flags);
+                }
+            }
+            flags
+        };
+        assert!(flags(&cert).contains(&1));
+        assert_eq!(flags(&cert), flags(&cert_));
+        Ok(())
+    }
+
+    #[test]
+    fn corruption() -> Result<()> {
+        let (cert, _) = CertBuilder::new()
+            .add_userid("alice@example.org")
+            .generate()?;
+        let cached = cert.to_cache()?;
+
+        // Every bit flip is detected.
+        for i in 0..cached.len() {
+            let mut c = cached.clone();
+            c[i] ^= 1;
+            assert!(Cert::from_cache(&c).is_err(), "flipped byte {}", i);
+        }
+
+        // Truncation is detected.
+        for i in 0..cached.len() {
+            assert!(Cert::from_cache(&cached[..i]).is_err());
+        }
+
+        // Other versions are rejected.
+        let mut c = cached.clone();
+        c[MAGIC.len()] = VERSION + 1;
+        let l = c.len() - INTEGRITY_LEN;
+        let mut ctx = INTEGRITY_ALGO.context()?;
+        ctx.update(&c[..l]);
+        c[l..].copy_from_slice(&ctx.into_digest()?);
+        assert!(Cert::from_cache(&c).unwrap_err().to_string()
+                .contains("unsupported version"));
+        Ok(())
+    }
+}
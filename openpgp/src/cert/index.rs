@@ -0,0 +1,1178 @@
+//! A sidecar index for keyrings.
+//!
+//! Finding a certificate in a keyring requires scanning the whole
+//! keyring.  For large keyrings that are searched repeatedly, e.g.,
+//! by command line tools, this is prohibitively expensive.  A
+//! [`KeyringIndex`] maps the fingerprints and key IDs of the keys,
+//! and the email addresses of the user IDs to the location of the
+//! certificates in the keyring.  Using the index, the matching
+//! certificates can be read directly.
+//!
+//! The index is stored next to the keyring, see
+//! [`KeyringIndex::sidecar_path`].  It records the keyring's size and
+//! modification time, and a digest of every certificate.  If the
+//! keyring's size or modification time changes, the index is
+//! considered stale.  Additionally, the digest of every certificate
+//! is checked when it is read, which catches modifications that
+//! preserve the keyring's size and modification time.
+//!
+//! Only binary keyrings can be indexed.  ASCII armored keyrings have
+//! to be dearmored, and offsets into the dearmored data are useless
+//! for seeking in the file.
+//!
+//! [`CertParser::from_indexed_file`] and
+//! [`CertParser::from_indexed_file_by_email`] use the index to parse
+//! only the matching certificates, creating the index if necessary.
+//!
+//! # Format
+//!
+//! The index consists of a fixed-size header, four tables of
+//! fixed-size records, a string pool, and a digest over everything
+//! before it.  All integers are stored in big endian.  The header
+//! contains the keyring's size and modification time, the number of
+//! records in each table, and the size of the string pool.
+//!
+//! The first table lists the certificates' offsets, lengths, and
+//! digests.  The other tables map fingerprints, key IDs, and email
+//! addresses to the certificates, and are sorted by the looked up
+//! value.  Email addresses are stored in the string pool.  Lookups
+//! binary search the tables in the sidecar file, without reading the
+//! whole index.  Thus, opening an index and looking up a certificate
+//! takes time logarithmic in the size of the keyring.
+//!
+//! The index is not authenticated.  Anyone who can modify the
+//! sidecar file can make lookups miss certificates.  All offsets and
+//! lengths read from the index are checked, however, and the
+//! returned certificates are checked against their digests.
+//!
+//! This module is only available if the `keyring-index` feature is
+//! enabled.
+
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use crate::{
+    Error,
+    Fingerprint,
+    KeyHandle,
+    KeyID,
+    Result,
+    cert::{CertParser, raw::RawCertParser},
+    crypto::hash::Digest,
+    parse::Parse,
+    types::HashAlgorithm,
+};
+
+/// Identifies the index format.
+const MAGIC: &[u8] = b"SQKI";
+
+/// The version of the index format.
+///
+/// This must be incremented whenever the format changes.
+const VERSION: u8 = 2;
+
+/// The hash algorithm used for the certificates' digests and the
+/// integrity check.
+const DIGEST_ALGO: HashAlgorithm = HashAlgorithm::SHA256;
+
+/// The length of the digests.
+const DIGEST_LEN: usize = 32;
+
+/// The length of the header.
+///
+/// The magic, the version, the stamp, the number of records in each
+/// table, and the size of the string pool.
+const HEADER_LEN: usize = 4 + 1 + (8 + 8 + 4) + 4 * 4 + 8;
+
+/// The length of the longest fingerprint that can be indexed.
+const MAX_FINGERPRINT_LEN: usize = 32;
+
+/// The length of a certificate record.
+///
+/// The offset, the length, and the digest.
+const CERT_RECORD_LEN: usize = 8 + 8 + DIGEST_LEN;
+
+/// The length of a fingerprint record.
+///
+/// The fingerprint's length, the zero-padded fingerprint, and the
+/// certificate.
+const FINGERPRINT_RECORD_LEN: usize = 1 + MAX_FINGERPRINT_LEN + 4;
+
+/// The length of a key ID record.
+///
+/// The key ID, and the certificate.
+const KEYID_RECORD_LEN: usize = 8 + 4;
+
+/// The length of an email address record.
+///
+/// The address's offset in the string pool, its length, and the
+/// certificate.
+const EMAIL_RECORD_LEN: usize = 8 + 4 + 4;
+
+/// The extension appended to the keyring's file name to derive the
+/// sidecar's file name.
+const SIDECAR_EXTENSION: &str = ".sqidx";
+
+/// Returns an error describing a malformed index.
+fn malformed<S: AsRef<str>>(msg: S) -> anyhow::Error {
+    Error::MalformedPacket(
+        format!("Malformed keyring index: {}", msg.as_ref())).into()
+}
+
+/// Returns an error describing a stale index.
+fn stale() -> anyhow::Error {
+    Error::InvalidOperation(
+        "Keyring index is stale, the keyring has been modified".into())
+        .into()
+}
+
+/// Returns the digest of `data`.
+fn digest(data: &[u8]) -> Result<Vec<u8>> {
+    let mut ctx = DIGEST_ALGO.context()?;
+    ctx.update(data);
+    ctx.into_digest()
+}
+
+/// Returns the key under which a fingerprint is stored.
+///
+/// Returns `None` if the fingerprint is too long to be indexed.
+fn fingerprint_key(fp: &Fingerprint) -> Option<[u8; 1 + MAX_FINGERPRINT_LEN]> {
+    let fp = fp.as_bytes();
+    if fp.len() > MAX_FINGERPRINT_LEN {
+        return None;
+    }
+    let mut key = [0; 1 + MAX_FINGERPRINT_LEN];
+    key[0] = fp.len() as u8;
+    key[1..1 + fp.len()].copy_from_slice(fp);
+    Some(key)
+}
+
+/// Returns the key under which a key ID is stored.
+///
+/// Returns `None` if the key ID is not a long key ID.
+fn keyid_key(keyid: &KeyID) -> Option<[u8; 8]> {
+    keyid.as_bytes().try_into().ok()
+}
+
+/// Reads a big endian `u32`.
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    let mut b = [0; 4];
+    b.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_be_bytes(b)
+}
+
+/// Reads a big endian `u64`.
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    let mut b = [0; 8];
+    b.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_be_bytes(b)
+}
+
+/// Identifies a version of the keyring.
+///
+/// This is the keyring's size and modification time.  If the
+/// platform doesn't support modification times, only the size is
+/// used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+}
+
+impl Stamp {
+    fn new(metadata: &fs::Metadata) -> Self {
+        let mtime = metadata.modified().ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Stamp {
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+        }
+    }
+
+    fn of_file(file: &File) -> Result<Self> {
+        Ok(Self::new(&file.metadata()?))
+    }
+}
+
+/// The header of a serialized index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    stamp: Stamp,
+    /// The number of certificate records.
+    certs: u32,
+    /// The number of fingerprint records.
+    fingerprints: u32,
+    /// The number of key ID records.
+    keyids: u32,
+    /// The number of email address records.
+    emails: u32,
+    /// The size of the string pool.
+    strings: u64,
+}
+
+impl Header {
+    /// Parses the header.
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_LEN || ! data.starts_with(MAGIC) {
+            return Err(malformed("not a keyring index"));
+        }
+        let version = data[MAGIC.len()];
+        if version != VERSION {
+            return Err(malformed(format!("unsupported version {}", version)));
+        }
+
+        let o = MAGIC.len() + 1;
+        Ok(Header {
+            stamp: Stamp {
+                size: u64_at(data, o),
+                mtime_secs: u64_at(data, o + 8),
+                mtime_nanos: u32_at(data, o + 16),
+            },
+            certs: u32_at(data, o + 20),
+            fingerprints: u32_at(data, o + 24),
+            keyids: u32_at(data, o + 28),
+            emails: u32_at(data, o + 32),
+            strings: u64_at(data, o + 36),
+        })
+    }
+
+    /// Serializes the header.
+    fn serialize(&self, o: &mut Vec<u8>) {
+        o.extend_from_slice(MAGIC);
+        o.push(VERSION);
+        o.extend_from_slice(&self.stamp.size.to_be_bytes());
+        o.extend_from_slice(&self.stamp.mtime_secs.to_be_bytes());
+        o.extend_from_slice(&self.stamp.mtime_nanos.to_be_bytes());
+        o.extend_from_slice(&self.certs.to_be_bytes());
+        o.extend_from_slice(&self.fingerprints.to_be_bytes());
+        o.extend_from_slice(&self.keyids.to_be_bytes());
+        o.extend_from_slice(&self.emails.to_be_bytes());
+        o.extend_from_slice(&self.strings.to_be_bytes());
+    }
+
+    /// Returns the certificate table.
+    fn certs(&self) -> Table {
+        Table {
+            offset: HEADER_LEN as u64,
+            len: self.certs,
+            record_len: CERT_RECORD_LEN,
+        }
+    }
+
+    /// Returns the fingerprint table.
+    fn fingerprints(&self) -> Table {
+        Table {
+            offset: self.certs().end(),
+            len: self.fingerprints,
+            record_len: FINGERPRINT_RECORD_LEN,
+        }
+    }
+
+    /// Returns the key ID table.
+    fn keyids(&self) -> Table {
+        Table {
+            offset: self.fingerprints().end(),
+            len: self.keyids,
+            record_len: KEYID_RECORD_LEN,
+        }
+    }
+
+    /// Returns the email address table.
+    fn emails(&self) -> Table {
+        Table {
+            offset: self.keyids().end(),
+            len: self.emails,
+            record_len: EMAIL_RECORD_LEN,
+        }
+    }
+
+    /// Returns the offset of the string pool.
+    fn strings_offset(&self) -> u64 {
+        self.emails().end()
+    }
+
+    /// Returns the length of the serialized index.
+    ///
+    /// Returns `None` on overflow.
+    fn len(&self) -> Option<u64> {
+        self.strings_offset()
+            .checked_add(self.strings)?
+            .checked_add(DIGEST_LEN as u64)
+    }
+}
+
+/// A table of fixed-size records.
+#[derive(Debug, Clone, Copy)]
+struct Table {
+    /// The offset of the first record.
+    offset: u64,
+    /// The number of records.
+    len: u32,
+    /// The length of a record.
+    record_len: usize,
+}
+
+impl Table {
+    /// Returns the offset of the `i`th record.
+    fn record(&self, i: u32) -> u64 {
+        self.offset + i as u64 * self.record_len as u64
+    }
+
+    /// Returns the offset just past the table.
+    fn end(&self) -> u64 {
+        self.record(self.len)
+    }
+}
+
+/// Where a serialized index is stored.
+#[derive(Debug, Clone)]
+enum Storage {
+    /// In memory.
+    Bytes(Vec<u8>),
+    /// In a file.
+    File(Arc<Mutex<File>>),
+}
+
+impl Storage {
+    /// Fills `buf` with the data at `offset`.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        match self {
+            Storage::Bytes(data) => {
+                let data = usize::try_from(offset).ok()
+                    .and_then(|o| data.get(o..))
+                    .and_then(|d| d.get(..buf.len()))
+                    .ok_or_else(|| malformed("truncated"))?;
+                buf.copy_from_slice(data);
+            },
+            Storage::File(file) => {
+                let mut file = file.lock()
+                    .map_err(|_| malformed("poisoned lock"))?;
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(buf)?;
+            },
+        }
+        Ok(())
+    }
+}
+
+/// A certificate in the keyring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    /// The offset of the certificate in the keyring.
+    offset: u64,
+    /// The length of the certificate.
+    len: u64,
+    /// The digest of the certificate.
+    digest: Vec<u8>,
+    /// The fingerprints of the certificate's keys.
+    fingerprints: Vec<Fingerprint>,
+    /// The normalized email addresses of the certificate's user IDs.
+    emails: Vec<String>,
+}
+
+/// An index of a keyring.
+///
+/// A `KeyringIndex` maps the fingerprints and key IDs of all keys,
+/// and the normalized email addresses of all user IDs to the location
+/// of the certificates in the keyring.  It can be stored in a sidecar
+/// file next to the keyring, and used to retrieve certificates from
+/// large keyrings without scanning them.  An index loaded from the
+/// sidecar file is not read into memory, but searched in place.
+///
+/// The index is generated using a [`RawCertParser`], which only
+/// looks at the packets' framing, and doesn't canonicalize the
+/// certificates.  In particular, the binding signatures are not
+/// checked.  Thus, the index may return certificates that don't
+/// match once they have been canonicalized.  The certificates are
+/// returned by a [`CertParser`], which does canonicalize them.
+///
+/// See the [module-level documentation] for details.
+///
+/// [`RawCertParser`]: crate::cert::raw::RawCertParser
+/// [module-level documentation]: self
+///
+/// This type is only available if the `keyring-index` feature is
+/// enabled.
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::index::KeyringIndex;
+/// # use openpgp::cert::prelude::*;
+/// # use openpgp::serialize::Serialize;
+///
+/// # fn main() -> openpgp::Result<()> {
+/// # let (alice, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+/// #     .generate()?;
+/// # let (bob, _) = CertBuilder::general_purpose(None, Some("bob@example.org"))
+/// #     .generate()?;
+/// # let keyring = std::env::temp_dir().join(
+/// #     format!("sequoia-keyring-index-doctest-{}.pgp", std::process::id()));
+/// # let mut sink = std::fs::File::create(&keyring)?;
+/// # alice.serialize(&mut sink)?;
+/// # bob.serialize(&mut sink)?;
+/// # drop(sink);
+/// // Loads the index from the sidecar file, or creates it.
+/// let index = KeyringIndex::open(&keyring)?;
+///
+/// let certs = index.lookup_email(&keyring, "bob@example.org")?
+///     .collect::<openpgp::Result<Vec<Cert>>>()?;
+/// assert_eq!(certs.len(), 1);
+/// assert_eq!(certs[0].fingerprint(), bob.fingerprint());
+///
+/// let certs = index.lookup(&keyring, &alice.keyid().into())?
+///     .collect::<openpgp::Result<Vec<Cert>>>()?;
+/// assert_eq!(certs.len(), 1);
+/// assert_eq!(certs[0].fingerprint(), alice.fingerprint());
+/// # std::fs::remove_file(KeyringIndex::sidecar_path(&keyring))?;
+/// # std::fs::remove_file(&keyring)?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyringIndex {
+    header: Header,
+    storage: Storage,
+}
+assert_send_and_sync!(KeyringIndex);
+
+impl KeyringIndex {
+    /// Creates an index from the entries.
+    fn new(stamp: Stamp, entries: &[Entry]) -> Result<Self> {
+        fn len<T: TryFrom<usize>>(l: usize) -> Result<T> {
+            l.try_into().map_err(|_| Error::InvalidArgument(
+                "keyring index too large".into()).into())
+        }
+
+        let mut fingerprints = Vec::new();
+        let mut keyids = Vec::new();
+        let mut emails = Vec::new();
+        for (i, e) in entries.iter().enumerate() {
+            let i: u32 = len(i)?;
+            for fp in e.fingerprints.iter() {
+                if let Some(key) = fingerprint_key(fp) {
+                    fingerprints.push((key, i));
+                }
+                if let Some(key) = keyid_key(&KeyID::from(fp)) {
+                    keyids.push((key, i));
+                }
+            }
+            for email in e.emails.iter() {
+                emails.push((email.as_str(), i));
+            }
+        }
+        fingerprints.sort();
+        fingerprints.dedup();
+        keyids.sort();
+        keyids.dedup();
+        emails.sort();
+        emails.dedup();
+
+        // Store every email address once.
+        let mut strings = Vec::new();
+        let mut email_records = Vec::with_capacity(emails.len());
+        for (j, (email, i)) in emails.iter().enumerate() {
+            if j == 0 || emails[j - 1].0 != *email {
+                strings.extend_from_slice(email.as_bytes());
+            }
+            let offset = strings.len() - email.len();
+            email_records.push((offset as u64, len::<u32>(email.len())?, *i));
+        }
+
+        let header = Header {
+            stamp,
+            certs: len(entries.len())?,
+            fingerprints: len(fingerprints.len())?,
+            keyids: len(keyids.len())?,
+            emails: len(emails.len())?,
+            strings: strings.len() as u64,
+        };
+
+        let mut o = Vec::new();
+        header.serialize(&mut o);
+        for e in entries.iter() {
+            o.extend_from_slice(&e.offset.to_be_bytes());
+            o.extend_from_slice(&e.len.to_be_bytes());
+            o.extend_from_slice(&e.digest);
+        }
+        for (key, i) in fingerprints {
+            o.extend_from_slice(&key);
+            o.extend_from_slice(&i.to_be_bytes());
+        }
+        for (key, i) in keyids {
+            o.extend_from_slice(&key);
+            o.extend_from_slice(&i.to_be_bytes());
+        }
+        for (offset, l, i) in email_records {
+            o.extend_from_slice(&offset.to_be_bytes());
+            o.extend_from_slice(&l.to_be_bytes());
+            o.extend_from_slice(&i.to_be_bytes());
+        }
+        o.extend_from_slice(&strings);
+
+        let integrity = digest(&o)?;
+        o.extend_from_slice(&integrity);
+        debug_assert_eq!(Some(o.len() as u64), header.len());
+
+        Ok(KeyringIndex {
+            header,
+            storage: Storage::Bytes(o),
+        })
+    }
+
+    /// Returns the path of the sidecar file for the given keyring.
+    ///
+    /// This is the keyring's path with `.sqidx` appended.
+    pub fn sidecar_path<P: AsRef<Path>>(keyring: P) -> PathBuf {
+        let mut path = keyring.as_ref().as_os_str().to_owned();
+        path.push(SIDECAR_EXTENSION);
+        path.into()
+    }
+
+    /// Indexes the given keyring.
+    ///
+    /// This reads the whole keyring.  Corrupted and unsupported
+    /// certificates are skipped.
+    ///
+    /// Returns an error if the keyring is ASCII armored, or if it is
+    /// modified while it is being indexed.
+    pub fn generate<P: AsRef<Path>>(keyring: P) -> Result<Self> {
+        let keyring = keyring.as_ref();
+        let stamp = Stamp::new(&fs::metadata(keyring)?);
+
+        let mut entries = Vec::new();
+        let mut parser = RawCertParser::from_file(keyring)?;
+        while let Some(cert) = parser.next() {
+            let cert = if let Ok(cert) = cert {
+                cert
+            } else {
+                continue;
+            };
+            let offset = parser.cert_offset().ok_or_else(|| {
+                Error::InvalidArgument(
+                    "Cannot index ASCII armored keyrings".into())
+            })?;
+
+            let mut fingerprints = Vec::new();
+            for key in cert.keys() {
+                fingerprints.push(key.fingerprint());
+            }
+            let mut emails = Vec::new();
+            for userid in cert.userids() {
+                if let Ok(Some(email)) = userid.email_normalized() {
+                    if ! emails.contains(&email) {
+                        emails.push(email);
+                    }
+                }
+            }
+
+            let data = cert.as_bytes();
+            entries.push(Entry {
+                offset: offset as u64,
+                len: data.len() as u64,
+                digest: digest(data)?,
+                fingerprints,
+                emails,
+            });
+        }
+
+        if Stamp::new(&fs::metadata(keyring)?) != stamp {
+            return Err(Error::InvalidOperation(
+                "Keyring was modified while it was being indexed".into())
+                       .into());
+        }
+
+        Self::new(stamp, &entries)
+    }
+
+    /// Loads the keyring's index from the sidecar file, creating or
+    /// updating it as necessary.
+    ///
+    /// The sidecar file is not read into memory.  Only its header is
+    /// checked, and lookups search it in place.  Thus, corruption is
+    /// only detected when looking up certificates.
+    ///
+    /// If the sidecar file doesn't exist, is malformed, or is stale,
+    /// the keyring is indexed and the sidecar file is replaced.  If
+    /// the sidecar file can't be written, e.g., because the directory
+    /// is read-only, the index is returned nevertheless.
+    pub fn open<P: AsRef<Path>>(keyring: P) -> Result<Self> {
+        let keyring = keyring.as_ref();
+        let sidecar = Self::sidecar_path(keyring);
+
+        if let Ok(index) = Self::from_sidecar(&sidecar) {
+            if index.is_current(keyring)? {
+                return Ok(index);
+            }
+        }
+
+        let index = Self::generate(keyring)?;
+
+        // Write the new index to a temporary file, and atomically
+        // replace the old one.
+        let mut tmp = sidecar.clone().into_os_string();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+        if fs::write(&tmp, index.to_vec()?).is_err()
+            || fs::rename(&tmp, &sidecar).is_err()
+        {
+            let _ = fs::remove_file(&tmp);
+        }
+
+        Ok(index)
+    }
+
+    /// Loads an index from the sidecar file without reading it into
+    /// memory.
+    fn from_sidecar(sidecar: &Path) -> Result<Self> {
+        let mut file = File::open(sidecar)?;
+        let mut header = [0; HEADER_LEN];
+        file.read_exact(&mut header)?;
+        let header = Header::parse(&header)?;
+        if header.len() != Some(file.metadata()?.len()) {
+            return Err(malformed("truncated"));
+        }
+
+        Ok(KeyringIndex {
+            header,
+            storage: Storage::File(Arc::new(Mutex::new(file))),
+        })
+    }
+
+    /// Returns whether the index is current.
+    ///
+    /// The index is considered current if the keyring's size and
+    /// modification time are the same as when the index was
+    /// generated.
+    pub fn is_current<P: AsRef<Path>>(&self, keyring: P) -> Result<bool> {
+        Ok(Stamp::new(&fs::metadata(keyring)?) == self.header.stamp)
+    }
+
+    /// Returns the number of indexed certificates.
+    pub fn len(&self) -> usize {
+        self.header.certs as usize
+    }
+
+    /// Returns whether the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.header.certs == 0
+    }
+
+    /// Returns the certificates that contain a key with the given
+    /// key handle.
+    ///
+    /// This considers the primary keys as well as the subkeys.
+    ///
+    /// The certificates are read from `keyring`, which must be the
+    /// indexed keyring.  Returns an error if the index is stale, see
+    /// [`KeyringIndex::is_current`], or if a certificate's digest
+    /// doesn't match.
+    pub fn lookup<P: AsRef<Path>>(&self, keyring: P, handle: &KeyHandle)
+                                  -> Result<CertParser<'static>>
+    {
+        let certs = match handle {
+            KeyHandle::Fingerprint(fp) => match fingerprint_key(fp) {
+                Some(key) => self.search(
+                    self.header.fingerprints(),
+                    |record| Ok(record[..key.len()].cmp(&key)))?,
+                None => Vec::new(),
+            },
+            KeyHandle::KeyID(keyid) => match keyid_key(keyid) {
+                Some(key) => self.search(
+                    self.header.keyids(),
+                    |record| Ok(record[..key.len()].cmp(&key)))?,
+                None => Vec::new(),
+            },
+        };
+        self.read(keyring.as_ref(), &certs)
+    }
+
+    /// Returns the certificates that contain a user ID with the given
+    /// email address.
+    ///
+    /// The email address is normalized as described in
+    /// [`UserID::email_normalized`].
+    ///
+    /// [`UserID::email_normalized`]: crate::packet::UserID::email_normalized
+    ///
+    /// The certificates are read from `keyring`, which must be the
+    /// indexed keyring.  Returns an error if the index is stale, see
+    /// [`KeyringIndex::is_current`], or if a certificate's digest
+    /// doesn't match.
+    pub fn lookup_email<P: AsRef<Path>>(&self, keyring: P, email: &str)
+                                        -> Result<CertParser<'static>>
+    {
+        let email = crate::packet::UserID::from(email).email_normalized()?
+            .ok_or_else(|| Error::InvalidArgument(
+                format!("Not an email address: {}", email)))?;
+        let certs = self.search(self.header.emails(), |record| {
+            let offset = u64_at(record, 0);
+            let len = u32_at(record, 8) as usize;
+            Ok(self.string(offset, len)?.as_slice().cmp(email.as_bytes()))
+        })?;
+        self.read(keyring.as_ref(), &certs)
+    }
+
+    /// Returns the certificates referenced by the records in `table`
+    /// that match.
+    ///
+    /// `cmp` compares a record to the value that is looked up.  The
+    /// table must be sorted accordingly.  The certificate is stored
+    /// in the last four bytes of a record.
+    fn search<F>(&self, table: Table, cmp: F) -> Result<Vec<u32>>
+    where
+        F: Fn(&[u8]) -> Result<Ordering>,
+    {
+        let mut record = vec![0; table.record_len];
+
+        // Find the first record that is not less than the value.
+        let (mut low, mut high) = (0, table.len);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            self.storage.read_at(table.record(mid), &mut record)?;
+            if cmp(&record)? == Ordering::Less {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let mut certs = Vec::new();
+        for i in low..table.len {
+            self.storage.read_at(table.record(i), &mut record)?;
+            if cmp(&record)? != Ordering::Equal {
+                break;
+            }
+            let cert = u32_at(&record, table.record_len - 4);
+            if ! certs.contains(&cert) {
+                certs.push(cert);
+            }
+        }
+        Ok(certs)
+    }
+
+    /// Returns the string at `offset` in the string pool.
+    fn string(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        if offset.checked_add(len as u64)
+            .map(|end| end > self.header.strings)
+            .unwrap_or(true)
+        {
+            return Err(malformed("string out of bounds"));
+        }
+        let mut s = vec![0; len];
+        self.storage.read_at(self.header.strings_offset() + offset, &mut s)?;
+        Ok(s)
+    }
+
+    /// Returns the offset, length, and digest of the `i`th
+    /// certificate.
+    ///
+    /// Returns an error if the certificate is not within the
+    /// keyring.
+    fn cert(&self, i: u32) -> Result<(u64, u64, [u8; DIGEST_LEN])> {
+        if i >= self.header.certs {
+            return Err(malformed("certificate out of bounds"));
+        }
+        let mut record = [0; CERT_RECORD_LEN];
+        self.storage.read_at(self.header.certs().record(i), &mut record)?;
+        let offset = u64_at(&record, 0);
+        let len = u64_at(&record, 8);
+        if offset.checked_add(len)
+            .map(|end| end > self.header.stamp.size)
+            .unwrap_or(true)
+        {
+            return Err(malformed("certificate exceeds the keyring"));
+        }
+        let mut digest = [0; DIGEST_LEN];
+        digest.copy_from_slice(&record[16..]);
+        Ok((offset, len, digest))
+    }
+
+    /// Reads the given certificates from the keyring.
+    fn read(&self, keyring: &Path, certs: &[u32])
+            -> Result<CertParser<'static>>
+    {
+        let mut file = File::open(keyring)?;
+        if Stamp::of_file(&file)? != self.header.stamp {
+            return Err(stale());
+        }
+
+        let mut data = Vec::new();
+        for &i in certs {
+            // The stamp matches, so the certificate is within the
+            // file, and `len` is bounded by the file's size.
+            let (offset, len, cert_digest) = self.cert(i)?;
+            let start = data.len();
+            data.resize(start + len as usize, 0);
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut data[start..])?;
+            if digest(&data[start..])? != cert_digest {
+                return Err(stale());
+            }
+        }
+
+        CertParser::from_reader(io::Cursor::new(data))
+    }
+
+    /// Serializes the index.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        match &self.storage {
+            Storage::Bytes(data) => Ok(data.clone()),
+            Storage::File(_) => {
+                let len = self.header.len()
+                    .and_then(|l| usize::try_from(l).ok())
+                    .ok_or_else(|| malformed("too large"))?;
+                let mut data = vec![0; len];
+                self.storage.read_at(0, &mut data)?;
+                Ok(data)
+            },
+        }
+    }
+
+    /// Parses a serialized index.
+    ///
+    /// `data` must have been produced by [`KeyringIndex::to_vec`].
+    /// Returns an error if the data is corrupted, if a certificate
+    /// is not within the indexed keyring, or if it was produced by a
+    /// version of Sequoia using a different version of the index
+    /// format.  In that case, the index should be generated again.
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let header = Header::parse(data)?;
+        if header.len() != Some(data.len() as u64) {
+            return Err(malformed("truncated"));
+        }
+
+        let (content, integrity) = data.split_at(data.len() - DIGEST_LEN);
+        if digest(content)? != integrity {
+            return Err(malformed("integrity check failed"));
+        }
+
+        let index = KeyringIndex {
+            header,
+            storage: Storage::Bytes(data.to_vec()),
+        };
+        for i in 0..header.certs {
+            index.cert(i)?;
+        }
+        Ok(index)
+    }
+}
+
+impl CertParser<'static> {
+    /// Returns the certificates in the keyring that contain a key
+    /// with the given key handle.
+    ///
+    /// This uses the keyring's index, which is created or updated as
+    /// necessary, see [`KeyringIndex::open`] and
+    /// [`KeyringIndex::lookup`].  Only the matching certificates are
+    /// parsed.
+    ///
+    /// This function is only available if the `keyring-index` feature
+    /// is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// # use openpgp::serialize::Serialize;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// # let (alice, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+    /// #     .generate()?;
+    /// # let keyring = std::env::temp_dir().join(
+    /// #     format!("sequoia-cert-parser-index-doctest-{}.pgp",
+    /// #             std::process::id()));
+    /// # alice.serialize(&mut std::fs::File::create(&keyring)?)?;
+    /// let certs =
+    ///     CertParser::from_indexed_file(&keyring, &alice.key_handle())?
+    ///     .collect::<openpgp::Result<Vec<Cert>>>()?;
+    /// assert_eq!(certs.len(), 1);
+    /// assert_eq!(certs[0].fingerprint(), alice.fingerprint());
+    /// # std::fs::remove_file(
+    /// #     openpgp::cert::index::KeyringIndex::sidecar_path(&keyring))?;
+    /// # std::fs::remove_file(&keyring)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn from_indexed_file<P: AsRef<Path>>(keyring: P, handle: &KeyHandle)
+                                             -> Result<Self>
+    {
+        let keyring = keyring.as_ref();
+        KeyringIndex::open(keyring)?.lookup(keyring, handle)
+    }
+
+    /// Returns the certificates in the keyring that contain a user ID
+    /// with the given email address.
+    ///
+    /// This uses the keyring's index, which is created or updated as
+    /// necessary, see [`KeyringIndex::open`] and
+    /// [`KeyringIndex::lookup_email`].  Only the matching
+    /// certificates are parsed.
+    ///
+    /// This function is only available if the `keyring-index` feature
+    /// is enabled.
+    pub fn from_indexed_file_by_email<P: AsRef<Path>>(keyring: P,
+                                                      email: &str)
+                                                      -> Result<Self>
+    {
+        let keyring = keyring.as_ref();
+        KeyringIndex::open(keyring)?.lookup_email(keyring, email)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Write;
+
+    use crate::Cert;
+    use crate::cert::prelude::*;
+    use crate::serialize::Serialize;
+
+    /// A keyring in the temporary directory that is removed, along
+    /// with its sidecar file, when dropped.
+    struct TempKeyring(PathBuf);
+
+    impl TempKeyring {
+        fn new(name: &str, certs: &[&Cert]) -> Result<Self> {
+            let path = std::env::temp_dir().join(format!(
+                "sequoia-keyring-index-{}-{}.pgp", std::process::id(), name));
+            let k = TempKeyring(path);
+            let mut sink = File::create(&k.0)?;
+            for cert in certs {
+                cert.serialize(&mut sink)?;
+            }
+            Ok(k)
+        }
+    }
+
+    impl Drop for TempKeyring {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(KeyringIndex::sidecar_path(&self.0));
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn certs() -> Result<Vec<Cert>> {
+        ["neal.pgp", "dkg.gpg", "testy.pgp"].iter()
+            .map(|n| Cert::from_bytes(crate::tests::key(n)))
+            .collect()
+    }
+
+    fn fingerprints(p: CertParser) -> Result<Vec<Fingerprint>> {
+        p.map(|c| c.map(|c| c.fingerprint())).collect()
+    }
+
+    #[test]
+    fn lookup() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new(
+            "lookup", &certs.iter().collect::<Vec<_>>())?;
+        let index = KeyringIndex::generate(&keyring.0)?;
+        assert_eq!(index.len(), certs.len());
+
+        for cert in certs.iter() {
+            for key in cert.keys() {
+                assert_eq!(fingerprints(index.lookup(
+                    &keyring.0, &key.fingerprint().into())?)?,
+                           vec![cert.fingerprint()]);
+                assert_eq!(fingerprints(index.lookup(
+                    &keyring.0, &key.keyid().into())?)?,
+                           vec![cert.fingerprint()]);
+            }
+            for ua in cert.userids() {
+                if let Some(email) = ua.email2()? {
+                    assert_eq!(fingerprints(index.lookup_email(
+                        &keyring.0, &email.to_uppercase())?)?,
+                               vec![cert.fingerprint()]);
+                }
+            }
+        }
+
+        assert!(fingerprints(index.lookup_email(
+            &keyring.0, "nobody@example.org")?)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new(
+            "roundtrip", &certs.iter().collect::<Vec<_>>())?;
+        let index = KeyringIndex::generate(&keyring.0)?;
+
+        let serialized = index.to_vec()?;
+        assert_eq!(KeyringIndex::from_slice(&serialized)?.to_vec()?,
+                   serialized);
+
+        for i in [0, MAGIC.len(), MAGIC.len() + 9, serialized.len() - 1] {
+            let mut corrupted = serialized.clone();
+            corrupted[i] ^= 1;
+            assert!(KeyringIndex::from_slice(&corrupted).is_err());
+        }
+        assert!(KeyringIndex::from_slice(
+            &serialized[..serialized.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sidecar() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new("sidecar", &[&certs[0]])?;
+        let sidecar = KeyringIndex::sidecar_path(&keyring.0);
+
+        let index = KeyringIndex::open(&keyring.0)?;
+        assert_eq!(index.len(), 1);
+        assert!(sidecar.exists());
+        let loaded = KeyringIndex::open(&keyring.0)?;
+        assert!(matches!(loaded.storage, Storage::File(_)));
+        assert_eq!(loaded.to_vec()?, index.to_vec()?);
+
+        // Appending a certificate makes the index stale.
+        let mut sink = fs::OpenOptions::new().append(true).open(&keyring.0)?;
+        certs[1].serialize(&mut sink)?;
+        sink.flush()?;
+        drop(sink);
+        assert!(! index.is_current(&keyring.0)?);
+        assert!(index.lookup(&keyring.0, &certs[0].key_handle()).is_err());
+
+        // Opening it again updates the sidecar.
+        let index = KeyringIndex::open(&keyring.0)?;
+        assert_eq!(index.len(), 2);
+        assert_eq!(fs::read(&sidecar)?, index.to_vec()?);
+        assert_eq!(fingerprints(index.lookup(
+            &keyring.0, &certs[1].key_handle())?)?,
+                   vec![certs[1].fingerprint()]);
+        Ok(())
+    }
+
+    #[test]
+    fn modified_in_place() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new("modified", &[&certs[0]])?;
+        let mut index = KeyringIndex::generate(&keyring.0)?;
+
+        // Modify the keyring without changing its size, and pretend
+        // that the modification time didn't change either.
+        let mut data = fs::read(&keyring.0)?;
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        fs::write(&keyring.0, &data)?;
+        index.header.stamp = Stamp::new(&fs::metadata(&keyring.0)?);
+
+        assert!(index.is_current(&keyring.0)?);
+        assert!(index.lookup(&keyring.0, &certs[0].key_handle()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn armored() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new("armored", &[])?;
+        let mut sink = File::create(&keyring.0)?;
+        certs[0].armored().serialize(&mut sink)?;
+        drop(sink);
+
+        assert!(KeyringIndex::generate(&keyring.0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn junk() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new("junk", &[&certs[0]])?;
+        let mut sink = fs::OpenOptions::new().append(true).open(&keyring.0)?;
+        sink.write_all(&[0; 64])?;
+        certs[1].serialize(&mut sink)?;
+        drop(sink);
+
+        let index = KeyringIndex::generate(&keyring.0)?;
+        assert_eq!(index.len(), 2);
+        assert_eq!(fingerprints(index.lookup(
+            &keyring.0, &certs[1].key_handle())?)?,
+                   vec![certs[1].fingerprint()]);
+        Ok(())
+    }
+
+    /// Checks that lookups using an index loaded from the sidecar
+    /// file, which is searched in place, find the same certificates.
+    #[test]
+    fn lookup_in_place() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new(
+            "in-place", &certs.iter().collect::<Vec<_>>())?;
+        KeyringIndex::open(&keyring.0)?;
+        let index = KeyringIndex::open(&keyring.0)?;
+        assert!(matches!(index.storage, Storage::File(_)));
+        assert_eq!(index.len(), certs.len());
+
+        for cert in certs.iter() {
+            for key in cert.keys() {
+                assert_eq!(fingerprints(index.lookup(
+                    &keyring.0, &key.keyid().into())?)?,
+                           vec![cert.fingerprint()]);
+            }
+            for ua in cert.userids() {
+                if let Some(email) = ua.email2()? {
+                    assert_eq!(fingerprints(index.lookup_email(
+                        &keyring.0, email)?)?,
+                               vec![cert.fingerprint()]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that certificates that don't lie within the keyring
+    /// are rejected.
+    #[test]
+    fn out_of_bounds() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new("out-of-bounds", &[&certs[0]])?;
+        let index = KeyringIndex::generate(&keyring.0)?;
+
+        // Forge the length of the certificate, and fix the digest.
+        let mut forged = index.to_vec()?;
+        let len = HEADER_LEN + 8;
+        forged[len..len + 8].copy_from_slice(&u64::MAX.to_be_bytes());
+        let content = forged.len() - DIGEST_LEN;
+        let integrity = digest(&forged[..content])?;
+        forged[content..].copy_from_slice(&integrity);
+        assert!(KeyringIndex::from_slice(&forged).is_err());
+
+        // When searching the sidecar file in place, the certificate
+        // is only checked when it is read.
+        fs::write(KeyringIndex::sidecar_path(&keyring.0), &forged)?;
+        let index = KeyringIndex::open(&keyring.0)?;
+        assert!(matches!(index.storage, Storage::File(_)));
+        assert!(index.lookup(&keyring.0, &certs[0].key_handle()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cert_parser() -> Result<()> {
+        let certs = certs()?;
+        let keyring = TempKeyring::new(
+            "cert-parser", &certs.iter().collect::<Vec<_>>())?;
+
+        assert_eq!(fingerprints(CertParser::from_indexed_file(
+            &keyring.0, &certs[1].key_handle())?)?,
+                   vec![certs[1].fingerprint()]);
+        assert!(KeyringIndex::sidecar_path(&keyring.0).exists());
+
+        let email = certs[2].userids().next().unwrap().email2()?.unwrap()
+            .to_string();
+        assert_eq!(fingerprints(CertParser::from_indexed_file_by_email(
+            &keyring.0, &email)?)?,
+                   vec![certs[2].fingerprint()]);
+        Ok(())
+    }
+}
@@ -11,6 +11,11 @@
 //! looking for the right self-signature), we can search the
 //! signatures without triggering the verification, and only verify
 //! the one we are really interested in.
+//!
+//! The verification results are kept when the certificate is
+//! canonicalized again, e.g. when merging in an update, as long as
+//! the signature's context has not changed.  This way, we don't
+//! verify the signatures of unchanged components again.
 
 use std::{
     cmp::Ordering,
@@ -146,6 +151,24 @@ impl LazySignatures {
         r
     }
 
+    /// Like [`LazySignatures::take`], but also returns the
+    /// signatures' states.
+    pub fn take_with_states(&mut self) -> Vec<(Signature, SigState)> {
+        self.assert_invariant();
+        let states = mem::take(&mut *self.states.lock().unwrap());
+        let sigs = mem::take(&mut self.sigs);
+        self.verified_sigs.take();
+        self.assert_invariant();
+        sigs.into_iter().zip(states).collect()
+    }
+
+    /// Returns the signatures' states.
+    #[cfg(feature = "cert-cache")]
+    pub fn states(&self) -> Vec<SigState> {
+        self.assert_invariant();
+        self.states.lock().unwrap().clone()
+    }
+
     /// Like [`Vec::push`].
     pub fn push(&mut self, s: Signature) {
         self.assert_invariant();
@@ -155,6 +178,19 @@ impl LazySignatures {
         self.assert_invariant();
     }
 
+    /// Like [`LazySignatures::push`], but with a known state.
+    ///
+    /// `state` must be the result of verifying `s` in the context of
+    /// this vector, i.e., using the same primary key, and, if this
+    /// is a subkey binding, the same subkey.
+    pub fn push_with_state(&mut self, s: Signature, state: SigState) {
+        self.assert_invariant();
+        self.sigs.push(s);
+        self.states.lock().unwrap().push(state);
+        self.verified_sigs.take();
+        self.assert_invariant();
+    }
+
     /// Like [`Vec::append`].
     pub fn append(&mut self, other: &mut LazySignatures) {
         // XXX check context
@@ -167,33 +203,53 @@ impl LazySignatures {
     }
 
     /// Like [`Vec::sort_by`].
-    pub fn sort_by<F>(&mut self, compare: F)
+    pub fn sort_by<F>(&mut self, mut compare: F)
     where
         F: FnMut(&Signature, &Signature) -> Ordering,
     {
         self.assert_invariant();
-        self.sigs.sort_by(compare);
-        self.states.lock().unwrap().iter_mut().for_each(|p| *p = SigState::Unverified);
-        self.verified_sigs.take();
+        // Keep the states: reordering the signatures doesn't change
+        // the verification results.
+        let mut sigs = self.take_with_states();
+        sigs.sort_by(|a, b| compare(&a.0, &b.0));
+        self.restore_with_states(sigs);
         self.assert_invariant();
     }
 
     /// Like [`Vec::dedup_by`].
-    pub fn dedup_by<F>(&mut self, same_bucket: F)
+    pub fn dedup_by<F>(&mut self, mut same_bucket: F)
     where
         F: FnMut(&mut Signature, &mut Signature) -> bool,
     {
         self.assert_invariant();
-        self.sigs.dedup_by(same_bucket);
-        {
-            let mut states = self.states.lock().unwrap();
-            states.truncate(self.sigs.len());
-            states.iter_mut().for_each(|p| *p = SigState::Unverified);
-        }
-        self.verified_sigs.take();
+        let mut sigs = self.take_with_states();
+        sigs.dedup_by(|a, b| {
+            if same_bucket(&mut a.0, &mut b.0) {
+                // `same_bucket` may have merged `a` into `b`, i.e.,
+                // added subpackets to `b`'s unhashed area.  This
+                // doesn't invalidate a good signature, but it may
+                // make a bad one good, e.g. if `a` carried a primary
+                // key binding signature in its unhashed area.
+                if b.1 != SigState::Good && a.1 != b.1 {
+                    b.1 = SigState::Unverified;
+                }
+                true
+            } else {
+                false
+            }
+        });
+        self.restore_with_states(sigs);
         self.assert_invariant();
     }
 
+    /// Replaces the signatures and their states.
+    fn restore_with_states(&mut self, sigs: Vec<(Signature, SigState)>) {
+        let (sigs, states) = sigs.into_iter().unzip();
+        self.sigs = sigs;
+        *self.states.lock().unwrap() = states;
+        self.verified_sigs.take();
+    }
+
     /// Like [`Vec::iter_mut`], but gives out **potentially
     /// unverified** signatures.
     pub fn iter_mut_unverified(&mut self) -> impl Iterator<Item = &mut Signature> {
@@ -360,3 +416,107 @@ impl LazySignatures {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::Packet;
+    use crate::cert::prelude::*;
+    use crate::parse::Parse;
+    use crate::serialize::SerializeInto;
+    use crate::packet::{UserID, signature::SignatureBuilder};
+    use crate::types::SignatureType;
+
+    fn states(sigs: &LazySignatures) -> Vec<SigState> {
+        sigs.states.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn merge_keeps_states() -> Result<()> {
+        let (cert, _) = CertBuilder::new()
+            .add_userid("Alice")
+            .add_signing_subkey()
+            .generate()?;
+        let mut signer = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+
+        // Verify all self signatures.
+        assert_eq!(cert.bad_signatures().count(), 0);
+
+        // Add a user ID.
+        let userid = UserID::from("Bob");
+        let binding = userid.bind(
+            &mut signer, &cert,
+            SignatureBuilder::new(SignatureType::PositiveCertification))?;
+        let update = cert.clone().insert_packets(
+            vec![Packet::from(userid), binding.into()])?;
+
+        // Parse the update, as if we got it from a keyserver.
+        let update = Cert::from_bytes(&update.to_vec()?)?;
+        assert_eq!(states(&update.primary.self_signatures),
+                   vec![SigState::Unverified]);
+
+        let merged = cert.merge_public(update)?;
+        assert_eq!(merged.userids().count(), 2);
+
+        // The signatures of the unchanged components are not
+        // verified again.
+        assert_eq!(states(&merged.primary.self_signatures),
+                   vec![SigState::Good]);
+        for b in merged.subkeys.iter() {
+            assert_eq!(states(&b.self_signatures), vec![SigState::Good]);
+        }
+        for b in merged.userids.iter() {
+            if b.userid().value() == b"Alice" {
+                assert_eq!(states(&b.self_signatures), vec![SigState::Good]);
+            } else {
+                assert_eq!(states(&b.self_signatures),
+                           vec![SigState::Unverified]);
+            }
+        }
+
+        // But they are when needed.
+        assert_eq!(merged.bad_signatures().count(), 0);
+        for b in merged.userids.iter() {
+            assert_eq!(states(&b.self_signatures), vec![SigState::Good]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sort_and_dedup_keep_states() -> Result<()> {
+        let (cert, _) = CertBuilder::new().add_userid("Alice").generate()?;
+        let direct = cert.primary_key().self_signatures().next().unwrap()
+            .clone();
+        let binding = cert.userids().next().unwrap().self_signatures()
+            .next().unwrap().clone();
+        let new = || LazySignatures::new(
+            Arc::new(cert.primary_key().key().clone()));
+
+        // Sorting keeps the states.
+        let mut sigs = new();
+        sigs.push_with_state(direct.clone(), SigState::Good);
+        sigs.push(binding.clone());
+        sigs.sort_by(|a, b| u8::from(a.typ()).cmp(&u8::from(b.typ())));
+        assert_eq!(sigs.as_slice_unverified(), &[binding, direct.clone()]);
+        assert_eq!(states(&sigs), vec![SigState::Unverified, SigState::Good]);
+
+        // A good signature stays good when another one is merged
+        // into it.
+        let mut sigs = new();
+        sigs.push_with_state(direct.clone(), SigState::Good);
+        sigs.push(direct.clone());
+        sigs.dedup_by(|a, b| a == b);
+        assert_eq!(states(&sigs), vec![SigState::Good]);
+
+        // Merging a good signature into an unverified one resets the
+        // state.
+        let mut sigs = new();
+        sigs.push(direct.clone());
+        sigs.push_with_state(direct, SigState::Good);
+        sigs.dedup_by(|a, b| a == b);
+        assert_eq!(states(&sigs), vec![SigState::Unverified]);
+        Ok(())
+    }
+}
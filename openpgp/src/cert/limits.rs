@@ -0,0 +1,163 @@
+//! Limits on the number of third-party certifications.
+//!
+//! Anyone can certify any certificate, and keyservers that don't
+//! filter third-party certifications distribute them.  This has been
+//! abused to flood certificates with thousands of bogus
+//! certifications, making them expensive to parse, and unusable in
+//! practice.  [`CertificationLimits`] caps the number of third-party
+//! certifications that are kept per component.
+
+use std::collections::HashMap;
+
+use crate::{
+    KeyID,
+    packet::Signature,
+};
+use super::bundle::ComponentBundle;
+
+/// Limits on the number of third-party certifications kept per
+/// component.
+///
+/// When limits are applied to a certificate, the third-party
+/// certifications of each component are considered newest first.  A
+/// certification is kept if neither the limit on certifications by
+/// its issuer, nor the limit on certifications of the component has
+/// been reached.  Otherwise, it is dropped.  Certifications without
+/// an issuer are counted as if they were made by the same issuer.
+///
+/// Self signatures, self revocations, and third-party revocations are
+/// never dropped.
+///
+/// By default, there are no limits.  Policies can configure limits,
+/// see [`Policy::certification_limits`] and
+/// [`StandardPolicy::set_certification_limits`].  Limits are applied
+/// during canonicalization when parsing certificates using
+/// [`CertParser::certification_limits`], or to an existing
+/// certificate using [`Cert::limit_certifications`].
+///
+///   [`Policy::certification_limits`]: crate::policy::Policy::certification_limits()
+///   [`StandardPolicy::set_certification_limits`]: crate::policy::StandardPolicy::set_certification_limits()
+///   [`CertParser::certification_limits`]: super::CertParser::certification_limits()
+///   [`Cert::limit_certifications`]: super::Cert::limit_certifications()
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::{CertificationLimits, CertParser};
+/// # use openpgp::cert::prelude::*;
+/// use openpgp::parse::Parse;
+/// use openpgp::policy::{Policy, StandardPolicy};
+/// # use openpgp::serialize::SerializeInto;
+///
+/// # fn main() -> openpgp::Result<()> {
+/// # let (cert, _) = CertBuilder::new().add_userid("Alice").generate()?;
+/// # let keyring = cert.to_vec()?;
+/// // Keep the newest certification by each issuer, and at most 100
+/// // certifications per component.
+/// let mut policy = StandardPolicy::new();
+/// policy.set_certification_limits(
+///     CertificationLimits::new()
+///         .set_per_issuer(Some(1))
+///         .set_per_component(Some(100)));
+///
+/// let mut parser = CertParser::from_bytes(&keyring)?
+///     .certification_limits(policy.certification_limits());
+/// while let Some(cert) = parser.next() {
+///     let cert = cert?;
+///     let dropped = parser.excess_certifications().len();
+///     # assert_eq!(dropped, 0);
+///     if dropped > 0 {
+///         eprintln!("{}: dropped {} certifications",
+///                   cert.fingerprint(), dropped);
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CertificationLimits {
+    per_issuer: Option<usize>,
+    per_component: Option<usize>,
+}
+assert_send_and_sync!(CertificationLimits);
+
+impl CertificationLimits {
+    /// Returns limits that don't limit anything.
+    ///
+    /// This is the same as [`CertificationLimits::default`], but
+    /// can be used in constant expressions.
+    pub const fn new() -> Self {
+        CertificationLimits {
+            per_issuer: None,
+            per_component: None,
+        }
+    }
+
+    /// Limits the number of certifications by each issuer per
+    /// component.
+    ///
+    /// `None` means no limit.
+    pub fn set_per_issuer(mut self, limit: Option<usize>) -> Self {
+        self.per_issuer = limit;
+        self
+    }
+
+    /// Returns the limit on the number of certifications by each
+    /// issuer per component.
+    pub fn per_issuer(&self) -> Option<usize> {
+        self.per_issuer
+    }
+
+    /// Limits the number of certifications per component.
+    ///
+    /// `None` means no limit.
+    pub fn set_per_component(mut self, limit: Option<usize>) -> Self {
+        self.per_component = limit;
+        self
+    }
+
+    /// Returns the limit on the number of certifications per
+    /// component.
+    pub fn per_component(&self) -> Option<usize> {
+        self.per_component
+    }
+
+    /// Returns whether there are no limits.
+    pub(super) fn is_unlimited(&self) -> bool {
+        self.per_issuer.is_none() && self.per_component.is_none()
+    }
+}
+
+impl<C> ComponentBundle<C> {
+    /// Drops the certifications exceeding `limits`, and appends
+    /// them to `excess`.
+    ///
+    /// The certifications must be sorted so that the newest one
+    /// comes first.
+    pub(super) fn limit_certifications(&mut self,
+                                       limits: &CertificationLimits,
+                                       excess: &mut Vec<Signature>)
+    {
+        if limits.is_unlimited() {
+            return;
+        }
+
+        let mut per_issuer: HashMap<Option<KeyID>, usize> = HashMap::new();
+        let mut kept = 0;
+        for sig in std::mem::take(&mut self.certifications) {
+            let issuer =
+                sig.get_issuers().into_iter().next().map(KeyID::from);
+            let n = per_issuer.entry(issuer).or_default();
+
+            if limits.per_issuer.map(|l| *n >= l).unwrap_or(false)
+                || limits.per_component.map(|l| kept >= l).unwrap_or(false)
+            {
+                excess.push(sig);
+            } else {
+                *n += 1;
+                kept += 1;
+                self.certifications.push(sig);
+            }
+        }
+    }
+}
@@ -0,0 +1,588 @@
+//! Checks certificates against best practices.
+//!
+//! Certificates created by older implementations, or with unusual
+//! settings, often have problems that make them hard to use, or that
+//! weaken their security.  [`Cert::lint`] finds such problems, and
+//! [`Cert::fix_lints`] creates new self signatures that fix those
+//! that can be fixed using the primary key.
+//!
+//!   [`Cert::lint`]: super::Cert::lint()
+//!   [`Cert::fix_lints`]: super::Cert::fix_lints()
+
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::{
+    Fingerprint,
+    Result,
+    crypto::{Signer, hash::Digest},
+    packet::{
+        Key,
+        Signature,
+        UserAttribute,
+        UserID,
+        key,
+        signature::SignatureBuilder,
+    },
+    policy::Policy,
+    types::{HashAlgorithm, KeyFlags, RevocationStatus, SignatureType},
+};
+use super::Cert;
+
+/// User attributes larger than this are considered oversized.
+const MAX_USER_ATTRIBUTE_SIZE: usize = 64 * 1024;
+
+/// A problem found by [`Cert::lint`].
+///
+///   [`Cert::lint`]: super::Cert::lint()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    component: Component,
+    problem: Problem,
+}
+assert_send_and_sync!(Lint);
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.component, self.problem)
+    }
+}
+
+impl Lint {
+    /// Returns the component that has the problem.
+    pub fn component(&self) -> &Component {
+        &self.component
+    }
+
+    /// Returns the problem.
+    pub fn problem(&self) -> &Problem {
+        &self.problem
+    }
+
+    /// Returns whether [`Cert::fix_lints`] can fix the problem.
+    ///
+    ///   [`Cert::fix_lints`]: super::Cert::fix_lints()
+    pub fn is_fixable(&self) -> bool {
+        matches!(self.problem, Problem::SHA1Binding)
+    }
+}
+
+/// The component of a certificate that a [`Lint`] refers to.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Component {
+    /// The primary key.
+    PrimaryKey(Fingerprint),
+    /// A subkey.
+    Subkey(Fingerprint),
+    /// A User ID.
+    UserID(UserID),
+    /// A User Attribute.
+    UserAttribute(UserAttribute),
+}
+assert_send_and_sync!(Component);
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Component::PrimaryKey(fp) => write!(f, "Primary key {}", fp),
+            Component::Subkey(fp) => write!(f, "Subkey {}", fp),
+            Component::UserID(u) =>
+                write!(f, "User ID {:?}", String::from_utf8_lossy(u.value())),
+            Component::UserAttribute(_) => write!(f, "User Attribute"),
+        }
+    }
+}
+
+/// A problem found by [`Cert::lint`].
+///
+///   [`Cert::lint`]: super::Cert::lint()
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// The binding signature uses SHA-1.
+    ///
+    /// SHA-1 is broken, and binding signatures using it are rejected
+    /// by the [`StandardPolicy`].  This can be fixed by creating a
+    /// new binding signature using a strong hash algorithm.
+    ///
+    ///   [`StandardPolicy`]: crate::policy::StandardPolicy
+    SHA1Binding,
+
+    /// The certificate does not expire.
+    ///
+    /// If the secret key material is lost, a certificate without an
+    /// expiration time stays valid forever.
+    NoExpiration,
+
+    /// The subkey has no key flags.
+    ///
+    /// A subkey without key flags can't be used for anything.
+    NoKeyFlags,
+
+    /// The subkey's key flags don't match its algorithm.
+    ///
+    /// For instance, the subkey is marked as encryption capable, but
+    /// uses a signing-only algorithm.  The flags that can't be used
+    /// are given as argument.
+    UnusableKeyFlags(KeyFlags),
+
+    /// The signing-capable subkey has no primary key binding signature.
+    ///
+    /// A signing-capable subkey must cross-sign the primary key, see
+    /// [Section 11.1 of RFC 4880].  Without it, the subkey is
+    /// rejected.  This can only be fixed using the subkey's secret
+    /// key material.
+    ///
+    ///   [Section 11.1 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-11.1
+    MissingBacksig,
+
+    /// The user attribute is very large.
+    ///
+    /// Large user attributes, usually photos, make the certificate
+    /// expensive to distribute.  The size is given as argument.
+    OversizedUserAttribute(usize),
+}
+assert_send_and_sync!(Problem);
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::SHA1Binding =>
+                write!(f, "binding signature uses SHA-1"),
+            Problem::NoExpiration =>
+                write!(f, "does not expire"),
+            Problem::NoKeyFlags =>
+                write!(f, "has no key flags"),
+            Problem::UnusableKeyFlags(flags) =>
+                write!(f, "key flags {:?} don't match the algorithm", flags),
+            Problem::MissingBacksig =>
+                write!(f, "signing-capable subkey does not cross-sign \
+                           the primary key"),
+            Problem::OversizedUserAttribute(size) =>
+                write!(f, "is {} bytes large", size),
+        }
+    }
+}
+
+/// Returns the newest signature that is not newer than `t`.
+fn newest<'a, I>(sigs: I, t: SystemTime) -> Option<&'a Signature>
+    where I: Iterator<Item = &'a Signature>,
+{
+    sigs.filter(|s| {
+        s.signature_creation_time().map(|c| c <= t).unwrap_or(false)
+    })
+        .max_by_key(|s| s.signature_creation_time())
+}
+
+/// Returns whether `sig` is a valid subkey binding signature, except
+/// for the missing primary key binding signature.
+///
+/// Such signatures are rejected when verifying the self signatures,
+/// hence we need to check them here.
+fn lacks_backsig(sig: &Signature,
+                 pk: &Key<key::PublicParts, key::PrimaryRole>,
+                 subkey: &Key<key::PublicParts, key::SubordinateRole>)
+                 -> bool
+{
+    if sig.typ() != SignatureType::SubkeyBinding
+        || ! sig.key_flags().map(|f| f.for_signing()).unwrap_or(false)
+        || sig.embedded_signatures().next().is_some()
+    {
+        return false;
+    }
+
+    let mut hash = match sig.hash_algo().context() {
+        Ok(hash) => hash,
+        Err(_) => return false,
+    };
+    sig.hash_subkey_binding(&mut hash, pk, subkey);
+    hash.into_digest()
+        .and_then(|digest| sig.verify_digest(pk, digest))
+        .is_ok()
+}
+
+/// Returns whether `sig` uses SHA-1.
+fn uses_sha1(sig: &Signature) -> bool {
+    sig.hash_algo() == HashAlgorithm::SHA1
+}
+
+impl Cert {
+    /// Checks the certificate against best practices.
+    ///
+    /// This checks for:
+    ///
+    ///   - binding signatures using SHA-1,
+    ///   - a missing expiration time,
+    ///   - subkeys with no or unusable key flags,
+    ///   - signing-capable subkeys without a primary key binding
+    ///     signature, and
+    ///   - oversized user attributes.
+    ///
+    /// Revoked components are not checked.  Binding signatures are
+    /// checked independently of `policy`, because, e.g., SHA-1
+    /// binding signatures are rejected by the [`StandardPolicy`], but
+    /// that is exactly what we want to detect.  `policy` is used to
+    /// check the expiration time and the key flags.
+    ///
+    /// Some problems can be fixed using [`Cert::fix_lints`].
+    ///
+    ///   [`StandardPolicy`]: crate::policy::StandardPolicy
+    ///   [`Cert::fix_lints`]: Cert::fix_lints()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::cert::lint::Problem;
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let p = &StandardPolicy::new();
+    /// let (cert, _) =
+    ///     CertBuilder::general_purpose(None, Some("alice@example.org"))
+    ///     .set_validity_period(None)
+    ///     .generate()?;
+    ///
+    /// let lints = cert.lint(p, None);
+    /// for lint in &lints {
+    ///     eprintln!("{}", lint);
+    /// }
+    /// assert_eq!(lints.len(), 1);
+    /// assert_eq!(lints[0].problem(), &Problem::NoExpiration);
+    /// # Ok(()) }
+    /// ```
+    pub fn lint<T>(&self, policy: &dyn Policy, t: T) -> Vec<Lint>
+        where T: Into<Option<SystemTime>>,
+    {
+        let t = t.into().unwrap_or_else(crate::now);
+        let mut lints = Vec::new();
+        let mut lint = |component: Component, problem| {
+            lints.push(Lint { component, problem });
+        };
+
+        let primary = self.primary_key();
+        let primary_fp = primary.fingerprint();
+        if let RevocationStatus::Revoked(_) = self.revocation_status(policy, t)
+        {
+            return lints;
+        }
+
+        if newest(primary.bundle().self_signatures2(), t)
+            .map(uses_sha1).unwrap_or(false)
+        {
+            lint(Component::PrimaryKey(primary_fp.clone()),
+                 Problem::SHA1Binding);
+        }
+
+        if let Ok(vc) = self.with_policy(policy, t) {
+            if vc.primary_key().key_expiration_time().is_none() {
+                lint(Component::PrimaryKey(primary_fp),
+                     Problem::NoExpiration);
+            }
+        }
+
+        for ua in self.userids() {
+            if let RevocationStatus::Revoked(_) = ua.revocation_status(policy, t)
+            {
+                continue;
+            }
+
+            if newest(ua.bundle().self_signatures2(), t)
+                .map(uses_sha1).unwrap_or(false)
+            {
+                lint(Component::UserID(ua.userid().clone()),
+                     Problem::SHA1Binding);
+            }
+        }
+
+        for ua in self.user_attributes() {
+            if let RevocationStatus::Revoked(_) = ua.revocation_status(policy, t)
+            {
+                continue;
+            }
+
+            if newest(ua.bundle().self_signatures2(), t)
+                .map(uses_sha1).unwrap_or(false)
+            {
+                lint(Component::UserAttribute(ua.user_attribute().clone()),
+                     Problem::SHA1Binding);
+            }
+
+            let size = ua.user_attribute().value().len();
+            if size > MAX_USER_ATTRIBUTE_SIZE {
+                lint(Component::UserAttribute(ua.user_attribute().clone()),
+                     Problem::OversizedUserAttribute(size));
+            }
+        }
+
+        for ka in self.keys().subkeys() {
+            if let RevocationStatus::Revoked(_) = ka.revocation_status(policy, t)
+            {
+                continue;
+            }
+
+            let component = || Component::Subkey(ka.fingerprint());
+            let binding = match newest(ka.bundle().self_signatures2(), t) {
+                Some(binding) => binding,
+                None => match newest(ka.bundle().bad_signatures(), t) {
+                    Some(binding)
+                        if lacks_backsig(binding, primary.key(), ka.key()) =>
+                    {
+                        lint(component(), Problem::MissingBacksig);
+                        binding
+                    },
+                    _ => continue,
+                },
+            };
+
+            if uses_sha1(binding) {
+                lint(component(), Problem::SHA1Binding);
+            }
+
+            let flags = match binding.key_flags() {
+                Some(flags) if ! flags.is_empty() => flags,
+                _ => {
+                    lint(component(), Problem::NoKeyFlags);
+                    continue;
+                },
+            };
+
+            let pk_algo = ka.pk_algo();
+            let mut unusable = KeyFlags::empty();
+            if ! pk_algo.for_signing() {
+                unusable = &unusable
+                    | &(&flags & &KeyFlags::empty()
+                        .set_signing()
+                        .set_certification()
+                        .set_authentication());
+            }
+            if ! pk_algo.for_encryption() {
+                unusable = &unusable
+                    | &(&flags & &KeyFlags::empty()
+                        .set_transport_encryption()
+                        .set_storage_encryption());
+            }
+            if ! unusable.is_empty() {
+                lint(component(), Problem::UnusableKeyFlags(unusable));
+            }
+        }
+
+        lints
+    }
+
+    /// Creates self signatures fixing the given problems.
+    ///
+    /// `lints` are the problems to fix, usually returned by
+    /// [`Cert::lint`] using the same `t`.  Problems that can't be
+    /// fixed (see [`Lint::is_fixable`]) are ignored.  `signer` must
+    /// be the certificate's primary key.
+    ///
+    /// Binding signatures using SHA-1 are replaced by copies using
+    /// SHA-512.  Note: primary key binding signatures embedded in
+    /// subkey binding signatures are kept as-is, because recreating
+    /// them requires the subkey's secret key material.
+    ///
+    /// Returns the new signatures, which can be merged into the
+    /// certificate using [`Cert::insert_packets`].
+    ///
+    ///   [`Cert::lint`]: Cert::lint()
+    ///   [`Cert::insert_packets`]: Cert::insert_packets()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::policy::StandardPolicy;
+    /// # use openpgp::packet::signature::SignatureBuilder;
+    /// # use openpgp::types::{HashAlgorithm, SignatureType};
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let p = &StandardPolicy::new();
+    /// # let (cert, _) =
+    /// #     CertBuilder::general_purpose(None, Some("alice@example.org"))
+    /// #     .generate()?;
+    /// # let mut signer = cert.primary_key().key().clone()
+    /// #     .parts_into_secret()?.into_keypair()?;
+    /// # let uid = cert.userids().next().unwrap();
+    /// # let sig = SignatureBuilder::from(uid.binding_signature(p, None)?.clone())
+    /// #     .set_hash_algo(HashAlgorithm::SHA1)
+    /// #     .sign_userid_binding(&mut signer, None, uid.userid())?;
+    /// # let cert = cert.insert_packets(sig)?;
+    /// // `cert` has a User ID binding signature using SHA-1.
+    /// let lints = cert.lint(p, None);
+    /// assert!(lints.iter().any(|l| l.is_fixable()));
+    ///
+    /// let sigs = cert.fix_lints(None, &mut signer, &lints)?;
+    /// let cert = cert.insert_packets(sigs)?;
+    /// assert!(cert.lint(p, None).iter().all(|l| ! l.is_fixable()));
+    /// # Ok(()) }
+    /// ```
+    pub fn fix_lints<T>(&self, t: T, signer: &mut dyn Signer, lints: &[Lint])
+                        -> Result<Vec<Signature>>
+        where T: Into<Option<SystemTime>>,
+    {
+        let t = t.into().unwrap_or_else(crate::now);
+        let primary = self.primary_key();
+        let pk = primary.key();
+
+        // Returns a template for the new signature.
+        let template = |sig: &Signature| {
+            SignatureBuilder::from(sig.clone())
+                .set_hash_algo(HashAlgorithm::SHA512)
+                .set_reference_time(t)
+        };
+
+        let mut sigs = Vec::new();
+        for lint in lints.iter().filter(|l| l.problem == Problem::SHA1Binding)
+        {
+            match &lint.component {
+                Component::PrimaryKey(_) => {
+                    if let Some(sig) =
+                        newest(primary.bundle().self_signatures2(), t)
+                    {
+                        sigs.push(template(sig).sign_direct_key(signer, pk)?);
+                    }
+                },
+                Component::UserID(userid) => {
+                    if let Some(sig) = self.userids()
+                        .find(|u| u.userid() == userid)
+                        .and_then(|u| newest(u.bundle().self_signatures2(), t))
+                    {
+                        sigs.push(template(sig)
+                                  .sign_userid_binding(signer, pk, userid)?);
+                    }
+                },
+                Component::UserAttribute(ua) => {
+                    if let Some(sig) = self.user_attributes()
+                        .find(|u| u.user_attribute() == ua)
+                        .and_then(|u| newest(u.bundle().self_signatures2(), t))
+                    {
+                        sigs.push(template(sig)
+                                  .sign_user_attribute_binding(
+                                      signer, pk, ua)?);
+                    }
+                },
+                Component::Subkey(fp) => {
+                    if let Some(ka) = self.keys().subkeys()
+                        .find(|ka| &ka.fingerprint() == fp)
+                    {
+                        if let Some(sig) =
+                            newest(ka.bundle().self_signatures2(), t)
+                        {
+                            debug_assert_eq!(sig.typ(),
+                                             SignatureType::SubkeyBinding);
+                            sigs.push(template(sig).sign_subkey_binding(
+                                signer, pk, ka.key())?);
+                        }
+                    }
+                },
+            }
+        }
+
+        Ok(sigs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cert::prelude::*;
+    use crate::packet::key::Key4;
+    use crate::policy::StandardPolicy;
+    use crate::types::Curve;
+
+    #[test]
+    fn clean() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (cert, _) =
+            CertBuilder::general_purpose(None, Some("alice@example.org"))
+            .generate()?;
+        assert_eq!(cert.lint(p, None), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn subkey_problems() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (cert, _) = CertBuilder::new()
+            .add_userid("alice@example.org")
+            .generate()?;
+        let mut signer = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+
+        // A signing subkey without backsig, and an EdDSA subkey
+        // marked as encryption capable.
+        let mut packets: Vec<crate::Packet> = Vec::new();
+        let signing: Key<_, key::SubordinateRole> =
+            Key4::generate_ecc(true, Curve::Ed25519)?.into();
+        packets.push(signing.clone().parts_into_public().into());
+        packets.push(SignatureBuilder::new(SignatureType::SubkeyBinding)
+            .set_key_flags(KeyFlags::empty().set_signing())?
+            .sign_subkey_binding(&mut signer, None, &signing)?.into());
+
+        let bad: Key<_, key::SubordinateRole> =
+            Key4::generate_ecc(true, Curve::Ed25519)?.into();
+        packets.push(bad.clone().parts_into_public().into());
+        packets.push(SignatureBuilder::new(SignatureType::SubkeyBinding)
+            .set_key_flags(KeyFlags::empty().set_storage_encryption())?
+            .sign_subkey_binding(&mut signer, None, &bad)?.into());
+
+        let cert = cert.insert_packets(packets)?;
+        let lints = cert.lint(p, None);
+        assert!(lints.contains(&Lint {
+            component: Component::Subkey(signing.fingerprint()),
+            problem: Problem::MissingBacksig,
+        }));
+        assert!(lints.contains(&Lint {
+            component: Component::Subkey(bad.fingerprint()),
+            problem: Problem::UnusableKeyFlags(
+                KeyFlags::empty().set_storage_encryption()),
+        }));
+        assert!(lints.iter().all(|l| ! l.is_fixable()));
+        assert!(cert.fix_lints(None, &mut signer, &lints)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn fix_sha1() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (cert, _) =
+            CertBuilder::general_purpose(None, Some("alice@example.org"))
+            .generate()?;
+        let mut signer = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+
+        // Replace all binding signatures with SHA-1 ones.
+        let mut sigs: Vec<Signature> = Vec::new();
+        let sha1 = |sig: &Signature| {
+            SignatureBuilder::from(sig.clone())
+                .set_hash_algo(HashAlgorithm::SHA1)
+        };
+        let uid = cert.userids().next().unwrap();
+        sigs.push(sha1(uid.self_signatures().next().unwrap())
+                  .sign_userid_binding(&mut signer, None, uid.userid())?);
+        for ka in cert.keys().subkeys() {
+            sigs.push(sha1(ka.self_signatures().next().unwrap())
+                      .sign_subkey_binding(&mut signer, None, ka.key())?);
+        }
+        let cert = cert.insert_packets(sigs)?;
+
+        let lints = cert.lint(p, None);
+        assert_eq!(lints.iter()
+                   .filter(|l| l.problem() == &Problem::SHA1Binding)
+                   .count(),
+                   1 + cert.keys().subkeys().count());
+
+        let fixes = cert.fix_lints(None, &mut signer, &lints)?;
+        assert_eq!(fixes.len(), lints.len());
+        assert!(fixes.iter().all(|s| s.hash_algo() == HashAlgorithm::SHA512));
+        let cert = cert.insert_packets(fixes)?;
+        assert_eq!(cert.lint(p, None), vec![]);
+        assert_eq!(cert.with_policy(p, None)?.keys().count(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,437 @@
+//! Printable backups of secret key material.
+//!
+//! Most of a transferable secret key is public: the public key
+//! material, the User IDs, and the signatures.  This information can
+//! be recovered from the certificate, e.g. from a key server.
+//! [`PaperBackup`] extracts just the secret key material, which is
+//! small enough to be printed on paper, and stored offline.  The
+//! idea, and the layout, is modeled after David Shaw's [paperkey].
+//!
+//!   [paperkey]: https://www.jabberwocky.com/software/paperkey/
+
+use std::fmt::Write;
+
+use crate::{
+    Cert,
+    Error,
+    Fingerprint,
+    Packet,
+    Result,
+    armor::crc::Crc,
+    crypto::{Decryptor, SessionKey, Signer},
+    packet::{Key, Tag, header::{BodyLength, CTB, Header}, key},
+    parse::Parse,
+    serialize::{Marshal, MarshalInto},
+    types::HashAlgorithm,
+};
+
+/// The version of the binary format.
+const FORMAT_VERSION: u8 = 0;
+
+/// The number of bytes per line in the textual representation.
+const BYTES_PER_LINE: usize = 16;
+
+/// A backup of a certificate's secret key material.
+///
+/// A `PaperBackup` contains the secret key material of every key
+/// that has secret key material, identified by the key's
+/// fingerprint.  Secret key material that is encrypted with a
+/// password remains encrypted.  To restore the secret keys, the
+/// certificate is needed, see [`PaperBackup::restore`].
+///
+/// The backup can be converted into a compact binary form, see
+/// [`PaperBackup::to_bytes`], e.g. to encode it in a QR code, or
+/// into a text form that is suitable for printing, see
+/// [`PaperBackup::to_text`].  The text form consists of numbered
+/// lines of hexadecimal digits, each protected by a CRC-24
+/// checksum, so that typos can be located when the backup is typed
+/// in again.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> sequoia_openpgp::Result<()> {
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::cert::PaperBackup;
+///
+/// let (tsk, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+///     .generate()?;
+///
+/// // Print the backup, and store it in a safe place.
+/// let text = PaperBackup::from_cert(&tsk)?.to_text();
+/// # assert!(text.lines().count() > 1);
+///
+/// // Later, get the certificate, e.g. from a key server, and type
+/// // in the backup.
+/// let cert = tsk.clone().strip_secret_key_material();
+/// let restored = PaperBackup::from_text(&text)?.restore(cert)?;
+/// assert!(restored.is_tsk());
+/// # assert_eq!(restored.keys().secret().count(), tsk.keys().secret().count());
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaperBackup {
+    /// The keys' fingerprints, and their secret key material.
+    ///
+    /// The secret key material is the tail of the secret key packet's
+    /// body following the public key material, i.e. it starts with
+    /// the S2K usage octet.
+    keys: Vec<(Fingerprint, Box<[u8]>)>,
+}
+assert_send_and_sync!(PaperBackup);
+
+impl PaperBackup {
+    /// Extracts the secret key material from `cert`.
+    ///
+    /// Returns an error if `cert` doesn't contain any secret key
+    /// material, or if a key's secret key material is too large to
+    /// be represented, see [`PaperBackup::to_bytes`].
+    pub fn from_cert(cert: &Cert) -> Result<Self> {
+        let mut keys = Vec::new();
+        for ka in cert.keys().secret() {
+            let Key::V4(key) = ka.key();
+            let public = key.parts_as_public().serialized_len();
+            let body = key.to_vec()?;
+            let secret = &body[public..];
+            if u16::try_from(secret.len()).is_err() {
+                return Err(Error::InvalidArgument(format!(
+                    "Secret key material of {} is too large",
+                    key.fingerprint())).into());
+            }
+            keys.push((key.fingerprint(), secret.into()));
+        }
+
+        if keys.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "{} has no secret key material", cert.fingerprint())).into());
+        }
+
+        Ok(PaperBackup { keys })
+    }
+
+    /// Returns the fingerprints of the keys in the backup.
+    pub fn fingerprints(&self) -> impl Iterator<Item = &Fingerprint> {
+        self.keys.iter().map(|(fpr, _)| fpr)
+    }
+
+    /// Merges the secret key material into `cert`.
+    ///
+    /// Returns an error if the backup contains secret key material
+    /// for a key that is not part of `cert`, if the secret key
+    /// material is malformed, or if it doesn't match the public key.
+    ///
+    /// Note: Unencrypted secret key material is checked by signing,
+    /// or encrypting and decrypting, using the key.  Secret key
+    /// material that is encrypted with a password can only be checked
+    /// once it is decrypted, e.g. using [`Key::decrypt_secret`].
+    ///
+    ///   [`Key::decrypt_secret`]: crate::packet::Key::decrypt_secret()
+    pub fn restore(&self, cert: Cert) -> Result<Cert> {
+        let mut packets: Vec<Packet> = Vec::with_capacity(self.keys.len());
+        for (fpr, secret) in self.keys.iter() {
+            let ka = cert.keys().find(|ka| &ka.fingerprint() == fpr)
+                .ok_or_else(|| Error::InvalidArgument(format!(
+                    "Key {} is not part of {}", fpr, cert.fingerprint())))?;
+            let tag = if ka.fingerprint() == cert.fingerprint() {
+                Tag::SecretKey
+            } else {
+                Tag::SecretSubkey
+            };
+
+            let mut body = ka.key().parts_as_public().to_vec()?;
+            body.extend_from_slice(secret);
+            let mut buf = Vec::with_capacity(6 + body.len());
+            Header::new(CTB::new(tag), BodyLength::Full(body.len() as u32))
+                .serialize(&mut buf)?;
+            buf.extend_from_slice(&body);
+
+            match Packet::from_bytes(&buf)? {
+                Packet::SecretKey(k) => {
+                    check_secret(&k)?;
+                    packets.push(k.into());
+                },
+                Packet::SecretSubkey(k) => {
+                    check_secret(&k)?;
+                    packets.push(k.into());
+                },
+                p => return Err(Error::MalformedPacket(format!(
+                    "Secret key material of {} is malformed: {}",
+                    fpr, p.tag())).into()),
+            }
+        }
+
+        cert.insert_packets(packets)
+    }
+
+    /// Returns the compact binary representation.
+    ///
+    /// The format starts with a version octet, 0.  For each key, it
+    /// contains the key's version, 4, its fingerprint, the length of
+    /// the secret key material as a two octet big endian number, and
+    /// the secret key material.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.serialize())
+    }
+
+    /// Returns the compact binary representation.
+    ///
+    /// The length of the secret key material was checked when it
+    /// was extracted, or parsed.
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = vec![FORMAT_VERSION];
+        for (fpr, secret) in self.keys.iter() {
+            buf.push(4);
+            buf.extend_from_slice(fpr.as_bytes());
+            buf.extend_from_slice(&(secret.len() as u16).to_be_bytes());
+            buf.extend_from_slice(secret);
+        }
+        buf
+    }
+
+    /// Parses the compact binary representation.
+    ///
+    /// See [`PaperBackup::to_bytes`].
+    pub fn from_bytes(mut data: &[u8]) -> Result<Self> {
+        let version = take(&mut data, 1)?[0];
+        if version != FORMAT_VERSION {
+            return Err(Error::MalformedMessage(format!(
+                "Unsupported secret key backup version {}", version)).into());
+        }
+
+        let mut keys = Vec::new();
+        while ! data.is_empty() {
+            let version = take(&mut data, 1)?[0];
+            if version != 4 {
+                return Err(Error::MalformedMessage(format!(
+                    "Unsupported key version {}", version)).into());
+            }
+            let fpr = Fingerprint::from_bytes(take(&mut data, 20)?);
+            let len = take(&mut data, 2)?;
+            let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+            keys.push((fpr, take(&mut data, len)?.into()));
+        }
+
+        if keys.is_empty() {
+            return Err(Error::MalformedMessage(
+                "Secret key backup is empty".into()).into());
+        }
+
+        Ok(PaperBackup { keys })
+    }
+
+    /// Returns the printable text representation.
+    ///
+    /// The text starts with comments, which are prefixed with `#`.
+    /// Then, the [binary representation] follows, in numbered lines
+    /// of hexadecimal digits.  Each line ends with the CRC-24 of the
+    /// line's data.  The last line only contains the CRC-24 of all
+    /// of the data.
+    ///
+    ///   [binary representation]: PaperBackup::to_bytes()
+    pub fn to_text(&self) -> String {
+        let data = self.serialize();
+
+        let mut s = String::new();
+        for (fpr, _) in self.keys.iter() {
+            writeln!(s, "# Secret key material for {}", fpr).unwrap();
+        }
+        writeln!(s, "# To restore the secret keys, the certificate is needed.")
+            .unwrap();
+
+        let mut line = 0;
+        for chunk in data.chunks(BYTES_PER_LINE) {
+            line += 1;
+            write!(s, "{:3}: ", line).unwrap();
+            for b in chunk {
+                write!(s, "{:02X} ", b).unwrap();
+            }
+            writeln!(s, "{:06X}", Crc::new().update(chunk).finalize())
+                .unwrap();
+        }
+        writeln!(s, "{:3}: {:06X}", line + 1,
+                 Crc::new().update(&data).finalize()).unwrap();
+        s
+    }
+
+    /// Parses the printable text representation.
+    ///
+    /// See [`PaperBackup::to_text`].  Empty lines and comments are
+    /// ignored.  If a line's checksum doesn't match, the error names
+    /// the line.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let malformed = |line: usize, msg: &str| -> anyhow::Error {
+            Error::MalformedMessage(format!("Line {}: {}", line, msg)).into()
+        };
+
+        let mut data = Vec::new();
+        let mut expected_line = 1;
+        let mut crc = None;
+        for l in text.lines().map(str::trim)
+            .filter(|l| ! l.is_empty() && ! l.starts_with('#'))
+        {
+            let (number, rest) = l.split_once(':')
+                .ok_or_else(|| malformed(expected_line, "missing line number"))?;
+            if number.trim().parse::<usize>().ok() != Some(expected_line) {
+                return Err(malformed(expected_line, "unexpected line number"));
+            }
+            if crc.is_some() {
+                return Err(malformed(expected_line, "data after checksum"));
+            }
+
+            let mut tokens = rest.split_whitespace().collect::<Vec<_>>();
+            let sum = tokens.pop()
+                .filter(|t| t.len() == 6)
+                .and_then(|t| u32::from_str_radix(t, 16).ok())
+                .ok_or_else(|| malformed(expected_line, "missing checksum"))?;
+            let bytes = tokens.iter()
+                .map(|t| if t.len() == 2 {
+                    u8::from_str_radix(t, 16).ok()
+                } else {
+                    None
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| malformed(expected_line, "invalid hex digits"))?;
+
+            if bytes.is_empty() {
+                // The last line contains the checksum over all data.
+                crc = Some(sum);
+            } else if Crc::new().update(&bytes).finalize() != sum {
+                return Err(malformed(expected_line, "checksum mismatch"));
+            } else {
+                data.extend_from_slice(&bytes);
+            }
+            expected_line += 1;
+        }
+
+        match crc {
+            Some(crc) if Crc::new().update(&data).finalize() == crc =>
+                Self::from_bytes(&data),
+            Some(_) => Err(malformed(expected_line - 1, "checksum mismatch")),
+            None => Err(malformed(expected_line, "missing final checksum")),
+        }
+    }
+}
+
+/// Checks that `key`'s secret key material matches its public key
+/// material.
+///
+/// Secret key material that is encrypted, or uses an unsupported
+/// algorithm, cannot be checked.
+fn check_secret<R>(key: &Key<key::SecretParts, R>) -> Result<()>
+    where R: key::KeyRole,
+{
+    if key.secret().is_encrypted() || ! key.pk_algo().is_supported() {
+        return Ok(());
+    }
+
+    let mismatch = || -> anyhow::Error {
+        Error::InvalidArgument(format!(
+            "Secret key material of {} doesn't match the public key",
+            key.fingerprint())).into()
+    };
+
+    let mut pair = key.clone().into_keypair()?;
+    if key.pk_algo().for_encryption() {
+        let sk = SessionKey::new(32);
+        let ciphertext = key.encrypt(&sk)?;
+        match pair.decrypt(&ciphertext, Some(sk.len())) {
+            Ok(plaintext) if plaintext == sk => Ok(()),
+            _ => Err(mismatch()),
+        }
+    } else {
+        let digest = [0x42; 64];
+        pair.sign(HashAlgorithm::SHA512, &digest)
+            .and_then(|sig| key.verify(&sig, HashAlgorithm::SHA512, &digest))
+            .map_err(|_| mismatch())
+    }
+}
+
+/// Splits `n` bytes off the front of `data`.
+fn take<'a>(data: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if data.len() < n {
+        return Err(Error::MalformedMessage(
+            "Truncated secret key backup".into()).into());
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cert::prelude::*;
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let (tsk, _) = CertBuilder::general_purpose(None, Some("alice"))
+            .set_password(Some("streng geheim".into()))
+            .generate()?;
+        let cert = tsk.clone().strip_secret_key_material();
+
+        let backup = PaperBackup::from_cert(&tsk)?;
+        assert_eq!(backup.fingerprints().count(), 3);
+        assert!(PaperBackup::from_cert(&cert).is_err());
+
+        let b = PaperBackup::from_bytes(&backup.to_bytes()?)?;
+        assert_eq!(b, backup);
+        let b = PaperBackup::from_text(&backup.to_text())?;
+        assert_eq!(b, backup);
+
+        let restored = backup.restore(cert)?;
+        assert_eq!(restored.as_tsk().to_vec()?, tsk.as_tsk().to_vec()?);
+
+        // The backup doesn't match another certificate.
+        let (other, _) = CertBuilder::new().generate()?;
+        assert!(backup.restore(other).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn mismatched_secret() -> Result<()> {
+        for cs in [CipherSuite::Cv25519, CipherSuite::RSA2k] {
+            let (alice, _) = CertBuilder::general_purpose(cs, Some("alice"))
+                .generate()?;
+            let (bob, _) = CertBuilder::general_purpose(cs, Some("bob"))
+                .generate()?;
+
+            // Graft Bob's secret key material onto Alice's keys.
+            let backup = PaperBackup::from_cert(&alice)?;
+            let bob_backup = PaperBackup::from_cert(&bob)?;
+            assert_eq!(backup.keys.len(), bob_backup.keys.len());
+            for i in 0..backup.keys.len() {
+                let mut forged = backup.clone();
+                forged.keys[i].1 = bob_backup.keys[i].1.clone();
+                assert!(forged.restore(alice.clone().strip_secret_key_material())
+                        .is_err());
+            }
+
+            assert!(backup.restore(alice.clone().strip_secret_key_material())
+                    .is_ok());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn typos() -> Result<()> {
+        let (tsk, _) = CertBuilder::new().generate()?;
+        let text = PaperBackup::from_cert(&tsk)?.to_text();
+
+        // Change a digit in the second line.
+        let mut lines = text.lines().map(String::from).collect::<Vec<_>>();
+        let i = lines.iter().position(|l| l.trim_start().starts_with("2:"))
+            .unwrap();
+        let digit = if lines[i].as_bytes()[5] == b'0' { "1" } else { "0" };
+        lines[i].replace_range(5..6, digit);
+        let err = PaperBackup::from_text(&lines.join("\n")).unwrap_err();
+        assert!(err.to_string().contains("Line 2"), "{}", err);
+
+        // Drop the last line.
+        let truncated = text.lines().take(text.lines().count() - 2)
+            .collect::<Vec<_>>().join("\n");
+        assert!(PaperBackup::from_text(&truncated).is_err());
+        Ok(())
+    }
+}
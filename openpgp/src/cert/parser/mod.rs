@@ -15,11 +15,14 @@ use crate::{
         Cookie,
         Parse,
         PacketParserResult,
-        PacketParser
+        PacketParser,
+        diagnostics::Diagnostic,
     },
     Result,
     cert::bundle::ComponentBundle,
+    cert::CertificationLimits,
     Cert,
+    packet::Signature,
 };
 
 mod low_level;
@@ -503,6 +506,10 @@ pub struct CertParser<'a> {
     packets: Vec<Packet>,
     queued_error: Option<anyhow::Error>,
     filter: Vec<Box<dyn Send + Sync + Fn(&Cert, bool) -> bool + 'a>>,
+    collect_diagnostics: bool,
+    diagnostics: Vec<Diagnostic>,
+    certification_limits: CertificationLimits,
+    excess_certifications: Vec<Signature>,
 }
 assert_send_and_sync!(CertParser<'_>);
 
@@ -760,6 +767,86 @@ impl<'a> CertParser<'a> {
         self
     }
 
+    /// Controls whether diagnostics are collected.
+    ///
+    /// If enabled, the `CertParser` records observations about the
+    /// packets of each certificate, like the use of weak algorithms.
+    /// They can be retrieved using [`CertParser::diagnostics`].  By
+    /// default, diagnostics are not collected.
+    ///
+    /// See the [`diagnostics`] module for details.
+    ///
+    ///   [`diagnostics`]: crate::parse::diagnostics
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// # use openpgp::parse::Parse;
+    /// # use openpgp::serialize::SerializeInto;
+    /// use openpgp::cert::prelude::*;
+    ///
+    /// # fn main() -> Result<()> {
+    /// # let (cert, _) = CertBuilder::new().generate()?;
+    /// # let keyring = cert.to_vec()?;
+    /// let mut parser = CertParser::from_bytes(&keyring)?
+    ///     .collect_diagnostics(true);
+    /// while let Some(certr) = parser.next() {
+    ///     let cert = certr?;
+    ///     for d in parser.diagnostics() {
+    ///         eprintln!("{}: {}", cert.fingerprint(), d);
+    ///     }
+    /// }
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn collect_diagnostics(mut self, enable: bool) -> Self {
+        self.collect_diagnostics = enable;
+        self
+    }
+
+    /// Returns the diagnostics for the most recently returned
+    /// certificate.
+    ///
+    /// The diagnostics' [paths] are the indices of the packets in the
+    /// certificate's packet sequence.  This is empty unless enabled
+    /// using [`CertParser::collect_diagnostics`].
+    ///
+    ///   [paths]: crate::parse::diagnostics::Diagnostic::path()
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Limits the number of third-party certifications kept per
+    /// component.
+    ///
+    /// This mitigates certificate flooding.  The limits are applied
+    /// when canonicalizing the certificates.  The certifications
+    /// exceeding the limits are dropped from the returned
+    /// certificates, and can be retrieved using
+    /// [`CertParser::excess_certifications`].  By default, there are
+    /// no limits.  Use [`Policy::certification_limits`] to get the
+    /// limits configured by a policy.  See [`CertificationLimits`]
+    /// for details and an example.
+    ///
+    ///   [`Policy::certification_limits`]: crate::policy::Policy::certification_limits()
+    pub fn certification_limits(mut self, limits: CertificationLimits)
+                                -> Self
+    {
+        self.certification_limits = limits;
+        self
+    }
+
+    /// Returns the third-party certifications that were dropped from
+    /// the most recently returned certificate.
+    ///
+    /// This is empty unless limits are set using
+    /// [`CertParser::certification_limits`].
+    pub fn excess_certifications(&self) -> &[Signature] {
+        &self.excess_certifications
+    }
+
     // Parses the next packet in the packet stream.
     //
     // If we complete parsing a Cert, returns the Cert.  Otherwise,
@@ -801,10 +888,13 @@ impl<'a> CertParser<'a> {
     //
     // Returns the old state.  Note: the packet iterator is preserved.
     fn reset(&mut self) -> Self {
-        // We need to preserve `source` and `filter`.
+        // We need to preserve `source`, `filter`, the diagnostics
+        // setting, and the certification limits.
         let mut orig = mem::take(self);
         self.source = orig.source.take();
         mem::swap(&mut self.filter, &mut orig.filter);
+        self.collect_diagnostics = orig.collect_diagnostics;
+        self.certification_limits = orig.certification_limits;
         orig
     }
 
@@ -823,6 +913,12 @@ impl<'a> CertParser<'a> {
         let n_packets = orig.packets.len();
         t!("Finalizing certificate with {} packets", n_packets);
 
+        if self.collect_diagnostics {
+            self.diagnostics = orig.packets.iter().enumerate()
+                .flat_map(|(i, p)| Diagnostic::for_packet(p, &[i]))
+                .collect();
+        }
+
         // Convert to tokens, but preserve packets if it fails.
         let mut failed = false;
         let mut packets: Vec<Packet> = Vec::with_capacity(0);
@@ -898,7 +994,10 @@ impl<'a> CertParser<'a> {
                 split_sigs(&primary_fp, b);
             }
 
-            let cert = cert.canonicalize();
+            let mut excess = Vec::new();
+            let cert = cert.canonicalize_with_limits(
+                &self.certification_limits, &mut excess);
+            self.excess_certifications = excess;
 
             // Make sure it is still wanted.
             for filter in &self.filter {
@@ -1894,4 +1993,102 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn diagnostics() -> Result<()> {
+        use crate::parse::diagnostics::Kind;
+        use crate::types::PublicKeyAlgorithm;
+
+        let mut keyring = Vec::new();
+        keyring.extend_from_slice(
+            crate::tests::key("pgp5-dsa-elg-v3-subkey-binding.pgp"));
+        keyring.extend_from_slice(crate::tests::key("testy-new.pgp"));
+
+        let mut parser = CertParser::from_bytes(&keyring)?
+            .collect_diagnostics(true);
+        parser.next().expect("a cert")?;
+        assert!(parser.diagnostics().iter().any(|d| d.path() == [0]
+            && d.tag() == Tag::PublicKey
+            && d.kind() == &Kind::WeakKey {
+                algo: PublicKeyAlgorithm::DSA,
+                bits: 768,
+            }));
+        parser.next().expect("a cert")?;
+        assert!(parser.diagnostics().is_empty());
+        assert!(parser.next().is_none());
+
+        // Disabled by default.
+        let mut parser = CertParser::from_bytes(&keyring)?;
+        parser.next().expect("a cert")?;
+        assert!(parser.diagnostics().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn certification_limits() -> Result<()> {
+        use std::time::{Duration, SystemTime};
+        use crate::policy::Policy;
+        use crate::types::SignatureType;
+
+        let t0 = SystemTime::now() - Duration::from_secs(3600);
+        let (alice, _) = CertBuilder::new()
+            .set_creation_time(t0)
+            .add_userid("Alice")
+            .generate()?;
+        let userid = alice.userids().next().unwrap().userid().clone();
+
+        // Five issuers each certify the user ID twice.
+        let mut certifications = Vec::new();
+        for i in 0..5 {
+            let (issuer, _) = CertBuilder::new()
+                .set_creation_time(t0)
+                .generate()?;
+            let mut signer = issuer.primary_key().key().clone()
+                .parts_into_secret()?.into_keypair()?;
+            for j in 0..2 {
+                certifications.push(userid.certify(
+                    &mut signer, &alice, SignatureType::GenericCertification,
+                    None, t0 + Duration::from_secs(10 * i + j))?);
+            }
+        }
+        let newest: Vec<_> = certifications.iter().rev().step_by(2)
+            .take(3).cloned().collect();
+        let flooded = alice.clone().insert_packets(certifications)?;
+
+        let mut keyring = Vec::new();
+        flooded.serialize(&mut keyring)?;
+        alice.serialize(&mut keyring)?;
+
+        let limits = CertificationLimits::default()
+            .set_per_issuer(Some(1))
+            .set_per_component(Some(3));
+        let mut policy = crate::policy::StandardPolicy::new();
+        policy.set_certification_limits(limits);
+        let mut parser = CertParser::from_bytes(&keyring)?
+            .certification_limits(policy.certification_limits());
+        let cert = parser.next().expect("a cert")?;
+        let kept: Vec<_> = cert.userids().next().unwrap()
+            .certifications().cloned().collect();
+        assert_eq!(kept, newest);
+        assert_eq!(parser.excess_certifications().len(), 7);
+        let cert = parser.next().expect("a cert")?;
+        assert_eq!(cert, alice);
+        assert!(parser.excess_certifications().is_empty());
+        assert!(parser.next().is_none());
+
+        // Applying the limits to the parsed certificate is the same.
+        let (cert, excess) = flooded.limit_certifications(&limits);
+        assert_eq!(cert.userids().next().unwrap()
+                   .certifications().cloned().collect::<Vec<_>>(),
+                   kept);
+        assert_eq!(excess.len(), 7);
+
+        // Unlimited by default.
+        let mut parser = CertParser::from_bytes(&keyring)?;
+        let cert = parser.next().expect("a cert")?;
+        assert_eq!(cert.userids().next().unwrap().certifications().count(),
+                   10);
+        assert!(parser.excess_certifications().is_empty());
+        Ok(())
+    }
 }
@@ -18,6 +18,7 @@ pub use crate::cert::{
     CertBuilder,
     CertParser,
     CertRevocationBuilder,
+    CertificationRevocationBuilder,
     CipherSuite,
     KeyBuilder,
     SubkeyBuilder,
@@ -21,6 +21,18 @@
 //!
 //! [`Cert::from`]: From<RawCert>
 //!
+//! When parsing a byte string that is not ASCII armored, the
+//! [`RawCert`]s borrow the data instead of copying it.  The
+//! [`RawPacket`]s returned by [`RawCert::packets`] and the
+//! [`RawSignature`]s returned by [`RawPacket::signature`] are in turn
+//! slices into the certificate's data.  For instance, a User ID's
+//! value is just the packet's [body].  This makes it possible to
+//! hold many certificates in memory without the overhead of the
+//! owned [`Packet`] representation, and to convert just the relevant
+//! ones on demand.
+//!
+//! [body]: RawPacket::body()
+//!
 //! # Examples
 //!
 //! Search for a specific certificate in a keyring:
@@ -81,15 +93,20 @@ use crate::cert::Cert;
 use crate::packet::Header;
 use crate::packet::Key;
 use crate::packet::Packet;
+use crate::packet::Signature;
 use crate::packet::Tag;
 use crate::packet::UserID;
 use crate::packet::header::BodyLength;
 use crate::packet::header::CTB;
 use crate::packet::key;
 use crate::parse::Cookie;
+use crate::parse::Dearmor;
 use crate::parse::PacketParser;
 use crate::parse::Parse;
 use crate::parse::RECOVERY_THRESHOLD;
+use crate::types::HashAlgorithm;
+use crate::types::PublicKeyAlgorithm;
+use crate::types::SignatureType;
 
 use super::TRACE;
 
@@ -178,14 +195,31 @@ impl<'a> RawPacket<'a> {
     }
 
     /// Returns the packet's bytes.
-    pub fn as_bytes(&self) -> &[u8] {
+    pub fn as_bytes(&self) -> &'a [u8] {
         self.data
     }
 
     /// Return the packet's body without the OpenPGP framing.
-    pub fn body(&self) -> &[u8] {
+    pub fn body(&self) -> &'a [u8] {
         &self.data[self.header_len..]
     }
+
+    /// Returns a view of the signature packet.
+    ///
+    /// This only looks at the signature's framing; the returned
+    /// [`RawSignature`] borrows the subpacket areas and the MPIs
+    /// from the packet's data.
+    ///
+    /// Returns an error if the packet is not a version 4 signature
+    /// packet, or if it is truncated.
+    pub fn signature(&self) -> Result<RawSignature<'a>> {
+        if self.tag != Tag::Signature {
+            return Err(crate::Error::InvalidOperation(
+                format!("Not a signature packet: {}", self.tag)).into());
+        }
+
+        RawSignature::new(self.body())
+    }
 }
 
 impl<'a> TryFrom<RawPacket<'a>> for Packet {
@@ -204,6 +238,187 @@ impl<'a> crate::serialize::Marshal for RawPacket<'a> {
     }
 }
 
+/// A mostly unparsed `Signature`.
+///
+/// This is returned by [`RawPacket::signature`].
+///
+/// A `RawSignature` only decodes the signature's fixed fields.  The
+/// subpacket areas and the MPIs are slices into the packet's data,
+/// which is not copied.  It can be converted to a [`Signature`]
+/// using `TryFrom`.
+///
+/// Note: the signature is not verified, and the subpacket areas are
+/// not parsed.  As such, this can only be used as part of a precheck.
+///
+/// [`Signature`]: crate::packet::Signature
+///
+/// # Examples
+///
+/// ```rust
+/// use sequoia_openpgp as openpgp;
+/// # use openpgp::Result;
+/// # use openpgp::cert::prelude::*;
+/// use openpgp::cert::raw::RawCert;
+/// use openpgp::packet::{Signature, Tag};
+/// use openpgp::parse::Parse;
+/// # use openpgp::serialize::Serialize;
+/// use openpgp::types::SignatureType;
+/// #
+/// # fn main() -> Result<()> {
+/// #      let (cert, _) = CertBuilder::new()
+/// #          .add_userid("Alice")
+/// #          .generate()?;
+/// #
+/// #      let mut bytes = Vec::new();
+/// #      cert.serialize(&mut bytes);
+/// # let mut count = 0;
+///
+/// let rawcert = RawCert::from_bytes(&bytes)?;
+/// for p in rawcert.packets().filter(|p| p.tag() == Tag::Signature) {
+///     let sig = p.signature()?;
+///     if sig.typ() == SignatureType::PositiveCertification {
+///         // Only parse the signatures we are interested in.
+///         let sig = Signature::try_from(sig)?;
+/// #       count += 1;
+///     }
+/// }
+/// # assert_eq!(count, 1);
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct RawSignature<'a> {
+    body: &'a [u8],
+    typ: SignatureType,
+    pk_algo: PublicKeyAlgorithm,
+    hash_algo: HashAlgorithm,
+    hashed_area: &'a [u8],
+    unhashed_area: &'a [u8],
+    digest_prefix: [u8; 2],
+    mpis: &'a [u8],
+}
+assert_send_and_sync!(RawSignature<'_>);
+
+impl fmt::Debug for RawSignature<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawSignature")
+            .field("typ", &self.typ)
+            .field("pk_algo", &self.pk_algo)
+            .field("hash_algo", &self.hash_algo)
+            .field("hashed_area (bytes)", &self.hashed_area.len())
+            .field("unhashed_area (bytes)", &self.unhashed_area.len())
+            .field("digest_prefix",
+                   &crate::fmt::to_hex(&self.digest_prefix, false))
+            .field("mpis (bytes)", &self.mpis.len())
+            .finish()
+    }
+}
+
+impl<'a> RawSignature<'a> {
+    /// Decodes the framing of the version 4 signature packet `body`.
+    fn new(body: &'a [u8]) -> Result<Self> {
+        let truncated = || -> anyhow::Error {
+            crate::Error::MalformedPacket(
+                "Truncated signature packet".into()).into()
+        };
+
+        // Splits off a subpacket area prefixed by its length.
+        let area = |data: &'a [u8]| -> Result<(&'a [u8], &'a [u8])> {
+            if data.len() < 2 {
+                return Err(truncated());
+            }
+            let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+            if data.len() < 2 + len {
+                return Err(truncated());
+            }
+            Ok((&data[2..2 + len], &data[2 + len..]))
+        };
+
+        match body.first() {
+            Some(4) => (),
+            Some(v) => return Err(crate::Error::MalformedPacket(
+                format!("Unsupported signature version {}", v)).into()),
+            None => return Err(truncated()),
+        }
+        if body.len() < 4 {
+            return Err(truncated());
+        }
+
+        let (hashed_area, rest) = area(&body[4..])?;
+        let (unhashed_area, rest) = area(rest)?;
+        if rest.len() < 2 {
+            return Err(truncated());
+        }
+
+        Ok(RawSignature {
+            body,
+            typ: body[1].into(),
+            pk_algo: body[2].into(),
+            hash_algo: body[3].into(),
+            hashed_area,
+            unhashed_area,
+            digest_prefix: [rest[0], rest[1]],
+            mpis: &rest[2..],
+        })
+    }
+
+    /// Returns the signature packet's body.
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// Returns the signature's version.
+    ///
+    /// Currently, only version 4 signatures are supported.
+    pub fn version(&self) -> u8 {
+        4
+    }
+
+    /// Returns the signature's type.
+    pub fn typ(&self) -> SignatureType {
+        self.typ
+    }
+
+    /// Returns the signature's public key algorithm.
+    pub fn pk_algo(&self) -> PublicKeyAlgorithm {
+        self.pk_algo
+    }
+
+    /// Returns the signature's hash algorithm.
+    pub fn hash_algo(&self) -> HashAlgorithm {
+        self.hash_algo
+    }
+
+    /// Returns the hashed subpacket area, without its length prefix.
+    pub fn hashed_area(&self) -> &'a [u8] {
+        self.hashed_area
+    }
+
+    /// Returns the unhashed subpacket area, without its length
+    /// prefix.
+    pub fn unhashed_area(&self) -> &'a [u8] {
+        self.unhashed_area
+    }
+
+    /// Returns the first two bytes of the signature's digest.
+    pub fn digest_prefix(&self) -> &[u8; 2] {
+        &self.digest_prefix
+    }
+
+    /// Returns the encoded MPIs.
+    pub fn mpis(&self) -> &'a [u8] {
+        self.mpis
+    }
+}
+
+impl<'a> TryFrom<RawSignature<'a>> for Signature {
+    type Error = anyhow::Error;
+
+    fn try_from(s: RawSignature<'a>) -> Result<Self> {
+        Signature::from_bytes(s.body())
+    }
+}
+
 /// A mostly unparsed `Cert`.
 ///
 /// This data structure contains the unparsed packets for a
@@ -456,7 +671,33 @@ impl<'a> Parse<'a, RawCert<'a>> for RawCert<'a> {
     where
         R: BufferedReader<Cookie> + 'a
     {
-        let mut parser = RawCertParser::from_buffered_reader(reader)?;
+        Self::from_parser(RawCertParser::from_buffered_reader(reader)?)
+    }
+
+    /// Returns the first RawCert encountered in the reader.
+    ///
+    /// Returns an error if there are multiple certificates.
+    fn from_reader<R: 'a + Read + Send + Sync>(reader: R) -> Result<Self> {
+        let br = Generic::with_cookie(reader, None, Cookie::default());
+        Self::from_buffered_reader(br)
+    }
+
+    /// Returns the RawCert in the byte string.
+    ///
+    /// Returns an error if there are multiple certificates.
+    ///
+    /// Unless the data is ASCII armored, the returned `RawCert`
+    /// borrows the data; it is not copied.
+    fn from_bytes<D: AsRef<[u8]> + ?Sized + Send + Sync>(data: &'a D)
+                                                         -> Result<Self>
+    {
+        Self::from_parser(RawCertParser::from_bytes(data)?)
+    }
+}
+
+impl<'a> RawCert<'a> {
+    /// Returns the only certificate returned by `parser`.
+    fn from_parser(mut parser: RawCertParser<'a>) -> Result<Self> {
         if let Some(cert_result) = parser.next() {
             if parser.next().is_some() {
                 Err(crate::Error::MalformedCert(
@@ -469,14 +710,6 @@ impl<'a> Parse<'a, RawCert<'a>> for RawCert<'a> {
             Err(crate::Error::MalformedCert("No data".into()).into())
         }
     }
-
-    /// Returns the first RawCert encountered in the reader.
-    ///
-    /// Returns an error if there are multiple certificates.
-    fn from_reader<R: 'a + Read + Send + Sync>(reader: R) -> Result<Self> {
-        let br = Generic::with_cookie(reader, None, Cookie::default());
-        Self::from_buffered_reader(br)
-    }
 }
 
 impl<'a> crate::seal::Sealed for RawCert<'a> {}
@@ -578,6 +811,10 @@ pub struct RawCertParser<'a>
     // The total number of bytes read.
     bytes_read: usize,
 
+    // The offset of the last certificate that was returned.
+    #[cfg(feature = "keyring-index")]
+    cert_offset: usize,
+
     // Any pending error.
     pending_error: Option<anyhow::Error>,
 
@@ -590,73 +827,60 @@ impl<'a> RawCertParser<'a> {
     fn new<R>(reader: R) -> Result<Self>
     where R: 'a + BufferedReader<Cookie>
     {
+        // Strip the ASCII armor, if any.
+        let (reader, dearmor) = Dearmor::Auto(armor::ReaderMode::Tolerant(None))
+            .apply(reader.into_boxed(), Default::default(), false)?;
+
         // Check that we can read the first header and that it is
         // reasonable.  Note: an empty keyring is not an error; we're
-        // just checking for bad data here.  If not, try again after
-        // dearmoring the input.
-        let mut dearmor = false;
+        // just checking for bad data here.  But, if we dearmored the
+        // input, we expect at least one packet.
         let mut dup = Dup::with_cookie(reader, Default::default());
-        if ! dup.eof() {
-            match Header::parse(&mut dup) {
-                Ok(header) => {
-                    let tag = header.ctb().tag();
-                    if matches!(tag, Tag::Unknown(_) | Tag::Private(_)) {
-                        return Err(crate::Error::MalformedCert(
-                            format!("A certificate must start with a \
-                                     public key or a secret key packet, \
-                                     got a {}",
-                                    tag))
-                                   .into());
-                    }
-                }
-                Err(_err) => {
-                    // We failed to read a header.  Try to dearmor the
-                    // input.
-                    dearmor = true;
-                }
+        if dearmor || ! dup.eof() {
+            let header = Header::parse(&mut dup)?;
+            let tag = header.ctb().tag();
+            if matches!(tag, Tag::Unknown(_) | Tag::Private(_)) {
+                return Err(crate::Error::MalformedCert(
+                    format!("A certificate must start with a \
+                             public key or a secret key packet, \
+                             got a {}",
+                            tag))
+                           .into());
             }
         }
 
         // Strip the Dup reader.
-        let mut reader = dup.into_boxed().into_inner().expect("inner");
-
-        if dearmor {
-            reader = armor::Reader::from_cookie_reader(
-                reader, armor::ReaderMode::Tolerant(None),
-                Default::default()).into_boxed();
-
-            let mut dup = Dup::with_cookie(reader, Default::default());
-            match Header::parse(&mut dup) {
-                Ok(header) => {
-                    let tag = header.ctb().tag();
-                    if matches!(tag, Tag::Unknown(_) | Tag::Private(_)) {
-                        return Err(crate::Error::MalformedCert(
-                            format!("A certificate must start with a \
-                                     public key or a secret key packet, \
-                                     got a {}",
-                                    tag))
-                                   .into());
-                    }
-                }
-                Err(err) => {
-                    return Err(err);
-                }
-            }
-
-            reader = dup.into_boxed().into_inner().expect("inner");
-        }
+        let reader = dup.into_boxed().into_inner().expect("inner");
 
         Ok(RawCertParser {
             slice: None,
             reader,
             dearmor,
             bytes_read: 0,
+            #[cfg(feature = "keyring-index")]
+            cert_offset: 0,
             pending_error: None,
             done: false,
         })
     }
 }
 
+impl<'a> RawCertParser<'a> {
+    /// Returns the offset of the last certificate that was returned.
+    ///
+    /// The offset is relative to the start of the input.  Returns
+    /// `None` if the input is being dearmored, because then the
+    /// offset doesn't correspond to a position in the input.
+    #[cfg(feature = "keyring-index")]
+    pub(crate) fn cert_offset(&self) -> Option<usize> {
+        if self.dearmor {
+            None
+        } else {
+            Some(self.cert_offset)
+        }
+    }
+}
+
 impl<'a> Parse<'a, RawCertParser<'a>> for RawCertParser<'a>
 {
     /// Initializes a `RawCertParser` from a `BufferedReader`.
@@ -962,6 +1186,10 @@ impl<'a> Iterator for RawCertParser<'a>
 
         assert!(cert_start <= cert_end);
         assert!(cert_end <= processed);
+        #[cfg(feature = "keyring-index")]
+        {
+            self.cert_offset = self.bytes_read + cert_start;
+        }
         self.bytes_read += processed;
 
         // Strip the buffered_reader::Dup.
@@ -1670,4 +1898,73 @@ mod test {
         assert!(certs.iter().all(|c| c.is_ok()));
         Ok(())
     }
+
+    #[test]
+    fn armored_cert_with_bom() -> Result<()> {
+        for prefix in [&b"\xef\xbb\xbf"[..], b"\xef\xbb\xbf\n"] {
+            let mut data = prefix.to_vec();
+            data.extend_from_slice(crate::tests::key("testy.asc"));
+
+            let certs = RawCertParser::from_bytes(&data)?
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(certs.len(), 1);
+            assert_eq!(Cert::try_from(&certs[0])?,
+                       Cert::from_bytes(&data)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn raw_signature() -> Result<()> {
+        use crate::serialize::MarshalInto;
+
+        let bytes = crate::tests::key("neal.pgp");
+        let pile = crate::PacketPile::from_bytes(bytes)?;
+        let raw = RawCert::from_bytes(bytes)?;
+
+        let sigs = pile.into_children()
+            .filter_map(|p| match p {
+                Packet::Signature(s) => Some(s),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let raw_sigs = raw.packets()
+            .filter(|p| p.tag() == Tag::Signature)
+            .map(|p| p.signature())
+            .collect::<Result<Vec<_>>>()?;
+        assert!(! sigs.is_empty());
+        assert_eq!(sigs.len(), raw_sigs.len());
+
+        for raw_sig in raw_sigs {
+            // The views borrow from the input.
+            let range = bytes.as_ptr_range();
+            assert!(range.contains(&raw_sig.body().as_ptr()));
+            assert!(range.contains(&raw_sig.mpis().as_ptr()));
+
+            let owned = Signature::try_from(raw_sig.clone())?;
+            assert!(sigs.contains(&owned));
+            assert_eq!(raw_sig.version(), owned.version());
+            assert_eq!(raw_sig.typ(), owned.typ());
+            assert_eq!(raw_sig.pk_algo(), owned.pk_algo());
+            assert_eq!(raw_sig.hash_algo(), owned.hash_algo());
+            assert_eq!(raw_sig.hashed_area(),
+                       &owned.hashed_area().to_vec()?[..]);
+            assert_eq!(raw_sig.unhashed_area(),
+                       &owned.unhashed_area().to_vec()?[..]);
+            assert_eq!(raw_sig.digest_prefix(), owned.digest_prefix());
+            assert_eq!(raw_sig.mpis(), &owned.mpis().to_vec()?[..]);
+        }
+
+        // Other packets are not signatures.
+        assert!(raw.packet(0).unwrap().signature().is_err());
+
+        // Truncated signatures are detected.
+        let sig = raw.packets().find(|p| p.tag() == Tag::Signature).unwrap();
+        let body = sig.body();
+        let mpis = sig.signature()?.mpis().len();
+        for len in [0, 3, 5, body.len() - mpis - 1] {
+            assert!(RawSignature::new(&body[..len]).is_err());
+        }
+        Ok(())
+    }
 }
@@ -988,6 +988,256 @@ impl TryFrom<signature::SignatureBuilder> for UserIDRevocationBuilder {
     }
 }
 
+/// A builder for revocations of third-party certifications.
+///
+/// When Alice certifies a User ID on Bob's certificate, she may later
+/// want to withdraw that certification, for instance, because she no
+/// longer believes that the binding is correct.  She does this by
+/// issuing a [certification revocation] over Bob's certificate and
+/// User ID using her own key.  The result is a third-party
+/// revocation: it doesn't revoke Bob's User ID, it only revokes
+/// Alice's earlier certifications of it.
+///
+/// Such a revocation is structurally identical to a User ID
+/// revocation issued by a designated revoker, which is what
+/// [`UserIDRevocationBuilder`] is for.  This builder differs in that
+/// it refuses to create a self-revocation: to revoke one's own User
+/// ID, use [`UserIDRevocationBuilder`].
+///
+/// When the revocation is merged into Bob's certificate, it is
+/// attached to the User ID as a third-party revocation (see
+/// [`UserIDAmalgamation::other_revocations`]).  It can be looked up
+/// using [`UserIDAmalgamation::valid_third_party_revocations_by_key`].
+///
+/// [certification revocation]: https://www.rfc-editor.org/rfc/rfc9580.html#name-certification-revocation-si
+/// [`UserIDAmalgamation::other_revocations`]: crate::cert::amalgamation::ComponentAmalgamation::other_revocations()
+/// [`UserIDAmalgamation::valid_third_party_revocations_by_key`]: crate::cert::amalgamation::UserIDAmalgamation::valid_third_party_revocations_by_key()
+///
+/// # Examples
+///
+/// Alice withdraws her certification of Bob's User ID:
+///
+/// ```rust
+/// use sequoia_openpgp as openpgp;
+/// # use openpgp::Result;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::policy::StandardPolicy;
+/// use openpgp::types::RevocationStatus;
+///
+/// # fn main() -> Result<()> {
+/// let p = &StandardPolicy::new();
+///
+/// # let (alice, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+/// #     .generate()?;
+/// # let (bob, _) = CertBuilder::general_purpose(None, Some("bob@example.org"))
+/// #     .generate()?;
+/// let mut alice_signer = alice.primary_key().key().clone()
+///     .parts_into_secret()?.into_keypair()?;
+///
+/// // Alice certifies Bob's User ID...
+/// let userid = bob.userids().next().unwrap().userid().clone();
+/// let certification = userid.certify(&mut alice_signer, &bob, None,
+///                                    None, None)?;
+/// let bob = bob.insert_packets(certification)?;
+///
+/// // ... and later changes her mind.
+/// let revocation = CertificationRevocationBuilder::new()
+///     .build(&mut alice_signer, &bob, &userid, None)?;
+/// let bob = bob.insert_packets(revocation.clone())?;
+///
+/// let ua = bob.userids().next().unwrap();
+/// assert_eq!(ua.other_revocations().collect::<Vec<_>>(),
+///            vec![&revocation]);
+/// assert_eq!(ua.valid_third_party_revocations_by_key(
+///                p, None, alice.primary_key().key()).count(),
+///            1);
+///
+/// // Bob's User ID is not revoked.  Since Alice is not one of Bob's
+/// // designated revokers, the revocation only means that Alice's
+/// // certifications of Bob's User ID are revoked.
+/// assert!(matches!(ua.revocation_status(p, None),
+///                  RevocationStatus::CouldBe(_)));
+/// # Ok(()) }
+/// ```
+pub struct CertificationRevocationBuilder {
+    builder: signature::SignatureBuilder,
+}
+assert_send_and_sync!(CertificationRevocationBuilder);
+
+impl CertificationRevocationBuilder {
+    /// Returns a new `CertificationRevocationBuilder`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// use openpgp::cert::prelude::*;
+    ///
+    /// # fn main() -> Result<()> {
+    /// let builder = CertificationRevocationBuilder::new();
+    /// # Ok(())
+    /// # }
+    pub fn new() -> Self {
+        Self {
+            builder:
+                signature::SignatureBuilder::new(SignatureType::CertificationRevocation)
+        }
+    }
+
+    /// Sets the reason for revocation.
+    ///
+    /// See [`UserIDRevocationBuilder::set_reason_for_revocation`] for
+    /// further documentation.  If no reason is set, the revocation is
+    /// considered a hard revocation, i.e., it also revokes
+    /// certifications made after it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::types::ReasonForRevocation;
+    ///
+    /// # fn main() -> Result<()> {
+    /// let builder = CertificationRevocationBuilder::new()
+    ///     .set_reason_for_revocation(ReasonForRevocation::UIDRetired,
+    ///                                b"Bob left example.org.");
+    /// # Ok(())
+    /// # }
+    pub fn set_reason_for_revocation(self, code: ReasonForRevocation,
+                                     reason: &[u8])
+        -> Result<Self>
+    {
+        Ok(Self {
+            builder: self.builder.set_reason_for_revocation(code, reason)?
+        })
+    }
+
+    /// Sets the revocation certificate's creation time.
+    ///
+    /// Certifications issued by the same key before this time are
+    /// considered revoked.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// use openpgp::cert::prelude::*;
+    ///
+    /// # fn main() -> Result<()> {
+    /// # let yesterday = std::time::SystemTime::now();
+    /// let builder = CertificationRevocationBuilder::new()
+    ///     .set_signature_creation_time(yesterday);
+    /// # Ok(())
+    /// # }
+    pub fn set_signature_creation_time(self, creation_time: time::SystemTime)
+        -> Result<Self>
+    {
+        Ok(Self {
+            builder: self.builder.set_signature_creation_time(creation_time)?
+        })
+    }
+
+    /// Adds a notation to the revocation certificate.
+    ///
+    /// Unlike the [`CertificationRevocationBuilder::set_notation`]
+    /// method, this function does not first remove any existing
+    /// notation with the specified name.
+    ///
+    /// See [`SignatureBuilder::add_notation`] for further documentation.
+    ///
+    /// [`SignatureBuilder::add_notation`]: crate::packet::signature::SignatureBuilder::add_notation()
+    pub fn add_notation<N, V, F>(self, name: N, value: V, flags: F,
+                                 critical: bool)
+        -> Result<Self>
+    where
+        N: AsRef<str>,
+        V: AsRef<[u8]>,
+        F: Into<Option<NotationDataFlags>>,
+    {
+        Ok(Self {
+            builder: self.builder.add_notation(name, value, flags, critical)?
+        })
+    }
+
+    /// Sets a notation to the revocation certificate.
+    ///
+    /// Unlike the [`CertificationRevocationBuilder::add_notation`]
+    /// method, this function first removes any existing notation with
+    /// the specified name.
+    ///
+    /// See [`SignatureBuilder::set_notation`] for further documentation.
+    ///
+    /// [`SignatureBuilder::set_notation`]: crate::packet::signature::SignatureBuilder::set_notation()
+    pub fn set_notation<N, V, F>(self, name: N, value: V, flags: F,
+                                 critical: bool)
+        -> Result<Self>
+    where
+        N: AsRef<str>,
+        V: AsRef<[u8]>,
+        F: Into<Option<NotationDataFlags>>,
+    {
+        Ok(Self {
+            builder: self.builder.set_notation(name, value, flags, critical)?
+        })
+    }
+
+    /// Returns a signed certification revocation.
+    ///
+    /// A revocation of the certifications that `signer` made over
+    /// `cert` and `userid` is generated and signed using `signer`
+    /// with the specified hash algorithm.  Normally, you should pass
+    /// `None` to select the default hash algorithm.
+    ///
+    /// This returns an error if `signer` is `cert`'s primary key.
+    /// Use [`UserIDRevocationBuilder`] to revoke one's own User ID.
+    pub fn build<H>(mut self, signer: &mut dyn Signer,
+                    cert: &Cert, userid: &UserID,
+                    hash_algo: H)
+        -> Result<Signature>
+        where H: Into<Option<HashAlgorithm>>
+    {
+        if signer.public().fingerprint() == cert.fingerprint() {
+            return Err(crate::Error::InvalidArgument(
+                "Cannot revoke a self-signature using a \
+                 CertificationRevocationBuilder, use a \
+                 UserIDRevocationBuilder instead".into()).into());
+        }
+
+        self.builder = self.builder
+            .set_hash_algo(hash_algo.into().unwrap_or(HashAlgorithm::SHA512));
+
+        userid.bind(signer, cert, self.builder)
+    }
+}
+
+impl Deref for CertificationRevocationBuilder {
+    type Target = signature::SignatureBuilder;
+
+    fn deref(&self) -> &Self::Target {
+        &self.builder
+    }
+}
+
+impl TryFrom<signature::SignatureBuilder> for CertificationRevocationBuilder {
+    type Error = anyhow::Error;
+
+    fn try_from(builder: signature::SignatureBuilder) -> Result<Self> {
+        if builder.typ() != SignatureType::CertificationRevocation {
+            return Err(
+                crate::Error::InvalidArgument(
+                    format!("Expected signature type to be CertificationRevocation but got {}",
+                            builder.typ())).into());
+        }
+        Ok(Self {
+            builder
+        })
+    }
+}
+
 /// A builder for revocation certificates for User Attributes.
 ///
 /// A revocation certificate for a [User Attribute] has three degrees of
@@ -1488,4 +1738,87 @@ mod tests {
         assert!(result.is_err());
         Ok(())
     }
+
+    #[test]
+    fn certification_revocation_builder() -> crate::Result<()> {
+        use std::time::Duration;
+        use crate as openpgp;
+        use openpgp::cert::prelude::*;
+        use openpgp::packet::signature::SignatureBuilder;
+        use openpgp::policy::StandardPolicy;
+        use openpgp::types::RevocationStatus;
+        use openpgp::types::SignatureType;
+
+        let p = &StandardPolicy::new();
+        let t0 = crate::now() - Duration::new(60, 0);
+        let t1 = t0 + Duration::new(10, 0);
+
+        let (alice, _) = CertBuilder::general_purpose(None, Some("alice@example.org"))
+            .set_creation_time(t0)
+            .generate()?;
+        let (bob, _) = CertBuilder::general_purpose(None, Some("bob@example.org"))
+            .set_creation_time(t0)
+            .generate()?;
+        let userid = bob.userids().next().unwrap().userid().clone();
+
+        let mut alice_signer = alice.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        let certification = userid.certify(
+            &mut alice_signer, &bob, SignatureType::PositiveCertification,
+            None, t0)?;
+        let revocation = CertificationRevocationBuilder::new()
+            .set_signature_creation_time(t1)?
+            .build(&mut alice_signer, &bob, &userid, None)?;
+        assert_eq!(revocation.typ(), SignatureType::CertificationRevocation);
+
+        // Bob receives the certification and the revocation from a
+        // third party, in any order.  Both end up on the User ID.
+        for packets in [
+            vec![certification.clone(), revocation.clone()],
+            vec![revocation.clone(), certification.clone()],
+        ] {
+            let bob = bob.clone().insert_packets(packets)?;
+            assert_eq!(bob.bad_signatures().count(), 0);
+
+            let ua = bob.userids().next().unwrap();
+            assert_eq!(ua.certifications().collect::<Vec<_>>(),
+                       vec![&certification]);
+            assert_eq!(ua.other_revocations().collect::<Vec<_>>(),
+                       vec![&revocation]);
+            assert_eq!(ua.valid_third_party_revocations_by_key(
+                p, None, alice.primary_key().key()).count(), 1);
+
+            // The revocation doesn't revoke Bob's User ID, Alice is
+            // not a designated revoker.
+            assert_eq!(ua.revocation_status(p, None),
+                       RevocationStatus::CouldBe(vec![&revocation]));
+        }
+
+        // A revocation received on its own, via a separately
+        // distributed copy of the certificate, is attached as well.
+        let update = Cert::from_packets(
+            bob.clone().into_packets2()
+                .chain(std::iter::once(revocation.clone().into())))?;
+        let merged = bob.clone().merge_public(update)?;
+        assert_eq!(merged.userids().next().unwrap()
+                   .other_revocations().collect::<Vec<_>>(),
+                   vec![&revocation]);
+
+        // Bob can't use it to revoke his own User ID.
+        let mut bob_signer = bob.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        assert!(CertificationRevocationBuilder::new()
+                .build(&mut bob_signer, &bob, &userid, None).is_err());
+
+        // Conversions from a signature builder.
+        let builder: CertificationRevocationBuilder =
+            SignatureBuilder::new(SignatureType::CertificationRevocation)
+            .try_into()?;
+        builder.build(&mut alice_signer, &bob, &userid, None)?;
+        let result: openpgp::Result<CertificationRevocationBuilder> =
+            SignatureBuilder::new(SignatureType::Binary).try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
 }
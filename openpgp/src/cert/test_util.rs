@@ -0,0 +1,305 @@
+//! Fast, deterministic certificates for test suites.
+//!
+//! Generating keys is slow, and freshly generated keys differ from
+//! run to run.  This module derives all key material and signature
+//! salts from a seed, so that certificates are created almost
+//! instantly, and the same seed always yields the same certificate.
+//!
+//! **The derived keys are not secret.**  Anyone who knows the seed
+//! can recreate them.  Never use these certificates outside of
+//! tests.
+//!
+//! This module is only available if the `test-util` feature is
+//! enabled.
+//!
+//! # Examples
+//!
+//! ```
+//! # fn main() -> sequoia_openpgp::Result<()> {
+//! use sequoia_openpgp as openpgp;
+//! use openpgp::cert::test_util::TestCertBuilder;
+//! use openpgp::policy::StandardPolicy;
+//!
+//! let p = &StandardPolicy::new();
+//!
+//! let cert = TestCertBuilder::new(1)
+//!     .add_userids(2)
+//!     .add_signing_subkeys(1)
+//!     .add_encryption_subkeys(1)
+//!     .generate()?;
+//! assert_eq!(cert.userids().count(), 2);
+//! assert_eq!(cert.keys().subkeys().count(), 2);
+//! assert!(cert.with_policy(p, None).is_ok());
+//!
+//! // The same seed yields the same certificate.
+//! assert_eq!(cert, TestCertBuilder::new(1)
+//!            .add_userids(2)
+//!            .add_signing_subkeys(1)
+//!            .add_encryption_subkeys(1)
+//!            .generate()?);
+//! # Ok(()) }
+//! ```
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::Cert;
+use crate::Packet;
+use crate::Result;
+use crate::crypto::hash::Digest;
+use crate::crypto::mem::Protected;
+use crate::packet::{
+    Key,
+    UserID,
+    key::{self, Key4},
+    signature::SignatureBuilder,
+};
+use crate::types::{
+    Features,
+    HashAlgorithm,
+    KeyFlags,
+    SignatureType,
+};
+
+/// The default creation time, 2020-01-01T00:00:00Z.
+const DEFAULT_CREATION_TIME: u64 = 1577836800;
+
+/// Builds deterministic certificates for tests.
+///
+/// The primary key is an Ed25519 key that is certification and
+/// signing capable.  Signing subkeys are Ed25519 keys, encryption
+/// subkeys are Cv25519 keys.  The secret key material is not
+/// encrypted.
+///
+/// The certificate, its components, and all signatures are created
+/// at the same time, by default at 2020-01-01T00:00:00Z, and do not
+/// expire.
+///
+/// See the [module-level documentation] for an example.
+///
+///   [module-level documentation]: self
+#[derive(Clone, Debug)]
+pub struct TestCertBuilder {
+    seed: u64,
+    creation_time: SystemTime,
+    userids: Vec<UserID>,
+    signing_subkeys: usize,
+    encryption_subkeys: usize,
+}
+
+impl TestCertBuilder {
+    /// Returns a builder for a certificate derived from `seed`.
+    ///
+    /// Without further configuration, the certificate consists only
+    /// of the primary key and a direct key signature.
+    pub fn new(seed: u64) -> Self {
+        TestCertBuilder {
+            seed,
+            creation_time: UNIX_EPOCH
+                + Duration::new(DEFAULT_CREATION_TIME, 0),
+            userids: Vec::new(),
+            signing_subkeys: 0,
+            encryption_subkeys: 0,
+        }
+    }
+
+    /// Sets the creation time of the keys and signatures.
+    pub fn set_creation_time<T>(mut self, creation_time: T) -> Self
+        where T: Into<SystemTime>
+    {
+        self.creation_time = creation_time.into();
+        self
+    }
+
+    /// Adds the given User ID.
+    ///
+    /// The first User ID is marked as primary.
+    pub fn add_userid<U>(mut self, userid: U) -> Self
+        where U: Into<UserID>
+    {
+        self.userids.push(userid.into());
+        self
+    }
+
+    /// Adds `n` generated User IDs.
+    ///
+    /// The User IDs are of the form `Test User <i>
+    /// <user<i>@example.org>`, where `i` is the User ID's index.
+    pub fn add_userids(mut self, n: usize) -> Self {
+        for _ in 0..n {
+            let i = self.userids.len();
+            self.userids.push(
+                format!("Test User {} <user{}@example.org>", i, i).into());
+        }
+        self
+    }
+
+    /// Adds `n` signing-capable subkeys.
+    ///
+    /// The signing subkeys precede the encryption subkeys.
+    pub fn add_signing_subkeys(mut self, n: usize) -> Self {
+        self.signing_subkeys += n;
+        self
+    }
+
+    /// Adds `n` encryption-capable subkeys.
+    pub fn add_encryption_subkeys(mut self, n: usize) -> Self {
+        self.encryption_subkeys += n;
+        self
+    }
+
+    /// Generates the certificate.
+    pub fn generate(self) -> Result<Cert> {
+        let mut salts = 0;
+        let mut salt = || -> Result<[u8; 32]> {
+            let mut s = [0; 32];
+            s.copy_from_slice(&self.derive("salt", salts)?);
+            salts += 1;
+            Ok(s)
+        };
+
+        let primary: Key<key::SecretParts, key::PrimaryRole> =
+            Key4::import_secret_ed25519(&self.derive("primary", 0)?,
+                                        self.creation_time)?.into();
+        let mut signer = primary.clone().into_keypair()?;
+        let primary_flags = KeyFlags::empty()
+            .set_certification()
+            .set_signing();
+
+        let mut packets: Vec<Packet> = Vec::new();
+        packets.push(
+            SignatureBuilder::new(SignatureType::DirectKey)
+                .set_signature_creation_time(self.creation_time)?
+                .set_key_flags(primary_flags.clone())?
+                .set_features(Features::sequoia())?
+                .set_fixed_salt(salt()?)
+                .sign_direct_key(&mut signer, None)?.into());
+
+        for (i, userid) in self.userids.iter().enumerate() {
+            let sig = SignatureBuilder::new(SignatureType::PositiveCertification)
+                .set_signature_creation_time(self.creation_time)?
+                .set_key_flags(primary_flags.clone())?
+                .set_features(Features::sequoia())?
+                .set_primary_userid(i == 0)?
+                .set_fixed_salt(salt()?)
+                .sign_userid_binding(&mut signer, None, userid)?;
+            packets.push(userid.clone().into());
+            packets.push(sig.into());
+        }
+
+        for i in 0..self.signing_subkeys {
+            let subkey: Key<key::SecretParts, key::SubordinateRole> =
+                Key4::import_secret_ed25519(
+                    &self.derive("signing subkey", i)?,
+                    self.creation_time)?.into();
+
+            let backsig = SignatureBuilder::new(SignatureType::PrimaryKeyBinding)
+                .set_signature_creation_time(self.creation_time)?
+                .set_hash_algo(HashAlgorithm::SHA512)
+                .set_fixed_salt(salt()?)
+                .sign_primary_key_binding(
+                    &mut subkey.clone().into_keypair()?, &primary, &subkey)?;
+            let sig = SignatureBuilder::new(SignatureType::SubkeyBinding)
+                .set_signature_creation_time(self.creation_time)?
+                .set_key_flags(KeyFlags::empty().set_signing())?
+                .set_embedded_signature(backsig)?
+                .set_fixed_salt(salt()?)
+                .sign_subkey_binding(&mut signer, None, &subkey)?;
+            packets.push(subkey.into());
+            packets.push(sig.into());
+        }
+
+        for i in 0..self.encryption_subkeys {
+            let subkey: Key<key::SecretParts, key::SubordinateRole> =
+                Key4::import_secret_cv25519(
+                    &self.derive("encryption subkey", i)?,
+                    None, None, self.creation_time)?.into();
+
+            let sig = SignatureBuilder::new(SignatureType::SubkeyBinding)
+                .set_signature_creation_time(self.creation_time)?
+                .set_key_flags(KeyFlags::empty()
+                               .set_transport_encryption()
+                               .set_storage_encryption())?
+                .set_fixed_salt(salt()?)
+                .sign_subkey_binding(&mut signer, None, &subkey)?;
+            packets.push(subkey.into());
+            packets.push(sig.into());
+        }
+
+        Cert::from_packets(
+            std::iter::once(primary.into()).chain(packets.into_iter()))
+    }
+
+    /// Derives 32 bytes from the seed, `label`, and `index`.
+    fn derive(&self, label: &str, index: usize) -> Result<Protected> {
+        let mut ctx = HashAlgorithm::SHA256.context()?;
+        ctx.update(b"sequoia-openpgp test-util\0");
+        ctx.update(&self.seed.to_be_bytes());
+        ctx.update(label.as_bytes());
+        ctx.update(&[0]);
+        ctx.update(&(index as u64).to_be_bytes());
+        Ok(ctx.into_digest()?.into())
+    }
+}
+
+/// Returns a deterministic certificate derived from `seed`.
+///
+/// The certificate has one User ID, one signing subkey, and one
+/// encryption subkey.  See [`TestCertBuilder`] for details.
+pub fn cert(seed: u64) -> Result<Cert> {
+    TestCertBuilder::new(seed)
+        .add_userids(1)
+        .add_signing_subkeys(1)
+        .add_encryption_subkeys(1)
+        .generate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parse;
+    use crate::policy::StandardPolicy;
+    use crate::serialize::SerializeInto;
+
+    #[test]
+    fn deterministic() -> Result<()> {
+        let a = cert(7)?;
+        let b = cert(7)?;
+        assert_eq!(a.as_tsk().to_vec()?, b.as_tsk().to_vec()?);
+        assert_ne!(a.fingerprint(), cert(8)?.fingerprint());
+
+        // Different structures over the same seed share the primary key.
+        let c = TestCertBuilder::new(7).generate()?;
+        assert_eq!(a.fingerprint(), c.fingerprint());
+        Ok(())
+    }
+
+    #[test]
+    fn structure() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let t = UNIX_EPOCH + Duration::new(1700000000, 0);
+        let cert = TestCertBuilder::new(3)
+            .set_creation_time(t)
+            .add_userid("Alice <alice@example.org>")
+            .add_userids(2)
+            .add_signing_subkeys(2)
+            .add_encryption_subkeys(3)
+            .generate()?;
+
+        let vc = cert.with_policy(p, None)?;
+        assert_eq!(vc.primary_key().creation_time(), t);
+        assert_eq!(vc.primary_userid()?.userid().value(),
+                   b"Alice <alice@example.org>");
+        assert_eq!(vc.userids().count(), 3);
+        assert!(vc.userids().any(
+            |u| u.userid().value() == b"Test User 2 <user2@example.org>"));
+        assert_eq!(vc.keys().subkeys().for_signing().count(), 2);
+        assert_eq!(vc.keys().subkeys().for_transport_encryption().count(), 3);
+        assert!(vc.keys().all(|k| k.has_unencrypted_secret()));
+        assert_eq!(cert.bad_signatures().count(), 0);
+
+        // It roundtrips.
+        let bytes = cert.as_tsk().to_vec()?;
+        assert_eq!(Cert::from_bytes(&bytes)?, cert);
+        Ok(())
+    }
+}
@@ -2,6 +2,13 @@ use std::cmp;
 use std::convert::TryInto;
 use std::fmt;
 use std::io;
+#[cfg(feature = "parallel-aead-decryption")]
+use std::{
+    collections::VecDeque,
+    panic,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
 
 use buffered_reader::BufferedReader;
 
@@ -28,6 +35,29 @@ const MIN_CHUNK_SIZE: usize = 1 << 6; // 64B
 /// Implementations MUST support chunk sizes up to 4MiB.
 const MAX_CHUNK_SIZE: usize = 1 << 22; // 4MiB
 
+/// The amount of plaintext a worker decrypts per job when
+/// decrypting in parallel.
+///
+/// Jobs consist of at least one chunk.
+#[cfg(feature = "parallel-aead-decryption")]
+const PARALLEL_JOB_SIZE: usize = 1 << 14; // 16KiB
+
+/// The amount of plaintext that is read ahead when decrypting in
+/// parallel.
+///
+/// This bounds the number of jobs in flight, but we always allow two
+/// jobs so that reading and decrypting can overlap.
+#[cfg(feature = "parallel-aead-decryption")]
+const PARALLEL_READ_AHEAD: usize = 1 << 20; // 1MiB
+
+/// The maximum number of threads used to decrypt chunks in
+/// parallel.
+///
+/// Decryption is fast compared to reading the data and copying it
+/// around, so more threads rarely help.
+#[cfg(feature = "parallel-aead-decryption")]
+const PARALLEL_MAX_WORKERS: usize = 4;
+
 /// Maximum size of any Nonce used by an AEAD mode.
 pub const MAX_NONCE_LEN: usize = 16;
 
@@ -246,8 +276,16 @@ pub struct Decryptor<'a, S: Schedule> {
     chunk_size: usize,
     chunk_index: u64,
     bytes_decrypted: u64,
-    // Up to a chunk of unread data.
+    final_tag_verified: bool,
+    // Unread data.  Up to a chunk, or up to a job's worth of chunks
+    // when decrypting in parallel.
     buffer: Vec<u8>,
+    // The number of threads to decrypt chunks with.
+    #[cfg(feature = "parallel-aead-decryption")]
+    workers: usize,
+    // The workers, started once there is enough data.
+    #[cfg(feature = "parallel-aead-decryption")]
+    pipeline: Option<Pipeline>,
 }
 assert_send_and_sync!(Decryptor<'_, S> where S: Schedule);
 
@@ -284,7 +322,14 @@ impl<'a, S: Schedule> Decryptor<'a, S> {
             chunk_size,
             chunk_index: 0,
             bytes_decrypted: 0,
+            final_tag_verified: false,
             buffer: Vec::with_capacity(chunk_size),
+            #[cfg(feature = "parallel-aead-decryption")]
+            workers: std::thread::available_parallelism()
+                .map(|n| cmp::min(n.get(), PARALLEL_MAX_WORKERS))
+                .unwrap_or(1),
+            #[cfg(feature = "parallel-aead-decryption")]
+            pipeline: None,
         })
     }
 
@@ -307,7 +352,16 @@ impl<'a, S: Schedule> Decryptor<'a, S> {
             }
         }
 
-        // 2. Decrypt the data a chunk at a time until we've filled
+        // 2. If possible, decrypt chunks in parallel.
+        #[cfg(feature = "parallel-aead-decryption")]
+        {
+            pos = self.read_parallel(plaintext, pos)?;
+            if pos == plaintext.len() {
+                return Ok(pos);
+            }
+        }
+
+        // 3. Decrypt the data a chunk at a time until we've filled
         // `plaintext`.
         //
         // Unfortunately, framing is hard.
@@ -359,11 +413,15 @@ impl<'a, S: Schedule> Decryptor<'a, S> {
                 Ok(chunk) => {
                     if chunk.is_empty() {
                         // Exhausted source.
+                        if pos == 0 {
+                            self.check_eof()?;
+                        }
                         return Ok(pos);
                     }
 
                     if chunk.len() < final_digest_size {
-                        return Err(Error::ManipulatedMessage.into());
+                        return self.check_truncation(
+                            pos, Error::ManipulatedMessage.into());
                     }
 
                     check_final_tag = chunk.len() < to_read;
@@ -381,7 +439,8 @@ impl<'a, S: Schedule> Decryptor<'a, S> {
                 // the final tag.
             } else if chunk.len() <= self.digest_size {
                 // A chunk has to include at least one byte and a tag.
-                return Err(Error::ManipulatedMessage.into());
+                return self.check_truncation(
+                    pos, Error::ManipulatedMessage.into());
             } else {
                 let mut aead = self.schedule.next_chunk(
                     self.chunk_index,
@@ -403,7 +462,12 @@ impl<'a, S: Schedule> Decryptor<'a, S> {
                     &mut plaintext[pos..pos + to_decrypt]
                 };
 
-                aead.decrypt_verify(buffer, chunk)?;
+                if let Err(err) = aead.decrypt_verify(buffer, chunk) {
+                    if check_final_tag {
+                        return self.check_truncation(pos, err);
+                    }
+                    return Err(err);
+                }
 
                 if double_buffer {
                     let to_copy = plaintext.len() - pos;
@@ -439,17 +503,193 @@ impl<'a, S: Schedule> Decryptor<'a, S> {
 
                 let final_digest = self.source.data(final_digest_size)?;
 
-                aead.decrypt_verify(&mut [], final_digest)?;
+                if let Err(err) = aead.decrypt_verify(&mut [], final_digest) {
+                    return self.check_truncation(pos, err);
+                }
 
                 // Consume the data only on success so that we keep
                 // returning the error.
                 self.source.consume(final_digest_size);
+                self.final_tag_verified = true;
                 break;
             }
         }
 
         Ok(pos)
     }
+
+    /// Decrypts chunks using multiple threads.
+    ///
+    /// The chunks are decrypted by a set of persistent worker
+    /// threads, which are started once enough complete chunks for
+    /// two jobs are buffered.  Smaller messages are decrypted
+    /// sequentially.  While the
+    /// workers decrypt, we read ahead and queue more chunks, so that
+    /// reading and decrypting overlap.  The amount of data in flight
+    /// is bounded by [`PARALLEL_READ_AHEAD`].
+    ///
+    /// This only decrypts chunks that are followed by at least a
+    /// final tag's worth of data.  Those chunks are complete, and
+    /// none of them is authenticated using the final tag.  Anything
+    /// else, i.e., the end of the data and any error handling, is
+    /// left to the sequential code in [`Decryptor::read_helper`].
+    ///
+    /// The ciphertext of queued chunks is only consumed once they
+    /// have been authenticated, and the plaintext is returned in
+    /// order.  If a chunk fails to authenticate, the sequential code
+    /// decrypts it again, returning the plaintext up to the bad
+    /// chunk, and the error, as it would have without this
+    /// optimization.  In that case, parallel decryption is disabled
+    /// for the rest of the data.
+    ///
+    /// Returns the new position in `plaintext`.  If the returned
+    /// position is less than `plaintext.len()`, the caller has to
+    /// continue sequentially.
+    #[cfg(feature = "parallel-aead-decryption")]
+    fn read_parallel(&mut self, plaintext: &mut [u8], mut pos: usize)
+                     -> Result<usize>
+    {
+        if self.workers < 2 {
+            return Ok(pos);
+        }
+
+        let chunk_size = self.chunk_size;
+        let chunk_digest_size = chunk_size + self.digest_size;
+        let final_digest_size = self.digest_size;
+
+        if self.pipeline.is_none() {
+            // Starting threads is not worth it if there is not enough
+            // data to keep two of them busy.
+            let chunks_per_job = cmp::max(1, PARALLEL_JOB_SIZE / chunk_size);
+            let needed = 2 * chunks_per_job * chunk_digest_size
+                + final_digest_size;
+            match self.source.data(needed) {
+                Ok(data) if data.len() >= needed => (),
+                // Let the sequential code deal with the rest, and
+                // with any error.
+                _ => return Ok(pos),
+            }
+
+            self.pipeline = Pipeline::new(
+                self.workers, self.sym_algo, self.aead, chunk_size,
+                chunks_per_job, self.digest_size, &self.key);
+            if self.pipeline.is_none() {
+                // We couldn't start any workers.
+                self.workers = 1;
+                return Ok(pos);
+            }
+        }
+        let pipeline = self.pipeline.as_mut().expect("started above");
+
+        while pos < plaintext.len() {
+            debug_assert!(self.buffer.is_empty());
+
+            // Read ahead, and queue complete chunks.
+            while pipeline.pending.len() < pipeline.capacity {
+                let offset = pipeline.queued * chunk_digest_size;
+                let to_read = offset
+                    + pipeline.chunks_per_job * chunk_digest_size
+                    + final_digest_size;
+                let available = match self.source.data(to_read) {
+                    Ok(data) => data.len(),
+                    // Let the sequential code deal with the error.
+                    Err(_) => break,
+                };
+                let n = cmp::min(
+                    pipeline.chunks_per_job,
+                    available.saturating_sub(offset + final_digest_size)
+                        / chunk_digest_size);
+                if n == 0 {
+                    break;
+                }
+
+                let index = self.chunk_index + pipeline.queued as u64;
+                let parameters = (index..index + n as u64)
+                    .map(|i| self.schedule.next_chunk(i, |iv, ad| {
+                        (iv.to_vec(), ad.to_vec())
+                    }))
+                    .collect();
+                let ciphertext = self.source.buffer()
+                    [offset..offset + n * chunk_digest_size].to_vec();
+                pipeline.queue(Job { index, parameters, ciphertext });
+            }
+
+            let (n, decrypted) = match pipeline.next() {
+                // Nothing is queued, let the sequential code handle
+                // the end of the data.
+                None => return Ok(pos),
+                Some((n, Ok(decrypted))) => (n, decrypted),
+                Some((_, Err(_))) => {
+                    // The sequential code will run into the bad
+                    // chunk.  Discard the queued chunks, and don't
+                    // bother with decrypting in parallel anymore.
+                    self.pipeline = None;
+                    self.workers = 1;
+                    return Ok(pos);
+                },
+            };
+
+            self.source.consume(n * chunk_digest_size);
+            self.chunk_index += n as u64;
+            self.bytes_decrypted += decrypted.len() as u64;
+
+            let to_copy = cmp::min(decrypted.len(), plaintext.len() - pos);
+            plaintext[pos..pos + to_copy]
+                .copy_from_slice(&decrypted[..to_copy]);
+            pos += to_copy;
+            if to_copy < decrypted.len() {
+                let mut decrypted = decrypted;
+                crate::vec_drain_prefix(&mut decrypted, to_copy);
+                self.buffer = decrypted;
+            }
+        }
+
+        Ok(pos)
+    }
+
+    /// Checks that the source ended where it should have.
+    ///
+    /// This must only be called once the source is exhausted.
+    fn check_eof(&mut self) -> Result<()> {
+        if ! self.source.consummated() {
+            // The source, e.g. the body of the encryption container,
+            // ended prematurely.  Return an io::Error so that the
+            // packet parser can recognize the error.
+            Err(io::Error::new(io::ErrorKind::Other,
+                               Error::TruncatedMessage(self.bytes_decrypted))
+                .into())
+        } else if ! self.final_tag_verified {
+            // The final authentication tag is missing, i.e., the
+            // message has been cut off at a chunk boundary.
+            Err(Error::TruncatedMessage(self.bytes_decrypted).into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks whether authenticating the end of the ciphertext failed
+    /// because the source has been truncated.
+    ///
+    /// If the source ended prematurely, then the last chunk and the
+    /// final tag are incomplete, and authenticating them fails.  To
+    /// tell truncation apart from manipulation, we consume the rest
+    /// of the source, and check whether it is consummated.
+    ///
+    /// Consuming the data is fine: on the next read, we find the
+    /// source exhausted, and [`Decryptor::check_eof`] returns the
+    /// same error again.  This also means that if we already
+    /// decrypted `pos` bytes, we can return them now, and report the
+    /// error on the next read.
+    fn check_truncation(&mut self, pos: usize, err: anyhow::Error)
+                        -> Result<usize>
+    {
+        let remaining = self.source.buffer().len();
+        self.source.consume(remaining);
+        if pos > 0 {
+            return Ok(pos);
+        }
+        Err(self.check_eof().err().unwrap_or(err))
+    }
 }
 
 // Note: this implementation tries *very* hard to make sure we don't
@@ -463,13 +703,184 @@ impl<'a, S: Schedule> io::Read for Decryptor<'a, S> {
             Err(e) => match e.downcast::<io::Error>() {
                 // An io::Error.  Pass as-is.
                 Ok(e) => Err(e),
-                // A failure.  Wrap it.
-                Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+                // A failure.  Wrap it, making sure that our errors
+                // can be recovered using `io::Error::get_ref`.
+                Err(e) => match e.downcast::<Error>() {
+                    Ok(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+                    Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+                },
             },
         }
     }
 }
 
+/// A run of chunks to be decrypted by a worker.
+#[cfg(feature = "parallel-aead-decryption")]
+struct Job {
+    // The index of the first chunk.
+    index: u64,
+    // The nonce and the additional authenticated data for each chunk.
+    parameters: Vec<(Vec<u8>, Vec<u8>)>,
+    // The chunks, including their authentication tags.
+    ciphertext: Vec<u8>,
+}
+
+/// A job that has been queued.
+#[cfg(feature = "parallel-aead-decryption")]
+struct Pending {
+    // The index of the first chunk.
+    index: u64,
+    // The number of chunks.
+    chunks: usize,
+    // The plaintext, once the job is done.
+    result: Option<Result<Vec<u8>>>,
+}
+
+/// Decrypts chunks using a set of persistent worker threads.
+///
+/// The workers stop once the pipeline is dropped.
+#[cfg(feature = "parallel-aead-decryption")]
+struct Pipeline {
+    // Jobs for the workers.
+    jobs: mpsc::SyncSender<Job>,
+    // The plaintext from the workers, tagged with the index of the
+    // job's first chunk.
+    results: Mutex<mpsc::Receiver<(u64, Result<Vec<u8>>)>>,
+    // The queued jobs, in order.
+    pending: VecDeque<Pending>,
+    // The maximum number of queued jobs.
+    capacity: usize,
+    // The number of queued chunks.
+    queued: usize,
+    // The number of chunks per job.
+    chunks_per_job: usize,
+}
+
+#[cfg(feature = "parallel-aead-decryption")]
+impl Pipeline {
+    /// Starts up to `workers` threads.
+    ///
+    /// Returns `None` if no thread could be started.
+    fn new(workers: usize, sym_algo: SymmetricAlgorithm,
+           aead: AEADAlgorithm, chunk_size: usize, chunks_per_job: usize,
+           digest_size: usize, key: &SessionKey)
+           -> Option<Self>
+    {
+        let capacity = cmp::max(2, cmp::min(
+            2 * workers,
+            PARALLEL_READ_AHEAD / (chunks_per_job * chunk_size)));
+
+        let (jobs, job_receiver) = mpsc::sync_channel::<Job>(capacity);
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, results) = mpsc::sync_channel(capacity);
+
+        let mut started = 0;
+        for _ in 0..workers {
+            let jobs = job_receiver.clone();
+            let results = result_sender.clone();
+            let key = key.clone();
+
+            let decrypt = move |job: &Job| -> Result<Vec<u8>> {
+                let mut decrypted = vec![0; job.parameters.len() * chunk_size];
+                for ((iv, ad), (plaintext, chunk)) in job.parameters.iter()
+                    .zip(decrypted.chunks_mut(chunk_size)
+                         .zip(job.ciphertext.chunks(chunk_size + digest_size)))
+                {
+                    aead.context(sym_algo, &key, ad, iv, CipherOp::Decrypt)?
+                        .decrypt_verify(plaintext, chunk)?;
+                }
+                Ok(decrypted)
+            };
+
+            let worker = move || loop {
+                let job = {
+                    // Don't hold the lock while decrypting.
+                    let jobs = match jobs.lock() {
+                        Ok(jobs) => jobs,
+                        Err(_) => return,
+                    };
+                    match jobs.recv() {
+                        Ok(job) => job,
+                        // The pipeline has been dropped.
+                        Err(_) => return,
+                    }
+                };
+
+                // Don't leave the reader waiting for a job that
+                // will never be done.
+                let result = panic::catch_unwind(
+                    panic::AssertUnwindSafe(|| decrypt(&job)))
+                    .unwrap_or_else(|_| Err(Error::InvalidOperation(
+                        "AEAD worker panicked".into()).into()));
+                if results.send((job.index, result)).is_err() {
+                    return;
+                }
+            };
+
+            match thread::Builder::new()
+                .name("aead-decryptor".into())
+                .spawn(worker)
+            {
+                Ok(_) => started += 1,
+                Err(_) => break,
+            }
+        }
+
+        if started == 0 {
+            return None;
+        }
+
+        Some(Pipeline {
+            jobs,
+            results: Mutex::new(results),
+            pending: VecDeque::with_capacity(capacity),
+            capacity,
+            queued: 0,
+            chunks_per_job,
+        })
+    }
+
+    /// Queues a job.
+    fn queue(&mut self, job: Job) {
+        let chunks = job.parameters.len();
+        let index = job.index;
+        let result = self.jobs.send(job).err().map(|_| Err(
+            Error::InvalidOperation("AEAD workers stopped".into()).into()));
+        self.pending.push_back(Pending { index, chunks, result });
+        self.queued += chunks;
+    }
+
+    /// Waits for the oldest job to be done.
+    ///
+    /// Returns the number of chunks, and the plaintext, or `None` if
+    /// nothing is queued.
+    fn next(&mut self) -> Option<(usize, Result<Vec<u8>>)> {
+        let results = self.results.get_mut()
+            .unwrap_or_else(|e| e.into_inner());
+        while self.pending.front()?.result.is_none() {
+            match results.recv() {
+                Ok((index, result)) => {
+                    if let Some(pending) = self.pending.iter_mut()
+                        .find(|p| p.index == index)
+                    {
+                        pending.result = Some(result);
+                    }
+                },
+                Err(_) => {
+                    // All workers are gone.
+                    self.pending.front_mut()?.result = Some(Err(
+                        Error::InvalidOperation(
+                            "AEAD workers stopped".into()).into()));
+                },
+            }
+        }
+
+        let pending = self.pending.pop_front()?;
+        self.queued -= pending.chunks;
+        pending.result.map(|result| (pending.chunks, result))
+    }
+}
+
 /// A `BufferedReader` that decrypts AEAD-encrypted data as it is
 /// read.
 pub(crate) struct BufferedReaderDecryptor<'a, S: Schedule> {
@@ -854,4 +1265,103 @@ mod tests {
             }
         }
     }
+
+    /// Checks that decrypting in parallel yields the same results as
+    /// decrypting sequentially, also for manipulated and truncated
+    /// ciphertexts.
+    #[cfg(feature = "parallel-aead-decryption")]
+    #[test]
+    fn parallel_decryption() -> Result<()> {
+        use std::io::Cursor;
+
+        let sym_algo = SymmetricAlgorithm::AES128;
+        let aead = AEADAlgorithm::default();
+        if ! sym_algo.is_supported() || ! aead.is_supported() {
+            eprintln!("Skipping test, algorithms are not supported.");
+            return Ok(());
+        }
+
+        let chunk_size = 64;
+        let mut key = vec![0; sym_algo.key_size()?];
+        crate::crypto::random(&mut key);
+        let key: SessionKey = key.into();
+        let mut iv = vec![0; aead.nonce_size()?];
+        crate::crypto::random(&mut iv);
+        let schedule = || AEDv1Schedule::new(sym_algo, aead, chunk_size, &iv);
+
+        // Enough chunks for more jobs than fit into the queue.
+        let mut plaintext = vec![0; 3000 * chunk_size + 17];
+        crate::crypto::random(&mut plaintext);
+
+        let mut ciphertext = Vec::new();
+        {
+            let mut encryptor = Encryptor::new(
+                sym_algo, aead, chunk_size, schedule()?, key.clone(),
+                &mut ciphertext)?;
+            encryptor.write_all(&plaintext)?;
+        }
+
+        // Decrypts `ciphertext` using `workers` threads, reading
+        // `read_size` bytes at a time.
+        let decrypt = |ciphertext: &[u8], workers: usize, read_size: usize|
+            -> Result<(Vec<u8>, Option<String>)>
+        {
+            let mut decryptor = Decryptor::new(
+                sym_algo, aead, chunk_size, schedule()?, key.clone(),
+                Cursor::new(ciphertext.to_vec()))?;
+            decryptor.workers = workers;
+
+            let mut plaintext = Vec::new();
+            let mut buf = vec![0; read_size];
+            loop {
+                match decryptor.read(&mut buf) {
+                    Ok(0) => return Ok((plaintext, None)),
+                    Ok(n) => plaintext.extend_from_slice(&buf[..n]),
+                    Err(e) => return Ok((plaintext, Some(e.to_string()))),
+                }
+            }
+        };
+
+        let chunk_digest_size = chunk_size + aead.digest_size()?;
+        let mut manipulated = ciphertext.clone();
+        manipulated[2000 * chunk_digest_size + 3] ^= 1;
+        let truncated = &ciphertext[..2500 * chunk_digest_size];
+
+        for read_size in [1, 100, 8192, 1 << 20] {
+            let (p, e) = decrypt(&ciphertext, 4, read_size)?;
+            assert_eq!(p, plaintext);
+            assert!(e.is_none());
+
+            for ciphertext in [&manipulated[..], truncated] {
+                let sequential = decrypt(ciphertext, 1, read_size)?;
+                let parallel = decrypt(ciphertext, 4, read_size)?;
+                assert!(sequential.1.is_some());
+                assert_eq!(sequential, parallel);
+            }
+        }
+
+        // The workers are only started if there are enough chunks to
+        // keep two of them busy.
+        for (chunks, started) in [(300, false), (3000, true)] {
+            let plaintext = &plaintext[..chunks * chunk_size];
+            let mut ciphertext = Vec::new();
+            {
+                let mut encryptor = Encryptor::new(
+                    sym_algo, aead, chunk_size, schedule()?, key.clone(),
+                    &mut ciphertext)?;
+                encryptor.write_all(plaintext)?;
+            }
+
+            let mut decryptor = Decryptor::new(
+                sym_algo, aead, chunk_size, schedule()?, key.clone(),
+                Cursor::new(ciphertext))?;
+            decryptor.workers = 4;
+            let mut decrypted = Vec::new();
+            decryptor.read_to_end(&mut decrypted)?;
+            assert_eq!(decrypted, plaintext);
+            assert_eq!(decryptor.pipeline.is_some(), started);
+        }
+
+        Ok(())
+    }
 }
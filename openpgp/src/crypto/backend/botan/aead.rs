@@ -18,7 +18,8 @@ impl Aead for Cipher {
 
     fn decrypt_verify(&mut self, dst: &mut [u8], src: &[u8]) -> Result<()> {
         debug_assert_eq!(dst.len() + self.digest_size(), src.len());
-        self.0.finish_into(src, dst)?;
+        // Finishing fails if the tag does not match.
+        self.0.finish_into(src, dst).map_err(|_| Error::BadAEADTag)?;
         Ok(())
     }
     fn digest_size(&self) -> usize {
@@ -0,0 +1,13 @@
+#[cfg(feature = "experimental-seipdv2")]
+use crate::Result;
+use crate::crypto::backend::interface::Kdf;
+
+impl Kdf for super::Backend {
+    #[cfg(feature = "experimental-seipdv2")]
+    fn hkdf_sha256(ikm: &[u8], salt: &[u8], info: &[u8], okm: &mut [u8])
+                   -> Result<()> {
+        let key = botan::kdf("HKDF(SHA-256)", okm.len(), ikm, salt, info)?;
+        okm.copy_from_slice(&key);
+        Ok(())
+    }
+}
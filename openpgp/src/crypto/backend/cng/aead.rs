@@ -201,7 +201,7 @@ macro_rules! impl_aead {
                 if secure_cmp(&chunk_digest[..], digest)
                     != Ordering::Equal && ! DANGER_DISABLE_AUTHENTICATION
                 {
-                    return Err(Error::ManipulatedMessage.into());
+                    return Err(Error::BadAEADTag.into());
                 }
                 Ok(())
             }
@@ -85,6 +85,8 @@ impl TryFrom<HashAlgorithm> for cng::HashAlgorithmId {
             HashAlgorithm::MD5 => cng::HashAlgorithmId::Md5,
 
             HashAlgorithm::SHA224 |
+            HashAlgorithm::SHA3_256 |
+            HashAlgorithm::SHA3_512 |
             HashAlgorithm::RipeMD |
             HashAlgorithm::Private(_) |
             HashAlgorithm::Unknown(_) =>
@@ -120,6 +122,8 @@ impl HashAlgorithm {
             HashAlgorithm::MD5 => true,
 
             HashAlgorithm::SHA224 |
+            HashAlgorithm::SHA3_256 |
+            HashAlgorithm::SHA3_512 |
             HashAlgorithm::RipeMD |
             HashAlgorithm::Private(_) |
             HashAlgorithm::Unknown(_) => false,
@@ -27,6 +27,10 @@ impl super::interface::Backend for Backend {
     }
 }
 
+// Use the default implementation built on top of the hash
+// functions.
+impl super::interface::Kdf for Backend {}
+
 impl AEADAlgorithm {
     /// Returns the best AEAD mode supported by the backend.
     ///
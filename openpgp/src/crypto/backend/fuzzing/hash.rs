@@ -28,6 +28,8 @@ impl Digest for NullHasher {
             HashAlgorithm::SHA256 => 32,
             HashAlgorithm::SHA384 => 48,
             HashAlgorithm::SHA512 => 64,
+            HashAlgorithm::SHA3_256 => 32,
+            HashAlgorithm::SHA3_512 => 64,
             HashAlgorithm::RipeMD => 20,
             HashAlgorithm::MD5 => 16,
             _ => 32, // Made up.
@@ -11,7 +11,7 @@ use crate::{
 };
 
 /// Abstracts over the cryptographic backends.
-pub trait Backend: Asymmetric {
+pub trait Backend: Asymmetric + Kdf {
     /// Returns a short, human-readable description of the backend.
     ///
     /// This starts with the name of the backend, possibly a version,
@@ -111,13 +111,130 @@ pub trait Asymmetric {
     }
 }
 
+/// Key-derivation functions.
+pub trait Kdf {
+    /// Computes HKDF instantiated with SHA256, see [RFC 5869].
+    ///
+    /// Fills `okm` with key material derived from `ikm`, `salt`, and
+    /// `info`.  `okm` must not be larger than 255 times the digest
+    /// size, i.e. 8160 bytes.
+    ///
+    /// The default implementation is built on top of the backend's
+    /// SHA256 implementation.  Backends that provide HKDF should
+    /// override it.
+    ///
+    ///   [RFC 5869]: https://www.rfc-editor.org/rfc/rfc5869
+    #[cfg(feature = "experimental-seipdv2")]
+    fn hkdf_sha256(ikm: &[u8], salt: &[u8], info: &[u8], okm: &mut [u8])
+                   -> Result<()> {
+        const HASH_SIZE: usize = 32;
+        if okm.len() > 255 * HASH_SIZE {
+            return Err(Error::InvalidArgument(
+                "Too much key material requested".into()).into());
+        }
+
+        // Extract.
+        let prk = hmac_sha256(salt, &[ikm])?;
+
+        // Expand.
+        let mut t = Protected::from(Vec::new());
+        for (i, chunk) in okm.chunks_mut(HASH_SIZE).enumerate() {
+            t = hmac_sha256(&prk, &[&t, info, &[i as u8 + 1]])?;
+            chunk.copy_from_slice(&t[..chunk.len()]);
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes HMAC-SHA256 over the concatenation of `data`, see [RFC
+/// 2104].
+///
+///   [RFC 2104]: https://www.rfc-editor.org/rfc/rfc2104
+#[cfg(feature = "experimental-seipdv2")]
+fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> Result<Protected> {
+    use crate::crypto::hash::Digest;
+    use crate::types::HashAlgorithm;
+
+    const BLOCK_SIZE: usize = 64;
+
+    let mut pad: Protected = vec![0; BLOCK_SIZE].into();
+    if key.len() > BLOCK_SIZE {
+        let mut h = HashAlgorithm::SHA256.context()?;
+        h.update(key);
+        h.digest(&mut pad[..32])?;
+    } else {
+        pad[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = HashAlgorithm::SHA256.context()?;
+    pad.iter_mut().for_each(|b| *b ^= 0x36);
+    inner.update(&pad);
+    for d in data {
+        inner.update(d);
+    }
+    let mut digest: Protected = vec![0; 32].into();
+    inner.digest(&mut digest)?;
+
+    let mut outer = HashAlgorithm::SHA256.context()?;
+    pad.iter_mut().for_each(|b| *b ^= 0x36 ^ 0x5c);
+    outer.update(&pad);
+    outer.update(&digest);
+    outer.digest(&mut digest)?;
+
+    Ok(digest)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::crypto::backend::{Backend, interface::Asymmetric};
+    use crate::crypto::backend::{Backend, interface::{Asymmetric, Kdf}};
+    use crate::fmt::hex;
 
     #[test]
     pub fn ed25519_generate_key_private_and_public_not_equal() {
         let (secret, public) = Backend::ed25519_generate_key().unwrap();
         assert_ne!(secret.as_ref(), public);
     }
+
+    /// Test case 1 from RFC 5869, Appendix A.1.
+    #[cfg(feature = "experimental-seipdv2")]
+    #[test]
+    fn hkdf_sha256() -> crate::Result<()> {
+        let ikm = [0x0b; 22];
+        let salt = hex::decode("000102030405060708090a0b0c")?;
+        let info = hex::decode("f0f1f2f3f4f5f6f7f8f9")?;
+        let mut okm = [0; 42];
+        Backend::hkdf_sha256(&ikm, &salt, &info, &mut okm)?;
+        assert_eq!(&okm[..], &hex::decode(
+            "3cb25f25faacd57a90434f64d0362f2a\
+             2d2d0a90cf1a5a4c5db02d56ecc4c5bf\
+             34007208d5b887185865")?[..]);
+        Ok(())
+    }
+
+    /// Makes sure the default implementation agrees with the
+    /// backend's.
+    #[cfg(feature = "experimental-seipdv2")]
+    #[test]
+    fn hkdf_sha256_default() -> crate::Result<()> {
+        struct Default;
+        impl Kdf for Default {}
+
+        for (salt_len, info_len, okm_len) in
+            [(0, 0, 1), (32, 5, 32), (100, 13, 100), (13, 0, 8160)]
+        {
+            let ikm = [1; 16];
+            let salt = vec![2; salt_len];
+            let info = vec![3; info_len];
+            let mut a = vec![0; okm_len];
+            let mut b = vec![0; okm_len];
+            Backend::hkdf_sha256(&ikm, &salt, &info, &mut a)?;
+            Default::hkdf_sha256(&ikm, &salt, &info, &mut b)?;
+            assert_eq!(a, b);
+        }
+
+        let mut okm = vec![0; 8161];
+        assert!(Default::hkdf_sha256(&[], &[], &[], &mut okm).is_err());
+        Ok(())
+    }
 }
@@ -8,6 +8,7 @@ pub mod aead;
 pub mod asymmetric;
 pub mod ecdh;
 pub mod hash;
+pub mod kdf;
 pub mod symmetric;
 
 pub struct Backend(());
@@ -29,6 +30,87 @@ impl super::interface::Backend for Backend {
     }
 }
 
+/// Random data for Nettle's operations.
+///
+/// Draws from the random source installed using
+/// [`crate::crypto::set_random_source`], or, if none is installed,
+/// from Nettle's CSPRNG.
+///
+/// Nettle can't handle errors in the generator.  If the source
+/// fails, the error is remembered, and the rest of the data is taken
+/// from Nettle's CSPRNG so that the operation completes.  Callers
+/// must call [`Rng::check`] after using the generator, and discard
+/// the operation's result if it fails.
+pub(crate) enum Rng {
+    /// No source is installed.
+    Yarrow(Yarrow),
+    /// A source is installed, and it may have failed.
+    Source(Option<anyhow::Error>),
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        if crate::crypto::rng::installed() {
+            Rng::Source(None)
+        } else {
+            Rng::Yarrow(Yarrow::default())
+        }
+    }
+}
+
+impl Rng {
+    /// Returns an error if the random source failed.
+    pub(crate) fn check(&mut self) -> crate::Result<()> {
+        match self {
+            Rng::Source(error) => match error.take() {
+                Some(e) => Err(e),
+                None => Ok(()),
+            },
+            Rng::Yarrow(_) => Ok(()),
+        }
+    }
+}
+
+impl Random for Rng {
+    unsafe fn context(&mut self) -> *mut std::os::raw::c_void {
+        self as *mut Self as *mut _
+    }
+
+    unsafe extern "C" fn random_impl(ctx: *mut std::os::raw::c_void,
+                                     length: usize, dst: *mut u8) {
+        if length == 0 {
+            return;
+        }
+        let rng = &mut *(ctx as *mut Self);
+        let buf = std::slice::from_raw_parts_mut(dst, length);
+
+        match rng {
+            Rng::Yarrow(yarrow) => yarrow.random(buf),
+            Rng::Source(error) => {
+                if error.is_none() {
+                    // Don't unwind into Nettle.
+                    let result = std::panic::catch_unwind(
+                        std::panic::AssertUnwindSafe(
+                            || crate::crypto::try_random(&mut *buf)))
+                        .unwrap_or_else(|_| Err(crate::Error::InvalidOperation(
+                            "the source of randomness panicked".into())
+                                                .into()));
+                    if let Err(e) = result {
+                        *error = Some(e);
+                    }
+                }
+
+                if error.is_some() {
+                    // Nettle may loop until it gets suitable random
+                    // data, so we can't just zero the buffer.  The
+                    // result will be discarded by the caller.
+                    Yarrow::default().random(buf);
+                }
+            },
+        }
+    }
+}
+
 impl AEADAlgorithm {
     /// Returns the best AEAD mode supported by the backend.
     ///
@@ -53,7 +53,7 @@ impl<T: nettle::aead::Aead> Aead for T {
         if secure_cmp(&chunk_digest[..], digest)
              != Ordering::Equal && ! DANGER_DISABLE_AUTHENTICATION
             {
-                 return Err(Error::ManipulatedMessage.into());
+                 return Err(Error::BadAEADTag.into());
             }
         Ok(())
     }
@@ -4,7 +4,7 @@
 //! [`Decryptor`]: crate::crypto::Decryptor
 //! [`KeyPair`]: crate::crypto::KeyPair
 
-use nettle::{curve25519, ecc, ecdh, ecdsa, ed25519, dsa, rsa, random::Yarrow};
+use nettle::{curve25519, ecc, ecdh, ecdsa, ed25519, dsa, rsa};
 
 use crate::{Error, Result};
 
@@ -14,6 +14,7 @@ use crate::crypto::backend::interface::Asymmetric;
 use crate::crypto::mpi::{self, MPI, ProtectedMPI, PublicKey};
 use crate::crypto::SessionKey;
 use crate::types::{Curve, HashAlgorithm};
+use super::Rng;
 
 impl Asymmetric for super::Backend {
     fn supports_algo(algo: PublicKeyAlgorithm) -> bool {
@@ -39,8 +40,9 @@ impl Asymmetric for super::Backend {
 
     fn x25519_generate_key() -> Result<(Protected, [u8; 32])> {
         debug_assert_eq!(curve25519::CURVE25519_SIZE, 32);
-        let mut rng = Yarrow::default();
+        let mut rng = Rng::default();
         let secret = curve25519::private_key(&mut rng);
+        rng.check()?;
         let mut public = [0; 32];
         curve25519::mul_g(&mut public, &secret)?;
         Ok((secret.into(), public))
@@ -63,10 +65,11 @@ impl Asymmetric for super::Backend {
 
     fn ed25519_generate_key() -> Result<(Protected, [u8; 32])> {
         debug_assert_eq!(ed25519::ED25519_KEY_SIZE, 32);
-        let mut rng = Yarrow::default();
+        let mut rng = Rng::default();
         let mut public = [0; 32];
         let secret: Protected =
             ed25519::private_key(&mut rng).into();
+        rng.check()?;
         ed25519::public_key(&mut public, &secret)?;
         Ok((secret, public))
     }
@@ -97,12 +100,13 @@ impl Asymmetric for super::Backend {
     fn dsa_generate_key(p_bits: usize)
                         -> Result<(MPI, MPI, MPI, MPI, ProtectedMPI)>
     {
-        let mut rng = Yarrow::default();
+        let mut rng = Rng::default();
         let q_bits = if p_bits <= 1024 { 160 } else { 256 };
         let params = dsa::Params::generate(&mut rng, p_bits, q_bits)?;
         let (p, q) = params.primes();
         let g = params.g();
         let (y, x) = dsa::generate_keypair(&params, &mut rng);
+        rng.check()?;
         Ok((p.into(), q.into(), g.into(), y.as_bytes().into(),
             x.as_bytes().into()))
     }
@@ -117,7 +121,7 @@ impl KeyPair {
     {
         use crate::PublicKeyAlgorithm::*;
 
-        let mut rng = Yarrow::default();
+        let mut rng = Rng::default();
 
         #[allow(deprecated)]
         match (self.public().pk_algo(), self.public().mpis(), secret)
@@ -144,6 +148,7 @@ impl KeyPair {
                 rsa::sign_digest_pkcs1(&public, &secret, digest,
                                        hash_algo.oid()?,
                                        &mut rng, &mut sig)?;
+                rng.check()?;
 
                 Ok(mpi::Signature::RSA {
                     s: MPI::new(&sig),
@@ -157,6 +162,7 @@ impl KeyPair {
                 let secret = dsa::PrivateKey::new(x.value());
 
                 let sig = dsa::sign(&params, &secret, digest, &mut rng)?;
+                rng.check()?;
 
                 Ok(mpi::Signature::DSA {
                     r: MPI::new(&sig.r()),
@@ -184,6 +190,7 @@ impl KeyPair {
                 };
 
                 let sig = ecdsa::sign(&secret, digest, &mut rng);
+                rng.check()?;
 
                 Ok(mpi::Signature::ECDSA {
                     r: MPI::new(&sig.r()),
@@ -213,8 +220,8 @@ impl KeyPair {
                 let public = rsa::PublicKey::new(n.value(), e.value())?;
                 let secret = rsa::PrivateKey::new(d.value(), p.value(),
                                                   q.value(), Option::None)?;
-                let mut rand = Yarrow::default();
-                if let Some(l) = plaintext_len {
+                let mut rand = Rng::default();
+                let plaintext = if let Some(l) = plaintext_len {
                     let mut plaintext: SessionKey = vec![0; l].into();
                     rsa::decrypt_pkcs1(&public, &secret, &mut rand,
                                        c.value(), plaintext.as_mut())?;
@@ -223,7 +230,9 @@ impl KeyPair {
                     rsa::decrypt_pkcs1_insecure(&public, &secret,
                                                 &mut rand, c.value())?
                     .into()
-                }
+                };
+                rand.check()?;
+                plaintext
             }
 
             (PublicKey::ElGamal{ .. },
@@ -267,10 +276,11 @@ impl<P: key::KeyParts, R: key::KeyRole> Key<P, R> {
                         }
 
                         let mut esk = vec![0u8; ciphertext_len];
-                        let mut rng = Yarrow::default();
+                        let mut rng = Rng::default();
                         let pk = rsa::PublicKey::new(n.value(), e.value())?;
                         rsa::encrypt_pkcs1(&pk, &mut rng, data,
                                            &mut esk)?;
+                        rng.check()?;
                         Ok(mpi::Ciphertext::RSA {
                             c: MPI::new(&esk),
                         })
@@ -388,9 +398,10 @@ impl<R> Key4<SecretParts, R>
 
     /// Generates a new RSA key with a public modulus of size `bits`.
     pub fn generate_rsa(bits: usize) -> Result<Self> {
-        let mut rng = Yarrow::default();
+        let mut rng = Rng::default();
 
         let (public, private) = rsa::generate_keypair(&mut rng, bits as u32)?;
+        rng.check()?;
         let (p, q, u) = private.as_rfc4880();
         let public_mpis = PublicKey::RSA {
             e: MPI::new(&*public.e()),
@@ -421,7 +432,7 @@ impl<R> Key4<SecretParts, R>
                                                   mpi::PublicKey,
                                                   mpi::SecretKeyMaterial)>
     {
-        let mut rng = Yarrow::default();
+        let mut rng = Rng::default();
 
         match (curve.clone(), for_signing) {
             (Curve::Ed25519, true) =>
@@ -450,6 +461,7 @@ impl<R> Key4<SecretParts, R>
                     }
                     _ => unreachable!(),
                 };
+                rng.check()?;
                 let (pub_x, pub_y) = public.as_bytes();
                 let public_mpis =  mpi::PublicKey::ECDSA{
                     curve,
@@ -485,6 +497,7 @@ impl<R> Key4<SecretParts, R>
                         }
                         _ => unreachable!(),
                     };
+                    rng.check()?;
                     let public = ecdh::point_mul_g(&private);
                     let (pub_x, pub_y) = public.as_bytes();
                     let public_mpis = mpi::PublicKey::ECDH{
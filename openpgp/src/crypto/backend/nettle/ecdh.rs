@@ -1,6 +1,6 @@
 //! Elliptic Curve Diffie-Hellman.
 
-use nettle::{ecc, ecdh, random::Yarrow};
+use nettle::{ecc, ecdh};
 
 use crate::{Error, Result};
 use crate::crypto::SessionKey;
@@ -9,6 +9,7 @@ use crate::crypto::mem::Protected;
 use crate::crypto::mpi::{MPI, PublicKey, SecretKeyMaterial, Ciphertext};
 use crate::packet::{key, Key};
 use crate::types::Curve;
+use super::Rng;
 
 /// Wraps a session key using Elliptic Curve Diffie-Hellman.
 #[allow(non_snake_case)]
@@ -17,7 +18,7 @@ pub fn encrypt<R>(recipient: &Key<key::PublicParts, R>,
     -> Result<Ciphertext>
     where R: key::KeyRole
 {
-    let mut rng = Yarrow::default();
+    let mut rng = Rng::default();
 
     if let PublicKey::ECDH {
         ref curve, ref q,..
@@ -60,6 +61,7 @@ pub fn encrypt<R>(recipient: &Key<key::PublicParts, R>,
                     }
                     _ => unreachable!(),
                 };
+                rng.check()?;
 
                 // Compute the public key.
                 let VB = ecdh::point_mul_g(&v);
@@ -0,0 +1,21 @@
+#[cfg(feature = "experimental-seipdv2")]
+use nettle::hash::Sha256;
+
+#[cfg(feature = "experimental-seipdv2")]
+use crate::{Error, Result};
+use crate::crypto::backend::interface::Kdf;
+
+impl Kdf for super::Backend {
+    #[cfg(feature = "experimental-seipdv2")]
+    fn hkdf_sha256(ikm: &[u8], salt: &[u8], info: &[u8], okm: &mut [u8])
+                   -> Result<()> {
+        // Nettle doesn't check the length.
+        if okm.len() > 255 * 32 {
+            return Err(Error::InvalidArgument(
+                "Too much key material requested".into()).into());
+        }
+
+        nettle::kdf::hkdf::<Sha256>(ikm, salt, info, okm);
+        Ok(())
+    }
+}
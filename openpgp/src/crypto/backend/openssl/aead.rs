@@ -42,7 +42,9 @@ impl Aead for OpenSslContext {
             self.ctx.cipher_update_unchecked(ciphertext, Some(dst))?
         };
         self.ctx.set_tag(tag)?;
-        unsafe { self.ctx.cipher_final_unchecked(&mut dst[size..])? };
+        // Finalizing fails if the tag does not match.
+        unsafe { self.ctx.cipher_final_unchecked(&mut dst[size..]) }
+            .map_err(|_| Error::BadAEADTag)?;
         Ok(())
     }
 
@@ -0,0 +1,25 @@
+#[cfg(feature = "experimental-seipdv2")]
+use openssl::md::Md;
+#[cfg(feature = "experimental-seipdv2")]
+use openssl::pkey::Id;
+#[cfg(feature = "experimental-seipdv2")]
+use openssl::pkey_ctx::PkeyCtx;
+
+#[cfg(feature = "experimental-seipdv2")]
+use crate::Result;
+use crate::crypto::backend::interface::Kdf;
+
+impl Kdf for super::Backend {
+    #[cfg(feature = "experimental-seipdv2")]
+    fn hkdf_sha256(ikm: &[u8], salt: &[u8], info: &[u8], okm: &mut [u8])
+                   -> Result<()> {
+        let mut ctx = PkeyCtx::new_id(Id::HKDF)?;
+        ctx.derive_init()?;
+        ctx.set_hkdf_md(Md::sha256())?;
+        ctx.set_hkdf_key(ikm)?;
+        ctx.set_hkdf_salt(salt)?;
+        ctx.add_hkdf_info(info)?;
+        ctx.derive(Some(okm))?;
+        Ok(())
+    }
+}
@@ -10,6 +10,7 @@ pub mod aead;
 pub mod asymmetric;
 pub mod ecdh;
 pub mod hash;
+pub mod kdf;
 pub mod symmetric;
 
 pub struct Backend(());
@@ -28,6 +29,37 @@ impl super::interface::Backend for Backend {
     }
 }
 
+/// A random number generator that honors the installed random
+/// source.
+///
+/// See [`crate::crypto::set_random_source`].  If the source fails,
+/// [`RngCore::fill_bytes`] panics.
+///
+///   [`RngCore::fill_bytes`]: rand_core::RngCore::fill_bytes
+#[derive(Clone, Copy, Debug, Default)]
+struct Rng;
+
+impl rand_core::RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        crate::crypto::random(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8])
+                      -> std::result::Result<(), rand_core::Error> {
+        crate::crypto::try_random(dest).map_err(rand_core::Error::new)
+    }
+}
+
+impl rand_core::CryptoRng for Rng {}
+
 trait GenericArrayExt<T, N: ArrayLength<T>> {
     const LEN: usize;
 
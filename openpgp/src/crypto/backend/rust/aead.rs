@@ -75,7 +75,7 @@ where
         if secure_cmp(&chunk_digest[..], digest)
              != Ordering::Equal && ! DANGER_DISABLE_AUTHENTICATION
             {
-                 return Err(Error::ManipulatedMessage.into());
+                 return Err(Error::BadAEADTag.into());
             }
         Ok(())
     }
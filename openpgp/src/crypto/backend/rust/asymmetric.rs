@@ -79,11 +79,7 @@ impl Asymmetric for super::Backend {
     fn x25519_generate_key() -> Result<(Protected, [u8; 32])> {
         use x25519_dalek::{StaticSecret, PublicKey};
 
-        // x25519_dalek v1.1 doesn't reexport OsRng.  It
-        // depends on rand 0.8.
-        use rand::rngs::OsRng;
-
-        let secret = StaticSecret::random_from_rng(&mut OsRng);
+        let secret = StaticSecret::random_from_rng(&mut super::Rng);
         let public = PublicKey::from(&secret);
         let mut secret_bytes = secret.to_bytes();
         let secret: Protected = secret_bytes.as_ref().into();
@@ -111,8 +107,7 @@ impl Asymmetric for super::Backend {
     }
 
     fn ed25519_generate_key() -> Result<(Protected, [u8; 32])> {
-        use rand::rngs::OsRng as OsRng;
-        let pair = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let pair = ed25519_dalek::SigningKey::generate(&mut super::Rng);
         Ok((pair.to_bytes().into(), pair.verifying_key().to_bytes()))
     }
 
@@ -157,7 +152,7 @@ impl Asymmetric for super::Backend {
                 format!("Key size {} is not supported", n)).into()),
         };
 
-        let mut rng = rand_core::OsRng;
+        let mut rng = super::Rng;
         let components = dsa::Components::generate(&mut rng, size);
         let p = components.p().into();
         let q = components.q().into();
@@ -201,6 +196,8 @@ fn pkcs1_padding(hash_algo: HashAlgorithm) -> Result<Pkcs1v15Sign> {
         HashAlgorithm::SHA256 => Pkcs1v15Sign::new::<sha2::Sha256>(),
         HashAlgorithm::SHA384 => Pkcs1v15Sign::new::<sha2::Sha384>(),
         HashAlgorithm::SHA512 => Pkcs1v15Sign::new::<sha2::Sha512>(),
+        HashAlgorithm::SHA3_256 => Pkcs1v15Sign::new::<sha3::Sha3_256>(),
+        HashAlgorithm::SHA3_512 => Pkcs1v15Sign::new::<sha3::Sha3_512>(),
         HashAlgorithm::RipeMD => Pkcs1v15Sign::new::<ripemd::Ripemd160>(),
         _ => return Err(Error::InvalidArgument(format!(
             "Algorithm {:?} not representable", hash_algo)).into()),
@@ -281,7 +278,7 @@ impl KeyPair {
 
                     let sig = loop {
                         let mut k: Protected = vec![0; LEN].into();
-                        crate::crypto::random(&mut k);
+                        crate::crypto::try_random(&mut k)?;
                         let k = Scalar::reduce_bytes(
                             GA::try_from_slice(&k)?);
                         if let Ok(s) = key.try_sign_prehashed(k, &dig) {
@@ -306,7 +303,7 @@ impl KeyPair {
 
                     let sig = loop {
                         let mut k: Protected = vec![0; LEN].into();
-                        crate::crypto::random(&mut k);
+                        crate::crypto::try_random(&mut k)?;
                         let k = Scalar::reduce_bytes(
                             GA::try_from_slice(&k)?);
                         if let Ok(s) = key.try_sign_prehashed(k, &dig) {
@@ -331,7 +328,7 @@ impl KeyPair {
 
                     let sig = loop {
                         let mut k: Protected = vec![0; LEN].into();
-                        crate::crypto::random(&mut k);
+                        crate::crypto::try_random(&mut k)?;
                         let k = Scalar::reduce_bytes(
                             GA::try_from_slice(&k)?);
                         if let Ok(s) = key.try_sign_prehashed(k, &dig) {
@@ -413,7 +410,7 @@ impl<P: key::KeyParts, R: key::KeyRole> Key<P, R> {
                     }
                     let key = rsa_public_key(e, n)?;
                     let ciphertext = key.encrypt(
-                        &mut rsa::rand_core::OsRng,
+                        &mut super::Rng,
                         Pkcs1v15Encrypt, data.as_ref())?;
                     Ok(mpi::Ciphertext::RSA {
                         c: mpi::MPI::new(&ciphertext)
@@ -606,7 +603,7 @@ impl<R> Key4<SecretParts, R>
 
     /// Generates a new RSA key with a public modulus of size `bits`.
     pub fn generate_rsa(bits: usize) -> Result<Self> {
-        let key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, bits)?;
+        let key = RsaPrivateKey::new(&mut super::Rng, bits)?;
         let (p, q) = match key.primes() {
             [p, q] => (p, q),
             _ => panic!("RSA key generation resulted in wrong number of primes"),
@@ -659,7 +656,7 @@ impl<R> Key4<SecretParts, R>
                 use p256::{EncodedPoint, SecretKey};
 
                 let secret = SecretKey::random(
-                    &mut p256::elliptic_curve::rand_core::OsRng);
+                    &mut super::Rng);
                 let public = EncodedPoint::from(secret.public_key());
 
                 let public_mpis = mpi::PublicKey::ECDSA {
@@ -677,7 +674,7 @@ impl<R> Key4<SecretParts, R>
                 use p256::{EncodedPoint, SecretKey};
 
                 let secret = SecretKey::random(
-                    &mut p256::elliptic_curve::rand_core::OsRng);
+                    &mut super::Rng);
                 let public = EncodedPoint::from(secret.public_key());
 
                 let public_mpis = mpi::PublicKey::ECDH {
@@ -699,7 +696,7 @@ impl<R> Key4<SecretParts, R>
                 use p384::{EncodedPoint, SecretKey};
 
                 let secret = SecretKey::random(
-                    &mut p384::elliptic_curve::rand_core::OsRng);
+                    &mut super::Rng);
                 let public = EncodedPoint::from(secret.public_key());
 
                 let public_mpis = mpi::PublicKey::ECDSA {
@@ -717,7 +714,7 @@ impl<R> Key4<SecretParts, R>
                 use p384::{EncodedPoint, SecretKey};
 
                 let secret = SecretKey::random(
-                    &mut p384::elliptic_curve::rand_core::OsRng);
+                    &mut super::Rng);
                 let public = EncodedPoint::from(secret.public_key());
 
                 let public_mpis = mpi::PublicKey::ECDH {
@@ -739,7 +736,7 @@ impl<R> Key4<SecretParts, R>
                 use p521::{EncodedPoint, SecretKey};
 
                 let secret = SecretKey::random(
-                    &mut p521::elliptic_curve::rand_core::OsRng);
+                    &mut super::Rng);
                 let public = EncodedPoint::from(secret.public_key());
 
                 let public_mpis = mpi::PublicKey::ECDSA {
@@ -757,7 +754,7 @@ impl<R> Key4<SecretParts, R>
                 use p521::{EncodedPoint, SecretKey};
 
                 let secret = SecretKey::random(
-                    &mut p521::elliptic_curve::rand_core::OsRng);
+                    &mut super::Rng);
                 let public = EncodedPoint::from(secret.public_key());
 
                 let public_mpis = mpi::PublicKey::ECDH {
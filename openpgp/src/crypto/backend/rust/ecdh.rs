@@ -43,7 +43,7 @@ pub fn encrypt<R>(recipient: &Key<key::PublicParts, R>,
 
             // Generate a keypair and perform Diffie-Hellman.
             let secret = EphemeralSecret::random(
-                &mut p256::elliptic_curve::rand_core::OsRng);
+                &mut super::Rng);
             let public = EncodedPoint::from(PublicKey::from(&secret));
             let shared = secret.diffie_hellman(&recipient_key);
 
@@ -65,7 +65,7 @@ pub fn encrypt<R>(recipient: &Key<key::PublicParts, R>,
 
             // Generate a keypair and perform Diffie-Hellman.
             let secret = EphemeralSecret::random(
-                &mut p384::elliptic_curve::rand_core::OsRng);
+                &mut super::Rng);
             let public = EncodedPoint::from(PublicKey::from(&secret));
             let shared = secret.diffie_hellman(&recipient_key);
 
@@ -87,7 +87,7 @@ pub fn encrypt<R>(recipient: &Key<key::PublicParts, R>,
 
             // Generate a keypair and perform Diffie-Hellman.
             let secret = EphemeralSecret::random(
-                &mut p521::elliptic_curve::rand_core::OsRng);
+                &mut super::Rng);
             let public = EncodedPoint::from(PublicKey::from(&secret));
             let shared = secret.diffie_hellman(&recipient_key);
 
@@ -0,0 +1,18 @@
+#[cfg(feature = "experimental-seipdv2")]
+use hkdf::Hkdf;
+#[cfg(feature = "experimental-seipdv2")]
+use sha2::Sha256;
+
+#[cfg(feature = "experimental-seipdv2")]
+use crate::{Error, Result};
+use crate::crypto::backend::interface::Kdf;
+
+impl Kdf for super::Backend {
+    #[cfg(feature = "experimental-seipdv2")]
+    fn hkdf_sha256(ikm: &[u8], salt: &[u8], info: &[u8], okm: &mut [u8])
+                   -> Result<()> {
+        Hkdf::<Sha256>::new(Some(salt), ikm).expand(info, okm)
+            .map_err(|_| Error::InvalidArgument(
+                "Too much key material requested".into()).into())
+    }
+}
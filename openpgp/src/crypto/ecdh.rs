@@ -24,7 +24,10 @@ use crate::utils::{read_be_u64, write_be_u64};
 pub(crate) use crate::crypto::backend::ecdh::{encrypt, decrypt};
 
 /// Returns the default ECDH KDF hash function.
-pub(crate) fn default_ecdh_kdf_hash(curve: &Curve) -> HashAlgorithm {
+///
+/// This is the hash function used by the key derivation function
+/// when generating ECDH keys over `curve`.
+pub fn default_ecdh_kdf_hash(curve: &Curve) -> HashAlgorithm {
     match curve {
         Curve::Cv25519 => HashAlgorithm::SHA256,
         // From RFC6637:
@@ -41,7 +44,10 @@ pub(crate) fn default_ecdh_kdf_hash(curve: &Curve) -> HashAlgorithm {
 }
 
 /// Returns the default ECDH KEK cipher.
-pub(crate) fn default_ecdh_kek_cipher(curve: &Curve) -> SymmetricAlgorithm {
+///
+/// This is the symmetric algorithm used to wrap session keys when
+/// generating ECDH keys over `curve`.
+pub fn default_ecdh_kek_cipher(curve: &Curve) -> SymmetricAlgorithm {
     match curve {
         Curve::Cv25519 => SymmetricAlgorithm::AES128,
         // From RFC6637:
@@ -57,6 +63,39 @@ pub(crate) fn default_ecdh_kek_cipher(curve: &Curve) -> SymmetricAlgorithm {
     }
 }
 
+/// Checks that the ECDH KDF parameters are acceptable.
+///
+/// [RFC 6637] requires the key derivation function to use SHA2-256
+/// or a stronger hash function, and the key encryption key to be an
+/// AES key.  Keys with other parameters are rejected when parsing.
+///
+///   [RFC 6637]: https://tools.ietf.org/html/rfc6637#section-8
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::crypto::ecdh::check_kdf_parameters;
+/// use openpgp::types::{HashAlgorithm, SymmetricAlgorithm};
+///
+/// assert!(check_kdf_parameters(HashAlgorithm::SHA256,
+///                              SymmetricAlgorithm::AES128).is_ok());
+/// assert!(check_kdf_parameters(HashAlgorithm::SHA1,
+///                              SymmetricAlgorithm::AES128).is_err());
+/// assert!(check_kdf_parameters(HashAlgorithm::SHA256,
+///                              SymmetricAlgorithm::CAST5).is_err());
+/// ```
+pub fn check_kdf_parameters(hash: HashAlgorithm, sym: SymmetricAlgorithm)
+                            -> Result<()>
+{
+    use HashAlgorithm::*;
+    use SymmetricAlgorithm::*;
+    match (hash, sym) {
+        (SHA256 | SHA384 | SHA512, AES128 | AES192 | AES256) => Ok(()),
+        _ => Err(Error::InvalidEcdhParameters(hash, sym).into()),
+    }
+}
+
 /// Wraps a session key.
 ///
 /// After using Elliptic-curve Diffie-Hellman to compute the shared
@@ -96,6 +96,18 @@ const ASN1_OID_SHA512: &[u8] = &[
     0x04, 0x02, 0x03, 0x05, 0x00, 0x04, 0x40,
 ];
 
+/// ASN.1 OID for SHA3-256
+const ASN1_OID_SHA3_256: &[u8] = &[
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03,
+    0x04, 0x02, 0x08, 0x05, 0x00, 0x04, 0x20,
+];
+
+/// ASN.1 OID for SHA3-512
+const ASN1_OID_SHA3_512: &[u8] = &[
+    0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03,
+    0x04, 0x02, 0x0a, 0x05, 0x00, 0x04, 0x40,
+];
+
 lazy_static::lazy_static! {
     /// List of hashes that the signer may produce.
     /// This list is ordered by the preference so that the most preferred
@@ -106,6 +118,9 @@ lazy_static::lazy_static! {
         HashAlgorithm::SHA384,
         HashAlgorithm::SHA256,
         HashAlgorithm::SHA224,
+        // The streaming Signer only uses SHA3 if explicitly requested.
+        HashAlgorithm::SHA3_512,
+        HashAlgorithm::SHA3_256,
         HashAlgorithm::SHA1,
         HashAlgorithm::RipeMD,
         HashAlgorithm::MD5,
@@ -198,6 +213,12 @@ impl HashAlgorithm {
                 Box::new(crate::crypto::backend::sha1cd::build()),
             _ => self.new_hasher()?,
         };
+        let hasher: Box<dyn Digest> =
+            if crate::crypto::Statistics::enabled() {
+                Box::new(Counting(hasher))
+            } else {
+                hasher
+            };
         Ok(if let Some(prefix) = DUMP_HASHED_VALUES {
             Box::new(HashDumper::new(hasher, prefix))
         } else {
@@ -234,12 +255,127 @@ impl HashAlgorithm {
             HashAlgorithm::SHA256 => Ok(ASN1_OID_SHA256),
             HashAlgorithm::SHA384 => Ok(ASN1_OID_SHA384),
             HashAlgorithm::SHA512 => Ok(ASN1_OID_SHA512),
+            HashAlgorithm::SHA3_256 => Ok(ASN1_OID_SHA3_256),
+            HashAlgorithm::SHA3_512 => Ok(ASN1_OID_SHA3_512),
             HashAlgorithm::MD5 => Ok(ASN1_OID_MD5),
             HashAlgorithm::RipeMD => Ok(ASN1_OID_RIPEMD160),
             HashAlgorithm::Private(_) | HashAlgorithm::Unknown(_) =>
                 Err(crate::Error::UnsupportedHashAlgorithm(self).into()),
         }
     }
+
+    /// Returns the size of the digest in bytes.
+    ///
+    /// This does not require the algorithm to be supported by the
+    /// cryptographic backend.
+    ///
+    /// ```
+    /// # use sequoia_openpgp::types::HashAlgorithm;
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// assert_eq!(HashAlgorithm::SHA256.digest_size()?, 32);
+    /// assert!(HashAlgorithm::Unknown(42).digest_size().is_err());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with `Error::UnsupportedHashAlgorithm` for unknown or
+    /// private hash algorithms.
+    pub fn digest_size(self) -> Result<usize> {
+        match self {
+            HashAlgorithm::MD5 => Ok(16),
+            HashAlgorithm::SHA1 => Ok(20),
+            HashAlgorithm::RipeMD => Ok(20),
+            HashAlgorithm::SHA224 => Ok(28),
+            HashAlgorithm::SHA256 => Ok(32),
+            HashAlgorithm::SHA384 => Ok(48),
+            HashAlgorithm::SHA512 => Ok(64),
+            HashAlgorithm::SHA3_256 => Ok(32),
+            HashAlgorithm::SHA3_512 => Ok(64),
+            HashAlgorithm::Private(_) | HashAlgorithm::Unknown(_) =>
+                Err(crate::Error::UnsupportedHashAlgorithm(self).into()),
+        }
+    }
+
+    /// Returns the approximate collision resistance in bits.
+    ///
+    /// For unbroken algorithms, this is half the digest size.  For
+    /// algorithms with known practical attacks, this is the cost of
+    /// the best published collision attack: the MD5 value is a
+    /// nominal lower bound, since collisions can be found in
+    /// seconds, and the SHA-1 value reflects the SHAttered and
+    /// chosen-prefix attacks.
+    ///
+    /// This is a coarse measure that is meant to let policies and
+    /// user interfaces compare hash algorithms.  It is not a
+    /// replacement for a [`Policy`].
+    ///
+    ///   [`Policy`]: crate::policy::Policy
+    ///
+    /// ```
+    /// # use sequoia_openpgp::types::HashAlgorithm;
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// assert_eq!(HashAlgorithm::SHA256.security_level()?, 128);
+    /// assert!(HashAlgorithm::SHA1.security_level()?
+    ///         < HashAlgorithm::RipeMD.security_level()?);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with `Error::UnsupportedHashAlgorithm` for unknown or
+    /// private hash algorithms.
+    pub fn security_level(self) -> Result<usize> {
+        match self {
+            HashAlgorithm::MD5 => Ok(18),
+            HashAlgorithm::SHA1 => Ok(63),
+            HashAlgorithm::RipeMD
+            | HashAlgorithm::SHA224
+            | HashAlgorithm::SHA256
+            | HashAlgorithm::SHA384
+            | HashAlgorithm::SHA512
+            | HashAlgorithm::SHA3_256
+            | HashAlgorithm::SHA3_512 => Ok(self.digest_size()? * 8 / 2),
+            HashAlgorithm::Private(_) | HashAlgorithm::Unknown(_) =>
+                Err(crate::Error::UnsupportedHashAlgorithm(self).into()),
+        }
+    }
+}
+
+/// Counts the bytes hashed.
+///
+/// See [`crate::crypto::Statistics::bytes_hashed`].
+#[derive(Clone)]
+struct Counting(Box<dyn Digest>);
+
+impl Digest for Counting {
+    fn algo(&self) -> HashAlgorithm {
+        self.0.algo()
+    }
+
+    fn digest_size(&self) -> usize {
+        self.0.digest_size()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        crate::crypto::stats::hashed(data.len());
+        self.0.update(data);
+    }
+
+    fn digest(&mut self, digest: &mut [u8]) -> Result<()> {
+        self.0.digest(digest)
+    }
+}
+
+impl io::Write for Counting {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 struct HashDumper {
@@ -754,4 +890,86 @@ mod test {
             = check(Cert::from_bytes(crate::tests::key("dkg.gpg")).unwrap());
         assert!(ua_sigs > 0);
     }
+
+    #[test]
+    fn digest_size() {
+        for algo in HashAlgorithm::variants() {
+            if ! algo.is_supported() {
+                continue;
+            }
+
+            let ctx = algo.context().unwrap();
+            assert_eq!(algo.digest_size().unwrap(), ctx.digest_size(),
+                       "{}", algo);
+            assert!(algo.security_level().unwrap() <= ctx.digest_size() * 4);
+        }
+
+        assert!(HashAlgorithm::Private(100).digest_size().is_err());
+        assert!(HashAlgorithm::Unknown(42).security_level().is_err());
+    }
+
+    #[test]
+    fn sha3() -> crate::Result<()> {
+        use crate::cert::prelude::*;
+        use crate::packet::signature::SignatureBuilder;
+        use crate::policy::StandardPolicy;
+        use crate::types::SignatureType;
+
+        // Known answers for the empty string.
+        for (algo, prefix) in [
+            (HashAlgorithm::SHA3_256, &b"\xa7\xff\xc6\xf8"[..]),
+            (HashAlgorithm::SHA3_512, &b"\xa6\x9f\x73\xcc"[..]),
+        ] {
+            if ! algo.is_supported() {
+                eprintln!("Skipping unsupported {}", algo);
+                continue;
+            }
+            let digest = algo.context()?.into_digest()?;
+            assert_eq!(&digest[..4], prefix);
+        }
+
+        let p = &StandardPolicy::new();
+        for cs in [CipherSuite::Cv25519, CipherSuite::RSA2k] {
+            if cs.is_supported().is_err() {
+                eprintln!("Skipping unsupported {:?}", cs);
+                continue;
+            }
+            let (cert, _) = CertBuilder::new()
+                .set_cipher_suite(cs)
+                .add_userid("Alice")
+                .generate()?;
+            let mut signer = cert.primary_key().key().clone()
+                .parts_into_secret()?.into_keypair()?;
+
+            for algo in [HashAlgorithm::SHA3_256, HashAlgorithm::SHA3_512] {
+                if ! algo.is_supported() {
+                    continue;
+                }
+
+                let msg = b"Hello";
+                let sig = SignatureBuilder::new(SignatureType::Binary)
+                    .set_hash_algo(algo)
+                    .sign_message(&mut signer, msg)?;
+                assert_eq!(sig.hash_algo(), algo);
+                sig.verify_message(signer.public(), msg)?;
+
+                // Certificates using SHA3 self-signatures are valid.
+                let uid = cert.userids().next().unwrap();
+                let sig = SignatureBuilder::from(
+                    uid.self_signatures().next().unwrap().clone())
+                    .set_hash_algo(algo)
+                    .set_signature_creation_time(
+                        crate::now() + std::time::Duration::new(1, 0))?
+                    .sign_userid_binding(&mut signer, None, uid.userid())?;
+                let cert = Cert::from_bytes(
+                    &crate::serialize::SerializeInto::to_vec(
+                        &cert.clone().insert_packets(sig)?)?)?;
+                let vc = cert.with_policy(
+                    p, crate::now() + std::time::Duration::new(2, 0))?;
+                assert_eq!(vc.primary_userid()?.binding_signature()
+                           .hash_algo(), algo);
+            }
+        }
+        Ok(())
+    }
 }
@@ -26,6 +26,7 @@ use std::borrow::Cow;
 use crate::{
     Error,
     Result,
+    types::SymmetricAlgorithm,
 };
 
 pub(crate) mod aead;
@@ -36,8 +37,14 @@ pub mod ecdh;
 pub mod hash;
 pub mod mem;
 pub mod mpi;
+pub(crate) mod rng;
+pub use rng::{RandomSource, set_random_source};
 mod s2k;
 pub use s2k::S2K;
+#[cfg(feature = "experimental-seipdv2")]
+pub mod seipdv2;
+pub(crate) mod stats;
+pub use stats::Statistics;
 pub(crate) mod symmetric;
 
 #[cfg(test)]
@@ -61,10 +68,28 @@ pub fn backend() -> String {
 /// cryptographic keys from.  However, to create session keys,
 /// consider using [`SessionKey::new`].
 ///
+/// The random data is drawn from the source installed using
+/// [`set_random_source`], or, if none is installed, from the
+/// cryptographic backend.
+///
+/// # Panics
+///
+/// Panics if the source of randomness fails.  Use [`try_random`] to
+/// handle the error instead.
+///
 ///   [`SessionKey::new`]: crate::crypto::SessionKey::new()
-pub fn random<B: AsMut<[u8]>>(mut buf: B) {
+pub fn random<B: AsMut<[u8]>>(buf: B) {
+    try_random(buf).expect("the source of randomness failed")
+}
+
+/// Fills the given buffer with random data, or returns an error.
+///
+/// Like [`random`], but returns an error if the source of randomness
+/// fails.
+pub fn try_random<B: AsMut<[u8]>>(mut buf: B) -> Result<()> {
     use backend::interface::Backend;
-    backend::Backend::random(buf.as_mut()).unwrap();
+    rng::random(buf.as_mut())
+        .unwrap_or_else(|| backend::Backend::random(buf.as_mut()))
 }
 
 /// Holds a session key.
@@ -180,6 +205,117 @@ impl fmt::Debug for SessionKey {
     }
 }
 
+/// A session key together with its symmetric algorithm.
+///
+/// This is what is needed to decrypt the body of an encrypted
+/// message without the recipient's secret key.  Mail clients can
+/// store these session keys per message to avoid repeated (and
+/// potentially expensive, or interactive) private key operations.
+/// The session key of a decrypted message can be obtained using
+/// [`Decryptor::session_key`], and stored session keys can be given
+/// to [`DecryptorBuilder::session_keys`].
+///
+///   [`Decryptor::session_key`]: crate::parse::stream::Decryptor::session_key()
+///   [`DecryptorBuilder::session_keys`]: crate::parse::stream::DecryptorBuilder::session_keys()
+///
+/// The session key is cleared when dropped, and comparisons of
+/// session keys are done in constant time.
+///
+/// The stable textual representation, produced using [`Display`],
+/// and parsed using [`FromStr`], is the one used by GnuPG's
+/// `--show-session-key` option: the decimal symmetric algorithm
+/// identifier, a colon, and the hex-encoded session key.  Note that
+/// the [`String`] returned by [`ToString::to_string`] is not cleared
+/// when dropped.
+///
+///   [`Display`]: std::fmt::Display
+///   [`FromStr`]: std::str::FromStr
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> sequoia_openpgp::Result<()> {
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::crypto::{EscrowedSessionKey, SessionKey};
+/// use openpgp::types::SymmetricAlgorithm;
+///
+/// let sk = EscrowedSessionKey::new(
+///     SymmetricAlgorithm::AES128,
+///     SessionKey::from(&b"\x01\x23\x45\x67\x89\xab\xcd\xef\
+///                         \x01\x23\x45\x67\x89\xab\xcd\xef"[..]));
+///
+/// let s = sk.to_string();
+/// assert_eq!(s, "7:0123456789ABCDEF0123456789ABCDEF");
+/// assert_eq!(s.parse::<EscrowedSessionKey>()?, sk);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EscrowedSessionKey {
+    algo: SymmetricAlgorithm,
+    session_key: SessionKey,
+}
+assert_send_and_sync!(EscrowedSessionKey);
+
+impl EscrowedSessionKey {
+    /// Creates a new escrowed session key.
+    pub fn new(algo: SymmetricAlgorithm, session_key: SessionKey) -> Self {
+        EscrowedSessionKey {
+            algo,
+            session_key,
+        }
+    }
+
+    /// Returns the symmetric algorithm.
+    pub fn symmetric_algo(&self) -> SymmetricAlgorithm {
+        self.algo
+    }
+
+    /// Returns the session key.
+    pub fn session_key(&self) -> &SessionKey {
+        &self.session_key
+    }
+
+    /// Returns the symmetric algorithm and the session key.
+    pub fn into_parts(self) -> (SymmetricAlgorithm, SessionKey) {
+        (self.algo, self.session_key)
+    }
+}
+
+impl From<(SymmetricAlgorithm, SessionKey)> for EscrowedSessionKey {
+    fn from((algo, session_key): (SymmetricAlgorithm, SessionKey)) -> Self {
+        EscrowedSessionKey::new(algo, session_key)
+    }
+}
+
+impl fmt::Display for EscrowedSessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", u8::from(self.algo))?;
+        for b in self.session_key.iter() {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for EscrowedSessionKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (algo, key) = s.split_once(':')
+            .ok_or_else(|| Error::InvalidArgument(
+                "Expected ALGO:HEXKEY".into()))?;
+        let algo: u8 = algo.parse()
+            .map_err(|_| Error::InvalidArgument(
+                format!("Invalid symmetric algorithm: {:?}", algo)))?;
+        let key: mem::Protected = crate::fmt::hex::decode(key)?.into();
+        if key.is_empty() {
+            return Err(Error::InvalidArgument(
+                "Session key is empty".into()).into());
+        }
+        Ok(EscrowedSessionKey::new(algo.into(), key.into()))
+    }
+}
+
 /// Holds a password.
 ///
 /// `Password`s can be converted from various types using [`From`].
@@ -20,7 +20,7 @@ use std::cmp::Ordering;
 use std::io::Write;
 use std::borrow::Cow;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::types::{
@@ -290,7 +290,7 @@ impl Hash for MPI {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for MPI {
     fn arbitrary(g: &mut Gen) -> Self {
         loop {
@@ -303,6 +303,51 @@ impl Arbitrary for MPI {
     }
 }
 
+#[cfg(any(test, feature = "quickcheck"))]
+impl MPI {
+    /// Returns an arbitrary MPI of at most `bits` bits.
+    fn arbitrary_bounded(g: &mut Gen, bits: usize) -> Self {
+        loop {
+            let mut buf = <Vec<u8>>::arbitrary(g);
+            buf.truncate((bits + 7) / 8);
+            if buf.len() * 8 > bits {
+                buf[0] &= (1 << (bits % 8)) - 1;
+            }
+
+            if !buf.is_empty() && buf[0] != 0 {
+                break MPI::new(&buf);
+            }
+        }
+    }
+
+    /// Returns an arbitrary odd MPI larger than one, of at most
+    /// `bits` bits.
+    fn arbitrary_odd(g: &mut Gen, bits: usize) -> Self {
+        let mut buf = Self::arbitrary_bounded(g, bits).value().to_vec();
+        *buf.last_mut().expect("not empty") |= 1;
+        if buf == [1] {
+            buf[0] = 3;
+        }
+        MPI::new(&buf)
+    }
+
+    /// Returns an arbitrary, well-formed point on `curve`.
+    fn arbitrary_point(g: &mut Gen, curve: &Curve) -> Self {
+        let (prefix, len) = match curve {
+            Curve::Ed25519 | Curve::Cv25519 => (0x40, 32),
+            _ => match curve.field_size() {
+                Ok(size) => (0x04, 2 * size),
+                Err(_) => return Self::arbitrary_bounded(
+                    g, crate::parse::mpis::MAX_ECC_BITS),
+            },
+        };
+
+        let mut buf = vec![prefix];
+        buf.extend((0..len).map(|_| u8::arbitrary(g)));
+        MPI::new(&buf)
+    }
+}
+
 impl PartialOrd for MPI {
     fn partial_cmp(&self, other: &MPI) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -632,6 +677,19 @@ impl PublicKey {
             Unknown { .. } => None,
         }
     }
+
+    /// Returns the curve, if any.
+    ///
+    /// Returns `None` for keys that are not ECC keys.
+    pub fn curve(&self) -> Option<&Curve> {
+        use self::PublicKey::*;
+        match self {
+            EdDSA { ref curve, .. } => Some(curve),
+            ECDSA { ref curve, .. } => Some(curve),
+            ECDH { ref curve, .. } => Some(curve),
+            RSA { .. } | DSA { .. } | ElGamal { .. } | Unknown { .. } => None,
+        }
+    }
 }
 
 impl Hash for PublicKey {
@@ -641,46 +699,74 @@ impl Hash for PublicKey {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for PublicKey {
     fn arbitrary(g: &mut Gen) -> Self {
         use self::PublicKey::*;
         use crate::arbitrary_helper::gen_arbitrary_from_range;
 
+        // Only well-formed MPIs survive a roundtrip, see
+        // parse::mpis.
+        use crate::parse::mpis::{MAX_FF_BITS, MAX_DSA_Q_BITS};
+        let ff = |g: &mut Gen| MPI::arbitrary_bounded(g, MAX_FF_BITS);
+
         match gen_arbitrary_from_range(0..6, g) {
             0 => RSA {
-                e: MPI::arbitrary(g),
-                n: MPI::arbitrary(g),
+                e: MPI::arbitrary_odd(g, MAX_FF_BITS),
+                n: MPI::arbitrary_odd(g, MAX_FF_BITS),
             },
 
-            1 => DSA {
-                p: MPI::arbitrary(g),
-                q: MPI::arbitrary(g),
-                g: MPI::arbitrary(g),
-                y: MPI::arbitrary(g),
+            1 => {
+                let p = ff(g);
+                let bits = p.bits();
+                DSA {
+                    p,
+                    q: MPI::arbitrary_bounded(g, MAX_DSA_Q_BITS),
+                    g: MPI::arbitrary_bounded(g, bits),
+                    y: MPI::arbitrary_bounded(g, bits),
+                }
             },
 
-            2 => ElGamal {
-                p: MPI::arbitrary(g),
-                g: MPI::arbitrary(g),
-                y: MPI::arbitrary(g),
+            2 => {
+                let p = ff(g);
+                let bits = p.bits();
+                ElGamal {
+                    p,
+                    g: MPI::arbitrary_bounded(g, bits),
+                    y: MPI::arbitrary_bounded(g, bits),
+                }
             },
 
-            3 => EdDSA {
-                curve: Curve::arbitrary(g),
-                q: MPI::arbitrary(g),
+            3 => {
+                let curve = Curve::arbitrary(g);
+                EdDSA {
+                    q: MPI::arbitrary_point(g, &curve),
+                    curve,
+                }
             },
 
-            4 => ECDSA {
-                curve: Curve::arbitrary(g),
-                q: MPI::arbitrary(g),
+            4 => {
+                let curve = Curve::arbitrary(g);
+                ECDSA {
+                    q: MPI::arbitrary_point(g, &curve),
+                    curve,
+                }
             },
 
-            5 => ECDH {
-                curve: Curve::arbitrary(g),
-                q: MPI::arbitrary(g),
-                hash: HashAlgorithm::arbitrary(g),
-                sym: SymmetricAlgorithm::arbitrary(g),
+            5 => {
+                let curve = Curve::arbitrary(g);
+                ECDH {
+                    q: MPI::arbitrary_point(g, &curve),
+                    curve,
+                    // Only acceptable parameters survive a roundtrip,
+                    // see crypto::ecdh::check_kdf_parameters.
+                    hash: *g.choose(&[HashAlgorithm::SHA256,
+                                      HashAlgorithm::SHA384,
+                                      HashAlgorithm::SHA512]).unwrap(),
+                    sym: *g.choose(&[SymmetricAlgorithm::AES128,
+                                     SymmetricAlgorithm::AES192,
+                                     SymmetricAlgorithm::AES256]).unwrap(),
+                }
             },
 
             _ => unreachable!(),
@@ -907,7 +993,7 @@ impl Hash for SecretKeyMaterial {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl SecretKeyMaterial {
     pub(crate) fn arbitrary_for(g: &mut Gen, pk: PublicKeyAlgorithm) -> Result<Self> {
         use self::PublicKeyAlgorithm::*;
@@ -945,7 +1031,7 @@ impl SecretKeyMaterial {
         }
     }
 }
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for SecretKeyMaterial {
     fn arbitrary(g: &mut Gen) -> Self {
         let pk = *g.choose(&crate::types::PUBLIC_KEY_ALGORITHM_VARIANTS)
@@ -1055,23 +1141,25 @@ impl Hash for Ciphertext {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Ciphertext {
     fn arbitrary(g: &mut Gen) -> Self {
         use crate::arbitrary_helper::gen_arbitrary_from_range;
 
+        use crate::parse::mpis::{MAX_FF_BITS, MAX_ECC_BITS};
+
         match gen_arbitrary_from_range(0..3, g) {
             0 => Ciphertext::RSA {
-                c: MPI::arbitrary(g),
+                c: MPI::arbitrary_bounded(g, MAX_FF_BITS),
             },
 
             1 => Ciphertext::ElGamal {
-                e: MPI::arbitrary(g),
-                c: MPI::arbitrary(g)
+                e: MPI::arbitrary_bounded(g, MAX_FF_BITS),
+                c: MPI::arbitrary_bounded(g, MAX_FF_BITS)
             },
 
             2 => Ciphertext::ECDH {
-                e: MPI::arbitrary(g),
+                e: MPI::arbitrary_bounded(g, MAX_ECC_BITS),
                 key: {
                     let mut k = <Vec<u8>>::arbitrary(g);
                     k.truncate(255);
@@ -1150,32 +1238,51 @@ impl Hash for Signature {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Signature {
     fn arbitrary(g: &mut Gen) -> Self {
-        use crate::arbitrary_helper::gen_arbitrary_from_range;
+        use crate::PublicKeyAlgorithm::*;
 
-        match gen_arbitrary_from_range(0..4, g) {
-            0 => Signature::RSA  {
-                s: MPI::arbitrary(g),
-            },
+        let pk = *g.choose(&[RSAEncryptSign, DSA, EdDSA, ECDSA])
+            .expect("not empty");
+        Self::arbitrary_for(g, pk).expect("only known variants")
+    }
+}
 
-            1 => Signature::DSA {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
+#[cfg(any(test, feature = "quickcheck"))]
+impl Signature {
+    /// Returns an arbitrary signature for the given algorithm.
+    ///
+    /// Only well-formed MPIs survive a roundtrip, see parse::mpis.
+    pub(crate) fn arbitrary_for(g: &mut Gen, pk: PublicKeyAlgorithm)
+                                -> Result<Self>
+    {
+        use crate::PublicKeyAlgorithm::*;
+        use crate::parse::mpis::{MAX_FF_BITS, MAX_DSA_Q_BITS, MAX_ECC_BITS};
 
-            2 => Signature::EdDSA  {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
+        #[allow(deprecated)]
+        match pk {
+            RSAEncryptSign | RSASign => Ok(Signature::RSA  {
+                s: MPI::arbitrary_bounded(g, MAX_FF_BITS),
+            }),
 
-            3 => Signature::ECDSA  {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
+            DSA => Ok(Signature::DSA {
+                r: MPI::arbitrary_bounded(g, MAX_DSA_Q_BITS),
+                s: MPI::arbitrary_bounded(g, MAX_DSA_Q_BITS),
+            }),
 
-            _ => unreachable!(),
+            EdDSA => Ok(Signature::EdDSA  {
+                r: MPI::arbitrary_bounded(g, MAX_ECC_BITS),
+                s: MPI::arbitrary_bounded(g, MAX_ECC_BITS),
+            }),
+
+            ECDSA => Ok(Signature::ECDSA  {
+                r: MPI::arbitrary_bounded(g, MAX_ECC_BITS),
+                s: MPI::arbitrary_bounded(g, MAX_ECC_BITS),
+            }),
+
+            _ => Err(Error::InvalidArgument(
+                format!("not a signature algorithm: {}", pk)).into()),
         }
     }
 }
@@ -0,0 +1,289 @@
+//! Pluggable sources of randomness.
+//!
+//! By default, random data is produced by the cryptographic
+//! backend's CSPRNG.  Using [`set_random_source`], this can be
+//! replaced by a custom source, e.g., a hardware security module.
+
+use std::sync::RwLock;
+
+use crate::{
+    Result,
+    crypto::{
+        backend::{Backend, interface::Asymmetric},
+        mem::Protected,
+    },
+};
+
+/// A source of random data.
+///
+/// See [`set_random_source`].
+pub trait RandomSource: Send + Sync {
+    /// Fills `buf` with random data.
+    ///
+    /// The data must be produced by a cryptographically secure
+    /// random number generator.  It is used for session keys, salts,
+    /// nonces, and to derive long-term secret keys from.
+    fn random(&self, buf: &mut [u8]) -> Result<()>;
+}
+
+/// The installed random source, if any.
+static RANDOM_SOURCE: RwLock<Option<Box<dyn RandomSource>>> =
+    RwLock::new(None);
+
+/// Replaces the source of randomness.
+///
+/// Installs `source` as the process-wide source of random data, or,
+/// if `source` is `None`, reverts to the cryptographic backend's
+/// CSPRNG.  Returns the previously installed source.
+///
+/// The source is used by [`random`] and [`try_random`], and hence
+/// for everything built on top of them, like [`SessionKey::new`],
+/// salts, initialization vectors, and padding.  With every
+/// cryptographic backend, it is also used to generate Ed25519 and
+/// Curve25519 keys, including the ephemeral keys used to encrypt to
+/// Curve25519 keys.  Note that Ed25519 signatures are deterministic
+/// and don't consume random data.
+///
+/// The RustCrypto and Nettle backends draw all of their random data
+/// from the source.  This includes generating RSA, DSA, and NIST
+/// curve keys, the ephemeral keys used to encrypt to NIST curve
+/// keys, the nonces of ECDSA and DSA signatures, the padding of RSA
+/// ciphertexts, and, with Nettle, the blinding used when decrypting
+/// RSA ciphertexts.
+///
+/// # Exceptions
+///
+/// The other backends, i.e. OpenSSL, Botan, and Windows CNG, use
+/// their own CSPRNG for the following operations:
+///
+///   - Generating RSA, DSA, ElGamal, and NIST curve keys.
+///   - Generating the ephemeral keys used to encrypt to NIST curve
+///     keys.
+///   - Creating signatures that require a random nonce, like ECDSA
+///     and DSA signatures.
+///   - Encrypting to RSA and ElGamal keys.
+///
+/// # Errors
+///
+/// If the source fails, operations that can return an error return
+/// it.  Operations that can't return an error, like
+/// [`SessionKey::new`] and [`random`], panic.
+///
+///   [`random`]: super::random()
+///   [`try_random`]: super::try_random()
+///   [`SessionKey::new`]: super::SessionKey::new()
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::Read;
+/// use std::sync::Mutex;
+///
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::crypto::{self, RandomSource, SessionKey};
+///
+/// /// Reads random data from a device.
+/// struct Device(Mutex<File>);
+///
+/// impl RandomSource for Device {
+///     fn random(&self, buf: &mut [u8]) -> openpgp::Result<()> {
+///         self.0.lock().unwrap().read_exact(buf)?;
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() -> openpgp::Result<()> {
+/// let device = Device(Mutex::new(File::open("/dev/hwrng")?));
+/// crypto::set_random_source(Some(Box::new(device)));
+///
+/// // The session key is read from the device.
+/// let sk = SessionKey::new(32);
+/// # Ok(()) }
+/// ```
+pub fn set_random_source(source: Option<Box<dyn RandomSource>>)
+                         -> Option<Box<dyn RandomSource>>
+{
+    let mut guard = RANDOM_SOURCE.write().unwrap_or_else(|e| e.into_inner());
+    std::mem::replace(&mut *guard, source)
+}
+
+/// Fills `buf` with random data from the installed source.
+///
+/// Returns `None` if no source is installed.
+pub(crate) fn random(buf: &mut [u8]) -> Option<Result<()>> {
+    RANDOM_SOURCE.read().unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|source| source.random(buf))
+}
+
+/// Returns whether a random source is installed.
+pub(crate) fn installed() -> bool {
+    RANDOM_SOURCE.read().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// Generates an X25519 key pair.
+///
+/// Like [`Asymmetric::x25519_generate_key`], but honors the installed
+/// random source.
+pub(crate) fn x25519_generate_key() -> Result<(Protected, [u8; 32])> {
+    if installed() {
+        let mut secret: Protected = vec![0; 32].into();
+        super::try_random(&mut secret[..])?;
+        let public = Backend::x25519_derive_public(&secret)?;
+        Ok((secret, public))
+    } else {
+        Backend::x25519_generate_key()
+    }
+}
+
+/// Generates an Ed25519 key pair.
+///
+/// Like [`Asymmetric::ed25519_generate_key`], but honors the
+/// installed random source.
+pub(crate) fn ed25519_generate_key() -> Result<(Protected, [u8; 32])> {
+    if installed() {
+        let mut secret: Protected = vec![0; 32].into();
+        super::try_random(&mut secret[..])?;
+        let public = Backend::ed25519_derive_public(&secret)?;
+        Ok((secret, public))
+    } else {
+        Backend::ed25519_generate_key()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::packet::Key;
+    use crate::packet::key::{self, Key4};
+    use crate::types::Curve;
+
+    /// Returns whether the backend draws all of its random data from
+    /// the installed source.
+    fn uses_source_for_everything() -> bool {
+        let backend = crate::crypto::backend();
+        backend.starts_with("RustCrypto") || backend.starts_with("Nettle")
+    }
+
+    /// Counts requests made by the given thread, and delegates to
+    /// the backend.
+    ///
+    /// Other tests run concurrently, so we must produce proper random
+    /// data.
+    struct Counting(Arc<AtomicUsize>, std::thread::ThreadId);
+
+    impl RandomSource for Counting {
+        fn random(&self, buf: &mut [u8]) -> Result<()> {
+            use crate::crypto::backend::interface::Backend as _;
+            if std::thread::current().id() == self.1 {
+                self.0.fetch_add(buf.len(), Ordering::SeqCst);
+            }
+            Backend::random(buf)
+        }
+    }
+
+    #[test]
+    fn random_source() -> Result<()> {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let previous = set_random_source(
+            Some(Box::new(Counting(counter.clone(),
+                                   std::thread::current().id()))));
+
+        let result = (|| -> Result<()> {
+            let before = counter.load(Ordering::SeqCst);
+            let _ = crate::crypto::SessionKey::new(32);
+            assert!(counter.load(Ordering::SeqCst) >= before + 32);
+
+            for (for_signing, curve) in [(true, Curve::Ed25519),
+                                         (false, Curve::Cv25519)]
+            {
+                let before = counter.load(Ordering::SeqCst);
+                let key: Key4<key::SecretParts, key::PrimaryRole> =
+                    Key4::generate_ecc(for_signing, curve)?;
+                assert!(counter.load(Ordering::SeqCst) >= before + 32);
+
+                // The public key matches the secret.
+                let mut pair = key.clone().into_keypair()?;
+                let key = Key::from(key);
+                if for_signing {
+                    use crate::crypto::Signer;
+                    let digest = [0; 32];
+                    let sig = pair.sign(crate::types::HashAlgorithm::SHA256,
+                                        &digest)?;
+                    key.verify(&sig, crate::types::HashAlgorithm::SHA256,
+                               &digest)?;
+                } else {
+                    use crate::crypto::Decryptor;
+                    let sk = crate::crypto::SessionKey::new(32);
+                    let ciphertext = key.encrypt(&sk)?;
+                    assert_eq!(pair.decrypt(&ciphertext, Some(32))?, sk);
+                }
+            }
+
+            if uses_source_for_everything() {
+                use crate::crypto::Signer;
+
+                // The RustCrypto and Nettle backends use the source
+                // for everything.
+                let before = counter.load(Ordering::SeqCst);
+                let key: Key4<key::SecretParts, key::PrimaryRole> =
+                    Key4::generate_ecc(true, Curve::NistP256)?;
+                assert!(counter.load(Ordering::SeqCst) >= before + 32);
+
+                let mut pair = key.into_keypair()?;
+                let before = counter.load(Ordering::SeqCst);
+                pair.sign(crate::types::HashAlgorithm::SHA256, &[0; 32])?;
+                assert!(counter.load(Ordering::SeqCst) >= before + 32);
+            }
+            Ok(())
+        })();
+
+        set_random_source(previous);
+        result
+    }
+
+    /// Fails on the given thread, and delegates to the backend on
+    /// all others.
+    struct FailOn(std::thread::ThreadId);
+
+    impl RandomSource for FailOn {
+        fn random(&self, buf: &mut [u8]) -> Result<()> {
+            use crate::crypto::backend::interface::Backend as _;
+            if std::thread::current().id() == self.0 {
+                Err(crate::Error::InvalidOperation("no entropy".into())
+                    .into())
+            } else {
+                Backend::random(buf)
+            }
+        }
+    }
+
+    #[test]
+    fn random_source_failure() -> Result<()> {
+        let previous = set_random_source(
+            Some(Box::new(FailOn(std::thread::current().id()))));
+
+        let result = (|| -> Result<()> {
+            let mut buf = [0; 32];
+            assert!(crate::crypto::try_random(&mut buf).is_err());
+            assert!(crate::crypto::s2k::S2K::new_iterated(
+                crate::types::HashAlgorithm::SHA256, 0x10000).is_err());
+            assert!(Key4::<key::SecretParts, key::PrimaryRole>
+                    ::generate_ecc(true, Curve::Ed25519).is_err());
+            if crate::crypto::backend().starts_with("Nettle") {
+                // Nettle ignores the error, but the key is discarded.
+                assert!(Key4::<key::SecretParts, key::PrimaryRole>
+                        ::generate_ecc(true, Curve::NistP256).is_err());
+            }
+            Ok(())
+        })();
+
+        set_random_source(previous);
+        result
+    }
+}
@@ -15,7 +15,7 @@ use crate::crypto::hash::Digest;
 
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 /// String-to-Key (S2K) specifiers.
@@ -164,7 +164,7 @@ impl S2K {
                 approx_hash_bytes)).into())
         } else {
             let mut salt = [0u8; 8];
-            crate::crypto::random(&mut salt);
+            crate::crypto::try_random(&mut salt)?;
             Ok(S2K::Iterated {
                 hash,
                 salt,
@@ -387,7 +387,7 @@ impl fmt::Display for S2K {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for S2K {
     fn arbitrary(g: &mut Gen) -> Self {
         use crate::arbitrary_helper::*;
@@ -0,0 +1,483 @@
+//! Experimental support for version 2 SEIPD packets.
+//!
+//! The [crypto refresh] introduces a second version of the
+//! Symmetrically Encrypted and Integrity Protected Data packet.
+//! Instead of using CFB mode and a modification detection code, the
+//! data is encrypted using an AEAD mode in chunks.  The key and nonce
+//! used for that are derived from the session key and a random salt
+//! using HKDF.  See [Section 5.13.2 of RFC 9580] for details.
+//!
+//!   [crypto refresh]: https://www.rfc-editor.org/rfc/rfc9580.html
+//!   [Section 5.13.2 of RFC 9580]: https://www.rfc-editor.org/rfc/rfc9580.html#section-5.13.2
+//!
+//! This module is only available if the `experimental-seipdv2`
+//! feature is enabled.  It is meant to test interoperability with
+//! other implementations that adopt the new format early.  The API
+//! may change or go away without notice.
+//!
+//! # Limitations
+//!
+//! This module is a standalone primitive that operates on packet
+//! bodies.  It is not wired into the rest of the library:
+//!
+//!   - The [parser] doesn't know about version 2 SEIPD packets.
+//!     They are parsed as [`Unknown`] packets, and
+//!     [`PacketParser::decrypt`] can't decrypt them.
+//!
+//!   - The [streaming decryptor] can't decrypt them.
+//!
+//!   - The [streaming encryptor] never emits them.
+//!
+//! Callers have to frame and unframe the packets themselves, see
+//! the example below, and parse the decrypted message separately.
+//!
+//!   [parser]: crate::parse::PacketParser
+//!   [`Unknown`]: crate::packet::Unknown
+//!   [`PacketParser::decrypt`]: crate::parse::PacketParser::decrypt()
+//!   [streaming decryptor]: crate::parse::stream::Decryptor
+//!   [streaming encryptor]: crate::serialize::stream::Encryptor2
+//!
+//! # Examples
+//!
+//! ```
+//! # fn main() -> sequoia_openpgp::Result<()> {
+//! use sequoia_openpgp as openpgp;
+//! use openpgp::crypto::SessionKey;
+//! use openpgp::crypto::seipdv2::{self, Header};
+//! use openpgp::packet::{Packet, Tag, Unknown};
+//! use openpgp::parse::Parse;
+//! use openpgp::serialize::MarshalInto;
+//! use openpgp::types::{AEADAlgorithm, SymmetricAlgorithm};
+//!
+//! let cipher = SymmetricAlgorithm::AES256;
+//! let session_key = SessionKey::new(cipher.key_size()?);
+//!
+//! // Encrypt a message, and frame it as a packet.
+//! let header = Header::new(cipher, AEADAlgorithm::EAX, 4096)?;
+//! let body = seipdv2::encrypt(&header, &session_key, b"Hello world.")?;
+//! let mut packet = Unknown::new(Tag::SEIP, anyhow::anyhow!("SEIPDv2"));
+//! packet.set_body(body);
+//! let bytes = Packet::from(packet).to_vec()?;
+//!
+//! // Then, parse and decrypt it.
+//! if let Packet::Unknown(packet) = Packet::from_bytes(&bytes)? {
+//!     assert_eq!(packet.tag(), Tag::SEIP);
+//!     let plaintext = seipdv2::decrypt(&session_key, packet.body())?;
+//!     assert_eq!(plaintext, b"Hello world.");
+//! } else {
+//!     unreachable!("version 2 SEIPD packets are not parsed");
+//! }
+//! # Ok(()) }
+//! ```
+
+use std::io::{Read, Write};
+
+use crate::{
+    Error,
+    Result,
+    crypto::{
+        self,
+        SessionKey,
+        aead::{self, Schedule},
+        backend::{Backend, interface::Kdf},
+        mem::Protected,
+    },
+    types::{
+        AEADAlgorithm,
+        SymmetricAlgorithm,
+    },
+};
+
+/// The size of the salt in bytes.
+pub const SALT_SIZE: usize = 32;
+
+/// The packet tag in new format, used in the associated data.
+const PACKET_TAG: u8 = 0xd2;
+
+/// The packet version.
+const VERSION: u8 = 2;
+
+/// The size of the header in bytes.
+const HEADER_SIZE: usize = 4 + SALT_SIZE;
+
+/// The parameters of a version 2 SEIPD packet.
+///
+/// This is the unencrypted prefix of the packet's body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    sym_algo: SymmetricAlgorithm,
+    aead: AEADAlgorithm,
+    chunk_size: usize,
+    salt: [u8; SALT_SIZE],
+}
+assert_send_and_sync!(Header);
+
+impl Header {
+    /// Returns a header with a random salt.
+    ///
+    /// `chunk_size` must be a power of two between 64 bytes and 4
+    /// MiB.
+    pub fn new(sym_algo: SymmetricAlgorithm, aead: AEADAlgorithm,
+               chunk_size: usize)
+               -> Result<Self>
+    {
+        let mut salt = [0; SALT_SIZE];
+        crypto::try_random(&mut salt)?;
+        Self::with_salt(sym_algo, aead, chunk_size, salt)
+    }
+
+    /// Returns a header with the given salt.
+    ///
+    /// This is useful to reproduce test vectors.  The salt must be
+    /// unique, use [`Header::new`] to generate a random one.
+    pub fn with_salt(sym_algo: SymmetricAlgorithm, aead: AEADAlgorithm,
+                     chunk_size: usize, salt: [u8; SALT_SIZE])
+                     -> Result<Self>
+    {
+        if ! chunk_size.is_power_of_two()
+            || ! (1 << 6..=1 << 22).contains(&chunk_size)
+        {
+            return Err(Error::InvalidArgument(
+                format!("Invalid AEAD chunk size: {}", chunk_size)).into());
+        }
+        sym_algo.key_size()?;
+        aead.nonce_size()?;
+
+        Ok(Header { sym_algo, aead, chunk_size, salt })
+    }
+
+    /// Parses the header at the start of a packet body.
+    ///
+    /// Returns the header and the size of the header in bytes.  The
+    /// encrypted data follows the header.
+    pub fn parse(body: &[u8]) -> Result<(Self, usize)> {
+        if body.len() < HEADER_SIZE {
+            return Err(Error::MalformedPacket(
+                "Truncated SEIPD packet".into()).into());
+        }
+        if body[0] != VERSION {
+            return Err(Error::MalformedPacket(
+                format!("Unsupported SEIPD version {}", body[0])).into());
+        }
+        if body[3] > 16 {
+            return Err(Error::MalformedPacket(
+                format!("Invalid AEAD chunk size octet: {}", body[3]))
+                       .into());
+        }
+
+        let mut salt = [0; SALT_SIZE];
+        salt.copy_from_slice(&body[4..HEADER_SIZE]);
+        let header = Self::with_salt(body[1].into(), body[2].into(),
+                                     1 << (body[3] + 6), salt)?;
+        Ok((header, HEADER_SIZE))
+    }
+
+    /// Writes the header.
+    pub fn serialize(&self, o: &mut dyn Write) -> Result<()> {
+        o.write_all(&self.prefix())?;
+        o.write_all(&self.salt)?;
+        Ok(())
+    }
+
+    /// Returns the symmetric algorithm.
+    pub fn symmetric_algo(&self) -> SymmetricAlgorithm {
+        self.sym_algo
+    }
+
+    /// Returns the AEAD algorithm.
+    pub fn aead_algo(&self) -> AEADAlgorithm {
+        self.aead
+    }
+
+    /// Returns the chunk size.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns the salt.
+    pub fn salt(&self) -> &[u8; SALT_SIZE] {
+        &self.salt
+    }
+
+    /// Derives the message key and the initialization vector.
+    ///
+    /// The message key and the initialization vector are derived
+    /// from the session key using HKDF-SHA256.  The initialization
+    /// vector is eight bytes shorter than the AEAD algorithm's nonce,
+    /// the chunk index makes up the rest of each chunk's nonce.
+    pub fn derive(&self, session_key: &SessionKey)
+                  -> Result<(SessionKey, Vec<u8>)>
+    {
+        let key_size = self.sym_algo.key_size()?;
+        if session_key.len() != key_size {
+            return Err(Error::InvalidArgument(
+                format!("Bad session key size: expected {}, got {}",
+                        key_size, session_key.len())).into());
+        }
+
+        let iv_size = self.aead.nonce_size()? - 8;
+        let mut okm: Protected = vec![0; key_size + iv_size].into();
+        Backend::hkdf_sha256(session_key, &self.salt, &self.associated_data(),
+                             &mut okm)?;
+
+        Ok((SessionKey::from(&okm[..key_size]), okm[key_size..].to_vec()))
+    }
+
+    /// Returns the version, the algorithms, and the chunk size octet.
+    fn prefix(&self) -> [u8; 4] {
+        [
+            VERSION,
+            self.sym_algo.into(),
+            self.aead.into(),
+            self.chunk_size.trailing_zeros() as u8 - 6,
+        ]
+    }
+
+    /// Returns the packet tag, the version, the algorithms, and the
+    /// chunk size octet.
+    ///
+    /// This is used both as HKDF info, and as associated data.
+    fn associated_data(&self) -> [u8; 5] {
+        let prefix = self.prefix();
+        [PACKET_TAG, prefix[0], prefix[1], prefix[2], prefix[3]]
+    }
+
+    /// Returns the message key and the nonce schedule.
+    fn schedule(&self, session_key: &SessionKey)
+                -> Result<(SessionKey, SEIPv2Schedule)>
+    {
+        let (key, iv) = self.derive(session_key)?;
+        Ok((key, SEIPv2Schedule {
+            iv: iv.into(),
+            ad: self.associated_data(),
+        }))
+    }
+}
+
+/// Encrypts `plaintext`.
+///
+/// Returns the body of a version 2 SEIPD packet.  The plaintext
+/// should be an OpenPGP message, e.g. a serialized literal data
+/// packet.
+pub fn encrypt(header: &Header, session_key: &SessionKey, plaintext: &[u8])
+               -> Result<Vec<u8>>
+{
+    let mut body = Vec::with_capacity(
+        HEADER_SIZE + plaintext.len()
+            + (plaintext.len() / header.chunk_size + 2)
+            * header.aead.digest_size()?);
+    header.serialize(&mut body)?;
+
+    let (key, schedule) = header.schedule(session_key)?;
+    let mut encryptor = aead::Encryptor::new(
+        header.sym_algo, header.aead, header.chunk_size, schedule, key,
+        body)?;
+    encryptor.write_all(plaintext)?;
+    encryptor.finish()
+}
+
+/// Decrypts the body of a version 2 SEIPD packet.
+///
+/// Returns the plaintext.  Returns an error if the data has been
+/// tampered with, or has been truncated.
+pub fn decrypt(session_key: &SessionKey, body: &[u8]) -> Result<Vec<u8>> {
+    let (header, header_size) = Header::parse(body)?;
+    let (key, schedule) = header.schedule(session_key)?;
+
+    let mut decryptor = aead::Decryptor::new(
+        header.sym_algo, header.aead, header.chunk_size, schedule, key,
+        &body[header_size..])?;
+    let mut plaintext = Vec::new();
+    decryptor.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// Schedules nonce and additional authenticated data for version 2
+/// SEIPD packets.
+struct SEIPv2Schedule {
+    iv: Box<[u8]>,
+    ad: [u8; 5],
+}
+
+impl SEIPv2Schedule {
+    /// Returns the nonce for the chunk with the given index.
+    fn nonce(&self, index: u64) -> ([u8; aead::MAX_NONCE_LEN], usize) {
+        // The nonce is the initialization vector followed by the
+        // chunk index in big endian.
+        let mut nonce = [0; aead::MAX_NONCE_LEN];
+        let nonce_len = self.iv.len() + 8;
+        nonce[..self.iv.len()].copy_from_slice(&self.iv);
+        nonce[self.iv.len()..nonce_len].copy_from_slice(&index.to_be_bytes());
+        (nonce, nonce_len)
+    }
+}
+
+impl Schedule for SEIPv2Schedule {
+    fn next_chunk<F, R>(&self, index: u64, mut fun: F) -> R
+    where
+        F: FnMut(&[u8], &[u8]) -> R,
+    {
+        let (nonce, nonce_len) = self.nonce(index);
+        fun(&nonce[..nonce_len], &self.ad)
+    }
+
+    fn final_chunk<F, R>(&self, index: u64, length: u64, mut fun: F) -> R
+    where
+        F: FnMut(&[u8], &[u8]) -> R,
+    {
+        // The associated data of the final chunk also includes the
+        // total number of plaintext bytes.
+        let mut ad = [0; 5 + 8];
+        ad[..5].copy_from_slice(&self.ad);
+        ad[5..].copy_from_slice(&length.to_be_bytes());
+
+        let (nonce, nonce_len) = self.nonce(index);
+        fun(&nonce[..nonce_len], &ad)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::fmt::hex;
+
+    /// Checks that we can decrypt, and reproduce the given
+    /// packet body.
+    fn check_vector(session_key: &str, body: &str, key: &str, iv: &str)
+                    -> Result<()> {
+        let session_key: SessionKey = hex::decode(session_key)?.into();
+        let body = hex::decode(body)?;
+
+        let (header, header_size) = Header::parse(&body)?;
+        if ! header.aead_algo().is_supported() {
+            eprintln!("Skipping {}", header.aead_algo());
+            return Ok(());
+        }
+
+        let (k, i) = header.derive(&session_key)?;
+        assert_eq!(&k[..], &hex::decode(key)?[..]);
+        assert_eq!(i, hex::decode(iv)?);
+
+        // The plaintext is a literal data packet containing "Hello,
+        // world!", followed by a padding packet.
+        let plaintext = decrypt(&session_key, &body)?;
+        assert_eq!(&plaintext[..21], &hex::decode(
+            "cb1362000000000048656c6c6f2c20776f726c6421")?[..]);
+        assert_eq!(plaintext[21], 0xd5);
+        assert_eq!(plaintext.len(), body.len() - header_size - 2 * 16);
+
+        assert_eq!(encrypt(&header, &session_key, &plaintext)?, body);
+        Ok(())
+    }
+
+    /// Sample v2 SEIPD packet using EAX, see [Appendix A.9 of RFC
+    /// 9580].
+    ///
+    ///   [Appendix A.9 of RFC 9580]: https://www.rfc-editor.org/rfc/rfc9580.html#appendix-A.9
+    #[test]
+    fn rfc9580_eax() -> Result<()> {
+        check_vector(
+            "3881bafe985412459b86c36f98cb9a5e",
+            "02070106\
+             9ff90e3b321964f3a42913c8dcc66193\
+             25015227efb7eaeaa49f04c2e674175d\
+             4a3d226ed6afcb9ca9ac122c1470e11c\
+             63d4c0ab241c6a938ad48bf99a5a99b9\
+             0bba8325de\
+             61047540258ab7959a95ad051dda96eb\
+             15431dfef5f5e2255ca78261546e339a",
+            "b50422ac1c26be9ddd831d5bbb36b64f",
+            "78b833f2e94a60c0")
+    }
+
+    /// Sample v2 SEIPD packet using GCM, see [Appendix A.11 of RFC
+    /// 9580].
+    ///
+    ///   [Appendix A.11 of RFC 9580]: https://www.rfc-editor.org/rfc/rfc9580.html#appendix-A.11
+    #[test]
+    fn rfc9580_gcm() -> Result<()> {
+        check_vector(
+            "1936fc8568980274bb900d8319360c77",
+            "02070306\
+             fcb94490bcb98bbdc9d106c609026694\
+             0f72e89edc21b5596b1576b101ed0f9f\
+             fc6fc6d65bbfd24dcd0790966e6d1e85\
+             a30053784cb1d8b6a0699ef12155a7b2\
+             ad6258531b\
+             57651fd7777912fa95e35d9b40216f69\
+             a4c248db28ff4331f1632907399e6ff9",
+            "ea1438803cb8a47740ce9b54c338778d",
+            "4d2bdc2b")
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        for aead in [AEADAlgorithm::EAX, AEADAlgorithm::OCB,
+                     AEADAlgorithm::GCM]
+        {
+            if ! aead.is_supported() {
+                eprintln!("Skipping {}", aead);
+                continue;
+            }
+
+            let cipher = SymmetricAlgorithm::AES128;
+            let sk = SessionKey::new(cipher.key_size()?);
+            for len in [0, 1, 63, 64, 65, 200] {
+                let plaintext = vec![len as u8; len];
+                let header = Header::new(cipher, aead, 64)?;
+                let body = encrypt(&header, &sk, &plaintext)?;
+
+                // Header, one tag per chunk, and the final tag.
+                let chunks = (len + 63) / 64;
+                assert_eq!(body.len(), HEADER_SIZE + len + (chunks + 1) * 16);
+                assert_eq!(Header::parse(&body)?, (header, HEADER_SIZE));
+                assert_eq!(decrypt(&sk, &body)?, plaintext);
+
+                // Tampering is detected.
+                for i in HEADER_SIZE - 1..body.len() {
+                    let mut tampered = body.clone();
+                    tampered[i] ^= 1;
+                    assert!(decrypt(&sk, &tampered).is_err());
+                }
+
+                // Truncation is detected.
+                assert!(decrypt(&sk, &body[..body.len() - 1]).is_err());
+                if chunks > 1 {
+                    assert!(decrypt(&sk, &body[..body.len() - 16 - 80])
+                            .is_err());
+                }
+
+                // A different key fails.
+                let other = SessionKey::new(cipher.key_size()?);
+                assert!(decrypt(&other, &body).is_err());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn header() -> Result<()> {
+        let header = Header::with_salt(SymmetricAlgorithm::AES256,
+                                       AEADAlgorithm::OCB, 1 << 22,
+                                       [7; SALT_SIZE])?;
+        let mut buf = Vec::new();
+        header.serialize(&mut buf)?;
+        assert_eq!(&buf[..4], &[2, 9, 2, 16]);
+        assert_eq!(&buf[4..], &[7; SALT_SIZE]);
+
+        // Bad chunk sizes.
+        for chunk_size in [0, 32, 100, 1 << 23] {
+            assert!(Header::with_salt(SymmetricAlgorithm::AES256,
+                                      AEADAlgorithm::OCB, chunk_size,
+                                      [7; SALT_SIZE]).is_err());
+        }
+        buf[3] = 17;
+        assert!(Header::parse(&buf).is_err());
+        buf[3] = 0;
+        buf[0] = 1;
+        assert!(Header::parse(&buf).is_err());
+        assert!(Header::parse(&buf[..HEADER_SIZE - 1]).is_err());
+        Ok(())
+    }
+}
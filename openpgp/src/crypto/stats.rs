@@ -0,0 +1,207 @@
+//! Counters for cryptographic operations.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Whether the counters are updated.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The counters.
+static VERIFICATIONS: AtomicU64 = AtomicU64::new(0);
+static DECRYPTIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES_HASHED: AtomicU64 = AtomicU64::new(0);
+
+/// Statistics about the cryptographic operations performed.
+///
+/// Sequoia can count some of the cryptographic operations it
+/// performs.  Counting is disabled by default, and must be enabled
+/// using [`Statistics::set_enabled`].  The counters are
+/// process-wide, and are never reset.  Long-running programs can use
+/// them to monitor their cryptographic workload, and to detect
+/// anomalies, like a flood of signatures, by periodically looking at
+/// the difference using [`Statistics::since`].
+///
+/// Note: Signatures that are found in the signature verification
+/// cache are not verified again, and are not counted as
+/// verifications.  See [`SignatureVerificationCache`].
+///
+///   [`SignatureVerificationCache`]: crate::packet::signature::cache::SignatureVerificationCache
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::crypto::Statistics;
+/// use openpgp::types::HashAlgorithm;
+///
+/// # fn main() -> openpgp::Result<()> {
+/// Statistics::set_enabled(true);
+/// let before = Statistics::get();
+///
+/// let mut ctx = HashAlgorithm::SHA512.context()?;
+/// ctx.update(b"The quick brown fox jumps over the lazy dog.");
+///
+/// let delta = Statistics::get().since(&before);
+/// // Other threads may hash data at the same time.
+/// assert!(delta.bytes_hashed >= 44);
+/// # Ok(()) }
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// The number of signatures verified.
+    ///
+    /// This includes signatures that turned out to be bad.
+    pub verifications: u64,
+
+    /// The number of public key decryption operations.
+    ///
+    /// This counts attempts to decrypt a PKESK packet using a
+    /// [`Decryptor`], including failed attempts.
+    ///
+    ///   [`Decryptor`]: super::Decryptor
+    pub decryptions: u64,
+
+    /// The number of bytes hashed.
+    ///
+    /// This includes data hashed when verifying and creating
+    /// signatures, computing fingerprints, and checking the integrity
+    /// of encrypted messages.
+    pub bytes_hashed: u64,
+}
+
+impl Statistics {
+    /// Enables or disables counting.
+    ///
+    /// Counting is disabled by default.  While it is disabled, the
+    /// counters are not updated, and hashing doesn't incur any
+    /// overhead.  Hash contexts created while counting is disabled
+    /// don't count the bytes they hash, even if counting is enabled
+    /// later.
+    pub fn set_enabled(enabled: bool) {
+        ENABLED.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether counting is enabled.
+    ///
+    /// See [`Statistics::set_enabled`].
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// Returns the current values of the counters.
+    pub fn get() -> Self {
+        Statistics {
+            verifications: VERIFICATIONS.load(Ordering::Relaxed),
+            decryptions: DECRYPTIONS.load(Ordering::Relaxed),
+            bytes_hashed: BYTES_HASHED.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the operations performed since `earlier` was
+    /// retrieved.
+    pub fn since(&self, earlier: &Statistics) -> Self {
+        Statistics {
+            verifications:
+                self.verifications.saturating_sub(earlier.verifications),
+            decryptions:
+                self.decryptions.saturating_sub(earlier.decryptions),
+            bytes_hashed:
+                self.bytes_hashed.saturating_sub(earlier.bytes_hashed),
+        }
+    }
+}
+
+/// Records a signature verification.
+pub(crate) fn verification() {
+    if Statistics::enabled() {
+        VERIFICATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records a public key decryption.
+pub(crate) fn decryption() {
+    if Statistics::enabled() {
+        DECRYPTIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records that `amount` bytes have been hashed.
+pub(crate) fn hashed(amount: usize) {
+    if Statistics::enabled() {
+        BYTES_HASHED.fetch_add(amount as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::Result;
+    use crate::cert::prelude::*;
+    use crate::crypto::SessionKey;
+    use crate::packet::pkesk::PKESK3;
+    use crate::policy::StandardPolicy;
+    use crate::types::SymmetricAlgorithm;
+
+    // The counters are shared with the other tests, which run
+    // concurrently.  Hence, we can only check lower bounds.  For the
+    // same reason, counting is never disabled again.
+
+    #[test]
+    fn verifications() -> Result<()> {
+        let p = &StandardPolicy::new();
+        Statistics::set_enabled(true);
+        let before = Statistics::get();
+        let (cert, _) = CertBuilder::new()
+            .add_userid("alice@example.org")
+            .generate()?;
+        let vc = cert.with_policy(p, None)?;
+        assert!(vc.primary_userid().is_ok());
+
+        let delta = Statistics::get().since(&before);
+        assert!(delta.verifications >= 1);
+        assert!(delta.bytes_hashed > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn decryptions() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (cert, _) = CertBuilder::new()
+            .add_transport_encryption_subkey()
+            .generate()?;
+        let key = cert.keys().with_policy(p, None)
+            .for_transport_encryption().secret().next().unwrap().key().clone();
+
+        let sk = SessionKey::new(32);
+        let pkesk =
+            PKESK3::for_recipient(SymmetricAlgorithm::AES256, &sk, &key)?;
+        let mut keypair = key.into_keypair()?;
+
+        Statistics::set_enabled(true);
+        let before = Statistics::get();
+        assert!(pkesk.decrypt(&mut keypair, None).is_some());
+        let delta = Statistics::get().since(&before);
+        assert!(delta.decryptions >= 1);
+        Ok(())
+    }
+
+    #[test]
+    fn since() {
+        let earlier = Statistics {
+            verifications: 3,
+            decryptions: 2,
+            bytes_hashed: 100,
+        };
+        let later = Statistics {
+            verifications: 5,
+            decryptions: 2,
+            bytes_hashed: 150,
+        };
+        assert_eq!(later.since(&earlier), Statistics {
+            verifications: 2,
+            decryptions: 0,
+            bytes_hashed: 50,
+        });
+        assert_eq!(earlier.since(&later), Statistics::default());
+    }
+}
@@ -84,12 +84,29 @@ impl<'a> Decryptor<'a> {
     }
 }
 
-// Note: this implementation tries *very* hard to make sure we don't
-// gratuitiously do a short read.  Specifically, if the return value
-// is less than `plaintext.len()`, then it is either because we
-// reached the end of the input or an error occurred.
 impl<'a> io::Read for Decryptor<'a> {
     fn read(&mut self, plaintext: &mut [u8]) -> io::Result<usize> {
+        let n = self.read_helper(plaintext)?;
+
+        // If we are out of data, but the source, e.g. the body of
+        // the encryption container, expected more, the message has
+        // been truncated.  Since we don't know anything about the
+        // integrity protection, no plaintext has been authenticated.
+        if n == 0 && ! plaintext.is_empty() && ! self.source.consummated() {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      crate::Error::TruncatedMessage(0)));
+        }
+
+        Ok(n)
+    }
+}
+
+impl<'a> Decryptor<'a> {
+    // Note: this implementation tries *very* hard to make sure we don't
+    // gratuitiously do a short read.  Specifically, if the return value
+    // is less than `plaintext.len()`, then it is either because we
+    // reached the end of the input or an error occurred.
+    fn read_helper(&mut self, plaintext: &mut [u8]) -> io::Result<usize> {
         let mut pos = 0;
 
         // 1. Copy any buffered data.
@@ -293,6 +310,10 @@ pub struct Encryptor<W: io::Write> {
 }
 assert_send_and_sync!(Encryptor<W> where W: io::Write);
 
+/// The maximum amount of data that the [`Encryptor`] encrypts at
+/// once.
+const ENCRYPTOR_SCRATCH_SIZE: usize = 64 * 1024;
+
 impl<W: io::Write> Encryptor<W> {
     /// Instantiate a new symmetric encryptor.
     pub fn new(algo: SymmetricAlgorithm, key: &[u8], sink: W) -> Result<Self> {
@@ -366,19 +387,23 @@ impl<W: io::Write> io::Write for Encryptor<W> {
             }
         }
 
-        // Then, encrypt all whole blocks.
+        // Then, encrypt all whole blocks.  We do this in pieces of at
+        // most ENCRYPTOR_SCRATCH_SIZE bytes so that the scratch
+        // buffer does not grow with the size of the write, and the
+        // data is still in the cache when it is written out.
         let whole_blocks = (buf.len() / self.block_size) * self.block_size;
-        if whole_blocks > 0 {
-            // Encrypt whole blocks.
-            if self.scratch.len() < whole_blocks {
-                vec_resize(&mut self.scratch, whole_blocks);
+        let piece_size = cmp::max(
+            ENCRYPTOR_SCRATCH_SIZE / self.block_size * self.block_size,
+            self.block_size);
+        for piece in buf[..whole_blocks].chunks(piece_size) {
+            if self.scratch.len() < piece.len() {
+                vec_resize(&mut self.scratch, piece.len());
             }
 
-            self.cipher.encrypt(&mut self.scratch[..whole_blocks],
-                                &buf[..whole_blocks])
+            self.cipher.encrypt(&mut self.scratch[..piece.len()], piece)
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput,
                                             format!("{}", e)))?;
-            inner.write_all(&self.scratch[..whole_blocks])?;
+            inner.write_all(&self.scratch[..piece.len()])?;
         }
 
         // Stash rest for later.
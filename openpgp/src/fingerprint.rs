@@ -1,11 +1,22 @@
 use std::borrow::Borrow;
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::KeyHandle;
 use crate::KeyID;
+use crate::Result;
+use crate::types::HashAlgorithm;
+
+/// The URI scheme for fingerprints.
+///
+/// See [`Fingerprint::to_openpgp4fpr_uri`].
+const OPENPGP4FPR: &str = "OPENPGP4FPR:";
+
+/// Domain separation for [`Fingerprint::verification_code`].
+const VERIFICATION_CODE_CONTEXT: &[u8] =
+    b"OpenPGP fingerprint verification code v1\0";
 
 /// A long identifier for certificates and keys.
 ///
@@ -102,6 +113,8 @@ impl std::str::FromStr for Fingerprint {
     }
 }
 
+impl_serde_using_version_and_hex!(Fingerprint, V4 => 4, V5 => 5);
+
 impl Fingerprint {
     /// Creates a `Fingerprint` from a byte slice in big endian
     /// representation.
@@ -377,6 +390,148 @@ impl Fingerprint {
         ret
     }
 
+    /// Returns the `OPENPGP4FPR:` URI for this fingerprint.
+    ///
+    /// This is the de-facto standard format for exchanging
+    /// fingerprints using QR codes, for instance to verify a
+    /// fingerprint in person.  The URI is upper case so that it can
+    /// be encoded using a QR code's compact alphanumeric mode.
+    ///
+    /// See also [`Fingerprint::from_openpgp4fpr_uri`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// # use sequoia_openpgp as openpgp;
+    /// use openpgp::Fingerprint;
+    ///
+    /// let fp: Fingerprint =
+    ///     "8F17 7771 18A3 3DDA 9BA4  8E62 AACB 3243 6300 52D9".parse()?;
+    /// assert_eq!(fp.to_openpgp4fpr_uri(),
+    ///            "OPENPGP4FPR:8F17777118A33DDA9BA48E62AACB3243630052D9");
+    /// # Ok(()) }
+    /// ```
+    pub fn to_openpgp4fpr_uri(&self) -> String {
+        format!("{}{:X}", OPENPGP4FPR, self)
+    }
+
+    /// Parses an `OPENPGP4FPR:` URI.
+    ///
+    /// The scheme and the fingerprint are case insensitive.  A
+    /// fragment, which some applications use to attach additional
+    /// information, is ignored.
+    ///
+    /// See also [`Fingerprint::to_openpgp4fpr_uri`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// # use sequoia_openpgp as openpgp;
+    /// use openpgp::Fingerprint;
+    ///
+    /// let fp = Fingerprint::from_openpgp4fpr_uri(
+    ///     "openpgp4fpr:8f17777118a33dda9ba48e62aacb3243630052d9#a=alice")?;
+    /// assert_eq!(fp.to_hex(), "8F17777118A33DDA9BA48E62AACB3243630052D9");
+    ///
+    /// assert!(Fingerprint::from_openpgp4fpr_uri(
+    ///     "8F17777118A33DDA9BA48E62AACB3243630052D9").is_err());
+    /// # Ok(()) }
+    /// ```
+    pub fn from_openpgp4fpr_uri(uri: &str) -> Result<Self> {
+        let uri = uri.trim();
+        let fp = uri.get(..OPENPGP4FPR.len())
+            .filter(|scheme| scheme.eq_ignore_ascii_case(OPENPGP4FPR))
+            .map(|_| &uri[OPENPGP4FPR.len()..])
+            .ok_or_else(|| crate::Error::InvalidArgument(
+                format!("Not an OPENPGP4FPR URI: {:?}", uri)))?;
+        let fp = fp.split('#').next().unwrap_or(fp);
+
+        match fp.parse()? {
+            Fingerprint::Invalid(_) => Err(crate::Error::InvalidArgument(
+                format!("Invalid fingerprint in URI: {:?}", uri)).into()),
+            fp => Ok(fp),
+        }
+    }
+
+    /// Derives a numeric code for verifying two fingerprints.
+    ///
+    /// When two people meet to verify each other's certificates,
+    /// comparing a short numeric code is easier than comparing two
+    /// fingerprints.  This derives a code from both fingerprints.
+    /// The code is the same regardless of the order of the
+    /// fingerprints, so both parties see the same code on their
+    /// devices, and can compare it by reading it aloud.
+    ///
+    /// The code consists of six groups of five decimal digits.  It
+    /// is computed as follows: the two fingerprints are sorted, and
+    /// hashed using SHA-512 together with a domain separator:
+    ///
+    /// ```text
+    /// SHA-512("OpenPGP fingerprint verification code v1" || 0x00
+    ///         || len(a) || a || len(b) || b)
+    /// ```
+    ///
+    /// where `a` is the smaller fingerprint, and the lengths are
+    /// single octets.  The `i`th group is the big-endian number
+    /// formed by the octets `5i` to `5i + 4` of the digest modulo
+    /// 100000.
+    ///
+    /// # Security
+    ///
+    /// The code provides about 100 bits of security against an
+    /// attacker who tries to create a certificate whose code matches
+    /// that of a legitimate one.  Do not shorten the code: unlike
+    /// short authentication strings in interactive protocols, the
+    /// fingerprints are fixed in advance, so an attacker can
+    /// generate keys until a shortened code matches.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// # use sequoia_openpgp as openpgp;
+    /// use openpgp::Fingerprint;
+    ///
+    /// let alice: Fingerprint =
+    ///     "8F17 7771 18A3 3DDA 9BA4  8E62 AACB 3243 6300 52D9".parse()?;
+    /// let bob: Fingerprint =
+    ///     "3E88 77C8 7727 4692 9751  89F5 D03F 6F86 5226 FE8B".parse()?;
+    ///
+    /// assert_eq!(alice.verification_code(&bob)?,
+    ///            bob.verification_code(&alice)?);
+    /// assert_eq!(alice.verification_code(&bob)?,
+    ///            "65470 51220 35270 21547 04979 77617");
+    /// # Ok(()) }
+    /// ```
+    pub fn verification_code(&self, other: &Fingerprint) -> Result<String> {
+        let (a, b) = if self.as_bytes() <= other.as_bytes() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        let mut ctx = HashAlgorithm::SHA512.context()?;
+        ctx.update(VERIFICATION_CODE_CONTEXT);
+        for fp in [a, b] {
+            let fp = fp.as_bytes();
+            let len = u8::try_from(fp.len()).map_err(
+                |_| crate::Error::InvalidArgument(
+                    "Fingerprint too long".into()))?;
+            ctx.update(&[len]);
+            ctx.update(fp);
+        }
+        let mut digest = [0; 64];
+        ctx.digest(&mut digest)?;
+
+        let groups = digest.chunks(5).take(6).map(|c| {
+            let v = c.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            format!("{:05}", v % 100_000)
+        }).collect::<Vec<_>>();
+        Ok(groups.join(" "))
+    }
+
     /// Returns whether `self` and `other` could be aliases of each
     /// other.
     ///
@@ -453,7 +608,7 @@ impl Fingerprint {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Fingerprint {
     fn arbitrary(g: &mut Gen) -> Self {
         if Arbitrary::arbitrary(g) {
@@ -472,6 +627,41 @@ impl Arbitrary for Fingerprint {
 mod tests {
     use super::*;
 
+    /// Checks that the variant survives a round trip, even if it
+    /// can't be told from the length.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() -> Result<()> {
+        for fp in [Fingerprint::V4([1; 20]),
+                   Fingerprint::V5([2; 32]),
+                   Fingerprint::Invalid(vec![3; 20].into()),
+                   Fingerprint::Invalid(vec![4; 32].into()),
+                   Fingerprint::Invalid(vec![5; 3].into())]
+        {
+            let json = serde_json::to_string(&fp)?;
+            assert!(json.contains(&format!("\"{:X}\"", fp)));
+            let fp_: Fingerprint = serde_json::from_str(&json)?;
+            assert_eq!(fp, fp_);
+            assert_eq!(std::mem::discriminant(&fp),
+                       std::mem::discriminant(&fp_));
+
+            let handle = KeyHandle::from(fp.clone());
+            let json = serde_json::to_string(&handle)?;
+            let handle_: KeyHandle = serde_json::from_str(&json)?;
+            assert!(matches!(handle_, KeyHandle::Fingerprint(ref f)
+                             if std::mem::discriminant(f)
+                             == std::mem::discriminant(&fp)));
+        }
+
+        // The version must match the length.
+        assert!(serde_json::from_str::<Fingerprint>(
+            r#"{"version":4,"hex":"0102"}"#).is_err());
+        assert!(serde_json::from_str::<Fingerprint>(
+            &format!(r#"{{"version":5,"hex":"{}"}}"#, "01".repeat(20)))
+                .is_err());
+        Ok(())
+    }
+
     #[test]
     fn v4_hex_formatting() {
         let fp = "0123 4567 89AB CDEF 0123 4567 89AB CDEF 0123 4567"
@@ -494,6 +684,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn openpgp4fpr() -> crate::Result<()> {
+        let fp = "8F17777118A33DDA9BA48E62AACB3243630052D9"
+            .parse::<Fingerprint>()?;
+        let uri = fp.to_openpgp4fpr_uri();
+        assert_eq!(uri, "OPENPGP4FPR:8F17777118A33DDA9BA48E62AACB3243630052D9");
+        assert_eq!(Fingerprint::from_openpgp4fpr_uri(&uri)?, fp);
+        assert_eq!(Fingerprint::from_openpgp4fpr_uri(
+            " OpenPGP4FPR:8f17777118a33dda9ba48e62aacb3243630052d9#a=x&n=y\n")?,
+                   fp);
+
+        let v5 = Fingerprint::V5([0xAB; 32]);
+        assert_eq!(Fingerprint::from_openpgp4fpr_uri(
+            &v5.to_openpgp4fpr_uri())?, v5);
+
+        assert!(Fingerprint::from_openpgp4fpr_uri("").is_err());
+        assert!(Fingerprint::from_openpgp4fpr_uri("OPENPGP4FPR:").is_err());
+        assert!(Fingerprint::from_openpgp4fpr_uri("OPENPGP4FPR:0123").is_err());
+        assert!(Fingerprint::from_openpgp4fpr_uri(
+            "mailto:8F17777118A33DDA9BA48E62AACB3243630052D9").is_err());
+        // Multi-byte characters must not trip up the scheme check.
+        assert!(Fingerprint::from_openpgp4fpr_uri("OPENPGP4FP\u{e9}").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn verification_code() -> crate::Result<()> {
+        let a = "8F17777118A33DDA9BA48E62AACB3243630052D9"
+            .parse::<Fingerprint>()?;
+        let b = "3E8877C877274692975189F5D03F6F865226FE8B"
+            .parse::<Fingerprint>()?;
+        let c = "280C0AB0B94D1302CAAEB71DA299CDCD3884EBEA"
+            .parse::<Fingerprint>()?;
+
+        let ab = a.verification_code(&b)?;
+        assert_eq!(ab, "65470 51220 35270 21547 04979 77617");
+        assert_eq!(ab, b.verification_code(&a)?);
+        assert_ne!(ab, a.verification_code(&c)?);
+        assert_ne!(ab, b.verification_code(&c)?);
+        assert_eq!(a.verification_code(&a)?.len(), 6 * 5 + 5);
+        Ok(())
+    }
+
     #[test]
     fn aliases() -> crate::Result<()> {
         // fp1 and fp15 have the same key ID, but are different
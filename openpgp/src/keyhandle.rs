@@ -2,6 +2,9 @@ use std::convert::TryFrom;
 use std::cmp::Ordering;
 use std::borrow::Borrow;
 
+#[cfg(any(test, feature = "quickcheck"))]
+use quickcheck::{Arbitrary, Gen};
+
 use crate::{
     Error,
     Fingerprint,
@@ -78,6 +81,7 @@ use crate::{
 /// # Ok(()) }
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KeyHandle {
     /// A Fingerprint.
     Fingerprint(Fingerprint),
@@ -431,21 +435,21 @@ impl KeyHandle {
     }
 }
 
+#[cfg(any(test, feature = "quickcheck"))]
+impl Arbitrary for KeyHandle {
+    fn arbitrary(g: &mut Gen) -> Self {
+        if bool::arbitrary(g) {
+            Fingerprint::arbitrary(g).into()
+        } else {
+            KeyID::arbitrary(g).into()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use quickcheck::{Arbitrary, Gen};
     use super::*;
 
-    impl Arbitrary for KeyHandle {
-        fn arbitrary(g: &mut Gen) -> Self {
-            if bool::arbitrary(g) {
-                Fingerprint::arbitrary(g).into()
-            } else {
-                KeyID::arbitrary(g).into()
-            }
-        }
-    }
-
     #[test]
     fn upper_hex_formatting() {
         let handle = KeyHandle::Fingerprint(Fingerprint::V4([1, 2, 3, 4, 5, 6, 7,
@@ -1,7 +1,7 @@
 use std::borrow::Borrow;
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::Error;
@@ -119,6 +119,8 @@ impl std::str::FromStr for KeyID {
     }
 }
 
+impl_serde_using_version_and_hex!(KeyID, V4 => 4);
+
 impl From<KeyID> for Vec<u8> {
     fn from(id: KeyID) -> Self {
         let mut r = Vec::with_capacity(8);
@@ -504,7 +506,7 @@ impl KeyID {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for KeyID {
     fn arbitrary(g: &mut Gen) -> Self {
         KeyID::new(u64::arbitrary(g))
@@ -514,6 +516,31 @@ impl Arbitrary for KeyID {
 #[cfg(test)]
 mod test {
     use super::*;
+
+    /// Checks that the variant survives a round trip, even if it
+    /// can't be told from the length.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() -> crate::Result<()> {
+        for id in [KeyID::V4([1; 8]),
+                   KeyID::Invalid(vec![2; 8].into()),
+                   KeyID::Invalid(vec![3; 20].into())]
+        {
+            let json = serde_json::to_string(&id)?;
+            let id_: KeyID = serde_json::from_str(&json)?;
+            assert_eq!(std::mem::discriminant(&id),
+                       std::mem::discriminant(&id_));
+            assert_eq!(id.as_bytes(), id_.as_bytes());
+
+            let handle = KeyHandle::from(id.clone());
+            let json = serde_json::to_string(&handle)?;
+            let handle_: KeyHandle = serde_json::from_str(&json)?;
+            assert!(matches!(handle_, KeyHandle::KeyID(ref i)
+                             if std::mem::discriminant(i)
+                             == std::mem::discriminant(&id)));
+        }
+        Ok(())
+    }
     quickcheck! {
         fn u64_roundtrip(id: u64) -> bool {
             KeyID::new(id).as_u64().unwrap() == id
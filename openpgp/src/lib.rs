@@ -48,6 +48,12 @@
 //! artifacts created using this functionality may not be usable in
 //! the future.  Do not use it for things other than experiments.
 //!
+//! Such messages are always parsed and can be decrypted.  Creating
+//! them, i.e. AEAD Encrypted Data packets and version 5 SKESK packets,
+//! using the streaming [`Encryptor2`] requires the `experimental-aed`
+//! feature.
+//!
+//! [`Encryptor2`]: serialize::stream::Encryptor2
 //! [RFC 4880bis]: https://tools.ietf.org/html/draft-ietf-openpgp-rfc4880bis-08
 
 #![doc(html_favicon_url = "https://docs.sequoia-pgp.org/favicon.png")]
@@ -108,8 +114,13 @@ fn vec_drain_prefix(v: &mut Vec<u8>, prefix_len: usize) {
     }
 }
 
-/// Like std::time::SystemTime::now, but works on WASM.
+/// Like std::time::SystemTime::now, but works on WASM, and honors
+/// the installed clock (see [`types::set_clock`]).
 fn now() -> std::time::SystemTime {
+    if let Some(t) = types::clock::now() {
+        return t;
+    }
+
     #[cfg(all(target_arch = "wasm32", target_os = "unknown"))] {
         chrono::Utc::now().into()
     }
@@ -230,6 +241,15 @@ pub enum Error {
            _0, _1, _2)]
     PacketTooLarge(packet::Tag, u32, u32),
 
+    /// Subpacket area size exceeds the configured limit.
+    #[error("Subpacket area ({} bytes) exceeds limit of {} bytes",
+           _0, _1)]
+    SubpacketAreaTooLarge(u32, u32),
+
+    /// Number of subpackets exceeds the configured limit.
+    #[error("Subpacket area contains more than {0} subpackets")]
+    TooManySubpackets(u32),
+
     /// Unsupported packet type.
     #[error("Unsupported packet type.  Tag: {0}")]
     UnsupportedPacketType(packet::Tag),
@@ -283,9 +303,47 @@ pub enum Error {
     BadSignature(String),
 
     /// Message has been manipulated.
+    ///
+    /// This is returned if the structure of an encrypted message
+    /// indicates tampering, e.g., if the decrypted packets do not
+    /// form a valid message.  More specific errors are returned if
+    /// the Modification Detection Code is missing or does not match
+    /// ([`Error::BadMDC`]), if an AEAD authentication tag does not
+    /// match ([`Error::BadAEADTag`]), if the message has been
+    /// truncated ([`Error::TruncatedMessage`]), and if the session
+    /// key is wrong ([`Error::InvalidSessionKey`]).
     #[error("Message has been manipulated")]
     ManipulatedMessage,
 
+    /// The Modification Detection Code is missing or does not match.
+    ///
+    /// The plaintext of a message protected by a Modification
+    /// Detection Code has been modified.  Any data that has been
+    /// returned to the caller must be discarded.
+    #[error("Message has been manipulated: bad modification detection code")]
+    BadMDC,
+
+    /// An AEAD authentication tag does not match.
+    ///
+    /// A chunk of an AEAD-protected message has been modified.  Note
+    /// that if the first chunk can't be authenticated, this is
+    /// indistinguishable from using the wrong session key, and
+    /// [`Error::InvalidSessionKey`] is returned instead.
+    #[error("Message has been manipulated: bad authentication tag")]
+    BadAEADTag,
+
+    /// Encrypted message has been truncated.
+    ///
+    /// The encryption container ended before its framing said it
+    /// would, or the final authentication tag of an AEAD-protected
+    /// message is missing.  The argument is the number of bytes of
+    /// plaintext that were authenticated before the truncation was
+    /// detected.  For messages protected by a Modification Detection
+    /// Code, this is always zero, because the plaintext can only be
+    /// authenticated once the whole message has been read.
+    #[error("Message has been truncated after {0} authenticated bytes")]
+    TruncatedMessage(u64),
+
     /// Malformed message.
     #[error("Malformed Message: {0}")]
     MalformedMessage(String),
@@ -349,6 +407,21 @@ pub enum Error {
     /// Short key IDs are insecure, and not supported.
     #[error("Short key IDs are insecure, and not supported: {0}")]
     ShortKeyID(String),
+
+    /// Invalid ECDH KDF parameters.
+    ///
+    /// The key derivation function's hash algorithm or the key
+    /// encryption key's symmetric algorithm is not acceptable.  See
+    /// [`crypto::ecdh::check_kdf_parameters`].
+    #[error("Invalid ECDH KDF parameters: hash {0}, cipher {1}")]
+    InvalidEcdhParameters(HashAlgorithm, SymmetricAlgorithm),
+
+    /// The message exceeds a limit.
+    ///
+    /// The arguments are the kind of limit that was exceeded, and the
+    /// limit.  See [`parse::stream::Limit`].
+    #[error("Message exceeds the limit of {1} {0}")]
+    MessageLimitExceeded(parse::stream::Limit, usize),
 }
 
 assert_send_and_sync!(Error);
@@ -356,7 +429,7 @@ assert_send_and_sync!(Error);
 /// Provide a helper function that generates an arbitrary value from a given
 /// range.  Quickcheck > 1 does not re-export rand so we need to implement this
 /// ourselves.
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 mod arbitrary_helper {
     use quickcheck::{Arbitrary, Gen};
 
@@ -11,6 +11,135 @@ macro_rules! trace {
     };
 }
 
+// Emits a message from a function instrumented using `tracer!`.
+//
+// If the `tracing` feature is enabled, the message is also emitted as
+// a `tracing` event, regardless of `$TRACE`.
+macro_rules! trace_fn {
+    ( $TRACE:expr, $indent:expr, $func:expr, $msg:expr ) => {{
+        trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, $msg);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(function = %$func, "{}", $msg);
+    }};
+}
+
+// Enters a `tracing` span for the rest of the enclosing scope.
+//
+// This expands to nothing if the `tracing` feature is disabled.  The
+// fields are only evaluated if the span is enabled.
+macro_rules! span {
+    ( $level:ident, $($args:tt)* ) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $($args)*)
+            .entered();
+    };
+}
+
+// Emits a `tracing` event.
+//
+// This expands to nothing if the `tracing` feature is disabled.  The
+// fields are only evaluated if the event is enabled.
+macro_rules! event {
+    ( $level:ident, $($args:tt)* ) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($args)*);
+    };
+}
+
+// Implements serde's `Serialize` and `Deserialize` for an
+// identifier, i.e. a `Fingerprint` or a `KeyID`.
+//
+// The identifier is serialized as a struct holding the version, and
+// the hexadecimal representation.  The version is `None` for the
+// `Invalid` variant.  Serializing the version makes sure that the
+// variant survives a round trip, even if it can't be told from the
+// length of the identifier.
+//
+// This expands to nothing if the `serde` feature is disabled.
+macro_rules! impl_serde_using_version_and_hex {
+    ( $t:ident, $( $variant:ident => $version:literal ),* ) => {
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $t {
+            fn serialize<S>(&self, serializer: S)
+                            -> std::result::Result<S::Ok, S::Error>
+                where S: serde::Serializer,
+            {
+                let version = match self {
+                    $( $t::$variant(_) => Some($version), )*
+                    $t::Invalid(_) => None,
+                };
+                serde::Serialize::serialize(
+                    &crate::macros::SerdeIdentifier {
+                        version,
+                        hex: self.to_hex(),
+                    },
+                    serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $t {
+            fn deserialize<D>(deserializer: D)
+                              -> std::result::Result<Self, D::Error>
+                where D: serde::Deserializer<'de>,
+            {
+                use serde::de::Error;
+
+                let r: crate::macros::SerdeIdentifier =
+                    serde::Deserialize::deserialize(deserializer)?;
+                let bytes = crate::fmt::hex::decode(&r.hex)
+                    .map_err(D::Error::custom)?;
+                match (r.version, $t::from_bytes(&bytes)) {
+                    (None, _) => Ok($t::Invalid(bytes.into())),
+                    $( (Some($version), v @ $t::$variant(_)) => Ok(v), )*
+                    (Some(v), _) => Err(D::Error::custom(format!(
+                        "Invalid version {} {}: {}",
+                        v, stringify!($t), r.hex))),
+                }
+            }
+        }
+    };
+}
+
+// The serialized form of identifiers, see
+// `impl_serde_using_version_and_hex`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Identifier")]
+pub(crate) struct SerdeIdentifier {
+    pub(crate) version: Option<u8>,
+    pub(crate) hex: String,
+}
+
+// Implements serde's `Serialize` and `Deserialize` for a type using
+// its numeric representation, i.e. its conversions from and to
+// `u8`.
+//
+// This expands to nothing if the `serde` feature is disabled.
+macro_rules! impl_serde_using_u8 {
+    ( $t:ty ) => {
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $t {
+            fn serialize<S>(&self, serializer: S)
+                            -> std::result::Result<S::Ok, S::Error>
+                where S: serde::Serializer,
+            {
+                serializer.serialize_u8((*self).into())
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $t {
+            fn deserialize<D>(deserializer: D)
+                              -> std::result::Result<Self, D::Error>
+                where D: serde::Deserializer<'de>,
+            {
+                u8::deserialize(deserializer).map(Into::into)
+            }
+        }
+    };
+}
+
 // Converts an indentation level to whitespace.
 pub(crate) fn indent(i: isize) -> &'static str {
     let s = "                                                  ";
@@ -28,29 +157,29 @@ macro_rules! tracer {
         #[allow(unused_macros)]
         macro_rules! t {
             ( $fmt:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, $fmt) };
+            { trace_fn!($TRACE, $indent, $func, $fmt) };
             ( $fmt:expr, $a:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a)) };
             ( $fmt:expr, $a:expr, $b:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr, $d:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c, $d)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c, $d)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c, $d, $e)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c, $d, $e)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c, $d, $e, $f)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c, $d, $e, $f)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr, $g:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr, $g:expr, $h:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g, $h)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g, $h)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr, $g:expr, $h:expr, $i:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g, $h, $i)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g, $h, $i)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr, $g:expr, $h:expr, $i:expr, $j:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g, $h, $i, $j)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g, $h, $i, $j)) };
             ( $fmt:expr, $a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr, $g:expr, $h:expr, $i:expr, $j:expr, $k:expr ) =>
-            { trace!($TRACE, "{}{}: {}", crate::macros::indent($indent), $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g, $h, $i, $j, $k)) };
+            { trace_fn!($TRACE, $indent, $func, format!($fmt, $a, $b, $c, $d, $e, $f, $g, $h, $i, $j, $k)) };
         }
     }
 }
@@ -267,3 +396,58 @@ macro_rules! zero_stack {
             #[inline(never)] || $code)
     };
 }
+
+#[cfg(all(test, feature = "tracing"))]
+mod test_tracing {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{
+        Event,
+        Metadata,
+        Subscriber,
+        span,
+        subscriber::with_default,
+    };
+
+    use crate::Cert;
+    use crate::parse::Parse;
+
+    /// Records the names of the spans and the number of events.
+    #[derive(Default, Clone)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<&'static str>>>,
+        events: Arc<Mutex<usize>>,
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span.metadata().name());
+            span::Id::from_u64(spans.len() as u64)
+        }
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+        fn event(&self, _: &Event<'_>) {
+            *self.events.lock().unwrap() += 1;
+        }
+        fn enter(&self, _: &span::Id) {}
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn spans() -> crate::Result<()> {
+        let recorder = Recorder::default();
+        with_default(recorder.clone(), || {
+            Cert::from_bytes(crate::tests::key("testy.pgp"))
+        })?;
+
+        let spans = recorder.spans.lock().unwrap();
+        assert!(spans.contains(&"parse_packet"));
+        assert!(spans.contains(&"canonicalize"));
+        assert!(*recorder.events.lock().unwrap() > 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+//! Sets of encrypted session keys.
+
+use std::convert::TryFrom;
+use std::io;
+use std::path::Path;
+
+use buffered_reader::BufferedReader;
+
+use crate::{
+    Error,
+    Packet,
+    PacketPile,
+    Result,
+    crypto::{Decryptor, Password, SessionKey},
+    packet::{PKESK, SKESK, pkesk::PKESK3, skesk::SKESK4},
+    parse::{Cookie, Parse},
+    serialize::stream::Recipient,
+    types::SymmetricAlgorithm,
+};
+
+/// A set of encrypted session keys.
+///
+/// An encrypted OpenPGP message starts with a number of [`PKESK`] and
+/// [`SKESK`] packets, each of which contains the session key used to
+/// encrypt the message's payload, encrypted for one recipient or
+/// password.  This data structure holds such a set of packets
+/// independently of a message.
+///
+/// This is useful when the session key is distributed out of band.
+/// For instance, a group messaging system may encrypt a message once
+/// using [`Encryptor2::with_session_key`], and then wrap the session
+/// key for the current members of the group.  As members join and
+/// leave, the session key can be wrapped for new members, and the
+/// wrappings for departed members can be removed, without touching
+/// the encrypted payload.
+///
+/// An `EncryptedSessionKeys` can be serialized using [`Marshal`], and
+/// parsed using [`Parse`].  When parsing, [`Marker`] packets are
+/// skipped, any other packet except for PKESK and SKESK packets is
+/// an error.
+///
+///   [`PKESK`]: crate::packet::PKESK
+///   [`SKESK`]: crate::packet::SKESK
+///   [`Encryptor2::with_session_key`]: crate::serialize::stream::Encryptor2::with_session_key()
+///   [`Marshal`]: crate::serialize::Marshal
+///   [`Marker`]: crate::packet::Marker
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::crypto::SessionKey;
+/// use openpgp::message::EncryptedSessionKeys;
+/// use openpgp::parse::Parse;
+/// use openpgp::policy::StandardPolicy;
+/// use openpgp::serialize::MarshalInto;
+/// use openpgp::types::SymmetricAlgorithm;
+///
+/// # fn main() -> openpgp::Result<()> {
+/// let p = &StandardPolicy::new();
+/// let (alice, _) =
+///     CertBuilder::general_purpose(None, Some("alice@example.org"))
+///     .generate()?;
+/// let (bob, _) =
+///     CertBuilder::general_purpose(None, Some("bob@example.org"))
+///     .generate()?;
+///
+/// // The group's session key.
+/// let algo = SymmetricAlgorithm::AES256;
+/// let sk = SessionKey::new(algo.key_size()?);
+///
+/// // Wrap it for the members of the group.
+/// let mut esks = EncryptedSessionKeys::new();
+/// for member in [&alice, &bob] {
+///     esks.add_recipients(
+///         algo, &sk,
+///         member.keys().with_policy(p, None).supported()
+///             .for_transport_encryption())?;
+/// }
+/// let distributed = esks.to_vec()?;
+///
+/// // Bob unwraps the session key.
+/// let esks = EncryptedSessionKeys::from_bytes(&distributed)?;
+/// let mut keypair = bob.keys().with_policy(p, None).secret()
+///     .for_transport_encryption().next().unwrap()
+///     .key().clone().into_keypair()?;
+/// assert_eq!(esks.decrypt(&mut keypair, Some(algo)), Some((algo, sk)));
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncryptedSessionKeys {
+    /// The PKESK and SKESK packets.
+    packets: Vec<Packet>,
+}
+assert_send_and_sync!(EncryptedSessionKeys);
+
+impl EncryptedSessionKeys {
+    /// Returns an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encrypts `session_key` for the given recipients, and adds
+    /// the resulting PKESK packets to the set.
+    ///
+    /// `algo` is the symmetric algorithm used to encrypt the
+    /// payload.
+    pub fn add_recipients<'a, R>(&mut self,
+                                 algo: SymmetricAlgorithm,
+                                 session_key: &SessionKey,
+                                 recipients: R)
+                                 -> Result<()>
+        where R: IntoIterator,
+              R::Item: Into<Recipient<'a>>,
+    {
+        check_session_key(algo, session_key)?;
+        for recipient in recipients {
+            let recipient = recipient.into();
+            let mut pkesk =
+                PKESK3::for_recipient(algo, session_key, recipient.key())?;
+            pkesk.set_recipient(recipient.keyid().clone());
+            self.packets.push(Packet::PKESK(pkesk.into()));
+        }
+        Ok(())
+    }
+
+    /// Encrypts `session_key` using the given passwords, and adds
+    /// the resulting SKESK packets to the set.
+    ///
+    /// `algo` is the symmetric algorithm used to encrypt the
+    /// payload.  Version 4 SKESK packets are created.
+    pub fn add_passwords<P>(&mut self,
+                            algo: SymmetricAlgorithm,
+                            session_key: &SessionKey,
+                            passwords: P)
+                            -> Result<()>
+        where P: IntoIterator,
+              P::Item: Into<Password>,
+    {
+        check_session_key(algo, session_key)?;
+        for password in passwords {
+            let skesk = SKESK4::with_password(algo, algo, Default::default(),
+                                              session_key, &password.into())?;
+            self.packets.push(Packet::SKESK(skesk.into()));
+        }
+        Ok(())
+    }
+
+    /// Returns the PKESK packets.
+    pub fn pkesks(&self) -> impl Iterator<Item = &PKESK> + Send + Sync {
+        self.packets.iter().filter_map(|p| match p {
+            Packet::PKESK(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    /// Returns the SKESK packets.
+    pub fn skesks(&self) -> impl Iterator<Item = &SKESK> + Send + Sync {
+        self.packets.iter().filter_map(|p| match p {
+            Packet::SKESK(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    /// Returns the number of packets in the set.
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Retains only the PKESK packets specified by the predicate.
+    ///
+    /// This can be used to revoke access for recipients, e.g.
+    /// members that left a group.  SKESK packets are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sequoia_openpgp as openpgp;
+    /// # use openpgp::cert::prelude::*;
+    /// # use openpgp::crypto::SessionKey;
+    /// # use openpgp::message::EncryptedSessionKeys;
+    /// # use openpgp::policy::StandardPolicy;
+    /// # use openpgp::types::SymmetricAlgorithm;
+    /// # fn main() -> openpgp::Result<()> {
+    /// # let p = &StandardPolicy::new();
+    /// # let (mallory, _) = CertBuilder::general_purpose(None, Some("m"))
+    /// #     .generate()?;
+    /// # let algo = SymmetricAlgorithm::AES256;
+    /// # let sk = SessionKey::new(algo.key_size()?);
+    /// # let mut esks = EncryptedSessionKeys::new();
+    /// # esks.add_recipients(algo, &sk, mallory.keys().with_policy(p, None)
+    /// #                      .for_transport_encryption())?;
+    /// let departed = mallory.keys().map(|ka| ka.keyid()).collect::<Vec<_>>();
+    /// esks.retain_pkesks(|pkesk| ! departed.contains(pkesk.recipient()));
+    /// assert!(esks.is_empty());
+    /// # Ok(()) }
+    /// ```
+    pub fn retain_pkesks<F>(&mut self, mut predicate: F)
+        where F: FnMut(&PKESK) -> bool,
+    {
+        self.packets.retain(|p| match p {
+            Packet::PKESK(p) => predicate(p),
+            _ => true,
+        })
+    }
+
+    /// Decrypts the session key using `decryptor`.
+    ///
+    /// Tries all PKESK packets addressed to `decryptor`'s key, or to
+    /// the wildcard key ID.  If the symmetric algorithm is known in
+    /// advance, it should be given as `sym_algo_hint`, see
+    /// [`PKESK3::decrypt`].
+    ///
+    /// Returns the symmetric algorithm and the session key, or `None`
+    /// if no packet could be decrypted.
+    ///
+    ///   [`PKESK3::decrypt`]: crate::packet::pkesk::PKESK3::decrypt()
+    pub fn decrypt(&self, decryptor: &mut dyn Decryptor,
+                   sym_algo_hint: Option<SymmetricAlgorithm>)
+                   -> Option<(SymmetricAlgorithm, SessionKey)>
+    {
+        let keyid = decryptor.public().keyid();
+        self.pkesks()
+            .filter(|p| p.recipient() == &keyid || p.recipient().is_wildcard())
+            .find_map(|p| p.decrypt(decryptor, sym_algo_hint))
+    }
+
+    /// Decrypts the session key using `password`.
+    ///
+    /// Tries all SKESK packets.  Returns the symmetric algorithm and
+    /// the session key, or `None` if no packet could be decrypted.
+    pub fn decrypt_with_password(&self, password: &Password)
+                                 -> Option<(SymmetricAlgorithm, SessionKey)>
+    {
+        self.skesks().find_map(|s| s.decrypt(password).ok())
+    }
+
+    /// Returns the packets.
+    pub(crate) fn packets(&self) -> &[Packet] {
+        &self.packets
+    }
+}
+
+/// Checks that the size of `session_key` matches `algo`.
+fn check_session_key(algo: SymmetricAlgorithm, session_key: &SessionKey)
+                     -> Result<()>
+{
+    let key_size = algo.key_size()?;
+    if session_key.len() != key_size {
+        return Err(Error::InvalidArgument(
+            format!("{} requires a {} bit key, but session key has {}",
+                    algo, key_size, session_key.len())).into());
+    }
+    Ok(())
+}
+
+impl TryFrom<Vec<Packet>> for EncryptedSessionKeys {
+    type Error = anyhow::Error;
+
+    /// Converts a sequence of PKESK and SKESK packets.
+    ///
+    /// Marker packets are skipped, any other packet is an error.
+    fn try_from(packets: Vec<Packet>) -> Result<Self> {
+        let packets = packets.into_iter()
+            .filter(|p| ! matches!(p, Packet::Marker(_)))
+            .map(|p| match p {
+                Packet::PKESK(_) | Packet::SKESK(_) => Ok(p),
+                p => Err(Error::MalformedMessage(
+                    format!("Unexpected packet in session key set: {}",
+                            p.tag())).into()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(EncryptedSessionKeys { packets })
+    }
+}
+
+impl TryFrom<PacketPile> for EncryptedSessionKeys {
+    type Error = anyhow::Error;
+
+    /// Converts a sequence of PKESK and SKESK packets.
+    ///
+    /// Marker packets are skipped, any other packet is an error.
+    fn try_from(pile: PacketPile) -> Result<Self> {
+        Self::try_from(Vec::from(pile))
+    }
+}
+
+impl From<EncryptedSessionKeys> for Vec<Packet> {
+    fn from(esks: EncryptedSessionKeys) -> Self {
+        esks.packets
+    }
+}
+
+impl<'a> Parse<'a, EncryptedSessionKeys> for EncryptedSessionKeys {
+    /// Reads a set of encrypted session keys from the specified
+    /// reader.
+    fn from_buffered_reader<R>(reader: R) -> Result<EncryptedSessionKeys>
+    where
+        R: BufferedReader<Cookie> + 'a,
+    {
+        Self::try_from(PacketPile::from_buffered_reader(reader)?)
+    }
+
+    /// Reads a set of encrypted session keys from the specified
+    /// reader.
+    fn from_reader<R: 'a + io::Read + Send + Sync>(reader: R) -> Result<Self> {
+        Self::try_from(PacketPile::from_reader(reader)?)
+    }
+
+    /// Reads a set of encrypted session keys from the specified
+    /// file.
+    fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::try_from(PacketPile::from_file(path)?)
+    }
+
+    /// Reads a set of encrypted session keys from `buf`.
+    fn from_bytes<D: AsRef<[u8]> + ?Sized + Send + Sync>(data: &'a D) -> Result<Self> {
+        Self::try_from(PacketPile::from_bytes(data)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cert::prelude::*;
+    use crate::packet::Marker;
+    use crate::policy::StandardPolicy;
+    use crate::serialize::{Marshal, MarshalInto};
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let p = &StandardPolicy::new();
+        let (cert, _) =
+            CertBuilder::general_purpose(None, Some("alice@example.org"))
+            .generate()?;
+
+        let algo = SymmetricAlgorithm::AES128;
+        let sk = SessionKey::new(algo.key_size()?);
+
+        let mut esks = EncryptedSessionKeys::new();
+        esks.add_recipients(algo, &sk, cert.keys().with_policy(p, None)
+                            .for_transport_encryption())?;
+        esks.add_passwords(algo, &sk, Some("streng geheim"))?;
+        assert_eq!(esks.pkesks().count(), 1);
+        assert_eq!(esks.skesks().count(), 1);
+
+        // A wrong key size is rejected.
+        assert!(esks.add_passwords(SymmetricAlgorithm::AES256, &sk,
+                                   Some("x")).is_err());
+        assert_eq!(esks.len(), 2);
+
+        // Serialize, with a marker packet in front.
+        let mut buf = Vec::new();
+        Packet::Marker(Marker::default()).serialize(&mut buf)?;
+        buf.extend_from_slice(&esks.to_vec()?);
+        let parsed = EncryptedSessionKeys::from_bytes(&buf)?;
+        assert_eq!(parsed, esks);
+
+        // Decrypt.
+        let mut keypair = cert.keys().with_policy(p, None).secret()
+            .for_transport_encryption().next().unwrap()
+            .key().clone().into_keypair()?;
+        assert_eq!(parsed.decrypt(&mut keypair, None), Some((algo, sk.clone())));
+        assert_eq!(parsed.decrypt_with_password(&"streng geheim".into()),
+                   Some((algo, sk.clone())));
+        // SKESK4 packets lack integrity protection, so a wrong
+        // password may yield a bogus session key.
+        assert_ne!(parsed.decrypt_with_password(&"falsch".into()),
+                   Some((algo, sk.clone())));
+
+        // Other keys are not tried.
+        let (other, _) = CertBuilder::general_purpose(None, Some("bob"))
+            .generate()?;
+        let mut keypair = other.keys().with_policy(p, None).secret()
+            .for_transport_encryption().next().unwrap()
+            .key().clone().into_keypair()?;
+        assert_eq!(parsed.decrypt(&mut keypair, None), None);
+
+        // Revoke access.
+        let mut esks = parsed;
+        esks.retain_pkesks(|_| false);
+        assert_eq!(esks.pkesks().count(), 0);
+        assert_eq!(esks.skesks().count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unexpected_packets() -> Result<()> {
+        let (cert, _) = CertBuilder::new().generate()?;
+        assert!(EncryptedSessionKeys::from_bytes(&cert.to_vec()?).is_err());
+        Ok(())
+    }
+}
@@ -33,12 +33,15 @@ use buffered_reader::BufferedReader;
 
 use crate::Result;
 use crate::Error;
+use crate::KeyHandle;
 use crate::Packet;
 use crate::PacketPile;
 use crate::packet::Literal;
 use crate::packet::Tag;
 use crate::parse::{Cookie, Parse};
 
+mod esk;
+pub use self::esk::EncryptedSessionKeys;
 mod lexer;
 lalrpop_util::lalrpop_mod!(#[allow(clippy::all, deprecated)] grammar, "/message/grammar.rs");
 
@@ -433,6 +436,146 @@ impl Message {
         // No literal data packet found.
         None
     }
+
+    /// Returns the key handles of the keys that claim to have signed
+    /// the message.
+    ///
+    /// The handles are taken from the issuers of the one-pass
+    /// signature packets and of the signature packets.  KeyIDs that
+    /// alias one of the fingerprints are replaced by the fingerprint,
+    /// and each handle is returned once, in the order in which the
+    /// packets appear.
+    ///
+    /// No cryptographic operation is performed: the signatures are
+    /// not verified, and the issuers are not authenticated.  Signed
+    /// messages are usually encrypted, and signatures inside an
+    /// encryption container are only returned if the container has
+    /// been decrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sequoia_openpgp as openpgp;
+    /// # fn main() -> openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use openpgp::Message;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::parse::Parse;
+    /// use openpgp::policy::StandardPolicy;
+    /// use openpgp::serialize::stream::{self, LiteralWriter, Signer};
+    ///
+    /// let p = &StandardPolicy::new();
+    /// let (alice, _) =
+    ///     CertBuilder::general_purpose(None, Some("alice@example.org"))
+    ///     .generate()?;
+    /// let signing_key = alice.keys().with_policy(p, None)
+    ///     .secret().for_signing().next().unwrap().key().clone();
+    ///
+    /// let mut sink = vec![];
+    /// let message = stream::Message::new(&mut sink);
+    /// let message = Signer::new(message, signing_key.clone().into_keypair()?)
+    ///     .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Hello world.")?;
+    /// message.finalize()?;
+    ///
+    /// let message = Message::from_bytes(&sink)?;
+    /// assert_eq!(message.signers(), vec![signing_key.key_handle()]);
+    /// # Ok(()) }
+    /// ```
+    pub fn signers(&self) -> Vec<KeyHandle> {
+        let mut issuers: Vec<KeyHandle> = Vec::new();
+        for packet in self.pile.descendants() {
+            match packet {
+                Packet::OnePassSig(ops) => issuers.push(ops.issuer().into()),
+                Packet::Signature(sig) => issuers.extend(sig.get_issuers()),
+                _ => (),
+            }
+        }
+
+        let fingerprints: Vec<KeyHandle> = issuers.iter()
+            .filter(|h| matches!(h, KeyHandle::Fingerprint(_)))
+            .cloned()
+            .collect();
+
+        let mut signers: Vec<KeyHandle> = Vec::new();
+        for handle in issuers {
+            let handle = if let KeyHandle::KeyID(_) = handle {
+                fingerprints.iter().find(|fp| fp.aliases(&handle))
+                    .cloned()
+                    .unwrap_or(handle)
+            } else {
+                handle
+            };
+
+            if ! signers.contains(&handle) {
+                signers.push(handle);
+            }
+        }
+        signers
+    }
+
+    /// Returns the key handles of the keys the message is encrypted
+    /// to.
+    ///
+    /// The handles are taken from the public key encrypted session
+    /// key packets, and each handle is returned once, in the order in
+    /// which the packets appear.  If the sender hid a recipient, the
+    /// wildcard KeyID is returned for it (see [`KeyID::wildcard`]).
+    ///
+    ///   [`KeyID::wildcard`]: crate::KeyID::wildcard()
+    ///
+    /// No cryptographic operation is performed.  This is useful to
+    /// display the recipients of a message, and to select the secret
+    /// key to decrypt it with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sequoia_openpgp as openpgp;
+    /// # fn main() -> openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use openpgp::{KeyHandle, Message};
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::parse::Parse;
+    /// use openpgp::policy::StandardPolicy;
+    /// use openpgp::serialize::stream::{self, Encryptor2, LiteralWriter};
+    ///
+    /// let p = &StandardPolicy::new();
+    /// let (bob, _) =
+    ///     CertBuilder::general_purpose(None, Some("bob@example.org"))
+    ///     .generate()?;
+    /// let recipients = bob.keys().with_policy(p, None)
+    ///     .for_transport_encryption();
+    ///
+    /// let mut sink = vec![];
+    /// let message = stream::Message::new(&mut sink);
+    /// let message = Encryptor2::for_recipients(message, recipients)
+    ///     .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Hello world.")?;
+    /// message.finalize()?;
+    ///
+    /// let message = Message::from_bytes(&sink)?;
+    /// let expected: Vec<KeyHandle> = bob.keys().with_policy(p, None)
+    ///     .for_transport_encryption()
+    ///     .map(|ka| ka.keyid().into())
+    ///     .collect();
+    /// assert_eq!(message.recipients(), expected);
+    /// # Ok(()) }
+    /// ```
+    pub fn recipients(&self) -> Vec<KeyHandle> {
+        let mut recipients: Vec<KeyHandle> = Vec::new();
+        for packet in self.pile.descendants() {
+            if let Packet::PKESK(pkesk) = packet {
+                let handle = pkesk.recipient().into();
+                if ! recipients.contains(&handle) {
+                    recipients.push(handle);
+                }
+            }
+        }
+        recipients
+    }
 }
 
 impl TryFrom<PacketPile> for Message {
@@ -1278,4 +1421,48 @@ mod tests {
 
         assert!(matches!(l.check(), MessageValidity::Message));
     }
+
+    #[test]
+    fn signers_and_recipients() -> Result<()> {
+        use crate::cert::prelude::*;
+        use crate::packet::signature::SignatureBuilder;
+        use crate::KeyID;
+
+        let (cert, _) = CertBuilder::new().generate()?;
+        let mut signer = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        let sig = SignatureBuilder::new(SignatureType::Binary)
+            .sign_message(&mut signer, b"data")?;
+        let mut ops = OnePassSig3::new(SignatureType::Binary);
+        ops.set_issuer(cert.keyid());
+
+        let mut seip = SEIP1::new();
+        seip.children_mut().unwrap().push(ops.into());
+        seip.children_mut().unwrap().push(
+            Literal::new(Text).into());
+        seip.children_mut().unwrap().push(sig.into());
+        seip.children_mut().unwrap().push(MDC::from([0u8; 20]).into());
+
+        let pkesk = |recipient: KeyID| -> Packet {
+            #[allow(deprecated)]
+            PKESK3::new(recipient, PublicKeyAlgorithm::RSAEncrypt,
+                        Ciphertext::RSA { c: MPI::new(&[]) })
+                .unwrap().into()
+        };
+        let a: KeyID = "0000111122223333".parse()?;
+        let packets = vec![
+            pkesk(a.clone()),
+            pkesk(KeyID::wildcard()),
+            pkesk(a.clone()),
+            seip.into(),
+        ];
+
+        let message = Message::try_from(packets)?;
+        // The KeyID of the one-pass signature packet is subsumed by
+        // the signature's issuer fingerprint.
+        assert_eq!(message.signers(), vec![cert.key_handle()]);
+        assert_eq!(message.recipients(),
+                   vec![KeyHandle::from(a), KeyID::wildcard().into()]);
+        Ok(())
+    }
 }
@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::packet;
@@ -97,7 +97,7 @@ impl From<CompressedData> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for CompressedData {
     fn arbitrary(g: &mut Gen) -> Self {
         use crate::serialize::SerializeInto;
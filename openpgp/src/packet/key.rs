@@ -82,11 +82,11 @@
 
 use std::fmt;
 use std::cmp::Ordering;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::hash::Hasher;
 use std::time;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::Error;
@@ -766,7 +766,7 @@ impl<P, R> Key<P, R>
 
                 // Generate an ephemeral key pair {v, V=vG}
                 // Compute the public key.
-                let (v, VB) = Backend::x25519_generate_key()?;
+                let (v, VB) = crate::crypto::rng::x25519_generate_key()?;
                 let VB = mpi::MPI::new_compressed_point(&VB);
 
                 // Compute the shared point S = vR;
@@ -793,6 +793,7 @@ impl<P, R> Key<P, R>
             Error::BadSignature(e.to_string()).into()
         }
 
+        crate::crypto::stats::verification();
         let ok = match (self.mpis(), sig) {
             (PublicKey::EdDSA { curve, q }, Signature::EdDSA { r, s }) =>
               match curve {
@@ -1102,19 +1103,24 @@ impl<R> Key4<key::PublicParts, R>
                               S: Into<Option<SymmetricAlgorithm>>,
                               T: Into<Option<time::SystemTime>>
     {
+        check_25519_len("X25519 public key", public_key)?;
         let mut point = Vec::from(public_key);
         point.insert(0, 0x40);
 
         use crate::crypto::ecdh;
+        let hash = hash.into().unwrap_or_else(
+            || ecdh::default_ecdh_kdf_hash(&Curve::Cv25519));
+        let sym = sym.into().unwrap_or_else(
+            || ecdh::default_ecdh_kek_cipher(&Curve::Cv25519));
+        ecdh::check_kdf_parameters(hash, sym)?;
+
         Self::new(
             ctime.into().unwrap_or_else(crate::now),
             PublicKeyAlgorithm::ECDH,
             mpi::PublicKey::ECDH {
                 curve: Curve::Cv25519,
-                hash: hash.into().unwrap_or_else(
-                    || ecdh::default_ecdh_kdf_hash(&Curve::Cv25519)),
-                sym: sym.into().unwrap_or_else(
-                    || ecdh::default_ecdh_kek_cipher(&Curve::Cv25519)),
+                hash,
+                sym,
                 q: mpi::MPI::new(&point),
             })
     }
@@ -1127,6 +1133,7 @@ impl<R> Key4<key::PublicParts, R>
     pub fn import_public_ed25519<T>(public_key: &[u8], ctime: T) -> Result<Self>
         where  T: Into<Option<time::SystemTime>>
     {
+        check_25519_len("Ed25519 public key", public_key)?;
         let mut point = Vec::from(public_key);
         point.insert(0, 0x40);
 
@@ -1198,7 +1205,15 @@ impl<R> Key4<SecretParts, R>
                               T: Into<Option<std::time::SystemTime>>
     {
         use crate::crypto::backend::{Backend, interface::Asymmetric};
+        use crate::crypto::ecdh;
+
+        let hash = hash.into().unwrap_or_else(
+            || ecdh::default_ecdh_kdf_hash(&Curve::Cv25519));
+        let sym = sym.into().unwrap_or_else(
+            || ecdh::default_ecdh_kek_cipher(&Curve::Cv25519));
+        ecdh::check_kdf_parameters(hash, sym)?;
 
+        check_25519_len("X25519 secret key", private_key)?;
         let mut private_key = Protected::from(private_key);
         let public_key = Backend::x25519_derive_public(&private_key)?;
 
@@ -1220,16 +1235,13 @@ impl<R> Key4<SecretParts, R>
         // https://lists.gnupg.org/pipermail/gnupg-devel/2018-February/033437.html.
         private_key.reverse();
 
-        use crate::crypto::ecdh;
         Self::with_secret(
             ctime.into().unwrap_or_else(crate::now),
             PublicKeyAlgorithm::ECDH,
             mpi::PublicKey::ECDH {
                 curve: Curve::Cv25519,
-                hash: hash.into().unwrap_or_else(
-                    || ecdh::default_ecdh_kdf_hash(&Curve::Cv25519)),
-                sym: sym.into().unwrap_or_else(
-                    || ecdh::default_ecdh_kek_cipher(&Curve::Cv25519)),
+                hash,
+                sym,
                 q: mpi::MPI::new_compressed_point(&public_key),
             },
             mpi::SecretKeyMaterial::ECDH {
@@ -1246,6 +1258,7 @@ impl<R> Key4<SecretParts, R>
     {
         use crate::crypto::backend::{Backend, interface::Asymmetric};
 
+        check_25519_len("Ed25519 secret key", private_key)?;
         let private_key = Protected::from(private_key);
         let public_key = Backend::ed25519_derive_public(&private_key)?;
 
@@ -1261,6 +1274,100 @@ impl<R> Key4<SecretParts, R>
             }.into())
     }
 
+    /// Returns the raw X25519 secret key.
+    ///
+    /// This is the inverse of [`Key4::import_secret_cv25519`]: the
+    /// secret is returned in the native X25519 representation, i.e.
+    /// as little-endian scalar of length 32.  Note that OpenPGP
+    /// requires the scalar to be clamped, so the returned secret may
+    /// differ from the one that was imported, but it is equivalent.
+    ///
+    /// Returns an error if this is not an ECDH key over Curve25519,
+    /// or if the secret key material is encrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::packet::key::{Key4, SecretParts, SubordinateRole};
+    /// use openpgp::types::Curve;
+    ///
+    /// let key: Key4<SecretParts, SubordinateRole> =
+    ///     Key4::generate_ecc(false, Curve::Cv25519)?;
+    /// let secret = key.export_secret_cv25519()?;
+    /// let public = key.export_public_cv25519()?;
+    ///
+    /// let key_: Key4<SecretParts, SubordinateRole> =
+    ///     Key4::import_secret_cv25519(&secret, None, None,
+    ///                                 key.creation_time())?;
+    /// assert_eq!(key_.export_public_cv25519()?, public);
+    /// assert_eq!(key, key_);
+    /// # Ok(()) }
+    /// ```
+    pub fn export_secret_cv25519(&self) -> Result<Protected> {
+        self.export_public_cv25519()?;
+        self.export_25519_secret(|mpis| match mpis {
+            mpi::SecretKeyMaterial::ECDH { scalar } => {
+                // OpenPGP stores the scalar as big-endian integer,
+                // see Key4::import_secret_cv25519.
+                let mut secret = scalar.value_padded(32);
+                secret.reverse();
+                Ok(secret)
+            },
+            _ => Err(Error::MalformedPacket(
+                "Mismatched secret key material".into()).into()),
+        })
+    }
+
+    /// Returns the raw Ed25519 secret key.
+    ///
+    /// This is the inverse of [`Key4::import_secret_ed25519`]: the
+    /// secret is returned as opaque byte string of length 32, i.e.
+    /// the seed from which the signing key is derived.
+    ///
+    /// Returns an error if this is not an EdDSA key over Ed25519, or
+    /// if the secret key material is encrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::packet::key::{Key4, PrimaryRole, SecretParts};
+    /// use openpgp::types::Curve;
+    ///
+    /// let key: Key4<SecretParts, PrimaryRole> =
+    ///     Key4::generate_ecc(true, Curve::Ed25519)?;
+    /// let secret = key.export_secret_ed25519()?;
+    ///
+    /// let key_: Key4<SecretParts, PrimaryRole> =
+    ///     Key4::import_secret_ed25519(&secret, key.creation_time())?;
+    /// assert_eq!(key, key_);
+    /// # Ok(()) }
+    /// ```
+    pub fn export_secret_ed25519(&self) -> Result<Protected> {
+        self.export_public_ed25519()?;
+        self.export_25519_secret(|mpis| match mpis {
+            mpi::SecretKeyMaterial::EdDSA { scalar } =>
+                Ok(scalar.value_padded(32)),
+            _ => Err(Error::MalformedPacket(
+                "Mismatched secret key material".into()).into()),
+        })
+    }
+
+    /// Common code for exporting Curve25519 secrets.
+    fn export_25519_secret<F>(&self, fun: F) -> Result<Protected>
+    where
+        F: FnMut(&mpi::SecretKeyMaterial) -> Result<Protected>,
+    {
+        match self.secret() {
+            SecretKeyMaterial::Unencrypted(u) => u.map(fun),
+            SecretKeyMaterial::Encrypted(_) => Err(Error::InvalidOperation(
+                "Secret key material is encrypted".into()).into()),
+        }
+    }
+
     /// Generates a new ECC key over `curve`.
     ///
     /// If `for_signing` is false a ECDH key, if it's true either a
@@ -1272,7 +1379,7 @@ impl<R> Key4<SecretParts, R>
 
         let (pk_algo, public, secret) = match (curve, for_signing) {
             (Curve::Ed25519, true) => {
-                let (secret, public) = Backend::ed25519_generate_key()?;
+                let (secret, public) = crate::crypto::rng::ed25519_generate_key()?;
 
                 (
                     PublicKeyAlgorithm::EdDSA,
@@ -1287,7 +1394,7 @@ impl<R> Key4<SecretParts, R>
             },
 
             (Curve::Cv25519, false) => {
-                let (mut secret, public) = Backend::x25519_generate_key()?;
+                let (mut secret, public) = crate::crypto::rng::x25519_generate_key()?;
 
                 // Clamp the X25519 secret key scalar.
                 //
@@ -1331,6 +1438,60 @@ impl<R> Key4<SecretParts, R>
         Self::with_secret(crate::now(), pk_algo, public, secret.into())
     }
 
+    /// Generates a new ECDH key over `curve`.
+    ///
+    /// The key derivation function will use hash algorithm `hash`,
+    /// and session keys will be wrapped using symmetric algorithm
+    /// `sym`.  If one or both are `None`, the defaults for `curve`
+    /// are used.  Returns [`Error::InvalidEcdhParameters`] if the
+    /// parameters are not acceptable, see
+    /// [`crypto::ecdh::check_kdf_parameters`].
+    ///
+    ///   [`Error::InvalidEcdhParameters`]: crate::Error::InvalidEcdhParameters
+    ///   [`crypto::ecdh::check_kdf_parameters`]: crate::crypto::ecdh::check_kdf_parameters
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::crypto::mpi;
+    /// use openpgp::packet::key::{Key4, SecretParts, SubordinateRole};
+    /// use openpgp::types::{Curve, HashAlgorithm, SymmetricAlgorithm};
+    ///
+    /// let key: Key4<SecretParts, SubordinateRole> =
+    ///     Key4::generate_ecdh(Curve::Cv25519, HashAlgorithm::SHA512,
+    ///                         SymmetricAlgorithm::AES256)?;
+    /// if let mpi::PublicKey::ECDH { hash, sym, .. } = key.mpis() {
+    ///     assert_eq!(hash, &HashAlgorithm::SHA512);
+    ///     assert_eq!(sym, &SymmetricAlgorithm::AES256);
+    /// }
+    ///
+    /// // Weak parameters are rejected.
+    /// assert!(Key4::<SecretParts, SubordinateRole>::generate_ecdh(
+    ///     Curve::Cv25519, HashAlgorithm::SHA1, None).is_err());
+    /// # Ok(()) }
+    /// ```
+    pub fn generate_ecdh<H, S>(curve: Curve, hash: H, sym: S) -> Result<Self>
+        where H: Into<Option<HashAlgorithm>>,
+              S: Into<Option<SymmetricAlgorithm>>,
+    {
+        use crate::crypto::ecdh;
+
+        let hash = hash.into().unwrap_or_else(
+            || ecdh::default_ecdh_kdf_hash(&curve));
+        let sym = sym.into().unwrap_or_else(
+            || ecdh::default_ecdh_kek_cipher(&curve));
+        ecdh::check_kdf_parameters(hash, sym)?;
+
+        let mut key = Self::generate_ecc(false, curve)?;
+        if let mpi::PublicKey::ECDH { hash: h, sym: s, .. } = key.mpis_mut() {
+            *h = hash;
+            *s = sym;
+        }
+        Ok(key)
+    }
+
     /// Generates a new DSA key with a public modulus of size `p_bits`.
     ///
     /// Note: In order to comply with FIPS 186-4, and to increase
@@ -1431,6 +1592,101 @@ impl<P, R> Key4<P, R>
         &self.mpis
     }
 
+    /// Returns the length of the public key in bits.
+    ///
+    /// For finite field crypto this returns the size of the field we
+    /// operate in, for ECC it returns `Curve::bits()`.  See
+    /// [`mpi::PublicKey::bits`] for details.
+    ///
+    /// Returns `None` for unknown keys and curves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::packet::key::{Key4, PrimaryRole, SecretParts};
+    /// use openpgp::types::Curve;
+    ///
+    /// let key: Key4<SecretParts, PrimaryRole> =
+    ///     Key4::generate_ecc(true, Curve::Ed25519)?;
+    /// assert_eq!(key.bits(), Some(256));
+    /// # Ok(()) }
+    /// ```
+    pub fn bits(&self) -> Option<usize> {
+        self.mpis.bits()
+    }
+
+    /// Returns the raw X25519 public key.
+    ///
+    /// This is the inverse of [`Key4::import_public_cv25519`]: the
+    /// public key is returned in the native X25519 representation,
+    /// i.e. without OpenPGP's `0x40` prefix.
+    ///
+    /// Returns an error if this is not an ECDH key over Curve25519.
+    pub fn export_public_cv25519(&self) -> Result<[u8; 32]> {
+        match &self.mpis {
+            mpi::PublicKey::ECDH { curve: Curve::Cv25519, q, .. } =>
+                export_25519_point(q, &Curve::Cv25519),
+            _ => Err(Error::InvalidOperation(
+                "Not an ECDH key over Curve25519".into()).into()),
+        }
+    }
+
+    /// Returns the raw Ed25519 public key.
+    ///
+    /// This is the inverse of [`Key4::import_public_ed25519`]: the
+    /// public key is returned in the native Ed25519 representation,
+    /// i.e. without OpenPGP's `0x40` prefix.
+    ///
+    /// Returns an error if this is not an EdDSA key over Ed25519.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::packet::key::{Key4, PrimaryRole, PublicParts};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let raw = [0x42; 32];
+    /// let t = UNIX_EPOCH + Duration::new(1_500_000_000, 0);
+    /// let key: Key4<PublicParts, PrimaryRole> =
+    ///     Key4::import_public_ed25519(&raw, t)?;
+    /// assert_eq!(key.creation_time(), t);
+    /// assert_eq!(key.export_public_ed25519()?, raw);
+    /// # Ok(()) }
+    /// ```
+    pub fn export_public_ed25519(&self) -> Result<[u8; 32]> {
+        match &self.mpis {
+            mpi::PublicKey::EdDSA { curve: Curve::Ed25519, q } =>
+                export_25519_point(q, &Curve::Ed25519),
+            _ => Err(Error::InvalidOperation(
+                "Not an EdDSA key over Ed25519".into()).into()),
+        }
+    }
+
+    /// Returns the curve, if any.
+    ///
+    /// Returns `None` for keys that are not ECC keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::packet::key::{Key4, PrimaryRole, SecretParts};
+    /// use openpgp::types::Curve;
+    ///
+    /// let key: Key4<SecretParts, PrimaryRole> =
+    ///     Key4::generate_ecc(false, Curve::Cv25519)?;
+    /// assert_eq!(key.curve(), Some(&Curve::Cv25519));
+    /// # Ok(()) }
+    /// ```
+    pub fn curve(&self) -> Option<&Curve> {
+        self.mpis.curve()
+    }
+
     /// Returns a mutable reference to the `Key`'s MPIs.
     pub fn mpis_mut(&mut self) -> &mut mpi::PublicKey {
         // Clear the cache.
@@ -1737,12 +1993,167 @@ impl SecretKeyMaterial {
     }
 
     /// Returns whether the secret key material is encrypted.
+    ///
+    /// Note: stubs for [unavailable] secret key material are
+    /// considered encrypted.
+    ///
+    /// [unavailable]: SecretKeyMaterial::unavailable()
     pub fn is_encrypted(&self) -> bool {
         match self {
             SecretKeyMaterial::Encrypted(_) => true,
             SecretKeyMaterial::Unencrypted(_) => false,
         }
     }
+
+    /// Returns why the secret key material is unavailable, if it is
+    /// merely a stub.
+    ///
+    /// See [`Unavailable`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::packet::key::{SecretKeyMaterial, Unavailable};
+    ///
+    /// let stub = SecretKeyMaterial::try_from(Unavailable::GnuPGDummy)?;
+    /// assert!(stub.is_encrypted());
+    /// assert_eq!(stub.unavailable(), Some(Unavailable::GnuPGDummy));
+    /// # Ok(()) }
+    /// ```
+    pub fn unavailable(&self) -> Option<Unavailable> {
+        match self {
+            SecretKeyMaterial::Encrypted(e) => e.unavailable(),
+            SecretKeyMaterial::Unencrypted(_) => None,
+        }
+    }
+}
+
+/// Describes why secret key material is not available.
+///
+/// GnuPG uses [private S2K extensions] to mark secret keys whose
+/// secret key material is not present in the keyring.  For instance,
+/// `gpg --export-secret-subkeys` replaces the primary key's secret
+/// key material with a `gnu-dummy` stub, and keys that have been
+/// moved to a smartcard using `keytocard` are replaced by a
+/// `gnu-divert-to-card` stub that records the card's serial number.
+///
+/// Such stubs are parsed into [`SecretKeyMaterial::Encrypted`] using
+/// a [`S2K::Private`] key derivation mechanism.  Use
+/// [`SecretKeyMaterial::unavailable`] to recognize them.  They are
+/// preserved when the key is serialized again.  Trying to decrypt a
+/// stub fails.
+///
+/// To create a stub, convert an `Unavailable` into a
+/// [`SecretKeyMaterial`] using [`TryFrom`].
+///
+///   [private S2K extensions]: https://git.gnupg.org/cgi-bin/gitweb.cgi?p=gnupg.git;a=blob;f=doc/DETAILS
+///   [`S2K::Private`]: super::super::crypto::S2K::Private
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Unavailable {
+    /// The secret key material has been removed (GnuPG's
+    /// `gnu-dummy` S2K extension).
+    GnuPGDummy,
+
+    /// The secret key material is stored on a smartcard (GnuPG's
+    /// `gnu-divert-to-card` S2K extension).
+    GnuPGDivertToCard {
+        /// The serial number of the card.
+        ///
+        /// This is at most 16 bytes long, and may be empty.
+        serial: Box<[u8]>,
+    },
+}
+
+assert_send_and_sync!(Unavailable);
+
+impl Unavailable {
+    /// The S2K tag used by GnuPG's extensions.
+    const GNUPG_S2K_TAG: u8 = 101;
+
+    /// The magic bytes following the hash algorithm octet.
+    const GNUPG_MAGIC: &'static [u8] = b"GNU";
+
+    /// Recognizes GnuPG's S2K extensions.
+    pub(crate) fn from_s2k(s2k: &S2K) -> Option<Self> {
+        #[allow(deprecated)]
+        let parameters = match s2k {
+            S2K::Private { tag: Self::GNUPG_S2K_TAG, parameters: Some(p) } => p,
+            _ => return None,
+        };
+
+        match parameters.get(1..) {
+            Some([b'G', b'N', b'U', 1]) => Some(Unavailable::GnuPGDummy),
+            Some([b'G', b'N', b'U', 2, len, serial @ ..])
+                if *len as usize == serial.len() =>
+                Some(Unavailable::GnuPGDivertToCard {
+                    serial: serial.into(),
+                }),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of the parameters of the GnuPG S2K
+    /// extension at the start of `data`, excluding the tag.
+    ///
+    /// `data` starts with the octet following the tag.  Returns
+    /// `None` if `data` is not a known GnuPG S2K extension.
+    pub(crate) fn gnupg_s2k_len(data: &[u8]) -> Option<usize> {
+        if data.get(1..4) != Some(Self::GNUPG_MAGIC) {
+            return None;
+        }
+
+        match data.get(4) {
+            Some(1) => Some(5),
+            Some(2) => data.get(5).map(|len| 6 + *len as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns the S2K object encoding this stub.
+    fn to_s2k(&self) -> Result<S2K> {
+        // GnuPG writes the hash algorithm octet, but ignores it.
+        let mut p = vec![0];
+        p.extend_from_slice(Self::GNUPG_MAGIC);
+        match self {
+            Unavailable::GnuPGDummy => p.push(1),
+            Unavailable::GnuPGDivertToCard { serial } => {
+                if serial.len() > 16 {
+                    return Err(Error::InvalidArgument(format!(
+                        "Card serial number too long: {} bytes",
+                        serial.len())).into());
+                }
+                p.push(2);
+                p.push(serial.len() as u8);
+                p.extend_from_slice(serial);
+            },
+        }
+
+        #[allow(deprecated)]
+        Ok(S2K::Private {
+            tag: Self::GNUPG_S2K_TAG,
+            parameters: Some(p.into()),
+        })
+    }
+}
+
+impl TryFrom<Unavailable> for SecretKeyMaterial {
+    type Error = anyhow::Error;
+
+    /// Creates a stub.
+    ///
+    /// Fails if the card serial number of a
+    /// [`Unavailable::GnuPGDivertToCard`] stub is longer than 16
+    /// bytes.
+    fn try_from(u: Unavailable) -> Result<Self> {
+        Ok(Encrypted::new(u.to_s2k()?,
+                          SymmetricAlgorithm::Unencrypted,
+                          Some(mpi::SecretKeyChecksum::SHA1),
+                          Vec::new().into_boxed_slice())
+           .into())
+    }
 }
 
 /// Unencrypted secret key material.
@@ -1930,6 +2341,14 @@ impl Encrypted {
         self.checksum
     }
 
+    /// Returns why the secret key material is unavailable, if it is
+    /// merely a stub.
+    ///
+    /// See [`Unavailable`] for details.
+    pub fn unavailable(&self) -> Option<Unavailable> {
+        Unavailable::from_s2k(&self.s2k)
+    }
+
     /// Returns the encrypted secret key material.
     ///
     /// If the [`S2K`] mechanism is not supported by Sequoia, this
@@ -1966,6 +2385,11 @@ impl Encrypted {
         use std::io::{Cursor, Read};
         use crate::crypto::symmetric::Decryptor;
 
+        if let Some(u) = self.unavailable() {
+            return Err(Error::InvalidOperation(format!(
+                "Secret key material is unavailable: {:?}", u)).into());
+        }
+
         let key = self.s2k.derive_key(password, self.algo.key_size()?)?;
         let ciphertext = self.ciphertext()?;
         let cur = Cursor::new(ciphertext);
@@ -1986,7 +2410,7 @@ impl Encrypted {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl<P, R> Arbitrary for super::Key<P, R>
     where P: KeyParts, P: Clone,
           R: KeyRole, R: Clone,
@@ -1997,21 +2421,21 @@ impl<P, R> Arbitrary for super::Key<P, R>
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Key4<PublicParts, PrimaryRole> {
     fn arbitrary(g: &mut Gen) -> Self {
         Key4::<PublicParts, UnspecifiedRole>::arbitrary(g).into()
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Key4<PublicParts, SubordinateRole> {
     fn arbitrary(g: &mut Gen) -> Self {
         Key4::<PublicParts, UnspecifiedRole>::arbitrary(g).into()
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Key4<PublicParts, UnspecifiedRole> {
     fn arbitrary(g: &mut Gen) -> Self {
         let mpis = mpi::PublicKey::arbitrary(g);
@@ -2029,21 +2453,21 @@ impl Arbitrary for Key4<PublicParts, UnspecifiedRole> {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Key4<SecretParts, PrimaryRole> {
     fn arbitrary(g: &mut Gen) -> Self {
         Key4::<SecretParts, UnspecifiedRole>::arbitrary(g).into()
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Key4<SecretParts, SubordinateRole> {
     fn arbitrary(g: &mut Gen) -> Self {
         Key4::<SecretParts, UnspecifiedRole>::arbitrary(g).into()
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Key4<SecretParts, UnspecifiedRole> {
     fn arbitrary(g: &mut Gen) -> Self {
         let key = Key4::arbitrary(g);
@@ -2061,6 +2485,23 @@ impl Arbitrary for Key4<SecretParts, UnspecifiedRole> {
     }
 }
 
+/// Returns an error if `key` is not a Curve25519 key of length 32.
+fn check_25519_len(what: &str, key: &[u8]) -> Result<()> {
+    if key.len() != 32 {
+        return Err(Error::InvalidArgument(
+            format!("{} has length {}, expected 32", what, key.len())).into());
+    }
+    Ok(())
+}
+
+/// Decodes a Curve25519 point, stripping OpenPGP's prefix.
+fn export_25519_point(q: &mpi::MPI, curve: &Curve) -> Result<[u8; 32]> {
+    let (x, _) = q.decode_point(curve)?;
+    let mut raw = [0; 32];
+    raw.copy_from_slice(x);
+    Ok(raw)
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -2394,6 +2835,10 @@ mod tests {
                 assert_eq!(&q.value()[1..], &public[..]),
             _ => unreachable!(),
         }
+        assert_eq!(&key.export_public_cv25519()?, public);
+        // The secret is already clamped, so we get it back unchanged.
+        assert_eq!(&key.parts_as_secret()?.export_secret_cv25519()?[..],
+                   &secret[..]);
 
         // PKESK
         let eph_pubkey: &[u8; 33] = b"\x40\xda\x1c\x69\xc4\xe3\xb6\x9c\x6e\xd4\xc6\x69\x6c\x89\xc7\x09\xe9\xf8\x6a\xf1\xe3\x8d\xb6\xaa\xb5\xf7\x29\xae\xa6\xe7\xdd\xfe\x38";
@@ -2416,6 +2861,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn export_25519() -> Result<()> {
+        let ed: Key4<SecretParts, PrimaryRole> =
+            Key4::generate_ecc(true, Curve::Ed25519)?;
+        let cv: Key4<SecretParts, SubordinateRole> =
+            Key4::generate_ecc(false, Curve::Cv25519)?;
+
+        // Mismatched algorithms.
+        assert!(ed.export_public_cv25519().is_err());
+        assert!(ed.export_secret_cv25519().is_err());
+        assert!(cv.export_public_ed25519().is_err());
+        assert!(cv.export_secret_ed25519().is_err());
+        let rsa: Key4<SecretParts, PrimaryRole> =
+            Key4::generate_rsa(2048)?;
+        assert!(rsa.export_public_ed25519().is_err());
+
+        // Encrypted secrets can't be exported.
+        let p: Password = "streng geheim".into();
+        let encrypted = ed.clone().encrypt_secret(&p)?;
+        assert!(encrypted.export_secret_ed25519().is_err());
+        assert_eq!(encrypted.decrypt_secret(&p)?.export_secret_ed25519()?,
+                   ed.export_secret_ed25519()?);
+
+        // Secrets with leading zeros are stored in shortened MPIs.
+        let mut raw = [0x23; 32];
+        raw[0] = 0;
+        raw[1] = 0;
+        let ed: Key4<SecretParts, PrimaryRole> =
+            Key4::import_secret_ed25519(&raw, None)?;
+        assert_eq!(&ed.export_secret_ed25519()?[..], &raw[..]);
+
+        // Keys of the wrong length are rejected.
+        assert!(Key4::<PublicParts, PrimaryRole>::import_public_ed25519(
+            &[0x40; 33], None).is_err());
+        assert!(Key4::<PublicParts, PrimaryRole>::import_public_cv25519(
+            &[0x40; 31], None, None, None).is_err());
+        assert!(Key4::<SecretParts, PrimaryRole>::import_secret_ed25519(
+            &[0x40; 33], None).is_err());
+        assert!(Key4::<SecretParts, PrimaryRole>::import_secret_cv25519(
+            &[0x40; 31], None, None, None).is_err());
+        Ok(())
+    }
+
     #[test]
     fn import_rsa() {
         use crate::crypto::SessionKey;
@@ -2721,6 +3209,114 @@ FwPoSAbbsLkNS/iNN2MDGAVYvezYn2QZ
         assert_ne!(fpr1, key.fingerprint());
     }
 
+    #[test]
+    fn key_material_accessors() -> Result<()> {
+        for for_signing in [true, false] {
+            for curve in Curve::variants()
+                .filter(Curve::is_supported)
+            {
+                match curve {
+                    Curve::Cv25519 if for_signing => continue,
+                    Curve::Ed25519 if ! for_signing => continue,
+                    _ => (),
+                }
+
+                let key: Key<SecretParts, UnspecifiedRole> =
+                    Key4::generate_ecc(for_signing, curve.clone())?.into();
+                assert_eq!(key.curve(), Some(&curve));
+                assert_eq!(key.bits(), curve.bits());
+                assert_eq!(key.mpis().algo(), Some(key.pk_algo()));
+            }
+        }
+
+        let key: Key<SecretParts, UnspecifiedRole> =
+            Key4::generate_rsa(2048)?.into();
+        assert_eq!(key.curve(), None);
+        assert_eq!(key.bits(), Some(2048));
+        assert_eq!(key.mpis().algo(), Some(key.pk_algo()));
+        Ok(())
+    }
+
+    #[test]
+    fn ecdh_kdf_parameters() -> Result<()> {
+        use crate::serialize::SerializeInto;
+
+        let check = |key: &Key4<_, _>, h, s| {
+            if let mpi::PublicKey::ECDH { hash, sym, .. } = key.mpis() {
+                assert_eq!(*hash, h);
+                assert_eq!(*sym, s);
+            } else {
+                panic!("not an ECDH key");
+            }
+        };
+
+        // Defaults depend on the curve.
+        for curve in Curve::variants()
+            .filter(Curve::is_supported)
+            .filter(|c| c != &Curve::Ed25519)
+        {
+            let key: Key4<SecretParts, SubordinateRole> =
+                Key4::generate_ecdh(curve.clone(), None, None)?;
+            check(&key, crate::crypto::ecdh::default_ecdh_kdf_hash(&curve),
+                  crate::crypto::ecdh::default_ecdh_kek_cipher(&curve));
+        }
+
+        let key: Key4<SecretParts, SubordinateRole> =
+            Key4::generate_ecdh(Curve::Cv25519, HashAlgorithm::SHA384,
+                                SymmetricAlgorithm::AES192)?;
+        check(&key, HashAlgorithm::SHA384, SymmetricAlgorithm::AES192);
+
+        // The key roundtrips, and can be used.
+        let key: Key<SecretParts, SubordinateRole> = key.into();
+        let bytes = Packet::SecretSubkey(key.clone()).to_vec()?;
+        let p = Packet::from_bytes(&bytes)?;
+        if let Packet::SecretSubkey(k) = &p {
+            assert_eq!(k, &key);
+        } else {
+            panic!("expected a secret subkey, got {:?}", p);
+        }
+        let mut pair = key.into_keypair()?;
+        use crate::crypto::{SessionKey, Decryptor};
+        let sk = SessionKey::new(32);
+        let ciphertext = pair.public().encrypt(&sk)?;
+        assert_eq!(pair.decrypt(&ciphertext, Some(sk.len()))?, sk);
+
+        // Unacceptable parameters are rejected at generation time...
+        for (h, s) in [(HashAlgorithm::SHA1, SymmetricAlgorithm::AES128),
+                       (HashAlgorithm::SHA256, SymmetricAlgorithm::CAST5),
+                       (HashAlgorithm::Unknown(42),
+                        SymmetricAlgorithm::AES256)]
+        {
+            let err = Key4::<SecretParts, SubordinateRole>::generate_ecdh(
+                Curve::Cv25519, h, s).unwrap_err();
+            assert!(matches!(err.downcast_ref::<Error>(),
+                             Some(Error::InvalidEcdhParameters(a, b))
+                             if *a == h && *b == s));
+            assert!(Key4::<PublicParts, SubordinateRole>::import_public_cv25519(
+                &[0; 32], h, s, None).is_err());
+        }
+
+        // ... and when parsing.
+        let key: Key<PublicParts, SubordinateRole> =
+            Key4::new(crate::now(), PublicKeyAlgorithm::ECDH,
+                      mpi::PublicKey::ECDH {
+                          curve: Curve::Cv25519,
+                          q: mpi::MPI::new_compressed_point(&[0; 32]),
+                          hash: HashAlgorithm::SHA1,
+                          sym: SymmetricAlgorithm::AES128,
+                      })?.into();
+        let p = Packet::from_bytes(&Packet::PublicSubkey(key).to_vec()?)?;
+        if let Packet::Unknown(u) = &p {
+            assert!(matches!(u.error().downcast_ref::<Error>(),
+                             Some(Error::InvalidEcdhParameters(
+                                 HashAlgorithm::SHA1,
+                                 SymmetricAlgorithm::AES128))));
+        } else {
+            panic!("expected an unknown packet, got {:?}", p);
+        }
+        Ok(())
+    }
+
     /// Smoke test for ECC key creation, signing and verification, and
     /// encryption and decryption.
     #[test]
@@ -2817,4 +3413,54 @@ FwPoSAbbsLkNS/iNN2MDGAVYvezYn2QZ
 
         Ok(())
     }
+
+    /// Checks that GnuPG's secret key stubs are recognized and
+    /// round-trip.
+    #[test]
+    fn gnupg_stubs() -> Result<()> {
+        use crate::parse::Parse;
+        use crate::serialize::MarshalInto;
+
+        let key: Key<_, key::UnspecifiedRole> =
+            Key4::generate_ecc(true, Curve::Ed25519)?.into();
+
+        for (stub, s2k) in vec![
+            (Unavailable::GnuPGDummy, &b"\xfe\x00\x65\x00GNU\x01"[..]),
+            (Unavailable::GnuPGDivertToCard {
+                serial: b"\xd2\x76\x00\x01\x24\x01"[..].into(),
+            }, &b"\xfe\x00\x65\x00GNU\x02\x06\xd2\x76\x00\x01\x24\x01"[..]),
+        ] {
+            let (stubbed, _) = key.clone().add_secret(
+                SecretKeyMaterial::try_from(stub.clone())?);
+            let buf = stubbed.to_vec()?;
+            // The stub is written at the end of the packet.
+            assert!(buf.ends_with(s2k));
+
+            let parsed = Key::from_bytes(&buf)?.parts_into_secret()?;
+            assert_eq!(parsed.secret().unavailable(), Some(stub.clone()));
+            assert_eq!(parsed, stubbed);
+            assert_eq!(parsed.to_vec()?, buf);
+
+            let err = parsed.decrypt_secret(&"password".into()).unwrap_err();
+            assert!(matches!(err.downcast_ref::<Error>(),
+                             Some(Error::InvalidOperation(_))));
+        }
+
+        // Card serial numbers are at most 16 bytes long.
+        assert!(SecretKeyMaterial::try_from(Unavailable::GnuPGDivertToCard {
+            serial: vec![0; 16].into(),
+        }).is_ok());
+        assert!(SecretKeyMaterial::try_from(Unavailable::GnuPGDivertToCard {
+            serial: vec![0; 17].into(),
+        }).is_err());
+
+        // Other private S2K mechanisms are not mistaken for stubs.
+        #[allow(deprecated)]
+        let other: SecretKeyMaterial = Encrypted::new(
+            S2K::Private { tag: 101, parameters: Some(b"\x00GNU\x03"[..].into()) },
+            SymmetricAlgorithm::Unencrypted, None, Vec::new().into()).into();
+        assert_eq!(other.unavailable(), None);
+
+        Ok(())
+    }
 }
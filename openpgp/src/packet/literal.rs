@@ -3,7 +3,7 @@ use std::cmp;
 use std::convert::TryInto;
 use std::time;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::types::{DataFormat, Timestamp};
@@ -174,7 +174,7 @@ impl From<Literal> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Literal {
     fn arbitrary(g: &mut Gen) -> Self {
         let mut l = Literal::new(DataFormat::arbitrary(g));
@@ -1,4 +1,4 @@
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::packet;
@@ -28,7 +28,7 @@ impl From<Marker> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Marker {
     fn arbitrary(_: &mut Gen) -> Self {
         Self::default()
@@ -155,7 +155,7 @@ use std::ops::{Deref, DerefMut};
 use std::slice;
 use std::iter::IntoIterator;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::Error;
@@ -544,7 +544,7 @@ impl fmt::Debug for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Packet {
     fn arbitrary(g: &mut Gen) -> Self {
         use crate::arbitrary_helper::gen_arbitrary_from_range;
@@ -622,7 +622,7 @@ impl Common {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Common {
     fn arbitrary(_: &mut Gen) -> Self {
         // XXX: Change if this gets interesting fields.
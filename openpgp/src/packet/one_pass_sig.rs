@@ -6,7 +6,7 @@
 
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::Error;
@@ -169,14 +169,14 @@ impl<'a> std::convert::TryFrom<&'a Signature> for OnePassSig3 {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for super::OnePassSig {
     fn arbitrary(g: &mut Gen) -> Self {
         OnePassSig3::arbitrary(g).into()
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for OnePassSig3 {
     fn arbitrary(g: &mut Gen) -> Self {
         let mut ops = OnePassSig3::new(SignatureType::arbitrary(g));
@@ -5,7 +5,7 @@
 //!
 //!   [Section 5.1 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.1
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::Error;
@@ -154,6 +154,7 @@ impl PKESK3 {
         } else {
             None
         };
+        crate::crypto::stats::decryption();
         let plain = decryptor.decrypt(&self.esk, plaintext_len)?;
         let key_rgn = 1..plain.len().saturating_sub(2);
         let sym_algo: SymmetricAlgorithm = plain[0].into();
@@ -193,14 +194,14 @@ impl From<PKESK3> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for super::PKESK {
     fn arbitrary(g: &mut Gen) -> Self {
         PKESK3::arbitrary(g).into()
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for PKESK3 {
     fn arbitrary(g: &mut Gen) -> Self {
         let (ciphertext, pk_algo) = loop {
@@ -121,7 +121,7 @@ use std::ops::{Deref, DerefMut};
 use std::sync::OnceLock;
 use std::time::SystemTime;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::Error;
@@ -154,7 +154,7 @@ use crate::packet::signature::subpacket::{
 };
 use crate::types::Timestamp;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 /// Like quickcheck::Arbitrary, but bounded.
 trait ArbitraryBounded {
     /// Generates an arbitrary value, but only recurses if `depth >
@@ -162,11 +162,11 @@ trait ArbitraryBounded {
     fn arbitrary_bounded(g: &mut Gen, depth: usize) -> Self;
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 /// Default depth when implementing Arbitrary using ArbitraryBounded.
 const DEFAULT_ARBITRARY_DEPTH: usize = 2;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 macro_rules! impl_arbitrary_with_bound {
     ($typ:path) => {
         impl Arbitrary for $typ {
@@ -228,7 +228,7 @@ pub struct SignatureFields {
 }
 assert_send_and_sync!(SignatureFields);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl ArbitraryBounded for SignatureFields {
     fn arbitrary_bounded(g: &mut Gen, depth: usize) -> Self {
         SignatureFields {
@@ -243,7 +243,7 @@ impl ArbitraryBounded for SignatureFields {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl_arbitrary_with_bound!(SignatureFields);
 
 impl Deref for SignatureFields {
@@ -454,6 +454,12 @@ pub struct SignatureBuilder {
     reference_time: Option<SystemTime>,
     overrode_creation_time: bool,
     original_creation_time: Option<SystemTime>,
+    /// The salt to use instead of a random one.
+    ///
+    /// This is only used to create deterministic signatures for
+    /// tests.
+    #[cfg(feature = "test-util")]
+    fixed_salt: Option<[u8; 32]>,
     fields: SignatureFields,
 }
 assert_send_and_sync!(SignatureBuilder);
@@ -479,6 +485,8 @@ impl SignatureBuilder {
             reference_time: None,
             overrode_creation_time: false,
             original_creation_time: None,
+            #[cfg(feature = "test-util")]
+            fixed_salt: None,
             fields: SignatureFields {
                 version: 4,
                 typ,
@@ -1560,6 +1568,160 @@ impl SignatureBuilder {
         self.sign(signer, digest)
     }
 
+    /// Computes the digest that a signature would be made over.
+    ///
+    /// This is useful if the signature is made by an external
+    /// component, like a hardware security module or a remote signing
+    /// service, that signs digests, and cannot be driven using the
+    /// [`Signer`] trait.  The signature is created in three steps:
+    /// first, this function computes the digest that the signature
+    /// covers.  Then, the external component signs the digest.
+    /// Finally, the raw signature is attached using
+    /// [`SignatureBuilder::attach_signature`].
+    ///
+    ///   [`SignatureBuilder::attach_signature`]: SignatureBuilder::attach_signature()
+    ///
+    /// `key` is the public key that will make the signature.  `data`
+    /// is what the signature is made over, see [`SignedData`].  This
+    /// function checks that the [signature type] is appropriate for
+    /// `data`, like the corresponding `sign_*` function does.
+    ///
+    ///   [signature type]: crate::types::SignatureType
+    ///
+    /// Like [`SignatureBuilder::pre_sign`], this function sets the
+    /// public-key algorithm, and, if they have not been set, the
+    /// signature creation time and the issuer subpackets, and adds a
+    /// salt.  Therefore, the returned builder must be used to attach
+    /// the signature: any modification invalidates the digest.
+    ///
+    ///   [`SignatureBuilder::pre_sign`]: SignatureBuilder::pre_sign()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::crypto::Signer;
+    /// use openpgp::packet::prelude::*;
+    /// use openpgp::packet::signature::SignedData;
+    /// use openpgp::types::SignatureType;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let (cert, _) = CertBuilder::new().generate()?;
+    /// let key = cert.primary_key().key();
+    ///
+    /// let msg = b"Hello, world!";
+    /// let (builder, digest) = SignatureBuilder::new(SignatureType::Binary)
+    ///     .compute_digest(key, SignedData::Message(msg))?;
+    ///
+    /// // Sign the digest, using an external component.
+    /// # let mut hsm = key.clone().parts_into_secret()?.into_keypair()?;
+    /// let mpis = hsm.sign(builder.hash_algo(), &digest)?;
+    ///
+    /// let sig = builder.attach_signature(digest, mpis)?;
+    /// sig.verify_message(key, msg)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn compute_digest<P, R>(mut self, key: &Key<P, R>,
+                                data: SignedData)
+        -> Result<(Self, Vec<u8>)>
+        where P: key::KeyParts,
+              R: key::KeyRole,
+    {
+        use SignatureType::*;
+        let ok = match (&data, self.typ) {
+            (_, Unknown(_)) => true,
+            (SignedData::Standalone, Standalone) => true,
+            (SignedData::Timestamp, Timestamp) => true,
+            (SignedData::DirectKey(_), DirectKey | KeyRevocation) => true,
+            (SignedData::UserID { .. } | SignedData::UserAttribute { .. },
+             GenericCertification | PersonaCertification
+             | CasualCertification | PositiveCertification
+             | CertificationRevocation) => true,
+            (SignedData::SubkeyBinding { .. },
+             SubkeyBinding | SubkeyRevocation) => true,
+            (SignedData::PrimaryKeyBinding { .. }, PrimaryKeyBinding) => true,
+            (SignedData::Message(_), Binary | Text) => true,
+            (SignedData::Hash(_), _) => true,
+            _ => false,
+        };
+        if ! ok {
+            return Err(Error::UnsupportedSignatureType(self.typ).into());
+        }
+
+        if let SignedData::Hash(hash) = &data {
+            self.hash_algo = hash.algo();
+        }
+
+        self = self.prepare(key.parts_as_public().role_as_unspecified())?;
+
+        let hash = match data {
+            SignedData::Hash(mut hash) => {
+                self.hash(&mut hash);
+                hash
+            },
+            data => {
+                let mut hash = self.hash_algo().context()?;
+                match data {
+                    SignedData::Standalone =>
+                        self.hash_standalone(&mut hash),
+                    SignedData::Timestamp =>
+                        self.hash_timestamp(&mut hash),
+                    SignedData::DirectKey(primary) =>
+                        self.hash_direct_key(&mut hash, primary),
+                    SignedData::UserID { primary, userid } =>
+                        self.hash_userid_binding(&mut hash, primary, userid),
+                    SignedData::UserAttribute { primary, user_attribute } =>
+                        self.hash_user_attribute_binding(
+                            &mut hash, primary, user_attribute),
+                    SignedData::SubkeyBinding { primary, subkey } =>
+                        self.hash_subkey_binding(&mut hash, primary, subkey),
+                    SignedData::PrimaryKeyBinding { primary, subkey } =>
+                        self.hash_primary_key_binding(
+                            &mut hash, primary, subkey),
+                    SignedData::Message(msg) => {
+                        hash.update(msg);
+                        self.hash(&mut hash);
+                    },
+                    SignedData::Hash(_) => unreachable!("handled above"),
+                }
+                hash
+            },
+        };
+
+        let digest = hash.into_digest()?;
+        Ok((self, digest))
+    }
+
+    /// Creates a signature from an externally made raw signature.
+    ///
+    /// `digest` is the digest returned by
+    /// [`SignatureBuilder::compute_digest`], and `mpis` is the
+    /// signature over it.  The builder must be the one returned by
+    /// `compute_digest`.
+    ///
+    ///   [`SignatureBuilder::compute_digest`]: SignatureBuilder::compute_digest()
+    ///
+    /// This function does not verify the signature.  Use, for
+    /// instance, [`Signature::verify_digest`] to check that the
+    /// external component made a valid signature.
+    ///
+    ///   [`Signature::verify_digest`]: super::Signature::verify_digest()
+    ///
+    /// See [`SignatureBuilder::compute_digest`] for an example.
+    pub fn attach_signature(self, digest: Vec<u8>, mpis: mpi::Signature)
+        -> Result<Signature>
+    {
+        if digest.len() != self.hash_algo().digest_size()? {
+            return Err(Error::InvalidArgument(format!(
+                "Expected a {} digest of {} bytes, got {} bytes",
+                self.hash_algo(), self.hash_algo().digest_size()?,
+                digest.len())).into());
+        }
+
+        Ok(self.assemble(digest, mpis))
+    }
+
     /// Sets the signature builder's default reference time.
     ///
     /// The reference time is used when no time is specified.  The
@@ -1632,9 +1794,9 @@ impl SignatureBuilder {
 
         let now = || -> Result<SystemTime> {
             let rt = self.reference_time.unwrap_or_else(crate::now);
-            // Roundtrip via Timestamp to ensure that the time has the
-            // right resolution and is representable.
-            Ok(SystemTime::from(Timestamp::try_from(rt)?))
+            // Ensure that the time has the right resolution and is
+            // representable.
+            Timestamp::normalize(rt)
         };
 
         if ! self.overrode_creation_time {
@@ -1671,6 +1833,20 @@ impl SignatureBuilder {
     /// including a salt.  Then, it sorts the subpackets.  The
     /// function is idempotent modulo salt value.
     ///
+    /// # Errors
+    ///
+    /// Subpackets in the unhashed area are not protected by the
+    /// signature, and all but the self-authenticating ones are
+    /// ignored (see [`SubpacketAreas::ignored_subpackets`]).  If a
+    /// security relevant subpacket, like the Signature Expiration
+    /// Time or the Key Flags subpacket, is found in the unhashed
+    /// area, this function returns [`Error::InvalidArgument`].  Use
+    /// [`SubpacketAreas::move_to_hashed_area`] to move it to the
+    /// hashed area.
+    ///
+    ///   [`SubpacketAreas::ignored_subpackets`]: subpacket::SubpacketAreas::ignored_subpackets()
+    ///   [`SubpacketAreas::move_to_hashed_area`]: subpacket::SubpacketAreas::move_to_hashed_area()
+    ///
     /// # Examples
     ///
     /// Occasionally, it is useful to determine the available space in
@@ -1704,8 +1880,28 @@ impl SignatureBuilder {
     ///     SubpacketArea::MAX_SIZE - sig.hashed_area().serialized_len());
     /// # Ok(()) }
     /// ```
-    pub fn pre_sign(mut self, signer: &dyn Signer) -> Result<Self> {
-        self.pk_algo = signer.public().pk_algo();
+    pub fn pre_sign(self, signer: &dyn Signer) -> Result<Self> {
+        self.prepare(signer.public())
+    }
+
+    /// Prepares the signature for signing by `key`.
+    ///
+    /// See [`SignatureBuilder::pre_sign`].
+    fn prepare(mut self, key: &Key<key::PublicParts, key::UnspecifiedRole>)
+               -> Result<Self>
+    {
+        // Security relevant subpackets in the unhashed area would be
+        // ignored, which is almost certainly not what the caller
+        // intended.
+        if let Some(sp) = self.unhashed_area().iter()
+            .find(|sp| sp.tag().must_be_hashed())
+        {
+            return Err(Error::InvalidArgument(format!(
+                "{} subpacket must be in the hashed area",
+                sp.tag())).into());
+        }
+
+        self.pk_algo = key.pk_algo();
 
         // Set the creation time.
         if ! self.overrode_creation_time {
@@ -1718,13 +1914,22 @@ impl SignatureBuilder {
         if self.issuers().next().is_none()
             && self.issuer_fingerprints().next().is_none()
         {
-            self = self.set_issuer(signer.public().keyid())?
-                .set_issuer_fingerprint(signer.public().fingerprint())?;
+            self = self.set_issuer(key.keyid())?
+                .set_issuer_fingerprint(key.fingerprint())?;
         }
 
         // Add a salt to make the signature unpredictable.
-        let mut salt = [0; 32];
-        crate::crypto::random(&mut salt);
+        #[cfg(feature = "test-util")]
+        let fixed_salt = self.fixed_salt;
+        #[cfg(not(feature = "test-util"))]
+        let fixed_salt: Option<[u8; 32]> = None;
+        let salt = if let Some(salt) = fixed_salt {
+            salt
+        } else {
+            let mut salt = [0; 32];
+            crate::crypto::try_random(&mut salt)?;
+            salt
+        };
         self = self.set_notation("salt@notations.sequoia-pgp.org",
                                  salt, None, false)?;
 
@@ -1733,12 +1938,26 @@ impl SignatureBuilder {
         Ok(self)
     }
 
+    /// Uses the given salt instead of a random one.
+    ///
+    /// This makes the signature predictable, and must only be used
+    /// to create reproducible test fixtures.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn set_fixed_salt(mut self, salt: [u8; 32]) -> Self {
+        self.fixed_salt = Some(salt);
+        self
+    }
+
     fn sign(self, signer: &mut dyn Signer, digest: Vec<u8>)
         -> Result<Signature>
     {
         let mpis = signer.sign(self.hash_algo, &digest)?;
+        Ok(self.assemble(digest, mpis))
+    }
 
-        Ok(Signature4 {
+    /// Creates the signature from the digest and the raw signature.
+    fn assemble(self, digest: Vec<u8>, mpis: mpi::Signature) -> Signature {
+        Signature4 {
             common: Default::default(),
             fields: self.fields,
             digest_prefix: [digest[0], digest[1]],
@@ -1746,10 +1965,99 @@ impl SignatureBuilder {
             computed_digest: digest.into(),
             level: 0,
             additional_issuers: OnceLock::new(),
-        }.into())
+        }.into()
     }
 }
 
+/// What a signature is made over.
+///
+/// This is used by [`SignatureBuilder::compute_digest`] to compute
+/// the digest that a signature covers.  Each variant corresponds to
+/// one of `SignatureBuilder`'s `sign_*` functions.
+///
+///   [`SignatureBuilder::compute_digest`]: SignatureBuilder::compute_digest()
+#[non_exhaustive]
+pub enum SignedData<'a> {
+    /// A standalone signature, see
+    /// [`SignatureBuilder::sign_standalone`].
+    ///
+    ///   [`SignatureBuilder::sign_standalone`]: SignatureBuilder::sign_standalone()
+    Standalone,
+
+    /// A timestamp signature, see
+    /// [`SignatureBuilder::sign_timestamp`].
+    ///
+    ///   [`SignatureBuilder::sign_timestamp`]: SignatureBuilder::sign_timestamp()
+    Timestamp,
+
+    /// A direct key signature or key revocation over the given
+    /// primary key, see [`SignatureBuilder::sign_direct_key`].
+    ///
+    ///   [`SignatureBuilder::sign_direct_key`]: SignatureBuilder::sign_direct_key()
+    DirectKey(&'a Key<key::PublicParts, key::PrimaryRole>),
+
+    /// A User ID binding, certification, or certification
+    /// revocation, see [`SignatureBuilder::sign_userid_binding`].
+    ///
+    ///   [`SignatureBuilder::sign_userid_binding`]: SignatureBuilder::sign_userid_binding()
+    UserID {
+        /// The certificate's primary key.
+        primary: &'a Key<key::PublicParts, key::PrimaryRole>,
+        /// The User ID.
+        userid: &'a UserID,
+    },
+
+    /// A User Attribute binding, certification, or certification
+    /// revocation, see
+    /// [`SignatureBuilder::sign_user_attribute_binding`].
+    ///
+    ///   [`SignatureBuilder::sign_user_attribute_binding`]: SignatureBuilder::sign_user_attribute_binding()
+    UserAttribute {
+        /// The certificate's primary key.
+        primary: &'a Key<key::PublicParts, key::PrimaryRole>,
+        /// The User Attribute.
+        user_attribute: &'a UserAttribute,
+    },
+
+    /// A subkey binding or subkey revocation, see
+    /// [`SignatureBuilder::sign_subkey_binding`].
+    ///
+    ///   [`SignatureBuilder::sign_subkey_binding`]: SignatureBuilder::sign_subkey_binding()
+    SubkeyBinding {
+        /// The certificate's primary key.
+        primary: &'a Key<key::PublicParts, key::PrimaryRole>,
+        /// The subkey.
+        subkey: &'a Key<key::PublicParts, key::SubordinateRole>,
+    },
+
+    /// A primary key binding, see
+    /// [`SignatureBuilder::sign_primary_key_binding`].
+    ///
+    ///   [`SignatureBuilder::sign_primary_key_binding`]: SignatureBuilder::sign_primary_key_binding()
+    PrimaryKeyBinding {
+        /// The certificate's primary key.
+        primary: &'a Key<key::PublicParts, key::PrimaryRole>,
+        /// The subkey.
+        subkey: &'a Key<key::PublicParts, key::SubordinateRole>,
+    },
+
+    /// A document signature over a message, see
+    /// [`SignatureBuilder::sign_message`].
+    ///
+    ///   [`SignatureBuilder::sign_message`]: SignatureBuilder::sign_message()
+    Message(&'a [u8]),
+
+    /// A signature over data that has already been hashed, see
+    /// [`SignatureBuilder::sign_hash`].
+    ///
+    /// The signature's hash algorithm is set to the hash context's
+    /// algorithm.
+    ///
+    ///   [`SignatureBuilder::sign_hash`]: SignatureBuilder::sign_hash()
+    Hash(Box<dyn hash::Digest>),
+}
+assert_send_and_sync!(SignedData<'_>);
+
 impl From<Signature> for SignatureBuilder {
     fn from(sig: Signature) -> Self {
         match sig {
@@ -1779,6 +2087,8 @@ impl From<Signature4> for SignatureBuilder {
             reference_time: None,
             overrode_creation_time: false,
             original_creation_time: creation_time,
+            #[cfg(feature = "test-util")]
+            fixed_salt: None,
             fields,
         }
     }
@@ -2730,6 +3040,13 @@ impl Signature {
 
     /// Verifies the signature against `digest`.
     ///
+    /// The `digest` must be computed over the payload followed by
+    /// the signature's hashed data (see [`Hash::hash`]).  If you hold
+    /// a hash context over the payload, e.g. because the payload has
+    /// been hashed while it was streamed, use
+    /// [`Signature::verify_hash`], which hashes in the signature
+    /// before verifying it.
+    ///
     /// Note: Due to limited context, this only verifies the
     /// cryptographic signature and checks that the key predates the
     /// signature.  Further constraints on the signature, like
@@ -3476,6 +3793,224 @@ impl Signature {
             signer.parts_as_public().role_as_unspecified(),
             Some(hash.into_digest()?.into()))
     }
+
+    /// Verifies a signature of a message read from `msg`.
+    ///
+    /// Like [`Signature::verify_message`], but hashes the message as
+    /// it is read from `msg`, so that large files do not have to be
+    /// read into memory first.
+    ///
+    /// Note: Due to limited context, this only verifies the
+    /// cryptographic signature, checks the signature's type, and
+    /// checks that the key predates the signature.  Further
+    /// constraints on the signature, like creation and expiration
+    /// time, or signature revocations must be checked by the caller.
+    ///
+    /// Likewise, this function does not check whether `signer` can
+    /// make valid signatures; it is up to the caller to make sure the
+    /// key is not revoked, not expired, has a valid self-signature,
+    /// has a subkey binding signature (if appropriate), has the
+    /// signing capability, etc.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sequoia_openpgp as openpgp;
+    /// # use openpgp::cert::prelude::*;
+    /// # use openpgp::packet::signature::SignatureBuilder;
+    /// # use openpgp::types::SignatureType;
+    /// # fn main() -> openpgp::Result<()> {
+    /// # let (cert, _) = CertBuilder::new().add_signing_subkey().generate()?;
+    /// # let key = cert.keys().subkeys().secret().next().unwrap().key();
+    /// # let mut signer = key.clone().into_keypair()?;
+    /// let msg = b"Hello, world!";
+    /// let sig = SignatureBuilder::new(SignatureType::Binary)
+    ///     .sign_message(&mut signer, msg)?;
+    ///
+    /// // The message may be any std::io::Read, e.g. a file.
+    /// sig.verify_message_reader(key, &msg[..])?;
+    /// # Ok(()) }
+    /// ```
+    pub fn verify_message_reader<M, P, R>(&self, signer: &Key<P, R>,
+                                          mut msg: M)
+        -> Result<()>
+        where M: std::io::Read,
+              P: key::KeyParts,
+              R: key::KeyRole,
+    {
+        if self.typ() != SignatureType::Binary &&
+            self.typ() != SignatureType::Text {
+            return Err(Error::UnsupportedSignatureType(self.typ()).into());
+        }
+
+        // Compute the digest.
+        let mut hash = self.hash_algo().context()?;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match msg.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => hash.update(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted =>
+                    continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.verify_hash(signer, hash)
+    }
+
+    /// Verifies the signature using any of the `candidates`.
+    ///
+    /// Like [`Signature::verify_signature`], but tries each of the
+    /// candidate keys in turn, and returns the first one that
+    /// verifies the signature.  This is useful if the signature only
+    /// identifies the issuer by its key ID, which may match several
+    /// keys.
+    ///
+    /// If none of the candidates verifies the signature, the error
+    /// returned for the last candidate is returned.  If there are no
+    /// candidates, [`Error::BadSignature`] is returned.
+    ///
+    /// Note: Due to limited context, this only verifies the
+    /// cryptographic signature, and checks that the key predates the
+    /// signature.  Further constraints on the signature, like
+    /// signature type, creation and expiration time, or signature
+    /// revocations must be checked by the caller.
+    ///
+    /// Likewise, this function does not check whether the keys can
+    /// make valid signatures; it is up to the caller to make sure the
+    /// keys are not revoked, not expired, have a valid
+    /// self-signature, have a subkey binding signature (if
+    /// appropriate), have the signing capability, etc.
+    ///
+    ///   [`Error::BadSignature`]: crate::Error::BadSignature
+    pub fn verify_signature_any<'a, P, R, I>(&self, candidates: I)
+        -> Result<&'a Key<P, R>>
+        where P: 'a + key::KeyParts,
+              R: 'a + key::KeyRole,
+              I: IntoIterator<Item = &'a Key<P, R>>,
+    {
+        self.verify_digest_any_internal(candidates, None)
+    }
+
+    /// Verifies the signature against `digest` using any of the
+    /// `candidates`.
+    ///
+    /// Like [`Signature::verify_digest`], but tries each of the
+    /// candidate keys in turn, and returns the first one that
+    /// verifies the signature.  See
+    /// [`Signature::verify_signature_any`] for details.
+    ///
+    /// Note: Due to limited context, this only verifies the
+    /// cryptographic signature and checks that the key predates the
+    /// signature.  Further constraints on the signature, like
+    /// creation and expiration time, or signature revocations must be
+    /// checked by the caller.
+    ///
+    /// Likewise, this function does not check whether the keys can
+    /// make valid signatures; it is up to the caller to make sure the
+    /// keys are not revoked, not expired, have a valid
+    /// self-signature, have a subkey binding signature (if
+    /// appropriate), have the signing capability, etc.
+    pub fn verify_digest_any<'a, P, R, I, D>(&self, candidates: I, digest: D)
+        -> Result<&'a Key<P, R>>
+        where P: 'a + key::KeyParts,
+              R: 'a + key::KeyRole,
+              I: IntoIterator<Item = &'a Key<P, R>>,
+              D: AsRef<[u8]>,
+    {
+        self.verify_digest_any_internal(candidates,
+                                        Some(digest.as_ref().into()))
+    }
+
+    /// Verifies a signature of a message using any of the
+    /// `candidates`.
+    ///
+    /// Like [`Signature::verify_message`], but tries each of the
+    /// candidate keys in turn, and returns the first one that
+    /// verifies the signature.  The message is only hashed once.
+    /// See [`Signature::verify_signature_any`] for details.
+    ///
+    /// Note: Due to limited context, this only verifies the
+    /// cryptographic signature, checks the signature's type, and
+    /// checks that the key predates the signature.  Further
+    /// constraints on the signature, like creation and expiration
+    /// time, or signature revocations must be checked by the caller.
+    ///
+    /// Likewise, this function does not check whether the keys can
+    /// make valid signatures; it is up to the caller to make sure the
+    /// keys are not revoked, not expired, have a valid
+    /// self-signature, have a subkey binding signature (if
+    /// appropriate), have the signing capability, etc.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sequoia_openpgp as openpgp;
+    /// # use openpgp::cert::prelude::*;
+    /// # use openpgp::packet::signature::SignatureBuilder;
+    /// # use openpgp::types::SignatureType;
+    /// # fn main() -> openpgp::Result<()> {
+    /// # let (cert, _) = CertBuilder::new()
+    /// #     .add_signing_subkey()
+    /// #     .add_signing_subkey()
+    /// #     .generate()?;
+    /// # let key = cert.keys().subkeys().secret().nth(1).unwrap().key();
+    /// # let mut signer = key.clone().into_keypair()?;
+    /// let msg = b"Hello, world!";
+    /// let sig = SignatureBuilder::new(SignatureType::Binary)
+    ///     .sign_message(&mut signer, msg)?;
+    ///
+    /// // Say we only know the issuer's key ID, which may match
+    /// // several keys.  Try all of them.
+    /// let candidates = cert.keys().subkeys().map(|ka| ka.key());
+    /// let signer = sig.verify_message_any(candidates, msg)?;
+    /// assert_eq!(signer.fingerprint(), key.fingerprint());
+    /// # Ok(()) }
+    /// ```
+    pub fn verify_message_any<'a, M, P, R, I>(&self, candidates: I, msg: M)
+        -> Result<&'a Key<P, R>>
+        where M: AsRef<[u8]>,
+              P: 'a + key::KeyParts,
+              R: 'a + key::KeyRole,
+              I: IntoIterator<Item = &'a Key<P, R>>,
+    {
+        if self.typ() != SignatureType::Binary &&
+            self.typ() != SignatureType::Text {
+            return Err(Error::UnsupportedSignatureType(self.typ()).into());
+        }
+
+        // Compute the digest.
+        let mut hash = self.hash_algo().context()?;
+        hash.update(msg.as_ref());
+        self.hash(&mut hash);
+        self.verify_digest_any_internal(candidates,
+                                        Some(hash.into_digest()?.into()))
+    }
+
+    /// Verifies the signature against `computed_digest`, or
+    /// `self.computed_digest` if the former is `None`, using any of
+    /// the `candidates`.
+    fn verify_digest_any_internal<'a, P, R, I>(&self, candidates: I,
+                                               computed_digest: Option<Cow<[u8]>>)
+        -> Result<&'a Key<P, R>>
+        where P: 'a + key::KeyParts,
+              R: 'a + key::KeyRole,
+              I: IntoIterator<Item = &'a Key<P, R>>,
+    {
+        let mut err = None;
+        for key in candidates {
+            match self.verify_digest_internal(
+                key.parts_as_public().role_as_unspecified(),
+                computed_digest.clone())
+            {
+                Ok(()) => return Ok(key),
+                Err(e) => err = Some(e),
+            }
+        }
+
+        Err(err.unwrap_or_else(|| Error::BadSignature(
+            "No candidate keys".into()).into()))
+    }
 }
 
 impl From<Signature3> for Packet {
@@ -3502,7 +4037,7 @@ impl From<Signature4> for super::Signature {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl ArbitraryBounded for super::Signature {
     fn arbitrary_bounded(g: &mut Gen, depth: usize) -> Self {
         if bool::arbitrary(g) {
@@ -3513,39 +4048,15 @@ impl ArbitraryBounded for super::Signature {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl_arbitrary_with_bound!(super::Signature);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl ArbitraryBounded for Signature4 {
     fn arbitrary_bounded(g: &mut Gen, depth: usize) -> Self {
-        use mpi::MPI;
-        use PublicKeyAlgorithm::*;
-
         let fields = SignatureFields::arbitrary_bounded(g, depth);
-        #[allow(deprecated)]
-        let mpis = match fields.pk_algo() {
-            RSAEncryptSign | RSASign => mpi::Signature::RSA  {
-                s: MPI::arbitrary(g),
-            },
-
-            DSA => mpi::Signature::DSA {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
-
-            EdDSA => mpi::Signature::EdDSA  {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
-
-            ECDSA => mpi::Signature::ECDSA  {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
-
-            _ => unreachable!(),
-        };
+        let mpis = mpi::Signature::arbitrary_for(g, fields.pk_algo())
+            .expect("signing algorithm");
 
         Signature4 {
             common: Arbitrary::arbitrary(g),
@@ -3560,40 +4071,16 @@ impl ArbitraryBounded for Signature4 {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl_arbitrary_with_bound!(Signature4);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl ArbitraryBounded for Signature3 {
     fn arbitrary_bounded(g: &mut Gen, _depth: usize) -> Self {
-        use mpi::MPI;
-        use PublicKeyAlgorithm::*;
-
         let pk_algo = PublicKeyAlgorithm::arbitrary_for_signing(g);
 
-        #[allow(deprecated)]
-        let mpis = match pk_algo {
-            RSAEncryptSign | RSASign => mpi::Signature::RSA  {
-                s: MPI::arbitrary(g),
-            },
-
-            DSA => mpi::Signature::DSA {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
-
-            EdDSA => mpi::Signature::EdDSA  {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
-
-            ECDSA => mpi::Signature::ECDSA  {
-                r: MPI::arbitrary(g),
-                s: MPI::arbitrary(g),
-            },
-
-            _ => unreachable!(),
-        };
+        let mpis = mpi::Signature::arbitrary_for(g, pk_algo)
+            .expect("signing algorithm");
 
         Signature3::new(
             SignatureType::arbitrary(g),
@@ -3606,7 +4093,7 @@ impl ArbitraryBounded for Signature3 {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl_arbitrary_with_bound!(Signature3);
 
 #[cfg(test)]
@@ -3899,6 +4386,86 @@ mod test {
         }
     }
 
+    #[test]
+    fn compute_digest() -> Result<()> {
+        let (cert, _) = CertBuilder::new()
+            .add_userid("Alice")
+            .add_user_attribute(
+                UserAttribute::new(&[
+                    crate::packet::user_attribute::Subpacket::Unknown(
+                        7, vec![1; 8].into_boxed_slice()),
+                ])?)
+            .add_signing_subkey()
+            .generate()?;
+        let primary = cert.primary_key().key();
+        let subkey = cert.keys().subkeys().next().unwrap().key();
+        let userid = cert.userids().next().unwrap().userid();
+        let ua = cert.user_attributes().next().unwrap().user_attribute();
+
+        // Signs using the external signer.
+        let sign = |builder: SignatureBuilder, data, key: &Key<_, _>|
+            -> Result<Signature>
+        {
+            let mut external = key.clone().parts_into_secret()?
+                .into_keypair()?;
+            let (builder, digest) = builder.compute_digest(key, data)?;
+            let mpis = external.sign(builder.hash_algo(), &digest)?;
+            builder.attach_signature(digest, mpis)
+        };
+
+        let primary_ = primary.role_as_unspecified();
+        let subkey_ = subkey.role_as_unspecified();
+
+        sign(SignatureBuilder::new(SignatureType::Standalone),
+             SignedData::Standalone, primary_)?
+            .verify_standalone(primary)?;
+        sign(SignatureBuilder::new(SignatureType::Timestamp),
+             SignedData::Timestamp, primary_)?
+            .verify_timestamp(primary)?;
+        sign(SignatureBuilder::new(SignatureType::DirectKey),
+             SignedData::DirectKey(primary), primary_)?
+            .verify_direct_key(primary, primary)?;
+        sign(SignatureBuilder::new(SignatureType::PositiveCertification),
+             SignedData::UserID { primary, userid }, primary_)?
+            .verify_userid_binding(primary, primary, userid)?;
+        sign(SignatureBuilder::new(SignatureType::CertificationRevocation),
+             SignedData::UserAttribute { primary, user_attribute: ua },
+             primary_)?
+            .verify_user_attribute_revocation(primary, primary, ua)?;
+        sign(SignatureBuilder::new(SignatureType::SubkeyRevocation),
+             SignedData::SubkeyBinding { primary, subkey }, primary_)?
+            .verify_subkey_revocation(primary, primary, subkey)?;
+        sign(SignatureBuilder::new(SignatureType::PrimaryKeyBinding),
+             SignedData::PrimaryKeyBinding { primary, subkey }, subkey_)?
+            .verify_primary_key_binding(primary, subkey)?;
+        sign(SignatureBuilder::new(SignatureType::Text),
+             SignedData::Message(b"Hello"), subkey_)?
+            .verify_message(subkey, b"Hello")?;
+
+        let mut hash = HashAlgorithm::SHA384.context()?;
+        hash.update(b"Hello");
+        let sig = sign(SignatureBuilder::new(SignatureType::Binary),
+                       SignedData::Hash(hash), subkey_)?;
+        assert_eq!(sig.hash_algo(), HashAlgorithm::SHA384);
+        sig.verify_message(subkey, b"Hello")?;
+
+        // The signature type is checked.
+        assert!(SignatureBuilder::new(SignatureType::Binary)
+                .compute_digest(primary, SignedData::Standalone).is_err());
+
+        // The digest's size is checked.
+        let (builder, mut digest) =
+            SignatureBuilder::new(SignatureType::Binary)
+            .compute_digest(subkey, SignedData::Message(b"Hello"))?;
+        let mut external = subkey.clone().parts_into_secret()?
+            .into_keypair()?;
+        let mpis = external.sign(builder.hash_algo(), &digest)?;
+        digest.pop();
+        assert!(builder.attach_signature(digest, mpis).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn verify_message() {
         let cert = Cert::from_bytes(crate::tests::key(
@@ -3914,6 +4481,59 @@ mod test {
         };
 
         sig.verify_message(cert.primary_key().key(), msg).unwrap();
+        sig.verify_message_reader(cert.primary_key().key(), msg).unwrap();
+
+        // Verify using a digest computed over the message and the
+        // signature.
+        let mut hash = sig.hash_algo().context().unwrap();
+        hash.update(msg);
+        sig.hash(&mut hash);
+        let digest = hash.into_digest().unwrap();
+        sig.verify_digest(cert.primary_key().key(), &digest).unwrap();
+
+        // A modified message doesn't verify.
+        let mut msg = msg.to_vec();
+        msg.push(b'\n');
+        assert!(sig.verify_message_reader(cert.primary_key().key(),
+                                          &msg[..]).is_err());
+    }
+
+    #[test]
+    fn verify_any() {
+        let cert = Cert::from_bytes(crate::tests::key(
+                "emmelie-dorothea-dina-samantha-awina-ed25519.pgp")).unwrap();
+        let (other, _) = CertBuilder::new().generate().unwrap();
+        let msg = crate::tests::manifesto();
+        let p = Packet::from_bytes(
+            crate::tests::message("a-cypherpunks-manifesto.txt.ed25519.sig"))
+            .unwrap();
+        let sig = if let Packet::Signature(s) = p {
+            s
+        } else {
+            panic!("Expected a Signature, got: {:?}", p);
+        };
+
+        let good = cert.primary_key().key();
+        let bad = other.primary_key().key();
+
+        let k = sig.verify_message_any([bad, good], msg).unwrap();
+        assert_eq!(k.fingerprint(), good.fingerprint());
+        let k = sig.verify_message_any([good, bad], msg).unwrap();
+        assert_eq!(k.fingerprint(), good.fingerprint());
+        assert!(sig.verify_message_any([bad], msg).is_err());
+        assert!(sig.verify_message_any([bad; 0], msg).is_err());
+
+        let mut hash = sig.hash_algo().context().unwrap();
+        hash.update(msg);
+        sig.hash(&mut hash);
+        let digest = hash.into_digest().unwrap();
+        let k = sig.verify_digest_any(vec![bad, good], &digest).unwrap();
+        assert_eq!(k.fingerprint(), good.fingerprint());
+
+        // Now the digest has been stashed in the signature.
+        let k = sig.verify_signature_any([bad, good]).unwrap();
+        assert_eq!(k.fingerprint(), good.fingerprint());
+        assert!(sig.verify_signature_any([bad]).is_err());
     }
 
     #[test]
@@ -4034,6 +4654,59 @@ mod test {
                                   false).unwrap());
     }
 
+    #[test]
+    fn unhashed_placement() -> Result<()> {
+        use std::time::Duration;
+        use crate::packet::signature::subpacket::*;
+
+        let key: Key<key::SecretParts, key::PrimaryRole>
+            = Key4::generate_ecc(true, Curve::Ed25519)?.into();
+        let mut pair = key.into_keypair()?;
+
+        let expiration = Subpacket::new(
+            SubpacketValue::SignatureExpirationTime(
+                Duration::from_secs(3600).try_into()?),
+            false)?;
+
+        // A misplaced, security relevant subpacket is an error.
+        let mut builder = SignatureBuilder::new(SignatureType::Binary);
+        builder.unhashed_area_mut().add(expiration.clone())?;
+        assert_eq!(builder.ignored_subpackets().count(), 1);
+        assert!(builder.clone().sign_message(&mut pair, b"hi").is_err());
+
+        // Moving it to the hashed area fixes that.
+        builder.move_to_hashed_area(SubpacketTag::SignatureExpirationTime)?;
+        assert_eq!(builder.ignored_subpackets().count(), 0);
+        assert_eq!(builder.unhashed_area().iter().count(), 0);
+        let sig = builder.sign_message(&mut pair, b"hi")?;
+        assert_eq!(sig.signature_validity_period(),
+                   Some(Duration::from_secs(3600)));
+
+        // Issuer subpackets may be moved to the unhashed area.
+        let mut builder = SignatureBuilder::new(SignatureType::Binary)
+            .set_issuer(pair.public().keyid())?
+            .set_issuer_fingerprint(pair.public().fingerprint())?;
+        builder.move_to_unhashed_area(SubpacketTag::Issuer)?;
+        builder.move_to_unhashed_area(SubpacketTag::IssuerFingerprint)?;
+        assert_eq!(builder.hashed_area().subpackets(SubpacketTag::Issuer)
+                   .count(), 0);
+        assert_eq!(builder.ignored_subpackets().count(), 0);
+        let sig = builder.sign_message(&mut pair, b"hi")?;
+        assert_eq!(sig.issuers().next(), Some(&pair.public().keyid()));
+        assert_eq!(sig.unhashed_area().subpackets(SubpacketTag::Issuer)
+                   .count(), 1);
+
+        // Non-security relevant subpackets are ignored, but allowed.
+        let mut builder = SignatureBuilder::new(SignatureType::Binary);
+        builder.unhashed_area_mut().add(Subpacket::new(
+            SubpacketValue::PreferredSymmetricAlgorithms(Vec::new()),
+            false)?)?;
+        assert_eq!(builder.ignored_subpackets().count(), 1);
+        builder.sign_message(&mut pair, b"hi")?;
+
+        Ok(())
+    }
+
     #[test]
     fn standalone_signature_roundtrip() {
         let key : key::SecretKey
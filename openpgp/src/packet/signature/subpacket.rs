@@ -62,9 +62,9 @@ use std::cmp;
 use std::sync::atomic;
 use std::time;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use crate::packet::signature::ArbitraryBounded;
 
 use crate::{
@@ -464,9 +464,48 @@ impl SubpacketTag {
     pub fn variants() -> impl Iterator<Item=Self> {
         SUBPACKET_TAG_VARIANTS.iter().cloned()
     }
+
+    /// Returns whether subpackets of this type are considered when
+    /// they occur in the unhashed subpacket area.
+    ///
+    /// Only the `Issuer`, `Issuer Fingerprint`, and `Embedded
+    /// Signature` subpackets are self-authenticating, and can thus
+    /// safely occur in the unhashed area.  Any other subpacket in the
+    /// unhashed area is ignored.
+    pub(crate) fn may_be_unhashed(&self) -> bool {
+        matches!(self,
+                 SubpacketTag::Issuer
+                 | SubpacketTag::IssuerFingerprint
+                 | SubpacketTag::EmbeddedSignature)
+    }
+
+    /// Returns whether placing subpackets of this type in the
+    /// unhashed subpacket area is likely a mistake with security
+    /// implications.
+    ///
+    /// Since these subpackets are ignored in the unhashed area, the
+    /// resulting signature would, for instance, never expire, or
+    /// have no key flags.
+    pub(crate) fn must_be_hashed(&self) -> bool {
+        matches!(self,
+                 SubpacketTag::SignatureCreationTime
+                 | SubpacketTag::SignatureExpirationTime
+                 | SubpacketTag::KeyExpirationTime
+                 | SubpacketTag::ExportableCertification
+                 | SubpacketTag::TrustSignature
+                 | SubpacketTag::RegularExpression
+                 | SubpacketTag::Revocable
+                 | SubpacketTag::RevocationKey
+                 | SubpacketTag::PrimaryUserID
+                 | SubpacketTag::KeyFlags
+                 | SubpacketTag::ReasonForRevocation
+                 | SubpacketTag::SignatureTarget
+                 | SubpacketTag::IntendedRecipient
+                 | SubpacketTag::AttestedCertifications)
+    }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for SubpacketTag {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
@@ -606,21 +645,29 @@ assert_send_and_sync!(SubpacketArea);
 // `SubpacketArea::parsed`.
 const SUBPACKET_NOT_PRESENT: u16 = u16::MAX;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl ArbitraryBounded for SubpacketArea {
     fn arbitrary_bounded(g: &mut Gen, depth: usize) -> Self {
         use crate::arbitrary_helper::gen_arbitrary_from_range;
 
         let mut a = Self::default();
         for _ in 0..gen_arbitrary_from_range(0..32, g) {
-            let _ = a.add(ArbitraryBounded::arbitrary_bounded(g, depth));
+            let p: Subpacket = ArbitraryBounded::arbitrary_bounded(g, depth);
+
+            // Stay within the parser's default limit so that the
+            // signature can be parsed.
+            if a.serialized_len() + p.serialized_len()
+                <= crate::parse::DEFAULT_MAX_SUBPACKET_AREA_SIZE as usize
+            {
+                let _ = a.add(p);
+            }
         }
 
         a
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl_arbitrary_with_bound!(SubpacketArea);
 
 impl Default for SubpacketArea {
@@ -939,6 +986,16 @@ impl SubpacketArea {
         self.iter_mut().filter(move |sp| sp.tag() == target)
     }
 
+    /// Returns all instances of the private subpacket `T`.
+    ///
+    /// The subpackets are parsed using [`PrivateSubpacket::from_bytes`].
+    /// See [`PrivateSubpacket`] for details.
+    pub fn private<T>(&self) -> impl Iterator<Item = Result<T>> + Send + Sync + '_
+        where T: PrivateSubpacket,
+    {
+        self.iter().filter_map(|sp| sp.to_private())
+    }
+
     /// Adds the given subpacket.
     ///
     /// Adds the given subpacket to the subpacket area.  If the
@@ -1245,7 +1302,7 @@ impl fmt::Debug for NotationData {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for NotationData {
     fn arbitrary(g: &mut Gen) -> Self {
         NotationData {
@@ -1291,7 +1348,7 @@ impl NotationData {
 pub struct NotationDataFlags(crate::types::Bitfield);
 assert_send_and_sync!(NotationDataFlags);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for NotationDataFlags {
     fn arbitrary(g: &mut Gen) -> Self {
         NotationDataFlags(vec![u8::arbitrary(g), u8::arbitrary(g),
@@ -1451,6 +1508,90 @@ impl NotationDataFlags {
     }
 }
 
+/// A typed private or experimental subpacket.
+///
+/// [Section 5.2.3.1 of RFC 4880] reserves the subpacket tags 100 to
+/// 110 for private or experimental use.  Sequoia doesn't know how to
+/// interpret these subpackets, and stores them as
+/// [`SubpacketValue::Unknown`].  This trait allows applications to
+/// register a type for a private subpacket tag that knows how to
+/// parse and serialize the subpacket's body.  The type can then be
+/// used with [`Subpacket::from_private`],
+/// [`Subpacket::to_private`], [`SubpacketArea::private`], and
+/// [`SignatureBuilder::set_private_subpacket`].
+///
+/// Because the subpackets are stored in their serialized form,
+/// signatures containing private subpackets round-trip byte for
+/// byte, even if the subpacket's body is not in canonical form.
+///
+///   [Section 5.2.3.1 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.1
+///   [`SignatureBuilder::set_private_subpacket`]: super::SignatureBuilder::set_private_subpacket()
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::packet::prelude::*;
+/// use openpgp::packet::signature::subpacket::PrivateSubpacket;
+/// use openpgp::types::SignatureType;
+/// # use openpgp::cert::prelude::*;
+///
+/// /// The build that produced the signed artifact.
+/// #[derive(Debug, PartialEq)]
+/// struct BuildId(u32);
+///
+/// impl PrivateSubpacket for BuildId {
+///     const TAG: u8 = 101;
+///
+///     fn from_bytes(body: &[u8]) -> openpgp::Result<Self> {
+///         let body = body.try_into()
+///             .map_err(|_| openpgp::Error::MalformedPacket(
+///                 "Bad build ID".into()))?;
+///         Ok(BuildId(u32::from_be_bytes(body)))
+///     }
+///
+///     fn to_vec(&self) -> openpgp::Result<Vec<u8>> {
+///         Ok(self.0.to_be_bytes().to_vec())
+///     }
+/// }
+///
+/// # fn main() -> openpgp::Result<()> {
+/// # let (cert, _) = CertBuilder::new().add_signing_subkey().generate()?;
+/// # let key = cert.keys().subkeys().secret().next().unwrap().key();
+/// # let mut signer = key.clone().into_keypair()?;
+/// let msg = b"Hello, world!";
+/// let sig = SignatureBuilder::new(SignatureType::Binary)
+///     .set_private_subpacket(&BuildId(42), false)?
+///     .sign_message(&mut signer, msg)?;
+///
+/// let ids = sig.hashed_area().private::<BuildId>()
+///     .collect::<openpgp::Result<Vec<_>>>()?;
+/// assert_eq!(ids, vec![BuildId(42)]);
+/// # Ok(()) }
+/// ```
+pub trait PrivateSubpacket: Sized {
+    /// The subpacket's tag.
+    ///
+    /// This must be in the range reserved for private or
+    /// experimental use, i.e. 100 to 110.
+    const TAG: u8;
+
+    /// Parses the subpacket's body.
+    fn from_bytes(body: &[u8]) -> Result<Self>;
+
+    /// Serializes the subpacket's body.
+    fn to_vec(&self) -> Result<Vec<u8>>;
+}
+
+/// Returns the tag of `T`, checking that it is a private tag.
+fn private_tag<T: PrivateSubpacket>() -> Result<SubpacketTag> {
+    match SubpacketTag::from(T::TAG) {
+        tag @ SubpacketTag::Private(_) => Ok(tag),
+        _ => Err(Error::InvalidArgument(
+            format!("Subpacket tag {} is not a private tag", T::TAG)).into()),
+    }
+}
+
 /// Holds an arbitrary, well-structured subpacket.
 ///
 /// The `SubpacketValue` enum holds a [`Subpacket`]'s value.  The
@@ -1699,7 +1840,7 @@ pub enum SubpacketValue {
 }
 assert_send_and_sync!(SubpacketValue);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl ArbitraryBounded for SubpacketValue {
     fn arbitrary_bounded(g: &mut Gen, depth: usize) -> Self {
         use self::SubpacketValue::*;
@@ -1752,7 +1893,7 @@ impl ArbitraryBounded for SubpacketValue {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl_arbitrary_with_bound!(SubpacketValue);
 
 impl SubpacketValue {
@@ -1899,7 +2040,7 @@ impl Hash for Subpacket {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl ArbitraryBounded for Subpacket {
     fn arbitrary_bounded(g: &mut Gen, depth: usize) -> Self {
         use crate::arbitrary_helper::gen_arbitrary_from_range;
@@ -1945,7 +2086,7 @@ impl ArbitraryBounded for Subpacket {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl_arbitrary_with_bound!(Subpacket);
 
 impl fmt::Debug for Subpacket {
@@ -1985,6 +2126,37 @@ impl Subpacket {
         }
     }
 
+    /// Creates a new private subpacket.
+    ///
+    /// See [`PrivateSubpacket`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if `T::TAG` is not a private
+    /// subpacket tag.
+    pub fn from_private<T>(value: &T, critical: bool) -> Result<Subpacket>
+        where T: PrivateSubpacket,
+    {
+        Subpacket::new(SubpacketValue::Unknown {
+            tag: private_tag::<T>()?,
+            body: value.to_vec()?,
+        }, critical)
+    }
+
+    /// Parses this subpacket as private subpacket `T`.
+    ///
+    /// Returns `None` if the subpacket's tag is not `T::TAG`.  See
+    /// [`PrivateSubpacket`] for details.
+    pub fn to_private<T>(&self) -> Option<Result<T>>
+        where T: PrivateSubpacket,
+    {
+        match &self.value {
+            SubpacketValue::Unknown { tag: SubpacketTag::Private(t), body }
+                if *t == T::TAG => Some(T::from_bytes(body)),
+            _ => None,
+        }
+    }
+
     /// Returns whether the critical bit is set.
     pub fn critical(&self) -> bool {
         self.critical
@@ -2146,7 +2318,7 @@ pub struct SubpacketAreas {
 }
 assert_send_and_sync!(SubpacketAreas);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl ArbitraryBounded for SubpacketAreas {
     fn arbitrary_bounded(g: &mut Gen, depth: usize) -> Self {
         SubpacketAreas::new(ArbitraryBounded::arbitrary_bounded(g, depth),
@@ -2154,7 +2326,7 @@ impl ArbitraryBounded for SubpacketAreas {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl_arbitrary_with_bound!(SubpacketAreas);
 
 impl SubpacketAreas {
@@ -2192,6 +2364,98 @@ impl SubpacketAreas {
         &mut self.unhashed_area
     }
 
+    /// Returns the subpackets in the unhashed area that are ignored.
+    ///
+    /// Only the `Issuer`, `Issuer Fingerprint`, and `Embedded
+    /// Signature` subpackets are considered when they occur in the
+    /// unhashed area, because they are self-authenticating.  All
+    /// other subpackets in the unhashed area are not protected by
+    /// the signature, and are ignored by accessors like
+    /// [`SubpacketAreas::subpacket`] and
+    /// [`SubpacketAreas::signature_expiration_time`].  This function
+    /// returns these subpackets, so that misplaced subpackets can be
+    /// detected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// # use sequoia_openpgp as openpgp;
+    /// # use openpgp::packet::prelude::*;
+    /// use openpgp::packet::signature::subpacket::{
+    ///     Subpacket, SubpacketTag, SubpacketValue,
+    /// };
+    /// use openpgp::types::SignatureType;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let mut builder = SignatureBuilder::new(SignatureType::Binary);
+    /// builder.unhashed_area_mut().add(Subpacket::new(
+    ///     SubpacketValue::SignatureExpirationTime(
+    ///         Duration::from_secs(3600).try_into()?),
+    ///     false)?)?;
+    ///
+    /// // The subpacket is ignored.
+    /// assert_eq!(builder.signature_validity_period(), None);
+    /// assert_eq!(builder.ignored_subpackets().next().map(|sp| sp.tag()),
+    ///            Some(SubpacketTag::SignatureExpirationTime));
+    ///
+    /// // Move it to the hashed area.
+    /// builder.move_to_hashed_area(SubpacketTag::SignatureExpirationTime)?;
+    /// assert_eq!(builder.signature_validity_period(),
+    ///            Some(Duration::from_secs(3600)));
+    /// assert_eq!(builder.ignored_subpackets().count(), 0);
+    /// # Ok(()) }
+    /// ```
+    pub fn ignored_subpackets(&self)
+        -> impl Iterator<Item = &Subpacket> + Send + Sync
+    {
+        self.unhashed_area.iter().filter(|sp| ! sp.tag().may_be_unhashed())
+    }
+
+    /// Moves all subpackets with the given tag from the unhashed area
+    /// to the hashed area.
+    ///
+    /// If the subpackets don't fit into the hashed area, an error is
+    /// returned, and neither area is modified.
+    ///
+    /// Note: if you modify the hashed area of a [`Signature4`], this
+    /// will invalidate the signature.  See
+    /// [`SubpacketAreas::hashed_area_mut`].
+    ///
+    /// See [`SubpacketAreas::ignored_subpackets`] for an example.
+    pub fn move_to_hashed_area(&mut self, tag: SubpacketTag) -> Result<()> {
+        Self::move_subpackets(&mut self.unhashed_area, &mut self.hashed_area,
+                              tag)
+    }
+
+    /// Moves all subpackets with the given tag from the hashed area
+    /// to the unhashed area.
+    ///
+    /// If the subpackets don't fit into the unhashed area, an error
+    /// is returned, and neither area is modified.
+    ///
+    /// Note: only the `Issuer`, `Issuer Fingerprint`, and `Embedded
+    /// Signature` subpackets are considered when they occur in the
+    /// unhashed area.  See [`SubpacketAreas::ignored_subpackets`].
+    pub fn move_to_unhashed_area(&mut self, tag: SubpacketTag) -> Result<()> {
+        Self::move_subpackets(&mut self.hashed_area, &mut self.unhashed_area,
+                              tag)
+    }
+
+    /// Moves all subpackets with the given tag from `from` to `to`.
+    fn move_subpackets(from: &mut SubpacketArea, to: &mut SubpacketArea,
+                       tag: SubpacketTag)
+                       -> Result<()>
+    {
+        let mut new_to = to.clone();
+        for sp in from.subpackets(tag) {
+            new_to.add(sp.clone())?;
+        }
+        from.remove_all(tag);
+        *to = new_to;
+        Ok(())
+    }
+
     /// Sorts the subpacket areas.
     ///
     /// See [`SubpacketArea::sort()`].
@@ -2225,9 +2489,7 @@ impl SubpacketAreas {
         // There are a couple of subpackets that we are willing to
         // take from the unhashed area.  The others we ignore
         // completely.
-        if !(tag == SubpacketTag::Issuer
-             || tag == SubpacketTag::IssuerFingerprint
-             || tag == SubpacketTag::EmbeddedSignature) {
+        if ! tag.may_be_unhashed() {
             return None;
         }
 
@@ -2259,9 +2521,7 @@ impl SubpacketAreas {
         // There are a couple of subpackets that we are willing to
         // take from the unhashed area.  The others we ignore
         // completely.
-        if !(tag == SubpacketTag::Issuer
-             || tag == SubpacketTag::IssuerFingerprint
-             || tag == SubpacketTag::EmbeddedSignature) {
+        if ! tag.may_be_unhashed() {
             return None;
         }
 
@@ -2304,12 +2564,7 @@ impl SubpacketAreas {
         self.hashed_area().subpackets(tag).chain(
             self.unhashed_area()
                 .iter()
-                .filter(move |sp| {
-                    (tag == SubpacketTag::Issuer
-                     || tag == SubpacketTag::IssuerFingerprint
-                     || tag == SubpacketTag::EmbeddedSignature)
-                        && sp.tag() == tag
-            }))
+                .filter(move |sp| tag.may_be_unhashed() && sp.tag() == tag))
     }
 
     pub(crate) fn subpackets_mut(&mut self, tag: SubpacketTag)
@@ -2318,12 +2573,7 @@ impl SubpacketAreas {
         self.hashed_area.subpackets_mut(tag).chain(
             self.unhashed_area
                 .iter_mut()
-                .filter(move |sp| {
-                    (tag == SubpacketTag::Issuer
-                     || tag == SubpacketTag::IssuerFingerprint
-                     || tag == SubpacketTag::EmbeddedSignature)
-                        && sp.tag() == tag
-            }))
+                .filter(move |sp| tag.may_be_unhashed() && sp.tag() == tag))
     }
 
     /// Returns the value of the Signature Creation Time subpacket.
@@ -5281,7 +5531,7 @@ impl signature::SignatureBuilder {
               E: Into<Option<time::SystemTime>>,
     {
         if let Some(e) = expiration.into()
-            .map(crate::types::normalize_systemtime)
+            .map(Timestamp::normalize).transpose()?
         {
             let ct = key.creation_time();
             let vp = match e.duration_since(ct) {
@@ -5810,6 +6060,47 @@ impl signature::SignatureBuilder {
         Ok(self)
     }
 
+    /// Sets a private subpacket.
+    ///
+    /// Replaces any private subpacket with the tag `T::TAG` in the
+    /// hashed subpacket area with a new subpacket containing the
+    /// specified value.  See [`PrivateSubpacket`] for details and an
+    /// example.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if `T::TAG` is not a private
+    /// subpacket tag.
+    pub fn set_private_subpacket<T>(mut self, value: &T, critical: bool)
+                                    -> Result<Self>
+        where T: PrivateSubpacket,
+    {
+        self.hashed_area.replace(Subpacket::from_private(value, critical)?)?;
+        Ok(self)
+    }
+
+    /// Adds a private subpacket.
+    ///
+    /// Adds a private subpacket to the hashed subpacket area.  Unlike
+    /// the [`SignatureBuilder::set_private_subpacket`] method, this
+    /// function does not first remove any existing subpacket with
+    /// the tag `T::TAG` from the hashed subpacket area.  See
+    /// [`PrivateSubpacket`] for details.
+    ///
+    /// [`SignatureBuilder::set_private_subpacket`]: super::SignatureBuilder::set_private_subpacket()
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if `T::TAG` is not a private
+    /// subpacket tag.
+    pub fn add_private_subpacket<T>(mut self, value: &T, critical: bool)
+                                    -> Result<Self>
+        where T: PrivateSubpacket,
+    {
+        self.hashed_area.add(Subpacket::from_private(value, critical)?)?;
+        Ok(self)
+    }
+
     /// Sets the Preferred Hash Algorithms subpacket.
     ///
     /// Replaces any [Preferred Hash Algorithms subpacket] in the
@@ -8112,3 +8403,90 @@ fn issuer_default() -> Result<()> {
     assert_eq!(sig_.issuers().count(), 0);
     Ok(())
 }
+
+#[test]
+fn private_subpackets() -> Result<()> {
+    use crate::Packet;
+    use crate::parse::Parse;
+    use crate::serialize::MarshalInto;
+    use crate::types::{Curve, SignatureType};
+
+    #[derive(Debug, PartialEq)]
+    struct Counter(u16);
+
+    impl PrivateSubpacket for Counter {
+        const TAG: u8 = 105;
+
+        fn from_bytes(body: &[u8]) -> Result<Self> {
+            let body = body.try_into().map_err(
+                |_| Error::MalformedPacket("Bad counter".into()))?;
+            Ok(Counter(u16::from_be_bytes(body)))
+        }
+
+        fn to_vec(&self) -> Result<Vec<u8>> {
+            Ok(self.0.to_be_bytes().to_vec())
+        }
+    }
+
+    #[derive(Debug)]
+    struct NotPrivate;
+
+    impl PrivateSubpacket for NotPrivate {
+        const TAG: u8 = 20;
+
+        fn from_bytes(_: &[u8]) -> Result<Self> {
+            Ok(NotPrivate)
+        }
+
+        fn to_vec(&self) -> Result<Vec<u8>> {
+            Ok(vec![])
+        }
+    }
+
+    let key: crate::packet::key::SecretKey =
+        crate::packet::key::Key4::generate_ecc(true, Curve::Ed25519)?.into();
+    let mut keypair = key.into_keypair()?;
+    let msg = b"Hello, world!";
+
+    assert!(Subpacket::from_private(&NotPrivate, false).is_err());
+    assert!(signature::SignatureBuilder::new(SignatureType::Binary)
+            .set_private_subpacket(&NotPrivate, false).is_err());
+
+    let sig = signature::SignatureBuilder::new(SignatureType::Binary)
+        .set_private_subpacket(&Counter(1), false)?
+        .set_private_subpacket(&Counter(2), true)?
+        .add_private_subpacket(&Counter(3), false)?
+        .sign_message(&mut keypair, msg)?;
+
+    let counters = sig.hashed_area().private::<Counter>()
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(counters, vec![Counter(2), Counter(3)]);
+    assert_eq!(sig.unhashed_area().private::<Counter>().count(), 0);
+
+    // Round-trip the signature.
+    let bytes = Packet::from(sig).to_vec()?;
+    let sig = if let Packet::Signature(s) = Packet::from_bytes(&bytes)? {
+        s
+    } else {
+        panic!("expected a signature");
+    };
+    sig.verify_message(keypair.public(), msg)?;
+    let sp = sig.hashed_area().subpackets(SubpacketTag::Private(105))
+        .next().unwrap();
+    assert!(sp.critical());
+    assert_eq!(sp.to_private::<Counter>().transpose()?, Some(Counter(2)));
+    let counters = sig.hashed_area().private::<Counter>()
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(counters, vec![Counter(2), Counter(3)]);
+
+    // Malformed bodies are reported.
+    let mut sig = signature::SignatureBuilder::new(SignatureType::Binary);
+    sig.unhashed_area_mut().add(Subpacket::new(SubpacketValue::Unknown {
+        tag: SubpacketTag::Private(105),
+        body: vec![1, 2, 3],
+    }, false)?)?;
+    let mut counters = sig.unhashed_area().private::<Counter>();
+    assert!(counters.next().unwrap().is_err());
+    assert!(counters.next().is_none());
+    Ok(())
+}
@@ -8,7 +8,7 @@
 
 use std::ops::{Deref, DerefMut};
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::Result;
@@ -36,7 +36,7 @@ impl SKESK {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for SKESK {
     fn arbitrary(g: &mut Gen) -> Self {
         if bool::arbitrary(g) {
@@ -289,7 +289,7 @@ impl From<SKESK4> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for SKESK4 {
     fn arbitrary(g: &mut Gen) -> Self {
         SKESK4::new(SymmetricAlgorithm::arbitrary(g),
@@ -458,7 +458,7 @@ impl SKESK5 {
         // Derive key and make a cipher.
         let key = s2k.derive_key(password, esk_algo.key_size()?)?;
         let mut iv = vec![0u8; esk_aead.nonce_size()?];
-        crypto::random(&mut iv);
+        crypto::try_random(&mut iv)?;
         let aad = [0xc3, 5, esk_algo.into(), esk_aead.into()];
         let mut ctx = esk_aead.context(esk_algo, &key, &aad, &iv,
                                        CipherOp::Encrypt)?;
@@ -569,7 +569,7 @@ impl From<SKESK5> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for SKESK5 {
     fn arbitrary(g: &mut Gen) -> Self {
         let algo = AEADAlgorithm::const_default();
@@ -2,7 +2,7 @@ use std::fmt;
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::packet::Packet;
@@ -238,7 +238,7 @@ const PACKET_TAG_VARIANTS: [Tag; 18] = [
     Tag::AED,
 ];
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Tag {
     fn arbitrary(g: &mut Gen) -> Self {
         loop {
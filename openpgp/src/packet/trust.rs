@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::packet;
@@ -62,7 +62,7 @@ impl From<Trust> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Trust {
     fn arbitrary(g: &mut Gen) -> Self {
         Vec::<u8>::arbitrary(g).into()
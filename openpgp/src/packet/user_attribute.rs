@@ -6,7 +6,7 @@
 
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use buffered_reader::BufferedReader;
@@ -132,7 +132,7 @@ impl From<UserAttribute> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for UserAttribute {
     fn arbitrary(g: &mut Gen) -> Self {
         use crate::arbitrary_helper::gen_arbitrary_from_range;
@@ -225,7 +225,7 @@ pub enum Subpacket {
 }
 assert_send_and_sync!(Subpacket);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Subpacket {
     fn arbitrary(g: &mut Gen) -> Self {
         use crate::arbitrary_helper::gen_arbitrary_from_range;
@@ -261,7 +261,7 @@ pub enum Image {
 }
 assert_send_and_sync!(Image);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Image {
 
     fn arbitrary(g: &mut Gen) -> Self {
@@ -4,7 +4,7 @@ use std::str;
 use std::hash::{Hash, Hasher};
 use std::cmp::Ordering;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use once_cell::sync::OnceCell;
@@ -1020,7 +1020,7 @@ impl From<UserID> for Packet {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for UserID {
     fn arbitrary(g: &mut Gen) -> Self {
         Vec::<u8>::arbitrary(g).into()
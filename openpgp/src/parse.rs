@@ -122,6 +122,40 @@
 //! This automatism can be disabled and fine-tuned using
 //! [`PacketParserBuilder::dearmor`].
 //!
+//! All [`Parse`] implementations, e.g. [`Cert::from_bytes`],
+//! [`PacketPile::from_reader`], and [`RawCertParser::from_file`],
+//! use the same detection as [`Dearmor::Auto`]: if the input starts
+//! with a valid packet header, it is parsed as binary OpenPGP data,
+//! otherwise it is dearmored.  When dearmoring, a leading UTF-8 byte
+//! order mark and any garbage before the armor header line are
+//! skipped.
+//!
+//! To strictly require either binary or ASCII armored input, use a
+//! [`PacketParserBuilder`] with [`Dearmor::Disabled`] or
+//! [`Dearmor::Enabled`], and convert the resulting
+//! [`PacketParserResult`]:
+//!
+//! ```
+//! # fn main() -> sequoia_openpgp::Result<()> {
+//! use sequoia_openpgp as openpgp;
+//! use openpgp::Cert;
+//! use openpgp::parse::{Parse, PacketParserBuilder, Dearmor};
+//!
+//! let data: &[u8] = // ...
+//! # include_bytes!("../tests/data/keys/testy-new.pgp");
+//!
+//! // Only accept binary data.
+//! let ppr = PacketParserBuilder::from_bytes(data)?
+//!     .dearmor(Dearmor::Disabled)
+//!     .build()?;
+//! let cert = Cert::try_from(ppr)?;
+//! # assert_eq!(cert.userids().count(), 1);
+//! # Ok(()) }
+//! ```
+//!
+//!   [`Cert::from_bytes`]: crate::Cert::from_bytes()
+//!   [`PacketPile::from_reader`]: crate::PacketPile::from_reader()
+//!   [`RawCertParser::from_file`]: crate::cert::raw::RawCertParser::from_file()
 //!   [Section 6 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-6
 //!   [`PacketParserBuilder::dearmor`]: PacketParserBuilder::dearmor()
 //!
@@ -266,7 +300,8 @@ pub use self::packet_parser_builder::{Dearmor, PacketParserBuilder};
 use packet_parser_builder::ARMOR_READER_LEVEL;
 
 pub mod map;
-mod mpis;
+pub mod diagnostics;
+pub(crate) mod mpis;
 pub mod stream;
 
 // Whether to trace execution by default (on stderr).
@@ -413,6 +448,77 @@ pub const DEFAULT_MAX_RECURSION_DEPTH : u8 = 16;
 ///   [`PacketParserBuilder::max_packet_size`]: PacketParserBuilder::max_packet_size()
 pub const DEFAULT_MAX_PACKET_SIZE: u32 = 1 << 20; // 1 MiB
 
+/// The default maximum size of a container's content buffered for
+/// hashing.
+///
+/// The default is `16 MiB`.
+///
+/// When [`PacketPileParser::hash_containers`] is used, and the parser
+/// descends into a container, the container's content is buffered in
+/// memory to compute the digest.  If the content exceeds this limit,
+/// parsing fails with `Error::PacketTooLarge`.
+///
+/// To change the limit, use
+/// [`PacketPileParser::max_hashed_container_size`].
+///
+///   [`PacketPileParser::hash_containers`]: PacketPileParser::hash_containers()
+///   [`PacketPileParser::max_hashed_container_size`]: PacketPileParser::max_hashed_container_size()
+pub const DEFAULT_MAX_HASHED_CONTAINER_SIZE: u32 = 16 << 20; // 16 MiB
+
+/// The default maximum size of a User ID packet.
+///
+/// The default is `4 KiB`.
+///
+/// User ID packets that exceed this limit will be returned as
+/// `Packet::Unknown`, with the error set to `Error::PacketTooLarge`.
+///
+/// To change the maximum User ID size, use
+/// [`PacketParserBuilder::max_user_id_size`].
+///
+///   [`PacketParserBuilder::max_user_id_size`]: PacketParserBuilder::max_user_id_size()
+pub const DEFAULT_MAX_USER_ID_SIZE: u32 = 1 << 12; // 4 KiB
+
+/// The default maximum size of a User Attribute packet.
+///
+/// The default is `256 KiB`.
+///
+/// User Attribute packets that exceed this limit will be returned as
+/// `Packet::Unknown`, with the error set to `Error::PacketTooLarge`.
+///
+/// To change the maximum User Attribute size, use
+/// [`PacketParserBuilder::max_user_attribute_size`].
+///
+///   [`PacketParserBuilder::max_user_attribute_size`]: PacketParserBuilder::max_user_attribute_size()
+pub const DEFAULT_MAX_USER_ATTRIBUTE_SIZE: u32 = 1 << 18; // 256 KiB
+
+/// The default maximum size of a signature's subpacket area.
+///
+/// The default is `32 KiB`.
+///
+/// Signatures with a hashed or unhashed subpacket area that exceeds
+/// this limit will be returned as `Packet::Unknown`, with the error
+/// set to `Error::SubpacketAreaTooLarge`.
+///
+/// To change the maximum subpacket area size, use
+/// [`PacketParserBuilder::max_subpacket_area_size`].
+///
+///   [`PacketParserBuilder::max_subpacket_area_size`]: PacketParserBuilder::max_subpacket_area_size()
+pub const DEFAULT_MAX_SUBPACKET_AREA_SIZE: u32 = 1 << 15; // 32 KiB
+
+/// The default maximum number of subpackets in a subpacket area.
+///
+/// The default is `512`.
+///
+/// Signatures with a hashed or unhashed subpacket area that contains
+/// more subpackets will be returned as `Packet::Unknown`, with the
+/// error set to `Error::TooManySubpackets`.
+///
+/// To change the maximum number of subpackets, use
+/// [`PacketParserBuilder::max_subpackets`].
+///
+///   [`PacketParserBuilder::max_subpackets`]: PacketParserBuilder::max_subpackets()
+pub const DEFAULT_MAX_SUBPACKETS: u32 = 512;
+
 // Used to parse an OpenPGP packet's header (note: in this case, the
 // header means a Packet's fixed data, not the OpenPGP framing
 // information, such as the CTB, and length information).
@@ -505,7 +611,8 @@ impl<'a> PacketHeaderParser<'a> {
             ..Default::default()
         };
         let map = if state.settings.map {
-            Some(map::Map::new(header_bytes.clone()))
+            Some(map::Map::new(header_bytes.clone(),
+                               *header.length()))
         } else {
             None
         };
@@ -558,7 +665,12 @@ impl<'a> PacketHeaderParser<'a> {
             if body.len() > total_out {
                 self.field("body", body.len() - total_out);
             }
-            self.map.as_mut().unwrap().finalize(body);
+            let partial_body_headers = self.reader.get_ref()
+                .and_then(|r| r.cookie_ref().partial_body_headers.clone())
+                .unwrap_or_default();
+            self.map.as_mut().unwrap().finalize(
+                body, self.state.settings.buffer_unread_content,
+                partial_body_headers);
         }
 
         // This is a buffered_reader::Dup, so this always has an
@@ -769,6 +881,26 @@ pub struct Cookie {
     /// doing a transformation of a message using the cleartext
     /// signature framework into a signed message.
     csf_transformation: bool,
+
+    /// Indicates that this `BufferedReader` reads the body of an
+    /// encryption container.
+    ///
+    /// If such a reader ends prematurely, the message has been
+    /// truncated.  buffered_reader_stack_pop uses this to report the
+    /// truncation instead of a generic error for whatever packet
+    /// inside the container was cut short.
+    encrypted_body: bool,
+
+    /// The partial body length headers read so far, if they are
+    /// recorded.
+    ///
+    /// If this is `Some`, a `BufferedReaderPartialBodyFilter`
+    /// records the headers of the chunks following the first one.
+    /// Each entry is the offset into the body at which the header
+    /// was encountered, the length it encodes, and the header as
+    /// read.  This is used by the map to reproduce the packet's
+    /// encoding.
+    partial_body_headers: Option<Vec<(usize, BodyLength, Vec<u8>)>>,
 }
 assert_send_and_sync!(Cookie);
 
@@ -817,6 +949,8 @@ impl Default for Cookie {
             hash_stash: None,
             fake_eof: false,
             csf_transformation: false,
+            encrypted_body: false,
+            partial_body_headers: None,
         }
     }
 }
@@ -833,6 +967,8 @@ impl Cookie {
             hash_stash: None,
             fake_eof: false,
             csf_transformation: false,
+            encrypted_body: false,
+            partial_body_headers: None,
         }
     }
 
@@ -923,6 +1059,23 @@ impl Cookie {
         self.csf_transformation = true;
     }
 
+    /// Checks whether the body of an encryption container on the
+    /// reader stack ended prematurely.
+    fn encrypted_body_truncated(reader: &mut dyn BufferedReader<Cookie>)
+                                -> bool {
+        let mut reader: Option<&mut dyn BufferedReader<Cookie>>
+            = Some(reader);
+        while let Some(r) = reader {
+            if r.cookie_ref().encrypted_body
+                && ! r.consummated() && r.data_hard(1).is_err()
+            {
+                return true;
+            }
+            reader = r.get_mut();
+        }
+        false
+    }
+
     /// Checks if we are processing a signed message using the
     /// Cleartext Signature Framework.
     fn processing_csf_message(reader: &dyn BufferedReader<Cookie>)
@@ -963,9 +1116,27 @@ fn buffered_reader_stack_pop<'a>(
                reader.cookie_ref().level,
                reader);
 
-            if reader.eof() && ! reader.consummated() {
-                return Err(Error::MalformedPacket("Truncated packet".into())
-                           .into());
+            if ! reader.consummated() {
+                if let Err(err) = reader.data_hard(1) {
+                    // We hit EOF prematurely.  If that is because
+                    // the encryption container was truncated,
+                    // report that.
+                    if let Some(e @ Error::TruncatedMessage(_)) = err.get_ref()
+                        .and_then(|e| e.downcast_ref::<Error>())
+                    {
+                        return Err(e.clone().into());
+                    }
+                    if Cookie::encrypted_body_truncated(reader.as_mut()) {
+                        // For containers using an MDC, none of the
+                        // plaintext has been authenticated.  AEAD
+                        // decryptors report the truncation
+                        // themselves.
+                        return Err(Error::TruncatedMessage(0).into());
+                    }
+
+                    return Err(Error::MalformedPacket("Truncated packet".into())
+                               .into());
+                }
             }
             reader.drop_eof()?;
             reader = reader.into_inner().unwrap();
@@ -1008,6 +1179,23 @@ struct PacketParserSettings {
     // data packet, or an AEAD encrypted data packet.
     max_packet_size: u32,
 
+    // The maximum size of User ID and User Attribute packets.
+    //
+    // Packets that exceed these limits will be returned as
+    // `Packet::Unknown`, with the error set to
+    // `Error::PacketTooLarge`.
+    max_user_id_size: u32,
+    max_user_attribute_size: u32,
+
+    // The maximum size of, and the maximum number of subpackets in a
+    // signature's subpacket areas.
+    //
+    // Signatures that exceed these limits will be returned as
+    // `Packet::Unknown`, with the error set to
+    // `Error::SubpacketAreaTooLarge` or `Error::TooManySubpackets`.
+    max_subpacket_area_size: u32,
+    max_subpackets: u32,
+
     // Whether a packet's contents should be buffered or dropped when
     // the next packet is retrieved.
     buffer_unread_content: bool,
@@ -1018,6 +1206,9 @@ struct PacketParserSettings {
     // Whether to implicitly start hashing upon parsing OnePassSig
     // packets.
     automatic_hashing: bool,
+
+    // Whether to collect diagnostics.
+    collect_diagnostics: bool,
 }
 
 // The default `PacketParser` settings.
@@ -1026,9 +1217,14 @@ impl Default for PacketParserSettings {
         PacketParserSettings {
             max_recursion_depth: DEFAULT_MAX_RECURSION_DEPTH,
             max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            max_user_id_size: DEFAULT_MAX_USER_ID_SIZE,
+            max_user_attribute_size: DEFAULT_MAX_USER_ATTRIBUTE_SIZE,
+            max_subpacket_area_size: DEFAULT_MAX_SUBPACKET_AREA_SIZE,
+            max_subpackets: DEFAULT_MAX_SUBPACKETS,
             buffer_unread_content: false,
             map: false,
             automatic_hashing: true,
+            collect_diagnostics: false,
         }
     }
 }
@@ -1091,6 +1287,11 @@ impl S2K {
                     Some(
                         php.parse_bytes("parameters", l as usize - 1 /* Tag */)?
                             .into())
+                } else if let Some(l) = Self::gnupg_extension_len(php, s2k)? {
+                    // We know how large GnuPG's extensions are, so
+                    // we can cleanly separate them from the rest of
+                    // the data.
+                    Some(php.parse_bytes("parameters", l)?.into())
                 } else {
                     None
                 },
@@ -1110,6 +1311,23 @@ impl S2K {
         Ok(ret)
     }
 
+    /// Returns the length of the parameters if `php` contains one
+    /// of GnuPG's S2K extensions.
+    fn gnupg_extension_len(php: &mut PacketHeaderParser<'_>, tag: u8)
+                           -> Result<Option<usize>>
+    {
+        use crate::packet::key::Unavailable;
+
+        if tag != 101 {
+            return Ok(None);
+        }
+
+        // The longest extension has a 16 byte serial number.
+        let data = php.reader.data(6 + 16)?;
+        Ok(Unavailable::gnupg_s2k_len(data)
+           .filter(|&l| l <= data.len()))
+    }
+
     fn read_salt(php: &mut PacketHeaderParser<'_>) -> Result<[u8; 8]> {
         let mut b = [0u8; 8];
         b.copy_from_slice(&php.parse_bytes("s2k_salt", 8)?);
@@ -1150,8 +1368,10 @@ impl BodyLength {
     /// 4.2.2 of RFC 4880].
     ///
     ///   [Section 4.2.2 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-4.2.2
-    pub(crate) fn parse_new_format<T: BufferedReader<C>, C: fmt::Debug + Send + Sync> (bio: &mut T)
+    pub(crate) fn parse_new_format<T, C> (bio: &mut T)
         -> io::Result<BodyLength>
+        where T: BufferedReader<C> + ?Sized,
+              C: fmt::Debug + Send + Sync,
     {
         let octet1 : u8 = bio.data_consume_hard(1)?[0];
         match octet1 {
@@ -1445,11 +1665,13 @@ impl Signature4 {
         let hash_algo: HashAlgorithm =
             php_try!(php.parse_u8("hash_algo")).into();
         let hashed_area_len = php_try!(php.parse_be_u16("hashed_area_len"));
+        php_try!(SubpacketArea::check_size(&php, hashed_area_len));
         let hashed_area
             = php_try!(SubpacketArea::parse(&mut php,
                                             hashed_area_len as usize,
                                             hash_algo));
         let unhashed_area_len = php_try!(php.parse_be_u16("unhashed_area_len"));
+        php_try!(SubpacketArea::check_size(&php, unhashed_area_len));
         let unhashed_area
             = php_try!(SubpacketArea::parse(&mut php,
                                             unhashed_area_len as usize,
@@ -1588,6 +1810,17 @@ fn signature_parser_test () {
 }
 
 impl SubpacketArea {
+    // Checks that a subpacket area of `len` bytes is within the
+    // configured limit.
+    fn check_size(php: &PacketHeaderParser, len: u16) -> Result<()> {
+        let max_size = php.state.settings.max_subpacket_area_size;
+        if u32::from(len) > max_size {
+            return Err(Error::SubpacketAreaTooLarge(len.into(), max_size)
+                       .into());
+        }
+        Ok(())
+    }
+
     // Parses a subpacket area.
     fn parse(php: &mut PacketHeaderParser,
              mut limit: usize,
@@ -1597,8 +1830,13 @@ impl SubpacketArea {
         let indent = php.recursion_depth();
         tracer!(TRACE, "SubpacketArea::parse", indent);
 
+        let max_subpackets = php.state.settings.max_subpackets;
         let mut packets = Vec::new();
         while limit > 0 {
+            if packets.len() >= max_subpackets as usize {
+                return Err(Error::TooManySubpackets(max_subpackets).into());
+            }
+
             let r = Subpacket::parse(php, limit, hash_algo);
             t!("Subpacket::parse(_, {}, {:?}) => {:?}",
                limit, hash_algo, r);
@@ -2371,7 +2609,9 @@ impl Key4<key::UnspecifiedParts, key::UnspecifiedRole>
                             #[allow(deprecated)] S2K::Implicit
                         },
                     };
-                    let s2k_supported = s2k.is_supported();
+                    let s2k_supported = s2k.is_supported()
+                        || crate::packet::key::Unavailable::from_s2k(&s2k)
+                            .is_some();
                     let cipher =
                         php_try!(php.parse_bytes_eof("encrypted_mpis"))
                         .into_boxed_slice();
@@ -3309,6 +3549,9 @@ struct PacketParserState {
 
     // Whether PacketParser::parse encountered an unrecoverable error.
     pending_error: Option<anyhow::Error>,
+
+    // The diagnostics collected so far, if enabled.
+    diagnostics: Vec<diagnostics::Diagnostic>,
 }
 
 impl PacketParserState {
@@ -3320,6 +3563,7 @@ impl PacketParserState {
             cert_validator: Default::default(),
             first_packet: true,
             pending_error: None,
+            diagnostics: Vec::new(),
         }
     }
 }
@@ -3685,6 +3929,18 @@ impl<'a> PacketParserEOF<'a> {
                 .into_boxed())
     }
 
+    /// Returns the diagnostics collected while parsing the stream.
+    ///
+    /// Returns an empty slice, unless diagnostics are enabled using
+    /// [`PacketParserBuilder::collect_diagnostics`].
+    ///
+    ///   [`PacketParserBuilder::collect_diagnostics`]: PacketParserBuilder::collect_diagnostics()
+    ///
+    /// See the [`diagnostics`] module for an example.
+    pub fn diagnostics(&self) -> &[diagnostics::Diagnostic] {
+        &self.state.diagnostics
+    }
+
     /// Returns whether the stream is an OpenPGP Message.
     ///
     /// A [`Message`] has a very specific structure.  Returns `true`
@@ -4068,6 +4324,20 @@ impl <'a> PacketParser<'a> {
             Box::new(buffered_reader::EOF::with_cookie(Default::default())))
     }
 
+    /// Takes the reader stack of an encryption container.
+    ///
+    /// Like [`PacketParser::take_reader`], but also marks the reader
+    /// of the container's body so that truncation can be detected.
+    /// If the body uses an indeterminate length encoding, there is
+    /// no such reader, and the stack is returned as is.
+    fn take_encrypted_body(&mut self) -> Box<dyn BufferedReader<Cookie> + 'a> {
+        let mut reader = self.take_reader();
+        if reader.cookie_ref().level == Some(self.recursion_depth()) {
+            reader.cookie_mut().encrypted_body = true;
+        }
+        reader
+    }
+
     /// Replaces the reader stack.
     ///
     /// This function may only be called when the `PacketParser` is in
@@ -4475,6 +4745,7 @@ impl <'a> PacketParser<'a> {
 
         let indent = path.len() as isize - 1;
         tracer!(TRACE, "PacketParser::parse", indent);
+        span!(DEBUG, "parse_packet", depth = indent);
 
         if let Some(err) = state.pending_error.take() {
             t!("Returning pending error: {}", err);
@@ -4587,6 +4858,7 @@ impl <'a> PacketParser<'a> {
 
         let tag = header.ctb().tag();
         t!("Packet's tag is {}", tag);
+        event!(DEBUG, %tag, length = ?header.length(), "parsing packet");
 
         // A buffered_reader::Dup always has an inner.
         let mut bio = Box::new(bio).into_inner().unwrap();
@@ -4630,6 +4902,11 @@ impl <'a> PacketParser<'a> {
                 &BodyLength::Partial(len) => {
                     t!("Pushing a partial body chunk decoder, level: {}.",
                        recursion_depth);
+                    let mut cookie = Cookie::new(recursion_depth);
+                    if state.settings.map {
+                        // Record the headers for the map.
+                        cookie.partial_body_headers = Some(Vec::new());
+                    }
                     Box::new(BufferedReaderPartialBodyFilter::with_cookie(
                         bio, len,
                         // When hashing a literal data packet, we only
@@ -4638,7 +4915,7 @@ impl <'a> PacketParser<'a> {
                         // length information, which includes the
                         // partial body headers.
                         tag != Tag::Literal,
-                        Cookie::new(recursion_depth)))
+                        cookie))
                 },
                 BodyLength::Indeterminate => {
                     t!("Indeterminate length packet, not adding a limitor.");
@@ -4653,7 +4930,13 @@ impl <'a> PacketParser<'a> {
 
         // Check packet size.
         if header_syntax_error.is_none() {
-            let max_size = state.settings.max_packet_size;
+            let max_size = match tag {
+                Tag::UserID => state.settings.max_user_id_size
+                    .min(state.settings.max_packet_size),
+                Tag::UserAttribute => state.settings.max_user_attribute_size
+                    .min(state.settings.max_packet_size),
+                _ => state.settings.max_packet_size,
+            };
             match tag {
                 // Don't check the size for container packets, those
                 // can be safely streamed.
@@ -4707,6 +4990,12 @@ impl <'a> PacketParser<'a> {
 
         result.state.first_packet = false;
 
+        if result.state.settings.collect_diagnostics {
+            let d = diagnostics::Diagnostic::for_packet(&result.packet,
+                                                        &result.path);
+            result.state.diagnostics.extend(d);
+        }
+
         t!(" -> {:?}, path: {:?}, level: {:?}.",
            result.packet.tag(), result.path, result.cookie_ref().level);
 
@@ -5235,6 +5524,19 @@ impl <'a> PacketParser<'a> {
         self.map.as_ref()
     }
 
+    /// Returns the diagnostics collected so far.
+    ///
+    /// This includes the diagnostics for the current packet.  Returns
+    /// an empty slice, unless diagnostics are enabled using
+    /// [`PacketParserBuilder::collect_diagnostics`].
+    ///
+    ///   [`PacketParserBuilder::collect_diagnostics`]: PacketParserBuilder::collect_diagnostics()
+    ///
+    /// See the [`diagnostics`] module for an example.
+    pub fn diagnostics(&self) -> &[diagnostics::Diagnostic] {
+        &self.state.diagnostics
+    }
+
     /// Takes the map (if any is written).
     ///
     /// # Examples
@@ -5567,7 +5869,7 @@ impl<'a> PacketParser<'a> {
 
                 // This can't fail, because we create a decryptor
                 // above with the same parameters.
-                let reader = self.take_reader();
+                let reader = self.take_encrypted_body();
                 let mut reader = BufferedReaderDecryptor::with_cookie(
                     algo, key, reader, Cookie::default()).unwrap();
                 reader.cookie_mut().level = Some(self.recursion_depth());
@@ -5643,7 +5945,19 @@ impl<'a> PacketParser<'a> {
                         schedule, key.clone(),
                         &data[..cmp::min(data.len(), amount)])?;
                     let mut chunk = Vec::new();
-                    dec.take(aed.chunk_size() as u64).read_to_end(&mut chunk)?;
+                    if let Err(e) = dec.take(aed.chunk_size() as u64)
+                        .read_to_end(&mut chunk)
+                    {
+                        // If the first chunk can't be authenticated,
+                        // the session key is most likely wrong.
+                        if let Some(Error::BadAEADTag) = e.get_ref()
+                            .and_then(|e| e.downcast_ref::<Error>())
+                        {
+                            return Err(Error::InvalidSessionKey(
+                                "Decryption failed".into()).into());
+                        }
+                        return Err(e.into());
+                    }
                 }
 
                 // Ok, we can decrypt the data.  Push a Decryptor and
@@ -5657,7 +5971,7 @@ impl<'a> PacketParser<'a> {
                     chunk_size,
                     aed.iv())?;
 
-                let reader = self.take_reader();
+                let reader = self.take_encrypted_body();
                 let mut reader = aead::BufferedReaderDecryptor::with_cookie(
                     aed.symmetric_algo(), aed.aead(), chunk_size,
                     schedule, key.clone(), reader, Cookie::default()).unwrap();
@@ -6406,6 +6720,66 @@ mod test {
 
     }
 
+    #[test]
+    fn component_and_subpacket_limits() -> Result<()> {
+        use crate::serialize::MarshalInto;
+        use crate::packet::signature::SignatureBuilder;
+
+        // Returns the error of the first packet, if it is unknown.
+        fn parse<F>(data: &[u8], f: F) -> Result<Option<Error>>
+            where F: Fn(PacketParserBuilder) -> PacketParserBuilder
+        {
+            let ppr = f(PacketParserBuilder::from_bytes(data)?).build()?;
+            if let PacketParserResult::Some(pp) = ppr {
+                if let Packet::Unknown(u) = &pp.packet {
+                    Ok(u.error().downcast_ref::<Error>().cloned())
+                } else {
+                    Ok(None)
+                }
+            } else {
+                panic!("no packet");
+            }
+        }
+
+        // User IDs.
+        let uid = Packet::UserID(vec![b'a'; 100].into()).to_vec()?;
+        assert_eq!(parse(&uid, |b| b)?, None);
+        assert_eq!(parse(&uid, |b| b.max_user_id_size(99))?,
+                   Some(Error::PacketTooLarge(Tag::UserID, 100, 99)));
+        // The general limit still applies.
+        assert_eq!(parse(&uid, |b| b.max_packet_size(50))?,
+                   Some(Error::PacketTooLarge(Tag::UserID, 100, 50)));
+        let uid = Packet::UserID(
+            vec![b'a'; DEFAULT_MAX_USER_ID_SIZE as usize + 1].into()).to_vec()?;
+        assert_match!(Some(Error::PacketTooLarge(Tag::UserID, _, _))
+                      = parse(&uid, |b| b)?);
+
+        // User Attributes.
+        let ua = Packet::UserAttribute(vec![0; 100].into()).to_vec()?;
+        assert_eq!(parse(&ua, |b| b)?, None);
+        assert_eq!(parse(&ua, |b| b.max_user_attribute_size(99))?,
+                   Some(Error::PacketTooLarge(Tag::UserAttribute, 100, 99)));
+
+        // Subpacket areas.
+        let (cert, _) = crate::cert::CertBuilder::new().generate()?;
+        let mut signer = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+        let mut builder = SignatureBuilder::new(SignatureType::Binary);
+        for _ in 0..10 {
+            builder = builder.add_notation(
+                "n@example.org", vec![0; 100], None, false)?;
+        }
+        let sig = Packet::from(builder.sign_message(&mut signer, b"")?)
+            .to_vec()?;
+        assert_eq!(parse(&sig, |b| b)?, None);
+        assert_match!(Some(Error::SubpacketAreaTooLarge(_, 512))
+                      = parse(&sig, |b| b.max_subpacket_area_size(512))?);
+        assert_eq!(parse(&sig, |b| b.max_subpackets(10))?,
+                   Some(Error::TooManySubpackets(10)));
+        assert_eq!(parse(&sig, |b| b.max_subpackets(20))?, None);
+        Ok(())
+    }
+
     /// We erroneously assumed that when BufferedReader::next() is
     /// called, a SEIP container be opaque and hence there cannot be a
     /// buffered_reader::Reserve on the stack with Cookie::fake_eof
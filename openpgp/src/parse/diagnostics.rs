@@ -0,0 +1,343 @@
+//! Diagnostics.
+//!
+//! If configured to do so, a [`PacketParser`] or a [`CertParser`]
+//! records non-fatal observations about the packets it parses, like
+//! the use of weak algorithms.  These are not errors: the packets are
+//! parsed as usual, and whether they are acceptable is decided by the
+//! [`Policy`] when they are used.  But, they are useful for tools that
+//! want to give the user feedback, for instance a certificate's
+//! "health check", without a separate validation pass.
+//!
+//!   [`PacketParser`]: super::PacketParser
+//!   [`CertParser`]: crate::cert::CertParser
+//!   [`Policy`]: crate::policy::Policy
+//!
+//! # Examples
+//!
+//! ```
+//! # fn main() -> sequoia_openpgp::Result<()> {
+//! use sequoia_openpgp as openpgp;
+//! use openpgp::cert::prelude::*;
+//! use openpgp::parse::{Parse, PacketParserBuilder, PacketParserResult};
+//! use openpgp::serialize::Serialize;
+//!
+//! let (cert, _) = CertBuilder::new()
+//!     .set_cipher_suite(CipherSuite::RSA2k)
+//!     .generate()?;
+//! let mut bytes = Vec::new();
+//! cert.serialize(&mut bytes)?;
+//!
+//! let mut ppr = PacketParserBuilder::from_bytes(&bytes)?
+//!     .collect_diagnostics(true)
+//!     .build()?;
+//! while let PacketParserResult::Some(pp) = ppr {
+//!     ppr = pp.recurse()?.1;
+//! }
+//! if let PacketParserResult::EOF(eof) = ppr {
+//!     for d in eof.diagnostics() {
+//!         eprintln!("{:?}: {}", d.path(), d);
+//!     }
+//! }
+//! # Ok(()) }
+//! ```
+
+use std::fmt;
+
+use crate::{
+    Packet,
+    packet::{
+        SKESK,
+        Tag,
+        key::SecretKeyMaterial,
+    },
+    crypto::S2K,
+    types::{
+        HashAlgorithm,
+        PublicKeyAlgorithm,
+        SymmetricAlgorithm,
+    },
+};
+
+/// How serious a diagnostic is.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The algorithm or parameter is deprecated, but still in use.
+    ///
+    /// Artifacts using it should be updated, but they are generally
+    /// still accepted.
+    Soft,
+
+    /// The algorithm or parameter is broken.
+    ///
+    /// Artifacts using it are generally rejected.
+    Hard,
+}
+assert_send_and_sync!(Severity);
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Soft => f.write_str("deprecated"),
+            Severity::Hard => f.write_str("broken"),
+        }
+    }
+}
+
+/// What a diagnostic is about.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A signature uses a weak hash algorithm.
+    WeakSignatureHash(HashAlgorithm),
+
+    /// A key is too short.
+    WeakKey {
+        /// The key's algorithm.
+        algo: PublicKeyAlgorithm,
+        /// The key's size in bits.
+        bits: usize,
+    },
+
+    /// A signature states a preference for a weak symmetric
+    /// algorithm.
+    WeakSymmetricPreference(SymmetricAlgorithm),
+
+    /// A signature states a preference for a weak hash algorithm.
+    WeakHashPreference(HashAlgorithm),
+
+    /// A password is stretched using a weak hash algorithm.
+    WeakS2KHash(HashAlgorithm),
+}
+assert_send_and_sync!(Kind);
+
+impl Kind {
+    /// Returns how serious this is.
+    pub fn severity(&self) -> Severity {
+        use self::Kind::*;
+        match self {
+            WeakSignatureHash(HashAlgorithm::MD5)
+                | WeakHashPreference(HashAlgorithm::MD5)
+                | WeakS2KHash(HashAlgorithm::MD5) => Severity::Hard,
+            WeakKey { bits, .. } if *bits < 1024 => Severity::Hard,
+            _ => Severity::Soft,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Kind::*;
+        match self {
+            WeakSignatureHash(a) =>
+                write!(f, "Signature uses {}", a),
+            WeakKey { algo, bits } =>
+                write!(f, "{} key has only {} bits", algo, bits),
+            WeakSymmetricPreference(a) =>
+                write!(f, "Signature prefers {}", a),
+            WeakHashPreference(a) =>
+                write!(f, "Signature prefers {}", a),
+            WeakS2KHash(a) =>
+                write!(f, "Password is stretched using {}", a),
+        }
+    }
+}
+
+/// A non-fatal observation about a packet.
+///
+/// See the [module-level documentation] for details.
+///
+///   [module-level documentation]: self
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    path: Vec<usize>,
+    tag: Tag,
+    kind: Kind,
+}
+assert_send_and_sync!(Diagnostic);
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} packet: {} ({})", self.tag, self.kind, self.severity())
+    }
+}
+
+impl Diagnostic {
+    /// Returns the diagnostics for the given packet.
+    ///
+    /// `path` is the packet's position, see
+    /// [`PacketParser::path`](super::PacketParser::path).
+    pub fn for_packet(packet: &Packet, path: &[usize]) -> Vec<Diagnostic> {
+        let mut kinds = Vec::new();
+        check(packet, &mut kinds);
+        kinds.into_iter()
+            .map(|kind| Diagnostic {
+                path: path.to_vec(),
+                tag: packet.tag(),
+                kind,
+            })
+            .collect()
+    }
+
+    /// Returns the position of the packet this diagnostic is about.
+    ///
+    /// For the [`PacketParser`], this is the path as returned by
+    /// [`PacketParser::path`].  For the [`CertParser`], this is the
+    /// index of the packet in the certificate's packet sequence.
+    ///
+    ///   [`PacketParser`]: super::PacketParser
+    ///   [`PacketParser::path`]: super::PacketParser::path()
+    ///   [`CertParser`]: crate::cert::CertParser
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    /// Returns the packet's tag.
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    /// Returns what this diagnostic is about.
+    pub fn kind(&self) -> &Kind {
+        &self.kind
+    }
+
+    /// Returns how serious this is.
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+/// Returns whether the hash algorithm is weak.
+fn weak_hash(algo: HashAlgorithm) -> bool {
+    matches!(algo,
+             HashAlgorithm::MD5 | HashAlgorithm::SHA1 | HashAlgorithm::RipeMD)
+}
+
+/// Returns whether the symmetric algorithm is weak.
+///
+/// Note: TripleDES is not flagged, because RFC 4880 mandates that it
+/// is implicitly the last preference, so many certificates list it.
+fn weak_symmetric(algo: SymmetricAlgorithm) -> bool {
+    matches!(algo,
+             SymmetricAlgorithm::IDEA
+             | SymmetricAlgorithm::CAST5
+             | SymmetricAlgorithm::Blowfish)
+}
+
+/// Checks the S2K's hash algorithm.
+fn check_s2k(s2k: &S2K, kinds: &mut Vec<Kind>) {
+    #[allow(deprecated)]
+    match s2k {
+        S2K::Iterated { hash, .. }
+        | S2K::Salted { hash, .. }
+        | S2K::Simple { hash } if *hash == HashAlgorithm::MD5 =>
+            kinds.push(Kind::WeakS2KHash(*hash)),
+        S2K::Implicit =>
+            kinds.push(Kind::WeakS2KHash(HashAlgorithm::MD5)),
+        _ => (),
+    }
+}
+
+/// Checks the packet, adding any observations to `kinds`.
+fn check(packet: &Packet, kinds: &mut Vec<Kind>) {
+    let key = match packet {
+        Packet::PublicKey(k) => Some(k.role_as_unspecified().parts_as_unspecified()),
+        Packet::PublicSubkey(k) => Some(k.role_as_unspecified().parts_as_unspecified()),
+        Packet::SecretKey(k) => Some(k.role_as_unspecified().parts_as_unspecified()),
+        Packet::SecretSubkey(k) => Some(k.role_as_unspecified().parts_as_unspecified()),
+        _ => None,
+    };
+
+    if let Some(key) = key {
+        #[allow(deprecated)]
+        let rsa_like = matches!(key.pk_algo(),
+                                PublicKeyAlgorithm::RSAEncryptSign
+                                | PublicKeyAlgorithm::RSAEncrypt
+                                | PublicKeyAlgorithm::RSASign
+                                | PublicKeyAlgorithm::DSA
+                                | PublicKeyAlgorithm::ElGamalEncrypt
+                                | PublicKeyAlgorithm::ElGamalEncryptSign);
+        if let Some(bits) = key.mpis().bits() {
+            if rsa_like && bits < 2048 {
+                kinds.push(Kind::WeakKey { algo: key.pk_algo(), bits });
+            }
+        }
+
+        if let Some(SecretKeyMaterial::Encrypted(e)) = key.optional_secret() {
+            check_s2k(e.s2k(), kinds);
+        }
+    }
+
+    match packet {
+        Packet::Signature(sig) => {
+            if weak_hash(sig.hash_algo()) {
+                kinds.push(Kind::WeakSignatureHash(sig.hash_algo()));
+            }
+
+            for a in sig.preferred_symmetric_algorithms().unwrap_or(&[]) {
+                if weak_symmetric(*a) {
+                    kinds.push(Kind::WeakSymmetricPreference(*a));
+                }
+            }
+
+            for a in sig.preferred_hash_algorithms().unwrap_or(&[]) {
+                if *a == HashAlgorithm::MD5 {
+                    kinds.push(Kind::WeakHashPreference(*a));
+                }
+            }
+        },
+
+        Packet::SKESK(SKESK::V4(s)) => check_s2k(s.s2k(), kinds),
+        Packet::SKESK(SKESK::V5(s)) => check_s2k(s.s2k(), kinds),
+
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::parse::{Parse, PacketParserBuilder, PacketParserResult};
+
+    #[test]
+    fn weak_cert() -> crate::Result<()> {
+        // A DSA-768/ElGamal-768 key with SHA-1 self-signatures
+        // preferring CAST5 and IDEA.
+        let bytes = crate::tests::key("pgp5-dsa-elg-v3-subkey-binding.pgp");
+
+        let mut ppr = PacketParserBuilder::from_bytes(bytes)?
+            .collect_diagnostics(true)
+            .build()?;
+        while let PacketParserResult::Some(pp) = ppr {
+            ppr = pp.recurse()?.1;
+        }
+        let diagnostics = if let PacketParserResult::EOF(eof) = ppr {
+            eof.diagnostics().to_vec()
+        } else {
+            unreachable!()
+        };
+
+        assert!(diagnostics.iter().any(|d| d.path() == [0]
+            && d.tag() == Tag::PublicKey
+            && d.kind() == &Kind::WeakKey {
+                algo: PublicKeyAlgorithm::DSA,
+                bits: 768,
+            }
+            && d.severity() == Severity::Hard));
+        assert!(diagnostics.iter().any(|d| d.tag() == Tag::Signature
+            && d.kind() == &Kind::WeakSignatureHash(HashAlgorithm::SHA1)
+            && d.severity() == Severity::Soft));
+        assert!(diagnostics.iter().any(|d| d.kind()
+            == &Kind::WeakSymmetricPreference(SymmetricAlgorithm::IDEA)));
+
+        // Disabled by default.
+        let mut ppr = PacketParserBuilder::from_bytes(bytes)?.build()?;
+        while let PacketParserResult::Some(pp) = ppr {
+            assert!(pp.diagnostics().is_empty());
+            ppr = pp.recurse()?.1;
+        }
+        Ok(())
+    }
+}
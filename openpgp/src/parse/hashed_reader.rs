@@ -11,6 +11,7 @@ use crate::parse::{Cookie, HashesFor, Hashing};
 use crate::Result;
 use crate::types::HashAlgorithm;
 use crate::types::SignatureType;
+use crate::utils::Progress;
 
 const TRACE : bool = false;
 
@@ -454,8 +455,12 @@ impl<R: BufferedReader<Cookie>>
 /// convenient method, see [`DetachedVerifier`].
 ///
 ///  [`DetachedVerifier`]: crate::parse::stream::DetachedVerifier
+///
+/// If `progress` is given, it is updated with the number of bytes
+/// hashed.
 pub(crate) fn hash_buffered_reader<R>(reader: R,
-                                      algos: &[HashingMode<HashAlgorithm>])
+                                      algos: &[HashingMode<HashAlgorithm>],
+                                      progress: Option<&mut Progress>)
     -> Result<Vec<HashingMode<Box<dyn crate::crypto::hash::Digest>>>>
     where R: BufferedReader<crate::parse::Cookie>,
 {
@@ -463,7 +468,18 @@ pub(crate) fn hash_buffered_reader<R>(reader: R,
         = HashedReader::new(reader, HashesFor::Signature, algos.to_vec())?;
 
     // Hash all of the data.
-    reader.drop_eof()?;
+    if let Some(progress) = progress {
+        const CHUNK_SIZE: usize = 64 * 1024;
+        loop {
+            let n = reader.data_consume(CHUNK_SIZE)?.len().min(CHUNK_SIZE);
+            if n == 0 {
+                break;
+            }
+            progress.update(n);
+        }
+    } else {
+        reader.drop_eof()?;
+    }
 
     let hashes =
         mem::take(&mut reader.cookie_mut().sig_group_mut().hashes);
@@ -586,7 +602,8 @@ mod test {
                 reader,
                 &expected.keys().cloned()
                     .map(HashingMode::Binary).
-                    collect::<Vec<_>>())
+                    collect::<Vec<_>>(),
+                None)
             .unwrap();
 
         for mut mode in result.into_iter() {
@@ -27,6 +27,8 @@
 
 use std::cmp;
 
+use crate::packet::header::BodyLength;
+
 /// Map created during parsing.
 #[derive(Clone, Debug)]
 pub struct Map {
@@ -34,6 +36,15 @@ pub struct Map {
     entries: Vec<Entry>,
     header: Vec<u8>,
     data: Vec<u8>,
+    /// The length of the body as encoded in the header.
+    body_length: BodyLength,
+    /// Whether `data` contains the whole body.
+    complete: bool,
+    /// The partial body length headers following the first chunk.
+    ///
+    /// Each entry is the offset into `data` at which the header was
+    /// encountered, the length it encodes, and the header as read.
+    partial_body_headers: Vec<(usize, BodyLength, Vec<u8>)>,
 }
 assert_send_and_sync!(Map);
 
@@ -47,12 +58,15 @@ struct Entry {
 
 impl Map {
     /// Creates a new map.
-    pub(super) fn new(header: Vec<u8>) -> Self {
+    pub(super) fn new(header: Vec<u8>, body_length: BodyLength) -> Self {
         Map {
             length: 0,
             entries: Vec::new(),
             header,
             data: Vec::new(),
+            body_length,
+            complete: false,
+            partial_body_headers: Vec::new(),
         }
     }
 
@@ -65,8 +79,136 @@ impl Map {
     }
 
     /// Finalizes the map providing the actual data.
-    pub(super) fn finalize(&mut self, data: Vec<u8>) {
+    ///
+    /// `complete` indicates whether `data` contains the whole body.
+    /// `partial_body_headers` are the partial body length headers
+    /// following the first chunk, if any.
+    pub(super) fn finalize(&mut self, data: Vec<u8>, complete: bool,
+                           partial_body_headers:
+                               Vec<(usize, BodyLength, Vec<u8>)>)
+    {
         self.data = data;
+        self.complete = complete;
+        self.partial_body_headers = partial_body_headers;
+    }
+
+    /// Returns the packet's header as it was read.
+    ///
+    /// This is the CTB followed by the length, if any.  For
+    /// synthetic packets, this is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::parse::{Parse, PacketParserBuilder};
+    ///
+    /// let message_data = b"\xcb\x12t\x00\x00\x00\x00\x00Hello world.";
+    /// let pp = PacketParserBuilder::from_bytes(message_data)?
+    ///     .map(true) // Enable mapping.
+    ///     .build()?
+    ///     .expect("One packet, not EOF");
+    /// let map = pp.map().expect("Mapping is enabled");
+    ///
+    /// assert_eq!(map.header(), &[0xcb, 0x12]);
+    /// # Ok(()) }
+    /// ```
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// Returns the packet's body as far as it was read.
+    ///
+    /// Unless [`PacketParserBuilder::buffer_unread_content`] is used,
+    /// this only contains the part of the body that was parsed.  If
+    /// the packet uses partial body lengths, the body is returned
+    /// without the partial body length headers.
+    ///
+    ///   [`PacketParserBuilder::buffer_unread_content`]: super::PacketParserBuilder::buffer_unread_content()
+    pub fn body(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the packet as it was read.
+    ///
+    /// This is the packet's header followed by its body.  If the
+    /// packet uses partial body lengths, the partial body length
+    /// headers are reinserted into the body.  Whether this is the
+    /// packet byte for byte depends on whether the packet was read
+    /// completely, see [`Map::is_exact`].
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(
+            self.header.len() + self.data.len()
+                + self.partial_body_headers.iter()
+                .map(|(_, _, h)| h.len()).sum::<usize>());
+        packet.extend_from_slice(&self.header);
+
+        let mut offset = 0;
+        for (at, _, header) in &self.partial_body_headers {
+            let at = cmp::min(*at, self.data.len());
+            packet.extend_from_slice(&self.data[offset..at]);
+            packet.extend_from_slice(header);
+            offset = at;
+        }
+        packet.extend_from_slice(&self.data[offset..]);
+        packet
+    }
+
+    /// Returns whether the map describes the packet's encoding
+    /// exactly.
+    ///
+    /// If this returns `true`, [`Map::to_vec`] returns the packet
+    /// byte for byte, as it appeared in the stream.  For packets
+    /// that don't use partial body lengths, this is the
+    /// concatenation of [`Map::header`] and [`Map::body`].  This is
+    /// not the case if the body was not completely read, e.g.
+    /// because [`PacketParserBuilder::buffer_unread_content`] was not
+    /// used, or because the packet is truncated.
+    ///
+    ///   [`PacketParserBuilder::buffer_unread_content`]: super::PacketParserBuilder::buffer_unread_content()
+    ///
+    /// # Examples
+    ///
+    /// Splitting a stream into packets, preserving their encoding:
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::parse::{Parse, PacketParserBuilder, PacketParserResult};
+    ///
+    /// let message_data = b"\xcb\x12t\x00\x00\x00\x00\x00Hello world.\
+    ///                      \xcb\x0et\x00\x00\x00\x00\x00Goodbye.";
+    /// let mut ppr = PacketParserBuilder::from_bytes(&message_data[..])?
+    ///     .map(true) // Enable mapping.
+    ///     .buffer_unread_content() // For the packet body.
+    ///     .build()?;
+    ///
+    /// let mut packets = Vec::new();
+    /// while let PacketParserResult::Some(pp) = ppr {
+    ///     let map = pp.map().expect("Mapping is enabled");
+    ///     assert!(map.is_exact());
+    ///     packets.push(map.to_vec());
+    ///     ppr = pp.next()?.1;
+    /// }
+    ///
+    /// assert_eq!(packets.len(), 2);
+    /// assert_eq!(packets.concat(), &message_data[..]);
+    /// # Ok(()) }
+    /// ```
+    pub fn is_exact(&self) -> bool {
+        self.complete && match self.body_length {
+            BodyLength::Full(len) => self.data.len() == len as usize,
+            BodyLength::Partial(_) =>
+                match self.partial_body_headers.last() {
+                    // The last chunk's header has been read, and so
+                    // has its body.
+                    Some((at, BodyLength::Full(len), _)) =>
+                        self.data.len() == at + *len as usize,
+                    _ => false,
+                },
+            BodyLength::Indeterminate => true,
+        }
     }
 
     /// Creates an iterator over the map.
@@ -278,3 +420,86 @@ impl<'a> Iterator for Iter<'a> {
         field
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::parse::{Parse, PacketParserBuilder};
+
+    #[test]
+    fn is_exact() -> crate::Result<()> {
+        let message_data = b"\xcb\x12t\x00\x00\x00\x00\x00Hello world.";
+
+        // The body has not been buffered.
+        let pp = PacketParserBuilder::from_bytes(message_data)?
+            .map(true)
+            .build()?
+            .expect("One packet, not EOF");
+        let map = pp.map().unwrap();
+        assert!(! map.is_exact());
+        assert_eq!(map.body(), b"t\x00\x00\x00\x00\x00");
+
+        // Truncated packet.
+        let pp = PacketParserBuilder::from_bytes(&message_data[..10])?
+            .map(true)
+            .buffer_unread_content()
+            .build()?
+            .expect("One packet, not EOF");
+        assert!(! pp.map().unwrap().is_exact());
+
+        // Partial body lengths: a 512 byte chunk followed by a 10
+        // byte chunk.
+        let mut message_data = vec![0xcb, 0xe9, b'b', 0, 0, 0, 0, 0];
+        message_data.extend_from_slice(&[0x42; 506]);
+        message_data.push(10);
+        message_data.extend_from_slice(&[0x42; 10]);
+        let pp = PacketParserBuilder::from_bytes(&message_data)?
+            .map(true)
+            .buffer_unread_content()
+            .build()?
+            .expect("One packet, not EOF");
+        let map = pp.map().unwrap();
+        assert!(map.is_exact());
+        assert_eq!(map.header(), &[0xcb, 0xe9]);
+        assert_eq!(map.body().len(), 512 + 10);
+        assert_eq!(map.to_vec(), message_data);
+
+        // Several chunks, with a non-canonical encoding of the last
+        // chunk's length.
+        let mut message_data = vec![0xcb, 0xe9, b'b', 0, 0, 0, 0, 0];
+        message_data.extend_from_slice(&[0x42; 506]);
+        message_data.extend_from_slice(&[0xe0, 0x42]);
+        message_data.extend_from_slice(&[0xe1, 0x42, 0x42]);
+        message_data.extend_from_slice(&[0xff, 0, 0, 0, 4, 0x42, 0x42, 0x42,
+                                         0x42]);
+        let pp = PacketParserBuilder::from_bytes(&message_data)?
+            .map(true)
+            .buffer_unread_content()
+            .build()?
+            .expect("One packet, not EOF");
+        let map = pp.map().unwrap();
+        assert!(map.is_exact());
+        assert_eq!(map.body().len(), 512 + 1 + 2 + 4);
+        assert_eq!(map.to_vec(), message_data);
+
+        // Truncated in the last chunk.
+        let message_data = &message_data[..message_data.len() - 2];
+        let pp = PacketParserBuilder::from_bytes(message_data)?
+            .map(true)
+            .buffer_unread_content()
+            .build()?
+            .expect("One packet, not EOF");
+        assert!(! pp.map().unwrap().is_exact());
+
+        // The partial body length headers are only recorded when
+        // mapping.
+        let mut message_data = vec![0xcb, 0xe9, b'b', 0, 0, 0, 0, 0];
+        message_data.extend_from_slice(&[0x42; 506]);
+        message_data.push(10);
+        message_data.extend_from_slice(&[0x42; 10]);
+        let pp = PacketParserBuilder::from_bytes(&message_data)?
+            .build()?
+            .expect("One packet, not EOF");
+        assert!(pp.map().is_none());
+        Ok(())
+    }
+}
@@ -16,6 +16,46 @@ use crate::parse::{
     Cookie,
 };
 
+/// The maximum size of RSA moduli and finite field primes, in bits.
+///
+/// This is well above any key size in practical use.  Larger keys
+/// make signature verification and encryption arbitrarily slow,
+/// which attackers can exploit to exhaust servers parsing untrusted
+/// certificates.
+pub(crate) const MAX_FF_BITS: usize = 16384;
+
+/// The maximum size of DSA subgroup orders, in bits.
+pub(crate) const MAX_DSA_Q_BITS: usize = 512;
+
+/// The maximum size of elliptic curve points and scalars, in bits.
+///
+/// This is only used for curves we don't know.  For known curves,
+/// the encoding of points is checked precisely.
+pub(crate) const MAX_ECC_BITS: usize = 2048;
+
+/// Returns an error if `mpi` is larger than `max` bits.
+fn check_size(name: &str, mpi: &MPI, max: usize) -> Result<()> {
+    if mpi.bits() > max {
+        return Err(Error::MalformedMPI(
+            format!("{} is too large: {} bits (maximum: {})",
+                    name, mpi.bits(), max)).into());
+    }
+    Ok(())
+}
+
+/// Returns an error if `mpi` is not a well-formed point on `curve`.
+///
+/// This checks the encoding, which is cheap.  Whether the point is
+/// actually on the curve is checked by the cryptographic backend
+/// when the key is used.
+fn check_point(name: &str, mpi: &MPI, curve: &Curve) -> Result<()> {
+    if curve.bits().is_some() {
+        mpi.decode_point(curve).map(|_| ())
+    } else {
+        check_size(name, mpi, MAX_ECC_BITS)
+    }
+}
+
 impl mpi::PublicKey {
     /// Parses a set of OpenPGP MPIs representing a public key.
     ///
@@ -48,6 +88,19 @@ impl mpi::PublicKey {
                 let n = MPI::parse("rsa_public_n_len", "rsa_public_n", php)?;
                 let e = MPI::parse("rsa_public_e_len", "rsa_public_e", php)?;
 
+                check_size("RSA modulus", &n, MAX_FF_BITS)?;
+                check_size("RSA public exponent", &e, MAX_FF_BITS)?;
+                let odd = |mpi: &MPI| mpi.value().last()
+                    .map(|b| b & 1 == 1).unwrap_or(false);
+                if ! odd(&n) {
+                    return Err(Error::MalformedMPI(
+                        "RSA modulus is even".into()).into());
+                }
+                if e.bits() < 2 || ! odd(&e) {
+                    return Err(Error::MalformedMPI(
+                        "Invalid RSA public exponent".into()).into());
+                }
+
                 Ok(mpi::PublicKey::RSA { e, n })
             }
 
@@ -57,6 +110,11 @@ impl mpi::PublicKey {
                 let g = MPI::parse("dsa_public_g_len", "dsa_public_g", php)?;
                 let y = MPI::parse("dsa_public_y_len", "dsa_public_y", php)?;
 
+                check_size("DSA prime", &p, MAX_FF_BITS)?;
+                check_size("DSA subgroup order", &q, MAX_DSA_Q_BITS)?;
+                check_size("DSA generator", &g, p.bits())?;
+                check_size("DSA public key", &y, p.bits())?;
+
                 Ok(mpi::PublicKey::DSA {
                     p,
                     q,
@@ -73,6 +131,10 @@ impl mpi::PublicKey {
                 let y = MPI::parse("elgamal_public_y_len", "elgamal_public_y",
                                    php)?;
 
+                check_size("ElGamal prime", &p, MAX_FF_BITS)?;
+                check_size("ElGamal generator", &g, p.bits())?;
+                check_size("ElGamal public key", &y, p.bits())?;
+
                 Ok(mpi::PublicKey::ElGamal {
                     p,
                     g,
@@ -84,9 +146,11 @@ impl mpi::PublicKey {
                 let curve_len = php.parse_u8("curve_len")? as usize;
                 let curve = php.parse_bytes("curve", curve_len)?;
                 let q = MPI::parse("eddsa_public_len", "eddsa_public", php)?;
+                let curve = Curve::from_oid(&curve);
+                check_point("EdDSA public key", &q, &curve)?;
 
                 Ok(mpi::PublicKey::EdDSA {
-                    curve: Curve::from_oid(&curve),
+                    curve,
                     q
                 })
             }
@@ -95,9 +159,11 @@ impl mpi::PublicKey {
                 let curve_len = php.parse_u8("curve_len")? as usize;
                 let curve = php.parse_bytes("curve", curve_len)?;
                 let q = MPI::parse("ecdsa_public_len", "ecdsa_public", php)?;
+                let curve = Curve::from_oid(&curve);
+                check_point("ECDSA public key", &q, &curve)?;
 
                 Ok(mpi::PublicKey::ECDSA {
-                    curve: Curve::from_oid(&curve),
+                    curve,
                     q
                 })
             }
@@ -106,6 +172,8 @@ impl mpi::PublicKey {
                 let curve_len = php.parse_u8("curve_len")? as usize;
                 let curve = php.parse_bytes("curve", curve_len)?;
                 let q = MPI::parse("ecdh_public_len", "ecdh_public", php)?;
+                let curve = Curve::from_oid(&curve);
+                check_point("ECDH public key", &q, &curve)?;
                 let kdf_len = php.parse_u8("kdf_len")?;
 
                 if kdf_len != 3 {
@@ -121,9 +189,10 @@ impl mpi::PublicKey {
                 }
                 let hash: HashAlgorithm = php.parse_u8("kdf_hash")?.into();
                 let sym: SymmetricAlgorithm = php.parse_u8("kek_symm")?.into();
+                crate::crypto::ecdh::check_kdf_parameters(hash, sym)?;
 
                 Ok(mpi::PublicKey::ECDH {
-                    curve: Curve::from_oid(&curve),
+                    curve,
                     q,
                     hash,
                     sym
@@ -382,6 +451,7 @@ impl mpi::Ciphertext {
             RSAEncryptSign | RSAEncrypt => {
                 let c = MPI::parse("rsa_ciphertxt_len", "rsa_ciphertxt",
                                    php)?;
+                check_size("RSA ciphertext", &c, MAX_FF_BITS)?;
 
                 Ok(mpi::Ciphertext::RSA {
                     c,
@@ -391,6 +461,8 @@ impl mpi::Ciphertext {
             ElGamalEncrypt | ElGamalEncryptSign => {
                 let e = MPI::parse("elgamal_e_len", "elgamal_e", php)?;
                 let c = MPI::parse("elgamal_c_len", "elgamal_c", php)?;
+                check_size("ElGamal ciphertext", &e, MAX_FF_BITS)?;
+                check_size("ElGamal ciphertext", &c, MAX_FF_BITS)?;
 
                 Ok(mpi::Ciphertext::ElGamal {
                     e,
@@ -400,6 +472,7 @@ impl mpi::Ciphertext {
 
             ECDH => {
                 let e = MPI::parse("ecdh_e_len", "ecdh_e", php)?;
+                check_size("ECDH ephemeral key", &e, MAX_ECC_BITS)?;
                 let key_len = php.parse_u8("ecdh_esk_len")? as usize;
                 let key = Vec::from(&php.parse_bytes("ecdh_esk", key_len)?
                                     [..key_len]);
@@ -460,6 +533,7 @@ impl mpi::Signature {
         match algo {
             RSAEncryptSign | RSASign => {
                 let s = MPI::parse("rsa_signature_len", "rsa_signature", php)?;
+                check_size("RSA signature", &s, MAX_FF_BITS)?;
 
                 Ok(mpi::Signature::RSA {
                     s,
@@ -471,6 +545,8 @@ impl mpi::Signature {
                                    php)?;
                 let s = MPI::parse("dsa_sig_s_len", "dsa_sig_s",
                                    php)?;
+                check_size("DSA signature", &r, MAX_DSA_Q_BITS)?;
+                check_size("DSA signature", &s, MAX_DSA_Q_BITS)?;
 
                 Ok(mpi::Signature::DSA {
                     r,
@@ -483,6 +559,8 @@ impl mpi::Signature {
                                    "elgamal_sig_r", php)?;
                 let s = MPI::parse("elgamal_sig_s_len",
                                    "elgamal_sig_s", php)?;
+                check_size("ElGamal signature", &r, MAX_FF_BITS)?;
+                check_size("ElGamal signature", &s, MAX_FF_BITS)?;
 
                 Ok(mpi::Signature::ElGamal {
                     r,
@@ -495,6 +573,8 @@ impl mpi::Signature {
                                    php)?;
                 let s = MPI::parse("eddsa_sig_s_len", "eddsa_sig_s",
                                    php)?;
+                check_size("EdDSA signature", &r, MAX_ECC_BITS)?;
+                check_size("EdDSA signature", &s, MAX_ECC_BITS)?;
 
                 Ok(mpi::Signature::EdDSA {
                     r,
@@ -507,6 +587,8 @@ impl mpi::Signature {
                                    php)?;
                 let s = MPI::parse("ecdsa_sig_s_len", "ecdsa_sig_s",
                                    php)?;
+                check_size("ECDSA signature", &r, MAX_ECC_BITS)?;
+                check_size("ECDSA signature", &s, MAX_ECC_BITS)?;
 
                 Ok(mpi::Signature::ECDSA {
                     r,
@@ -543,7 +625,7 @@ fn mpis_parse_test() {
 
     // Dummy RSA public key.
     {
-        let buf = Cursor::new("\x00\x01\x01\x00\x02\x02");
+        let buf = Cursor::new("\x00\x01\x01\x00\x02\x03");
         let mpis = mpi::PublicKey::parse(RSAEncryptSign, buf).unwrap();
 
         //assert_eq!(mpis.serialized_len(), 6);
@@ -553,7 +635,7 @@ fn mpis_parse_test() {
                 assert_eq!(n.value()[0], 1);
                 assert_eq!(n.value().len(), 1);
                 assert_eq!(e.bits(), 2);
-                assert_eq!(e.value()[0], 2);
+                assert_eq!(e.value()[0], 3);
                 assert_eq!(e.value().len(), 1);
             }
 
@@ -580,3 +662,51 @@ fn mpis_parse_test() {
     // not 2).
     assert!(MPI::from_bytes(b"\x00\x02\x01").is_err());
 }
+
+#[test]
+fn mpis_sanity_checks() -> Result<()> {
+    use std::io::Cursor;
+    use crate::PublicKeyAlgorithm::*;
+    use crate::serialize::MarshalInto;
+
+    let rsa = |n: &[u8], e: &[u8]| -> Result<mpi::PublicKey> {
+        let mut buf = MPI::new(n).to_vec()?;
+        buf.extend_from_slice(&MPI::new(e).to_vec()?);
+        mpi::PublicKey::parse(RSAEncryptSign, Cursor::new(buf))
+    };
+
+    let n = vec![0xff; 256];
+    assert!(rsa(&n, &[1, 0, 1]).is_ok());
+    // Bad public exponents.
+    assert!(rsa(&n, &[1]).is_err());
+    assert!(rsa(&n, &[2]).is_err());
+    // Even modulus.
+    assert!(rsa(&[0xfe; 256], &[1, 0, 1]).is_err());
+    // Huge modulus.
+    assert!(rsa(&vec![0xff; MAX_FF_BITS / 8], &[3]).is_ok());
+    assert!(rsa(&vec![0xff; MAX_FF_BITS / 8 + 1], &[3]).is_err());
+
+    // Oversized DSA signatures.
+    let dsa = |r: &[u8]| -> Result<mpi::Signature> {
+        let mut buf = MPI::new(r).to_vec()?;
+        buf.extend_from_slice(&MPI::new(&[1]).to_vec()?);
+        mpi::Signature::parse(DSA, Cursor::new(buf))
+    };
+    assert!(dsa(&[0xff; MAX_DSA_Q_BITS / 8]).is_ok());
+    assert!(dsa(&[0xff; MAX_DSA_Q_BITS / 8 + 1]).is_err());
+
+    // Malformed points.
+    let ecdsa = |q: &[u8]| -> Result<mpi::PublicKey> {
+        let mut buf = vec![Curve::NistP256.oid().len() as u8];
+        buf.extend_from_slice(Curve::NistP256.oid());
+        buf.extend_from_slice(&MPI::new(q).to_vec()?);
+        mpi::PublicKey::parse(ECDSA, Cursor::new(buf))
+    };
+    let mut q = vec![0x04];
+    q.extend_from_slice(&[0xaa; 64]);
+    assert!(ecdsa(&q).is_ok());
+    assert!(ecdsa(&q[..33]).is_err());
+    q[0] = 0x40;
+    assert!(ecdsa(&q).is_err());
+    Ok(())
+}
@@ -13,7 +13,6 @@ use crate::parse::ParserResult;
 use crate::parse::Parse;
 use crate::parse::Cookie;
 use crate::armor;
-use crate::packet;
 
 /// Controls transparent stripping of ASCII armor when parsing.
 ///
@@ -44,8 +43,9 @@ pub enum Dearmor {
     /// If input does not appear to be a binary encoded OpenPGP
     /// message, treat it as if it were encoded using ASCII armor.
     ///
-    /// This is the default.  The [`ReaderMode`] allow further
-    /// customization of the ASCII armor parser.
+    /// See [`armor::sniff`] for details.  This is the default.  The
+    /// [`ReaderMode`] allow further customization of the ASCII armor
+    /// parser.
     ///
     ///   [`ReaderMode`]: crate::armor::ReaderMode
     Auto(armor::ReaderMode),
@@ -58,6 +58,61 @@ impl Default for Dearmor {
     }
 }
 
+impl Dearmor {
+    /// Strips the ASCII armor from `bio`, if appropriate.
+    ///
+    /// This implements the dearmoring logic that is shared by all
+    /// [`Parse`] implementations.  In [`Dearmor::Auto`] mode, the
+    /// input is considered binary OpenPGP data if [`armor::sniff`]
+    /// says so, and ASCII armored otherwise.  If the input looks
+    /// neither like binary OpenPGP data nor like text, the armor
+    /// reader only scans the sniffed prefix for the armor header
+    /// line.  When dearmoring, a leading UTF-8 byte order mark is
+    /// skipped, and the armor reader skips any garbage preceding the
+    /// armor header line according to the [`armor::ReaderMode`].
+    ///
+    /// If dearmoring, the armor reader is created with the given
+    /// `cookie`.  Returns the resulting reader, and whether the armor
+    /// reader has been inserted.
+    pub(crate) fn apply<'a>(&self,
+                            mut bio: Box<dyn BufferedReader<Cookie> + 'a>,
+                            cookie: Cookie,
+                            csf_transformation: bool)
+        -> Result<(Box<dyn BufferedReader<Cookie> + 'a>, bool)>
+    {
+        let (mode, max_scan_bytes) = match self {
+            Dearmor::Enabled(mode) => (Some(*mode), usize::MAX),
+            Dearmor::Disabled => (None, usize::MAX),
+            Dearmor::Auto(_) if bio.eof() => (None, usize::MAX),
+            Dearmor::Auto(mode) =>
+                match armor::sniff(bio.data(armor::SNIFF_LEN)?) {
+                    armor::Encoding::Armored => (Some(*mode), usize::MAX),
+                    armor::Encoding::Binary => (None, usize::MAX),
+                    // If it is neither binary OpenPGP data nor text,
+                    // it is unlikely to be ASCII armored.  Still
+                    // look for the Armor Header Line, but don't scan
+                    // further than we sniffed.
+                    armor::Encoding::Unknown =>
+                        (Some(*mode), armor::SNIFF_LEN),
+                },
+        };
+
+        if let Some(mode) = mode {
+            if bio.data(armor::BOM.len())?.starts_with(armor::BOM) {
+                bio.consume(armor::BOM.len());
+            }
+
+            bio = armor::Reader::from_cookie_reader_csft(
+                bio, Some(mode), cookie, csf_transformation)
+                .with_max_scan_bytes(max_scan_bytes)
+                .into_boxed();
+            Ok((bio, true))
+        } else {
+            Ok((bio, false))
+        }
+    }
+}
+
 /// This is the level at which we insert the dearmoring filter into
 /// the buffered reader stack.
 pub(super) const ARMOR_READER_LEVEL: isize = -2;
@@ -247,6 +302,122 @@ impl<'a> PacketParserBuilder<'a> {
         self
     }
 
+    /// Sets the maximum size in bytes of User ID packets.
+    ///
+    /// User ID packets that exceed this limit, or the limit set using
+    /// [`PacketParserBuilder::max_packet_size`], will be returned as
+    /// `Packet::Unknown`, with the error set to
+    /// `Error::PacketTooLarge`.
+    ///
+    /// The default is [`DEFAULT_MAX_USER_ID_SIZE`].
+    ///
+    /// [`PacketParserBuilder::max_packet_size`]: PacketParserBuilder::max_packet_size()
+    /// [`DEFAULT_MAX_USER_ID_SIZE`]: crate::parse::DEFAULT_MAX_USER_ID_SIZE
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::{Error, Packet};
+    /// use openpgp::packet::{Tag, UserID};
+    /// use openpgp::parse::{Parse, PacketParserResult, PacketParserBuilder};
+    /// use openpgp::serialize::MarshalInto;
+    ///
+    /// let uid = Packet::from(UserID::from("Alice <alice@example.org>"));
+    /// let data = uid.to_vec()?;
+    ///
+    /// let ppr = PacketParserBuilder::from_bytes(&data)?
+    ///     .max_user_id_size(16)
+    ///     .build()?;
+    /// if let PacketParserResult::Some(pp) = ppr {
+    ///     if let Packet::Unknown(u) = &pp.packet {
+    ///         assert_eq!(
+    ///             &Error::PacketTooLarge(Tag::UserID, 25, 16),
+    ///             u.error().downcast_ref().unwrap());
+    ///     } else {
+    ///         unreachable!();
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn max_user_id_size(mut self, value: u32) -> Self {
+        self.settings.max_user_id_size = value;
+        self
+    }
+
+    /// Sets the maximum size in bytes of User Attribute packets.
+    ///
+    /// User Attribute packets that exceed this limit, or the limit
+    /// set using [`PacketParserBuilder::max_packet_size`], will be
+    /// returned as `Packet::Unknown`, with the error set to
+    /// `Error::PacketTooLarge`.
+    ///
+    /// The default is [`DEFAULT_MAX_USER_ATTRIBUTE_SIZE`].
+    ///
+    /// [`PacketParserBuilder::max_packet_size`]: PacketParserBuilder::max_packet_size()
+    /// [`DEFAULT_MAX_USER_ATTRIBUTE_SIZE`]: crate::parse::DEFAULT_MAX_USER_ATTRIBUTE_SIZE
+    pub fn max_user_attribute_size(mut self, value: u32) -> Self {
+        self.settings.max_user_attribute_size = value;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a signature's subpacket
+    /// areas.
+    ///
+    /// Signatures with a hashed or unhashed subpacket area that
+    /// exceeds this limit will be returned as `Packet::Unknown`, with
+    /// the error set to `Error::SubpacketAreaTooLarge`.
+    ///
+    /// The default is [`DEFAULT_MAX_SUBPACKET_AREA_SIZE`].
+    ///
+    /// [`DEFAULT_MAX_SUBPACKET_AREA_SIZE`]: crate::parse::DEFAULT_MAX_SUBPACKET_AREA_SIZE
+    pub fn max_subpacket_area_size(mut self, value: u32) -> Self {
+        self.settings.max_subpacket_area_size = value;
+        self
+    }
+
+    /// Sets the maximum number of subpackets in a signature's
+    /// subpacket areas.
+    ///
+    /// Signatures with a hashed or unhashed subpacket area that
+    /// contains more subpackets will be returned as
+    /// `Packet::Unknown`, with the error set to
+    /// `Error::TooManySubpackets`.
+    ///
+    /// The default is [`DEFAULT_MAX_SUBPACKETS`].
+    ///
+    /// [`DEFAULT_MAX_SUBPACKETS`]: crate::parse::DEFAULT_MAX_SUBPACKETS
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::{Error, Packet};
+    /// use openpgp::parse::{Parse, PacketParserResult, PacketParserBuilder};
+    ///
+    /// // A signature with a handful of subpackets.
+    /// let sig_data: &[u8] = // ...
+    /// #    include_bytes!("../../tests/data/messages/a-cypherpunks-manifesto.txt.ed25519.sig");
+    /// let ppr = PacketParserBuilder::from_bytes(sig_data)?
+    ///     .max_subpackets(1)
+    ///     .build()?;
+    /// if let PacketParserResult::Some(pp) = ppr {
+    ///     if let Packet::Unknown(u) = &pp.packet {
+    ///         assert_eq!(&Error::TooManySubpackets(1),
+    ///                    u.error().downcast_ref().unwrap());
+    ///     } else {
+    ///         unreachable!();
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn max_subpackets(mut self, value: u32) -> Self {
+        self.settings.max_subpackets = value;
+        self
+    }
+
     /// Causes `PacketParser::build()` to buffer any unread content.
     ///
     /// The unread content can be accessed using [`Literal::body`],
@@ -348,6 +519,34 @@ impl<'a> PacketParserBuilder<'a> {
         self
     }
 
+    /// Controls whether diagnostics are collected.
+    ///
+    /// If enabled, the `PacketParser` records non-fatal observations
+    /// about the packets, like the use of weak algorithms.  See the
+    /// [`diagnostics`] module for details.
+    ///
+    ///   [`diagnostics`]: crate::parse::diagnostics
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::parse::{Parse, PacketParserBuilder};
+    ///
+    /// let message_data = b"\xcb\x12t\x00\x00\x00\x00\x00Hello world.";
+    /// let pp = PacketParserBuilder::from_bytes(message_data)?
+    ///     .collect_diagnostics(true)
+    ///     .build()?
+    ///     .expect("One packet, not EOF");
+    /// assert!(pp.diagnostics().is_empty());
+    /// # Ok(()) }
+    /// ```
+    pub fn collect_diagnostics(mut self, enable: bool) -> Self {
+        self.settings.collect_diagnostics = enable;
+        self
+    }
+
     /// Controls dearmoring.
     ///
     /// By default, if the input does not appear to be plain binary
@@ -439,52 +638,20 @@ impl<'a> PacketParserBuilder<'a> {
     ///     ppr = pp.recurse()?.1;
     /// }
     /// # Ok(()) }
-    #[allow(clippy::redundant_pattern_matching)]
-    pub fn build(mut self)
+    pub fn build(self)
         -> Result<PacketParserResult<'a>>
         where Self: 'a
     {
         let state = PacketParserState::new(self.settings);
 
-        let dearmor_mode = match self.dearmor {
-            Dearmor::Enabled(mode) => Some(mode),
-            Dearmor::Disabled => None,
-            Dearmor::Auto(mode) => {
-                if self.bio.eof() {
-                    None
-                } else {
-                    let mut reader = buffered_reader::Dup::with_cookie(
-                        self.bio, Cookie::default());
-                    let header = packet::Header::parse(&mut reader);
-                    self.bio = Box::new(reader).into_inner().unwrap();
-                    if let Ok(header) = header {
-                        if let Err(_) = header.valid(false) {
-                            // Invalid header: better try an ASCII armor
-                            // decoder.
-                            Some(mode)
-                        } else {
-                            None
-                        }
-                    } else {
-                        // Failed to parse the header: better try an ASCII
-                        // armor decoder.
-                        Some(mode)
-                    }
-                }
-            }
-        };
-
-        if let Some(mode) = dearmor_mode {
-            // Add a top-level filter so that it is peeled off when
-            // the packet parser is finished.  We use level -2 for that.
-            self.bio =
-                armor::Reader::from_cookie_reader_csft(self.bio, Some(mode),
-                    Cookie::new(ARMOR_READER_LEVEL), self.csf_transformation)
-                .into_boxed();
-        }
+        // Add a top-level filter so that it is peeled off when the
+        // packet parser is finished.  We use level -2 for that.
+        let (bio, _) = self.dearmor.apply(
+            self.bio, Cookie::new(ARMOR_READER_LEVEL),
+            self.csf_transformation)?;
 
         // Parse the first packet.
-        match PacketParser::parse(Box::new(self.bio), state, vec![ 0 ])? {
+        match PacketParser::parse(Box::new(bio), state, vec![ 0 ])? {
             ParserResult::Success(mut pp) => {
                 // We successfully parsed the first packet's header.
                 pp.state.message_validator.push(
@@ -545,4 +712,21 @@ mod tests {
             .build();
         assert_match!(Ok(PacketParserResult::Some(ref _pp)) = ppr);
     }
+
+    #[test]
+    fn armor_with_bom() {
+        let msg = crate::tests::message("a-cypherpunks-manifesto.txt.ed25519.sig");
+        let mut data = b"\xef\xbb\xbf".to_vec();
+        data.extend_from_slice(msg);
+
+        let ppr = PacketParserBuilder::from_bytes(&data).unwrap()
+            .dearmor(Dearmor::Auto(Default::default()))
+            .build();
+        assert_match!(Ok(PacketParserResult::Some(ref _pp)) = ppr);
+
+        let ppr = PacketParserBuilder::from_bytes(&data).unwrap()
+            .dearmor(Dearmor::Enabled(Default::default()))
+            .build();
+        assert_match!(Ok(PacketParserResult::Some(ref _pp)) = ppr);
+    }
 }
@@ -1,12 +1,17 @@
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::io;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
 
 use crate::{
+    Error,
     Result,
     Packet,
     PacketPile,
+    crypto::hash::Digest,
+    packet::Tag,
+    types::HashAlgorithm,
 };
 use crate::parse::{
     PacketParserBuilder,
@@ -190,6 +195,16 @@ pub struct PacketPileParser<'a> {
 
     /// The packet pile that has been assembled so far.
     pile: PacketPile,
+
+    /// If set, the algorithm used to hash the containers' contents.
+    digest_algo: Option<HashAlgorithm>,
+
+    /// The containers' content digests, indexed by path.
+    digests: BTreeMap<Vec<usize>, Vec<u8>>,
+
+    /// The maximum amount of content buffered to hash a container
+    /// that we descend into.
+    max_hashed_container_size: u32,
 }
 assert_send_and_sync!(PacketPileParser<'_>);
 
@@ -260,6 +275,10 @@ impl<'a> PacketPileParser<'a> {
         Ok(PacketPileParser {
             pile: Default::default(),
             ppr,
+            digest_algo: None,
+            digests: Default::default(),
+            max_hashed_container_size:
+                crate::parse::DEFAULT_MAX_HASHED_CONTAINER_SIZE,
         })
     }
 
@@ -333,21 +352,7 @@ impl<'a> PacketPileParser<'a> {
     /// # Ok(()) }
     /// ```
     pub fn recurse(&mut self) -> Result<()> {
-        match self.ppr.take() {
-            PacketParserResult::Some(pp) => {
-                let recursion_depth = pp.recursion_depth();
-                let (packet, ppr) = pp.recurse()?;
-                self.insert_packet(
-                    packet,
-                    recursion_depth as isize);
-                self.ppr = ppr;
-            }
-            eof @ PacketParserResult::EOF(_) => {
-                self.ppr = eof;
-            }
-        }
-
-        Ok(())
+        self.descend(true)
     }
 
     /// Finishes parsing the current packet and starts parsing the
@@ -386,10 +391,89 @@ impl<'a> PacketPileParser<'a> {
     /// # Ok(()) }
     /// ```
     pub fn next(&mut self) -> Result<()> {
+        self.descend(false)
+    }
+
+    /// Finishes parsing the current packet and starts parsing the
+    /// next one, recursing into containers if `descend` is true.
+    ///
+    /// `ppp.descend(true)` is equivalent to [`recurse()`], and
+    /// `ppp.descend(false)` is equivalent to [`next()`].  This makes
+    /// it convenient to decide whether to look inside a container
+    /// based on the packet at hand, for instance, to skip over
+    /// encrypted data while still looking inside compressed data.
+    ///
+    /// If [`hash_containers`] has been used to enable content
+    /// hashing, and the current packet is a container, then this
+    /// also computes the digest over the container's unread content
+    /// before advancing.  When descending, the content is buffered
+    /// in memory to compute the digest; otherwise, it is streamed.
+    /// If the buffered content exceeds the limit set using
+    /// [`max_hashed_container_size`], this fails with
+    /// `Error::PacketTooLarge`.
+    ///
+    ///   [`recurse()`]: PacketPileParser::recurse()
+    ///   [`next()`]: PacketPileParser::next()
+    ///   [`hash_containers`]: PacketPileParser::hash_containers()
+    ///   [`max_hashed_container_size`]: PacketPileParser::max_hashed_container_size()
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::Packet;
+    /// use openpgp::parse::{Parse, PacketPileParser};
+    ///
+    /// // Parse a message.
+    /// let message_data: &[u8] = // ...
+    /// #    include_bytes!("../../tests/data/messages/compressed-data-algo-0.pgp");
+    /// let mut ppp = PacketPileParser::from_bytes(message_data)?;
+    /// while let Ok(pp) = ppp.as_ref() {
+    ///     // Look inside compressed data, but don't descend into
+    ///     // anything else.
+    ///     let descend = matches!(pp.packet, Packet::CompressedData(_));
+    ///     ppp.descend(descend)?;
+    /// }
+    ///
+    /// let pile = ppp.finish();
+    /// assert!(pile.path_ref(&[0, 0]).is_some());
+    /// # Ok(()) }
+    /// ```
+    pub fn descend(&mut self, descend: bool) -> Result<()> {
         match self.ppr.take() {
-            PacketParserResult::Some(pp) => {
+            PacketParserResult::Some(mut pp) => {
+                if let Some(algo) = self.digest_algo {
+                    if is_container(pp.packet.tag()) {
+                        let mut ctx = algo.context()?;
+                        if descend {
+                            // We need to keep the content around so
+                            // that we can recurse into it.
+                            let limit = self.max_hashed_container_size;
+                            let len = pp.data(limit as usize + 1)?.len();
+                            if len > limit as usize {
+                                let tag = pp.packet.tag();
+                                // Let the caller skip the container.
+                                self.ppr = PacketParserResult::Some(pp);
+                                return Err(Error::PacketTooLarge(
+                                    tag, len.try_into().unwrap_or(u32::MAX),
+                                    limit).into());
+                            }
+                            ctx.update(pp.data(len)?);
+                        } else {
+                            io::copy(&mut pp, &mut ctx)?;
+                        }
+                        self.digests.insert(pp.path().to_vec(),
+                                            ctx.into_digest()?);
+                    }
+                }
+
                 let recursion_depth = pp.recursion_depth();
-                let (packet, ppr) = pp.next()?;
+                let (packet, ppr) = if descend {
+                    pp.recurse()?
+                } else {
+                    pp.next()?
+                };
                 self.insert_packet(
                     packet,
                     recursion_depth as isize);
@@ -403,6 +487,84 @@ impl<'a> PacketPileParser<'a> {
         Ok(())
     }
 
+    /// Enables or disables hashing of the containers' contents.
+    ///
+    /// If `algo` is `Some`, then whenever the parser advances past a
+    /// container (a compressed data packet, or an encrypted data
+    /// packet), the container's unread content is hashed using
+    /// `algo`.  The digest can then be retrieved using
+    /// [`container_digest`].  This is useful to, for instance,
+    /// deduplicate messages, or to look up opaque content in a
+    /// database of known content.
+    ///
+    /// Note: the digest is computed over the container's content as
+    /// it is presented by the [`PacketParser`].  That is, for
+    /// compressed data packets, the digest is computed over the
+    /// decompressed content, for encrypted data packets that have
+    /// been decrypted, it is computed over the plaintext, and for
+    /// encrypted data packets that have not been decrypted, over the
+    /// ciphertext.  Further, content that has already been read is not
+    /// included.
+    ///
+    ///   [`container_digest`]: PacketPileParser::container_digest()
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::parse::{Parse, PacketPileParser};
+    /// use openpgp::types::HashAlgorithm;
+    ///
+    /// // Parse a message.
+    /// let message_data: &[u8] = // ...
+    /// #    include_bytes!("../../tests/data/messages/compressed-data-algo-0.pgp");
+    /// let mut ppp = PacketPileParser::from_bytes(message_data)?;
+    /// ppp.hash_containers(Some(HashAlgorithm::SHA256));
+    /// while ppp.is_some() {
+    ///     // Don't look inside the containers, just hash them.
+    ///     ppp.next()?;
+    /// }
+    ///
+    /// let digest = ppp.container_digest(&[0]).expect("is a container");
+    /// assert_eq!(digest.len(), 32);
+    /// # Ok(()) }
+    /// ```
+    pub fn hash_containers(&mut self, algo: Option<HashAlgorithm>) {
+        self.digest_algo = algo;
+    }
+
+    /// Sets the maximum amount of content buffered to hash a
+    /// container.
+    ///
+    /// When descending into a container while hashing is enabled
+    /// using [`hash_containers`], the container's content is buffered
+    /// in memory.  Containers whose content exceeds `limit` bytes
+    /// make [`descend`] and [`recurse`] fail.  Containers that are
+    /// skipped are streamed, and are not subject to this limit.
+    ///
+    /// The default is [`DEFAULT_MAX_HASHED_CONTAINER_SIZE`].
+    ///
+    ///   [`hash_containers`]: PacketPileParser::hash_containers()
+    ///   [`descend`]: PacketPileParser::descend()
+    ///   [`recurse`]: PacketPileParser::recurse()
+    ///   [`DEFAULT_MAX_HASHED_CONTAINER_SIZE`]: crate::parse::DEFAULT_MAX_HASHED_CONTAINER_SIZE
+    pub fn max_hashed_container_size(&mut self, limit: u32) {
+        self.max_hashed_container_size = limit;
+    }
+
+    /// Returns the digest of the content of the container at `path`.
+    ///
+    /// This returns `None` if hashing was not enabled using
+    /// [`hash_containers`] when the parser advanced past the packet
+    /// at `path`, if the packet is not a container, or if there is no
+    /// such packet.
+    ///
+    ///   [`hash_containers`]: PacketPileParser::hash_containers()
+    pub fn container_digest(&self, path: &[usize]) -> Option<&[u8]> {
+        self.digests.get(path).map(|d| &d[..])
+    }
+
     /// Returns the current packet's recursion depth.
     ///
     /// A top-level packet has a recursion depth of 0.  Packets in a
@@ -508,6 +670,11 @@ impl<'a> PacketPileParser<'a> {
     }
 }
 
+/// Returns whether packets with the given tag contain other packets.
+fn is_container(tag: Tag) -> bool {
+    matches!(tag, Tag::CompressedData | Tag::SEIP | Tag::AED | Tag::SED)
+}
+
 #[test]
 fn test_recurse() -> Result<()> {
     let mut count = 0;
@@ -538,6 +705,67 @@ fn test_next() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn container_digests() -> Result<()> {
+    use crate::packet::Body;
+
+    let msg = crate::tests::message("compressed-data-algo-0.pgp");
+
+    // Compute the reference digest over the container's body.
+    let mut ppr = PacketParserBuilder::from_bytes(msg)?
+        .buffer_unread_content()
+        .build()?;
+    let mut expected = None;
+    while let PacketParserResult::Some(pp) = ppr {
+        let (packet, next_ppr) = pp.next()?;
+        if let Packet::CompressedData(c) = packet {
+            if let Body::Processed(body) = c.body() {
+                let mut ctx = HashAlgorithm::SHA256.context()?;
+                ctx.update(body);
+                expected = Some(ctx.into_digest()?);
+            }
+        }
+        ppr = next_ppr;
+    }
+    let expected = expected.expect("has a compressed data packet");
+
+    for descend in [false, true] {
+        let mut ppp = PacketPileParser::from_bytes(msg)?;
+        ppp.hash_containers(Some(HashAlgorithm::SHA256));
+        let mut count = 0;
+        while ppp.is_some() {
+            count += 1;
+            ppp.descend(descend)?;
+        }
+        assert_eq!(count, if descend { 2 } else { 1 });
+        assert_eq!(ppp.container_digest(&[0]), Some(&expected[..]));
+        // Only containers are hashed.
+        assert_eq!(ppp.container_digest(&[0, 0]), None);
+
+        let pile = ppp.finish();
+        assert_eq!(pile.path_ref(&[0, 0]).is_some(), descend);
+    }
+
+    // Containers that we descend into are limited in size.
+    let mut ppp = PacketPileParser::from_bytes(msg)?;
+    ppp.hash_containers(Some(HashAlgorithm::SHA256));
+    ppp.max_hashed_container_size(4);
+    assert!(ppp.recurse().is_err());
+    // But they can be skipped.
+    assert!(ppp.is_some());
+    ppp.next()?;
+    assert_eq!(ppp.container_digest(&[0]), Some(&expected[..]));
+
+    // Hashing is off by default.
+    let mut ppp = PacketPileParser::from_bytes(msg)?;
+    while ppp.is_some() {
+        ppp.recurse()?;
+    }
+    assert_eq!(ppp.container_digest(&[0]), None);
+
+    Ok(())
+}
+
 /// Check that we can use the read interface to stream the contents of
 /// a packet.
 #[cfg(feature = "compression-deflate")]
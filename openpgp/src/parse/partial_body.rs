@@ -1,9 +1,9 @@
 
 use std::cmp;
-use std::io;
+use std::io::{self, Read};
 use std::io::{Error, ErrorKind};
 
-use buffered_reader::{buffered_reader_generic_read_impl, BufferedReader};
+use buffered_reader::{buffered_reader_generic_read_impl, BufferedReader, Limitor};
 
 use crate::{vec_resize, vec_truncate};
 use crate::packet::header::BodyLength;
@@ -13,18 +13,22 @@ const TRACE : bool = false;
 
 
 /// A `BufferedReader` that transparently handles OpenPGP's chunking
-/// scheme.  This implicitly implements a limitor.
+/// scheme.
 pub(crate) struct BufferedReaderPartialBodyFilter<T: BufferedReader<Cookie>> {
-    // The underlying reader.
-    reader: T,
-
-    // The amount of unread data in the current partial body chunk.
-    // That is, if `buffer` contains 10 bytes and
-    // `partial_body_length` is 20, then there are 30 bytes of
-    // unprocessed (unconsumed) data in the current chunk.
-    partial_body_length: u32,
+    // The underlying reader, limited to the current partial body
+    // chunk.
+    //
+    // The limitor's remaining budget is the amount of unread data
+    // in the current chunk.  That is, if `buffer` contains 10 bytes
+    // and the limitor has 20 bytes left, then there are 30 bytes of
+    // unprocessed (unconsumed) data in the current chunk.  When we
+    // read the next chunk's header, we extend the limit by the
+    // chunk's length.
+    reader: Limitor<T, Cookie>,
     // Whether this is the last partial body chuck.
     last: bool,
+    // The total length of the chunks whose headers have been read.
+    chunks_len: u64,
 
     // Sometimes we have to double buffer.  This happens if the caller
     // requests X bytes and that chunk straddles a partial body length
@@ -55,12 +59,12 @@ impl<T: BufferedReader<Cookie>> std::fmt::Debug
         for BufferedReaderPartialBodyFilter<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_struct("BufferedReaderPartialBodyFilter")
-            .field("partial_body_length", &self.partial_body_length)
+            .field("partial_body_length", &self.reader.remaining())
             .field("last", &self.last)
             .field("hash headers", &self.hash_headers)
             .field("buffer (bytes left)",
                    &self.buffer.as_ref().map(|buffer| buffer.len()))
-            .field("reader", &self.reader)
+            .field("reader", self.reader.get_ref().expect("inner"))
             .finish()
     }
 }
@@ -72,9 +76,10 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
     pub fn with_cookie(reader: T, partial_body_length: u32,
                        hash_headers: bool, cookie: Cookie) -> Self {
         BufferedReaderPartialBodyFilter {
-            reader,
-            partial_body_length,
+            reader: Limitor::with_cookie(reader, partial_body_length.into(),
+                                         Cookie::default()),
             last: false,
+            chunks_len: partial_body_length.into(),
             buffer: None,
             cursor: 0,
             unused_buffers: Vec::with_capacity(2),
@@ -88,9 +93,9 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
         tracer!(TRACE, "PBF::do_fill_buffer", self.cookie.level.unwrap_or(0));
         t!("BufferedReaderPartialBodyFilter::do_fill_buffer(\
             amount: {}) (partial body length: {}, last: {})",
-           amount, self.partial_body_length, self.last);
+           amount, self.reader.remaining(), self.last);
 
-        if self.last && self.partial_body_length == 0 {
+        if self.last && self.reader.remaining() == 0 {
             // We reached the end.  Avoid fruitlessly copying data
             // over and over again trying to buffer more data.
             return Ok(());
@@ -128,12 +133,12 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
         loop {
             let to_read = cmp::min(
                 // Data in current chunk.
-                self.partial_body_length as usize,
+                self.reader.remaining(),
                 // Space left in the buffer.
-                buffer.len() - amount_buffered);
+                (buffer.len() - amount_buffered) as u64) as usize;
             t!("Trying to buffer {} bytes \
                 (partial body length: {}; space: {})",
-               to_read, self.partial_body_length,
+               to_read, self.reader.remaining(),
                buffer.len() - amount_buffered);
             if to_read > 0 {
                 let result = self.reader.read(
@@ -142,7 +147,6 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
                     Ok(did_read) => {
                         t!("Buffered {} bytes", did_read);
                         amount_buffered += did_read;
-                        self.partial_body_length -= did_read as u32;
 
                         if did_read < to_read {
                             // Short read => EOF.  We're done.
@@ -164,37 +168,60 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
                 break;
             }
 
-            // Read the next partial body length header.
-            assert_eq!(self.partial_body_length, 0);
+            // Read the next partial body length header.  The header
+            // is not part of the chunk, so we read it from the reader
+            // below the limitor.
+            assert_eq!(self.reader.remaining(), 0);
+            let reader = self.reader.get_mut().expect("inner");
 
             // Disable hashing, if necessary.
             if ! self.hash_headers {
-                if let Some(level) = self.reader.cookie_ref().level {
-                    Cookie::hashing(
-                        &mut self.reader, Hashing::Disabled, level);
+                if let Some(level) = reader.cookie_ref().level {
+                    Cookie::hashing(reader, Hashing::Disabled, level);
                 }
             }
 
             t!("Reading next chunk's header (hashing: {}, level: {:?})",
-               self.hash_headers, self.reader.cookie_ref().level);
-            let body_length = BodyLength::parse_new_format(&mut self.reader);
+               self.hash_headers, reader.cookie_ref().level);
+            let header = if self.cookie.partial_body_headers.is_some() {
+                // Save the header as is.
+                reader.data(5).ok().and_then(|d| {
+                    let len = match *d.first()? {
+                        0..=191 | 224..=254 => 1,
+                        192..=223 => 2,
+                        255 => 5,
+                    };
+                    d.get(..len).map(|h| h.to_vec())
+                })
+            } else {
+                None
+            };
+            let body_length = BodyLength::parse_new_format(reader);
 
             if ! self.hash_headers {
-                if let Some(level) = self.reader.cookie_ref().level {
-                    Cookie::hashing(
-                        &mut self.reader, Hashing::Enabled, level);
+                if let Some(level) = reader.cookie_ref().level {
+                    Cookie::hashing(reader, Hashing::Enabled, level);
                 }
             }
 
+            if let (Some(headers), Some(header), Ok(body_length)) =
+                (self.cookie.partial_body_headers.as_mut(), header,
+                 body_length.as_ref())
+            {
+                headers.push((self.chunks_len as usize, *body_length, header));
+            }
+
             match body_length {
                 Ok(BodyLength::Full(len)) => {
                     t!("Last chunk: {} bytes", len);
                     self.last = true;
-                    self.partial_body_length = len;
+                    self.chunks_len += u64::from(len);
+                    self.reader.extend(len.into());
                 },
                 Ok(BodyLength::Partial(len)) => {
                     t!("Next chunk: {} bytes", len);
-                    self.partial_body_length = len;
+                    self.chunks_len += u64::from(len);
+                    self.reader.extend(len.into());
                 },
                 Ok(BodyLength::Indeterminate) => {
                     // A new format packet can't return Indeterminate.
@@ -265,7 +292,7 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
 
             assert_eq!(self.cursor, 0);
 
-            if amount <= self.partial_body_length as usize
+            if amount as u64 <= self.reader.remaining()
                 || /* Short read.  */ self.last {
                 // The amount of data that the caller requested does
                 // not exceed the amount of data in the current chunk.
@@ -280,23 +307,14 @@ impl<T: BufferedReader<Cookie>> BufferedReaderPartialBodyFilter<T> {
                 } else {
                     self.reader.data(amount)
                 };
-                match result {
-                    Ok(buffer) => {
-                        let amount_buffered =
-                            std::cmp::min(buffer.len(),
-                                          self.partial_body_length as usize);
-                        if hard && amount_buffered < amount {
-                            return Err(Error::new(ErrorKind::UnexpectedEof,
-                                                  "unexpected EOF"));
-                        } else {
-                            if and_consume {
-                                self.partial_body_length -=
-                                    cmp::min(amount, amount_buffered) as u32;
-                            }
-                            return Ok(&buffer[..amount_buffered]);
-                        }
-                    },
-                    Err(err) => return Err(err),
+                // The limitor makes sure that we don't read past
+                // the end of the current chunk.
+                let buffer = result?;
+                if hard && buffer.len() < amount {
+                    return Err(Error::new(ErrorKind::UnexpectedEof,
+                                          "unexpected EOF"));
+                } else {
+                    return Ok(buffer);
                 }
             } else {
                 // `amount` crosses a partial body length boundary.
@@ -350,9 +368,7 @@ impl<T: BufferedReader<Cookie>> BufferedReader<Cookie>
         if let Some(ref buffer) = self.buffer {
             &buffer[self.cursor..]
         } else {
-            let buf = self.reader.buffer();
-            &buf[..cmp::min(buf.len(),
-                            self.partial_body_length as usize)]
+            self.reader.buffer()
         }
     }
 
@@ -379,8 +395,6 @@ impl<T: BufferedReader<Cookie>> BufferedReader<Cookie>
         } else {
             // Since we don't have a buffer, just pass through to the
             // underlying reader.
-            assert!(amount <= self.partial_body_length as usize);
-            self.partial_body_length -= amount as u32;
             self.reader.consume(amount)
         }
     }
@@ -394,20 +408,21 @@ impl<T: BufferedReader<Cookie>> BufferedReader<Cookie>
     }
 
     fn consummated(&mut self) -> bool {
-        self.partial_body_length == 0 && self.last
+        self.reader.remaining() == 0 && self.last
     }
 
     fn get_mut(&mut self) -> Option<&mut dyn BufferedReader<Cookie>> {
-        Some(&mut self.reader)
+        // Skip the limitor, it is an implementation detail.
+        self.reader.get_mut()
     }
 
     fn get_ref(&self) -> Option<&dyn BufferedReader<Cookie>> {
-        Some(&self.reader)
+        self.reader.get_ref()
     }
 
     fn into_inner<'b>(self: Box<Self>) -> Option<Box<dyn BufferedReader<Cookie> + 'b>>
             where Self: 'b {
-        Some(self.reader.into_boxed())
+        self.reader.into_boxed().into_inner()
     }
 
     fn cookie_set(&mut self, cookie: Cookie) -> Cookie {
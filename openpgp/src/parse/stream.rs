@@ -102,6 +102,7 @@
 //! # Ok(()) }
 //! ```
 use std::cmp;
+use std::fmt;
 use std::io;
 use std::path::Path;
 use std::time;
@@ -110,14 +111,19 @@ use buffered_reader::BufferedReader;
 use crate::{
     Error,
     Fingerprint,
+    KeyID,
     types::{
         AEADAlgorithm,
         CompressionAlgorithm,
+        HashAlgorithm,
+        PublicKeyAlgorithm,
         RevocationStatus,
+        SignatureType,
         SymmetricAlgorithm,
     },
     packet::{
         key,
+        Literal,
         OnePassSig,
         PKESK,
         SKESK,
@@ -128,8 +134,9 @@ use crate::{
     packet,
     packet::Signature,
     cert::prelude::*,
-    crypto::SessionKey,
+    crypto::{EscrowedSessionKey, SessionKey},
     policy::Policy,
+    utils::Progress,
 };
 use crate::parse::{
     Cookie,
@@ -165,6 +172,95 @@ const TRACE_INDENT: isize = 5;
 ///   [`DecryptorBuilder::buffer_size`]: DecryptorBuilder::buffer_size()
 pub const DEFAULT_BUFFER_SIZE: usize = 25 * 1024 * 1024;
 
+/// How many encrypted session key packets a message may contain.
+///
+/// Each [`PKESK`] and [`SKESK`] packet may be tried with every secret
+/// key and password.  A hostile message containing thousands of them
+/// can therefore cause a lot of work.
+///
+/// The default can be changed using [`DecryptorBuilder::max_esks`].
+///
+///   [`PKESK`]: crate::packet::PKESK
+///   [`SKESK`]: crate::packet::SKESK
+///   [`DecryptorBuilder::max_esks`]: DecryptorBuilder::max_esks()
+pub const DEFAULT_MAX_ESKS: usize = 256;
+
+/// How many signature layers a message may contain.
+///
+/// Every [`OnePassSig`] packet, and every [`Signature`] packet
+/// preceding the literal data, opens a signature layer.  Each of them
+/// requires hashing the message, and verifying a signature.
+///
+/// The default can be changed using
+/// [`DecryptorBuilder::max_signature_layers`].
+///
+///   [`OnePassSig`]: crate::packet::OnePassSig
+///   [`Signature`]: crate::packet::Signature
+///   [`DecryptorBuilder::max_signature_layers`]: DecryptorBuilder::max_signature_layers()
+pub const DEFAULT_MAX_SIGNATURE_LAYERS: usize = 64;
+
+/// A limit imposed on messages by the streaming [`Decryptor`] and
+/// [`Verifier`].
+///
+/// If a message exceeds one of them, processing it fails with
+/// [`Error::MessageLimitExceeded`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Limit {
+    /// The number of encrypted session key packets.
+    ///
+    /// See [`DecryptorBuilder::max_esks`].
+    ///
+    ///   [`DecryptorBuilder::max_esks`]: DecryptorBuilder::max_esks()
+    EncryptedSessionKeys,
+
+    /// The number of signature layers.
+    ///
+    /// See [`DecryptorBuilder::max_signature_layers`].
+    ///
+    ///   [`DecryptorBuilder::max_signature_layers`]: DecryptorBuilder::max_signature_layers()
+    SignatureLayers,
+
+    /// The number of nested containers.
+    ///
+    /// See [`DecryptorBuilder::max_nesting_depth`].
+    ///
+    ///   [`DecryptorBuilder::max_nesting_depth`]: DecryptorBuilder::max_nesting_depth()
+    NestingDepth,
+}
+assert_send_and_sync!(Limit);
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::EncryptedSessionKeys =>
+                f.write_str("encrypted session key packets"),
+            Limit::SignatureLayers =>
+                f.write_str("signature layers"),
+            Limit::NestingDepth =>
+                f.write_str("nested containers"),
+        }
+    }
+}
+
+/// The limits enforced by the `Decryptor`.
+#[derive(Clone, Copy, Debug)]
+struct Limits {
+    max_esks: usize,
+    max_signature_layers: usize,
+    max_nesting_depth: u8,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_esks: DEFAULT_MAX_ESKS,
+            max_signature_layers: DEFAULT_MAX_SIGNATURE_LAYERS,
+            max_nesting_depth: crate::parse::DEFAULT_MAX_RECURSION_DEPTH,
+        }
+    }
+}
+
 /// Result of a signature verification.
 ///
 /// A signature verification is either successful yielding a
@@ -203,6 +299,16 @@ pub type VerificationResult<'a> =
 ///
 ///   - The signature was generated by the signing key.
 ///
+/// Because the certificate and the signing key are evaluated as of
+/// the signature's creation time, signatures made by keys that have
+/// since been rotated out, i.e. keys that have expired, or that have
+/// been softly revoked (e.g., because they have been superseded),
+/// are still considered good.  Hard revocations, on the other hand,
+/// are always in effect.  The time at which the signing key has been
+/// evaluated is returned by [`GoodChecksum::key_validity_time`].
+///
+///   [`GoodChecksum::key_validity_time`]: GoodChecksum::key_validity_time()
+///
 /// **Note**: This doesn't mean that the key that generated the
 /// signature is in anyway trustworthy in the sense that it
 /// belongs to the person or entity that the user thinks it
@@ -226,6 +332,22 @@ pub struct GoodChecksum<'a> {
 }
 assert_send_and_sync!(GoodChecksum<'_>);
 
+impl GoodChecksum<'_> {
+    /// Returns the time at which the signing key has been evaluated.
+    ///
+    /// This is the signature's creation time.  The signing key and
+    /// the certificate were valid at that time, but they may have
+    /// expired or been superseded since.  To check whether the key
+    /// is still valid, evaluate it at the current time, e.g. using
+    /// [`ValidAmalgamation::cert`] and [`ValidCert::with_policy`].
+    ///
+    ///   [`ValidAmalgamation::cert`]: crate::cert::amalgamation::ValidAmalgamation::cert()
+    ///   [`ValidCert::with_policy`]: crate::cert::ValidCert::with_policy()
+    pub fn key_validity_time(&self) -> time::SystemTime {
+        self.ka.time()
+    }
+}
+
 /// A bad signature.
 ///
 /// Represents the result of an unsuccessful signature verification.
@@ -271,6 +393,11 @@ pub enum VerificationError<'a> {
         error: anyhow::Error,
     },
     /// Bad key (have a key, but it is not alive, etc.)
+    ///
+    /// If the key or the certificate expired before the signature
+    /// was made, but the signature is otherwise good, `error` is an
+    /// [`Error::Expired`] with the expiration time.  See
+    /// [`VerificationError::key_expired`].
     BadKey {
         /// The signature.
         sig: &'a Signature,
@@ -282,6 +409,10 @@ pub enum VerificationError<'a> {
         error: anyhow::Error,
     },
     /// Bad signature (have a valid key, but the signature didn't check out)
+    ///
+    /// If the signature expired, but is otherwise good, `error` is
+    /// an [`Error::Expired`] with the expiration time.  See
+    /// [`VerificationError::signature_expired`].
     BadSignature {
         /// The signature.
         sig: &'a Signature,
@@ -295,6 +426,40 @@ pub enum VerificationError<'a> {
 }
 assert_send_and_sync!(VerificationError<'_>);
 
+impl<'a> VerificationError<'a> {
+    /// Returns when the signature expired, if the signature is good
+    /// but expired.
+    ///
+    /// This corresponds to GnuPG's `EXPSIG` status.
+    pub fn signature_expired(&self) -> Option<time::SystemTime> {
+        match self {
+            VerificationError::BadSignature { error, .. } =>
+                match error.downcast_ref::<Error>() {
+                    Some(Error::Expired(t)) => Some(*t),
+                    _ => None,
+                },
+            _ => None,
+        }
+    }
+
+    /// Returns when the signing key expired, if the signature is
+    /// good but the key was expired when the signature was made.
+    ///
+    /// This corresponds to GnuPG's `EXPKEYSIG` status.  Note that
+    /// signatures made by keys that were alive when the signature
+    /// was made, but have expired since, are good.
+    pub fn key_expired(&self) -> Option<time::SystemTime> {
+        match self {
+            VerificationError::BadKey { error, .. } =>
+                match error.downcast_ref::<Error>() {
+                    Some(Error::Expired(t)) => Some(*t),
+                    _ => None,
+                },
+            _ => None,
+        }
+    }
+}
+
 impl<'a> std::fmt::Display for VerificationError<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         use self::VerificationError::*;
@@ -337,6 +502,367 @@ impl<'a> From<VerificationError<'a>> for Error {
     }
 }
 
+/// An owned summary of a [`VerificationResult`].
+///
+/// Unlike [`VerificationResult`], this does not borrow the signature
+/// or the certificate, so it can be collected, stored, and reported
+/// after the verifier is gone.  This is useful for tools that need to
+/// emit machine-readable verification results.
+///
+/// The [`Display`] implementation emits lines modeled after GnuPG's
+/// `--status-fd` interface (`GOODSIG`, `EXPSIG`, `EXPKEYSIG`,
+/// `KEYEXPIRED`, `VALIDSIG`, `BADSIG`, `ERRSIG`, and `NO_PUBKEY`),
+/// without the `[GNUPG:] ` prefix.
+///
+///   [`Display`]: std::fmt::Display
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> sequoia_openpgp::Result<()> {
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::parse::stream::{MessageLayer, MessageStructure};
+/// use openpgp::parse::stream::SignatureStatus;
+///
+/// fn report(structure: &MessageStructure) -> Vec<SignatureStatus> {
+///     let mut statuses = Vec::new();
+///     for layer in structure.iter() {
+///         if let MessageLayer::SignatureGroup { results } = layer {
+///             statuses.extend(results.iter().map(SignatureStatus::from));
+///         }
+///     }
+///     statuses
+/// }
+/// # Ok(()) }
+/// ```
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignatureStatus {
+    kind: SignatureStatusKind,
+    issuers: Vec<KeyHandle>,
+    key: Option<Fingerprint>,
+    cert: Option<Fingerprint>,
+    creation_time: Option<time::SystemTime>,
+    expiration_time: Option<time::SystemTime>,
+    key_expiration_time: Option<time::SystemTime>,
+    key_validity_time: Option<time::SystemTime>,
+    version: u8,
+    typ: SignatureType,
+    pk_algo: PublicKeyAlgorithm,
+    hash_algo: HashAlgorithm,
+    error: Option<String>,
+}
+assert_send_and_sync!(SignatureStatus);
+
+/// The outcome of a signature verification.
+///
+/// See [`SignatureStatus`].  The variants correspond to
+/// [`GoodChecksum`] and the variants of [`VerificationError`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SignatureStatusKind {
+    /// A good signature.
+    Good,
+    /// Malformed signature.
+    MalformedSignature,
+    /// Missing key.
+    MissingKey,
+    /// Unbound key.
+    UnboundKey,
+    /// Bad key.
+    BadKey,
+    /// Bad signature.
+    BadSignature,
+    /// A good, but expired signature.
+    ///
+    /// See [`VerificationError::signature_expired`].
+    ExpiredSignature,
+    /// A good signature made by an expired key.
+    ///
+    /// See [`VerificationError::key_expired`].
+    ExpiredKey,
+}
+assert_send_and_sync!(SignatureStatusKind);
+
+impl SignatureStatus {
+    /// Returns the outcome of the verification.
+    pub fn kind(&self) -> SignatureStatusKind {
+        self.kind
+    }
+
+    /// Returns the issuers named in the signature.
+    pub fn issuers(&self) -> &[KeyHandle] {
+        &self.issuers
+    }
+
+    /// Returns the signing key's fingerprint, if the key was found.
+    pub fn key(&self) -> Option<&Fingerprint> {
+        self.key.as_ref()
+    }
+
+    /// Returns the certificate's fingerprint, if the certificate was found.
+    pub fn cert(&self) -> Option<&Fingerprint> {
+        self.cert.as_ref()
+    }
+
+    /// Returns the signature's creation time, if any.
+    pub fn creation_time(&self) -> Option<time::SystemTime> {
+        self.creation_time
+    }
+
+    /// Returns the signature's expiration time, if any.
+    pub fn expiration_time(&self) -> Option<time::SystemTime> {
+        self.expiration_time
+    }
+
+    /// Returns the signing key's expiration time, if the key was
+    /// found and expires.
+    ///
+    /// If the kind is [`SignatureStatusKind::ExpiredKey`], this is
+    /// when the key or the certificate expired.
+    pub fn key_expiration_time(&self) -> Option<time::SystemTime> {
+        self.key_expiration_time
+    }
+
+    /// Returns the time at which the signing key has been evaluated,
+    /// if the key was found.
+    ///
+    /// See [`GoodChecksum::key_validity_time`].
+    ///
+    ///   [`GoodChecksum::key_validity_time`]: GoodChecksum::key_validity_time()
+    pub fn key_validity_time(&self) -> Option<time::SystemTime> {
+        self.key_validity_time
+    }
+
+    /// Returns the signature's version.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the signature's type.
+    pub fn typ(&self) -> SignatureType {
+        self.typ
+    }
+
+    /// Returns the signature's public key algorithm.
+    pub fn pk_algo(&self) -> PublicKeyAlgorithm {
+        self.pk_algo
+    }
+
+    /// Returns the signature's hash algorithm.
+    pub fn hash_algo(&self) -> HashAlgorithm {
+        self.hash_algo
+    }
+
+    /// Returns the reason why the verification failed, if it failed.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Returns whether the signature is good.
+    pub fn is_good(&self) -> bool {
+        self.kind == SignatureStatusKind::Good
+    }
+
+    fn new(kind: SignatureStatusKind, sig: &Signature,
+           ka: Option<&ValidErasedKeyAmalgamation<key::PublicParts>>,
+           cert: Option<Fingerprint>,
+           error: Option<String>)
+           -> Self
+    {
+        SignatureStatus {
+            kind,
+            issuers: sig.get_issuers(),
+            key: ka.map(|ka| ka.fingerprint()),
+            cert: ka.map(|ka| ka.cert().fingerprint()).or(cert),
+            creation_time: sig.signature_creation_time(),
+            expiration_time: sig.signature_expiration_time(),
+            key_expiration_time: ka.and_then(|ka| ka.key_expiration_time()),
+            key_validity_time: ka.map(|ka| ka.time()),
+            version: sig.version(),
+            typ: sig.typ(),
+            pk_algo: sig.pk_algo(),
+            hash_algo: sig.hash_algo(),
+            error,
+        }
+    }
+}
+
+impl<'a> From<&VerificationResult<'a>> for SignatureStatus {
+    fn from(r: &VerificationResult<'a>) -> Self {
+        use self::VerificationError::*;
+        use self::SignatureStatusKind as K;
+        match r {
+            Ok(GoodChecksum { sig, ka }) =>
+                SignatureStatus::new(K::Good, sig, Some(ka), None, None),
+            Err(MalformedSignature { sig, error }) =>
+                SignatureStatus::new(K::MalformedSignature, sig, None, None,
+                                     Some(error.to_string())),
+            Err(MissingKey { sig }) =>
+                SignatureStatus::new(K::MissingKey, sig, None, None, None),
+            Err(UnboundKey { sig, cert, error }) =>
+                SignatureStatus::new(K::UnboundKey, sig, None,
+                                     Some(cert.fingerprint()),
+                                     Some(error.to_string())),
+            Err(e @ BadKey { sig, ka, error }) =>
+                if let Some(t) = e.key_expired() {
+                    let mut s = SignatureStatus::new(
+                        K::ExpiredKey, sig, Some(ka), None,
+                        Some(error.to_string()));
+                    s.key_expiration_time = Some(t);
+                    s
+                } else {
+                    SignatureStatus::new(K::BadKey, sig, Some(ka), None,
+                                         Some(error.to_string()))
+                },
+            Err(e @ BadSignature { sig, ka, error }) => {
+                let kind = if e.signature_expired().is_some() {
+                    K::ExpiredSignature
+                } else {
+                    K::BadSignature
+                };
+                SignatureStatus::new(kind, sig, Some(ka), None,
+                                     Some(error.to_string()))
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::SignatureStatusKind as K;
+
+        let secs = |t: Option<time::SystemTime>| {
+            t.and_then(|t| t.duration_since(time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        };
+        let timestamp = secs(self.creation_time);
+        let keyid = self.key.as_ref().map(KeyID::from)
+            .or_else(|| self.issuers.get(0).map(KeyID::from))
+            .unwrap_or_else(KeyID::wildcard);
+        let cert = self.cert.as_ref().map(|fp| fp.to_hex())
+            .unwrap_or_else(|| "-".into());
+
+        let validsig = |f: &mut std::fmt::Formatter| {
+            let date = crate::fmt::time(
+                &self.creation_time.unwrap_or(time::UNIX_EPOCH));
+            let date = date.split('T').next().unwrap_or("-");
+            write!(f, "VALIDSIG {} {} {} {} {} 0 {} {} {:02X} {}",
+                   self.key.as_ref().map(|fp| fp.to_hex())
+                       .unwrap_or_else(|| "-".into()),
+                   date, timestamp, secs(self.expiration_time),
+                   self.version, u8::from(self.pk_algo),
+                   u8::from(self.hash_algo), u8::from(self.typ), cert)
+        };
+
+        match self.kind {
+            K::Good => {
+                writeln!(f, "GOODSIG {:X} {}", keyid, cert)?;
+                validsig(f)
+            },
+            K::ExpiredSignature => {
+                writeln!(f, "EXPSIG {:X} {}", keyid, cert)?;
+                validsig(f)
+            },
+            K::ExpiredKey => {
+                writeln!(f, "KEYEXPIRED {}", secs(self.key_expiration_time))?;
+                writeln!(f, "EXPKEYSIG {:X} {}", keyid, cert)?;
+                validsig(f)
+            },
+            K::BadSignature =>
+                write!(f, "BADSIG {:X} {}", keyid, cert),
+            K::MissingKey => {
+                writeln!(f, "ERRSIG {:X} {} {} {:02X} {} 9",
+                         keyid, u8::from(self.pk_algo),
+                         u8::from(self.hash_algo), u8::from(self.typ),
+                         timestamp)?;
+                write!(f, "NO_PUBKEY {:X}", keyid)
+            },
+            K::MalformedSignature | K::UnboundKey | K::BadKey =>
+                write!(f, "ERRSIG {:X} {} {} {:02X} {} 4",
+                       keyid, u8::from(self.pk_algo),
+                       u8::from(self.hash_algo), u8::from(self.typ),
+                       timestamp),
+        }
+    }
+}
+
+/// An owned summary of a [`MessageLayer`].
+///
+/// Like [`SignatureStatus`], this does not borrow from the verifier,
+/// so it can be stored and reported after the verifier is gone.
+///
+/// If the `serde` feature is enabled, this and [`SignatureStatus`]
+/// implement serde's `Serialize` and `Deserialize` traits.  This can
+/// be used to log verification results, and to later re-evaluate
+/// them without verifying the signatures again.  Fingerprints and Key
+/// IDs are serialized as their version and their hexadecimal
+/// representation, and algorithms and signature types using their
+/// numeric identifiers.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> sequoia_openpgp::Result<()> {
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::parse::stream::{MessageLayerStatus, MessageStructure};
+///
+/// fn report(structure: &MessageStructure) -> Vec<MessageLayerStatus> {
+///     structure.iter().map(MessageLayerStatus::from).collect()
+/// }
+/// # Ok(()) }
+/// ```
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MessageLayerStatus {
+    /// A compression container.
+    ///
+    /// See [`MessageLayer::Compression`].
+    Compression {
+        /// Compression algorithm used.
+        algo: CompressionAlgorithm,
+    },
+    /// An encryption container.
+    ///
+    /// See [`MessageLayer::Encryption`].
+    Encryption {
+        /// Symmetric algorithm used.
+        sym_algo: SymmetricAlgorithm,
+        /// AEAD algorithm used, if any.
+        aead_algo: Option<AEADAlgorithm>,
+    },
+    /// A signature group.
+    ///
+    /// See [`MessageLayer::SignatureGroup`].
+    SignatureGroup {
+        /// The results of the signature verifications.
+        results: Vec<SignatureStatus>,
+    },
+}
+assert_send_and_sync!(MessageLayerStatus);
+
+impl<'a> From<&MessageLayer<'a>> for MessageLayerStatus {
+    fn from(l: &MessageLayer<'a>) -> Self {
+        match l {
+            MessageLayer::Compression { algo } =>
+                MessageLayerStatus::Compression { algo: *algo },
+            MessageLayer::Encryption { sym_algo, aead_algo } =>
+                MessageLayerStatus::Encryption {
+                    sym_algo: *sym_algo,
+                    aead_algo: *aead_algo,
+                },
+            MessageLayer::SignatureGroup { results } =>
+                MessageLayerStatus::SignatureGroup {
+                    results: results.iter().map(Into::into).collect(),
+                },
+        }
+    }
+}
+
 /// Like VerificationError, but without referencing the signature.
 ///
 /// This avoids borrowing the signature, so that we can continue to
@@ -379,6 +905,30 @@ enum VerificationErrorInternal<'a> {
 }
 
 impl<'a> VerificationErrorInternal<'a> {
+    /// Checks the signature if it or the key expired.
+    ///
+    /// Expired signatures, and signatures made by expired keys are
+    /// only reported as such (i.e. with an [`Error::Expired`]) if
+    /// they are otherwise good.  If the signature doesn't check out,
+    /// the reason is reported instead.
+    fn check_expired(self, sig: &Signature, policy: &dyn Policy) -> Self {
+        use self::VerificationErrorInternal::*;
+        let ka = match &self {
+            BadKey { ka, error } | BadSignature { ka, error }
+                if matches!(error.downcast_ref::<Error>(),
+                            Some(Error::Expired(_))) => ka,
+            _ => return self,
+        };
+
+        if let Err(error) = sig.verify(ka.key())
+            .and_then(|_| policy.signature(sig, Default::default()))
+        {
+            BadSignature { ka: ka.clone(), error }
+        } else {
+            self
+        }
+    }
+
     fn attach_sig(self, sig: &'a Signature) -> VerificationError<'a> {
         use self::VerificationErrorInternal::*;
         match self {
@@ -632,6 +1182,15 @@ impl IMessageStructure {
         false
     }
 
+    /// Returns whether a packet at this recursion depth is inside an
+    /// encryption container protected by an MDC.
+    fn within_mdc(&self, depth: isize) -> bool {
+        self.layers.iter().any(|l| matches!(
+            l,
+            IMessageLayer::Encryption { depth: d, expect_mdc: true, .. }
+            if *d < depth))
+    }
+
     /// Makes sure that we insert a signature group even if the
     /// previous OPS packet had the last flag set to false.
     fn insert_missing_signature_group(&mut self) {
@@ -1053,6 +1612,7 @@ pub struct VerifierBuilder<'a> {
     message: Box<dyn BufferedReader<Cookie> + 'a>,
     buffer_size: usize,
     mapping: bool,
+    progress: Option<Progress<'a>>,
 }
 assert_send_and_sync!(VerifierBuilder<'_>);
 
@@ -1096,6 +1656,7 @@ impl<'a> VerifierBuilder<'a> {
             message: Box::new(signatures),
             buffer_size: DEFAULT_BUFFER_SIZE,
             mapping: false,
+            progress: None,
         })
     }
 
@@ -1209,6 +1770,64 @@ impl<'a> VerifierBuilder<'a> {
         self
     }
 
+    /// Reports progress to `callback`.
+    ///
+    /// `callback` is invoked with the total number of bytes verified so
+    /// far whenever at least `interval` bytes have been verified since
+    /// the last invocation.  This is useful to display progress when
+    /// verifying large files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Read;
+    /// use sequoia_openpgp as openpgp;
+    /// # use openpgp::{KeyHandle, Cert, Result};
+    /// use openpgp::parse::{Parse, stream::*};
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// struct Helper {};
+    /// impl VerificationHelper for Helper {
+    ///     // ...
+    /// #   fn get_certs(&mut self, ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+    /// #       Ok(Vec::new())
+    /// #   }
+    /// #
+    /// #   fn check(&mut self, structure: MessageStructure) -> Result<()> {
+    /// #       Ok(())
+    /// #   }
+    /// }
+    ///
+    /// let message =
+    ///     // ...
+    /// # &b"-----BEGIN PGP MESSAGE-----
+    /// #
+    /// #    xA0DAAoW+zdR8Vh9rvEByxJiAAAAAABIZWxsbyBXb3JsZCHCdQQAFgoABgWCXrLl
+    /// #    AQAhCRD7N1HxWH2u8RYhBDnRAKtn1b2MBAECBfs3UfFYfa7xRUsBAJaxkU/RCstf
+    /// #    UD7TM30IorO1Mb9cDa/hPRxyzipulT55AQDN1m9LMqi9yJDjHNHwYYVwxDcg+pLY
+    /// #    YmAFv/UfO0vYBw==
+    /// #    =+l94
+    /// #    -----END PGP MESSAGE-----
+    /// #    "[..];
+    ///
+    /// let h = Helper {};
+    /// let mut v = VerifierBuilder::from_bytes(message)?
+    ///     .progress(1 << 20, |n| eprint!("\r{} MiB", n >> 20))
+    ///     .with_policy(p, None, h)?;
+    /// let mut content = Vec::new();
+    /// v.read_to_end(&mut content)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn progress<F>(mut self, interval: u64, callback: F) -> Self
+        where F: FnMut(u64) + Send + Sync + 'a
+    {
+        self.progress = Some(Progress::new(interval, callback));
+        self
+    }
+
     /// Creates the `Verifier`.
     ///
     /// Signature verifications are done under the given `policy` and
@@ -1270,7 +1889,8 @@ impl<'a> VerifierBuilder<'a> {
                 policy,
                 self.message,
                 NoDecryptionHelper { v: helper, },
-                t, Mode::Verify, self.buffer_size, self.mapping, true)?,
+                t, Mode::Verify, self.buffer_size, self.mapping, true,
+                false, Default::default(), Vec::new(), self.progress)?,
         })
     }
 }
@@ -1385,6 +2005,13 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
     pub fn message_processed(&self) -> bool {
         self.decryptor.message_processed()
     }
+
+    /// Returns the literal data packet's metadata.
+    ///
+    /// See [`Decryptor::literal_header`] for details.
+    pub fn literal_header(&self) -> Option<&Literal> {
+        self.decryptor.literal_header()
+    }
 }
 
 impl<'a, H: VerificationHelper> io::Read for Verifier<'a, H> {
@@ -1461,6 +2088,7 @@ assert_send_and_sync!(DetachedVerifier<'_, H> where H: VerificationHelper);
 pub struct DetachedVerifierBuilder<'a> {
     signatures: Box<dyn BufferedReader<Cookie> + 'a>,
     mapping: bool,
+    progress: Option<Progress<'a>>,
 }
 assert_send_and_sync!(DetachedVerifierBuilder<'_>);
 
@@ -1503,6 +2131,7 @@ impl<'a> DetachedVerifierBuilder<'a> {
         Ok(DetachedVerifierBuilder {
             signatures: Box::new(signatures),
             mapping: false,
+            progress: None,
         })
     }
 
@@ -1561,15 +2190,12 @@ impl<'a> DetachedVerifierBuilder<'a> {
         self
     }
 
-    /// Creates the `DetachedVerifier`.
-    ///
-    /// Signature verifications are done under the given `policy` and
-    /// relative to time `time`, or the current time, if `time` is
-    /// `None`.  `helper` is the [`VerificationHelper`] to use.
-    /// [`VerificationHelper::check`] will be called with a
-    /// [`MessageStructure`] containing exactly one layer, a signature
-    /// group.
+    /// Reports progress to `callback`.
     ///
+    /// `callback` is invoked with the total number of bytes verified so
+    /// far whenever at least `interval` bytes have been verified since
+    /// the last invocation.  This is useful to display progress when
+    /// verifying large files.
     ///
     /// # Examples
     ///
@@ -1606,32 +2232,91 @@ impl<'a> DetachedVerifierBuilder<'a> {
     ///
     /// let h = Helper {};
     /// let mut v = DetachedVerifierBuilder::from_bytes(&signature[..])?
-    ///     // Customize the `DetachedVerifier` here.
+    ///     .progress(1 << 20, |n| eprint!("\r{} MiB", n >> 20))
     ///     .with_policy(p, None, h)?;
-    /// # let _ = v;
+    /// # let data = b"Hello World!";
+    /// v.verify_bytes(data)?;
     /// # Ok(()) }
     /// ```
-    pub fn with_policy<T, H>(self, policy: &'a dyn Policy, time: T, helper: H)
-                             -> Result<DetachedVerifier<'a, H>>
-        where H: VerificationHelper,
-              T: Into<Option<time::SystemTime>>,
+    pub fn progress<F>(mut self, interval: u64, callback: F) -> Self
+        where F: FnMut(u64) + Send + Sync + 'a
     {
-        // Do not eagerly map `t` to the current time.
-        let t = time.into();
-        Ok(DetachedVerifier {
-            decryptor: Decryptor::from_cookie_reader(
-                policy,
-                self.signatures,
-                NoDecryptionHelper { v: helper, },
-                t, Mode::VerifyDetached, 0, self.mapping, false)?,
-        })
+        self.progress = Some(Progress::new(interval, callback));
+        self
     }
-}
 
-impl<'a, H: VerificationHelper> DetachedVerifier<'a, H> {
-    /// Verifies the given data.
-    pub fn verify_reader<R: io::Read + Send + Sync>(&mut self, reader: R) -> Result<()> {
-        self.verify(buffered_reader::Generic::with_cookie(
+    /// Creates the `DetachedVerifier`.
+    ///
+    /// Signature verifications are done under the given `policy` and
+    /// relative to time `time`, or the current time, if `time` is
+    /// `None`.  `helper` is the [`VerificationHelper`] to use.
+    /// [`VerificationHelper::check`] will be called with a
+    /// [`MessageStructure`] containing exactly one layer, a signature
+    /// group.
+    ///
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// # use openpgp::{KeyHandle, Cert, Result};
+    /// use openpgp::parse::{Parse, stream::*};
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// struct Helper {};
+    /// impl VerificationHelper for Helper {
+    ///     // ...
+    /// #   fn get_certs(&mut self, ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+    /// #       Ok(Vec::new())
+    /// #   }
+    /// #
+    /// #   fn check(&mut self, structure: MessageStructure) -> Result<()> {
+    /// #       Ok(())
+    /// #   }
+    /// }
+    ///
+    /// let signature =
+    ///     // ...
+    /// #  b"-----BEGIN PGP SIGNATURE-----
+    /// #
+    /// #    wnUEABYKACcFglt+z/EWoQSOjDP6RiYzeXbZeXgGnAw0jdgsGQmQBpwMNI3YLBkA
+    /// #    AHmUAP9mpj2wV0/ekDuzxZrPQ0bnobFVaxZGg7YzdlksSOERrwEA6v6czXQjKcv2
+    /// #    KOwGTamb+ajTLQ3YRG9lh+ZYIXynvwE=
+    /// #    =IJ29
+    /// #    -----END PGP SIGNATURE-----";
+    ///
+    /// let h = Helper {};
+    /// let mut v = DetachedVerifierBuilder::from_bytes(&signature[..])?
+    ///     // Customize the `DetachedVerifier` here.
+    ///     .with_policy(p, None, h)?;
+    /// # let _ = v;
+    /// # Ok(()) }
+    /// ```
+    pub fn with_policy<T, H>(self, policy: &'a dyn Policy, time: T, helper: H)
+                             -> Result<DetachedVerifier<'a, H>>
+        where H: VerificationHelper,
+              T: Into<Option<time::SystemTime>>,
+    {
+        // Do not eagerly map `t` to the current time.
+        let t = time.into();
+        Ok(DetachedVerifier {
+            decryptor: Decryptor::from_cookie_reader(
+                policy,
+                self.signatures,
+                NoDecryptionHelper { v: helper, },
+                t, Mode::VerifyDetached, 0, self.mapping, false,
+                false, Default::default(), Vec::new(), self.progress)?,
+        })
+    }
+}
+
+impl<'a, H: VerificationHelper> DetachedVerifier<'a, H> {
+    /// Verifies the given data.
+    pub fn verify_reader<R: io::Read + Send + Sync>(&mut self, reader: R) -> Result<()> {
+        self.verify(buffered_reader::Generic::with_cookie(
             reader, None, Default::default()).into_boxed())
     }
 
@@ -1771,6 +2456,19 @@ pub struct Decryptor<'a, H: VerificationHelper + DecryptionHelper> {
     identity: Option<Fingerprint>,
     structure: IMessageStructure,
 
+    /// Session keys to try before asking the helper.
+    session_keys: Vec<EscrowedSessionKey>,
+
+    /// The session key that decrypted the message, if any.
+    session_key: Option<EscrowedSessionKey>,
+
+    /// The literal data packet's metadata, without the body.
+    literal: Option<Literal>,
+
+    /// Reports the number of bytes read or, when verifying detached
+    /// signatures, hashed.
+    progress: Option<Progress<'a>>,
+
     /// We want to hold back some data until the signatures checked
     /// out.  We buffer this here, cursor is the offset of unread
     /// bytes in the buffer.
@@ -1778,6 +2476,14 @@ pub struct Decryptor<'a, H: VerificationHelper + DecryptionHelper> {
     reserve: Option<Vec<u8>>,
     cursor: usize,
 
+    /// Whether to release the plaintext of truncated messages.
+    allow_truncation: bool,
+
+    /// If the message has been truncated and we release the
+    /// plaintext nevertheless, this is the error to return once the
+    /// reserve has been drained.
+    truncation: Option<Error>,
+
     /// The mode of operation.
     mode: Mode,
 
@@ -1825,6 +2531,10 @@ pub struct DecryptorBuilder<'a> {
     message: Box<dyn BufferedReader<Cookie> + 'a>,
     buffer_size: usize,
     mapping: bool,
+    allow_truncation: bool,
+    limits: Limits,
+    session_keys: Vec<EscrowedSessionKey>,
+    progress: Option<Progress<'a>>,
 }
 assert_send_and_sync!(DecryptorBuilder<'_>);
 
@@ -1868,6 +2578,10 @@ impl<'a> DecryptorBuilder<'a> {
             message: Box::new(signatures),
             buffer_size: DEFAULT_BUFFER_SIZE,
             mapping: false,
+            allow_truncation: false,
+            limits: Default::default(),
+            session_keys: Vec::new(),
+            progress: None,
         })
     }
 
@@ -2001,18 +2715,220 @@ impl<'a> DecryptorBuilder<'a> {
         self
     }
 
-    /// Creates the `Decryptor`.
+    /// Enables or disables the recovery of truncated messages.
     ///
-    /// Signature verifications are done under the given `policy` and
-    /// relative to time `time`, or the current time, if `time` is
-    /// `None`.  `helper` is the [`VerificationHelper`] and
-    /// [`DecryptionHelper`] to use.
+    /// If the encryption container ends prematurely, the
+    /// [`Decryptor`] fails with [`Error::TruncatedMessage`], and does
+    /// not release any of the plaintext that it holds back.  If this
+    /// is enabled, the `Decryptor` instead releases the plaintext
+    /// that has been decrypted up to the point of truncation, and
+    /// then returns an [`io::Error`] of kind
+    /// [`io::ErrorKind::UnexpectedEof`] wrapping the
+    /// [`Error::TruncatedMessage`] from [`io::Read::read`].
+    ///
+    /// By default, this is disabled.
+    ///
+    /// # Security Considerations
+    ///
+    /// This is dangerous.  The recovered plaintext is incomplete,
+    /// any signatures are not verified, and
+    /// [`VerificationHelper::check`] is not called.  Worse, if the
+    /// message is protected by a Modification Detection Code, none of
+    /// the recovered plaintext has been authenticated, and an
+    /// attacker may have modified it.  Only use this to salvage data
+    /// from damaged messages, and never act on the recovered
+    /// plaintext automatically.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::{Read, Write};
+    /// use sequoia_openpgp as openpgp;
+    /// # use openpgp::{*, crypto::*, packet::prelude::*, types::*};
+    /// use openpgp::parse::{Parse, stream::*};
+    /// use openpgp::policy::StandardPolicy;
+    /// use openpgp::serialize::stream::{Encryptor2, LiteralWriter, Message};
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// struct Helper {};
+    /// impl VerificationHelper for Helper {
+    ///     // ...
+    /// #   fn get_certs(&mut self, ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+    /// #       Ok(Vec::new())
+    /// #   }
+    /// #
+    /// #   fn check(&mut self, structure: MessageStructure) -> Result<()> {
+    /// #       Ok(())
+    /// #   }
+    /// }
+    /// impl DecryptionHelper for Helper {
+    ///     // ...
+    /// #   fn decrypt<D>(&mut self, _: &[PKESK], skesks: &[SKESK],
+    /// #                 _sym_algo: Option<SymmetricAlgorithm>,
+    /// #                 mut decrypt: D) -> Result<Option<Fingerprint>>
+    /// #       where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+    /// #   {
+    /// #       let (algo, sk) = skesks[0].decrypt(&"password".into())?;
+    /// #       decrypt(algo, &sk);
+    /// #       Ok(None)
+    /// #   }
+    /// }
+    ///
+    /// let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message = Encryptor2::with_passwords(message, Some("password"))
+    ///     .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(&[0; 4096])?;
+    /// message.finalize()?;
+    ///
+    /// // Cut the message short.
+    /// let truncated = &sink[..sink.len() - 100];
+    ///
+    /// let r = DecryptorBuilder::from_bytes(truncated)?
+    ///     .with_policy(p, None, Helper {});
+    /// assert!(r.is_err());
+    ///
+    /// let mut v = DecryptorBuilder::from_bytes(truncated)?
+    ///     .dangerously_allow_truncation(true)
+    ///     .with_policy(p, None, Helper {})?;
+    /// let mut plaintext = vec![];
+    /// let e = v.read_to_end(&mut plaintext).unwrap_err();
+    /// assert!(! plaintext.is_empty());
+    /// assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof);
+    /// assert_eq!(e.into_inner().unwrap().downcast::<Error>().unwrap(),
+    ///            Box::new(Error::TruncatedMessage(0)));
+    /// # Ok(()) }
+    /// ```
+    pub fn dangerously_allow_truncation(mut self, enabled: bool) -> Self {
+        self.allow_truncation = enabled;
+        self
+    }
+
+    /// Limits the number of encrypted session key packets.
+    ///
+    /// Hostile messages may contain thousands of [`PKESK`] and
+    /// [`SKESK`] packets, each of which may be tried with every
+    /// secret key and password.  If a message contains more than
+    /// `n` of them, processing it fails with
+    /// [`Error::MessageLimitExceeded`].
+    ///
+    /// By default, up to [`DEFAULT_MAX_ESKS`] encrypted session key
+    /// packets are accepted.
+    ///
+    ///   [`PKESK`]: crate::packet::PKESK
+    ///   [`SKESK`]: crate::packet::SKESK
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::{Error, Fingerprint, KeyHandle, Cert, Result};
+    /// use openpgp::crypto::SessionKey;
+    /// use openpgp::packet::{PKESK, SKESK};
+    /// use openpgp::parse::{Parse, stream::*};
+    /// use openpgp::policy::StandardPolicy;
+    /// use openpgp::serialize::stream::*;
+    /// use openpgp::types::SymmetricAlgorithm;
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// // A message encrypted with three passwords.
+    /// let mut message = Vec::new();
+    /// let m = Message::new(&mut message);
+    /// let m = Encryptor2::with_passwords(m, ["a", "b", "c"]).build()?;
+    /// let mut m = LiteralWriter::new(m).build()?;
+    /// m.write_all(b"Hello world.")?;
+    /// m.finalize()?;
+    ///
+    /// struct Helper;
+    /// impl VerificationHelper for Helper {
+    ///     fn get_certs(&mut self, _: &[KeyHandle]) -> Result<Vec<Cert>> {
+    ///         Ok(Vec::new())
+    ///     }
+    ///     fn check(&mut self, _: MessageStructure) -> Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    /// impl DecryptionHelper for Helper {
+    ///     fn decrypt<D>(&mut self, _: &[PKESK], skesks: &[SKESK],
+    ///                   _: Option<SymmetricAlgorithm>, mut decrypt: D)
+    ///                   -> Result<Option<Fingerprint>>
+    ///         where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+    ///     {
+    ///         let (algo, sk) = skesks[2].decrypt(&"c".into())?;
+    ///         decrypt(algo, &sk);
+    ///         Ok(None)
+    ///     }
+    /// }
+    ///
+    /// let r = DecryptorBuilder::from_bytes(&message)?
+    ///     .max_esks(2)
+    ///     .with_policy(p, None, Helper);
+    /// assert_eq!(r.err().unwrap().downcast::<Error>()?,
+    ///            Error::MessageLimitExceeded(Limit::EncryptedSessionKeys, 2));
+    ///
+    /// let r = DecryptorBuilder::from_bytes(&message)?
+    ///     .max_esks(3)
+    ///     .with_policy(p, None, Helper);
+    /// assert!(r.is_ok());
+    /// # Ok(()) }
+    /// ```
+    pub fn max_esks(mut self, n: usize) -> Self {
+        self.limits.max_esks = n;
+        self
+    }
+
+    /// Limits the number of signature layers.
+    ///
+    /// Every [`OnePassSig`] packet, and every [`Signature`] packet
+    /// preceding the literal data, opens a signature layer.  Each of
+    /// them requires hashing the message, and verifying a signature.
+    /// If a message contains more than `n` signature layers,
+    /// processing it fails with [`Error::MessageLimitExceeded`].
+    ///
+    /// By default, up to [`DEFAULT_MAX_SIGNATURE_LAYERS`] signature
+    /// layers are accepted.
+    ///
+    ///   [`OnePassSig`]: crate::packet::OnePassSig
+    ///   [`Signature`]: crate::packet::Signature
+    pub fn max_signature_layers(mut self, n: usize) -> Self {
+        self.limits.max_signature_layers = n;
+        self
+    }
+
+    /// Limits how deeply containers may be nested.
+    ///
+    /// Compressed data packets and encryption containers may contain
+    /// further containers.  If they are nested more than `n` levels
+    /// deep, processing the message fails with
+    /// [`Error::MessageLimitExceeded`].
+    ///
+    /// By default, containers may be nested up to
+    /// [`DEFAULT_MAX_RECURSION_DEPTH`] levels deep.
+    ///
+    ///   [`DEFAULT_MAX_RECURSION_DEPTH`]: crate::parse::DEFAULT_MAX_RECURSION_DEPTH
+    pub fn max_nesting_depth(mut self, n: u8) -> Self {
+        self.limits.max_nesting_depth = n;
+        self
+    }
+
+    /// Reports progress to `callback`.
+    ///
+    /// `callback` is invoked with the total number of bytes decrypted so
+    /// far whenever at least `interval` bytes have been decrypted since
+    /// the last invocation.  This is useful to display progress when
+    /// decrypting large files.
     ///
     /// # Examples
     ///
     /// ```
     /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Read;
     /// use sequoia_openpgp as openpgp;
     /// # use openpgp::{*, crypto::*, packet::prelude::*, types::*};
     /// use openpgp::parse::{Parse, stream::*};
@@ -2056,101 +2972,249 @@ impl<'a> DecryptorBuilder<'a> {
     ///
     /// let h = Helper {};
     /// let mut v = DecryptorBuilder::from_bytes(message)?
-    ///     // Customize the `Decryptor` here.
+    ///     .progress(1 << 20, |n| eprint!("\r{} MiB", n >> 20))
     ///     .with_policy(p, None, h)?;
-    /// # let _ = v;
+    /// let mut content = Vec::new();
+    /// v.read_to_end(&mut content)?;
     /// # Ok(()) }
     /// ```
-    pub fn with_policy<T, H>(self, policy: &'a dyn Policy, time: T, helper: H)
-                             -> Result<Decryptor<'a, H>>
-        where H: VerificationHelper + DecryptionHelper,
-              T: Into<Option<time::SystemTime>>,
+    pub fn progress<F>(mut self, interval: u64, callback: F) -> Self
+        where F: FnMut(u64) + Send + Sync + 'a
     {
-        // Do not eagerly map `t` to the current time.
-        let t = time.into();
-        Decryptor::from_cookie_reader(
-            policy,
-            self.message,
-            helper,
-            t, Mode::Decrypt, self.buffer_size, self.mapping, false)
+        self.progress = Some(Progress::new(interval, callback));
+        self
     }
-}
 
-/// Helper for decrypting messages.
-///
-/// This trait abstracts over session key decryption.  It allows us to
-/// provide the [`Decryptor`] without imposing any policy on how the
-/// session key is decrypted.
-///
-pub trait DecryptionHelper {
-    /// Decrypts the message.
-    ///
-    /// This function is called with every [`PKESK`] and [`SKESK`]
-    /// packet found in the message.  The implementation must decrypt
-    /// the symmetric algorithm and session key from one of the
-    /// [`PKESK`] packets, the [`SKESK`] packets, or retrieve it from
-    /// a cache, and then call `decrypt` with the symmetric algorithm
-    /// and session key.  `decrypt` returns `true` if the decryption
-    /// was successful.
-    ///
-    ///   [`PKESK`]: crate::packet::PKESK
-    ///   [`SKESK`]: crate::packet::SKESK
-    ///
-    /// If a symmetric algorithm is given, it should be passed on to
-    /// [`PKESK::decrypt`].
+    /// Adds session keys to try before asking the helper.
     ///
-    ///   [`PKESK::decrypt`]: crate::packet::PKESK#method.decrypt
-    ///
-    /// If the message is decrypted using a [`PKESK`] packet, then the
-    /// fingerprint of the certificate containing the encryption
-    /// subkey should be returned.  This is used in conjunction with
-    /// the intended recipient subpacket (see [Section 5.2.3.29 of RFC
-    /// 4880bis]) to prevent [*Surreptitious Forwarding*].
+    /// When the encryption container is encountered, the given
+    /// session keys are tried in order.  Only if none of them
+    /// decrypts the message, [`DecryptionHelper::decrypt`] is
+    /// invoked.  This allows applications to cache session keys
+    /// obtained using [`Decryptor::session_key`], and avoid repeated
+    /// private key operations.
     ///
-    ///   [Section 5.2.3.29 of RFC 4880bis]: https://tools.ietf.org/html/draft-ietf-openpgp-rfc4880bis-08#section-5.2.3.29
-    ///   [*Surreptitious Forwarding*]: http://world.std.com/~dtd/sign_encrypt/sign_encrypt7.html
+    /// If the message is decrypted using one of these session keys,
+    /// the identity of the recipient is not known, and signatures
+    /// carrying the [Intended Recipient subpacket] are checked as if
+    /// [`DecryptionHelper::decrypt`] returned `None`.
     ///
-    /// This method will be called once per encryption layer.
+    ///   [Intended Recipient subpacket]: https://tools.ietf.org/html/draft-ietf-openpgp-rfc4880bis-08#section-5.2.3.29
     ///
     /// # Examples
     ///
-    /// This example demonstrates how to decrypt a message using local
-    /// keys (i.e. excluding remote keys like smart cards) while
-    /// maximizing convenience for the user.
-    ///
     /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::{Read, Write};
     /// use sequoia_openpgp as openpgp;
-    /// use openpgp::{Fingerprint, Cert, Result};
-    /// # use openpgp::KeyID;
-    /// use openpgp::crypto::SessionKey;
-    /// use openpgp::types::SymmetricAlgorithm;
-    /// use openpgp::packet::{PKESK, SKESK};
-    /// # use openpgp::packet::{Key, key::*};
-    /// use openpgp::parse::stream::*;
-    /// # fn lookup_cache(_: &[PKESK], _: &[SKESK])
-    /// #                 -> Option<(Option<Fingerprint>, SymmetricAlgorithm, SessionKey)> {
-    /// #     unimplemented!()
-    /// # }
-    /// # fn lookup_key(_: &KeyID)
-    /// #               -> Option<(Fingerprint, Key<SecretParts, UnspecifiedRole>)> {
-    /// #     unimplemented!()
-    /// # }
-    /// # fn all_keys() -> impl Iterator<Item = (Fingerprint, Key<SecretParts, UnspecifiedRole>)> {
-    /// #     Vec::new().into_iter()
-    /// # }
+    /// # use openpgp::{*, crypto::*, packet::prelude::*, types::*};
+    /// use openpgp::parse::{Parse, stream::*};
+    /// use openpgp::policy::StandardPolicy;
+    /// use openpgp::serialize::stream::{Encryptor2, LiteralWriter, Message};
     ///
-    /// struct Helper { /* ... */ };
-    /// impl DecryptionHelper for Helper {
-    ///     fn decrypt<D>(&mut self, pkesks: &[PKESK], skesks: &[SKESK],
-    ///                   sym_algo: Option<SymmetricAlgorithm>,
-    ///                   mut decrypt: D) -> Result<Option<Fingerprint>>
-    ///         where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
-    ///     {
-    ///         // Try to decrypt, from the most convenient method to the
-    ///         // least convenient one.
+    /// let p = &StandardPolicy::new();
     ///
-    ///         // First, see if it is in the cache.
-    ///         if let Some((fp, algo, sk)) = lookup_cache(pkesks, skesks) {
+    /// struct Helper {};
+    /// impl VerificationHelper for Helper {
+    ///     // ...
+    /// #   fn get_certs(&mut self, ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+    /// #       Ok(Vec::new())
+    /// #   }
+    /// #
+    /// #   fn check(&mut self, structure: MessageStructure) -> Result<()> {
+    /// #       Ok(())
+    /// #   }
+    /// }
+    /// impl DecryptionHelper for Helper {
+    ///     fn decrypt<D>(&mut self, _: &[PKESK], _: &[SKESK],
+    ///                   _sym_algo: Option<SymmetricAlgorithm>,
+    ///                   _decrypt: D) -> Result<Option<Fingerprint>>
+    ///         where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+    ///     {
+    ///         unreachable!("decrypted using the cached session key")
+    ///     }
+    /// }
+    ///
+    /// // Encrypt a message using a known session key.
+    /// let algo = SymmetricAlgorithm::AES256;
+    /// let sk = SessionKey::new(algo.key_size()?);
+    /// let mut message = Vec::new();
+    /// let m = Message::new(&mut message);
+    /// let m = Encryptor2::with_session_key(m, algo, sk.clone())?
+    ///     .add_passwords(Some("streng geheim"))
+    ///     .build()?;
+    /// let mut m = LiteralWriter::new(m).build()?;
+    /// m.write_all(b"Hello world.")?;
+    /// m.finalize()?;
+    ///
+    /// let h = Helper {};
+    /// let mut v = DecryptorBuilder::from_bytes(&message)?
+    ///     .session_keys(Some(EscrowedSessionKey::new(algo, sk)))
+    ///     .with_policy(p, None, h)?;
+    /// let mut content = Vec::new();
+    /// v.read_to_end(&mut content)?;
+    /// assert_eq!(content, b"Hello world.");
+    /// # Ok(()) }
+    /// ```
+    pub fn session_keys<I>(mut self, session_keys: I) -> Self
+        where I: IntoIterator<Item = EscrowedSessionKey>,
+    {
+        self.session_keys.extend(session_keys);
+        self
+    }
+
+    /// Creates the `Decryptor`.
+    ///
+    /// Signature verifications are done under the given `policy` and
+    /// relative to time `time`, or the current time, if `time` is
+    /// `None`.  `helper` is the [`VerificationHelper`] and
+    /// [`DecryptionHelper`] to use.
+    ///
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// # use openpgp::{*, crypto::*, packet::prelude::*, types::*};
+    /// use openpgp::parse::{Parse, stream::*};
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// struct Helper {};
+    /// impl VerificationHelper for Helper {
+    ///     // ...
+    /// #   fn get_certs(&mut self, ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+    /// #       Ok(Vec::new())
+    /// #   }
+    /// #
+    /// #   fn check(&mut self, structure: MessageStructure) -> Result<()> {
+    /// #       Ok(())
+    /// #   }
+    /// }
+    /// impl DecryptionHelper for Helper {
+    ///     // ...
+    /// #   fn decrypt<D>(&mut self, _: &[PKESK], skesks: &[SKESK],
+    /// #                 _sym_algo: Option<SymmetricAlgorithm>,
+    /// #                 mut decrypt: D) -> Result<Option<Fingerprint>>
+    /// #       where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+    /// #   {
+    /// #       Ok(None)
+    /// #   }
+    /// }
+    ///
+    /// let message =
+    ///     // ...
+    /// # &b"-----BEGIN PGP MESSAGE-----
+    /// #
+    /// #    xA0DAAoW+zdR8Vh9rvEByxJiAAAAAABIZWxsbyBXb3JsZCHCdQQAFgoABgWCXrLl
+    /// #    AQAhCRD7N1HxWH2u8RYhBDnRAKtn1b2MBAECBfs3UfFYfa7xRUsBAJaxkU/RCstf
+    /// #    UD7TM30IorO1Mb9cDa/hPRxyzipulT55AQDN1m9LMqi9yJDjHNHwYYVwxDcg+pLY
+    /// #    YmAFv/UfO0vYBw==
+    /// #    =+l94
+    /// #    -----END PGP MESSAGE-----
+    /// #    "[..];
+    ///
+    /// let h = Helper {};
+    /// let mut v = DecryptorBuilder::from_bytes(message)?
+    ///     // Customize the `Decryptor` here.
+    ///     .with_policy(p, None, h)?;
+    /// # let _ = v;
+    /// # Ok(()) }
+    /// ```
+    pub fn with_policy<T, H>(self, policy: &'a dyn Policy, time: T, helper: H)
+                             -> Result<Decryptor<'a, H>>
+        where H: VerificationHelper + DecryptionHelper,
+              T: Into<Option<time::SystemTime>>,
+    {
+        // Do not eagerly map `t` to the current time.
+        let t = time.into();
+        Decryptor::from_cookie_reader(
+            policy,
+            self.message,
+            helper,
+            t, Mode::Decrypt, self.buffer_size, self.mapping, false,
+            self.allow_truncation, self.limits, self.session_keys,
+            self.progress)
+    }
+}
+
+/// Helper for decrypting messages.
+///
+/// This trait abstracts over session key decryption.  It allows us to
+/// provide the [`Decryptor`] without imposing any policy on how the
+/// session key is decrypted.
+///
+pub trait DecryptionHelper {
+    /// Decrypts the message.
+    ///
+    /// This function is called with every [`PKESK`] and [`SKESK`]
+    /// packet found in the message.  The implementation must decrypt
+    /// the symmetric algorithm and session key from one of the
+    /// [`PKESK`] packets, the [`SKESK`] packets, or retrieve it from
+    /// a cache, and then call `decrypt` with the symmetric algorithm
+    /// and session key.  `decrypt` returns `true` if the decryption
+    /// was successful.
+    ///
+    ///   [`PKESK`]: crate::packet::PKESK
+    ///   [`SKESK`]: crate::packet::SKESK
+    ///
+    /// If a symmetric algorithm is given, it should be passed on to
+    /// [`PKESK::decrypt`].
+    ///
+    ///   [`PKESK::decrypt`]: crate::packet::PKESK#method.decrypt
+    ///
+    /// If the message is decrypted using a [`PKESK`] packet, then the
+    /// fingerprint of the certificate containing the encryption
+    /// subkey should be returned.  This is used in conjunction with
+    /// the intended recipient subpacket (see [Section 5.2.3.29 of RFC
+    /// 4880bis]) to prevent [*Surreptitious Forwarding*].
+    ///
+    ///   [Section 5.2.3.29 of RFC 4880bis]: https://tools.ietf.org/html/draft-ietf-openpgp-rfc4880bis-08#section-5.2.3.29
+    ///   [*Surreptitious Forwarding*]: http://world.std.com/~dtd/sign_encrypt/sign_encrypt7.html
+    ///
+    /// This method will be called once per encryption layer.
+    ///
+    /// # Examples
+    ///
+    /// This example demonstrates how to decrypt a message using local
+    /// keys (i.e. excluding remote keys like smart cards) while
+    /// maximizing convenience for the user.
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::{Fingerprint, Cert, Result};
+    /// # use openpgp::KeyID;
+    /// use openpgp::crypto::SessionKey;
+    /// use openpgp::types::SymmetricAlgorithm;
+    /// use openpgp::packet::{PKESK, SKESK};
+    /// # use openpgp::packet::{Key, key::*};
+    /// use openpgp::parse::stream::*;
+    /// # fn lookup_cache(_: &[PKESK], _: &[SKESK])
+    /// #                 -> Option<(Option<Fingerprint>, SymmetricAlgorithm, SessionKey)> {
+    /// #     unimplemented!()
+    /// # }
+    /// # fn lookup_key(_: &KeyID)
+    /// #               -> Option<(Fingerprint, Key<SecretParts, UnspecifiedRole>)> {
+    /// #     unimplemented!()
+    /// # }
+    /// # fn all_keys() -> impl Iterator<Item = (Fingerprint, Key<SecretParts, UnspecifiedRole>)> {
+    /// #     Vec::new().into_iter()
+    /// # }
+    ///
+    /// struct Helper { /* ... */ };
+    /// impl DecryptionHelper for Helper {
+    ///     fn decrypt<D>(&mut self, pkesks: &[PKESK], skesks: &[SKESK],
+    ///                   sym_algo: Option<SymmetricAlgorithm>,
+    ///                   mut decrypt: D) -> Result<Option<Fingerprint>>
+    ///         where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+    ///     {
+    ///         // Try to decrypt, from the most convenient method to the
+    ///         // least convenient one.
+    ///
+    ///         // First, see if it is in the cache.
+    ///         if let Some((fp, algo, sk)) = lookup_cache(pkesks, skesks) {
     ///             if decrypt(algo, &sk) {
     ///                 return Ok(fp);
     ///             }
@@ -2229,6 +3293,43 @@ pub trait DecryptionHelper {
         where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool;
 }
 
+/// Returns the error to report if `e` indicates that the encryption
+/// container was truncated.
+///
+/// Most errors encountered while processing an encryption container
+/// must be reported uniformly to avoid creating a decryption oracle.
+/// Truncation is different: it is detected using the container's
+/// framing, which is not encrypted, so reporting it does not reveal
+/// anything about the plaintext.
+fn truncation_error(e: &anyhow::Error) -> Option<Error> {
+    let e = e.downcast_ref::<Error>().or_else(|| {
+        e.downcast_ref::<io::Error>()
+            .and_then(|e| e.get_ref())
+            .and_then(|e| e.downcast_ref::<Error>())
+    });
+
+    match e {
+        Some(e @ Error::TruncatedMessage(_)) => Some(e.clone()),
+        _ => None,
+    }
+}
+
+/// Fails if `count` exceeds the `limit` of kind `what`.
+fn check_limit(what: Limit, count: usize, limit: usize) -> Result<()> {
+    if count > limit {
+        Err(Error::MessageLimitExceeded(what, limit).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Fails if the container `pp` is too deeply nested to be processed.
+fn check_nesting_depth(pp: &PacketParser, limits: &Limits) -> Result<()> {
+    check_limit(Limit::NestingDepth,
+                pp.recursion_depth() as usize + 1,
+                limits.max_nesting_depth as usize)
+}
+
 impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
     /// Returns a reference to the helper.
     pub fn helper_ref(&self) -> &H {
@@ -2245,6 +3346,33 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
         self.helper
     }
 
+    /// Returns the session key that decrypted the message.
+    ///
+    /// The session key can be stored, and later be given to
+    /// [`DecryptorBuilder::session_keys`] to decrypt the message
+    /// again without the recipient's secret key.  Note that anyone
+    /// who has the session key can decrypt the message, so it needs
+    /// to be protected accordingly.
+    ///
+    /// Returns `None` if the message is not encrypted.
+    pub fn session_key(&self) -> Option<&EscrowedSessionKey> {
+        self.session_key.as_ref()
+    }
+
+    /// Returns the literal data packet's metadata.
+    ///
+    /// The returned packet carries the format, the filename, and the
+    /// date from the literal data packet's header, but not the body.
+    /// This can be used to restore the file's name and modification
+    /// time.  Note: this metadata is not covered by the signatures,
+    /// and must not be trusted.
+    ///
+    /// Returns `None` if the literal data packet has not been
+    /// encountered yet.
+    pub fn literal_header(&self) -> Option<&Literal> {
+        self.literal.as_ref()
+    }
+
     /// Returns true if the whole message has been processed and
     /// authenticated.
     ///
@@ -2350,6 +3478,10 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
         buffer_size: usize,
         mapping: bool,
         csf_transformation: bool,
+        allow_truncation: bool,
+        limits: Limits,
+        session_keys: Vec<EscrowedSessionKey>,
+        progress: Option<Progress<'a>>,
     )
         -> Result<Decryptor<'a, H>>
         where T: Into<Option<time::SystemTime>>
@@ -2366,6 +3498,7 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
         let mut ppr = PacketParserBuilder::from_cookie_reader(bio)?
             .map(mapping)
             .csf_transformation(csf_transformation)
+            .max_recursion_depth(limits.max_nesting_depth)
             .build()?;
 
         let mut v = Decryptor {
@@ -2375,9 +3508,15 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
             oppr: None,
             identity: None,
             structure: IMessageStructure::new(),
+            session_keys,
+            session_key: None,
+            literal: None,
+            progress,
             buffer_size,
             reserve: None,
             cursor: 0,
+            allow_truncation,
+            truncation: None,
             mode,
             time,
             clock_skew_tolerance: tolerance,
@@ -2387,6 +3526,7 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
 
         let mut pkesks: Vec<packet::PKESK> = Vec::new();
         let mut skesks: Vec<packet::SKESK> = Vec::new();
+        let mut signature_layers = 0;
 
         while let PacketParserResult::Some(mut pp) = ppr {
             t!("Found a {:?} at depth {}", pp.packet.tag(),
@@ -2428,6 +3568,22 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                 }
             }
 
+            // Containers at the maximum nesting depth are not
+            // recursed into.  Rather than failing later with a
+            // confusing error, report that the limit was exceeded.
+            match pp.packet {
+                Packet::CompressedData(_) =>
+                    check_nesting_depth(&pp, &limits)?,
+                Packet::SEIP(_) | Packet::AED(_) if v.mode == Mode::Decrypt =>
+                    check_nesting_depth(&pp, &limits)?,
+                Packet::OnePassSig(_) if v.mode != Mode::VerifyDetached => {
+                    signature_layers += 1;
+                    check_limit(Limit::SignatureLayers, signature_layers,
+                                limits.max_signature_layers)?;
+                },
+                _ => (),
+            }
+
             let sym_algo_hint = if let Packet::AED(ref aed) = pp.packet {
                 Some(aed.symmetric_algo())
             } else {
@@ -2444,7 +3600,21 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                     // proxy function.  This is necessary because we
                     // cannot get the algorithm from the SEIP packet.
                     let mut sym_algo = None;
-                    {
+                    let mut session_key = None;
+
+                    // First, try the session keys we were given.
+                    for sk in v.session_keys.iter() {
+                        let algo =
+                            sym_algo_hint.unwrap_or(sk.symmetric_algo());
+                        if pp.decrypt(algo, sk.session_key()).is_ok() {
+                            t!("Decrypted using a given session key");
+                            sym_algo = Some(algo);
+                            session_key = Some(sk.clone());
+                            break;
+                        }
+                    }
+
+                    if sym_algo.is_none() {
                         let decryption_proxy = |algo, secret: &SessionKey| {
                             // Take the algo from the AED packet over
                             // the dummy one from the SKESK5 packet.
@@ -2454,6 +3624,8 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                                algo, secret, result);
                             if let Ok(_) = result {
                                 sym_algo = Some(algo);
+                                session_key = Some(EscrowedSessionKey::new(
+                                    algo, secret.clone()));
                                 true
                             } else {
                                 false
@@ -2465,6 +3637,7 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                                              sym_algo_hint,
                                              decryption_proxy)?;
                     }
+                    v.session_key = session_key;
                     if ! pp.processed() {
                         return Err(
                             Error::MissingSessionKey(
@@ -2493,7 +3666,12 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                     v.structure.push_ops(ops);
                     v.push_issuer(ops.issuer().clone());
                 },
-                Packet::Literal(_) => {
+                Packet::Literal(ref l) => {
+                    let mut header = Literal::new(l.format());
+                    header.set_filename(l.filename().unwrap_or(b""))?;
+                    header.set_date(l.date())?;
+                    v.literal = Some(header);
+
                     v.structure.insert_missing_signature_group();
                     v.oppr = Some(PacketParserResult::Some(pp));
                     v.finish_maybe()?;
@@ -2502,16 +3680,32 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                 },
                 #[allow(deprecated)]
                 Packet::MDC(ref mdc) => if ! mdc.valid() {
-                    return Err(Error::ManipulatedMessage.into());
+                    return Err(Error::BadMDC.into());
                 },
                 _ => (),
             }
 
             let (p, ppr_tmp) = pp.recurse()?;
             match p {
-                Packet::PKESK(pkesk) => pkesks.push(pkesk),
-                Packet::SKESK(skesk) => skesks.push(skesk),
+                Packet::PKESK(pkesk) => {
+                    pkesks.push(pkesk);
+                    check_limit(Limit::EncryptedSessionKeys,
+                                pkesks.len() + skesks.len(),
+                                limits.max_esks)?;
+                },
+                Packet::SKESK(skesk) => {
+                    skesks.push(skesk);
+                    check_limit(Limit::EncryptedSessionKeys,
+                                pkesks.len() + skesks.len(),
+                                limits.max_esks)?;
+                },
                 Packet::Signature(sig) => {
+                    if v.mode != Mode::VerifyDetached {
+                        signature_layers += 1;
+                        check_limit(Limit::SignatureLayers, signature_layers,
+                                    limits.max_signature_layers)?;
+                    }
+
                     // The following structure is allowed:
                     //
                     //   SIG LITERAL
@@ -2554,8 +3748,11 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
         let algos: Vec<_> = sigs.iter().map(|s| {
             HashingMode::for_signature(s.hash_algo(), s.typ())
         }).collect();
-        let hashes =
-            crate::parse::hashed_reader::hash_buffered_reader(data, &algos)?;
+        if let Some(progress) = self.progress.as_mut() {
+            progress.reset();
+        }
+        let hashes = crate::parse::hashed_reader::hash_buffered_reader(
+            data, &algos, self.progress.as_mut())?;
 
         // Attach the digests.
         for sig in sigs.iter_mut() {
@@ -2645,7 +3842,13 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                    self.cursor);
                 pp.consume(self.cursor);
                 self.cursor = 0;
-                self.reserve = Some(pp.steal_eof()?);
+                match pp.steal_eof() {
+                    Ok(reserve) => self.reserve = Some(reserve),
+                    Err(e) => {
+                        let plaintext = pp.buffer().to_vec();
+                        return self.handle_truncation(e.into(), plaintext);
+                    },
+                }
 
                 // Process the rest of the packets.
                 let mut ppr = PacketParserResult::Some(pp);
@@ -2673,13 +3876,27 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                     let (p, ppr_tmp) = match pp.recurse() {
                         Ok(v) => v,
                         Err(e) => {
+                            if let Some(e) = truncation_error(&e) {
+                                if self.allow_truncation {
+                                    // Release the reserve, and report
+                                    // the truncation once it has
+                                    // been drained.
+                                    t!("Message truncated, releasing \
+                                        the reserve.");
+                                    self.truncation = Some(e);
+                                    return Ok(());
+                                }
+
+                                return Err(e.into());
+                            }
+
                             // Assuming we just tried to ascend,
                             // should there have been a MDC packet?
                             // If so, this may be an attack.
                             if self.structure.expect_mdc_at(
                                 last_recursion_depth - 1)
                             {
-                                return Err(Error::ManipulatedMessage.into());
+                                return Err(Error::BadMDC.into());
                             } else {
                                 return Err(e);
                             }
@@ -2698,7 +3915,7 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                             Packet::MDC(mdc) if mdc.valid() =>
                                 (), // Good.
                             _ =>    // Bad.
-                                return Err(Error::ManipulatedMessage.into()),
+                                return Err(Error::BadMDC.into()),
                         }
 
                         if possible_message.is_err() {
@@ -2711,6 +3928,14 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                             // CSF transformation creates slightly out
                             // of spec message structure.  See above
                             // for longer explanation.
+                        } else if self.structure.within_mdc(
+                            last_recursion_depth)
+                        {
+                            // A tampered MDC packet may not be
+                            // recognized as such.  Report this
+                            // uniformly to avoid creating a
+                            // decryption oracle.
+                            return Err(Error::BadMDC.into());
                         } else {
                             return Err(Error::ManipulatedMessage.into());
                         }
@@ -2826,14 +4051,14 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                                 VerificationErrorInternal::BadKey {
                                     ka,
                                     error,
-                                }
+                                }.check_expired(sig, self.policy)
                             } else if let Err(error) = ka.alive() {
                                 t!("{:02X}{:02X}: key {} not alive: {}",
                                    sigid[0], sigid[1], ka.fingerprint(), error);
                                 VerificationErrorInternal::BadKey {
                                     ka,
                                     error,
-                                }
+                                }.check_expired(sig, self.policy)
                             } else if let
                                 RevocationStatus::Revoked(rev) = ka.cert().revocation_status()
                             {
@@ -2873,7 +4098,7 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                                 VerificationErrorInternal::BadSignature {
                                     ka,
                                     error,
-                                }
+                                }.check_expired(sig, self.policy)
                             } else if self.identity.as_ref().map(|identity| {
                                 let (have_one, contains_identity) =
                                     sig.intended_recipients()
@@ -2947,6 +4172,28 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
         r
     }
 
+    /// Handles an error encountered while reading the plaintext.
+    ///
+    /// If the message has been truncated, and the caller opted into
+    /// processing truncated messages, `plaintext` becomes the reserve
+    /// that is released without verification, and the truncation is
+    /// reported once it has been drained.  Otherwise, the error is
+    /// returned.
+    fn handle_truncation(&mut self, e: anyhow::Error, plaintext: Vec<u8>)
+                         -> Result<()>
+    {
+        match truncation_error(&e) {
+            Some(e) if self.allow_truncation => {
+                self.reserve = Some(plaintext);
+                self.cursor = 0;
+                self.truncation = Some(e);
+                Ok(())
+            },
+            Some(e) => Err(e.into()),
+            None => Err(e),
+        }
+    }
+
     /// Like `io::Read::read()`, but returns our `Result`.
     fn read_helper(&mut self, buf: &mut [u8]) -> Result<usize> {
         tracer!(TRACE, "Decryptor::read_helper", TRACE_INDENT);
@@ -2963,6 +4210,12 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
             assert!(self.oppr.is_none());
             assert!(self.cursor <= reserve.len());
             let n = cmp::min(buf.len(), reserve.len() - self.cursor);
+            if n == 0 {
+                if let Some(e) = &self.truncation {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                              e.clone()).into());
+                }
+            }
             buf[..n]
                 .copy_from_slice(&reserve[self.cursor..n + self.cursor]);
             self.cursor += n;
@@ -2982,7 +4235,14 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
             // We request two times what our buffer size is, the first
             // part is the one we give out, the second part is the one
             // we hold back.
-            let data_len = pp.data(2 * self.buffer_size)?.len();
+            let data_len = match pp.data(2 * self.buffer_size) {
+                Ok(data) => data.len(),
+                Err(e) => {
+                    let plaintext = pp.buffer()[self.cursor..].to_vec();
+                    self.handle_truncation(e.into(), plaintext)?;
+                    return self.read_helper(buf);
+                },
+            };
             t!("Read {} bytes.", data_len);
             if data_len - self.cursor <= self.buffer_size {
                 self.oppr = Some(PacketParserResult::Some(pp));
@@ -3010,7 +4270,12 @@ impl<'a, H: VerificationHelper + DecryptionHelper> io::Read for Decryptor<'a, H>
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self.read_helper(buf) {
-            Ok(n) => Ok(n),
+            Ok(n) => {
+                if let Some(progress) = self.progress.as_mut() {
+                    progress.update(n);
+                }
+                Ok(n)
+            },
             Err(e) => match e.downcast::<io::Error>() {
                 // An io::Error.  Pass as-is.
                 Ok(e) => Err(e),
@@ -3818,10 +5083,10 @@ pub mod test {
             match e.downcast::<io::Error>() {
                 Ok(e) =>
                     assert_eq!(e.into_inner().unwrap().downcast().unwrap(),
-                               Box::new(Error::ManipulatedMessage)),
+                               Box::new(Error::BadMDC)),
                 Err(e) =>
                     assert_eq!(e.downcast::<Error>().unwrap(),
-                               Error::ManipulatedMessage),
+                               Error::BadMDC),
             };
             Ok(())
         }
@@ -3859,32 +5124,434 @@ EK8=
         Ok(())
     }
 
-    /// Tests samples of messages signed with the cleartext signature
-    /// framework.
+    /// Checks that session keys can be exported from and imported
+    /// into the decryptor.
     #[test]
-    fn csf_verification() -> Result<()> {
-        struct H(Vec<Cert>, bool);
+    fn escrowed_session_keys() -> Result<()> {
+        use std::io::Write;
+        use crate::serialize::stream::{Encryptor2, LiteralWriter, Message};
+
+        struct H(Option<Password>);
         impl VerificationHelper for H {
             fn get_certs(&mut self, _ids: &[crate::KeyHandle])
                          -> Result<Vec<Cert>> {
-                Ok(std::mem::take(&mut self.0))
+                Ok(Vec::new())
             }
 
-            fn check(&mut self, m: MessageStructure)
+            fn check(&mut self, _: MessageStructure)
                      -> Result<()> {
-                for (i, layer) in m.into_iter().enumerate() {
-                    assert_eq!(i, 0);
-                    if let MessageLayer::SignatureGroup { results } = layer {
-                        assert!(! results.is_empty());
-                        for result in results {
-                            result.unwrap();
-                        }
-                        self.1 = true;
-                    } else {
-                        panic!();
-                    }
-                }
-
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for H {
+            fn decrypt<D>(&mut self, _: &[PKESK], s: &[SKESK],
+                          _: Option<SymmetricAlgorithm>, mut decrypt: D)
+                          -> Result<Option<Fingerprint>>
+            where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+            {
+                let password = self.0.as_ref().ok_or_else(
+                    || Error::MissingSessionKey("no password".into()))?;
+                let (algo, sk) = s[0].decrypt(password)?;
+                assert!(decrypt(algo, &sk));
+                Ok(None)
+            }
+        }
+
+        let p = &P::new();
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Encryptor2::with_passwords(m, Some("123")).build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+
+        // Decrypt using the password, and remember the session key.
+        let mut d = DecryptorBuilder::from_bytes(&message)?
+            .with_policy(p, None, H(Some("123".into())))?;
+        let mut content = Vec::new();
+        d.read_to_end(&mut content)?;
+        assert_eq!(content, b"Hello world.");
+        let sk = d.session_key().expect("message is encrypted").clone();
+
+        // Round-trip the session key through its textual form.
+        let sk: EscrowedSessionKey = sk.to_string().parse()?;
+
+        // Without the password, decryption fails.
+        assert!(DecryptorBuilder::from_bytes(&message)?
+                .with_policy(p, None, H(None)).is_err());
+
+        // A wrong session key is skipped.
+        let wrong = EscrowedSessionKey::new(
+            sk.symmetric_algo(),
+            SessionKey::new(sk.session_key().len()));
+        assert!(DecryptorBuilder::from_bytes(&message)?
+                .session_keys(vec![wrong.clone()])
+                .with_policy(p, None, H(None)).is_err());
+
+        // But the right one works.
+        let mut d = DecryptorBuilder::from_bytes(&message)?
+            .session_keys(vec![wrong, sk.clone()])
+            .with_policy(p, None, H(None))?;
+        let mut content = Vec::new();
+        d.read_to_end(&mut content)?;
+        assert_eq!(content, b"Hello world.");
+        assert_eq!(d.session_key(), Some(&sk));
+
+        Ok(())
+    }
+
+    /// Checks that truncated messages are detected and reported, and
+    /// that the plaintext can be recovered if so desired.
+    #[test]
+    fn truncated_messages() -> Result<()> {
+        use std::io::Write;
+        use crate::serialize::stream::{Encryptor2, LiteralWriter, Message};
+        use crate::types::AEADAlgorithm;
+
+        struct H;
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(Vec::new())
+            }
+
+            fn check(&mut self, _: MessageStructure)
+                     -> Result<()> {
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for H {
+            fn decrypt<D>(&mut self, _: &[PKESK], s: &[SKESK],
+                          _: Option<SymmetricAlgorithm>, mut decrypt: D)
+                          -> Result<Option<Fingerprint>>
+            where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+            {
+                let (algo, sk) = s[0].decrypt(&"123".into())?;
+                assert!(decrypt(algo, &sk));
+                Ok(None)
+            }
+        }
+
+        // Decrypts `message`, returning the recovered plaintext and
+        // the number of authenticated bytes reported by the
+        // truncation error.
+        fn decrypt(message: &[u8], allow_truncation: bool)
+                   -> (Vec<u8>, Option<u64>)
+        {
+            let mut p = P::new();
+            p.accept_packet_tag(packet::Tag::AED);
+            let p = &p;
+            let mut content = Vec::new();
+            let r = DecryptorBuilder::from_bytes(message).unwrap()
+                .dangerously_allow_truncation(allow_truncation)
+                .with_policy(p, None, H)
+                .and_then(|mut d| Ok(d.read_to_end(&mut content)?));
+            let authenticated = match r {
+                Ok(_) => None,
+                Err(e) => match truncation_error(&e) {
+                    Some(Error::TruncatedMessage(n)) => Some(n),
+                    _ => panic!("expected a truncation error, got {}", e),
+                },
+            };
+            (content, authenticated)
+        }
+
+        let plaintext = (0..20_000).map(|i| i as u8).collect::<Vec<_>>();
+        for aead in [None, Some(AEADAlgorithm::default())] {
+            let mut message = Vec::new();
+            let m = Message::new(&mut message);
+            let mut m = Encryptor2::with_passwords(m, Some("123"));
+            if let Some(aead) = aead {
+                m = m.aead_algo(aead);
+            }
+            let m = m.build()?;
+            let mut m = LiteralWriter::new(m).build()?;
+            m.write_all(&plaintext)?;
+            m.finalize()?;
+
+            let (content, authenticated) = decrypt(&message, false);
+            assert_eq!(content, plaintext);
+            assert_eq!(authenticated, None);
+
+            for cut in [message.len() / 2, message.len() - 30,
+                        message.len() - 1]
+            {
+                let truncated = &message[..cut];
+
+                // By default, no plaintext is released.
+                let (content, authenticated) = decrypt(truncated, false);
+                assert!(content.is_empty());
+                let authenticated = authenticated.unwrap();
+                if aead.is_none() {
+                    assert_eq!(authenticated, 0);
+                } else {
+                    // Encryptor2 uses 4k chunks.
+                    assert_eq!(authenticated % 4096, 0);
+                    assert!(authenticated < plaintext.len() as u64);
+                }
+
+                // But we can recover it.
+                let (content, recovered_authenticated) =
+                    decrypt(truncated, true);
+                assert_eq!(recovered_authenticated, Some(authenticated));
+                assert!(! content.is_empty());
+                assert!(plaintext.starts_with(&content));
+                if aead.is_some() {
+                    // Only authenticated chunks are released.
+                    assert!(content.len() as u64 <= authenticated);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that tampering, truncation, and wrong session keys are
+    /// reported using distinct errors.
+    #[test]
+    fn tampering_errors() -> Result<()> {
+        use std::io::Write;
+        use crate::serialize::stream::{Encryptor2, LiteralWriter, Message};
+        use crate::types::AEADAlgorithm;
+
+        struct H;
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(Vec::new())
+            }
+
+            fn check(&mut self, _: MessageStructure)
+                     -> Result<()> {
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for H {
+            fn decrypt<D>(&mut self, _: &[PKESK], s: &[SKESK],
+                          _: Option<SymmetricAlgorithm>, mut decrypt: D)
+                          -> Result<Option<Fingerprint>>
+            where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+            {
+                let (algo, sk) = s[0].decrypt(&"123".into())?;
+                if decrypt(algo, &sk) {
+                    Ok(None)
+                } else {
+                    Err(Error::InvalidSessionKey("test".into()).into())
+                }
+            }
+        }
+
+        // Decrypts `message`, returning the error.
+        fn decrypt(message: &[u8]) -> Error {
+            let mut p = P::new();
+            p.accept_packet_tag(packet::Tag::AED);
+            let p = &p;
+            let mut content = Vec::new();
+            let e = DecryptorBuilder::from_bytes(message).unwrap()
+                .with_policy(p, None, H)
+                .and_then(|mut d| Ok(d.read_to_end(&mut content)?))
+                .unwrap_err();
+            e.downcast_ref::<Error>().or_else(|| {
+                e.downcast_ref::<io::Error>()
+                    .and_then(|e| e.get_ref())
+                    .and_then(|e| e.downcast_ref::<Error>())
+            }).unwrap_or_else(|| panic!("unexpected error: {:?}", e)).clone()
+        }
+
+        let plaintext = vec![42; 20_000];
+        for aead in [None, Some(AEADAlgorithm::default())] {
+            let mut message = Vec::new();
+            let m = Message::new(&mut message);
+            let mut m = Encryptor2::with_passwords(m, Some("123"));
+            if let Some(aead) = aead {
+                m = m.aead_algo(aead);
+            }
+            let m = m.build()?;
+            let mut m = LiteralWriter::new(m).build()?;
+            m.write_all(&plaintext)?;
+            m.finalize()?;
+
+            // Flip a bit in the middle of the ciphertext.
+            let mut tampered = message.clone();
+            tampered[message.len() / 2] ^= 1;
+            if aead.is_none() {
+                assert_eq!(decrypt(&tampered), Error::BadMDC);
+            } else {
+                assert_eq!(decrypt(&tampered), Error::BadAEADTag);
+            }
+
+            // Truncate.
+            assert!(matches!(decrypt(&message[..message.len() / 2]),
+                             Error::TruncatedMessage(_)));
+        }
+
+        // If the first chunk of an AEAD-protected message can't be
+        // authenticated, the session key is most likely wrong.
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Encryptor2::with_passwords(m, Some("123"))
+            .aead_algo(AEADAlgorithm::default())
+            .build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(&plaintext)?;
+        m.finalize()?;
+        let mut ppr = PacketParser::from_bytes(&message)?;
+        while let PacketParserResult::Some(mut pp) = ppr {
+            if let Packet::AED(_) = pp.packet {
+                let algo = SymmetricAlgorithm::default();
+                let sk = SessionKey::new(algo.key_size()?);
+                let e = pp.decrypt(algo, &sk).unwrap_err();
+                assert!(matches!(e.downcast_ref::<Error>(),
+                                 Some(Error::InvalidSessionKey(_))));
+                break;
+            }
+            ppr = pp.recurse()?.1;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the limits on encrypted session key packets,
+    /// signature layers, and nesting depth are enforced.
+    #[test]
+    fn limits() -> Result<()> {
+        use std::io::Write;
+        use crate::serialize::stream::{
+            Compressor, Encryptor2, LiteralWriter, Message, Signer,
+        };
+
+        struct H;
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(Vec::new())
+            }
+
+            fn check(&mut self, _: MessageStructure)
+                     -> Result<()> {
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for H {
+            fn decrypt<D>(&mut self, _: &[PKESK], s: &[SKESK],
+                          _: Option<SymmetricAlgorithm>, mut decrypt: D)
+                          -> Result<Option<Fingerprint>>
+            where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+            {
+                for skesk in s {
+                    if let Ok((algo, sk)) = skesk.decrypt(&"123".into()) {
+                        if decrypt(algo, &sk) {
+                            break;
+                        }
+                    }
+                }
+                Ok(None)
+            }
+        }
+
+        // Decrypts `message` using the given builder customization,
+        // returning the exceeded limit, if any.
+        fn decrypt<F>(message: &[u8], f: F) -> Result<Option<Error>>
+            where F: FnOnce(DecryptorBuilder) -> DecryptorBuilder
+        {
+            let p = &P::new();
+            let r = f(DecryptorBuilder::from_bytes(message)?)
+                .with_policy(p, None, H)
+                .and_then(|mut d| {
+                    let mut content = Vec::new();
+                    d.read_to_end(&mut content)?;
+                    assert_eq!(&content, b"Hello world.");
+                    Ok(())
+                });
+            match r {
+                Ok(()) => Ok(None),
+                Err(e) => match e.downcast::<Error>()? {
+                    e @ Error::MessageLimitExceeded(_, _) => Ok(Some(e)),
+                    e => Err(e.into()),
+                },
+            }
+        }
+
+        // Three SKESKs.
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Encryptor2::with_passwords(m, ["1", "2", "123"]).build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+
+        assert_eq!(decrypt(&message, |b| b)?, None);
+        assert_eq!(decrypt(&message, |b| b.max_esks(3))?, None);
+        assert_eq!(decrypt(&message, |b| b.max_esks(2))?,
+                   Some(Error::MessageLimitExceeded(
+                       Limit::EncryptedSessionKeys, 2)));
+
+        // Two signature layers.
+        let p = &P::new();
+        let (cert, _) = CertBuilder::new()
+            .add_signing_subkey()
+            .generate()?;
+        let signer = || -> Result<_> {
+            Ok(cert.keys().with_policy(p, None).for_signing()
+               .secret().next().unwrap().key().clone().into_keypair()?)
+        };
+
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Signer::new(m, signer()?).add_signer(signer()?).build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+
+        assert_eq!(decrypt(&message, |b| b)?, None);
+        assert_eq!(decrypt(&message, |b| b.max_signature_layers(2))?, None);
+        assert_eq!(decrypt(&message, |b| b.max_signature_layers(1))?,
+                   Some(Error::MessageLimitExceeded(
+                       Limit::SignatureLayers, 1)));
+
+        // Two nested containers.
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Compressor::new(m).build()?;
+        let m = Compressor::new(m).build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+
+        assert_eq!(decrypt(&message, |b| b)?, None);
+        assert_eq!(decrypt(&message, |b| b.max_nesting_depth(2))?, None);
+        assert_eq!(decrypt(&message, |b| b.max_nesting_depth(1))?,
+                   Some(Error::MessageLimitExceeded(Limit::NestingDepth, 1)));
+
+        Ok(())
+    }
+
+    /// Tests samples of messages signed with the cleartext signature
+    /// framework.
+    #[test]
+    fn csf_verification() -> Result<()> {
+        struct H(Vec<Cert>, bool);
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(std::mem::take(&mut self.0))
+            }
+
+            fn check(&mut self, m: MessageStructure)
+                     -> Result<()> {
+                for (i, layer) in m.into_iter().enumerate() {
+                    assert_eq!(i, 0);
+                    if let MessageLayer::SignatureGroup { results } = layer {
+                        assert!(! results.is_empty());
+                        for result in results {
+                            result.unwrap();
+                        }
+                        self.1 = true;
+                    } else {
+                        panic!();
+                    }
+                }
+
                 Ok(())
             }
         }
@@ -4151,4 +5818,415 @@ xHUDBRY0WIQ+50WENDPP";
         assert_eq!(v.helper_ref().good, 2);
         Ok(())
     }
+
+    /// Checks that the literal data packet's metadata is exposed.
+    #[test]
+    fn literal_header() -> Result<()> {
+        use std::io::Write;
+        use crate::serialize::stream::{LiteralWriter, Message};
+        use crate::types::{DataFormat, Timestamp};
+
+        let p = &P::new();
+        let date = Timestamp::from(1585925313);
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let mut m = LiteralWriter::new(m)
+            .format(DataFormat::Text)
+            .filename("hello.txt")?
+            .date(date)?
+            .build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+
+        struct H(());
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(Vec::new())
+            }
+
+            fn check(&mut self, _: MessageStructure)
+                     -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut v = VerifierBuilder::from_bytes(&message)?
+            .with_policy(p, None, H(()))?;
+        let mut content = Vec::new();
+        v.read_to_end(&mut content)?;
+        assert_eq!(content, b"Hello world.");
+
+        let header = v.literal_header().expect("literal data packet");
+        assert_eq!(header.format(), DataFormat::Text);
+        assert_eq!(header.filename(), Some(&b"hello.txt"[..]));
+        assert_eq!(header.date(), Some(date.into()));
+        assert!(header.body().is_empty());
+        Ok(())
+    }
+
+    /// Checks that verification results can be summarized.
+    #[test]
+    fn signature_status() -> Result<()> {
+        use std::io::Write;
+        use crate::serialize::stream::{LiteralWriter, Message, Signer};
+
+        struct H(Vec<Cert>, Vec<SignatureStatus>);
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(self.0.clone())
+            }
+
+            fn check(&mut self, structure: MessageStructure)
+                     -> Result<()> {
+                for layer in structure.iter() {
+                    if let MessageLayer::SignatureGroup { results } = layer {
+                        self.1.extend(results.iter().map(SignatureStatus::from));
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        let p = &P::new();
+        let (cert, _) = CertBuilder::new()
+            .add_signing_subkey()
+            .generate()?;
+        let signer = cert.keys().with_policy(p, None).for_signing()
+            .secret().next().unwrap().key().clone().into_keypair()?;
+        let signing_fpr = signer.public().fingerprint();
+
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Signer::new(m, signer).build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+
+        // With the cert, the signature is good.
+        let mut v = VerifierBuilder::from_bytes(&message)?
+            .with_policy(p, None, H(vec![cert.clone()], Vec::new()))?;
+        io::copy(&mut v, &mut io::sink())?;
+        let statuses = v.into_helper().1;
+        assert_eq!(statuses.len(), 1);
+        let s = &statuses[0];
+        assert!(s.is_good());
+        assert_eq!(s.key(), Some(&signing_fpr));
+        assert_eq!(s.cert(), Some(&cert.fingerprint()));
+        assert!(s.error().is_none());
+        let status = s.to_string();
+        assert!(status.starts_with(
+            &format!("GOODSIG {:X} {}\nVALIDSIG {} ",
+                     KeyID::from(&signing_fpr), cert.fingerprint().to_hex(),
+                     signing_fpr.to_hex())));
+
+        // Without it, the key is missing.
+        let mut v = VerifierBuilder::from_bytes(&message)?
+            .with_policy(p, None, H(Vec::new(), Vec::new()))?;
+        io::copy(&mut v, &mut io::sink())?;
+        let statuses = v.into_helper().1;
+        assert_eq!(statuses.len(), 1);
+        let s = &statuses[0];
+        assert_eq!(s.kind(), SignatureStatusKind::MissingKey);
+        assert!(s.key().is_none());
+        assert!(s.to_string().ends_with(
+            &format!("NO_PUBKEY {:X}", KeyID::from(&signing_fpr))));
+        Ok(())
+    }
+
+    /// Checks that verification results can be serialized, and read
+    /// back.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn message_layer_status_serde() -> Result<()> {
+        use std::io::Write;
+        use crate::serialize::stream::{
+            Encryptor2, LiteralWriter, Message, Signer,
+        };
+
+        struct H(Vec<Cert>, Vec<MessageLayerStatus>);
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(self.0.clone())
+            }
+
+            fn check(&mut self, structure: MessageStructure)
+                     -> Result<()> {
+                self.1 = structure.iter().map(Into::into).collect();
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for H {
+            fn decrypt<D>(&mut self, _: &[PKESK], skesks: &[SKESK],
+                          _: Option<SymmetricAlgorithm>,
+                          mut decrypt: D) -> Result<Option<Fingerprint>>
+                where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+            {
+                let (algo, sk) = skesks[0].decrypt(&"password".into())?;
+                decrypt(algo, &sk);
+                Ok(None)
+            }
+        }
+
+        let p = &P::new();
+        let (cert, _) = CertBuilder::new()
+            .add_signing_subkey()
+            .generate()?;
+        let signer = cert.keys().with_policy(p, None).for_signing()
+            .secret().next().unwrap().key().clone().into_keypair()?;
+        let signing_fpr = signer.public().fingerprint();
+
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Encryptor2::with_passwords(m, Some("password")).build()?;
+        let m = Signer::new(m, signer).build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+
+        let mut v = DecryptorBuilder::from_bytes(&message)?
+            .with_policy(p, None, H(vec![cert.clone()], Vec::new()))?;
+        io::copy(&mut v, &mut io::sink())?;
+        let layers = v.into_helper().1;
+        assert_eq!(layers.len(), 2);
+        assert!(matches!(layers[0], MessageLayerStatus::Encryption { .. }));
+
+        let json = serde_json::to_string(&layers)?;
+        assert!(json.contains(&format!("\"{:X}\"", signing_fpr)));
+        let layers_: Vec<MessageLayerStatus> = serde_json::from_str(&json)?;
+        assert_eq!(layers, layers_);
+        match &layers_[1] {
+            MessageLayerStatus::SignatureGroup { results } => {
+                assert_eq!(results.len(), 1);
+                assert!(results[0].is_good());
+                assert_eq!(results[0].key.as_ref(), Some(&signing_fpr));
+                assert_eq!(results[0].cert.as_ref(),
+                           Some(&cert.fingerprint()));
+            },
+            l => panic!("expected a signature group, got {:?}", l),
+        }
+        Ok(())
+    }
+
+    /// Checks that signatures made by keys that have since been
+    /// rotated out still verify.
+    #[test]
+    fn rotated_signing_key() -> Result<()> {
+        use std::io::Write;
+        use std::time::Duration;
+        use crate::serialize::stream::{LiteralWriter, Message, Signer};
+        use crate::types::{KeyFlags, ReasonForRevocation};
+
+        struct H(Vec<Cert>, Vec<SignatureStatus>);
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(self.0.clone())
+            }
+
+            fn check(&mut self, structure: MessageStructure)
+                     -> Result<()> {
+                for layer in structure.iter() {
+                    if let MessageLayer::SignatureGroup { results } = layer {
+                        self.1.extend(results.iter().map(SignatureStatus::from));
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        let verify = |cert: &Cert, message: &[u8], t|
+                      -> Result<SignatureStatus>
+        {
+            let p = &P::new();
+            let mut v = VerifierBuilder::from_bytes(message)?
+                .with_policy(p, t, H(vec![cert.clone()], Vec::new()))?;
+            io::copy(&mut v, &mut io::sink())?;
+            let mut statuses = v.into_helper().1;
+            assert_eq!(statuses.len(), 1);
+            Ok(statuses.pop().unwrap())
+        };
+
+        let p = &P::new();
+        let day = Duration::new(24 * 60 * 60, 0);
+        let t0 = crate::frozen_time();
+        let (cert, _) = CertBuilder::new()
+            .set_creation_time(t0)
+            .add_subkey(KeyFlags::empty().set_signing(), 30 * day, None)
+            .generate()?;
+        let old = cert.keys().with_policy(p, t0 + day).for_signing()
+            .secret().next().unwrap().key().clone();
+        let mut primary = cert.primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+
+        // Sign a message using the old subkey.
+        let t1 = t0 + day;
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Signer::new(m, old.clone().into_keypair()?)
+            .creation_time(t1)
+            .build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+
+        // Later, the old subkey is superseded.
+        let t2 = t0 + 2 * day;
+        let rev = SubkeyRevocationBuilder::new()
+            .set_reason_for_revocation(ReasonForRevocation::KeySuperseded,
+                                       b"Rotated")?
+            .set_signature_creation_time(t2)?
+            .build(&mut primary, &cert, old.role_as_subordinate(), None)?;
+        let rotated = cert.clone().insert_packets(rev)?;
+        assert!(rotated.keys().with_policy(p, t2).for_signing().revoked(false)
+                .next().is_none());
+
+        // Even after the subkey expired, the signature is good.
+        let t3 = t0 + 365 * day;
+        for cert in [&cert, &rotated] {
+            let s = verify(cert, &message, t3)?;
+            assert!(s.is_good(), "{:?}", s);
+            assert_eq!(s.key(), Some(&old.fingerprint()));
+            assert_eq!(s.key_validity_time(), Some(t1));
+        }
+
+        // But, if the subkey is compromised, it isn't.
+        let rev = SubkeyRevocationBuilder::new()
+            .set_reason_for_revocation(ReasonForRevocation::KeyCompromised,
+                                       b"Stolen")?
+            .set_signature_creation_time(t2)?
+            .build(&mut primary, &cert, old.role_as_subordinate(), None)?;
+        let compromised = cert.insert_packets(rev)?;
+        let s = verify(&compromised, &message, t3)?;
+        assert_eq!(s.kind(), SignatureStatusKind::BadKey);
+        assert_eq!(s.key_validity_time(), Some(t1));
+        Ok(())
+    }
+
+    /// Checks that good but expired signatures, and good signatures
+    /// made by expired keys are reported as such.
+    #[test]
+    fn expired_signatures() -> Result<()> {
+        use std::io::Write;
+        use std::time::Duration;
+        use crate::packet::Key;
+        use crate::packet::signature::SignatureBuilder;
+        use crate::serialize::stream::{LiteralWriter, Message, Signer};
+        use crate::types::KeyFlags;
+
+        struct H(Vec<Cert>, Vec<SignatureStatus>);
+        impl VerificationHelper for H {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(self.0.clone())
+            }
+
+            fn check(&mut self, structure: MessageStructure)
+                     -> Result<()> {
+                for layer in structure.iter() {
+                    if let MessageLayer::SignatureGroup { results } = layer {
+                        self.1.extend(results.iter().map(SignatureStatus::from));
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        let verify = |cert: &Cert, message: &[u8], t|
+                      -> Result<SignatureStatus>
+        {
+            let p = &P::new();
+            let mut v = VerifierBuilder::from_bytes(message)?
+                .with_policy(p, t, H(vec![cert.clone()], Vec::new()))?;
+            io::copy(&mut v, &mut io::sink())?;
+            let mut statuses = v.into_helper().1;
+            assert_eq!(statuses.len(), 1);
+            Ok(statuses.pop().unwrap())
+        };
+
+        let sign = |key: &Key<key::SecretParts, key::UnspecifiedRole>, t,
+                    validity: Option<Duration>|
+                    -> Result<Vec<u8>>
+        {
+            let mut template = SignatureBuilder::new(SignatureType::Binary);
+            if let Some(validity) = validity {
+                template = template.set_signature_validity_period(validity)?;
+            }
+            let mut message = Vec::new();
+            let m = Message::new(&mut message);
+            let m = Signer::with_template(
+                m, key.clone().into_keypair()?, template)
+                .creation_time(t)
+                .build()?;
+            let mut m = LiteralWriter::new(m).build()?;
+            m.write_all(b"Hello world.")?;
+            m.finalize()?;
+            Ok(message)
+        };
+
+        let p = &P::new();
+        let day = Duration::new(24 * 60 * 60, 0);
+        let t0 = crate::frozen_time();
+        let (cert, _) = CertBuilder::new()
+            .set_creation_time(t0)
+            .add_subkey(KeyFlags::empty().set_signing(), 30 * day, None)
+            .generate()?;
+        let signer = cert.keys().with_policy(p, t0).for_signing()
+            .secret().next().unwrap().key().clone();
+        let key_expiration = t0 + 30 * day;
+
+        // A signature that expires after a day.
+        let t1 = t0 + day;
+        let message = sign(&signer, t1, Some(day))?;
+
+        let s = verify(&cert, &message, t1 + day / 2)?;
+        assert!(s.is_good(), "{:?}", s);
+        assert_eq!(s.expiration_time(), Some(t1 + day));
+        assert_eq!(s.key_expiration_time(), Some(key_expiration));
+
+        let s = verify(&cert, &message, t1 + 2 * day)?;
+        assert_eq!(s.kind(), SignatureStatusKind::ExpiredSignature);
+        assert_eq!(s.expiration_time(), Some(t1 + day));
+        assert_eq!(s.key(), Some(&signer.fingerprint()));
+        let status = s.to_string();
+        assert!(status.starts_with(
+            &format!("EXPSIG {:X} {}\nVALIDSIG {} ",
+                     KeyID::from(signer.fingerprint()),
+                     cert.fingerprint().to_hex(),
+                     signer.fingerprint().to_hex())), "{}", status);
+        assert!(status.contains(&format!(
+            " {} ", (t1 + day).duration_since(time::UNIX_EPOCH)?.as_secs())));
+
+        // If it has been tampered with, it is bad.
+        let mut tampered = message.clone();
+        let i = tampered.windows(5).position(|w| w == b"Hello").unwrap();
+        tampered[i] = b'J';
+        let s = verify(&cert, &tampered, t1 + 2 * day)?;
+        assert_eq!(s.kind(), SignatureStatusKind::BadSignature);
+
+        // A signature made after the signing key expired.
+        let t2 = key_expiration + day;
+        let message = sign(&signer, t2, None)?;
+
+        let s = verify(&cert, &message, t2 + day)?;
+        assert_eq!(s.kind(), SignatureStatusKind::ExpiredKey);
+        assert_eq!(s.key_expiration_time(), Some(key_expiration));
+        assert_eq!(s.expiration_time(), None);
+        let status = s.to_string();
+        assert!(status.starts_with(
+            &format!("KEYEXPIRED {}\nEXPKEYSIG {:X} {}\nVALIDSIG ",
+                     key_expiration.duration_since(time::UNIX_EPOCH)?
+                         .as_secs(),
+                     KeyID::from(signer.fingerprint()),
+                     cert.fingerprint().to_hex())), "{}", status);
+
+        let mut tampered = message.clone();
+        let i = tampered.windows(5).position(|w| w == b"Hello").unwrap();
+        tampered[i] = b'J';
+        let s = verify(&cert, &tampered, t2 + day)?;
+        assert_eq!(s.kind(), SignatureStatusKind::BadSignature);
+        Ok(())
+    }
 }
@@ -33,6 +33,7 @@ use anyhow::Context;
 
 use crate::{
     cert::prelude::*,
+    cert::CertificationLimits,
     Error,
     Packet,
     packet::{
@@ -151,6 +152,20 @@ pub trait Policy : fmt::Debug + Send + Sync {
         Err(Error::PolicyViolation(
             "By default all packets are rejected.".into(), None).into())
     }
+
+    /// Returns the limits on the number of third-party
+    /// certifications kept per component.
+    ///
+    /// This mitigates certificate flooding.  Pass the limits to
+    /// [`CertParser::certification_limits`] to apply them when
+    /// parsing certificates.
+    ///
+    /// By default, there are no limits.
+    ///
+    ///   [`CertParser::certification_limits`]: crate::cert::CertParser::certification_limits()
+    fn certification_limits(&self) -> CertificationLimits {
+        CertificationLimits::new()
+    }
 }
 
 /// Whether the signed data requires a hash algorithm with collision
@@ -607,6 +622,9 @@ pub struct StandardPolicy<'a> {
 
     // Asymmetric algorithms.
     asymmetric_algos: AsymmetricAlgorithmCutoffList,
+
+    // Limits on third-party certifications.
+    certification_limits: CertificationLimits,
 }
 
 assert_send_and_sync!(StandardPolicy<'_>);
@@ -626,7 +644,7 @@ impl<'a> From<&'a StandardPolicy<'a>> for Option<&'a dyn Policy> {
 // Signatures that require a hash with collision Resistance and second
 // Pre-image Resistance.  See the documentation for HashAlgoSecurity
 // for more details.
-a_cutoff_list!(CollisionResistantHashCutoffList, HashAlgorithm, 12,
+a_cutoff_list!(CollisionResistantHashCutoffList, HashAlgorithm, 15,
                [
                    REJECT,                   // 0. Not assigned.
                    Some(Timestamp::Y1997M2), // 1. MD5
@@ -640,11 +658,14 @@ a_cutoff_list!(CollisionResistantHashCutoffList, HashAlgorithm, 12,
                    ACCEPT,                   // 9. SHA384
                    ACCEPT,                   // 10. SHA512
                    ACCEPT,                   // 11. SHA224
+                   ACCEPT,                   // 12. SHA3-256
+                   REJECT,                   // 13. Reserved.
+                   ACCEPT,                   // 14. SHA3-512
                ]);
 // Signatures that *only* require a hash with Second Pre-image
 // Resistance.  See the documentation for HashAlgoSecurity for more
 // details.
-a_cutoff_list!(SecondPreImageResistantHashCutoffList, HashAlgorithm, 12,
+a_cutoff_list!(SecondPreImageResistantHashCutoffList, HashAlgorithm, 15,
                [
                    REJECT,                   // 0. Not assigned.
                    Some(Timestamp::Y2004M2), // 1. MD5
@@ -658,6 +679,9 @@ a_cutoff_list!(SecondPreImageResistantHashCutoffList, HashAlgorithm, 12,
                    ACCEPT,                   // 9. SHA384
                    ACCEPT,                   // 10. SHA512
                    ACCEPT,                   // 11. SHA224
+                   ACCEPT,                   // 12. SHA3-256
+                   REJECT,                   // 13. Reserved.
+                   ACCEPT,                   // 14. SHA3-512
                ]);
 
 a_cutoff_list!(SubpacketTagCutoffList, SubpacketTag, 38,
@@ -833,6 +857,7 @@ impl<'a> StandardPolicy<'a> {
             symmetric_algos: SymmetricAlgorithmCutoffList::Default(),
             aead_algos: AEADAlgorithmCutoffList::Default(),
             packet_tags: PacketTagCutoffList::Default(),
+            certification_limits: CertificationLimits::new(),
         }
     }
 
@@ -1463,6 +1488,15 @@ impl<'a> StandardPolicy<'a> {
             .expect("have one")
             .map(Into::into)
     }
+
+    /// Sets the limits on the number of third-party certifications
+    /// kept per component.
+    ///
+    /// By default, there are no limits.  See
+    /// [`CertificationLimits`] for details.
+    pub fn set_certification_limits(&mut self, limits: CertificationLimits) {
+        self.certification_limits = limits;
+    }
 }
 
 impl<'a> Policy for StandardPolicy<'a> {
@@ -1473,6 +1507,18 @@ impl<'a> Policy for StandardPolicy<'a> {
                 | SignatureType::SubkeyRevocation
                 | SignatureType::CertificationRevocation);
 
+        // Describes the strength of the hash algorithm for error
+        // messages.
+        let strength = || {
+            let algo = sig.hash_algo();
+            match (algo.is_supported(), algo.security_level()) {
+                (true, Ok(bits)) =>
+                    format!("{} provides about {} bits of collision \
+                             resistance", algo, bits),
+                _ => format!("{} is not supported", algo),
+            }
+        };
+
         // Note: collision resistance requires 2nd pre-image resistance.
         if sec == HashAlgoSecurity::CollisionResistance {
             if rev {
@@ -1482,14 +1528,14 @@ impl<'a> Policy for StandardPolicy<'a> {
                            Some(self.hash_revocation_tolerance))
                     .with_context(|| format!(
                         "Policy rejected revocation signature ({}) requiring \
-                         collision resistance", sig.typ()))?
+                         collision resistance; {}", sig.typ(), strength()))?
             } else {
                 self
                     .collision_resistant_hash_algos
                     .check(sig.hash_algo(), time, None)
                     .with_context(|| format!(
                         "Policy rejected non-revocation signature ({}) requiring \
-                         collision resistance", sig.typ()))?
+                         collision resistance; {}", sig.typ(), strength()))?
             }
         }
 
@@ -1664,6 +1710,10 @@ impl<'a> Policy for StandardPolicy<'a> {
         self.aead_algos.check(algo, time, None)
             .context("Policy rejected authenticated encryption algorithm")
     }
+
+    fn certification_limits(&self) -> CertificationLimits {
+        self.certification_limits
+    }
 }
 
 /// Asymmetric encryption algorithms.
@@ -140,8 +140,13 @@ use super::*;
 mod cert;
 pub use self::cert::TSK;
 mod cert_armored;
+mod keyring;
+pub use self::keyring::KeyringWriter;
+mod transformer;
+pub use self::transformer::PacketPileTransformer;
 pub mod stream;
 use crate::crypto::S2K;
+use crate::message::EncryptedSessionKeys;
 use crate::packet::header::{
     BodyLength,
     CTB,
@@ -3120,6 +3125,33 @@ impl MarshalInto for PacketPile {
     }
 }
 
+impl Serialize for EncryptedSessionKeys {}
+impl seal::Sealed for EncryptedSessionKeys {}
+impl Marshal for EncryptedSessionKeys {
+    /// Writes a serialized version of the specified
+    /// `EncryptedSessionKeys` to `o`.
+    fn serialize(&self, o: &mut dyn std::io::Write) -> Result<()> {
+        for p in self.packets() {
+            (p as &dyn Marshal).serialize(o)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SerializeInto for EncryptedSessionKeys {}
+impl MarshalInto for EncryptedSessionKeys {
+    fn serialized_len(&self) -> usize {
+        self.packets().iter().map(|p| {
+            (p as &dyn MarshalInto).serialized_len()
+        }).sum()
+    }
+
+    fn serialize_into(&self, buf: &mut [u8]) -> Result<usize> {
+        generic_serialize_into(self, MarshalInto::serialized_len(self), buf)
+    }
+}
+
 impl Serialize for Message {}
 impl seal::Sealed for Message {}
 impl Marshal for Message {
@@ -282,6 +282,167 @@ impl Cert {
     pub fn into_tsk(self) -> TSK<'static> {
         TSK::from(self)
     }
+
+    /// Serializes the certificate using a canonical packet order.
+    ///
+    /// The order in which [`Serialize`] emits components and
+    /// signatures is stable, but unspecified, and may change between
+    /// versions of this library.  This function instead emits the
+    /// packets in a documented order, and guarantees that the output
+    /// does not change across versions.  This makes the output
+    /// suitable for comparing certificates byte-wise, and for
+    /// content-addressed storage.
+    ///
+    /// Like [`Serialize`], this function never emits secret key
+    /// material, and includes non-exportable signatures and
+    /// [`bad signatures`].  The packets are serialized using
+    /// OpenPGP's new packet format with the shortest length encoding.
+    /// The unhashed subpacket areas are normalized: issuer
+    /// information learned while verifying the signatures is added,
+    /// and the subpackets are sorted.
+    ///
+    /// The packets are ordered as follows:
+    ///
+    ///   - The primary key, followed by its signatures.
+    ///   - The User IDs, ordered by their value, each followed by
+    ///     its signatures.
+    ///   - The User Attributes, ordered by their value, each
+    ///     followed by its signatures.
+    ///   - The subkeys, ordered by their fingerprint, each followed
+    ///     by its signatures.
+    ///   - The unknown components, ordered by their serialized form,
+    ///     each followed by its signatures.
+    ///   - The bad signatures.
+    ///
+    /// The signatures of a component are emitted in groups: self
+    /// revocations, self signatures, attestations, third-party
+    /// certifications, and third-party revocations.  Within each
+    /// group, and among the bad signatures, the newest signature
+    /// comes first, signatures without a creation time come last,
+    /// and ties are broken by comparing the serialized signatures
+    /// byte-wise.  Values, fingerprints, and serialized forms are
+    /// compared byte-wise.
+    ///
+    ///   [`Serialize`]: crate::serialize::Serialize
+    ///   [`bad signatures`]: Cert::bad_signatures()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::parse::Parse;
+    /// use openpgp::serialize::SerializeInto;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let (cert, _) =
+    ///     CertBuilder::general_purpose(None, Some("alice@example.org"))
+    ///         .generate()?;
+    ///
+    /// let canonical = cert.canonical_serialization()?;
+    ///
+    /// // The canonical serialization can be parsed as usual, and
+    /// // round-trips.
+    /// let cert_ = Cert::from_bytes(&canonical)?;
+    /// assert_eq!(cert_, Cert::from_bytes(&cert.to_vec()?)?);
+    /// assert_eq!(cert_.canonical_serialization()?, canonical);
+    /// # Ok(()) }
+    /// ```
+    pub fn canonical_serialization(&self) -> Result<Vec<u8>> {
+        // Serializes a packet.
+        fn packet(p: PacketRef) -> Result<Vec<u8>> {
+            let mut buf = Vec::new();
+            p.serialize(&mut buf)?;
+            Ok(buf)
+        }
+
+        // Serializes signatures in canonical order.
+        fn signatures<'a, I>(o: &mut Vec<u8>, sigs: I) -> Result<()>
+            where I: Iterator<Item = &'a Signature>
+        {
+            let mut sigs = sigs
+                .map(|s| {
+                    // Normalize the unhashed area like canonicalizing
+                    // the certificate again would.
+                    let mut s = s.clone();
+                    let _ = s.add_missing_issuers();
+                    s.unhashed_area_mut().sort();
+                    Ok((s.signature_creation_time(),
+                        packet(PacketRef::Signature(&s))?))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            // Note: None < Some, so comparing b to a orders the
+            // newest signatures first, and those without creation
+            // time last.
+            sigs.sort_by(|(a_time, a), (b_time, b)| {
+                b_time.cmp(a_time).then_with(|| a.cmp(b))
+            });
+            sigs.into_iter().for_each(|(_, s)| o.extend_from_slice(&s));
+            Ok(())
+        }
+
+        // Serializes the signatures of a component in canonical
+        // order.
+        fn bundle<C>(o: &mut Vec<u8>, b: &ComponentBundle<C>) -> Result<()> {
+            signatures(o, b.self_revocations2())?;
+            signatures(o, b.self_signatures2())?;
+            signatures(o, b.attestations())?;
+            signatures(o, b.certifications2())?;
+            signatures(o, b.other_revocations2())?;
+            Ok(())
+        }
+
+        // Serializes sorted components.
+        fn components<'a, C: 'a, K: Ord>(
+            o: &mut Vec<u8>,
+            mut components: Vec<(K, Vec<u8>, &'a ComponentBundle<C>)>)
+            -> Result<()>
+        {
+            components.sort_by(|(a, a_packet, _), (b, b_packet, _)| {
+                a.cmp(b).then_with(|| a_packet.cmp(b_packet))
+            });
+            for (_, p, b) in components {
+                o.extend_from_slice(&p);
+                bundle(o, b)?;
+            }
+            Ok(())
+        }
+
+        let mut o = Vec::new();
+
+        let primary = self.primary_key();
+        o.extend_from_slice(&packet(PacketRef::PublicKey(primary.key()))?);
+        bundle(&mut o, primary.bundle())?;
+
+        components(&mut o, self.userids()
+                   .map(|u| Ok((u.userid().value().to_vec(),
+                                packet(PacketRef::UserID(u.userid()))?,
+                                u.bundle())))
+                   .collect::<Result<Vec<_>>>()?)?;
+
+        components(&mut o, self.user_attributes()
+                   .map(|u| Ok((u.user_attribute().value().to_vec(),
+                                packet(PacketRef::UserAttribute(
+                                    u.user_attribute()))?,
+                                u.bundle())))
+                   .collect::<Result<Vec<_>>>()?)?;
+
+        components(&mut o, self.subkeys()
+                   .map(|k| Ok((k.key().fingerprint().as_bytes().to_vec(),
+                                packet(PacketRef::PublicSubkey(k.key()))?,
+                                k.bundle())))
+                   .collect::<Result<Vec<_>>>()?)?;
+
+        components(&mut o, self.unknowns()
+                   .map(|u| Ok(((),
+                                packet(PacketRef::Unknown(u.unknown()))?,
+                                u.bundle())))
+                   .collect::<Result<Vec<_>>>()?)?;
+
+        signatures(&mut o, self.bad_signatures())?;
+
+        Ok(o)
+    }
 }
 
 /// A reference to a `Cert` that allows serialization of secret keys.
@@ -1167,4 +1328,42 @@ mod test {
 
         Ok(())
     }
+
+    /// Checks that the canonical serialization doesn't depend on
+    /// the order of the packets, and doesn't change.
+    #[test]
+    fn canonical_serialization() -> Result<()> {
+        use crate::Packet;
+        use crate::crypto::hash::Digest;
+        use crate::types::HashAlgorithm;
+
+        let cert = Cert::from_bytes(crate::tests::key("neal.pgp"))?;
+        let canonical = cert.canonical_serialization()?;
+        assert_eq!(Cert::from_bytes(&canonical)?.canonical_serialization()?,
+                   canonical);
+
+        // Reverse the order of the components, and of the signatures
+        // of each component.
+        let mut components: Vec<Vec<Packet>> = Vec::new();
+        for p in cert.clone().into_packets2() {
+            match p {
+                Packet::Signature(_) => components.last_mut().unwrap().push(p),
+                p => components.push(vec![p]),
+            }
+        }
+        components[1..].reverse();
+        components.iter_mut().for_each(|c| c[1..].reverse());
+        let cert_ = Cert::from_packets(components.into_iter().flatten())?;
+        assert_eq!(cert_.canonical_serialization()?, canonical);
+
+        // The canonical serialization must not change between
+        // versions.
+        let mut h = HashAlgorithm::SHA256.context()?;
+        h.update(&canonical);
+        let mut digest = vec![0; 32];
+        h.digest(&mut digest)?;
+        assert_eq!(crate::fmt::hex::encode(&digest),
+                   "BBB54F7F01289922A35ABCCF8B949838095F4ACFCDBC156D6A77E45CD55FD396");
+        Ok(())
+    }
 }
@@ -0,0 +1,218 @@
+//! Streaming keyring serialization.
+use std::io;
+
+use crate::armor;
+use crate::cert::Cert;
+use crate::Result;
+use crate::serialize::Marshal;
+
+/// Writes a keyring, one certificate at a time.
+///
+/// This is the counterpart to [`CertParser`]: certificates are
+/// serialized as they are handed to the writer, and written to the
+/// sink right away, so that exporting a large number of certificates
+/// doesn't require holding them all in memory.
+///
+/// The keyring is either emitted in binary form (see
+/// [`KeyringWriter::new`]), or as a single ASCII Armor block (see
+/// [`KeyringWriter::armored`]).  Note that ASCII Armor only allows
+/// comments in the armor header, i.e. before any certificate has been
+/// written.  Comments describing the individual certificates, like
+/// those returned by [`Cert::armor_headers`], must therefore be
+/// given up front using [`KeyringWriter::armored_with_headers`].
+///
+///   [`CertParser`]: crate::cert::CertParser
+///
+/// # Examples
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::parse::Parse;
+/// use openpgp::serialize::KeyringWriter;
+///
+/// # fn main() -> openpgp::Result<()> {
+/// let (alice, _) =
+///     CertBuilder::general_purpose(None, Some("alice@example.org"))
+///     .generate()?;
+/// let (bob, _) =
+///     CertBuilder::general_purpose(None, Some("bob@example.org"))
+///     .generate()?;
+///
+/// let mut writer = KeyringWriter::armored(Vec::new())?;
+/// for cert in [&alice, &bob] {
+///     writer.export(cert)?;
+/// }
+/// assert_eq!(writer.count(), 2);
+/// let keyring = writer.finalize()?;
+///
+/// let certs = CertParser::from_bytes(&keyring)?
+///     .collect::<openpgp::Result<Vec<_>>>()?;
+/// assert_eq!(certs, vec![alice, bob]);
+/// # Ok(()) }
+/// ```
+pub struct KeyringWriter<W: io::Write> {
+    sink: Sink<W>,
+    count: usize,
+}
+assert_send_and_sync!(KeyringWriter<W> where W: io::Write);
+
+/// Where the certificates are written to.
+enum Sink<W: io::Write> {
+    Binary(W),
+    Armored(armor::Writer<W>),
+}
+
+impl<W: io::Write> KeyringWriter<W> {
+    /// Returns a writer emitting a binary keyring to `sink`.
+    pub fn new(sink: W) -> Self {
+        KeyringWriter {
+            sink: Sink::Binary(sink),
+            count: 0,
+        }
+    }
+
+    /// Returns a writer emitting an ASCII armored keyring to `sink`.
+    pub fn armored(sink: W) -> Result<Self> {
+        Self::armored_with_headers(sink, Option::<(&str, &str)>::None)
+    }
+
+    /// Returns a writer emitting an ASCII armored keyring with the
+    /// given armor headers to `sink`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::serialize::KeyringWriter;
+    ///
+    /// # fn main() -> openpgp::Result<()> {
+    /// let (cert, _) = CertBuilder::new().generate()?;
+    ///
+    /// let mut writer = KeyringWriter::armored_with_headers(
+    ///     Vec::new(),
+    ///     cert.armor_headers().iter().map(|c| ("Comment", c)))?;
+    /// writer.export(&cert)?;
+    /// let keyring = String::from_utf8(writer.finalize()?)?;
+    ///
+    /// assert!(keyring.contains(
+    ///     &format!("Comment: {}", cert.fingerprint().to_spaced_hex())));
+    /// # Ok(()) }
+    /// ```
+    pub fn armored_with_headers<I, K, V>(sink: W, headers: I) -> Result<Self>
+        where I: IntoIterator<Item = (K, V)>,
+              K: AsRef<str>,
+              V: AsRef<str>,
+    {
+        Ok(KeyringWriter {
+            sink: Sink::Armored(armor::Writer::with_headers(
+                sink, armor::Kind::PublicKey, headers)?),
+            count: 0,
+        })
+    }
+
+    /// Writes the exportable parts of `cert` to the keyring.
+    ///
+    /// Certificates that are not exportable are skipped, see
+    /// [`Cert::exportable`].  Returns whether `cert` has been
+    /// written.
+    pub fn export(&mut self, cert: &Cert) -> Result<bool> {
+        if ! cert.exportable() {
+            return Ok(false);
+        }
+
+        match &mut self.sink {
+            Sink::Binary(w) => cert.export(w)?,
+            Sink::Armored(w) => cert.export(w)?,
+        }
+        self.count += 1;
+        Ok(true)
+    }
+
+    /// Returns the number of certificates written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Flushes the underlying sink.
+    pub fn flush(&mut self) -> Result<()> {
+        match &mut self.sink {
+            Sink::Binary(w) => w.flush()?,
+            Sink::Armored(w) => io::Write::flush(w)?,
+        }
+        Ok(())
+    }
+
+    /// Finishes the keyring, and returns the sink.
+    ///
+    /// For armored keyrings, this writes the armor footer.  If no
+    /// certificate has been written, nothing is emitted.
+    pub fn finalize(self) -> Result<W> {
+        match self.sink {
+            Sink::Binary(w) => Ok(w),
+            Sink::Armored(w) => Ok(w.finalize()?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cert::prelude::*;
+    use crate::parse::Parse;
+    use crate::serialize::SerializeInto;
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let certs = (0..3).map(|i| {
+            CertBuilder::general_purpose(
+                None, Some(format!("<{}@example.org>", i)))
+                .generate().map(|(cert, _)| cert)
+        }).collect::<Result<Vec<_>>>()?;
+
+        // Binary.
+        let mut writer = KeyringWriter::new(Vec::new());
+        for cert in &certs {
+            assert!(writer.export(cert)?);
+        }
+        assert_eq!(writer.count(), 3);
+        let keyring = writer.finalize()?;
+        let expected = certs.iter().map(|c| c.export_to_vec())
+            .collect::<Result<Vec<_>>>()?.concat();
+        assert_eq!(keyring, expected);
+
+        // Armored.
+        let mut writer = KeyringWriter::armored(Vec::new())?;
+        for cert in &certs {
+            writer.export(cert)?;
+        }
+        let keyring = writer.finalize()?;
+        assert!(keyring.starts_with(b"-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+        assert_eq!(keyring.windows(6).filter(|w| w == b"-----B").count(), 1);
+        let parsed = CertParser::from_bytes(&keyring)?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(parsed, certs);
+
+        // Nothing written, nothing emitted.
+        let writer = KeyringWriter::armored(Vec::new())?;
+        assert!(writer.finalize()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn non_exportable() -> Result<()> {
+        let (cert, _) = CertBuilder::new()
+            .set_exportable(false)
+            .generate()?;
+        assert!(! cert.exportable());
+
+        let mut writer = KeyringWriter::new(Vec::new());
+        assert!(! writer.export(&cert)?);
+        assert_eq!(writer.count(), 0);
+        assert!(writer.finalize()?.is_empty());
+        Ok(())
+    }
+}
@@ -24,6 +24,9 @@
 //!   - and finally, [`ArbitraryWriter`] can be used to create
 //!     arbitrary packets for testing purposes.
 //!
+//! Custom filters can be inserted into the writer stack using the
+//! [`filter`] module.
+//!
 //!   [`io::Write`]: std::io::Write
 //!   [`Message::new`]: Message::new()
 //!   [`Padder`]: padding::Padder
@@ -132,18 +135,23 @@ use crate::{
     packet::signature,
     packet::key,
     cert::prelude::*,
+    cert::Preferences,
 };
 use crate::packet::header::CTB;
 use crate::packet::header::BodyLength;
 use crate::parse::HashingMode;
+use crate::utils::Progress;
 use super::{
     Marshal,
 };
+use crate::policy::Policy;
 use crate::types::{
     AEADAlgorithm,
     CompressionAlgorithm,
     CompressionLevel,
     DataFormat,
+    Features,
+    Profile,
     SignatureType,
     SymmetricAlgorithm,
 };
@@ -151,12 +159,15 @@ use crate::types::{
 pub(crate) mod writer;
 #[cfg(feature = "compression-deflate")]
 pub mod padding;
+pub mod filter;
 mod partial_body;
 use partial_body::PartialBodyFilter;
 mod dash_escape;
 use dash_escape::DashEscapeFilter;
 mod trim_whitespace;
 use trim_whitespace::TrailingWSFilter;
+mod reencrypt;
+pub use reencrypt::add_recipients;
 
 
 /// Cookie must be public because the writers are.
@@ -164,12 +175,25 @@ use trim_whitespace::TrailingWSFilter;
 struct Cookie {
     level: usize,
     private: Private,
+    /// The recipients of an encryption container.
+    recipients: Vec<EncryptedRecipient>,
 }
 
 #[derive(Debug)]
 enum Private {
     Nothing,
     Signer,
+    /// The body of a literal data packet.
+    Literal,
+    /// The text of a message using the Cleartext Signature
+    /// Framework.
+    Cleartext,
+    /// The body of a container, and the position of the writer
+    /// when the container was created.
+    Container {
+        name: &'static str,
+        position: u64,
+    },
 }
 
 impl Cookie {
@@ -177,6 +201,27 @@ impl Cookie {
         Cookie {
             level,
             private: Private::Nothing,
+            recipients: Vec::new(),
+        }
+    }
+
+    /// Checks whether a writer may be pushed on top of the writer
+    /// owning this cookie.
+    ///
+    /// `name` is the name of the new writer, and is used in the
+    /// error message.
+    fn check_nesting(&self, name: &str) -> Result<()> {
+        match self.private {
+            Private::Literal =>
+                Err(Error::InvalidOperation(format!(
+                    "Cannot put a {} inside a LiteralWriter, \
+                     finalize the LiteralWriter first", name)).into()),
+            Private::Cleartext =>
+                Err(Error::InvalidOperation(format!(
+                    "Cannot put a {} inside a cleartext signature, \
+                     cleartext signatures can only contain text", name))
+                    .into()),
+            _ => Ok(()),
         }
     }
 }
@@ -289,7 +334,10 @@ impl<'a> Message<'a> {
     /// # Note
     ///
     /// Failing to finalize the message may result in corrupted
-    /// messages.
+    /// messages.  To also check that no layer of the message is
+    /// unfinished, use [`Message::finalize_all`].
+    ///
+    ///   [`Message::finalize_all`]: Message::finalize_all()
     ///
     /// # Examples
     ///
@@ -315,6 +363,135 @@ impl<'a> Message<'a> {
         }
         Ok(())
     }
+
+    /// Finalizes the message, checking that no layer is unfinished.
+    ///
+    /// Like [`Message::finalize`], this finalizes all writers on the
+    /// stack.  Additionally, it checks that something has been
+    /// written to every container, i.e. every [`Compressor`] and
+    /// every [`Encryptor2`].  An empty container usually means that
+    /// the [`LiteralWriter`] has been forgotten, or that data has
+    /// been written to the wrong writer.  If a container is empty,
+    /// all writers are still finalized, but an
+    /// [`Error::InvalidOperation`] is returned.
+    ///
+    ///   [`Message::finalize`]: Message::finalize()
+    ///   [`Error::InvalidOperation`]: crate::Error::InvalidOperation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::serialize::stream::{Message, Compressor, LiteralWriter};
+    ///
+    /// # let mut sink = vec![]; // Vec<u8> implements io::Write.
+    /// let message = Message::new(&mut sink);
+    /// let message = Compressor::new(message).build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Hello world.")?;
+    /// message.finalize_all()?;
+    ///
+    /// // Forgetting the LiteralWriter is detected.
+    /// let message = Message::new(&mut sink);
+    /// let message = Compressor::new(message).build()?;
+    /// assert!(message.finalize_all().is_err());
+    /// # Ok(()) }
+    /// ```
+    pub fn finalize_all(self) -> Result<()> {
+        let mut error = None;
+        let mut stack = self;
+        loop {
+            if let Private::Container { name, position } =
+                stack.0.cookie_ref().private
+            {
+                if error.is_none() && stack.0.position() == position {
+                    error = Some(Error::InvalidOperation(format!(
+                        "Nothing has been written to the {}", name)));
+                }
+            }
+
+            match stack.finalize_one()? {
+                Some(s) => stack = s,
+                None => break,
+            }
+        }
+
+        match error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Marks the topmost writer as the body of a container.
+    ///
+    /// See [`Message::finalize_all`].
+    fn set_container(&mut self, name: &'static str) {
+        let position = self.0.position();
+        self.0.cookie_mut().private = Private::Container { name, position };
+    }
+
+    /// Returns the recipients the message is encrypted to.
+    ///
+    /// This returns the recipients of all [`Encryptor2`]s on the
+    /// writer stack, i.e. the keys that the session keys have
+    /// actually been encrypted to, starting with the innermost
+    /// encryption container.  This is useful to record who a message
+    /// was encrypted for, e.g. in an audit log.  Recipients of
+    /// encryption containers that have already been finalized are
+    /// not returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::serialize::stream::{
+    ///     EncryptionMode, Encryptor2, LiteralWriter, Message, Recipient,
+    /// };
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// let (cert, _) = CertBuilder::new()
+    ///     .add_transport_encryption_subkey()
+    ///     .generate()?;
+    /// let vc = cert.with_policy(p, None)?;
+    ///
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message = Encryptor2::for_recipients(
+    ///     message, Recipient::for_cert(&vc, EncryptionMode::Transport)?)
+    ///     .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Hello world.")?;
+    ///
+    /// let recipients = message.recipients();
+    /// assert_eq!(recipients.len(), 1);
+    /// assert_eq!(recipients[0].cert_fingerprint(), Some(&cert.fingerprint()));
+    /// message.finalize()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn recipients(&self) -> Vec<EncryptedRecipient> {
+        let mut recipients = Vec::new();
+        let mut layer: Option<&(dyn writer::Stackable<'a, Cookie> + Send + Sync)>
+            = Some(self.0.as_ref());
+        while let Some(l) = layer {
+            recipients.extend(l.cookie_ref().recipients.iter().cloned());
+            layer = l.inner_ref();
+        }
+        recipients
+    }
+
+    /// Records the recipients of the topmost writer.
+    ///
+    /// See [`Message::recipients`].
+    fn set_recipients(&mut self, recipients: Vec<EncryptedRecipient>) {
+        self.0.cookie_mut().recipients = recipients;
+    }
 }
 
 impl<'a> From<&'a mut (dyn io::Write + Send + Sync)> for Message<'a> {
@@ -499,6 +676,7 @@ impl<'a> Armorer<'a> {
     ///     .build()?;
     /// # Ok(()) }
     pub fn build(self) -> Result<Message<'a>> {
+        self.inner.as_ref().cookie_ref().check_nesting("Armorer")?;
         let level = self.inner.as_ref().cookie_ref().level;
         writer::Armorer::new(
             self.inner,
@@ -632,13 +810,15 @@ pub struct Signer<'a> {
     // digests.
     inner: Option<writer::BoxStack<'a, Cookie>>,
     signers: Vec<(Box<dyn crypto::Signer + Send + Sync + 'a>,
-                  HashAlgorithm, Vec<u8>)>,
+                  Vec<HashAlgorithm>, Vec<u8>)>,
     intended_recipients: Vec<Fingerprint>,
     mode: SignatureMode,
     template: signature::SignatureBuilder,
     creation_time: Option<SystemTime>,
-    hash_algo: HashAlgorithm,
+    hash_algos: Vec<HashAlgorithm>,
+    profile: Profile,
     hashes: Vec<HashingMode<Box<dyn crypto::hash::Digest>>>,
+    progress: Option<Progress<'a>>,
     cookie: Cookie,
     position: u64,
 }
@@ -747,14 +927,15 @@ impl<'a> Signer<'a> {
     /// intended recipients are added (see
     /// [`Signer::add_intended_recipient`]), the issuer and issuer
     /// fingerprint subpackets are set according to the signing key,
-    /// and the hash algorithm set using [`Signer::hash_algo`] is used
-    /// to create the signature.
+    /// and the hash algorithms set using [`Signer::hash_algo`] and
+    /// [`Signer::add_hash_algo`] are used to create the signatures.
     ///
     ///   [`crypto::Signer`]: super::super::crypto::Signer
     ///   [`Signer::new`]: Message::new()
     ///   [`signature::SignatureBuilder`]: crate::packet::signature::SignatureBuilder
     ///   [`Signer::creation_time`]: Signer::creation_time()
     ///   [`Signer::hash_algo`]: Signer::hash_algo()
+    ///   [`Signer::add_hash_algo`]: Signer::add_hash_algo()
     ///   [`Signer::add_intended_recipient`]: Signer::add_intended_recipient()
     ///
     /// # Examples
@@ -809,11 +990,14 @@ impl<'a> Signer<'a> {
             mode: SignatureMode::Inline,
             template: template.into(),
             creation_time: None,
-            hash_algo: Default::default(),
+            hash_algos: vec![Default::default()],
+            profile: Default::default(),
             hashes: vec![],
+            progress: None,
             cookie: Cookie {
                 level,
                 private: Private::Signer,
+                recipients: Vec::new(),
             },
             position: 0,
         }
@@ -1144,10 +1328,135 @@ impl<'a> Signer<'a> {
     /// # Ok(()) }
     /// ```
     pub fn hash_algo(mut self, algo: HashAlgorithm) -> Result<Self> {
-        self.hash_algo = algo;
+        self.hash_algos = vec![algo];
+        Ok(self)
+    }
+
+    /// Adds a hash algorithm to use for the signatures.
+    ///
+    /// By default, every signer makes one signature.  This method
+    /// adds another hash algorithm in addition to the one set using
+    /// [`Signer::hash_algo`].  Then, every signer makes one
+    /// signature per hash algorithm it supports (see
+    /// [`crate::crypto::Signer.acceptable_hashes`]).  This is useful
+    /// if some recipients only support a subset of the hash
+    /// algorithms.  The message is hashed only once per algorithm.
+    ///
+    /// If none of the hash algorithms is supported by a signer, a
+    /// hash supported by the signer is selected instead.
+    ///
+    ///   [`Signer::hash_algo`]: Signer::hash_algo()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::types::HashAlgorithm;
+    /// use openpgp::serialize::stream::{Message, Signer, LiteralWriter};
+    /// # use openpgp::policy::StandardPolicy;
+    /// # use openpgp::{Result, Cert};
+    /// # use openpgp::packet::prelude::*;
+    /// # use openpgp::parse::Parse;
+    /// # use openpgp::parse::stream::*;
+    ///
+    /// # let p = &StandardPolicy::new();
+    /// # let cert = Cert::from_bytes(&include_bytes!(
+    /// #     "../../tests/data/keys/testy-new-private.pgp")[..])?;
+    /// # let signing_keypair = cert.keys().secret()
+    /// #     .with_policy(p, None).supported().alive().revoked(false).for_signing()
+    /// #     .nth(0).unwrap()
+    /// #     .key().clone().into_keypair()?;
+    ///
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message = Signer::new(message, signing_keypair)
+    ///     .hash_algo(HashAlgorithm::SHA256)?
+    ///     .add_hash_algo(HashAlgorithm::SHA512)?
+    ///     .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Make it so, number one!")?;
+    /// message.finalize()?;
+    /// # let pile = openpgp::PacketPile::from_bytes(&sink)?;
+    /// # assert_eq!(pile.children()
+    /// #            .filter(|p| p.tag() == openpgp::packet::Tag::Signature)
+    /// #            .count(), 2);
+    /// # Ok(()) }
+    /// ```
+    pub fn add_hash_algo(mut self, algo: HashAlgorithm) -> Result<Self> {
+        if ! self.hash_algos.contains(&algo) {
+            self.hash_algos.push(algo);
+        }
         Ok(self)
     }
 
+    /// Sets the profile.
+    ///
+    /// The profile restricts the hash algorithms that are used to
+    /// create the signatures, see [`Profile`] for details.  Hash
+    /// algorithms configured using [`Signer::hash_algo`] and
+    /// [`Signer::add_hash_algo`] that are not allowed by the profile
+    /// are ignored.  If a signing key uses an algorithm that the
+    /// profile does not allow, [`Signer::build`] fails.
+    ///
+    /// By default, [`Profile::RFC4880bis`] is used.
+    ///
+    ///   [`Signer::hash_algo`]: Signer::hash_algo()
+    ///   [`Signer::add_hash_algo`]: Signer::add_hash_algo()
+    ///   [`Signer::build`]: Signer::build()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::types::{HashAlgorithm, Profile};
+    /// use openpgp::serialize::stream::{Message, Signer, LiteralWriter};
+    /// use openpgp::policy::StandardPolicy;
+    /// # use openpgp::{Result, Cert};
+    /// # use openpgp::packet::prelude::*;
+    /// # use openpgp::parse::Parse;
+    ///
+    /// let p = &StandardPolicy::new();
+    /// let cert: Cert = // ...
+    /// #     Cert::from_bytes(&include_bytes!(
+    /// #     "../../tests/data/keys/testy-new-private.pgp")[..])?;
+    /// let signing_keypair = cert.keys().secret()
+    ///     .with_policy(p, None).supported().alive().revoked(false).for_signing()
+    ///     .nth(0).unwrap()
+    ///     .key().clone().into_keypair()?;
+    ///
+    /// let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message = Signer::new(message, signing_keypair)
+    ///     .hash_algo(HashAlgorithm::SHA3_512)?
+    ///     .add_hash_algo(HashAlgorithm::SHA256)?
+    ///     .profile(Profile::GnuPG22)
+    ///     .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Make it so, number one!")?;
+    /// message.finalize()?;
+    ///
+    /// // GnuPG 2.2 does not support SHA-3.
+    /// let pile = openpgp::PacketPile::from_bytes(&sink)?;
+    /// let sigs = pile.children()
+    ///     .filter_map(|p| if let Packet::Signature(s) = p {
+    ///         Some(s)
+    ///     } else {
+    ///         None
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(sigs.len(), 1);
+    /// assert_eq!(sigs[0].hash_algo(), HashAlgorithm::SHA256);
+    /// # Ok(()) }
+    /// ```
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
     /// Sets the signature's creation time to `time`.
     ///
     /// Note: it is up to the caller to make sure the signing keys are
@@ -1195,6 +1504,51 @@ impl<'a> Signer<'a> {
         self
     }
 
+    /// Reports progress to `callback`.
+    ///
+    /// `callback` is invoked with the total number of bytes signed so
+    /// far whenever at least `interval` bytes have been signed since
+    /// the last invocation.  This is useful to display progress
+    /// when signing large files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::serialize::stream::{Message, Signer, LiteralWriter};
+    /// # use openpgp::policy::StandardPolicy;
+    /// # use openpgp::{Result, Cert};
+    /// # use openpgp::parse::Parse;
+    ///
+    /// # let p = &StandardPolicy::new();
+    /// # let cert: Cert = // ...
+    /// #     Cert::from_bytes(&include_bytes!(
+    /// #     "../../tests/data/keys/testy-new-private.pgp")[..])?;
+    /// # let signing_keypair
+    /// #     = cert.keys().secret()
+    /// #           .with_policy(p, None).supported().alive().revoked(false).for_signing()
+    /// #           .nth(0).unwrap()
+    /// #           .key().clone().into_keypair()?;
+    /// #
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message = Signer::new(message, signing_keypair)
+    ///     .progress(1 << 20, |n| eprint!("\rSigned {} MiB", n >> 20))
+    ///     .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(&vec![0; 4 << 20])?;
+    /// message.finalize()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn progress<F>(mut self, interval: u64, callback: F) -> Self
+        where F: FnMut(u64) + Send + Sync + 'a
+    {
+        self.progress = Some(Progress::new(interval, callback));
+        self
+    }
+
     /// Builds the signer, returning the writer stack.
     ///
     /// The most useful filter to push to the writer stack next is the
@@ -1243,16 +1597,11 @@ impl<'a> Signer<'a> {
     {
         assert!(!self.signers.is_empty(), "The constructor adds a signer.");
         assert!(self.inner.is_some(), "The constructor adds an inner writer.");
+        if let Some(inner) = self.inner.as_ref() {
+            inner.cookie_ref().check_nesting("Signer")?;
+        }
 
-        for (keypair, signer_hash, _signer_salt) in self.signers.iter_mut() {
-            // First, compute a suitable hash algorithm, starting with
-            // the one configured using Self::hash_algo.
-            let mut acceptable_hashes =
-                std::iter::once(&self.hash_algo)
-                .chain(crate::crypto::hash::DEFAULT_HASHES.iter())
-                .cloned()
-                .collect::<Vec<_>>();
-
+        for (keypair, signer_hashes_out, _signer_salt) in self.signers.iter_mut() {
             let is_sorted = |data: &[HashAlgorithm]| {
                 data.windows(2).all(|w| w[0] <= w[1])
             };
@@ -1264,41 +1613,75 @@ impl<'a> Signer<'a> {
                 signer_hashes_.sort();
                 signer_hashes = &signer_hashes_;
             }
-            acceptable_hashes.retain(
-                |hash| signer_hashes.binary_search(hash).is_ok());
+            let profile = self.profile;
+            let acceptable = |hash: &&HashAlgorithm| {
+                hash.is_supported()
+                    && signer_hashes.binary_search(hash).is_ok()
+                    && profile.check_hash_algorithm(**hash).is_ok()
+            };
 
-            if acceptable_hashes.is_empty() {
+            // First, compute the suitable hash algorithms, i.e. the
+            // ones configured using Self::hash_algo and
+            // Self::add_hash_algo.  If none of them is acceptable,
+            // fall back to the default hashes.  SHA3 is only used if
+            // explicitly requested.
+            let mut algos = self.hash_algos.iter()
+                .filter(acceptable)
+                .cloned()
+                .collect::<Vec<_>>();
+            if algos.is_empty() {
+                algos.extend(crate::crypto::hash::DEFAULT_HASHES.iter()
+                             .filter(|hash| ! matches!(
+                                 hash,
+                                 HashAlgorithm::SHA3_256
+                                     | HashAlgorithm::SHA3_512))
+                             .find(acceptable));
+            }
+            if algos.is_empty() {
                 return Err(Error::NoAcceptableHash.into());
             }
-            let algo = acceptable_hashes[0];
-            *signer_hash = algo;
-            let hash = algo.context()?;
 
             match keypair.public().version() {
-                4 => {
-                    self.hashes.push(
-                        if self.template.typ() == SignatureType::Text
-                            || self.mode == SignatureMode::Cleartext
-                        {
-                            HashingMode::Text(hash)
-                        } else {
-                            HashingMode::Binary(hash)
-                        });
-                },
+                4 => (),
                 v => return Err(Error::InvalidOperation(
                     format!("Unsupported Key version {}", v)).into()),
             }
+            self.profile.check_key(keypair.public())?;
+
+            // Hash the message only once per algorithm.
+            for algo in &algos {
+                if self.hashes.iter().any(|h| h.as_ref().algo() == *algo) {
+                    continue;
+                }
+
+                let hash = algo.context()?;
+                self.hashes.push(
+                    if self.template.typ() == SignatureType::Text
+                        || self.mode == SignatureMode::Cleartext
+                    {
+                        HashingMode::Text(hash)
+                    } else {
+                        HashingMode::Binary(hash)
+                    });
+            }
+            *signer_hashes_out = algos;
         }
 
         match self.mode {
             SignatureMode::Inline => {
-                // For every key we collected, build and emit a one pass
-                // signature packet.
-                let signers_count = self.signers.len();
-                for (i, (keypair, hash_algo, _salt)) in
-                    self.signers.iter().enumerate()
+                // For every key and hash algorithm we collected,
+                // build and emit a one pass signature packet.
+                let signatures_count = self.signers.iter()
+                    .map(|(_, algos, _)| algos.len())
+                    .sum::<usize>();
+                for (i, (keypair, hash_algo)) in
+                    self.signers.iter()
+                    .flat_map(|(keypair, algos, _)| {
+                        algos.iter().map(move |algo| (keypair, algo))
+                    })
+                    .enumerate()
                 {
-                    let last = i == signers_count - 1;
+                    let last = i == signatures_count - 1;
                     let key = keypair.public();
 
                     match key.version() {
@@ -1308,8 +1691,9 @@ impl<'a> Signer<'a> {
                             ops.set_hash_algo(*hash_algo);
                             ops.set_issuer(key.keyid());
                             ops.set_last(last);
-                            Packet::from(ops)
-                                .serialize(self.inner.as_mut().unwrap())?;
+                            let ops = Packet::from(ops);
+                            self.profile.check_packet(&ops)?;
+                            ops.serialize(self.inner.as_mut().unwrap())?;
                         },
                         v => return Err(Error::InvalidOperation(
                             format!("Unsupported Key version {}", v)).into()),
@@ -1325,11 +1709,12 @@ impl<'a> Signer<'a> {
                 let mut sink = self.inner.take().unwrap();
                 writeln!(sink, "-----BEGIN PGP SIGNED MESSAGE-----")?;
                 let mut hashes = self.signers.iter().filter_map(
-                    |(keypair, algo, _)| if keypair.public().version() == 4 {
-                        Some(algo)
+                    |(keypair, algos, _)| if keypair.public().version() == 4 {
+                        Some(algos)
                     } else {
                         None
                     })
+                    .flatten()
                     .collect::<Vec<_>>();
                 hashes.sort();
                 hashes.dedup();
@@ -1349,14 +1734,133 @@ impl<'a> Signer<'a> {
 
                 // Install the filter trimming the trailing whitespace
                 // above us.
+                let level = self.cookie.level + 1;
                 return Ok(TrailingWSFilter::new(Message::from(Box::new(self)),
-                                                Default::default()));
+                                                Cookie {
+                                                    level,
+                                                    private: Private::Cleartext,
+                                                    recipients: Vec::new(),
+                                                }));
             },
         }
 
         Ok(Message::from(Box::new(self)))
     }
 
+    /// Notarizes an existing message.
+    ///
+    /// Reads the OpenPGP message from `message`, and writes it out
+    /// wrapped in a new signature layer.  The new signatures are
+    /// notarizations (see [`Signature::level`]), i.e. they are over
+    /// the message's signatures and its literal data.  The signatures
+    /// in the existing message are left untouched.  If the message
+    /// is not signed, the new signatures are ordinary signatures.
+    ///
+    ///   [`Signature::level`]: crate::packet::Signature#method.level
+    ///
+    /// `message` may be ASCII armored.  If the existing message is
+    /// compressed, the compression is removed.  Encrypted messages
+    /// cannot be notarized.
+    ///
+    /// Returns the `Message` the signer was built upon, e.g. to
+    /// finalize an [`Armorer`].  Notarizing detached signatures or
+    /// cleartext signed messages is not supported.
+    ///
+    ///   [`Armorer`]: Armorer
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::policy::StandardPolicy;
+    /// use openpgp::serialize::stream::{Message, Signer, LiteralWriter};
+    ///
+    /// let p = &StandardPolicy::new();
+    /// let (alice, _) =
+    ///     CertBuilder::general_purpose(None, Some("alice@example.org"))
+    ///     .generate()?;
+    /// let (notary, _) =
+    ///     CertBuilder::general_purpose(None, Some("notary@example.org"))
+    ///     .generate()?;
+    /// let keypair = |cert: &Cert| -> openpgp::Result<_> {
+    ///     cert.keys().with_policy(p, None).secret().for_signing()
+    ///         .next().unwrap().key().clone().into_keypair()
+    /// };
+    ///
+    /// // Alice signs a message.
+    /// let mut signed = vec![];
+    /// let message = Message::new(&mut signed);
+    /// let message = Signer::new(message, keypair(&alice)?).build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"I, Alice, hereby ...")?;
+    /// message.finalize()?;
+    ///
+    /// // The notary notarizes it.
+    /// let mut notarized = vec![];
+    /// let message = Message::new(&mut notarized);
+    /// let message = Signer::new(message, keypair(&notary)?)
+    ///     .notarize(&signed[..])?;
+    /// message.finalize()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn notarize<R>(self, message: R) -> Result<Message<'a>>
+        where R: io::Read + Send + Sync
+    {
+        use crate::parse::{Parse, PacketParser, PacketParserResult};
+
+        if self.mode != SignatureMode::Inline {
+            return Err(Error::InvalidOperation(
+                "Only inline signatures can be notarized".into()).into());
+        }
+
+        let mut sink = self.build()?;
+
+        // Inner one-pass signature and signature packets are written
+        // through the signer, so that they are hashed.  Literal data
+        // packets are framed using a LiteralWriter, which only hashes
+        // the body.
+        let mut ppr = PacketParser::from_reader(message)?;
+        while let PacketParserResult::Some(mut pp) = ppr {
+            match &pp.packet {
+                Packet::OnePassSig(_) | Packet::Signature(_) =>
+                    pp.packet.serialize(&mut sink)?,
+                Packet::Literal(l) => {
+                    let mut literal = LiteralWriter::new(sink)
+                        .format(l.format());
+                    if let Some(filename) = l.filename() {
+                        literal = literal.filename(filename)?;
+                    }
+                    if let Some(date) = l.date() {
+                        literal = literal.date(date)?;
+                    }
+                    let mut literal = literal.build()?;
+                    io::copy(&mut pp, &mut literal)?;
+                    sink = literal.finalize_one()?
+                        .expect("the LiteralWriter has an inner writer");
+                },
+                // Recurse into compressed data packets.
+                Packet::CompressedData(_) | Packet::Marker(_) => (),
+                Packet::PKESK(_) | Packet::SKESK(_) | Packet::SEIP(_)
+                    | Packet::AED(_) =>
+                    return Err(Error::InvalidOperation(
+                        "Cannot notarize encrypted messages".into()).into()),
+                p => return Err(Error::MalformedMessage(
+                    format!("Unexpected {} packet", p.tag())).into()),
+            }
+            ppr = pp.recurse()?.1;
+        }
+
+        if let PacketParserResult::EOF(eof) = ppr {
+            eof.is_message()?;
+        }
+
+        Ok(sink.finalize_one()?
+           .expect("the Signer has an inner writer"))
+    }
+
     fn emit_signatures(&mut self) -> Result<()> {
         if self.mode == SignatureMode::Cleartext {
             // Pop off the DashEscapeFilter.
@@ -1380,48 +1884,52 @@ impl<'a> Signer<'a> {
             // Emit the signatures in reverse, so that the
             // one-pass-signature and signature packets "bracket" the
             // message.
-            for (signer, algo, _signer_salt) in self.signers.iter_mut().rev() {
-                let (mut sig, hash) = match signer.public().version() {
-                    4 => {
-                        // V4 signature.
-
-                        let hash = self.hashes.iter()
-                            .find_map(|hash| {
-                                if hash.as_ref().algo() == *algo
-                                {
-                                    Some(hash.clone())
-                                } else {
-                                    None
-                                }
-                            })
-                            .expect("we put it in there");
-
-                        // Make and hash a signature packet.
-                        let sig = self.template.clone();
-
-                        (sig, hash)
-                    },
-                    v => return Err(Error::InvalidOperation(
-                        format!("Unsupported Key version {}", v)).into()),
-                };
+            for (signer, algos, _signer_salt) in self.signers.iter_mut().rev() {
+                for algo in algos.iter().rev() {
+                    let (mut sig, hash) = match signer.public().version() {
+                        4 => {
+                            // V4 signature.
 
-                sig = sig.set_signature_creation_time(
-                    self.creation_time
-                        .unwrap_or_else(crate::now))?;
+                            let hash = self.hashes.iter()
+                                .find_map(|hash| {
+                                    if hash.as_ref().algo() == *algo
+                                    {
+                                        Some(hash.clone())
+                                    } else {
+                                        None
+                                    }
+                                })
+                                .expect("we put it in there");
 
-                if ! self.intended_recipients.is_empty() {
-                    sig = sig.set_intended_recipients(
-                        self.intended_recipients.clone())?;
-                }
+                            // Make and hash a signature packet.
+                            let sig = self.template.clone();
 
-                // Compute the signature.
-                let sig = sig.sign_hash(signer.as_mut(),
-                                        hash.into_inner())?;
+                            (sig, hash)
+                        },
+                        v => return Err(Error::InvalidOperation(
+                            format!("Unsupported Key version {}", v)).into()),
+                    };
 
-                // And emit the packet.
-                Packet::Signature(sig).serialize(sink)?;
-            }
-        }
+                    sig = sig.set_signature_creation_time(
+                        self.creation_time
+                            .unwrap_or_else(crate::now))?;
+
+                    if ! self.intended_recipients.is_empty() {
+                        sig = sig.set_intended_recipients(
+                            self.intended_recipients.clone())?;
+                    }
+
+                    // Compute the signature.
+                    let sig = sig.sign_hash(signer.as_mut(),
+                                            hash.into_inner())?;
+
+                    // And emit the packet.
+                    let sig = Packet::Signature(sig);
+                    self.profile.check_packet(&sig)?;
+                    sig.serialize(sink)?;
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -1468,6 +1976,9 @@ impl<'a> Write for Signer<'a> {
             self.hashes.iter_mut().for_each(
                 |hash| hash.update(data));
             self.position += amount as u64;
+            if let Some(progress) = self.progress.as_mut() {
+                progress.update(amount);
+            }
         }
 
         written
@@ -1548,6 +2059,13 @@ pub struct LiteralWriter<'a> {
 assert_send_and_sync!(LiteralWriter<'_>);
 
 impl<'a> LiteralWriter<'a> {
+    // The maximum amount of data we process in one write when
+    // signing.
+    //
+    // Large writes are split so that the data is still in the cache
+    // when we hash it for the signatures.
+    const MAX_WRITE_SIZE : usize = 64 * 1024;
+
     /// Creates a new literal writer.
     ///
     /// # Examples
@@ -1674,6 +2192,43 @@ impl<'a> LiteralWriter<'a> {
         Ok(self)
     }
 
+    /// Sets the date from the given file metadata.
+    ///
+    /// This sets the date to the file's modification time, so that
+    /// the recipient can restore it.  OpenPGP has no way to store
+    /// other metadata like permissions.  If the platform does not
+    /// provide a modification time, the date is left unchanged.
+    /// Returns an error if the modification time is not
+    /// representable by OpenPGP.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::serialize::stream::{Message, LiteralWriter};
+    ///
+    /// let metadata = std::fs::metadata(std::env::temp_dir())?;
+    ///
+    /// let mut sink = vec![];
+    /// {
+    ///     let message = Message::new(&mut sink);
+    ///     let mut message = LiteralWriter::new(message)
+    ///         .metadata(&metadata)?
+    ///         .build()?;
+    ///     message.write_all(b"Hello world.")?;
+    ///     message.finalize()?;
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn metadata(mut self, metadata: &std::fs::Metadata) -> Result<Self> {
+        if let Ok(modified) = metadata.modified() {
+            self.template.set_date(Some(modified))?;
+        }
+        Ok(self)
+    }
+
     /// Builds the literal writer, returning the writer stack.
     ///
     /// The next step is to write the payload to the writer stack.
@@ -1700,6 +2255,7 @@ impl<'a> LiteralWriter<'a> {
     /// # Ok(()) }
     /// ```
     pub fn build(mut self) -> Result<Message<'a>> {
+        self.inner.cookie_ref().check_nesting("LiteralWriter")?;
         let level = self.inner.cookie_ref().level + 1;
 
         // For historical reasons, signatures over literal data
@@ -1727,7 +2283,11 @@ impl<'a> LiteralWriter<'a> {
         // Neither is any framing added by the PartialBodyFilter.
         self.inner
             = PartialBodyFilter::new(Message::from(self.inner),
-                                     Cookie::new(level)).into();
+                                     Cookie {
+                                         level,
+                                         private: Private::Literal,
+                                         recipients: Vec::new(),
+                                     }).into();
 
         // Nor the headers.
         self.template.serialize_headers(&mut self.inner, false)?;
@@ -1746,6 +2306,14 @@ impl<'a> fmt::Debug for LiteralWriter<'a> {
 
 impl<'a> Write for LiteralWriter<'a> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // If we need to hash the data, limit the amount of data we
+        // process at once so that the data is still in the cache
+        // when we hash it.
+        let buf = if self.signature_writer.is_some() {
+            &buf[..buf.len().min(Self::MAX_WRITE_SIZE)]
+        } else {
+            buf
+        };
         let written = self.inner.write(buf);
 
         // Any successful written bytes needs to be hashed too.
@@ -1939,6 +2507,7 @@ impl<'a> Compressor<'a> {
     /// # Ok(()) }
     /// ```
     pub fn build(mut self) -> Result<Message<'a>> {
+        self.inner.cookie_ref().check_nesting("Compressor")?;
         let level = self.inner.cookie_ref().level + 1;
 
         // Packet header.
@@ -1947,7 +2516,9 @@ impl<'a> Compressor<'a> {
             = PartialBodyFilter::new(Message::from(self.inner),
                                      Cookie::new(level));
 
-        Self::new_naked(inner, self.algo, self.level, level)
+        let mut message = Self::new_naked(inner, self.algo, self.level, level)?;
+        message.set_container("Compressor");
+        Ok(message)
     }
 
 
@@ -2040,6 +2611,110 @@ impl<'a> writer::Stackable<'a, Cookie> for Compressor<'a> {
     }
 }
 
+/// Selects encryption-capable keys by the kind of data they protect.
+///
+/// OpenPGP distinguishes between keys that are meant to encrypt
+/// communications (data in transit) and keys that are meant to
+/// encrypt storage (data at rest).  See the [`Key Flags`] subpacket.
+/// Tools that create backups, for instance, should select keys for
+/// storage encryption.
+///
+///   [`Key Flags`]: https://tools.ietf.org/html/rfc4880#section-5.2.3.21
+///
+/// Use [`Recipient::for_cert`] to select the recipients from a
+/// certificate.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionMode {
+    /// Keys for encrypting data in transit.
+    Transport,
+    /// Keys for encrypting data at rest.
+    Storage,
+    /// Keys for encrypting data in transit, or at rest.
+    Universal,
+}
+assert_send_and_sync!(EncryptionMode);
+
+impl Default for EncryptionMode {
+    fn default() -> Self {
+        EncryptionMode::Transport
+    }
+}
+
+impl fmt::Display for EncryptionMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncryptionMode::Transport => f.write_str("transport"),
+            EncryptionMode::Storage => f.write_str("storage"),
+            EncryptionMode::Universal => f.write_str("transport or storage"),
+        }
+    }
+}
+
+impl From<EncryptionMode> for crate::types::KeyFlags {
+    fn from(mode: EncryptionMode) -> Self {
+        use crate::types::KeyFlags;
+        match mode {
+            EncryptionMode::Transport =>
+                KeyFlags::empty().set_transport_encryption(),
+            EncryptionMode::Storage =>
+                KeyFlags::empty().set_storage_encryption(),
+            EncryptionMode::Universal =>
+                KeyFlags::empty()
+                .set_transport_encryption()
+                .set_storage_encryption(),
+        }
+    }
+}
+
+/// A recipient that a session key has been encrypted to.
+///
+/// Unlike a [`Recipient`], this doesn't borrow the key, so it can be
+/// kept around after the message has been written.  See
+/// [`Message::recipients`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedRecipient {
+    keyid: KeyID,
+    key: Fingerprint,
+    cert: Option<Fingerprint>,
+}
+assert_send_and_sync!(EncryptedRecipient);
+
+impl From<&Recipient<'_>> for EncryptedRecipient {
+    fn from(r: &Recipient<'_>) -> Self {
+        EncryptedRecipient {
+            keyid: r.keyid.clone(),
+            key: r.key.fingerprint(),
+            cert: r.cert.clone(),
+        }
+    }
+}
+
+impl EncryptedRecipient {
+    /// Returns the recipient keyid emitted in the [`PKESK`] packet.
+    ///
+    /// This is the wildcard keyid if the recipient is hidden.
+    ///
+    ///   [`PKESK`]: crate::packet::PKESK
+    pub fn keyid(&self) -> &KeyID {
+        &self.keyid
+    }
+
+    /// Returns the fingerprint of the key that the session key was
+    /// encrypted to.
+    pub fn key_fingerprint(&self) -> &Fingerprint {
+        &self.key
+    }
+
+    /// Returns the fingerprint of the certificate that the key
+    /// belongs to, if known.
+    ///
+    /// See [`Recipient::cert_fingerprint`].
+    pub fn cert_fingerprint(&self) -> Option<&Fingerprint> {
+        self.cert.as_ref()
+    }
+}
+
 /// A recipient of an encrypted message.
 ///
 /// OpenPGP messages are encrypted with the subkeys of recipients,
@@ -2059,10 +2734,24 @@ impl<'a> writer::Stackable<'a, Cookie> for Compressor<'a> {
 /// encryption subkey, while others use all of them.  This crate does
 /// not dictate a policy, but allows for arbitrary policies.  We do,
 /// however, suggest to encrypt to all suitable subkeys.
-#[derive(Debug)]
+///
+/// When created from a [`ValidKeyAmalgamation`], the recipient also
+/// records the fingerprint of the certificate the key belongs to.
+/// Together with [`Encryptor2::recipients`], this can be used to
+/// record who a message was encrypted for, e.g. in an audit log.  It
+/// also records the recipient's [`Features`] and preferred AEAD
+/// algorithms, which are used when negotiating the use of AEAD.
+///
+///   [`Features`]: crate::types::Features
+///
+///   [`ValidKeyAmalgamation`]: crate::cert::amalgamation::key::ValidKeyAmalgamation
+#[derive(Debug, Clone)]
 pub struct Recipient<'a> {
     keyid: KeyID,
     key: &'a Key<key::PublicParts, key::UnspecifiedRole>,
+    cert: Option<Fingerprint>,
+    features: Option<Features>,
+    aead_algos: Option<&'a [AEADAlgorithm]>,
 }
 assert_send_and_sync!(Recipient<'_>);
 
@@ -2080,9 +2769,16 @@ impl<'a, P, R, R2> From<ValidKeyAmalgamation<'a, P, R, R2>>
     where P: key::KeyParts,
           R: key::KeyRole,
           R2: Copy,
+          ValidKeyAmalgamation<'a, P, R, R2>: Preferences<'a>,
 {
     fn from(ka: ValidKeyAmalgamation<'a, P, R, R2>) -> Self {
-        ka.key().into()
+        let mut r = Recipient::from(ka.key());
+        r.cert = Some(ka.cert().fingerprint());
+        r.features = ka.features();
+        #[allow(deprecated)]
+        let aead_algos = ka.preferred_aead_algorithms();
+        r.aead_algos = aead_algos;
+        r
     }
 }
 
@@ -2152,7 +2848,70 @@ impl<'a> Recipient<'a> {
         Recipient {
             keyid,
             key: key.parts_as_public().role_as_unspecified(),
+            cert: None,
+            features: None,
+            aead_algos: None,
+        }
+    }
+
+    /// Returns all suitable recipients of a certificate.
+    ///
+    /// Selects all supported, alive, and non-revoked keys that are
+    /// marked as encryption-capable for the given [`EncryptionMode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidArgument` if the certificate does not
+    /// have a key that is suitable for `mode`.  For instance, this
+    /// fails if `mode` is [`EncryptionMode::Storage`], and the
+    /// certificate only has a transport encryption subkey.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::serialize::stream::{
+    ///     EncryptionMode, Recipient, Message, Encryptor2,
+    /// };
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// let (cert, _) = CertBuilder::new()
+    ///     .add_storage_encryption_subkey()
+    ///     .generate()?;
+    /// let vc = cert.with_policy(p, None)?;
+    ///
+    /// // The certificate doesn't have a key for transport encryption.
+    /// assert!(Recipient::for_cert(&vc, EncryptionMode::Transport).is_err());
+    ///
+    /// let recipients = Recipient::for_cert(&vc, EncryptionMode::Storage)?;
+    /// assert_eq!(recipients.len(), 1);
+    ///
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message = Encryptor2::for_recipients(message, recipients).build()?;
+    /// # let _ = message;
+    /// # Ok(()) }
+    /// ```
+    pub fn for_cert(vc: &ValidCert<'a>, mode: EncryptionMode)
+                    -> Result<Vec<Recipient<'a>>>
+    {
+        let recipients: Vec<Recipient> = vc.keys()
+            .supported().alive().revoked(false)
+            .key_flags(crate::types::KeyFlags::from(mode))
+            .map(Into::into)
+            .collect();
+
+        if recipients.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "{} has no usable {} encryption-capable key",
+                vc.fingerprint(), mode)).into());
         }
+
+        Ok(recipients)
     }
 
     /// Gets the recipient keyid.
@@ -2264,6 +3023,82 @@ impl<'a> Recipient<'a> {
         self.keyid = keyid;
         self
     }
+
+    /// Returns the key that the session key is encrypted to.
+    pub fn key(&self) -> &'a Key<key::PublicParts, key::UnspecifiedRole> {
+        self.key
+    }
+
+    /// Returns the fingerprint of the certificate that the key
+    /// belongs to, if known.
+    ///
+    /// This is only known if the recipient was created from a
+    /// [`ValidKeyAmalgamation`], or set using
+    /// [`Recipient::set_cert_fingerprint`].
+    ///
+    ///   [`ValidKeyAmalgamation`]: crate::cert::amalgamation::key::ValidKeyAmalgamation
+    pub fn cert_fingerprint(&self) -> Option<&Fingerprint> {
+        self.cert.as_ref()
+    }
+
+    /// Sets the fingerprint of the certificate that the key belongs
+    /// to.
+    ///
+    /// This is only recorded for the benefit of the caller, it is
+    /// not included in the message.
+    pub fn set_cert_fingerprint(mut self, fingerprint: Fingerprint) -> Self {
+        self.cert = Some(fingerprint);
+        self
+    }
+
+    /// Returns whether the recipient keyid is hidden.
+    ///
+    /// This is the case if the recipient keyid has been set to the
+    /// wildcard keyid, either using [`Recipient::set_keyid`], or
+    /// [`Recipient::set_throw_keyid`].
+    pub fn throw_keyid(&self) -> bool {
+        self.keyid.is_wildcard()
+    }
+
+    /// Hides the recipient keyid.
+    ///
+    /// If `throw` is true, the recipient keyid is set to the wildcard
+    /// keyid, obscuring the identity of the recipient.  Otherwise,
+    /// the recipient keyid is set to the keyid of the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::serialize::stream::Recipient;
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// let (cert, _) = CertBuilder::new()
+    ///     .add_transport_encryption_subkey()
+    ///     .generate()?;
+    ///
+    /// let recipients =
+    ///     cert.keys().with_policy(p, None).supported().alive().revoked(false)
+    ///     .for_transport_encryption()
+    ///     .map(|ka| Recipient::from(ka).set_throw_keyid(true))
+    ///     .collect::<Vec<_>>();
+    ///
+    /// assert!(recipients[0].throw_keyid());
+    /// assert_eq!(recipients[0].cert_fingerprint(), Some(&cert.fingerprint()));
+    /// # Ok(()) }
+    /// ```
+    pub fn set_throw_keyid(mut self, throw: bool) -> Self {
+        self.keyid = if throw {
+            KeyID::wildcard()
+        } else {
+            self.key.keyid()
+        };
+        self
+    }
 }
 
 /// Encrypts a message.
@@ -2340,7 +3175,10 @@ where 'b: 'a
     passwords: Vec<Password>,
     sym_algo: SymmetricAlgorithm,
     aead_algo: Option<AEADAlgorithm>,
+    aead_policy: Option<&'b dyn Policy>,
+    profile: Profile,
     hash: Box<dyn crypto::hash::Digest>,
+    progress: Option<Progress<'a>>,
     cookie: Cookie,
 }
 assert_send_and_sync!(Encryptor2<'_, '_>);
@@ -2412,7 +3250,10 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
             passwords: Vec::new(),
             sym_algo: Default::default(),
             aead_algo: Default::default(),
+            aead_policy: None,
+            profile: Default::default(),
             hash: HashAlgorithm::SHA1.context().unwrap(),
+            progress: None,
             cookie: Default::default(), // Will be fixed in build.
         }
     }
@@ -2454,7 +3295,10 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
             passwords: passwords.into_iter().map(|p| p.into()).collect(),
             sym_algo: Default::default(),
             aead_algo: Default::default(),
+            aead_policy: None,
+            profile: Default::default(),
             hash: HashAlgorithm::SHA1.context().unwrap(),
+            progress: None,
             cookie: Default::default(), // Will be fixed in build.
         }
     }
@@ -2608,12 +3452,7 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
                             session_key: SessionKey)
                             -> Result<Self>
     {
-        let sym_key_size = sym_algo.key_size()?;
-        if session_key.len() != sym_key_size {
-            return Err(Error::InvalidArgument(
-                format!("{} requires a {} bit key, but session key has {}",
-                        sym_algo, sym_key_size, session_key.len())).into());
-        }
+        Self::check_session_key(sym_algo, &session_key)?;
 
         Ok(Self {
             inner: inner.into(),
@@ -2622,11 +3461,93 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
             passwords: Vec::with_capacity(0),
             sym_algo,
             aead_algo: Default::default(),
+            aead_policy: None,
+            profile: Default::default(),
             hash: HashAlgorithm::SHA1.context().unwrap(),
+            progress: None,
             cookie: Default::default(), // Will be fixed in build.
         })
     }
 
+    /// Sets the symmetric algorithm and the session key to use.
+    ///
+    /// Normally, a fresh session key is generated for every message.
+    /// This overrides the session key, and the symmetric algorithm,
+    /// of an encryptor created using [`Encryptor2::for_recipients`]
+    /// or [`Encryptor2::with_passwords`].  The session key is then
+    /// encrypted for the recipients and passwords as usual.
+    ///
+    /// This is needed to, for instance, generate test vectors, or to
+    /// implement a key escrow scheme where the session key is
+    /// deposited with a third party, see
+    /// [`EscrowedSessionKey`](crate::crypto::EscrowedSessionKey).
+    /// To make the output completely deterministic, a deterministic
+    /// random source has to be installed as well, see
+    /// [`crypto::set_random_source`](crate::crypto::set_random_source).
+    ///
+    /// # Warning
+    ///
+    /// Reusing a session key for different messages, or using a
+    /// session key that is not chosen uniformly at random, undermines
+    /// the confidentiality of the messages.  Anyone who knows the
+    /// session key can decrypt the message, regardless of the
+    /// recipients.
+    ///
+    /// Returns an error if the session key's length does not match
+    /// the algorithm's key size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::crypto::SessionKey;
+    /// use openpgp::types::SymmetricAlgorithm;
+    /// use openpgp::serialize::stream::{
+    ///     Message, Encryptor2, LiteralWriter,
+    /// };
+    ///
+    /// let algo = SymmetricAlgorithm::AES256;
+    /// let sk = SessionKey::new(algo.key_size()?);
+    /// // Deposit `algo` and `sk` here.
+    ///
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message =
+    ///     Encryptor2::with_passwords(message, Some("совершенно секретно"))
+    ///         .dangerously_set_session_key(algo, sk)?
+    ///         .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Hello world.")?;
+    /// message.finalize()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn dangerously_set_session_key(mut self,
+                                       sym_algo: SymmetricAlgorithm,
+                                       session_key: SessionKey)
+                                       -> Result<Self>
+    {
+        Self::check_session_key(sym_algo, &session_key)?;
+        self.sym_algo = sym_algo;
+        self.session_key = Some(session_key);
+        Ok(self)
+    }
+
+    /// Checks that `session_key` is suitable for `sym_algo`.
+    fn check_session_key(sym_algo: SymmetricAlgorithm,
+                         session_key: &SessionKey)
+                         -> Result<()>
+    {
+        let sym_key_size = sym_algo.key_size()?;
+        if session_key.len() != sym_key_size {
+            return Err(Error::InvalidArgument(
+                format!("{} requires a {} byte key, but session key has {}",
+                        sym_algo, sym_key_size, session_key.len())).into());
+        }
+        Ok(())
+    }
+
     /// Adds recipients.
     ///
     /// The resulting message can be encrypted by any recipient and
@@ -2822,9 +3743,242 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
         self
     }
 
-    /// Enables AEAD and sets the AEAD algorithm to use.
+    /// Enables AEAD and sets the AEAD algorithm to use.
+    ///
+    /// The message is encrypted using an AEAD Encrypted Data packet,
+    /// and passwords are protected using version 5 SKESK packets.
+    ///
+    /// This feature is [experimental](super::super#experimental-features),
+    /// and only available if the `experimental-aed` feature is
+    /// enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::types::AEADAlgorithm;
+    /// use openpgp::serialize::stream::{
+    ///     Message, Encryptor2, LiteralWriter,
+    /// };
+    ///
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message =
+    ///     Encryptor2::with_passwords(message, Some("совершенно секретно"))
+    ///         .aead_algo(AEADAlgorithm::default())
+    ///         .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Hello world.")?;
+    /// message.finalize()?;
+    /// # Ok(()) }
+    /// ```
+    // Function hidden from the public API due to
+    // https://gitlab.com/sequoia-pgp/sequoia/-/issues/550
+    // unless explicitly opted in to.  It is always available for
+    // tests so that it does not bit-rot.
+    #[cfg(any(test, feature = "experimental-aed"))]
+    pub fn aead_algo(mut self, algo: AEADAlgorithm) -> Self {
+        self.aead_algo = Some(algo);
+        self
+    }
+
+    /// Enables AEAD if all recipients support it.
+    ///
+    /// If all recipients advertise support for AEAD using the
+    /// [`Features`] subpacket, the message is encrypted using an AEAD
+    /// Encrypted Data packet.  The AEAD algorithm is the first
+    /// algorithm in the first recipient's [preferred AEAD algorithms]
+    /// that all recipients prefer, and that `policy` and the
+    /// [profile] accept.  A recipient that doesn't state its
+    /// preferred AEAD algorithms is assumed to support only
+    /// [`AEADAlgorithm::EAX`].  If there is no such algorithm, or if
+    /// a recipient doesn't support AEAD, the message is encrypted
+    /// using a Symmetrically Encrypted and Integrity Protected Data
+    /// packet.
+    ///
+    /// The features and preferences are only known for recipients
+    /// that were created from a [`ValidKeyAmalgamation`].  If there
+    /// are no recipients, only passwords, AEAD is not used.  If an
+    /// AEAD algorithm is set explicitly using
+    /// [`Encryptor2::aead_algo`], it is used instead.
+    ///
+    /// This feature is [experimental](super::super#experimental-features),
+    /// and only available if the `experimental-aed` feature is
+    /// enabled.
+    ///
+    ///   [`Features`]: crate::types::Features
+    ///   [preferred AEAD algorithms]: crate::cert::Preferences::preferred_aead_algorithms()
+    ///   [profile]: Encryptor2::profile()
+    ///   [`ValidKeyAmalgamation`]: crate::cert::amalgamation::key::ValidKeyAmalgamation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::policy::StandardPolicy;
+    /// use openpgp::serialize::stream::{
+    ///     Message, Encryptor2, LiteralWriter,
+    /// };
+    ///
+    /// let p = &StandardPolicy::new();
+    /// let (cert, _) = CertBuilder::new()
+    ///     .add_transport_encryption_subkey()
+    ///     .generate()?;
+    /// let recipients = cert.keys().with_policy(p, None)
+    ///     .for_transport_encryption();
+    ///
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let message =
+    ///     Encryptor2::for_recipients(message, recipients)
+    ///         .negotiate_aead(p)
+    ///         .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Hello world.")?;
+    /// message.finalize()?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(any(test, feature = "experimental-aed"))]
+    pub fn negotiate_aead(mut self, policy: &'b dyn Policy) -> Self {
+        self.aead_policy = Some(policy);
+        self
+    }
+
+    /// Picks an AEAD algorithm supported by all recipients.
+    ///
+    /// See [`Encryptor2::negotiate_aead`].
+    fn negotiated_aead_algo(&self, policy: &dyn Policy)
+                            -> Option<AEADAlgorithm>
+    {
+        // If the preferences are not stated, EAX is implied.
+        const IMPLIED: &[AEADAlgorithm] = &[AEADAlgorithm::EAX];
+        let prefs = |r: &Recipient<'b>| r.aead_algos.unwrap_or(IMPLIED);
+
+        #[allow(deprecated)]
+        let supported = ! self.recipients.is_empty()
+            && self.recipients.iter().all(|r| {
+                r.features.as_ref().map(|f| f.supports_aead()).unwrap_or(false)
+            });
+        if ! supported {
+            return None;
+        }
+
+        prefs(&self.recipients[0]).iter().cloned().find(|&algo| {
+            algo.is_supported()
+                && policy.aead_algorithm(algo).is_ok()
+                && self.profile.check_aead_algorithm(algo).is_ok()
+                && self.recipients.iter().all(|r| prefs(r).contains(&algo))
+        })
+    }
+
+    /// Sets the profile.
+    ///
+    /// The profile restricts the algorithms that are used to encrypt
+    /// the message, see [`Profile`] for details.  If the symmetric
+    /// algorithm, the AEAD algorithm, or a recipient's key uses an
+    /// algorithm that the profile does not allow,
+    /// [`Encryptor2::build`] fails.
+    ///
+    /// By default, [`Profile::RFC4880bis`] is used.
+    ///
+    ///   [`Encryptor2::build`]: Encryptor2::build()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::types::{Profile, SymmetricAlgorithm};
+    /// use openpgp::serialize::stream::{
+    ///     Message, Encryptor2, LiteralWriter,
+    /// };
+    ///
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// // RFC 4880 does not define Camellia.
+    /// assert!(
+    ///     Encryptor2::with_passwords(message, Some("совершенно секретно"))
+    ///         .symmetric_algo(SymmetricAlgorithm::Camellia256)
+    ///         .profile(Profile::RFC4880)
+    ///         .build().is_err());
+    ///
+    /// let message = Message::new(&mut sink);
+    /// let message =
+    ///     Encryptor2::with_passwords(message, Some("совершенно секретно"))
+    ///         .symmetric_algo(SymmetricAlgorithm::AES256)
+    ///         .profile(Profile::RFC4880)
+    ///         .build()?;
+    /// let mut message = LiteralWriter::new(message).build()?;
+    /// message.write_all(b"Hello world.")?;
+    /// message.finalize()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Returns the recipients.
+    ///
+    /// When the encryptor is built, one [`PKESK`] packet is emitted
+    /// for each recipient, in this order.  To find out who a message
+    /// was encrypted for after building the encryptor, use
+    /// [`Message::recipients`].
+    ///
+    ///   [`PKESK`]: crate::packet::PKESK
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::io::Write;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::cert::prelude::*;
+    /// use openpgp::serialize::stream::{
+    ///     EncryptionMode, Encryptor2, LiteralWriter, Message, Recipient,
+    /// };
+    /// use openpgp::policy::StandardPolicy;
+    ///
+    /// let p = &StandardPolicy::new();
+    ///
+    /// let (cert, _) = CertBuilder::new()
+    ///     .add_transport_encryption_subkey()
+    ///     .generate()?;
+    /// let vc = cert.with_policy(p, None)?;
+    ///
+    /// # let mut sink = vec![];
+    /// let message = Message::new(&mut sink);
+    /// let encryptor = Encryptor2::for_recipients(
+    ///     message, Recipient::for_cert(&vc, EncryptionMode::Transport)?);
+    /// let audit = encryptor.recipients().to_vec();
+    /// let mut message = LiteralWriter::new(encryptor.build()?).build()?;
+    /// message.write_all(b"Hello world.")?;
+    /// message.finalize()?;
+    ///
+    /// for r in audit {
+    ///     assert_eq!(r.cert_fingerprint(), Some(&cert.fingerprint()));
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn recipients(&self) -> &[Recipient<'b>] {
+        &self.recipients
+    }
+
+    /// Reports progress to `callback`.
+    ///
+    /// `callback` is invoked with the total number of bytes encrypted
+    /// so far whenever at least `interval` bytes have been encrypted
+    /// since the last invocation.  This is useful to display
+    /// progress when encrypting large files.
     ///
-    /// This feature is [experimental](super::super#experimental-features).
+    /// Note: the number of bytes includes the framing of the
+    /// encrypted packets, e.g. the [`LiteralWriter`]'s header.
     ///
     /// # Examples
     ///
@@ -2832,28 +3986,24 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
     /// # fn main() -> sequoia_openpgp::Result<()> {
     /// use std::io::Write;
     /// use sequoia_openpgp as openpgp;
-    /// use openpgp::types::AEADAlgorithm;
     /// use openpgp::serialize::stream::{
     ///     Message, Encryptor2, LiteralWriter,
     /// };
     ///
     /// # let mut sink = vec![];
     /// let message = Message::new(&mut sink);
-    /// let message =
-    ///     Encryptor2::with_passwords(message, Some("совершенно секретно"))
-    ///         .aead_algo(AEADAlgorithm::default())
-    ///         .build()?;
+    /// let message = Encryptor2::with_passwords(message, Some("streng geheim"))
+    ///     .progress(1 << 20, |n| eprint!("\rEncrypted {} MiB", n >> 20))
+    ///     .build()?;
     /// let mut message = LiteralWriter::new(message).build()?;
-    /// message.write_all(b"Hello world.")?;
+    /// message.write_all(&vec![0; 4 << 20])?;
     /// message.finalize()?;
     /// # Ok(()) }
     /// ```
-    // Function hidden from the public API due to
-    // https://gitlab.com/sequoia-pgp/sequoia/-/issues/550
-    // It is used only for tests so that it does not bit-rot.
-    #[cfg(test)]
-    pub fn aead_algo(mut self, algo: AEADAlgorithm) -> Self {
-        self.aead_algo = Some(algo);
+    pub fn progress<F>(mut self, interval: u64, callback: F) -> Self
+        where F: FnMut(u64) + Send + Sync + 'a
+    {
+        self.progress = Some(Progress::new(interval, callback));
         self
     }
 
@@ -2862,6 +4012,12 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
     // A page, 3 per mille overhead.
     const AEAD_CHUNK_SIZE : usize = 4096;
 
+    // The maximum amount of data we process in one write.
+    //
+    // Large writes are split so that the data is still in the cache
+    // when we hash it for the MDC.
+    const MAX_WRITE_SIZE : usize = 64 * 1024;
+
     /// Builds the encryptor, returning the writer stack.
     ///
     /// The most useful filters to push to the writer stack next are
@@ -2905,6 +4061,22 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
             ).into());
         }
 
+        self.inner.as_ref().cookie_ref().check_nesting("Encryptor2")?;
+
+        if self.aead_algo.is_none() {
+            if let Some(policy) = self.aead_policy {
+                self.aead_algo = self.negotiated_aead_algo(policy);
+            }
+        }
+
+        self.profile.check_symmetric_algorithm(self.sym_algo)?;
+        if let Some(algo) = self.aead_algo {
+            self.profile.check_aead_algorithm(algo)?;
+        }
+        for recipient in self.recipients.iter() {
+            self.profile.check_key(recipient.key)?;
+        }
+
         struct AEADParameters {
             algo: AEADAlgorithm,
             chunk_size: usize,
@@ -2913,7 +4085,7 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
 
         let aead = if let Some(algo) = self.aead_algo {
             let mut nonce = vec![0; algo.nonce_size()?];
-            crypto::random(&mut nonce);
+            crypto::try_random(&mut nonce)?;
             Some(AEADParameters {
                 algo,
                 chunk_size: Self::AEAD_CHUNK_SIZE,
@@ -2923,6 +4095,9 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
             None
         };
 
+        let recipients: Vec<EncryptedRecipient> =
+            self.recipients.iter().map(Into::into).collect();
+
         let mut inner = self.inner;
         let level = inner.as_ref().cookie_ref().level + 1;
 
@@ -2932,7 +4107,7 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
             .unwrap_or_else(|| SessionKey::new(sym_key_size));
         if sk.len() != sym_key_size {
             return Err(Error::InvalidOperation(
-                format!("{} requires a {} bit key, but session key has {}",
+                format!("{} requires a {} byte key, but session key has {}",
                         self.sym_algo, sym_key_size, sk.len())).into());
         }
 
@@ -2941,7 +4116,9 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
             let mut pkesk =
                 PKESK3::for_recipient(self.sym_algo, &sk, recipient.key)?;
             pkesk.set_recipient(recipient.keyid.clone());
-            Packet::PKESK(pkesk.into()).serialize(&mut inner)?;
+            let pkesk = Packet::PKESK(pkesk.into());
+            self.profile.check_packet(&pkesk)?;
+            pkesk.serialize(&mut inner)?;
         }
 
         // Write the SKESK packet(s).
@@ -2951,31 +4128,36 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
                                                   self.sym_algo,
                                                   aead.algo,
                                                   Default::default(),
-                                                  &sk, password).unwrap();
-                Packet::SKESK(skesk.into()).serialize(&mut inner)?;
+                                                  &sk, password)?;
+                let skesk = Packet::SKESK(skesk.into());
+                self.profile.check_packet(&skesk)?;
+                skesk.serialize(&mut inner)?;
             } else {
                 let skesk = SKESK4::with_password(self.sym_algo,
                                                   self.sym_algo,
                                                   Default::default(),
-                                                  &sk, password).unwrap();
-                Packet::SKESK(skesk.into()).serialize(&mut inner)?;
+                                                  &sk, password)?;
+                let skesk = Packet::SKESK(skesk.into());
+                self.profile.check_packet(&skesk)?;
+                skesk.serialize(&mut inner)?;
             }
         }
 
         if let Some(aead) = aead {
             // Write the AED packet.
+            let aed = AED1::new(self.sym_algo, aead.algo,
+                                aead.chunk_size as u64, aead.nonce)?;
+            self.profile.check_packet(&Packet::AED(aed.clone().into()))?;
             CTB::new(Tag::AED).serialize(&mut inner)?;
             let mut inner = PartialBodyFilter::new(Message::from(inner),
                                                    Cookie::new(level));
-            let aed = AED1::new(self.sym_algo, aead.algo,
-                                aead.chunk_size as u64, aead.nonce)?;
             aed.serialize_headers(&mut inner)?;
 
             use crate::crypto::aead::AEDv1Schedule;
             let schedule = AEDv1Schedule::new(
                 aed.symmetric_algo(), aed.aead(), aead.chunk_size, aed.iv())?;
 
-            writer::AEADEncryptor::new(
+            let mut message = writer::AEADEncryptor::new(
                 inner,
                 Cookie::new(level),
                 aed.symmetric_algo(),
@@ -2983,7 +4165,11 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
                 aead.chunk_size,
                 schedule,
                 sk,
-            )
+                self.progress,
+            )?;
+            message.set_container("Encryptor2");
+            message.set_recipients(recipients);
+            Ok(message)
         } else {
             // Write the SEIP packet.
             CTB::new(Tag::SEIP).serialize(&mut inner)?;
@@ -3004,12 +4190,18 @@ impl<'a, 'b> Encryptor2<'a, 'b> {
             // bytes.  The hash for the MDC must include the
             // initialization vector, hence we must write this to
             // self after installing the encryptor at self.inner.
+            // It doesn't count towards the progress.
+            let progress = self.progress.take();
             let mut iv = vec![0; self.sym_algo.block_size()?];
-            crypto::random(&mut iv);
+            crypto::try_random(&mut iv)?;
             self.write_all(&iv)?;
             self.write_all(&iv[iv.len() - 2..])?;
+            self.progress = progress;
 
-            Ok(Message::from(Box::new(self)))
+            let mut message = Message::from(Box::new(self));
+            message.set_container("Encryptor2");
+            message.set_recipients(recipients);
+            Ok(message)
         }
     }
 
@@ -3052,9 +4244,15 @@ impl<'a, 'b> Write for Encryptor2<'a, 'b>
     where 'b: 'a
 {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Limit the amount of data we process at once so that the
+        // data is still in the cache when we hash it.
+        let buf = &buf[..buf.len().min(Self::MAX_WRITE_SIZE)];
         let written = self.inner.write(buf);
         if let Ok(amount) = written {
             self.hash.update(&buf[..amount]);
+            if let Some(progress) = self.progress.as_mut() {
+                progress.update(amount);
+            }
         }
         written
     }
@@ -3192,7 +4390,7 @@ impl<'a> Encryptor<'a> {
     /// Enables AEAD and sets the AEAD algorithm to use.
     ///
     /// See [`Encryptor2::aead_algo`].
-    #[cfg(test)]
+    #[cfg(any(test, feature = "experimental-aed"))]
     pub fn aead_algo(self, algo: AEADAlgorithm) -> Self {
         Encryptor {
             inner: self.inner.aead_algo(algo),
@@ -3609,6 +4807,75 @@ mod test {
         test_aead_messages(AEADAlgorithm::GCM)
     }
 
+    /// Checks that passwords are protected using SKESK5 packets when
+    /// AEAD is used.
+    #[test]
+    fn aead_passwords() -> Result<()> {
+        use crate::parse::stream::{
+            DecryptorBuilder,
+            DecryptionHelper,
+            VerificationHelper,
+            MessageStructure,
+        };
+
+        struct Helper;
+        impl VerificationHelper for Helper {
+            fn get_certs(&mut self, _ids: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(Vec::new())
+            }
+            fn check(&mut self, _structure: MessageStructure) -> Result<()> {
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for Helper {
+            fn decrypt<D>(&mut self, _pkesks: &[PKESK], skesks: &[SKESK],
+                          _sym_algo: Option<SymmetricAlgorithm>,
+                          mut decrypt: D) -> Result<Option<crate::Fingerprint>>
+                where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+            {
+                let (algo, sk) = skesks[0].decrypt(&"streng geheim".into())?;
+                assert!(decrypt(algo, &sk));
+                Ok(None)
+            }
+        }
+
+        // AED packets are rejected by the standard policy.
+        let p = &crate::policy::NullPolicy::new();
+        for algo in [AEADAlgorithm::EAX, AEADAlgorithm::OCB,
+                     AEADAlgorithm::GCM]
+        {
+            if ! algo.is_supported() {
+                eprintln!("Skipping because {} is not supported.", algo);
+                continue;
+            }
+
+            let mut msg = vec![];
+            let m = Message::new(&mut msg);
+            let m = Encryptor2::with_passwords(m, Some("streng geheim"))
+                .aead_algo(algo)
+                .build()?;
+            let mut m = LiteralWriter::new(m).build()?;
+            m.write_all(b"Hello world.")?;
+            m.finalize()?;
+
+            let pile = PacketPile::from_bytes(&msg)?;
+            match pile.children().next() {
+                Some(Packet::SKESK(SKESK::V5(skesk))) =>
+                    assert_eq!(skesk.aead_algo(), algo),
+                p => panic!("expected an SKESK5 packet, got {:?}", p),
+            }
+            assert!(matches!(pile.children().nth(1), Some(Packet::AED(_))));
+
+            let mut decryptor = DecryptorBuilder::from_bytes(&msg)?
+                .with_policy(p, None, Helper)?;
+            let mut content = Vec::new();
+            decryptor.read_to_end(&mut content)?;
+            assert_eq!(&content, b"Hello world.");
+        }
+        Ok(())
+    }
+
     fn test_aead_messages(algo: AEADAlgorithm) -> Result<()> {
         if ! algo.is_supported() {
             eprintln!("Skipping because {} is not supported.", algo);
@@ -3997,6 +5264,37 @@ mod test {
         };
     }
 
+    /// Tests that SHA3 is only used if explicitly requested, and
+    /// only if the backend supports it.
+    #[test]
+    fn sha3_only_if_requested() -> Result<()> {
+        for algo in [HashAlgorithm::SHA3_256, HashAlgorithm::SHA3_512] {
+            let mut signer = GoodSigner::default();
+            signer.0 = vec![algo];
+
+            let mut sink = vec![];
+            let e = Signer::new(Message::new(&mut sink), signer)
+                .build().unwrap_err();
+            assert_eq!(e.downcast_ref::<Error>(),
+                       Some(&Error::NoAcceptableHash));
+
+            let mut signer = GoodSigner::default();
+            signer.0 = vec![algo];
+
+            let mut sink = vec![];
+            let r = Signer::new(Message::new(&mut sink), signer)
+                .hash_algo(algo)?
+                .build();
+            if algo.is_supported() {
+                r?;
+            } else {
+                assert_eq!(r.unwrap_err().downcast_ref::<Error>(),
+                           Some(&Error::NoAcceptableHash));
+            }
+        }
+        Ok(())
+    }
+
     /// Tests that multiple signatures are in the correct order.
     #[test]
     fn issue_816() -> Result<()> {
@@ -4186,6 +5484,198 @@ mod test {
         Ok(())
     }
 
+    /// Notarizes signed messages.
+    #[test]
+    fn notarize() -> Result<()> {
+        use crate::parse::stream::*;
+
+        let p = &P::new();
+        let (alice, _) = CertBuilder::new().add_signing_subkey().generate()?;
+        let (notary, _) = CertBuilder::new().add_signing_subkey().generate()?;
+        let keypair = |cert: &Cert| -> Result<crypto::KeyPair> {
+            cert.keys().with_policy(p, None).secret().for_signing()
+                .next().unwrap().key().clone().into_keypair()
+        };
+
+        struct Helper(Vec<Cert>, Vec<(usize, Fingerprint)>);
+        impl VerificationHelper for Helper {
+            fn get_certs(&mut self, _: &[crate::KeyHandle])
+                         -> Result<Vec<Cert>> {
+                Ok(self.0.clone())
+            }
+
+            fn check(&mut self, structure: MessageStructure) -> Result<()> {
+                for layer in structure {
+                    if let MessageLayer::SignatureGroup { results } = layer {
+                        for r in results {
+                            let r = r.map_err(|e| anyhow::anyhow!(
+                                "verification failed: {}", e))?;
+                            self.1.push((r.sig.level(),
+                                         r.ka.cert().fingerprint()));
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        // Alice signs a compressed message.
+        let mut signed = vec![];
+        let message = Message::new(&mut signed);
+        let message = Compressor::new(message)
+            .algo(CompressionAlgorithm::Uncompressed).build()?;
+        let message = Signer::new(message, keypair(&alice)?).build()?;
+        let mut message = LiteralWriter::new(message)
+            .filename("manifesto.txt")?.build()?;
+        message.write_all(b"Hello world.")?;
+        message.finalize()?;
+
+        // The notary notarizes it, armoring the result.
+        let mut notarized = vec![];
+        let message = Message::new(&mut notarized);
+        let message = Armorer::new(message).build()?;
+        let message = Signer::new(message, keypair(&notary)?)
+            .notarize(&signed[..])?;
+        message.finalize()?;
+
+        let h = Helper(vec![alice.clone(), notary.clone()], vec![]);
+        let mut v = VerifierBuilder::from_bytes(&notarized)?
+            .with_policy(p, None, h)?;
+        let mut content = Vec::new();
+        v.read_to_end(&mut content)?;
+        assert_eq!(content, b"Hello world.");
+        assert_eq!(crate::Message::from_bytes(&notarized)?
+                   .body().unwrap().filename(),
+                   Some(&b"manifesto.txt"[..]));
+        assert_eq!(v.into_helper().1, vec![
+            (1, notary.fingerprint()),
+            (0, alice.fingerprint()),
+        ]);
+
+        // Notarizing the notarized message adds another level.
+        let mut twice = vec![];
+        let message = Message::new(&mut twice);
+        let message = Signer::new(message, keypair(&alice)?)
+            .notarize(&notarized[..])?;
+        message.finalize()?;
+
+        let h = Helper(vec![alice.clone(), notary.clone()], vec![]);
+        let v = VerifierBuilder::from_bytes(&twice)?
+            .with_policy(p, None, h)?;
+        assert_eq!(v.into_helper().1, vec![
+            (2, alice.fingerprint()),
+            (1, notary.fingerprint()),
+            (0, alice.fingerprint()),
+        ]);
+
+        // Encrypted messages cannot be notarized.
+        let mut encrypted = vec![];
+        let message = Message::new(&mut encrypted);
+        let message = Encryptor2::with_passwords(message, Some("secret"))
+            .build()?;
+        let mut message = LiteralWriter::new(message).build()?;
+        message.write_all(b"Hello world.")?;
+        message.finalize()?;
+        let message = Message::new(Vec::new());
+        assert!(Signer::new(message, keypair(&notary)?)
+                .notarize(&encrypted[..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn signer_multiple_hash_algos() -> Result<()> {
+        use crate::parse::stream::{
+            DetachedVerifierBuilder,
+            VerifierBuilder,
+            test::VHelper,
+        };
+
+        let p = &P::new();
+        let (alice, _) = CertBuilder::new().add_signing_subkey().generate()?;
+        let (bob, _) = CertBuilder::new().add_signing_subkey().generate()?;
+        let signing_key = |cert: &Cert| {
+            cert.keys().subkeys().secret().next().unwrap().key().clone()
+        };
+        let keypair = |cert: &Cert| signing_key(cert).into_keypair();
+        let msg = b"Hello world.";
+        let algos = [HashAlgorithm::SHA256, HashAlgorithm::SHA512];
+
+        let sign = |mode: SignatureMode| -> Result<Vec<u8>> {
+            let mut sink = vec![];
+            let message = Message::new(&mut sink);
+            let mut signer = Signer::new(message, keypair(&alice)?)
+                .add_signer(keypair(&bob)?)
+                .hash_algo(algos[0])?
+                .add_hash_algo(algos[1])?
+                // Adding an algorithm twice is a no-op.
+                .add_hash_algo(algos[0])?;
+            signer = match mode {
+                SignatureMode::Inline => signer,
+                SignatureMode::Detached => signer.detached(),
+                SignatureMode::Cleartext => signer.cleartext(),
+            };
+            let mut message = signer.build()?;
+            if mode == SignatureMode::Inline {
+                message = LiteralWriter::new(message).build()?;
+            }
+            message.write_all(msg)?;
+            message.finalize()?;
+            Ok(sink)
+        };
+
+        // Check the order of the one-pass signature and the
+        // signature packets.
+        let sink = sign(SignatureMode::Inline)?;
+        let pile = PacketPile::from_bytes(&sink)?;
+        let ops = pile.children().filter_map(|p| match p {
+            Packet::OnePassSig(ops) =>
+                Some((ops.issuer().clone(), ops.hash_algo(), ops.last())),
+            _ => None,
+        }).collect::<Vec<_>>();
+        let alice_id = signing_key(&alice).keyid();
+        let bob_id = signing_key(&bob).keyid();
+        assert_eq!(ops, vec![
+            (alice_id.clone(), algos[0], false),
+            (alice_id, algos[1], false),
+            (bob_id.clone(), algos[0], false),
+            (bob_id, algos[1], true),
+        ]);
+        let sigs = pile.children().filter_map(|p| match p {
+            Packet::Signature(sig) =>
+                Some((sig.issuers().next().unwrap().clone(), sig.hash_algo())),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(sigs, ops.into_iter().rev()
+                   .map(|(issuer, algo, _)| (issuer, algo))
+                   .collect::<Vec<_>>());
+
+        let h = VHelper::new(0, 0, 0, 0, vec![alice.clone(), bob.clone()]);
+        let mut v = VerifierBuilder::from_bytes(&sink)?
+            .with_policy(p, None, h)?;
+        let mut content = Vec::new();
+        v.read_to_end(&mut content)?;
+        assert_eq!(&content[..], &msg[..]);
+        assert!(v.message_processed());
+
+        // Cleartext signatures list both algorithms.
+        let sink = sign(SignatureMode::Cleartext)?;
+        assert!(String::from_utf8_lossy(&sink)
+                .contains("Hash: SHA256\nHash: SHA512\n"));
+        let h = VHelper::new(0, 0, 0, 0, vec![alice.clone(), bob.clone()]);
+        let mut v = VerifierBuilder::from_bytes(&sink)?
+            .with_policy(p, None, h)?;
+        v.read_to_end(&mut Vec::new())?;
+        assert!(v.message_processed());
+
+        // Detached signatures.
+        let sink = sign(SignatureMode::Detached)?;
+        let h = VHelper::new(0, 0, 0, 0, vec![alice, bob]);
+        let mut v = DetachedVerifierBuilder::from_bytes(&sink)?
+            .with_policy(p, None, h)?;
+        v.verify_bytes(msg)?;
+        Ok(())
+    }
+
     /// Encrypts using public key cryptography.
     #[test]
     fn pk_encryptor() -> Result<()> {
@@ -4249,6 +5739,165 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn recipients_for_cert() -> Result<()> {
+        use crate::policy::StandardPolicy;
+        let p = &StandardPolicy::new();
+
+        let (cert, _) = CertBuilder::new()
+            .add_transport_encryption_subkey()
+            .add_storage_encryption_subkey()
+            .add_signing_subkey()
+            .generate()?;
+        let vc = cert.with_policy(p, None)?;
+
+        let transport = Recipient::for_cert(&vc, EncryptionMode::Transport)?;
+        assert_eq!(transport.len(), 1);
+        let storage = Recipient::for_cert(&vc, EncryptionMode::Storage)?;
+        assert_eq!(storage.len(), 1);
+        assert!(transport[0].keyid() != storage[0].keyid());
+        let universal = Recipient::for_cert(&vc, EncryptionMode::Universal)?;
+        assert_eq!(universal.len(), 2);
+
+        let (cert, _) = CertBuilder::new()
+            .add_signing_subkey()
+            .generate()?;
+        let vc = cert.with_policy(p, None)?;
+        assert!(Recipient::for_cert(&vc, EncryptionMode::Universal).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn negotiate_aead() -> Result<()> {
+        use std::time::Duration;
+        use crate::types::Features;
+        let p = &P::new();
+        let t0 = crate::now() - Duration::from_secs(3600);
+
+        // Creates a certificate with the given AEAD support.
+        let cert = |aead: Option<&[AEADAlgorithm]>| -> Result<Cert> {
+            let (cert, _) = CertBuilder::new()
+                .set_creation_time(t0)
+                .add_transport_encryption_subkey()
+                .generate()?;
+            let aead = if let Some(aead) = aead {
+                aead
+            } else {
+                return Ok(cert);
+            };
+
+            let mut signer = cert.primary_key().key().clone()
+                .parts_into_secret()?.into_keypair()?;
+            #[allow(deprecated)]
+            let sig = signature::SignatureBuilder::from(
+                cert.with_policy(p, None)?.direct_key_signature()?.clone())
+                .set_signature_creation_time(crate::now())?
+                .set_features(Features::sequoia().set_aead())?
+                .set_preferred_aead_algorithms(aead.to_vec())?
+                .sign_direct_key(&mut signer, None)?;
+            cert.insert_packets(sig)
+        };
+
+        // Encrypts a message, returns the AEAD algorithm used.
+        let encrypt = |certs: &[&Cert]| -> Result<Option<AEADAlgorithm>> {
+            let mut sink = Vec::new();
+            let message = Message::new(&mut sink);
+            let recipients = certs.iter().flat_map(|c| {
+                c.keys().with_policy(p, None).for_transport_encryption()
+            });
+            let message = Encryptor2::for_recipients(message, recipients)
+                .negotiate_aead(p)
+                .build()?;
+            let mut message = LiteralWriter::new(message).build()?;
+            message.write_all(b"Hello world.")?;
+            message.finalize()?;
+
+            for packet in PacketPile::from_bytes(&sink)?.children() {
+                match packet {
+                    Packet::AED(aed) => return Ok(Some(aed.aead())),
+                    Packet::SEIP(_) => return Ok(None),
+                    _ => (),
+                }
+            }
+            unreachable!("no encryption container")
+        };
+
+        let alice = cert(Some(&[AEADAlgorithm::OCB, AEADAlgorithm::EAX]))?;
+        let bob = cert(Some(&[AEADAlgorithm::EAX, AEADAlgorithm::OCB]))?;
+        let carol = cert(Some(&[AEADAlgorithm::GCM]))?;
+        let dave = cert(None)?;
+
+        let supported = |algo: AEADAlgorithm| {
+            if algo.is_supported() { Some(algo) } else { None }
+        };
+        assert_eq!(encrypt(&[&alice])?,
+                   supported(AEADAlgorithm::OCB)
+                   .or_else(|| supported(AEADAlgorithm::EAX)));
+        assert_eq!(encrypt(&[&bob, &alice])?,
+                   supported(AEADAlgorithm::EAX)
+                   .or_else(|| supported(AEADAlgorithm::OCB)));
+        // No common algorithm.
+        assert_eq!(encrypt(&[&alice, &carol])?, None);
+        // Dave doesn't support AEAD.
+        assert_eq!(encrypt(&[&alice, &dave])?, None);
+        assert_eq!(encrypt(&[&dave])?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encryptor_dangerously_set_session_key() -> Result<()> {
+        let message = b"Hello world.";
+        let algo = SymmetricAlgorithm::AES128;
+        let sk = SessionKey::from(&b"\x01\x23\x45\x67\x89\xab\xcd\xef\
+                                     \x01\x23\x45\x67\x89\xab\xcd\xef"[..]);
+
+        // The key size must match the algorithm.
+        assert!(Encryptor2::with_passwords(Message::new(vec![]),
+                                           Some("top secret"))
+                .dangerously_set_session_key(SymmetricAlgorithm::AES256,
+                                             sk.clone())
+                .is_err());
+
+        let mut o = vec![];
+        let m = Message::new(&mut o);
+        let encryptor = Encryptor2::with_passwords(m, Some("top secret"))
+            .symmetric_algo(SymmetricAlgorithm::AES256)
+            .dangerously_set_session_key(algo, sk.clone())?
+            .build()?;
+        let mut literal = LiteralWriter::new(encryptor).build()?;
+        literal.write_all(message)?;
+        literal.finalize()?;
+
+        // The password protects the given session key...
+        let mut ppr = PacketParser::from_bytes(&o)?;
+        if let PacketParserResult::Some(pp) = ppr {
+            if let Packet::SKESK(ref skesk) = pp.packet {
+                assert_eq!(skesk.decrypt(&"top secret".into())?,
+                           (algo, sk.clone()));
+            } else {
+                panic!("Unexpected packet: {:?}", pp.packet);
+            }
+            ppr = pp.recurse()?.1;
+        }
+
+        // ... which decrypts the message.
+        let mut body = Vec::new();
+        while let PacketParserResult::Some(mut pp) = ppr {
+            match pp.packet {
+                Packet::SEIP(_) => pp.decrypt(algo, &sk)?,
+                Packet::Literal(_) => {
+                    pp.read_to_end(&mut body)?;
+                },
+                _ => (),
+            }
+            ppr = pp.recurse()?.1;
+        }
+        assert_eq!(&body, message);
+        Ok(())
+    }
+
     #[test]
     fn encryptor_lifetime()
     {
@@ -4284,4 +5933,248 @@ mod test {
             Ok(sink)
         }
     }
+
+    #[test]
+    fn progress() -> Result<()> {
+        use crate::policy::StandardPolicy;
+        use crate::parse::stream::{
+            DetachedVerifierBuilder,
+            VerifierBuilder,
+            test::VHelper,
+        };
+
+        let p = &StandardPolicy::new();
+        let cert = Cert::from_bytes(crate::tests::key("testy-new-private.pgp"))?;
+        let keypair = || cert.keys().secret()
+            .with_policy(p, None).supported().alive().revoked(false)
+            .for_signing().next().unwrap()
+            .key().clone().into_keypair();
+        let data = vec![0x42; 3 * 1024 * 1024 + 1];
+
+        // Inline signature.
+        let mut calls = Vec::new();
+        let mut signed = vec![];
+        let message = Message::new(&mut signed);
+        let message = Signer::new(message, keypair()?)
+            .progress(1024 * 1024, |n| calls.push(n))
+            .build()?;
+        let mut message = LiteralWriter::new(message).build()?;
+        for chunk in data.chunks(4096) {
+            message.write_all(chunk)?;
+        }
+        message.finalize()?;
+        assert_eq!(calls, vec![1024 * 1024, 2 * 1024 * 1024, 3 * 1024 * 1024]);
+
+        let mut calls = Vec::new();
+        let h = VHelper::new(1, 0, 0, 0, vec![cert.clone()]);
+        let mut v = VerifierBuilder::from_bytes(&signed)?
+            .progress(0, |n| calls.push(n))
+            .with_policy(p, None, h)?;
+        let mut content = Vec::new();
+        v.read_to_end(&mut content)?;
+        drop(v);
+        assert_eq!(content, data);
+        assert!(calls.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(calls.last(), Some(&(data.len() as u64)));
+
+        // Detached signature.
+        let mut sig = vec![];
+        let message = Message::new(&mut sig);
+        let mut message = Signer::new(message, keypair()?)
+            .detached()
+            .build()?;
+        message.write_all(&data)?;
+        message.finalize()?;
+
+        let mut calls = Vec::new();
+        let h = VHelper::new(1, 0, 0, 0, vec![cert.clone()]);
+        let mut v = DetachedVerifierBuilder::from_bytes(&sig)?
+            .progress(1024 * 1024, |n| calls.push(n))
+            .with_policy(p, None, h)?;
+        v.verify_bytes(&data)?;
+        v.verify_bytes(&data)?;
+        drop(v);
+        assert_eq!(calls.len(), 6);
+        assert_eq!(calls[..3], calls[3..]);
+
+        // Encryption.
+        for aead in [false, true] {
+            let mut calls = Vec::new();
+            let mut encrypted = vec![];
+            let message = Message::new(&mut encrypted);
+            let mut encryptor = Encryptor2::with_passwords(message, Some("p"))
+                .progress(0, |n| calls.push(n));
+            if aead {
+                encryptor =
+                    encryptor.aead_algo(AEADAlgorithm::const_default());
+            }
+            let message = encryptor.build()?;
+            let mut message = LiteralWriter::new(message).build()?;
+            message.write_all(&data)?;
+            message.finalize()?;
+
+            // The first call is for the literal data packet's
+            // header, not the initialization vector.
+            assert!(calls[0] < 16);
+            assert!(calls.windows(2).all(|w| w[0] < w[1]));
+            assert!(*calls.last().unwrap() > data.len() as u64);
+        }
+        Ok(())
+    }
+
+    /// Checks that invalid writer stacks are rejected.
+    #[test]
+    fn invalid_nesting() -> Result<()> {
+        let (cert, _) = CertBuilder::new().add_signing_subkey().generate()?;
+        let keypair = || {
+            cert.keys().subkeys().secret().next().unwrap().key().clone()
+                .into_keypair()
+        };
+        let literal = || LiteralWriter::new(Message::new(Vec::new())).build();
+        let is_invalid_operation = |r: Result<Message>| {
+            matches!(r.unwrap_err().downcast_ref::<Error>(),
+                     Some(Error::InvalidOperation(_)))
+        };
+
+        assert!(is_invalid_operation(
+            LiteralWriter::new(literal()?).build()));
+        assert!(is_invalid_operation(
+            Signer::new(literal()?, keypair()?).build()));
+        assert!(is_invalid_operation(
+            Compressor::new(literal()?).build()));
+        assert!(is_invalid_operation(
+            Encryptor2::with_passwords(literal()?, Some("p"))
+                .build()));
+        assert!(is_invalid_operation(
+            Armorer::new(literal()?).build()));
+        assert!(is_invalid_operation(
+            padding::Padder::new(literal()?).build()));
+
+        // Cleartext signatures cannot contain packets.
+        let message = Signer::new(Message::new(Vec::new()), keypair()?)
+            .cleartext().build()?;
+        assert!(is_invalid_operation(LiteralWriter::new(message).build()));
+
+        // Valid stacks are still accepted.
+        let message = Message::new(Vec::new());
+        let message = Armorer::new(message).build()?;
+        let message = Encryptor2::with_passwords(message, Some("p")).build()?;
+        let message = Compressor::new(message).build()?;
+        let message = Signer::new(message, keypair()?).build()?;
+        let mut message = LiteralWriter::new(message).build()?;
+        message.write_all(b"Hello world.")?;
+        message.finalize_all()?;
+        Ok(())
+    }
+
+    /// Checks that Message::recipients returns the recipients of all
+    /// encryption containers on the stack.
+    #[test]
+    fn message_recipients() -> Result<()> {
+        use crate::cert::prelude::*;
+
+        let p = &P::new();
+        let (alice, _) = CertBuilder::new()
+            .add_transport_encryption_subkey()
+            .generate()?;
+        let (bob, _) = CertBuilder::new()
+            .add_transport_encryption_subkey()
+            .generate()?;
+        let alice_vc = alice.with_policy(p, None)?;
+        let bob_vc = bob.with_policy(p, None)?;
+
+        for aead_algo in [None, Some(AEADAlgorithm::const_default())] {
+            if aead_algo.map(|a| ! a.is_supported()).unwrap_or(false) {
+                continue;
+            }
+
+            let mut sink = vec![];
+            let message = Message::new(&mut sink);
+            assert!(message.recipients().is_empty());
+
+            let outer = Encryptor2::for_recipients(
+                message,
+                Recipient::for_cert(&alice_vc, EncryptionMode::Transport)?);
+            let outer = if let Some(a) = aead_algo {
+                outer.aead_algo(a)
+            } else {
+                outer
+            };
+            let message = outer.build()?;
+            let inner = Encryptor2::for_recipients(
+                message,
+                Recipient::for_cert(&bob_vc, EncryptionMode::Transport)?
+                    .into_iter()
+                    .map(|r| r.set_throw_keyid(true)));
+            let message = inner.build()?;
+            let mut message = LiteralWriter::new(message).build()?;
+            message.write_all(b"Hello world.")?;
+
+            let recipients = message.recipients();
+            assert_eq!(recipients.len(), 2);
+            assert_eq!(recipients[0].cert_fingerprint(),
+                       Some(&bob.fingerprint()));
+            assert!(recipients[0].keyid().is_wildcard());
+            assert_eq!(recipients[1].cert_fingerprint(),
+                       Some(&alice.fingerprint()));
+            assert_eq!(recipients[1].keyid(),
+                       &KeyID::from(recipients[1].key_fingerprint()));
+
+            // Finalizing the inner container drops its recipients.
+            let message = message.finalize_one()?.unwrap()
+                .finalize_one()?.unwrap();
+            assert_eq!(message.recipients().len(), 1);
+            message.finalize()?;
+        }
+        Ok(())
+    }
+
+    /// Checks that Message::finalize_all detects empty containers.
+    #[test]
+    fn finalize_all() -> Result<()> {
+        let is_invalid_operation = |r: Result<()>| {
+            matches!(r.unwrap_err().downcast_ref::<Error>(),
+                     Some(Error::InvalidOperation(_)))
+        };
+
+        let message = Message::new(Vec::new());
+        let message = Encryptor2::with_passwords(message, Some("p")).build()?;
+        assert!(is_invalid_operation(message.finalize_all()));
+
+        let message = Message::new(Vec::new());
+        let message = Encryptor2::with_passwords(message, Some("p"))
+            .aead_algo(AEADAlgorithm::default())
+            .build()?;
+        assert!(is_invalid_operation(message.finalize_all()));
+
+        for algo in [CompressionAlgorithm::Uncompressed,
+                     CompressionAlgorithm::Zip]
+        {
+            let message = Message::new(Vec::new());
+            let message = Compressor::new(message).algo(algo).build()?;
+            assert!(is_invalid_operation(message.finalize_all()));
+
+            let message = Message::new(Vec::new());
+            let message = Compressor::new(message).algo(algo).build()?;
+            let message = LiteralWriter::new(message).build()?;
+            message.finalize_all()?;
+        }
+
+        let message = Message::new(Vec::new());
+        let message = padding::Padder::new(message).build()?;
+        assert!(is_invalid_operation(message.finalize_all()));
+
+        // An inner container is empty.
+        let message = Message::new(Vec::new());
+        let mut message = Compressor::new(message).build()?;
+        message.write_all(b"\xcb\x12b\x00\x00\x00\x00\x00Hello world.")?;
+        let message = Encryptor2::with_passwords(message, Some("p")).build()?;
+        assert!(is_invalid_operation(message.finalize_all()));
+
+        // Without containers, there is nothing to check.
+        let message = Message::new(Vec::new());
+        let message = LiteralWriter::new(message).build()?;
+        message.finalize_all()?;
+        Ok(())
+    }
 }
@@ -0,0 +1,286 @@
+//! Custom filters for the writer stack.
+//!
+//! The streaming serialization interface composes a [`Message`] from
+//! a stack of filters, like the [`Armorer`], the [`Compressor`], or
+//! the [`Encryptor2`].  This module allows third parties to insert
+//! their own filters into the writer stack, e.g. to implement a
+//! custom framing or transport encoding.
+//!
+//!   [`Armorer`]: super::Armorer
+//!   [`Compressor`]: super::Compressor
+//!   [`Encryptor2`]: super::Encryptor2
+//!
+//! A filter implements the [`Filter`] trait, and is pushed onto the
+//! writer stack using [`FilterWriter::new`].  Data written to the
+//! resulting [`Message`] is handed to the filter, which writes the
+//! transformed data to the next filter on the stack.  When the
+//! writer stack is finalized, the filter is given a chance to write
+//! out any buffered data and a trailer.
+//!
+//!   [`FilterWriter::new`]: FilterWriter::new()
+//!
+//! # Examples
+//!
+//! This example implements a filter that hex-encodes data, and
+//! emits a trailing newline when finalized.
+//!
+//! ```
+//! # fn main() -> sequoia_openpgp::Result<()> {
+//! use std::io::{self, Write};
+//! use sequoia_openpgp as openpgp;
+//! use openpgp::serialize::stream::{Message, LiteralWriter};
+//! use openpgp::serialize::stream::filter::{Filter, FilterWriter};
+//!
+//! #[derive(Debug)]
+//! struct Hex;
+//!
+//! impl<'a> Filter<'a> for Hex {
+//!     fn write(&mut self, inner: &mut Message<'a>, buf: &[u8])
+//!              -> io::Result<usize>
+//!     {
+//!         for b in buf {
+//!             write!(inner, "{:02x}", b)?;
+//!         }
+//!         Ok(buf.len())
+//!     }
+//!
+//!     fn finalize(&mut self, inner: &mut Message<'a>)
+//!                 -> openpgp::Result<()>
+//!     {
+//!         inner.write_all(b"\n")?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut sink = vec![];
+//! {
+//!     let message = Message::new(&mut sink);
+//!     let message = FilterWriter::new(message, Hex);
+//!     let mut message = LiteralWriter::new(message).build()?;
+//!     message.write_all(b"Hello world.")?;
+//!     message.finalize()?;
+//! }
+//! assert_eq!(sink.as_slice(),
+//!            &b"cb1262000000000048656c6c6f20776f726c642e\n"[..]);
+//! # Ok(()) }
+//! ```
+
+use std::fmt;
+use std::io;
+
+use crate::Result;
+use super::{
+    Cookie,
+    Message,
+    writer,
+};
+
+/// A custom filter for the writer stack.
+///
+/// See the [module-level documentation] for an example.
+///
+///   [module-level documentation]: self
+pub trait Filter<'a>: fmt::Debug + Send + Sync {
+    /// Writes `buf` to the filter.
+    ///
+    /// The filter writes the transformed data to `inner`, the next
+    /// filter on the writer stack.  Returns the number of bytes of
+    /// `buf` that have been consumed, like [`io::Write::write`].
+    fn write(&mut self, inner: &mut Message<'a>, buf: &[u8])
+             -> io::Result<usize>;
+
+    /// Flushes the filter.
+    ///
+    /// The default implementation flushes `inner`.
+    fn flush(&mut self, inner: &mut Message<'a>) -> io::Result<()> {
+        io::Write::flush(inner)
+    }
+
+    /// Finalizes the filter.
+    ///
+    /// This is called when the filter is popped off the writer
+    /// stack, i.e., when the [`Message`] is finalized.  The filter
+    /// must write out any buffered data, and any trailer, to
+    /// `inner`.  The default implementation does nothing.
+    fn finalize(&mut self, inner: &mut Message<'a>) -> Result<()> {
+        let _ = inner;
+        Ok(())
+    }
+}
+
+/// Inserts a [`Filter`] into the writer stack.
+///
+/// See the [module-level documentation] for an example.
+///
+///   [module-level documentation]: self
+pub struct FilterWriter<'a> {
+    inner: Message<'a>,
+    filter: Box<dyn Filter<'a> + 'a>,
+    cookie: Cookie,
+    position: u64,
+}
+assert_send_and_sync!(FilterWriter<'_>);
+
+impl<'a> FilterWriter<'a> {
+    /// Pushes `filter` onto the writer stack.
+    ///
+    /// Returns the new writer stack.  Data written to it is handed
+    /// to `filter`.
+    pub fn new<F>(inner: Message<'a>, filter: F) -> Message<'a>
+        where F: Filter<'a> + 'a,
+    {
+        let level = inner.as_ref().cookie_ref().level + 1;
+        Message::from(Box::new(FilterWriter {
+            inner,
+            filter: Box::new(filter),
+            cookie: Cookie::new(level),
+            position: 0,
+        }))
+    }
+}
+
+impl<'a> fmt::Debug for FilterWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FilterWriter")
+            .field("filter", &self.filter)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<'a> io::Write for FilterWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amount = self.filter.write(&mut self.inner, buf)?;
+        self.position += amount as u64;
+        Ok(amount)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.filter.flush(&mut self.inner)
+    }
+}
+
+impl<'a> writer::Stackable<'a, Cookie> for FilterWriter<'a> {
+    fn into_inner(mut self: Box<Self>)
+                  -> Result<Option<writer::BoxStack<'a, Cookie>>> {
+        self.filter.finalize(&mut self.inner)?;
+        Ok(Some(self.inner.into()))
+    }
+    fn pop(&mut self) -> Result<Option<writer::BoxStack<'a, Cookie>>> {
+        unreachable!("Only implemented by Signer")
+    }
+    /// Sets the inner stackable.
+    fn mount(&mut self, _new: writer::BoxStack<'a, Cookie>) {
+        unreachable!("Only implemented by Signer")
+    }
+    fn inner_ref(&self) -> Option<&(dyn writer::Stackable<'a, Cookie> + Send + Sync)> {
+        Some(self.inner.as_ref().as_ref())
+    }
+    fn inner_mut(&mut self) -> Option<&mut (dyn writer::Stackable<'a, Cookie> + Send + Sync)> {
+        Some(self.inner.as_mut().as_mut())
+    }
+    fn cookie_set(&mut self, cookie: Cookie) -> Cookie {
+        ::std::mem::replace(&mut self.cookie, cookie)
+    }
+    fn cookie_ref(&self) -> &Cookie {
+        &self.cookie
+    }
+    fn cookie_mut(&mut self) -> &mut Cookie {
+        &mut self.cookie
+    }
+    fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::{
+        Packet,
+        PacketPile,
+        parse::Parse,
+        serialize::stream::{Armorer, LiteralWriter},
+        serialize::stream::writer::Stackable,
+    };
+
+    /// XORs the data with a key, and appends the key when finalized.
+    #[derive(Debug)]
+    struct Xor {
+        key: u8,
+        finalized: bool,
+    }
+
+    impl<'a> Filter<'a> for Xor {
+        fn write(&mut self, inner: &mut Message<'a>, buf: &[u8])
+                 -> io::Result<usize>
+        {
+            assert!(! self.finalized);
+            let buf: Vec<u8> = buf.iter().map(|b| b ^ self.key).collect();
+            inner.write_all(&buf)?;
+            Ok(buf.len())
+        }
+
+        fn finalize(&mut self, inner: &mut Message<'a>) -> Result<()> {
+            self.finalized = true;
+            inner.write_all(&[self.key])?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stacked_filters() -> Result<()> {
+        let mut sink = vec![];
+        {
+            let message = Message::new(&mut sink);
+            let message = Armorer::new(message).build()?;
+            let message = FilterWriter::new(message, Xor {
+                key: 0x2a,
+                finalized: false,
+            });
+            let message = FilterWriter::new(message, Xor {
+                key: 0x17,
+                finalized: false,
+            });
+            let mut message = LiteralWriter::new(message).build()?;
+            message.write_all(b"Hello world.")?;
+            message.finalize()?;
+        }
+
+        // Undo the filters: the outer filter wrote its trailer
+        // through the inner one.
+        let mut data = crate::armor::Reader::from_bytes(
+            &sink, crate::armor::ReaderMode::Tolerant(None));
+        let mut buf = vec![];
+        std::io::copy(&mut data, &mut buf)?;
+
+        assert_eq!(buf.pop(), Some(0x2a));
+        assert_eq!(buf.pop(), Some(0x17 ^ 0x2a));
+        let buf: Vec<u8> =
+            buf.into_iter().map(|b| b ^ 0x17 ^ 0x2a).collect();
+        let pile = PacketPile::from_bytes(&buf)?;
+        if let Some(Packet::Literal(l)) = pile.path_ref(&[0]) {
+            assert_eq!(l.body(), b"Hello world.");
+        } else {
+            panic!("expected a literal data packet");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn position() -> Result<()> {
+        let mut sink = vec![];
+        let message = Message::new(&mut sink);
+        let mut message = FilterWriter::new(message, Xor {
+            key: 1,
+            finalized: false,
+        });
+        message.write_all(b"0123")?;
+        assert_eq!(message.as_ref().position(), 4);
+        message.finalize()?;
+        assert_eq!(sink, b"1032\x01");
+        Ok(())
+    }
+}
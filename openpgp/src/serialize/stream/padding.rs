@@ -234,6 +234,7 @@ impl<'a> Padder<'a> {
     /// ```
     pub fn build(mut self) -> Result<Message<'a>> {
         let mut inner = self.inner;
+        inner.cookie_ref().check_nesting("Padder")?;
         let level = inner.cookie_ref().level + 1;
 
         // Packet header.
@@ -250,7 +251,9 @@ impl<'a> Padder<'a> {
             writer::ZIP::new(inner, Cookie::new(level),
                              CompressionLevel::none()).into();
 
-        Ok(Message::from(Box::new(self)))
+        let mut message = Message::from(Box::new(self));
+        message.set_container("Padder");
+        Ok(message)
     }
 }
 
@@ -312,7 +315,7 @@ impl<'a> writer::Stackable<'a, Cookie> for Padder<'a>
         let mut padding = vec![0; BUFFER_SIZE];
         while amount > 0 {
             let n = std::cmp::min(BUFFER_SIZE as u64, amount) as usize;
-            crate::crypto::random(&mut padding[..n]);
+            crate::crypto::try_random(&mut padding[..n])?;
             pb_writer.write_all(&padding[..n])?;
             amount -= n as u64;
         }
@@ -170,10 +170,17 @@ impl<'a, C: 'a> PartialBodyFilter<'a, C> {
 impl<'a, C: 'a> io::Write for PartialBodyFilter<'a, C> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         // If we can write out a chunk, avoid an extra copy.
+        //
+        // XXX: Opportunity for optimization.  Smaller writes are
+        // copied into our buffer.  We could avoid this by emitting
+        // smaller chunks, or by handing the header, our buffer, and
+        // `buf` to the inner writer using a vectored write.  When
+        // encrypting, the copy costs a few percent of the time spent
+        // in the cipher, so this has not been worth it so far.
         if buf.len() >= self.buffer_threshold - self.buffer.len() {
             self.write_out(buf, false)?;
         } else {
-            self.buffer.append(buf.to_vec().as_mut());
+            self.buffer.extend_from_slice(buf);
         }
         self.position += buf.len() as u64;
         Ok(buf.len())
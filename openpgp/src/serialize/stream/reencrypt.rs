@@ -0,0 +1,299 @@
+//! Adds recipients to encrypted messages.
+
+use std::io::{self, Write};
+
+use crate::{
+    Error,
+    Packet,
+    Result,
+    crypto::EscrowedSessionKey,
+    packet::{
+        AED,
+        SEIP,
+        header::CTB,
+        PKESK,
+        pkesk::PKESK3,
+    },
+    parse::{
+        Parse,
+        PacketParser,
+        PacketParserResult,
+    },
+    serialize::Marshal,
+};
+use super::{
+    Cookie,
+    Message,
+    PartialBodyFilter,
+    Recipient,
+};
+
+/// Adds recipients to an encrypted message.
+///
+/// Reads the encrypted message from `source`, and writes it to `sink`
+/// with additional PKESK packets, one for each of the given
+/// recipients.  The encrypted data itself is copied as is, it is
+/// neither decrypted nor re-encrypted.  This makes it cheap to grant
+/// access to large encrypted archives.
+///
+/// `session_key` must be the message's session key.  It can be
+/// obtained by decrypting one of the message's PKESK or SKESK
+/// packets, or from [`Decryptor::session_key`].  Note: this function
+/// does not check the session key.  If it is wrong, the new
+/// recipients will not be able to decrypt the message.
+///
+///   [`Decryptor::session_key`]: crate::parse::stream::Decryptor::session_key()
+///
+/// `source` may be ASCII armored, `sink` receives a binary message.
+/// The message's existing PKESK and SKESK packets are preserved.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> sequoia_openpgp::Result<()> {
+/// use std::io::Write;
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::crypto::EscrowedSessionKey;
+/// use openpgp::packet::PKESK;
+/// use openpgp::parse::{Parse, PacketParser, PacketParserResult};
+/// use openpgp::policy::StandardPolicy;
+/// use openpgp::serialize::stream::*;
+///
+/// let p = &StandardPolicy::new();
+/// let (alice, _) = CertBuilder::new()
+///     .add_transport_encryption_subkey()
+///     .generate()?;
+/// let (bob, _) = CertBuilder::new()
+///     .add_transport_encryption_subkey()
+///     .generate()?;
+///
+/// // Encrypt a message for Alice.
+/// let mut message = Vec::new();
+/// let m = Message::new(&mut message);
+/// let m = Encryptor2::for_recipients(
+///     m, alice.keys().with_policy(p, None).for_transport_encryption())
+///     .build()?;
+/// let mut m = LiteralWriter::new(m).build()?;
+/// m.write_all(b"Hello world.")?;
+/// m.finalize()?;
+///
+/// // Alice recovers the session key from her PKESK.
+/// let mut keypair = alice.keys().with_policy(p, None)
+///     .for_transport_encryption().secret().next().unwrap()
+///     .key().clone().into_keypair()?;
+/// let pkesk = match PacketParser::from_bytes(&message)? {
+///     PacketParserResult::Some(pp) => match pp.packet {
+///         openpgp::Packet::PKESK(pkesk) => pkesk,
+///         _ => unreachable!(),
+///     },
+///     _ => unreachable!(),
+/// };
+/// let (algo, session_key) = pkesk.decrypt(&mut keypair, None)
+///     .expect("decryption works");
+///
+/// // And grants Bob access.
+/// let mut reencrypted = Vec::new();
+/// add_recipients(&message[..], &mut reencrypted,
+///                &EscrowedSessionKey::new(algo, session_key),
+///                bob.keys().with_policy(p, None).for_transport_encryption())?;
+/// # Ok(()) }
+/// ```
+pub fn add_recipients<'a, R, W, I>(source: R, sink: W,
+                                   session_key: &EscrowedSessionKey,
+                                   recipients: I)
+                                   -> Result<()>
+where
+    R: io::Read + Send + Sync,
+    W: io::Write + Send + Sync,
+    I: IntoIterator,
+    I::Item: Into<Recipient<'a>>,
+{
+    let algo = session_key.symmetric_algo();
+    let sk = session_key.session_key();
+    if sk.len() != algo.key_size()? {
+        return Err(Error::InvalidArgument(
+            format!("{} requires a {} bit key, but session key has {}",
+                    algo, algo.key_size()? * 8, sk.len() * 8)).into());
+    }
+
+    let mut sink = Message::new(sink);
+    let mut ppr = PacketParser::from_reader(source)?;
+    while let PacketParserResult::Some(mut pp) = ppr {
+        match pp.packet {
+            Packet::PKESK(_) | Packet::SKESK(_) | Packet::Marker(_) => {
+                let (packet, next) = pp.next()?;
+                packet.serialize(&mut sink)?;
+                ppr = next;
+            },
+
+            Packet::SEIP(_) | Packet::AED(_) => {
+                for recipient in recipients {
+                    let recipient: Recipient = recipient.into();
+                    let mut pkesk =
+                        PKESK3::for_recipient(algo, sk, recipient.key())?;
+                    pkesk.set_recipient(recipient.keyid().clone());
+                    Packet::PKESK(PKESK::from(pkesk)).serialize(&mut sink)?;
+                }
+
+                // Copy the encryption container.  The parser already
+                // consumed the container's header, so we write it
+                // anew.
+                let tag = pp.packet.tag();
+                CTB::new(tag).serialize(&mut sink)?;
+                let mut inner = PartialBodyFilter::new(sink, Cookie::new(1));
+                match &pp.packet {
+                    Packet::SEIP(SEIP::V1(_)) =>
+                        inner.write_all(&[1])?, // Version.
+                    Packet::AED(AED::V1(aed)) =>
+                        aed.serialize_headers(&mut inner)?,
+                    p => return Err(Error::InvalidOperation(
+                        format!("Unsupported {} version {:?}",
+                                p.tag(), p.version())).into()),
+                }
+                io::copy(&mut pp, &mut inner)?;
+                inner.finalize()?;
+
+                let (_, next) = pp.next()?;
+                if let PacketParserResult::Some(pp) = next {
+                    return Err(Error::MalformedMessage(
+                        format!("Unexpected {} packet after the encryption \
+                                 container", pp.packet.tag())).into());
+                }
+                return Ok(());
+            },
+
+            ref p => return Err(Error::MalformedMessage(
+                format!("Expected an encrypted message, found a {} packet",
+                        p.tag())).into()),
+        }
+    }
+
+    Err(Error::MalformedMessage(
+        "Expected an encrypted message, found no encryption container"
+            .into()).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Read;
+
+    use crate::{
+        Cert,
+        Fingerprint,
+        KeyHandle,
+        cert::prelude::*,
+        crypto::SessionKey,
+        packet::SKESK,
+        parse::stream::*,
+        policy::StandardPolicy as P,
+        serialize::stream::{Encryptor2, LiteralWriter},
+        types::SymmetricAlgorithm,
+    };
+
+    struct Helper(Cert, Option<EscrowedSessionKey>);
+
+    impl VerificationHelper for Helper {
+        fn get_certs(&mut self, _ids: &[KeyHandle]) -> Result<Vec<Cert>> {
+            Ok(Vec::new())
+        }
+
+        fn check(&mut self, _: MessageStructure) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl DecryptionHelper for Helper {
+        fn decrypt<D>(&mut self, pkesks: &[PKESK], _: &[SKESK],
+                      sym_algo: Option<SymmetricAlgorithm>, mut decrypt: D)
+                      -> Result<Option<Fingerprint>>
+        where D: FnMut(SymmetricAlgorithm, &SessionKey) -> bool
+        {
+            let p = &P::new();
+            let mut keypair = self.0.keys().with_policy(p, None)
+                .for_transport_encryption().secret().next().unwrap()
+                .key().clone().into_keypair()?;
+            for pkesk in pkesks {
+                if let Some((algo, sk)) = pkesk.decrypt(&mut keypair, sym_algo)
+                {
+                    if decrypt(algo, &sk) {
+                        self.1 = Some(EscrowedSessionKey::new(algo, sk));
+                        return Ok(None);
+                    }
+                }
+            }
+            Err(Error::MissingSessionKey("no matching PKESK".into()).into())
+        }
+    }
+
+    fn decrypt(message: &[u8], cert: &Cert) -> Result<(Vec<u8>, Helper)> {
+        let p = &P::new();
+        let mut d = DecryptorBuilder::from_bytes(message)?
+            .with_policy(p, None, Helper(cert.clone(), None))?;
+        let mut content = Vec::new();
+        d.read_to_end(&mut content)?;
+        Ok((content, d.into_helper()))
+    }
+
+    #[test]
+    fn add_recipients() -> Result<()> {
+        let p = &P::new();
+        let (alice, _) = CertBuilder::new()
+            .add_transport_encryption_subkey()
+            .generate()?;
+        let (bob, _) = CertBuilder::new()
+            .add_transport_encryption_subkey()
+            .generate()?;
+
+        // Large enough to use several partial body chunks.
+        let payload = vec![0x42; 3 * 1024 * 1024 + 7];
+        let mut message = Vec::new();
+        let m = Message::new(&mut message);
+        let m = Encryptor2::for_recipients(
+            m, alice.keys().with_policy(p, None).for_transport_encryption())
+            .build()?;
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(&payload)?;
+        m.finalize()?;
+
+        // Bob can't read it.
+        assert!(decrypt(&message, &bob).is_err());
+
+        let (content, helper) = decrypt(&message, &alice)?;
+        assert_eq!(content, payload);
+        let sk = helper.1.expect("session key");
+
+        let mut reencrypted = Vec::new();
+        super::add_recipients(
+            &message[..], &mut reencrypted, &sk,
+            bob.keys().with_policy(p, None).for_transport_encryption())?;
+
+        // Now both can.
+        let (content, _) = decrypt(&reencrypted, &alice)?;
+        assert_eq!(content, payload);
+        let (content, _) = decrypt(&reencrypted, &bob)?;
+        assert_eq!(content, payload);
+
+        // A session key of the wrong size is rejected.
+        let wrong = EscrowedSessionKey::new(
+            sk.symmetric_algo(), SessionKey::new(sk.session_key().len() - 1));
+        assert!(super::add_recipients(
+            &message[..], &mut Vec::new(), &wrong,
+            bob.keys().with_policy(p, None).for_transport_encryption())
+                .is_err());
+
+        // Unencrypted messages are rejected.
+        let mut literal = Vec::new();
+        let m = Message::new(&mut literal);
+        let mut m = LiteralWriter::new(m).build()?;
+        m.write_all(b"Hello world.")?;
+        m.finalize()?;
+        assert!(super::add_recipients(
+            &literal[..], &mut Vec::new(), &sk,
+            bob.keys().with_policy(p, None).for_transport_encryption())
+                .is_err());
+        Ok(())
+    }
+}
@@ -23,6 +23,7 @@ use crate::{
     crypto::SessionKey,
 };
 use super::{Message, Cookie};
+use crate::utils::Progress;
 
 impl<'a> Message<'a> {
     pub(super) fn from(bs: BoxStack<'a, Cookie>) -> Self {
@@ -499,6 +500,7 @@ impl<'a, C: 'a> Stackable<'a, C> for Encryptor<'a, C> {
 /// AEAD encrypting writer.
 pub struct AEADEncryptor<'a, C: 'a, S: aead::Schedule> {
     inner: Generic<aead::Encryptor<BoxStack<'a, C>, S>, C>,
+    progress: Option<Progress<'a>>,
 }
 assert_send_and_sync!(AEADEncryptor<'_, C, S> where C, S: aead::Schedule);
 
@@ -506,7 +508,8 @@ impl<'a, S: 'a + aead::Schedule> AEADEncryptor<'a, Cookie, S> {
     /// Makes an encrypting writer.
     pub fn new(inner: Message<'a>, cookie: Cookie,
                cipher: SymmetricAlgorithm, aead: AEADAlgorithm,
-               chunk_size: usize, schedule: S, key: SessionKey)
+               chunk_size: usize, schedule: S, key: SessionKey,
+               progress: Option<Progress<'a>>)
         -> Result<Message<'a>>
     {
         Ok(Message::from(Box::new(AEADEncryptor {
@@ -514,6 +517,7 @@ impl<'a, S: 'a + aead::Schedule> AEADEncryptor<'a, Cookie, S> {
                 aead::Encryptor::new(cipher, aead, chunk_size, schedule, key,
                                      inner.into())?,
                 cookie),
+            progress,
         })))
     }
 }
@@ -528,7 +532,12 @@ impl<'a, C: 'a, S: aead::Schedule> fmt::Debug for AEADEncryptor<'a, C, S> {
 
 impl<'a, C: 'a, S: aead::Schedule> io::Write for AEADEncryptor<'a, C, S> {
     fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
-        self.inner.write(bytes)
+        let written = self.inner.write(bytes);
+        if let (Ok(amount), Some(progress)) = (&written, self.progress.as_mut())
+        {
+            progress.update(*amount);
+        }
+        written
     }
 
     fn flush(&mut self) -> io::Result<()> {
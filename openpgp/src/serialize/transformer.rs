@@ -0,0 +1,331 @@
+//! Streaming packet transformations.
+use std::io;
+
+use crate::Packet;
+use crate::Result;
+use crate::parse::PacketParserResult;
+use crate::serialize::Marshal;
+
+/// A function transforming a packet.
+type Function<'a> =
+    Box<dyn FnMut(Packet) -> Result<Option<Packet>> + Send + Sync + 'a>;
+
+/// Transforms a sequence of packets, one packet at a time.
+///
+/// The transformer reads the top-level packets from a
+/// [`PacketParser`], passes each one through the functions installed
+/// using [`PacketPileTransformer::filter`],
+/// [`PacketPileTransformer::map`], and
+/// [`PacketPileTransformer::filter_map`] in the order they were
+/// installed, and serializes the result to a sink.  Only one packet
+/// is held in memory at a time, so that keyrings larger than the
+/// available memory can be processed.
+///
+/// Containers, like compressed data packets, are not descended into.
+/// They are passed to the functions as a whole, with their content
+/// buffered.
+///
+/// The functions are invoked in the order of the packets in the
+/// input, and may keep state.  This can be used to, for instance,
+/// drop signatures depending on the component they belong to, or on
+/// the certificate they are part of.
+///
+/// The output is written as is.  To emit ASCII Armor, wrap the sink
+/// in an [`armor::Writer`].  Since the [`PacketParser`] transparently
+/// removes ASCII Armor from its input, this can also be used to
+/// convert between the binary and the armored form.
+///
+///   [`PacketParser`]: crate::parse::PacketParser
+///   [`armor::Writer`]: crate::armor::Writer
+///
+/// # Examples
+///
+/// Strip all user attributes from a keyring:
+///
+/// ```
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::Packet;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::packet::user_attribute::{Subpacket, Image};
+/// use openpgp::packet::UserAttribute;
+/// use openpgp::parse::{Parse, PacketParser};
+/// use openpgp::serialize::{PacketPileTransformer, SerializeInto};
+///
+/// # fn main() -> openpgp::Result<()> {
+/// let (cert, _) = CertBuilder::new()
+///     .add_userid("alice@example.org")
+///     .add_user_attribute(UserAttribute::new(&[
+///         Subpacket::Image(Image::Private(100, vec![0, 1, 2].into())),
+///     ])?)
+///     .generate()?;
+/// let keyring = cert.to_vec()?;
+///
+/// // Drop user attributes and the signatures following them.
+/// let mut in_user_attribute = false;
+/// let mut transformer = PacketPileTransformer::new()
+///     .filter(move |packet| {
+///         match packet {
+///             Packet::UserAttribute(_) => in_user_attribute = true,
+///             Packet::Signature(_) => (),
+///             _ => in_user_attribute = false,
+///         }
+///         ! in_user_attribute
+///     });
+///
+/// let mut stripped = Vec::new();
+/// transformer.transform(PacketParser::from_bytes(&keyring)?, &mut stripped)?;
+///
+/// let cert = Cert::from_bytes(&stripped)?;
+/// assert_eq!(cert.userids().count(), 1);
+/// assert_eq!(cert.user_attributes().count(), 0);
+/// # Ok(()) }
+/// ```
+pub struct PacketPileTransformer<'a> {
+    functions: Vec<Function<'a>>,
+}
+assert_send_and_sync!(PacketPileTransformer<'_>);
+
+impl Default for PacketPileTransformer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> PacketPileTransformer<'a> {
+    /// Returns a transformer that copies all packets.
+    pub fn new() -> Self {
+        PacketPileTransformer {
+            functions: Vec::new(),
+        }
+    }
+
+    /// Drops all packets for which `f` returns false.
+    pub fn filter<F>(self, mut f: F) -> Self
+        where F: FnMut(&Packet) -> bool + Send + Sync + 'a
+    {
+        self.filter_map(move |packet| Ok(if f(&packet) {
+            Some(packet)
+        } else {
+            None
+        }))
+    }
+
+    /// Replaces all packets by the packet returned by `f`.
+    ///
+    /// If `f` returns an error, the transformation is aborted.
+    pub fn map<F>(self, mut f: F) -> Self
+        where F: FnMut(Packet) -> Result<Packet> + Send + Sync + 'a
+    {
+        self.filter_map(move |packet| f(packet).map(Some))
+    }
+
+    /// Replaces all packets by the packet returned by `f`, dropping
+    /// those for which `f` returns `None`.
+    ///
+    /// If `f` returns an error, the transformation is aborted.
+    pub fn filter_map<F>(mut self, f: F) -> Self
+        where F: FnMut(Packet) -> Result<Option<Packet>> + Send + Sync + 'a
+    {
+        self.functions.push(Box::new(f));
+        self
+    }
+
+    /// Transforms a single packet.
+    ///
+    /// Returns `None` if one of the functions dropped the packet.
+    pub fn apply(&mut self, packet: Packet) -> Result<Option<Packet>> {
+        let mut packet = packet;
+        for f in self.functions.iter_mut() {
+            match f(packet)? {
+                Some(p) => packet = p,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(packet))
+    }
+
+    /// Transforms the packets read by `ppr`, and writes them to
+    /// `sink`.
+    ///
+    /// Returns the number of packets written.  The transformer can
+    /// be reused to transform several inputs, e.g. a number of
+    /// keyrings.  The state of the functions is preserved.
+    pub fn transform(&mut self, ppr: PacketParserResult,
+                     sink: &mut dyn io::Write)
+                     -> Result<usize>
+    {
+        let mut ppr = ppr;
+        let mut count = 0;
+        while let PacketParserResult::Some(mut pp) = ppr {
+            pp.buffer_unread_content()?;
+            let (packet, next) = pp.next()?;
+            ppr = next;
+
+            if let Some(packet) = self.apply(packet)? {
+                packet.serialize(sink)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{Error, KeyHandle, PacketPile};
+    use crate::armor;
+    use crate::cert::prelude::*;
+    use crate::packet::prelude::*;
+    use crate::parse::{Parse, PacketParser};
+    use crate::serialize::SerializeInto;
+    use crate::types::SignatureType;
+
+    fn certs() -> Result<Vec<Cert>> {
+        (0..3).map(|i| {
+            CertBuilder::general_purpose(
+                None, Some(format!("<{}@example.org>", i)))
+                .generate().map(|(cert, _)| cert)
+        }).collect()
+    }
+
+    #[test]
+    fn identity() -> Result<()> {
+        let keyring = certs()?.iter().map(|c| c.to_vec())
+            .collect::<Result<Vec<_>>>()?.concat();
+
+        let mut output = Vec::new();
+        let count = PacketPileTransformer::new()
+            .transform(PacketParser::from_bytes(&keyring)?, &mut output)?;
+        assert_eq!(output, keyring);
+        assert_eq!(count, PacketPile::from_bytes(&keyring)?.children().count());
+        Ok(())
+    }
+
+    #[test]
+    fn armor() -> Result<()> {
+        let keyring = certs()?.iter().map(|c| c.to_vec())
+            .collect::<Result<Vec<_>>>()?.concat();
+
+        // Binary to armored.
+        let mut armored = Vec::new();
+        let mut transformer = PacketPileTransformer::new();
+        {
+            let mut sink =
+                armor::Writer::new(&mut armored, armor::Kind::PublicKey)?;
+            transformer.transform(PacketParser::from_bytes(&keyring)?,
+                                  &mut sink)?;
+            sink.finalize()?;
+        }
+        assert!(armored.starts_with(b"-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+
+        // And back.
+        let mut binary = Vec::new();
+        transformer.transform(PacketParser::from_bytes(&armored)?,
+                              &mut binary)?;
+        assert_eq!(binary, keyring);
+        Ok(())
+    }
+
+    #[test]
+    fn third_party_certifications() -> Result<()> {
+        let certs = certs()?;
+        let mut signer = certs[0].primary_key().key().clone()
+            .parts_into_secret()?.into_keypair()?;
+
+        // Certify the User IDs of the other certificates.
+        let mut keyring = certs[0].to_vec()?;
+        for cert in &certs[1..] {
+            let userid = cert.userids().next().unwrap().userid().clone();
+            let certification = userid.certify(
+                &mut signer, cert, SignatureType::GenericCertification,
+                None, None)?;
+            let cert = cert.clone().insert_packets(certification)?;
+            keyring.extend(cert.to_vec()?);
+        }
+
+        let mut primary: Option<KeyHandle> = None;
+        let mut transformer = PacketPileTransformer::new()
+            .filter(move |packet| match packet {
+                Packet::PublicKey(key) => {
+                    primary = Some(key.key_handle());
+                    true
+                },
+                Packet::Signature(sig) => match sig.typ() {
+                    SignatureType::GenericCertification
+                        | SignatureType::PersonaCertification
+                        | SignatureType::CasualCertification
+                        | SignatureType::PositiveCertification =>
+                        sig.get_issuers().iter().any(|issuer| {
+                            primary.as_ref()
+                                .map(|p| p.aliases(issuer))
+                                .unwrap_or(false)
+                        }),
+                    _ => true,
+                },
+                _ => true,
+            });
+
+        let mut stripped = Vec::new();
+        transformer.transform(PacketParser::from_bytes(&keyring)?,
+                              &mut stripped)?;
+
+        let stripped = CertParser::from_bytes(&stripped)?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(stripped.len(), 3);
+        assert_eq!(stripped, certs);
+        Ok(())
+    }
+
+    #[test]
+    fn map() -> Result<()> {
+        let keyring = certs()?.iter().map(|c| c.to_vec())
+            .collect::<Result<Vec<_>>>()?.concat();
+
+        // Replace every User ID.
+        let mut transformer = PacketPileTransformer::new()
+            .map(|packet| Ok(match packet {
+                Packet::UserID(_) =>
+                    UserID::from("<nobody@example.org>").into(),
+                p => p,
+            }));
+        let mut output = Vec::new();
+        transformer.transform(PacketParser::from_bytes(&keyring)?,
+                              &mut output)?;
+
+        let pile = PacketPile::from_bytes(&output)?;
+        let userids = pile.children().filter_map(|p| match p {
+            Packet::UserID(u) => Some(u.value()),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(userids, vec![&b"<nobody@example.org>"[..]; 3]);
+
+        // Errors abort the transformation.
+        let mut transformer = PacketPileTransformer::new()
+            .map(|_| Err(Error::InvalidOperation("nope".into()).into()));
+        assert!(transformer.transform(PacketParser::from_bytes(&keyring)?,
+                                      &mut Vec::new()).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "compression-deflate")]
+    #[test]
+    fn containers() -> Result<()> {
+        let message =
+            crate::tests::message("compressed-data-algo-1.gpg");
+
+        let mut output = Vec::new();
+        let count = PacketPileTransformer::new()
+            .transform(PacketParser::from_bytes(message)?, &mut output)?;
+        assert_eq!(count, 1);
+
+        let pile = PacketPile::from_bytes(&output)?;
+        match pile.path_ref(&[0, 0]) {
+            Some(Packet::Literal(l)) =>
+                assert_eq!(l.body(), crate::tests::manifesto()),
+            p => panic!("Expected a literal data packet, got {:?}", p),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//! A pluggable clock.
+//!
+//! By default, the current time is the system time.  Using
+//! [`set_clock`], this can be replaced by a custom clock, e.g., to
+//! fake the system time.
+
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// A source of the current time.
+///
+/// See [`set_clock`].
+///
+/// A [`SystemTime`] is a clock that is frozen at that time.  Closures
+/// returning a `SystemTime` are clocks, too.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+impl Clock for SystemTime {
+    fn now(&self) -> SystemTime {
+        *self
+    }
+}
+
+impl<F> Clock for F
+    where F: Fn() -> SystemTime + Send + Sync
+{
+    fn now(&self) -> SystemTime {
+        self()
+    }
+}
+
+/// The installed clock, if any.
+static CLOCK: RwLock<Option<Box<dyn Clock>>> = RwLock::new(None);
+
+/// Replaces the clock.
+///
+/// Installs `clock` as the process-wide clock, or, if `clock` is
+/// `None`, reverts to the system time.  Returns the previously
+/// installed clock.
+///
+/// The clock is consulted whenever Sequoia needs to know the current
+/// time, for instance when creating keys and signatures using
+/// [`CertBuilder`] or [`SignatureBuilder`] without an explicit
+/// creation time, when evaluating a certificate under a policy
+/// without an explicit reference time, and by [`Timestamp::now`].
+/// This allows running programs at a fixed time, or evaluating data
+/// as of a historical time, without passing the reference time to
+/// every call.
+///
+/// Since the clock is process-wide, it should only be set by
+/// programs, not by libraries.
+///
+///   [`CertBuilder`]: crate::cert::CertBuilder
+///   [`SignatureBuilder`]: crate::packet::signature::SignatureBuilder
+///   [`Timestamp::now`]: super::Timestamp::now()
+///
+/// # Examples
+///
+/// Freeze the time:
+///
+/// ```
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+///
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::types::{self, Timestamp};
+///
+/// # fn main() -> openpgp::Result<()> {
+/// let t = UNIX_EPOCH + Duration::new(1_500_000_000, 0);
+/// let previous = types::set_clock(Some(Box::new(t)));
+///
+/// assert_eq!(Timestamp::now(), Timestamp::from(1_500_000_000));
+/// let (cert, _) = CertBuilder::new().generate()?;
+/// assert!(cert.primary_key().creation_time() <= t);
+///
+/// types::set_clock(previous);
+/// # Ok(()) }
+/// ```
+///
+/// Pretend that it is ten years ago, while letting the time pass:
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+///
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::types;
+///
+/// let offset = Duration::new(10 * 365 * 24 * 60 * 60, 0);
+/// let previous =
+///     types::set_clock(Some(Box::new(move || SystemTime::now() - offset)));
+/// # types::set_clock(previous);
+/// ```
+pub fn set_clock(clock: Option<Box<dyn Clock>>) -> Option<Box<dyn Clock>> {
+    let mut guard = CLOCK.write().unwrap_or_else(|e| e.into_inner());
+    std::mem::replace(&mut *guard, clock)
+}
+
+/// Returns the current time according to the installed clock.
+///
+/// Returns `None` if no clock is installed.
+pub(crate) fn now() -> Option<SystemTime> {
+    CLOCK.read().unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|clock| clock.now())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::Result;
+    use crate::cert::prelude::*;
+    use crate::policy::StandardPolicy;
+
+    #[test]
+    fn clock() -> Result<()> {
+        // Other tests run concurrently, so we must return the actual
+        // time.
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_ = calls.clone();
+        let previous = set_clock(Some(Box::new(move || {
+            calls_.fetch_add(1, Ordering::Relaxed);
+            SystemTime::now()
+        })));
+
+        let (cert, _) = CertBuilder::new()
+            .add_userid("alice@example.org")
+            .generate()?;
+        let generated = calls.load(Ordering::Relaxed);
+        assert!(generated > 0);
+
+        let p = &StandardPolicy::new();
+        cert.with_policy(p, None)?.alive()?;
+        assert!(calls.load(Ordering::Relaxed) > generated);
+
+        set_clock(previous);
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::types::Bitfield;
@@ -389,7 +389,7 @@ const FEATURE_FLAG_SEIPDV1: usize = 0;
 /// Encrypted Session Key Packets (packet 3).
 const FEATURE_FLAG_AEAD: usize = 1;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Features {
     fn arbitrary(g: &mut Gen) -> Self {
         Self::new(Vec::arbitrary(g))
@@ -1,7 +1,7 @@
 use std::fmt;
 use std::ops::{BitAnd, BitOr};
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::types::Bitfield;
@@ -513,7 +513,7 @@ const KEY_FLAG_AUTHENTICATE: usize = 5;
 /// than one person.
 const KEY_FLAG_GROUP_KEY: usize = 7;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for KeyFlags {
     fn arbitrary(g: &mut Gen) -> Self {
         Self::new(Vec::arbitrary(g))
@@ -11,6 +11,11 @@
 //!
 //! # Data structures
 //!
+//! ## `Clock`
+//!
+//! A source of the current time, which can be replaced process-wide
+//! using [`set_clock`], e.g., to fake the system time.
+//!
 //! ## `CompressionLevel`
 //!
 //! Allows adjusting the amount of effort spent on compressing encoded data.
@@ -25,6 +30,11 @@
 //!
 //! Holds information about a key in particular how the given key can be used.
 //!
+//! ## `Profile`
+//!
+//! Selects the OpenPGP dialect, i.e. which packet versions and
+//! algorithms may be used when producing data.
+//!
 //! ## `RevocationKey`
 //!
 //! Describes a key that has been designated to issue revocation signatures.
@@ -46,7 +56,7 @@ use std::fmt;
 use std::str::FromStr;
 use std::result;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::Error;
@@ -54,19 +64,22 @@ use crate::Result;
 
 mod bitfield;
 pub use bitfield::Bitfield;
+pub(crate) mod clock;
+pub use clock::{Clock, set_clock};
 mod compression_level;
 pub use compression_level::CompressionLevel;
 mod features;
 pub use self::features::Features;
 mod key_flags;
 pub use self::key_flags::KeyFlags;
+mod profile;
+pub use profile::Profile;
 mod revocation_key;
 pub use revocation_key::RevocationKey;
 mod server_preferences;
 pub use self::server_preferences::KeyServerPreferences;
 mod timestamp;
 pub use timestamp::{Timestamp, Duration};
-pub(crate) use timestamp::normalize_systemtime;
 
 pub(crate) trait Sendable : Send {}
 pub(crate) trait Syncable : Sync {}
@@ -261,6 +274,8 @@ impl From<PublicKeyAlgorithm> for u8 {
     }
 }
 
+impl_serde_using_u8!(PublicKeyAlgorithm);
+
 /// Formats the public key algorithm name.
 ///
 /// There are two ways the public key algorithm name can be formatted.
@@ -319,14 +334,14 @@ impl fmt::Display for PublicKeyAlgorithm {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for PublicKeyAlgorithm {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl PublicKeyAlgorithm {
     pub(crate) fn arbitrary_for_signing(g: &mut Gen) -> Self {
         use self::PublicKeyAlgorithm::*;
@@ -659,7 +674,7 @@ impl Curve {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Curve {
     fn arbitrary(g: &mut Gen) -> Self {
         match u8::arbitrary(g) % 9 {
@@ -808,6 +823,7 @@ impl From<SymmetricAlgorithm> for u8 {
     }
 }
 
+impl_serde_using_u8!(SymmetricAlgorithm);
 
 /// Formats the symmetric algorithm name.
 ///
@@ -895,7 +911,7 @@ impl fmt::Display for SymmetricAlgorithm {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for SymmetricAlgorithm {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
@@ -1085,6 +1101,8 @@ impl From<AEADAlgorithm> for u8 {
     }
 }
 
+impl_serde_using_u8!(AEADAlgorithm);
+
 /// Formats the AEAD algorithm name.
 ///
 /// There are two ways the AEAD algorithm name can be formatted.  By
@@ -1135,7 +1153,7 @@ impl fmt::Display for AEADAlgorithm {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for AEADAlgorithm {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
@@ -1277,6 +1295,8 @@ impl From<CompressionAlgorithm> for u8 {
     }
 }
 
+impl_serde_using_u8!(CompressionAlgorithm);
+
 impl fmt::Display for CompressionAlgorithm {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -1292,7 +1312,7 @@ impl fmt::Display for CompressionAlgorithm {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for CompressionAlgorithm {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
@@ -1337,6 +1357,14 @@ pub enum HashAlgorithm {
     SHA512,
     /// 224-bit version of SHA2
     SHA224,
+    /// 256-bit version of SHA3, see [Section 9.5 of RFC 9580].
+    ///
+    /// [Section 9.5 of RFC 9580]: https://www.rfc-editor.org/rfc/rfc9580.html#section-9.5
+    SHA3_256,
+    /// 512-bit version of SHA3, see [Section 9.5 of RFC 9580].
+    ///
+    /// [Section 9.5 of RFC 9580]: https://www.rfc-editor.org/rfc/rfc9580.html#section-9.5
+    SHA3_512,
     /// Private hash algorithm identifier.
     Private(u8),
     /// Unknown hash algorithm identifier.
@@ -1344,7 +1372,7 @@ pub enum HashAlgorithm {
 }
 assert_send_and_sync!(HashAlgorithm);
 
-const HASH_ALGORITHM_VARIANTS: [HashAlgorithm; 7] = [
+const HASH_ALGORITHM_VARIANTS: [HashAlgorithm; 9] = [
     HashAlgorithm::MD5,
     HashAlgorithm::SHA1,
     HashAlgorithm::RipeMD,
@@ -1352,6 +1380,8 @@ const HASH_ALGORITHM_VARIANTS: [HashAlgorithm; 7] = [
     HashAlgorithm::SHA384,
     HashAlgorithm::SHA512,
     HashAlgorithm::SHA224,
+    HashAlgorithm::SHA3_256,
+    HashAlgorithm::SHA3_512,
 ];
 
 impl Default for HashAlgorithm {
@@ -1372,6 +1402,8 @@ impl From<u8> for HashAlgorithm {
             9 => HashAlgorithm::SHA384,
             10 => HashAlgorithm::SHA512,
             11 => HashAlgorithm::SHA224,
+            12 => HashAlgorithm::SHA3_256,
+            14 => HashAlgorithm::SHA3_512,
             100..=110 => HashAlgorithm::Private(u),
             u => HashAlgorithm::Unknown(u),
         }
@@ -1388,12 +1420,16 @@ impl From<HashAlgorithm> for u8 {
             HashAlgorithm::SHA384 => 9,
             HashAlgorithm::SHA512 => 10,
             HashAlgorithm::SHA224 => 11,
+            HashAlgorithm::SHA3_256 => 12,
+            HashAlgorithm::SHA3_512 => 14,
             HashAlgorithm::Private(u) => u,
             HashAlgorithm::Unknown(u) => u,
         }
     }
 }
 
+impl_serde_using_u8!(HashAlgorithm);
+
 impl FromStr for HashAlgorithm {
     type Err = ();
 
@@ -1412,6 +1448,10 @@ impl FromStr for HashAlgorithm {
             Ok(HashAlgorithm::SHA512)
         } else if s.eq_ignore_ascii_case("SHA224") {
             Ok(HashAlgorithm::SHA224)
+        } else if s.eq_ignore_ascii_case("SHA3-256") {
+            Ok(HashAlgorithm::SHA3_256)
+        } else if s.eq_ignore_ascii_case("SHA3-512") {
+            Ok(HashAlgorithm::SHA3_512)
         } else {
             Err(())
         }
@@ -1428,6 +1468,8 @@ impl fmt::Display for HashAlgorithm {
             HashAlgorithm::SHA384 => f.write_str("SHA384"),
             HashAlgorithm::SHA512 => f.write_str("SHA512"),
             HashAlgorithm::SHA224 => f.write_str("SHA224"),
+            HashAlgorithm::SHA3_256 => f.write_str("SHA3-256"),
+            HashAlgorithm::SHA3_512 => f.write_str("SHA3-512"),
             HashAlgorithm::Private(u) =>
                 f.write_fmt(format_args!("Private/Experimental hash algorithm {}", u)),
             HashAlgorithm::Unknown(u) =>
@@ -1464,6 +1506,8 @@ impl HashAlgorithm {
             HashAlgorithm::SHA384 => Ok("SHA384"),
             HashAlgorithm::SHA512 => Ok("SHA512"),
             HashAlgorithm::SHA224 => Ok("SHA224"),
+            HashAlgorithm::SHA3_256 => Ok("SHA3-256"),
+            HashAlgorithm::SHA3_512 => Ok("SHA3-512"),
             HashAlgorithm::Private(_) =>
                 Err(Error::UnsupportedHashAlgorithm(*self).into()),
             HashAlgorithm::Unknown(_) =>
@@ -1481,7 +1525,7 @@ impl HashAlgorithm {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for HashAlgorithm {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
@@ -1629,6 +1673,8 @@ impl From<SignatureType> for u8 {
     }
 }
 
+impl_serde_using_u8!(SignatureType);
+
 impl fmt::Display for SignatureType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -1670,7 +1716,7 @@ impl fmt::Display for SignatureType {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for SignatureType {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
@@ -1832,7 +1878,7 @@ impl fmt::Display for ReasonForRevocation {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for ReasonForRevocation {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
@@ -2100,7 +2146,7 @@ impl fmt::Display for DataFormat {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for DataFormat {
     fn arbitrary(g: &mut Gen) -> Self {
         u8::arbitrary(g).into()
@@ -0,0 +1,345 @@
+use crate::Error;
+use crate::Packet;
+use crate::Result;
+use crate::packet::{Key, Signature, SKESK, key};
+use crate::types::{
+    AEADAlgorithm,
+    Curve,
+    HashAlgorithm,
+    PublicKeyAlgorithm,
+    SymmetricAlgorithm,
+};
+
+/// Selects the OpenPGP dialect used when producing data.
+///
+/// Sequoia happily consumes data using any packet versions and
+/// algorithms it knows about.  When producing data, it is sometimes
+/// necessary to restrict the output to what a particular
+/// implementation or standard understands, e.g. because the
+/// recipient uses an older version of GnuPG.  A profile describes
+/// which packet versions and algorithms may be emitted.
+///
+/// Profiles are not a security mechanism.  Whether an algorithm is
+/// considered secure is decided by the [`Policy`].  Rather, they
+/// describe interoperability constraints.
+///
+/// Profiles can be selected using [`CertBuilder::set_profile`],
+/// [`Signer::profile`], and [`Encryptor2::profile`].  The streaming
+/// serializer checks every packet it emits against the profile.  To
+/// check arbitrary packets, e.g. before exporting a certificate, use
+/// [`Profile::check_packet`].
+///
+/// The default profile is [`Profile::RFC4880bis`], which does not
+/// restrict the output.
+///
+/// Note: This enum cannot be exhaustively matched to allow future
+/// extensions.
+///
+///   [`Policy`]: crate::policy::Policy
+///   [`CertBuilder::set_profile`]: crate::cert::CertBuilder::set_profile()
+///   [`Signer::profile`]: crate::serialize::stream::Signer::profile()
+///   [`Encryptor2::profile`]: crate::serialize::stream::Encryptor2::profile()
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> sequoia_openpgp::Result<()> {
+/// use sequoia_openpgp as openpgp;
+/// use openpgp::cert::prelude::*;
+/// use openpgp::types::Profile;
+///
+/// // RFC 4880 does not know about elliptic curve cryptography.
+/// assert!(CertBuilder::new()
+///     .set_cipher_suite(CipherSuite::Cv25519)
+///     .set_profile(Profile::RFC4880)
+///     .generate().is_err());
+///
+/// // But GnuPG 2.2 does.
+/// let (cert, _) = CertBuilder::new()
+///     .set_cipher_suite(CipherSuite::Cv25519)
+///     .set_profile(Profile::GnuPG22)
+///     .generate()?;
+///
+/// for p in cert.into_packets2() {
+///     Profile::GnuPG22.check_packet(&p)?;
+/// }
+/// # Ok(()) }
+/// ```
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// Only emits packets and algorithms defined in [RFC 4880].
+    ///
+    /// In particular, elliptic curve cryptography, Camellia, SHA-3,
+    /// and AEAD are not used.
+    ///
+    ///   [RFC 4880]: https://tools.ietf.org/html/rfc4880
+    RFC4880,
+
+    /// Only emits packets and algorithms understood by GnuPG 2.2.
+    ///
+    /// This is [RFC 4880], plus elliptic curve cryptography as
+    /// defined in [RFC 6637] and [RFC 4880bis] (including Ed25519,
+    /// Cv25519, and the Brainpool curves), plus Camellia as defined
+    /// in [RFC 5581].  SHA-3 and AEAD are not used.
+    ///
+    ///   [RFC 4880]: https://tools.ietf.org/html/rfc4880
+    ///   [RFC 6637]: https://tools.ietf.org/html/rfc6637
+    ///   [RFC 4880bis]: https://tools.ietf.org/html/draft-ietf-openpgp-rfc4880bis-09
+    ///   [RFC 5581]: https://tools.ietf.org/html/rfc5581
+    GnuPG22,
+
+    /// Emits everything Sequoia supports, including experimental
+    /// features from [RFC 4880bis] like AEAD.
+    ///
+    ///   [RFC 4880bis]: https://tools.ietf.org/html/draft-ietf-openpgp-rfc4880bis-09
+    RFC4880bis,
+}
+assert_send_and_sync!(Profile);
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::RFC4880bis
+    }
+}
+
+impl Profile {
+    /// Checks whether the public key algorithm may be emitted.
+    ///
+    /// For the elliptic curve algorithms, the curve is checked as
+    /// well, if it is given.  Signatures, for instance, do not
+    /// identify the curve.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::types::{Curve, Profile, PublicKeyAlgorithm};
+    ///
+    /// let p = Profile::GnuPG22;
+    /// assert!(p.check_public_key_algorithm(
+    ///     PublicKeyAlgorithm::EdDSA, Some(&Curve::Ed25519)).is_ok());
+    /// assert!(Profile::RFC4880.check_public_key_algorithm(
+    ///     PublicKeyAlgorithm::EdDSA, Some(&Curve::Ed25519)).is_err());
+    /// ```
+    pub fn check_public_key_algorithm(&self, algo: PublicKeyAlgorithm,
+                                      curve: Option<&Curve>)
+                                      -> Result<()>
+    {
+        #[allow(deprecated)]
+        use PublicKeyAlgorithm::*;
+
+        if *self == Profile::RFC4880bis {
+            return Ok(());
+        }
+
+        #[allow(deprecated)]
+        let ok = match algo {
+            RSAEncryptSign | RSAEncrypt | RSASign
+                | ElGamalEncrypt | ElGamalEncryptSign | DSA => true,
+            ECDH | ECDSA | EdDSA if *self == Profile::RFC4880 => false,
+            ECDH | ECDSA | EdDSA => {
+                let curve = match curve {
+                    Some(c) => c,
+                    None => return Ok(()),
+                };
+                let ok = match (algo, curve) {
+                    (EdDSA, Curve::Ed25519) => true,
+                    (ECDH, Curve::Cv25519) => true,
+                    (ECDH, _) | (ECDSA, _) => matches!(
+                        curve,
+                        Curve::NistP256 | Curve::NistP384 | Curve::NistP521
+                            | Curve::BrainpoolP256 | Curve::BrainpoolP512),
+                    _ => false,
+                };
+                if ! ok {
+                    return Err(
+                        Error::UnsupportedEllipticCurve(curve.clone()).into());
+                }
+                true
+            },
+            Private(_) | Unknown(_) => false,
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedPublicKeyAlgorithm(algo).into())
+        }
+    }
+
+    /// Checks whether the symmetric algorithm may be emitted.
+    pub fn check_symmetric_algorithm(&self, algo: SymmetricAlgorithm)
+                                     -> Result<()>
+    {
+        use SymmetricAlgorithm::*;
+
+        let ok = match (self, algo) {
+            (Profile::RFC4880bis, _) => true,
+            (_, IDEA | TripleDES | CAST5 | Blowfish
+             | AES128 | AES192 | AES256 | Twofish) => true,
+            (Profile::GnuPG22, Camellia128 | Camellia192 | Camellia256) =>
+                true,
+            _ => false,
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedSymmetricAlgorithm(algo).into())
+        }
+    }
+
+    /// Checks whether the hash algorithm may be emitted.
+    pub fn check_hash_algorithm(&self, algo: HashAlgorithm) -> Result<()> {
+        use HashAlgorithm::*;
+
+        let ok = match (self, algo) {
+            (Profile::RFC4880bis, _) => true,
+            (_, MD5 | SHA1 | RipeMD | SHA256 | SHA384 | SHA512 | SHA224) =>
+                true,
+            _ => false,
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedHashAlgorithm(algo).into())
+        }
+    }
+
+    /// Checks whether the AEAD algorithm may be emitted.
+    ///
+    /// AEAD is only available in the [`Profile::RFC4880bis`]
+    /// profile.
+    pub fn check_aead_algorithm(&self, algo: AEADAlgorithm) -> Result<()> {
+        match self {
+            Profile::RFC4880bis => Ok(()),
+            _ => Err(Error::UnsupportedAEADAlgorithm(algo).into()),
+        }
+    }
+
+    /// Checks whether the key may be emitted.
+    ///
+    /// This checks the key's version and its algorithm.
+    pub fn check_key<P, R>(&self, key: &Key<P, R>) -> Result<()>
+        where P: key::KeyParts,
+              R: key::KeyRole,
+    {
+        match key {
+            Key::V4(k) =>
+                self.check_public_key_algorithm(k.pk_algo(), k.mpis().curve()),
+        }
+    }
+
+    /// Checks whether the signature may be emitted.
+    ///
+    /// This checks the signature's public key algorithm, and its hash
+    /// algorithm.
+    pub fn check_signature(&self, sig: &Signature) -> Result<()> {
+        self.check_public_key_algorithm(sig.pk_algo(), None)?;
+        self.check_hash_algorithm(sig.hash_algo())
+    }
+
+    /// Checks whether the packet may be emitted.
+    ///
+    /// This checks the packet's version, and the algorithms used by
+    /// the packet.  Packets that are not affected by the profile,
+    /// like user IDs, are always accepted.
+    ///
+    /// Note: the content of container packets is not checked.
+    pub fn check_packet(&self, packet: &Packet) -> Result<()> {
+        match packet {
+            Packet::PublicKey(k) => self.check_key(k),
+            Packet::PublicSubkey(k) => self.check_key(k),
+            Packet::SecretKey(k) => self.check_key(k),
+            Packet::SecretSubkey(k) => self.check_key(k),
+            Packet::Signature(s) => self.check_signature(s),
+            Packet::OnePassSig(ops) => {
+                self.check_public_key_algorithm(ops.pk_algo(), None)?;
+                self.check_hash_algorithm(ops.hash_algo())
+            },
+            Packet::PKESK(p) =>
+                self.check_public_key_algorithm(p.pk_algo(), None),
+            Packet::SKESK(SKESK::V4(s)) =>
+                self.check_symmetric_algorithm(s.symmetric_algo()),
+            Packet::SKESK(SKESK::V5(s)) => {
+                self.check_symmetric_algorithm(s.symmetric_algo())?;
+                self.check_aead_algorithm(s.aead_algo())
+            },
+            Packet::AED(a) => {
+                self.check_symmetric_algorithm(a.symmetric_algo())?;
+                self.check_aead_algorithm(a.aead())
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cert::prelude::*;
+
+    #[test]
+    fn algorithms() {
+        use Profile::*;
+
+        for p in [RFC4880, GnuPG22, RFC4880bis] {
+            assert!(p.check_public_key_algorithm(
+                PublicKeyAlgorithm::RSAEncryptSign, None).is_ok());
+            assert!(p.check_symmetric_algorithm(
+                SymmetricAlgorithm::AES256).is_ok());
+            assert!(p.check_hash_algorithm(HashAlgorithm::SHA512).is_ok());
+        }
+
+        assert!(RFC4880.check_public_key_algorithm(
+            PublicKeyAlgorithm::ECDSA, Some(&Curve::NistP256)).is_err());
+        assert!(GnuPG22.check_public_key_algorithm(
+            PublicKeyAlgorithm::ECDSA, Some(&Curve::NistP256)).is_ok());
+        assert!(GnuPG22.check_public_key_algorithm(
+            PublicKeyAlgorithm::ECDSA, Some(&Curve::Ed25519)).is_err());
+        assert!(GnuPG22.check_public_key_algorithm(
+            PublicKeyAlgorithm::ECDH, None).is_ok());
+        assert!(RFC4880.check_public_key_algorithm(
+            PublicKeyAlgorithm::ECDH, None).is_err());
+        assert!(GnuPG22.check_public_key_algorithm(
+            PublicKeyAlgorithm::Private(100), None).is_err());
+        assert!(RFC4880bis.check_public_key_algorithm(
+            PublicKeyAlgorithm::Private(100), None).is_ok());
+
+        assert!(RFC4880.check_symmetric_algorithm(
+            SymmetricAlgorithm::Camellia256).is_err());
+        assert!(GnuPG22.check_symmetric_algorithm(
+            SymmetricAlgorithm::Camellia256).is_ok());
+
+        assert!(GnuPG22.check_hash_algorithm(
+            HashAlgorithm::SHA3_256).is_err());
+        assert!(RFC4880bis.check_hash_algorithm(
+            HashAlgorithm::SHA3_256).is_ok());
+
+        assert!(RFC4880.check_aead_algorithm(AEADAlgorithm::EAX).is_err());
+        assert!(GnuPG22.check_aead_algorithm(AEADAlgorithm::EAX).is_err());
+        assert!(RFC4880bis.check_aead_algorithm(AEADAlgorithm::EAX).is_ok());
+    }
+
+    #[test]
+    fn packets() -> Result<()> {
+        let (cert, _) = CertBuilder::general_purpose(
+            CipherSuite::Cv25519, Some("alice@example.org"))
+            .generate()?;
+        let packets = cert.into_packets2().collect::<Vec<_>>();
+
+        assert!(packets.iter()
+                .all(|p| Profile::RFC4880bis.check_packet(p).is_ok()));
+        assert!(packets.iter()
+                .all(|p| Profile::GnuPG22.check_packet(p).is_ok()));
+        assert!(packets.iter()
+                .any(|p| Profile::RFC4880.check_packet(p).is_err()));
+        // User IDs are not affected.
+        assert!(packets.iter()
+                .filter(|p| p.tag() == crate::packet::Tag::UserID)
+                .all(|p| Profile::RFC4880.check_packet(p).is_ok()));
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::{
@@ -149,7 +149,7 @@ const REVOCATION_KEY_FLAG_SENSITIVE: u8 = 0x40;
 const REVOCATION_KEY_MASK_UNKNOWN: u8 = ! (REVOCATION_KEY_FLAG_MUST_BE_SET
                                            | REVOCATION_KEY_FLAG_SENSITIVE);
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for RevocationKey {
     fn arbitrary(g: &mut Gen) -> Self {
         RevocationKey {
@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::types::Bitfield;
@@ -282,7 +282,7 @@ impl KeyServerPreferences {
 /// by the key holder or an administrator of the key server.
 const KEYSERVER_PREFERENCE_NO_MODIFY: usize = 7;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for KeyServerPreferences {
     fn arbitrary(g: &mut Gen) -> Self {
         Self::new(Vec::arbitrary(g))
@@ -4,7 +4,7 @@ use std::fmt;
 use std::time::{SystemTime, Duration as SystemDuration, UNIX_EPOCH};
 use std::u32;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 use quickcheck::{Arbitrary, Gen};
 
 use crate::{
@@ -128,6 +128,47 @@ impl Timestamp {
             .expect("representable for the next hundred years")
     }
 
+    /// Rounds the given time to the resolution of OpenPGP
+    /// timestamps.
+    ///
+    /// OpenPGP timestamps have a resolution of one second, so `t` is
+    /// rounded down to the whole second.  This is what happens
+    /// implicitly when `t` is used as a key's or a signature's
+    /// creation time.  Comparing times that have been normalized
+    /// avoids subtle bugs due to sub-second differences.
+    ///
+    /// Returns an error if `t` is not representable, i.e. if it
+    /// predates the [UNIX epoch], or if it is after 7th February
+    /// of 2106.
+    ///
+    ///   [UNIX epoch]: https://en.wikipedia.org/wiki/Unix_time
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::types::Timestamp;
+    ///
+    /// let t = UNIX_EPOCH + Duration::new(1585753307, 999_999_999);
+    /// assert_eq!(Timestamp::normalize(t)?,
+    ///            UNIX_EPOCH + Duration::new(1585753307, 0));
+    ///
+    /// assert!(Timestamp::normalize(UNIX_EPOCH - Duration::new(1, 0)).is_err());
+    /// # Ok(()) }
+    /// ```
+    pub fn normalize<T>(t: T) -> Result<SystemTime>
+        where T: Into<SystemTime>
+    {
+        Timestamp::try_from(t.into()).map(Into::into)
+    }
+
+    /// Returns the number of seconds since the UNIX epoch.
+    pub fn as_secs(self) -> u64 {
+        self.0 as u64
+    }
+
     /// Adds a duration to this timestamp.
     ///
     /// Returns `None` if the resulting timestamp is not
@@ -250,7 +291,7 @@ impl Timestamp {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Timestamp {
     fn arbitrary(g: &mut Gen) -> Self {
         Timestamp(u32::arbitrary(g))
@@ -419,6 +460,35 @@ impl Duration {
         self.0 as u64
     }
 
+    /// Rounds the given duration to the resolution of OpenPGP
+    /// durations.
+    ///
+    /// OpenPGP durations have a resolution of one second, so `d` is
+    /// rounded down to the whole second.  This is what happens
+    /// implicitly when `d` is used as a validity period.
+    ///
+    /// Returns an error if `d` is not representable, i.e. if it
+    /// exceeds `u32::MAX` seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> sequoia_openpgp::Result<()> {
+    /// use std::time;
+    /// use sequoia_openpgp as openpgp;
+    /// use openpgp::types::Duration;
+    ///
+    /// assert_eq!(Duration::normalize(time::Duration::new(60, 500_000_000))?,
+    ///            time::Duration::new(60, 0));
+    /// assert!(Duration::normalize(time::Duration::new(1 << 32, 0)).is_err());
+    /// # Ok(()) }
+    /// ```
+    pub fn normalize<D>(d: D) -> Result<SystemDuration>
+        where D: Into<SystemDuration>
+    {
+        Duration::try_from(d.into()).map(Into::into)
+    }
+
     /// Rounds up to the given level of precision.
     ///
     /// If [`Timestamp::round_down`] is used to round the creation
@@ -606,18 +676,53 @@ impl Timestamp {
     pub(crate) const Y2106M2 : Timestamp = Timestamp(4294425600);
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "quickcheck"))]
 impl Arbitrary for Duration {
     fn arbitrary(g: &mut Gen) -> Self {
         Duration(u32::arbitrary(g))
     }
 }
 
-/// Normalizes the given SystemTime to the resolution OpenPGP
-/// supports.
-pub(crate) fn normalize_systemtime(t: SystemTime) -> SystemTime {
-    UNIX_EPOCH + SystemDuration::new(
-        t.duration_since(UNIX_EPOCH).unwrap().as_secs(), 0)
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> TryFrom<chrono::DateTime<Tz>> for Timestamp {
+    type Error = anyhow::Error;
+
+    /// Converts the time, rounding down to the whole second.
+    fn try_from(t: chrono::DateTime<Tz>) -> Result<Self> {
+        let s = t.timestamp();
+        u32::try_from(s).map(Timestamp).map_err(
+            |_| Error::InvalidArgument(
+                format!("Time exceeds u32 epoch: {}s", s)).into())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Timestamp> for chrono::DateTime<chrono::Utc> {
+    fn from(t: Timestamp) -> Self {
+        use chrono::TimeZone;
+        chrono::Utc.timestamp_opt(t.0 as i64, 0).single()
+            .expect("every u32 timestamp is representable")
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::Duration> for Duration {
+    type Error = anyhow::Error;
+
+    /// Converts the duration, rounding down to the whole second.
+    fn try_from(d: chrono::Duration) -> Result<Self> {
+        let s = d.num_seconds();
+        u32::try_from(s).map(Duration).map_err(
+            |_| Error::InvalidArgument(
+                format!("Duration exceeds u32: {}s", s)).into())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Duration> for chrono::Duration {
+    fn from(d: Duration) -> Self {
+        chrono::Duration::seconds(d.0 as i64)
+    }
 }
 
 #[cfg(test)]
@@ -726,4 +831,62 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn normalize() -> Result<()> {
+        let t = UNIX_EPOCH + SystemDuration::new(1585753307, 123_456_789);
+        let n = Timestamp::normalize(t)?;
+        assert_eq!(n, UNIX_EPOCH + SystemDuration::new(1585753307, 0));
+        assert_eq!(Timestamp::normalize(n)?, n);
+        assert_eq!(Timestamp::try_from(t)?.as_secs(), 1585753307);
+
+        assert!(Timestamp::normalize(UNIX_EPOCH - SystemDuration::new(0, 1))
+                .is_err());
+        assert!(Timestamp::normalize(
+            UNIX_EPOCH + SystemDuration::new(u32::MAX as u64 + 1, 0))
+                .is_err());
+
+        let d = SystemDuration::new(10, 999_999_999);
+        assert_eq!(Duration::normalize(d)?, SystemDuration::new(10, 0));
+        assert!(Duration::normalize(
+            SystemDuration::new(u32::MAX as u64 + 1, 0)).is_err());
+        Ok(())
+    }
+
+    /// Out-of-range expiration times are rejected, not rounded.
+    #[test]
+    fn expiration_time_out_of_range() -> Result<()> {
+        use crate::packet::key::{Key4, PrimaryRole, SecretParts};
+        use crate::packet::signature::SignatureBuilder;
+        use crate::types::{Curve, SignatureType};
+
+        let key: crate::packet::Key<SecretParts, PrimaryRole> =
+            Key4::generate_ecc(true, Curve::Ed25519)?.into();
+        let b = SignatureBuilder::new(SignatureType::DirectKey);
+        assert!(b.clone().set_key_expiration_time(
+            &key, UNIX_EPOCH - SystemDuration::new(1, 0)).is_err());
+        assert!(b.set_key_expiration_time(
+            &key, UNIX_EPOCH + SystemDuration::new(1 << 33, 0)).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono() -> Result<()> {
+        use chrono::{DateTime, TimeZone, Utc};
+
+        let t = Utc.timestamp_opt(1585753307, 999_999_999).single().unwrap();
+        let ts = Timestamp::try_from(t)?;
+        assert_eq!(u32::from(ts), 1585753307);
+        assert_eq!(DateTime::<Utc>::from(ts),
+                   Utc.timestamp_opt(1585753307, 0).single().unwrap());
+        assert!(Timestamp::try_from(
+            Utc.timestamp_opt(-1, 0).single().unwrap()).is_err());
+
+        let d = Duration::try_from(chrono::Duration::milliseconds(61_500))?;
+        assert_eq!(d, Duration::seconds(61));
+        assert_eq!(chrono::Duration::from(d), chrono::Duration::seconds(61));
+        assert!(Duration::try_from(chrono::Duration::seconds(-1)).is_err());
+        Ok(())
+    }
 }
@@ -1,5 +1,6 @@
 //! Utility functions that don't fit anywhere else.
 use std::convert::TryFrom;
+use std::fmt;
 
 pub fn read_be_u64(b: &[u8]) -> u64 {
     let array = <[u8; 8]>::try_from(b).unwrap();
@@ -10,6 +11,61 @@ pub fn write_be_u64(b: &mut [u8], n: u64) {
     b.copy_from_slice(&n.to_be_bytes());
 }
 
+/// Reports the number of bytes processed to a callback.
+///
+/// The callback is invoked with the total number of bytes processed
+/// so far whenever at least `interval` bytes have been processed
+/// since the last invocation.
+pub(crate) struct Progress<'a> {
+    interval: u64,
+    processed: u64,
+    reported: u64,
+    callback: Box<dyn FnMut(u64) + Send + Sync + 'a>,
+}
+assert_send_and_sync!(Progress<'_>);
+
+impl fmt::Debug for Progress<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("interval", &self.interval)
+            .field("processed", &self.processed)
+            .finish()
+    }
+}
+
+impl<'a> Progress<'a> {
+    /// Returns a new progress reporter.
+    pub fn new<F>(interval: u64, callback: F) -> Self
+        where F: FnMut(u64) + Send + Sync + 'a
+    {
+        Progress {
+            interval,
+            processed: 0,
+            reported: 0,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Records that `amount` bytes have been processed.
+    pub fn update(&mut self, amount: usize) {
+        if amount == 0 {
+            return;
+        }
+
+        self.processed += amount as u64;
+        if self.processed - self.reported >= self.interval {
+            self.reported = self.processed;
+            (self.callback)(self.processed);
+        }
+    }
+
+    /// Starts counting from zero.
+    pub fn reset(&mut self) {
+        self.processed = 0;
+        self.reported = 0;
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -21,4 +77,19 @@ mod test {
             n == read_be_u64(&b)
         }
     }
+
+    #[test]
+    fn progress() {
+        let mut calls = Vec::new();
+        let mut p = Progress::new(10, |n| calls.push(n));
+        p.update(3);
+        p.update(0);
+        p.update(7);
+        p.update(9);
+        p.update(25);
+        p.reset();
+        p.update(11);
+        drop(p);
+        assert_eq!(calls, vec![10, 44, 11]);
+    }
 }